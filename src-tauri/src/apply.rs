@@ -0,0 +1,217 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::llm;
+use crate::parts::MessagePart;
+use crate::{approvals, checkpoints, claims, AppState, GuiSettings};
+
+#[derive(Clone, Serialize)]
+pub struct AppliedBlock {
+    pub path: String,
+    pub language: Option<String>,
+    pub applied: bool,
+    pub summary: String,
+}
+
+/// User turns in `messages`: the number of the turn whose reply follows them.
+fn user_turns(messages: &[crate::Message]) -> usize {
+    messages.iter().filter(|m| m.role == "user").count()
+}
+
+/// Turn the file-annotated code blocks of an assistant reply into WriteFile
+/// calls, for models or modes that answer in markdown instead of tool calls.
+/// Each block goes through the same approval and claim flow as a model's
+/// WriteFile, and emits the usual `tool_status` events.
+#[tauri::command]
+pub async fn apply_code_blocks(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    message_index: usize,
+    work_dir: Option<String>,
+    settings: Option<GuiSettings>,
+) -> Result<Vec<AppliedBlock>, String> {
+    let settings = settings.unwrap_or_default();
+
+    let (message, turn_number, session_work_dir) = {
+        let mut manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        match manager.find_session(&session_id) {
+            Some(mut session) => {
+                if message_index >= session.messages.len() {
+                    return Err("Message index out of range".to_string());
+                }
+                let turn_number = user_turns(&session.messages[..message_index]);
+                (session.messages.swap_remove(message_index), turn_number, session.work_dir)
+            }
+            None => {
+                let wd = work_dir.clone().ok_or_else(|| "Session not found".to_string())?;
+                let mut messages = manager.load_messages(&wd, &session_id)?;
+                if message_index >= messages.len() {
+                    return Err("Message index out of range".to_string());
+                }
+                let turn_number = user_turns(&messages[..message_index]);
+                (messages.swap_remove(message_index), turn_number, wd)
+            }
+        }
+    };
+
+    if message.role != "assistant" {
+        return Err("Only assistant messages contain code blocks to apply".to_string());
+    }
+
+    let blocks: Vec<(String, Option<String>, String)> = message
+        .parts
+        .into_iter()
+        .filter_map(|part| match part {
+            MessagePart::Code {
+                language,
+                file: Some(file),
+                code,
+            } => Some((file, language, code)),
+            _ => None,
+        })
+        .collect();
+    if blocks.is_empty() {
+        return Err("No code blocks with file paths in this message".to_string());
+    }
+
+    let work_dir = work_dir
+        .or(settings.work_dir)
+        .filter(|wd| !wd.is_empty())
+        .unwrap_or(session_work_dir);
+    let config_path = settings
+        .config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::app_paths().config));
+    let auto_approve = settings.yolo.unwrap_or(false);
+    let approval_hook = settings.approval_hook.clone().unwrap_or_default();
+    let hooks = settings.hooks.clone().unwrap_or_default();
+    // Files are saved before the blocks overwrite them, so they can be rolled back
+    let turn = checkpoints::Turn::new(turn_number);
+    let tool_context = llm::ToolContext {
+        work_dir: &work_dir,
+        config_path: config_path.as_deref(),
+        dry_run: false,
+        env: &settings.env,
+        shell: settings.shell_interpreter,
+        policy: None,
+        disabled_tools: &settings.disabled_tools,
+        turn: Some(&turn),
+        hooks: Some(&hooks),
+    };
+
+    let (stream_id, mut cancel_rx) = crate::register_stream(&state, &session_id)?;
+    let result = async {
+        let _claims = claims::SessionClaims::new(&state.file_claims, &session_id);
+
+        let mut results = Vec::new();
+        for (path, language, code) in blocks {
+            let tool_call_id = Uuid::new_v4().to_string();
+            let args = serde_json::json!({ "path": path, "content": code });
+
+            let approved = if let Some(reason) =
+                crate::paths::refuse_outside_write(&session_id, &work_dir, "WriteFile", &args)
+            {
+                Err(reason)
+            } else if crate::paths::write_escapes(&session_id, &work_dir, "WriteFile", &args)
+                || (!auto_approve
+                    && llm::approval_required("WriteFile", &args, &work_dir, &settings.tool_approvals))
+            {
+                let request = llm::ApprovalRequest {
+                    session_id: &session_id,
+                    tool_call_id: &tool_call_id,
+                    name: "WriteFile",
+                    args: &args,
+                    work_dir: &work_dir,
+                    config_path: None,
+                };
+                match approvals::run_hook(&approval_hook, &session_id, &work_dir, "WriteFile", &args)
+                    .await
+                {
+                    approvals::HookDecision::Allow => Ok(()),
+                    approvals::HookDecision::Deny(reason) => Err(reason),
+                    approvals::HookDecision::Ask => {
+                        llm::request_approval(&window, &state, &request, &mut cancel_rx)
+                            .await?
+                            .then_some(())
+                            .ok_or_else(|| "Rejected by user".to_string())
+                    }
+                }
+            } else {
+                Ok(())
+            };
+
+            if let Err(summary) = approved {
+                results.push(AppliedBlock {
+                    path,
+                    language,
+                    applied: false,
+                    summary,
+                });
+                continue;
+            }
+
+            let targets = claims::claim_targets("WriteFile", &args, &work_dir);
+            claims::acquire(
+                &window,
+                &state.file_claims,
+                &session_id,
+                &tool_call_id,
+                &targets,
+                &mut cancel_rx,
+            )
+            .await
+            .map_err(|error| match error {
+                claims::ClaimError::Cancelled => "Cancelled".to_string(),
+                claims::ClaimError::TimedOut(reason) => reason,
+            })?;
+
+            let label = llm::tool_label("WriteFile", &args);
+            llm::emit_tool_status(
+                &window,
+                &session_id,
+                &tool_call_id,
+                "start",
+                "WriteFile",
+                &label,
+                None,
+                None,
+            );
+            let output = llm::execute_tool(
+                &window,
+                &state,
+                &session_id,
+                &tool_call_id,
+                "WriteFile",
+                &args,
+                &tool_context,
+            )
+            .await;
+            llm::emit_tool_status(
+                &window,
+                &session_id,
+                &tool_call_id,
+                "end",
+                "WriteFile",
+                &label,
+                Some(output.ok),
+                Some(output.summary.clone()),
+            );
+
+            results.push(AppliedBlock {
+                path,
+                language,
+                applied: output.ok,
+                summary: output.summary,
+            });
+        }
+
+        Ok(results)
+    }
+    .await;
+    crate::unregister_stream(&state, stream_id);
+    result
+}
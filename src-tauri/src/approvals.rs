@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+/// Shell syntax that could run more than the allowed prefix
+const SHELL_CHAINING: &[&str] = &[";", "&", "|", "`", "$(", ">", "<", "\n", "\r"];
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 10;
+
+/// Standing answer to an approval prompt, scoped to one work dir.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApprovalRule {
+    #[serde(default)]
+    pub id: String,
+    pub work_dir: String,
+    /// "command_prefix" (Shell), "path_prefix" (file edits, every path) or
+    /// "tool" (any call to `tool`)
+    pub kind: String,
+    pub tool: String,
+    #[serde(default)]
+    pub pattern: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ApprovalStore {
+    #[serde(default)]
+    rules: Vec<ApprovalRule>,
+}
+
+fn approvals_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_approvals.json")
+}
+
+fn load_store() -> Result<ApprovalStore, String> {
+    let path = approvals_path();
+    if !path.exists() {
+        return Ok(ApprovalStore::default());
+    }
+    let raw = crate::read_text(&path)?;
+    serde_json::from_str(&raw).map_err(|error| format!("Invalid approvals file: {error}"))
+}
+
+fn save_store(store: &ApprovalStore) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(store).map_err(|error| error.to_string())?;
+    crate::write_text(&approvals_path(), &raw)
+}
+
+/// `command` is exactly `prefix` or starts with it followed by arguments, and
+/// does not chain anything else after it.
+fn command_matches(prefix: &str, command: &str) -> bool {
+    let (prefix, command) = (prefix.trim(), command.trim());
+    if prefix.is_empty() || SHELL_CHAINING.iter().any(|token| command.contains(token)) {
+        return false;
+    }
+    command == prefix
+        || command
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+fn path_matches(work_dir: &str, prefix: &str, path: &str) -> bool {
+    let root = Path::new(work_dir);
+    let allowed = crate::claims::normalize(&root.join(prefix.trim()));
+    let target = Path::new(path);
+    let target = crate::claims::normalize(&if target.is_relative() {
+        root.join(target)
+    } else {
+        target.to_path_buf()
+    });
+    // `normalize` leaves `..` in paths that don't exist yet, and `starts_with`
+    // would let those climb out of the allowed folder
+    let escapes = |path: &Path| path.components().any(|c| c == Component::ParentDir);
+    !escapes(&allowed)
+        && !escapes(&target)
+        && allowed.starts_with(crate::claims::normalize(root))
+        && target.starts_with(&allowed)
+}
+
+fn rule_matches(rule: &ApprovalRule, work_dir: &str, name: &str, args: &serde_json::Value) -> bool {
+    if rule.tool != name || !crate::same_path(&rule.work_dir, work_dir) {
+        return false;
+    }
+    let arg = |key: &str| args.get(key).and_then(|v| v.as_str()).unwrap_or_default();
+    match rule.kind.as_str() {
+        "command_prefix" => command_matches(&rule.pattern, arg("command")),
+        "path_prefix" => {
+            let paths = crate::tools::edit_paths(name, args);
+            !paths.is_empty() && paths.iter().all(|path| path_matches(work_dir, &rule.pattern, path))
+        }
+        "tool" => true,
+        _ => false,
+    }
+}
+
+/// Whether a saved rule already approves this call in `work_dir`.
+pub fn rule_allows(work_dir: &str, name: &str, args: &serde_json::Value) -> bool {
+    load_store()
+        .map(|store| {
+            store
+                .rules
+                .iter()
+                .any(|rule| rule_matches(rule, work_dir, name, args))
+        })
+        .unwrap_or(false)
+}
+
+/// Command consulted before each approval prompt. It gets the request as
+/// JSON on stdin (`tool`, `args`, `work_dir`, `session_id`) and prints
+/// `allow`, `deny` or `ask`, or `{"decision": ..., "reason": ...}`. A failing,
+/// slow or unclear hook falls back to asking.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ApprovalHook {
+    pub command: String,
+    pub timeout_secs: Option<u64>,
+}
+
+pub enum HookDecision {
+    Allow,
+    /// Reason passed on to the model
+    Deny(String),
+    Ask,
+}
+
+fn parse_decision(stdout: &str) -> HookDecision {
+    let stdout = stdout.trim();
+    let (decision, reason) = match serde_json::from_str::<serde_json::Value>(stdout) {
+        Ok(value) if value.is_object() => (
+            value["decision"].as_str().unwrap_or("").to_lowercase(),
+            value["reason"].as_str().map(str::to_string),
+        ),
+        _ => (stdout.lines().next().unwrap_or("").trim().to_lowercase(), None),
+    };
+    match decision.as_str() {
+        "allow" => HookDecision::Allow,
+        "deny" => HookDecision::Deny(
+            reason.unwrap_or_else(|| "Denied by the approval hook.".to_string()),
+        ),
+        _ => HookDecision::Ask,
+    }
+}
+
+/// Ask `hook` about a call that would otherwise prompt the user.
+pub async fn run_hook(
+    hook: &ApprovalHook,
+    session_id: &str,
+    work_dir: &str,
+    name: &str,
+    args: &serde_json::Value,
+) -> HookDecision {
+    if hook.command.trim().is_empty() {
+        return HookDecision::Ask;
+    }
+    let input = serde_json::json!({
+        "tool": name,
+        "args": args,
+        "work_dir": work_dir,
+        "session_id": session_id,
+    })
+    .to_string();
+    let timeout =
+        std::time::Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS));
+    match crate::hooks::run_command(&hook.command, work_dir, &input, timeout).await {
+        Ok(output) if output.status.success() => {
+            parse_decision(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => HookDecision::Ask,
+    }
+}
+
+#[tauri::command]
+pub fn approval_rules_list(work_dir: Option<String>) -> Result<Vec<ApprovalRule>, String> {
+    let rules = load_store()?.rules;
+    Ok(match work_dir {
+        Some(work_dir) => rules
+            .into_iter()
+            .filter(|rule| crate::same_path(&rule.work_dir, &work_dir))
+            .collect(),
+        None => rules,
+    })
+}
+
+#[tauri::command]
+pub fn approval_rule_add(mut rule: ApprovalRule) -> Result<ApprovalRule, String> {
+    rule.pattern = rule.pattern.trim().to_string();
+    match rule.kind.as_str() {
+        "command_prefix" | "path_prefix" if rule.pattern.is_empty() => {
+            return Err("Rule pattern cannot be empty".to_string());
+        }
+        "command_prefix" | "path_prefix" | "tool" => {}
+        other => return Err(format!("Unknown rule kind: {}", other)),
+    }
+    if rule.work_dir.trim().is_empty() {
+        return Err("Rule needs a work dir".to_string());
+    }
+    rule.id = Uuid::new_v4().to_string();
+    rule.created_at = chrono::Utc::now().timestamp();
+
+    let mut store = load_store()?;
+    store.rules.push(rule.clone());
+    save_store(&store)?;
+    Ok(rule)
+}
+
+#[tauri::command]
+pub fn approval_rule_delete(id: String) -> Result<(), String> {
+    let mut store = load_store()?;
+    let before = store.rules.len();
+    store.rules.retain(|rule| rule.id != id);
+    if store.rules.len() == before {
+        return Err("Rule not found".to_string());
+    }
+    save_store(&store)
+}
@@ -0,0 +1,266 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri_plugin_dialog::DialogExt;
+use uuid::Uuid;
+
+use crate::checkpoints::{self, FileVersion};
+use crate::parts::MessagePart;
+use crate::session::Session;
+use crate::AppState;
+
+const ARCHIVE_FORMAT: &str = "kimichat";
+/// Bump when a change would make older builds misread an archive
+const ARCHIVE_VERSION: u32 = 1;
+const ARCHIVE_EXTENSION: &str = "kimichat";
+/// Per-session files copied along with the transcript. The command policy is
+/// left out so an archive can't loosen it.
+const SIDECARS: &[&str] = &["outline.json", "worklog.json"];
+
+/// A whole GUI session in one JSON file: the transcript, the images attached
+/// to it, and the file checkpoints its turns took, so it can be opened on
+/// another machine or attached to a bug report.
+#[derive(Serialize, Deserialize)]
+pub struct SessionArchive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub session: Session,
+    /// Attachment file name to base64 content
+    #[serde(default)]
+    pub attachments: BTreeMap<String, String>,
+    #[serde(default)]
+    pub checkpoints: Vec<ArchivedCheckpoint>,
+    /// Sidecar suffix, such as `outline.json`, to its content
+    #[serde(default)]
+    pub sidecars: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedCheckpoint {
+    #[serde(flatten)]
+    pub version: FileVersion,
+    /// Base64 content; None when the turn created the file
+    pub data: Option<String>,
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| format!("Invalid archive data: {}", e))
+}
+
+fn load_session(state: &AppState, session_id: &str, work_dir: Option<String>) -> Result<Session, String> {
+    let found = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .find_session(session_id);
+    if let Some(session) = found {
+        return Ok(session);
+    }
+    // A CLI session becomes a GUI session when imported
+    let (title, work_dir, messages) = crate::export::load_transcript(state, session_id, work_dir)?;
+    let now = chrono::Utc::now().timestamp();
+    Ok(Session {
+        id: session_id.to_string(),
+        title,
+        work_dir,
+        created_at: messages.first().map(|m| m.timestamp).unwrap_or(now),
+        updated_at: messages.last().map(|m| m.timestamp).unwrap_or(now),
+        messages,
+    })
+}
+
+pub fn build(state: &AppState, session_id: &str, work_dir: Option<String>) -> Result<SessionArchive, String> {
+    let session = load_session(state, session_id, work_dir)?;
+    if session.messages.is_empty() {
+        return Err("Session has no messages to export".to_string());
+    }
+
+    let mut attachments = BTreeMap::new();
+    for part in session.messages.iter().flat_map(|m| &m.parts) {
+        if let MessagePart::Image { src, .. } = part {
+            if let Some((name, bytes)) = crate::attachments::stored(src) {
+                attachments.entry(name).or_insert_with(|| encode(&bytes));
+            }
+        }
+    }
+
+    let checkpoints = checkpoints::session_versions(session_id)
+        .into_iter()
+        .map(|version| {
+            let data = version
+                .blob
+                .as_deref()
+                .filter(|_| version.existed)
+                .and_then(checkpoints::read_blob)
+                .map(|bytes| encode(&bytes));
+            ArchivedCheckpoint { version, data }
+        })
+        .collect();
+
+    let sidecars = {
+        let manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        SIDECARS
+            .iter()
+            .filter_map(|suffix| {
+                let content = std::fs::read_to_string(manager.sidecar_path(session_id, suffix)).ok()?;
+                Some((suffix.to_string(), content))
+            })
+            .collect()
+    };
+
+    Ok(SessionArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().timestamp(),
+        session,
+        attachments,
+        checkpoints,
+        sidecars,
+    })
+}
+
+/// Move `path` from under `from` to under `to`; paths elsewhere are kept.
+fn rebase(path: &str, from: &str, to: &str) -> String {
+    match Path::new(path).strip_prefix(from) {
+        Ok(rest) if from != to => Path::new(to).join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Store the archived session under a new id, returning it. The session moves
+/// to `work_dir` when given, taking its checkpoint paths along. Attachments and
+/// checkpoint blobs must match the hash they are stored under.
+pub fn restore(state: &AppState, archive: SessionArchive, work_dir: Option<String>) -> Result<String, String> {
+    if archive.format != ARCHIVE_FORMAT {
+        return Err("Not a Kimi chat archive".to_string());
+    }
+    if archive.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Archive version {} was made by a newer version of the app",
+            archive.version
+        ));
+    }
+
+    let mut session = archive.session;
+    let old_work_dir = session.work_dir.clone();
+    if let Some(work_dir) = work_dir.filter(|dir| !dir.is_empty()) {
+        session.work_dir = work_dir;
+    }
+
+    let mut manager = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+    // Never trust the archived id; it could name a session already here
+    session.id = Uuid::new_v4().to_string();
+
+    let mut restored = BTreeMap::new();
+    for (name, data) in &archive.attachments {
+        restored.insert(name.as_str(), crate::attachments::restore(name, &decode(data)?)?);
+    }
+    for part in session.messages.iter_mut().flat_map(|m| m.parts.iter_mut()) {
+        if let MessagePart::Image { src, .. } = part {
+            let name = Path::new(src.as_str())
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+            if let Some(local) = name.and_then(|name| restored.get(name.as_str())) {
+                *src = local.clone();
+            }
+        }
+    }
+
+    for checkpoint in archive.checkpoints {
+        let mut version = checkpoint.version;
+        version.session_id = session.id.clone();
+        version.path = rebase(&version.path, &old_work_dir, &session.work_dir);
+        let bytes = checkpoint.data.as_deref().map(decode).transpose()?;
+        checkpoints::restore(&version, bytes.as_deref())?;
+    }
+
+    for (suffix, content) in &archive.sidecars {
+        if SIDECARS.contains(&suffix.as_str()) {
+            crate::write_text(&manager.sidecar_path(&session.id, suffix), content)?;
+        }
+    }
+
+    let id = session.id.clone();
+    manager.import_session(session)?;
+    Ok(id)
+}
+
+/// Export a complete session as a `.kimichat` archive to `path`, or to a path
+/// picked in a save dialog. Returns the written path, or None if the dialog
+/// was cancelled.
+#[tauri::command]
+pub async fn session_export_archive(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    work_dir: Option<String>,
+    path: Option<String>,
+) -> Result<Option<String>, String> {
+    let archive = build(&state, &session_id, work_dir)?;
+    let path = match path.filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let Some(picked) = app
+                .dialog()
+                .file()
+                .set_file_name(format!(
+                    "{}.{}",
+                    crate::export::file_name(&archive.session.title),
+                    ARCHIVE_EXTENSION
+                ))
+                .add_filter("Kimi chat archive", &[ARCHIVE_EXTENSION])
+                .blocking_save_file()
+            else {
+                return Ok(None);
+            };
+            picked.into_path().map_err(|e| e.to_string())?
+        }
+    };
+    let contents = serde_json::to_string(&archive).map_err(|e| e.to_string())?;
+    crate::write_text(&path, &contents)?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// Import a `.kimichat` archive from `path`, or from a file picked in a dialog.
+/// Returns the id of the imported session, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn session_import_archive(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: Option<String>,
+    work_dir: Option<String>,
+) -> Result<Option<String>, String> {
+    let path = match path.filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let Some(picked) = app
+                .dialog()
+                .file()
+                .add_filter("Kimi chat archive", &[ARCHIVE_EXTENSION])
+                .blocking_pick_file()
+            else {
+                return Ok(None);
+            };
+            picked.into_path().map_err(|e| e.to_string())?
+        }
+    };
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let archive: SessionArchive =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid chat archive: {}", e))?;
+    restore(&state, archive, work_dir).map(Some)
+}
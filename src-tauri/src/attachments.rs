@@ -0,0 +1,211 @@
+use base64::Engine;
+use image::{GenericImageView, ImageFormat};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::parts::MessagePart;
+
+/// Largest file accepted before decoding
+const MAX_INPUT_BYTES: usize = 20 * 1024 * 1024;
+/// Longest side sent to the model; larger images are downscaled
+const MAX_DIMENSION: u32 = 2048;
+/// Images above this are re-encoded even when their size is fine
+const MAX_UPLOAD_BYTES: usize = 4 * 1024 * 1024;
+const MAX_ATTACHMENTS: usize = 8;
+const JPEG_QUALITY: u8 = 85;
+
+/// Image sent with a chat message, as a file path or base64 data (a bare
+/// string or a `data:` URL).
+#[derive(Deserialize)]
+pub struct Attachment {
+    pub path: Option<String>,
+    pub data: Option<String>,
+    pub name: Option<String>,
+}
+
+fn store_dir() -> PathBuf {
+    crate::kimi_share_dir().join("gui_attachments")
+}
+
+fn mime_type(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Gif => "image/gif",
+        ImageFormat::WebP => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+fn read_bytes(attachment: &Attachment) -> Result<Vec<u8>, String> {
+    if let Some(path) = attachment.path.as_deref().filter(|p| !p.is_empty()) {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?
+            .len();
+        if size as usize > MAX_INPUT_BYTES {
+            return Err(format!("{} is larger than 20 MB", path));
+        }
+        return std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e));
+    }
+    let data = attachment
+        .data
+        .as_deref()
+        .ok_or_else(|| "Attachment has neither a path nor data".to_string())?;
+    let encoded = data.split_once(";base64,").map_or(data, |(_, rest)| rest);
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid base64 image data: {}", e))?;
+    if bytes.len() > MAX_INPUT_BYTES {
+        return Err("Attached image is larger than 20 MB".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Check the format, downscale if needed and return (bytes, format).
+fn normalize(bytes: Vec<u8>) -> Result<(Vec<u8>, ImageFormat), String> {
+    let format = image::guess_format(&bytes).map_err(|_| "Unrecognized image format".to_string())?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err(format!(
+            "Unsupported image format {:?}; use PNG, JPEG, GIF or WebP",
+            format
+        ));
+    }
+    let image = image::load_from_memory_with_format(&bytes, format)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let (width, height) = image.dimensions();
+    if width.max(height) <= MAX_DIMENSION && bytes.len() <= MAX_UPLOAD_BYTES {
+        return Ok((bytes, format));
+    }
+
+    let image = if width.max(height) > MAX_DIMENSION {
+        image.resize(MAX_DIMENSION, MAX_DIMENSION, image::imageops::FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let mut out = Cursor::new(Vec::new());
+    // Keep transparency; everything else becomes a JPEG
+    let format = if image.color().has_alpha() {
+        image
+            .write_to(&mut out, ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode image: {}", e))?;
+        ImageFormat::Png
+    } else {
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| format!("Failed to encode image: {}", e))?;
+        ImageFormat::Jpeg
+    };
+    Ok((out.into_inner(), format))
+}
+
+/// Validate, downscale and store the attachments of a message, returning the
+/// image parts to save with it.
+pub fn prepare(attachments: &[Attachment]) -> Result<Vec<MessagePart>, String> {
+    if attachments.len() > MAX_ATTACHMENTS {
+        return Err(format!("At most {} images can be attached to a message", MAX_ATTACHMENTS));
+    }
+    let mut parts = Vec::new();
+    for attachment in attachments {
+        let (bytes, format) = normalize(read_bytes(attachment)?)?;
+        let hash: String = Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let extension = format.extensions_str().first().copied().unwrap_or("img");
+        let path = store_dir().join(format!("{}.{}", hash, extension));
+        if !path.exists() {
+            crate::ensure_parent(&path)?;
+            std::fs::write(&path, &bytes)
+                .map_err(|e| format!("Failed to store attachment: {}", e))?;
+        }
+        let alt = attachment.name.clone().or_else(|| {
+            attachment
+                .path
+                .as_deref()
+                .and_then(|p| Path::new(p).file_name())
+                .map(|name| name.to_string_lossy().to_string())
+        });
+        parts.push(MessagePart::Image {
+            src: path.to_string_lossy().to_string(),
+            alt,
+        });
+    }
+    Ok(parts)
+}
+
+/// File name and bytes of an attachment in the store; None for any other path.
+pub fn stored(src: &str) -> Option<(String, Vec<u8>)> {
+    let path = Path::new(src);
+    if !path.starts_with(store_dir()) {
+        return None;
+    }
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let bytes = std::fs::read(path).ok()?;
+    Some((name, bytes))
+}
+
+/// Put an attachment from another machine into the store under `name`,
+/// returning its local path.
+pub fn restore(name: &str, bytes: &[u8]) -> Result<String, String> {
+    let invalid = || format!("Invalid attachment name: {}", name);
+    let file_name = Path::new(name).file_name().ok_or_else(invalid)?;
+    if file_name != name {
+        return Err(invalid());
+    }
+    let hash = name.split_once('.').map_or(name, |(hash, _)| hash);
+    if !crate::checkpoints::is_hash(hash) {
+        return Err(invalid());
+    }
+    let actual: String = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != hash {
+        return Err(format!("Attachment {} is corrupt", name));
+    }
+    let path = store_dir().join(file_name);
+    if !path.exists() {
+        crate::ensure_parent(&path)?;
+        std::fs::write(&path, bytes).map_err(|e| format!("Failed to store attachment: {}", e))?;
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Data URL of a stored attachment. Images that aren't in the attachment
+/// store, such as links in the message text, are not sent.
+fn data_url(src: &str) -> Option<String> {
+    let path = Path::new(src);
+    if !path.starts_with(store_dir()) {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    let format = image::guess_format(&bytes).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        mime_type(format),
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Content of a user message for the API: the text alone, or text and image
+/// blocks when images are attached.
+pub fn user_content(text: &str, parts: &[MessagePart]) -> serde_json::Value {
+    let images: Vec<serde_json::Value> = parts
+        .iter()
+        .filter_map(|part| match part {
+            MessagePart::Image { src, .. } => data_url(src),
+            _ => None,
+        })
+        .map(|url| serde_json::json!({ "type": "image_url", "image_url": { "url": url } }))
+        .collect();
+    if images.is_empty() {
+        return serde_json::json!(text);
+    }
+    let mut content = vec![serde_json::json!({ "type": "text", "text": text })];
+    content.extend(images);
+    serde_json::Value::Array(content)
+}
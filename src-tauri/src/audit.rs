@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::policy::Decision;
+
+/// Past this size the older half of the log is dropped
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// A tool call approved, refused or sent to the user by a rule rather than
+/// by the user, and the rule that decided it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    pub session_id: String,
+    pub work_dir: String,
+    pub tool: String,
+    /// Files the call edits, or the command it runs
+    pub targets: Vec<String>,
+    /// "allow", "deny" or "ask"
+    pub decision: String,
+    pub rule: String,
+    /// Why a call was refused
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+fn log_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_approval_audit.jsonl")
+}
+
+fn trim_log(path: &PathBuf) {
+    if fs::metadata(path).map(|meta| meta.len()).unwrap_or(0) <= MAX_LOG_BYTES {
+        return;
+    }
+    let Ok(contents) = fs::read_to_string(path) else {
+        return;
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    let kept = lines[lines.len() / 2..].join("\n");
+    let _ = fs::write(path, format!("{}\n", kept));
+}
+
+/// Append a decision to the audit log. Failures are ignored; the log must
+/// never hold up a tool call.
+pub fn record(
+    session_id: &str,
+    work_dir: &str,
+    tool: &str,
+    args: &serde_json::Value,
+    decision: &Decision,
+    rule: &str,
+) {
+    let mut targets = crate::tools::edit_paths(tool, args);
+    if targets.is_empty() {
+        targets.extend(
+            args.get("command")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+        );
+    }
+    let (decision, reason) = match decision {
+        Decision::Allow => ("allow", None),
+        Decision::Deny(reason) => ("deny", Some(reason.clone())),
+        Decision::Ask => ("ask", None),
+    };
+    let entry = AuditEntry {
+        timestamp: chrono::Utc::now().timestamp(),
+        session_id: session_id.to_string(),
+        work_dir: work_dir.to_string(),
+        tool: tool.to_string(),
+        targets,
+        decision: decision.to_string(),
+        rule: rule.to_string(),
+        reason,
+    };
+    let Ok(line) = serde_json::to_string(&entry) else {
+        return;
+    };
+    let path = log_path();
+    if crate::ensure_parent(&path).is_err() {
+        return;
+    }
+    trim_log(&path);
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// The latest automatic approval decisions, newest first, optionally for one
+/// work dir.
+#[tauri::command]
+pub fn approval_audit_list(
+    work_dir: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEntry>, String> {
+    let path = log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = crate::read_text(&path)?;
+    Ok(contents
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter(|entry| {
+            work_dir
+                .as_deref()
+                .is_none_or(|dir| crate::same_path(&entry.work_dir, dir))
+        })
+        .take(limit.unwrap_or(DEFAULT_LIST_LIMIT))
+        .collect())
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_autostart::ManagerExt;
+
+/// Passed by the login item so the app starts hidden in the tray
+pub const BACKGROUND_ARG: &str = "--background";
+
+#[derive(Clone, Serialize)]
+pub struct BackgroundStatus {
+    /// Registered to start at login
+    pub autostart: bool,
+    /// Closing the window hides it to the tray instead of quitting
+    pub background_mode: bool,
+    pub launched_in_background: bool,
+}
+
+pub fn launched_in_background() -> bool {
+    std::env::args().any(|arg| arg == BACKGROUND_ARG)
+}
+
+/// Whether closing the main window should leave the app running in the tray,
+/// so queued prompts and monitors keep working with no window open.
+pub fn keep_running() -> bool {
+    launched_in_background()
+        || crate::gui_settings_load(None)
+            .map(|payload| payload.settings.background_mode.unwrap_or(false))
+            .unwrap_or(false)
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Tray icon with Show/Quit, and the main window hidden when started by the
+/// login item. The webview stays loaded while hidden, so the UI's queue and
+/// network monitor keep running.
+pub fn setup(app: &tauri::App) -> tauri::Result<()> {
+    let show = MenuItem::with_id(app, "show", "Show Kimi GUI", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&show, &quit])?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("Kimi GUI")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            "show" => show_main_window(app),
+            "quit" => app.exit(0),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    if launched_in_background() {
+        if let Some(window) = app.get_webview_window("main") {
+            window.hide()?;
+        }
+    }
+    Ok(())
+}
+
+fn status(app: &AppHandle) -> Result<BackgroundStatus, String> {
+    let autostart = app
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read login item: {}", e))?;
+    let settings = crate::gui_settings_load(None)?.settings;
+    Ok(BackgroundStatus {
+        autostart,
+        background_mode: settings.background_mode.unwrap_or(false),
+        launched_in_background: launched_in_background(),
+    })
+}
+
+#[tauri::command]
+pub fn background_status(app: AppHandle) -> Result<BackgroundStatus, String> {
+    status(&app)
+}
+
+/// Register or remove the login item. It launches the app with
+/// `--background`, hidden in the tray.
+#[tauri::command]
+pub fn autostart_set(app: AppHandle, enabled: bool) -> Result<BackgroundStatus, String> {
+    let launcher = app.autolaunch();
+    if enabled {
+        launcher.enable()
+    } else {
+        launcher.disable()
+    }
+    .map_err(|e| format!("Failed to update login item: {}", e))?;
+    status(&app)
+}
@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+use tauri::Emitter;
+
+use crate::llm::{self, StreamEvent};
+use crate::tools;
+
+/// Fixed so every model sees the same context, whatever folder is open
+const BENCHMARK_SYSTEM_PROMPT: &str =
+    "You are a coding assistant working in the project at /workspace. \
+Use the provided tools when a task needs them; otherwise answer directly and briefly.";
+
+/// One canned prompt and what a correct first response looks like. Tool calls
+/// are checked, never run.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BenchmarkTask {
+    pub name: String,
+    pub prompt: String,
+    /// Tool the model should call; None when it should answer without tools
+    #[serde(default)]
+    pub expect_tool: Option<String>,
+    /// Text each named argument of the expected call must contain
+    #[serde(default)]
+    pub expect_args: HashMap<String, String>,
+    /// Text the answer must contain (case-insensitive)
+    #[serde(default)]
+    pub expect_text: Option<String>,
+}
+
+impl BenchmarkTask {
+    fn new(
+        name: &str,
+        prompt: &str,
+        expect_tool: Option<&str>,
+        expect_args: &[(&str, &str)],
+        expect_text: Option<&str>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            prompt: prompt.to_string(),
+            expect_tool: expect_tool.map(str::to_string),
+            expect_args: expect_args
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            expect_text: expect_text.map(str::to_string),
+        }
+    }
+}
+
+/// Suite used when the caller doesn't bring its own.
+pub fn default_suite() -> Vec<BenchmarkTask> {
+    vec![
+        BenchmarkTask::new(
+            "direct_answer",
+            "What is 17 * 23? Reply with just the number.",
+            None,
+            &[],
+            Some("391"),
+        ),
+        BenchmarkTask::new(
+            "read_file",
+            "Show me what is in Cargo.toml.",
+            Some("ReadFile"),
+            &[("path", "Cargo.toml")],
+            None,
+        ),
+        BenchmarkTask::new(
+            "run_command",
+            "Run the test suite with `cargo test`.",
+            Some("Shell"),
+            &[("command", "cargo test")],
+            None,
+        ),
+        BenchmarkTask::new(
+            "write_file",
+            "Create a file notes.txt containing the single line: hello",
+            Some("WriteFile"),
+            &[("path", "notes.txt"), ("content", "hello")],
+            None,
+        ),
+        BenchmarkTask::new(
+            "no_tool_needed",
+            "In one sentence, what does a mutex do?",
+            None,
+            &[],
+            None,
+        ),
+    ]
+}
+
+#[derive(Clone, Serialize)]
+pub struct TaskResult {
+    pub task: String,
+    pub passed: bool,
+    /// Why the response was judged wrong, or the error
+    pub detail: Option<String>,
+    pub latency_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Completion tokens per second of the whole request
+    pub tokens_per_sec: f64,
+    pub tool_calls: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ModelResult {
+    pub model: String,
+    pub tasks: Vec<TaskResult>,
+    pub passed: usize,
+    pub avg_latency_ms: u64,
+    pub avg_tokens_per_sec: f64,
+    /// Set when the model couldn't be reached at all
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub tasks: Vec<String>,
+    pub models: Vec<ModelResult>,
+    /// Markdown table comparing the models
+    pub summary: String,
+    pub duration_ms: u64,
+}
+
+fn emit_benchmark(window: &tauri::Window, event: &str, data: serde_json::Value) {
+    let _ = window.emit(
+        "benchmark://event",
+        StreamEvent {
+            event: event.to_string(),
+            data,
+        },
+    );
+}
+
+/// Why `message` doesn't do what `task` asks; None when it does.
+fn check(task: &BenchmarkTask, message: &serde_json::Value) -> Option<String> {
+    let content = message
+        .get("content")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let calls = message
+        .get("tool_calls")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let Some(expected) = &task.expect_tool else {
+        if let Some(call) = calls.first() {
+            return Some(format!(
+                "Called {} instead of answering",
+                call["function"]["name"].as_str().unwrap_or("a tool")
+            ));
+        }
+        return match &task.expect_text {
+            Some(text) if !content.to_lowercase().contains(&text.to_lowercase()) => {
+                Some(format!("Answer doesn't contain \"{}\"", text))
+            }
+            _ => None,
+        };
+    };
+
+    let Some(call) = calls
+        .iter()
+        .find(|call| call["function"]["name"].as_str() == Some(expected.as_str()))
+    else {
+        return Some(match calls.first() {
+            Some(call) => format!(
+                "Called {} instead of {}",
+                call["function"]["name"]
+                    .as_str()
+                    .unwrap_or("an unknown tool"),
+                expected
+            ),
+            None => format!("Didn't call {}", expected),
+        });
+    };
+    let raw = call["function"]["arguments"].as_str().unwrap_or("");
+    let Ok(serde_json::Value::Object(args)) = serde_json::from_str::<serde_json::Value>(raw) else {
+        return Some(format!("Arguments aren't a JSON object: {}", raw));
+    };
+    for (key, want) in &task.expect_args {
+        let value = match args.get(key) {
+            Some(serde_json::Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+            None => return Some(format!("Missing argument `{}`", key)),
+        };
+        if !value.contains(want.as_str()) {
+            return Some(format!(
+                "Argument `{}` is {:?}, expected it to contain {:?}",
+                key, value, want
+            ));
+        }
+    }
+    None
+}
+
+async fn run_task(
+    client: &reqwest::Client,
+    credentials: &crate::http::Credentials,
+    api_model: &str,
+    tools_def: &[serde_json::Value],
+    task: &BenchmarkTask,
+) -> TaskResult {
+    let request = serde_json::json!({
+        "model": api_model,
+        "messages": [
+            { "role": "system", "content": BENCHMARK_SYSTEM_PROMPT },
+            { "role": "user", "content": task.prompt },
+        ],
+        "stream": false,
+        "tools": tools_def,
+        "tool_choice": "auto",
+    });
+    let started = Instant::now();
+    let response = llm::post_chat_completion(client, credentials, &request, None).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let mut result = TaskResult {
+        task: task.name.clone(),
+        passed: false,
+        detail: None,
+        latency_ms,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        tokens_per_sec: 0.0,
+        tool_calls: Vec::new(),
+    };
+    let data = match response {
+        Ok(data) => data,
+        Err(err) => {
+            result.detail = Some(err);
+            return result;
+        }
+    };
+
+    let usage = data.get("usage").cloned().unwrap_or_default();
+    result.prompt_tokens = usage
+        .get("prompt_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    result.completion_tokens = usage
+        .get("completion_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    if latency_ms > 0 {
+        result.tokens_per_sec = result.completion_tokens as f64 * 1000.0 / latency_ms as f64;
+    }
+    let Some(message) = data.pointer("/choices/0/message") else {
+        result.detail = Some("No message in response".to_string());
+        return result;
+    };
+    result.tool_calls = message
+        .get("tool_calls")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|call| call["function"]["name"].as_str().map(str::to_string))
+        .collect();
+    result.detail = check(task, message);
+    result.passed = result.detail.is_none();
+    result
+}
+
+fn summarize(model: String, tasks: Vec<TaskResult>, error: Option<String>) -> ModelResult {
+    // Failed requests have no meaningful speed
+    let answered: Vec<&TaskResult> = tasks.iter().filter(|t| t.completion_tokens > 0).collect();
+    let count = answered.len().max(1);
+    ModelResult {
+        passed: tasks.iter().filter(|t| t.passed).count(),
+        avg_latency_ms: answered.iter().map(|t| t.latency_ms).sum::<u64>() / count as u64,
+        avg_tokens_per_sec: answered.iter().map(|t| t.tokens_per_sec).sum::<f64>() / count as f64,
+        model,
+        tasks,
+        error,
+    }
+}
+
+fn render_summary(tasks: &[BenchmarkTask], models: &[ModelResult]) -> String {
+    let mut markdown = String::from("| Model | Passed | Avg latency | Avg tokens/s |");
+    for task in tasks {
+        markdown.push_str(&format!(" {} |", task.name));
+    }
+    markdown.push_str("\n|---|---|---|---|");
+    markdown.push_str(&"---|".repeat(tasks.len()));
+    markdown.push('\n');
+    for model in models {
+        markdown.push_str(&format!(
+            "| {} | {}/{} | {} ms | {:.1} |",
+            model.model,
+            model.passed,
+            tasks.len(),
+            model.avg_latency_ms,
+            model.avg_tokens_per_sec
+        ));
+        for task in tasks {
+            let mark = match model.tasks.iter().find(|t| t.task == task.name) {
+                Some(result) if result.passed => "✓",
+                Some(_) => "✗",
+                None => "–",
+            };
+            markdown.push_str(&format!(" {} |", mark));
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Run a prompt suite (the built-in one when `prompt_suite` is None) against
+/// each model and compare latency, throughput and whether each model called
+/// the right tool with the right arguments. Progress is reported as
+/// `benchmark://event` events.
+#[tauri::command]
+pub async fn benchmark_models(
+    window: tauri::Window,
+    models: Vec<String>,
+    prompt_suite: Option<Vec<BenchmarkTask>>,
+    config_file: Option<String>,
+) -> Result<BenchmarkReport, String> {
+    if models.is_empty() {
+        return Err("Pick at least one model to benchmark".to_string());
+    }
+    let suite = prompt_suite
+        .filter(|suite| !suite.is_empty())
+        .unwrap_or_else(default_suite);
+    let auth_config = crate::load_auth_config();
+    let config_path = config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::default_config_path().to_string_lossy().to_string()));
+    let tools_def = tools::tool_definitions();
+    let started = Instant::now();
+
+    let mut results = Vec::new();
+    for model in models {
+        let resolved = crate::providers::resolve(&auth_config, config_path.as_deref(), &model)
+            .await
+            .and_then(|(credentials, api_model)| {
+                crate::http::client(&credentials).map(|client| (credentials, client, api_model))
+            });
+        let (credentials, client, api_model) = match resolved {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                results.push(summarize(model, Vec::new(), Some(err)));
+                continue;
+            }
+        };
+
+        let mut tasks = Vec::new();
+        for task in &suite {
+            emit_benchmark(
+                &window,
+                "task_start",
+                serde_json::json!({ "model": model, "task": task.name }),
+            );
+            let result = run_task(&client, &credentials, &api_model, &tools_def, task).await;
+            emit_benchmark(
+                &window,
+                "task_end",
+                serde_json::json!({
+                    "model": model,
+                    "task": task.name,
+                    "passed": result.passed,
+                    "latency_ms": result.latency_ms,
+                }),
+            );
+            tasks.push(result);
+        }
+        results.push(summarize(model, tasks, None));
+    }
+
+    Ok(BenchmarkReport {
+        tasks: suite.iter().map(|task| task.name.clone()).collect(),
+        summary: render_summary(&suite, &results),
+        models: results,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
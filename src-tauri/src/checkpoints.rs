@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// Days a checkpoint is kept when the setting is unset
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// The index, read from disk once and kept in step with it after that, so
+/// snapshots don't re-read every version ever saved.
+static INDEX: Mutex<Option<Vec<FileVersion>>> = Mutex::new(None);
+
+/// Content of a file as it was before a turn first modified it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FileVersion {
+    pub path: String,
+    pub turn_id: String,
+    pub session_id: String,
+    /// 1-based user turn in the session; 0 for snapshots taken by a rollback
+    pub turn: usize,
+    /// False when the turn created the file
+    pub existed: bool,
+    pub blob: Option<String>,
+    pub size: u64,
+    pub created_at: i64,
+}
+
+/// The chat turn mutating tools run in.
+pub struct Turn {
+    pub id: String,
+    pub number: usize,
+}
+
+impl Turn {
+    pub fn new(number: usize) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            number,
+        }
+    }
+}
+
+fn store_dir() -> PathBuf {
+    crate::kimi_share_dir().join("gui_checkpoints")
+}
+
+fn index_path() -> PathBuf {
+    store_dir().join("index.jsonl")
+}
+
+/// Whether `value` is a hex SHA-256, the only names blobs and attachments are
+/// stored under.
+pub fn is_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn blob_path(hash: &str) -> PathBuf {
+    store_dir().join("blobs").join(hash)
+}
+
+fn read_index() -> Vec<FileVersion> {
+    fs::read_to_string(index_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Run `f` on the index, loading it on first use.
+fn with_index<T>(f: impl FnOnce(&mut Vec<FileVersion>) -> T) -> T {
+    let mut index = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    f(index.get_or_insert_with(read_index))
+}
+
+/// The versions `keep` accepts, oldest first.
+fn load_index(keep: impl Fn(&FileVersion) -> bool) -> Vec<FileVersion> {
+    with_index(|versions| versions.iter().filter(|v| keep(v)).cloned().collect())
+}
+
+fn indexed(session_id: &str, turn_id: &str, path: &str) -> bool {
+    with_index(|versions| {
+        versions
+            .iter()
+            .any(|v| v.session_id == session_id && v.turn_id == turn_id && v.path == path)
+    })
+}
+
+fn append_index(version: &FileVersion) -> Result<(), String> {
+    let path = index_path();
+    crate::ensure_parent(&path)?;
+    let line = serde_json::to_string(version).map_err(|e| e.to_string())?;
+    with_index(|versions| {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("Failed to write checkpoint index: {}", e))?;
+        versions.push(version.clone());
+        Ok(())
+    })
+}
+
+/// Drop versions older than `retention_days` and the blobs no version uses any
+/// more. Zero keeps everything.
+pub fn prune(retention_days: u64) {
+    if retention_days == 0 {
+        return;
+    }
+    let cutoff = chrono::Utc::now().timestamp() - (retention_days * 24 * 60 * 60) as i64;
+    let max_age = Duration::from_secs(retention_days * 24 * 60 * 60);
+    with_index(|versions| {
+        let before = versions.len();
+        versions.retain(|version| version.created_at >= cutoff);
+        if versions.len() != before {
+            let lines: String = versions
+                .iter()
+                .filter_map(|version| serde_json::to_string(version).ok())
+                .map(|line| line + "\n")
+                .collect();
+            let _ = crate::write_text(&index_path(), &lines);
+        }
+        let used: HashSet<&str> = versions.iter().filter_map(|v| v.blob.as_deref()).collect();
+        for entry in fs::read_dir(store_dir().join("blobs")).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Blobs written since the cutoff may belong to a snapshot in flight
+            let old = entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > max_age);
+            if old && !used.contains(name.as_str()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    });
+}
+
+fn snapshot_with(session_id: &str, turn_id: &str, turn: usize, path: &Path) -> Result<FileVersion, String> {
+    let (existed, blob, size) = match fs::read(path) {
+        Ok(bytes) => {
+            let hash: String = Sha256::digest(&bytes).iter().map(|b| format!("{:02x}", b)).collect();
+            let blob = blob_path(&hash);
+            if !blob.exists() {
+                crate::ensure_parent(&blob)?;
+                fs::write(&blob, &bytes).map_err(|e| format!("Failed to store checkpoint: {}", e))?;
+            }
+            (true, Some(hash), bytes.len() as u64)
+        }
+        Err(_) => (false, None, 0),
+    };
+    let version = FileVersion {
+        path: path.to_string_lossy().to_string(),
+        turn_id: turn_id.to_string(),
+        session_id: session_id.to_string(),
+        turn,
+        existed,
+        blob,
+        size,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    append_index(&version)?;
+    Ok(version)
+}
+
+/// Save `path` before the turn's first change to it; later changes in the same
+/// turn keep the earlier snapshot.
+pub fn snapshot(session_id: &str, turn: &Turn, path: &Path) -> Result<(), String> {
+    if indexed(session_id, &turn.id, &path.to_string_lossy()) {
+        return Ok(());
+    }
+    snapshot_with(session_id, &turn.id, turn.number, path).map(|_| ())
+}
+
+/// Content a file had before `session_id` first changed it, keyed by path; None
+/// when the session created the file.
+pub fn session_originals(session_id: &str) -> Vec<(String, Option<String>)> {
+    let mut originals: Vec<(String, Option<String>)> = Vec::new();
+    for version in load_index(|version| version.session_id == session_id) {
+        if originals.iter().any(|(path, _)| *path == version.path) {
+            continue;
+        }
+        let content = version
+            .blob
+            .as_deref()
+            .filter(|_| version.existed)
+            .and_then(|hash| fs::read(blob_path(hash)).ok())
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string());
+        originals.push((version.path, content));
+    }
+    originals
+}
+
+/// Every version saved by `session_id`, oldest first.
+pub fn session_versions(session_id: &str) -> Vec<FileVersion> {
+    load_index(|version| version.session_id == session_id)
+}
+
+pub fn read_blob(hash: &str) -> Option<Vec<u8>> {
+    fs::read(blob_path(hash)).ok()
+}
+
+/// Add a version saved on another machine. Versions already in the index are
+/// skipped, and content that doesn't match its hash is refused.
+pub fn restore(version: &FileVersion, bytes: Option<&[u8]>) -> Result<(), String> {
+    if indexed(&version.session_id, &version.turn_id, &version.path) {
+        return Ok(());
+    }
+    if let Some(hash) = version.blob.as_deref().filter(|hash| !is_hash(hash)) {
+        return Err(format!("Invalid checkpoint hash for {}: {}", version.path, hash));
+    }
+    if let (Some(hash), Some(bytes)) = (version.blob.as_deref(), bytes) {
+        let actual: String = Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect();
+        if actual != hash {
+            return Err(format!("Checkpoint data for {} is corrupt", version.path));
+        }
+        let blob = blob_path(hash);
+        if !blob.exists() {
+            crate::ensure_parent(&blob)?;
+            fs::write(&blob, bytes).map_err(|e| format!("Failed to store checkpoint: {}", e))?;
+        }
+    }
+    append_index(version)
+}
+
+fn resolve(path: &str, work_dir: Option<&str>) -> PathBuf {
+    let path = Path::new(path);
+    let joined = match work_dir {
+        Some(work_dir) if path.is_relative() => Path::new(work_dir).join(path),
+        _ => path.to_path_buf(),
+    };
+    crate::claims::normalize(&joined)
+}
+
+/// Saved versions of a file, newest first.
+#[tauri::command]
+pub fn file_versions(path: String, work_dir: Option<String>) -> Result<Vec<FileVersion>, String> {
+    let key = resolve(&path, work_dir.as_deref()).to_string_lossy().to_string();
+    let mut versions = load_index(|version| version.path == key);
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Restore one file to how it was before `turn_id`, leaving every other file
+/// alone. The current content is saved first, so the rollback itself can be undone.
+#[tauri::command]
+pub fn file_rollback(path: String, turn_id: String, work_dir: Option<String>) -> Result<FileVersion, String> {
+    let resolved = resolve(&path, work_dir.as_deref());
+    let key = resolved.to_string_lossy().to_string();
+    let version = load_index(|version| version.path == key && version.turn_id == turn_id)
+        .pop()
+        .ok_or_else(|| "No saved version of this file for that turn".to_string())?;
+
+    let safety = snapshot_with("", &format!("rollback-{}", Uuid::new_v4()), 0, &resolved)?;
+    match version.blob.as_deref() {
+        Some(hash) if version.existed => {
+            let bytes = fs::read(blob_path(hash)).map_err(|e| format!("Checkpoint data is missing: {}", e))?;
+            crate::ensure_parent(&resolved)?;
+            fs::write(&resolved, bytes).map_err(|e| format!("Failed to restore file: {}", e))?;
+        }
+        _ => {
+            // The turn created the file
+            if resolved.exists() {
+                fs::remove_file(&resolved).map_err(|e| format!("Failed to remove file: {}", e))?;
+            }
+        }
+    }
+    Ok(safety)
+}
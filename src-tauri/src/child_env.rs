@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::sync::RwLock;
+
+use crate::GuiSettings;
+
+/// Variables child processes always inherit: what shells and toolchains need
+/// to find programs, the user's home, locale and display. A trailing `*`
+/// matches a prefix.
+const DEFAULT_ALLOW: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "LANG",
+    "LANGUAGE",
+    "LC_*",
+    "TZ",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "XDG_*",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "SSH_AUTH_SOCK",
+    // npm, cargo and pip can't reach registries behind a corporate proxy without these
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+    "SSL_CERT_FILE",
+    // Toolchains installed outside their default locations
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "JAVA_HOME",
+    // Windows programs fail to start without these
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERNAME",
+    "USERPROFILE",
+    "HOMEDRIVE",
+    "HOMEPATH",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+    "PROGRAMFILES*",
+    "COMMONPROGRAMFILES*",
+    "PROCESSOR_ARCHITECTURE",
+    "NUMBER_OF_PROCESSORS",
+];
+
+/// Which of the GUI's environment variables Shell commands, background
+/// processes, hooks and MCP servers inherit. Anything else, such as API keys
+/// the app was started with, is withheld; variables set in the settings or an
+/// MCP server's config are added on top.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EnvPolicy {
+    /// Pass the whole GUI environment through unfiltered
+    #[serde(default)]
+    pub inherit_all: bool,
+    /// Names inherited on top of the defaults, e.g. `GOPATH` or `AWS_*`
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+static POLICY: RwLock<Option<EnvPolicy>> = RwLock::new(None);
+
+pub fn set_policy(policy: EnvPolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+}
+
+fn policy() -> EnvPolicy {
+    POLICY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    // Windows variable names are case-insensitive
+    let (pattern, name) = if cfg!(windows) {
+        (pattern.to_uppercase(), name.to_uppercase())
+    } else {
+        (pattern.to_string(), name.to_string())
+    };
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+impl EnvPolicy {
+    pub fn allows(&self, name: &str) -> bool {
+        self.inherit_all
+            || DEFAULT_ALLOW
+                .iter()
+                .copied()
+                .chain(self.allow.iter().map(|p| p.trim()))
+                .filter(|p| !p.is_empty())
+                .any(|p| matches(p, name))
+    }
+
+    /// The GUI's variables split into those a child inherits and the names of
+    /// those withheld. Names that aren't valid Unicode are always withheld.
+    fn partition(&self) -> (Vec<(OsString, OsString)>, Vec<String>) {
+        let mut inherited = Vec::new();
+        let mut withheld = Vec::new();
+        for (name, value) in std::env::vars_os() {
+            match name.to_str() {
+                Some(text) if self.allows(text) => inherited.push((name, value)),
+                Some(text) => withheld.push(text.to_string()),
+                None => withheld.push(name.to_string_lossy().to_string()),
+            }
+        }
+        (inherited, withheld)
+    }
+}
+
+/// Start `cmd` from the GUI environment filtered by the current policy. Call
+/// it before setting the command's own variables, which it would clear.
+pub fn scrub(cmd: &mut tokio::process::Command) {
+    let policy = policy();
+    if policy.inherit_all {
+        return;
+    }
+    let (inherited, _) = policy.partition();
+    cmd.env_clear().envs(inherited);
+}
+
+#[derive(Serialize)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    /// `inherited` from the GUI, or set by `settings` or the MCP `server` config
+    pub source: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct EnvPreview {
+    pub variables: Vec<EnvVar>,
+    /// GUI variables the policy keeps from the child
+    pub withheld: Vec<String>,
+}
+
+/// The environment a Shell command would receive under `settings`, or the MCP
+/// server named `mcp_server` when given.
+#[tauri::command]
+pub fn child_env_preview(
+    settings: Option<GuiSettings>,
+    mcp_server: Option<String>,
+) -> Result<EnvPreview, String> {
+    let settings = settings.unwrap_or_default();
+    let policy = settings.child_env.clone().unwrap_or_default();
+    let (explicit, source): (HashMap<String, String>, &'static str) = match mcp_server {
+        Some(server) => {
+            let config = crate::mcp::load_server_configs(&settings.mcp_config_files)
+                .into_iter()
+                .flatten()
+                .find(|config| config.name == server)
+                .ok_or_else(|| format!("MCP server {} is not configured", server))?;
+            (config.env, "server")
+        }
+        None => (settings.env, "settings"),
+    };
+
+    let (inherited, mut withheld) = policy.partition();
+    withheld.retain(|name| !explicit.contains_key(name));
+    let mut variables: Vec<EnvVar> = inherited
+        .into_iter()
+        .map(|(name, value)| EnvVar {
+            name: name.to_string_lossy().to_string(),
+            value: value.to_string_lossy().to_string(),
+            source: "inherited",
+        })
+        .filter(|var| !explicit.contains_key(&var.name))
+        .collect();
+    variables.extend(explicit.into_iter().map(|(name, value)| EnvVar {
+        name,
+        value,
+        source,
+    }));
+    variables.sort_by(|a, b| a.name.cmp(&b.name));
+    withheld.sort();
+    Ok(EnvPreview {
+        variables,
+        withheld,
+    })
+}
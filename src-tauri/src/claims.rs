@@ -0,0 +1,215 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Emitter;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::llm::StreamEvent;
+use crate::AppState;
+
+/// How long a tool call waits on another session's claim. Two sessions each
+/// waiting on a file the other holds would otherwise wait forever.
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Clone, Serialize)]
+pub struct FileClaim {
+    pub path: String,
+    pub session_id: String,
+    pub tool_call_id: String,
+    pub claimed_at: i64,
+}
+
+/// Files that sessions intend to modify. A claim is held until the owning
+/// session's turn ends, so a second agent on the same repo waits instead of
+/// interleaving edits to the same file.
+#[derive(Default)]
+pub struct ClaimRegistry {
+    claims: Mutex<HashMap<PathBuf, FileClaim>>,
+    released: Notify,
+}
+
+impl ClaimRegistry {
+    /// Claim every path for `session_id`, or return the first conflicting claim.
+    fn try_claim(
+        &self,
+        session_id: &str,
+        tool_call_id: &str,
+        paths: &[PathBuf],
+    ) -> Result<(), FileClaim> {
+        let mut claims = self.claims.lock().unwrap_or_else(|e| e.into_inner());
+        for path in paths {
+            if let Some(existing) = claims.get(path) {
+                if existing.session_id != session_id {
+                    return Err(existing.clone());
+                }
+            }
+        }
+        let now = chrono::Utc::now().timestamp();
+        for path in paths {
+            claims.insert(
+                path.clone(),
+                FileClaim {
+                    path: path.to_string_lossy().to_string(),
+                    session_id: session_id.to_string(),
+                    tool_call_id: tool_call_id.to_string(),
+                    claimed_at: now,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    pub fn release_session(&self, session_id: &str) {
+        let mut claims = self.claims.lock().unwrap_or_else(|e| e.into_inner());
+        let before = claims.len();
+        claims.retain(|_, claim| claim.session_id != session_id);
+        if claims.len() != before {
+            self.released.notify_waiters();
+        }
+    }
+
+    pub fn list(&self) -> Vec<FileClaim> {
+        let claims = self.claims.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<FileClaim> = claims.values().cloned().collect();
+        list.sort_by(|a, b| a.path.cmp(&b.path));
+        list
+    }
+}
+
+/// Releases a session's claims when its turn ends, however `stream_chat` exits.
+pub struct SessionClaims<'a> {
+    registry: &'a ClaimRegistry,
+    session_id: String,
+}
+
+impl<'a> SessionClaims<'a> {
+    pub fn new(registry: &'a ClaimRegistry, session_id: &str) -> Self {
+        Self {
+            registry,
+            session_id: session_id.to_string(),
+        }
+    }
+}
+
+impl Drop for SessionClaims<'_> {
+    fn drop(&mut self) {
+        self.registry.release_session(&self.session_id);
+    }
+}
+
+pub fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Files a tool call will modify.
+pub fn claim_targets(name: &str, args: &serde_json::Value, work_dir: &str) -> Vec<PathBuf> {
+    crate::tools::edit_paths(name, args)
+        .into_iter()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| {
+            let path = Path::new(work_dir).join(path);
+            // DeleteFile removes a link itself, so claim the link, not its target
+            match (name, path.parent(), path.file_name()) {
+                ("DeleteFile", Some(parent), Some(file_name)) if path.is_symlink() => {
+                    normalize(parent).join(file_name)
+                }
+                _ => normalize(&path),
+            }
+        })
+        .collect()
+}
+
+pub enum ClaimError {
+    /// The turn was cancelled while blocked
+    Cancelled,
+    /// Still blocked after CLAIM_TIMEOUT; the message is the tool's error
+    TimedOut(String),
+}
+
+/// Wait until `paths` can be claimed for this session, for up to
+/// CLAIM_TIMEOUT.
+pub async fn acquire(
+    window: &tauri::Window,
+    registry: &ClaimRegistry,
+    session_id: &str,
+    tool_call_id: &str,
+    paths: &[PathBuf],
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<(), ClaimError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let deadline = tokio::time::Instant::now() + CLAIM_TIMEOUT;
+    let mut blocked = false;
+    loop {
+        let released = registry.released.notified();
+        match registry.try_claim(session_id, tool_call_id, paths) {
+            Ok(()) => {
+                if blocked {
+                    let _ = window.emit(
+                        "chat://event",
+                        StreamEvent {
+                            event: "file_claim_acquired".to_string(),
+                            data: serde_json::json!({
+                                "session_id": session_id,
+                                "tool_call_id": tool_call_id,
+                            }),
+                        },
+                    );
+                }
+                return Ok(());
+            }
+            Err(conflict) => {
+                if !blocked {
+                    blocked = true;
+                    let _ = window.emit(
+                        "chat://event",
+                        StreamEvent {
+                            event: "file_claim_blocked".to_string(),
+                            data: serde_json::json!({
+                                "session_id": session_id,
+                                "tool_call_id": tool_call_id,
+                                "path": conflict.path,
+                                "held_by": conflict.session_id,
+                            }),
+                        },
+                    );
+                }
+                tokio::select! {
+                    _ = released => continue,
+                    _ = &mut *cancel_rx => return Err(ClaimError::Cancelled),
+                    _ = tokio::time::sleep_until(deadline) => {
+                        return Err(ClaimError::TimedOut(format!(
+                            "{} is being edited by another session ({}). Gave up after waiting {} seconds; try again later or work on other files.",
+                            conflict.path,
+                            conflict.session_id,
+                            CLAIM_TIMEOUT.as_secs()
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn file_claims_list(state: tauri::State<'_, AppState>) -> Vec<FileClaim> {
+    state.file_claims.list()
+}
+
+#[tauri::command]
+pub fn file_claims_release(state: tauri::State<'_, AppState>, session_id: String) {
+    state.file_claims.release_session(&session_id);
+}
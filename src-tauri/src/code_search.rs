@@ -0,0 +1,244 @@
+use grep::regex::RegexMatcherBuilder;
+use grep::searcher::{BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use ignore::overrides::OverrideBuilder;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::paths::{self, Access};
+use crate::tools::{self, GlobArgs, GrepArgs, ToolOutput};
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_RESULTS: usize = 1000;
+const MAX_CONTEXT: usize = 10;
+/// Files a Glob walk looks at before it stops, so huge trees stay quick
+const MAX_GLOB_FILES: usize = 100_000;
+
+/// Writes matches and their context ripgrep-style, `path:line:text` and
+/// `path-line-text`, stopping once `limit` matches are in.
+struct Collector<'a> {
+    rel: &'a str,
+    output: &'a mut String,
+    matches: usize,
+    limit: usize,
+}
+
+impl Collector<'_> {
+    fn push(&mut self, separator: char, line_number: Option<u64>, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes);
+        let (line, _) = tools::truncate_line(text.trim_end_matches(['\r', '\n']));
+        self.output.push_str(&format!(
+            "{}{}{}{}{}\n",
+            self.rel,
+            separator,
+            line_number.unwrap_or(0),
+            separator,
+            line
+        ));
+    }
+}
+
+impl Sink for Collector<'_> {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, found: &SinkMatch<'_>) -> Result<bool, Self::Error> {
+        self.push(':', found.line_number(), found.bytes());
+        self.matches += 1;
+        Ok(self.matches < self.limit)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, Self::Error> {
+        self.push('-', context.line_number(), context.bytes());
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _searcher: &Searcher) -> Result<bool, Self::Error> {
+        self.output.push_str("--\n");
+        Ok(true)
+    }
+}
+
+fn failure(summary: String) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary,
+        output: String::new(),
+    }
+}
+
+/// The work dir and the file or folder in it a search starts from.
+fn search_roots(work_dir: &str, path: Option<&str>) -> Result<(PathBuf, PathBuf), String> {
+    let root = Path::new(work_dir);
+    let start = match path.filter(|p| !p.trim().is_empty()) {
+        Some(path) => paths::resolve_inside(work_dir, path, Access::Read)?,
+        None => root.to_path_buf(),
+    };
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    Ok((root, start))
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// The Grep tool: a regex search over the work dir (or a file or folder in
+/// it) that skips gitignored, ignored and binary files.
+pub fn search_tool(work_dir: &str, args: &GrepArgs) -> ToolOutput {
+    let matcher = match RegexMatcherBuilder::new()
+        .case_insensitive(args.ignore_case.unwrap_or(false))
+        .build(&args.pattern)
+    {
+        Ok(matcher) => matcher,
+        Err(err) => return failure(format!("Invalid pattern: {}", err)),
+    };
+    let (root, start) = match search_roots(work_dir, args.path.as_deref()) {
+        Ok(roots) => roots,
+        Err(err) => return failure(err),
+    };
+
+    let mut walker = crate::context::project_walker(&root, &start);
+    if let Some(glob) = args.glob.as_deref().filter(|g| !g.trim().is_empty()) {
+        let mut overrides = OverrideBuilder::new(&root);
+        if let Err(err) = overrides.add(glob) {
+            return failure(format!("Invalid glob {}: {}", glob, err));
+        }
+        match overrides.build() {
+            Ok(overrides) => {
+                walker.overrides(overrides);
+            }
+            Err(err) => return failure(format!("Invalid glob {}: {}", glob, err)),
+        }
+    }
+
+    let context = args.context.unwrap_or(0).min(MAX_CONTEXT);
+    let mut searcher = SearcherBuilder::new()
+        .binary_detection(BinaryDetection::quit(0))
+        .line_number(true)
+        .before_context(context)
+        .after_context(context)
+        .build();
+    let limit = args
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, MAX_RESULTS);
+
+    let mut output = String::new();
+    let mut matches = 0;
+    let mut files = 0;
+    for entry in walker.build().flatten() {
+        if matches >= limit {
+            break;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let rel = relative(&root, entry.path());
+        let mut collector = Collector {
+            rel: &rel,
+            output: &mut output,
+            matches: 0,
+            limit: limit - matches,
+        };
+        // Unreadable files are skipped like binary ones
+        if searcher
+            .search_path(&matcher, entry.path(), &mut collector)
+            .is_err()
+        {
+            continue;
+        }
+        if collector.matches > 0 {
+            matches += collector.matches;
+            files += 1;
+        }
+    }
+
+    if matches == 0 {
+        return ToolOutput {
+            ok: true,
+            summary: "No matches found.".to_string(),
+            output: String::new(),
+        };
+    }
+    let (output, truncated) = tools::truncate_output(&output);
+    let mut summary = format!("Found {} matching lines in {} files.", matches, files);
+    if matches >= limit {
+        summary.push_str(&format!(
+            " Stopped at {} results; narrow the pattern, path or glob to see the rest.",
+            limit
+        ));
+    }
+    ToolOutput {
+        ok: true,
+        summary: tools::append_truncation(summary, truncated),
+        output,
+    }
+}
+
+/// The Glob tool: files under the work dir (or a folder in it) matching a
+/// gitignore-style glob, newest first. Ignored files are skipped.
+pub fn glob_tool(work_dir: &str, args: &GlobArgs) -> ToolOutput {
+    let (root, start) = match search_roots(work_dir, args.path.as_deref()) {
+        Ok(roots) => roots,
+        Err(err) => return failure(err),
+    };
+    let mut overrides = OverrideBuilder::new(&start);
+    let built = overrides
+        .add(args.pattern.trim())
+        .and_then(|overrides| overrides.build());
+    let overrides = match built {
+        Ok(overrides) => overrides,
+        Err(err) => return failure(format!("Invalid glob {}: {}", args.pattern, err)),
+    };
+    let mut walker = crate::context::project_walker(&root, &start);
+    walker.overrides(overrides);
+
+    let mut files: Vec<(SystemTime, String)> = Vec::new();
+    let mut walk_truncated = false;
+    for entry in walker.build().flatten() {
+        if files.len() >= MAX_GLOB_FILES {
+            walk_truncated = true;
+            break;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_file() {
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, relative(&root, entry.path())));
+        }
+    }
+    if files.is_empty() {
+        return ToolOutput {
+            ok: true,
+            summary: "No files found.".to_string(),
+            output: String::new(),
+        };
+    }
+    files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let limit = args
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, MAX_RESULTS);
+    let total = files.len();
+    let listed: Vec<String> = files.into_iter().take(limit).map(|(_, path)| path).collect();
+    let mut summary = format!(
+        "Found {}{} files.",
+        if walk_truncated { "over " } else { "" },
+        total
+    );
+    if total > limit {
+        summary.push_str(&format!(
+            " Showing the {} most recently modified; narrow the pattern or path to see the rest.",
+            limit
+        ));
+    }
+    let (output, truncated) = tools::truncate_output(&listed.join("\n"));
+    ToolOutput {
+        ok: true,
+        summary: tools::append_truncation(summary, truncated),
+        output,
+    }
+}
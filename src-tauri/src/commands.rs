@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A user-defined slash command: a Markdown prompt template in a commands
+/// folder, invoked as `/<file stem> args`.
+#[derive(Clone, Serialize)]
+pub struct CustomCommand {
+    pub name: String,
+    pub description: Option<String>,
+    /// Shown after the name in the picker, e.g. `<file> [focus]`
+    pub argument_hint: Option<String>,
+    pub path: String,
+    /// "user" or "project"; project commands win on a name clash
+    pub scope: String,
+    #[serde(skip)]
+    body: String,
+}
+
+fn command_dirs(work_dir: &Path) -> [(PathBuf, &'static str); 2] {
+    [
+        (crate::kimi_share_dir().join("commands"), "user"),
+        (work_dir.join(".kimi/commands"), "project"),
+    ]
+}
+
+fn load_dir(dir: &Path, scope: &str) -> Vec<CustomCommand> {
+    let mut commands = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("md") || !path.is_file() {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+        let (frontmatter, body) = crate::skills::parse_frontmatter(&contents);
+        let field = |key: &str| frontmatter.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        commands.push(CustomCommand {
+            name: name.to_string(),
+            description: field("description"),
+            argument_hint: field("argument-hint"),
+            path: path.to_string_lossy().to_string(),
+            scope: scope.to_string(),
+            body,
+        });
+    }
+    commands
+}
+
+fn load_all(work_dir: &Path) -> Vec<CustomCommand> {
+    let mut by_name: HashMap<String, CustomCommand> = HashMap::new();
+    for (dir, scope) in command_dirs(work_dir) {
+        for command in load_dir(&dir, scope) {
+            by_name.insert(command.name.clone(), command);
+        }
+    }
+    let mut commands: Vec<CustomCommand> = by_name.into_values().collect();
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+    commands
+}
+
+/// Fill in a template: `$ARGUMENTS` is everything after the name and `$1`..`$9`
+/// are its words. Arguments a template doesn't reference are appended.
+fn render(template: &str, arguments: &str) -> String {
+    let words = shell_words::split(arguments)
+        .unwrap_or_else(|_| arguments.split_whitespace().map(str::to_string).collect());
+    let mut prompt = template.replace("$ARGUMENTS", arguments);
+    for index in (1..=9).rev() {
+        let value = words.get(index - 1).map(String::as_str).unwrap_or("");
+        prompt = prompt.replace(&format!("${}", index), value);
+    }
+    let uses_arguments = template.contains("$ARGUMENTS")
+        || (1..=9).any(|index| template.contains(&format!("${}", index)));
+    if !uses_arguments && !arguments.is_empty() {
+        prompt.push_str(&format!("\n\n{}", arguments));
+    }
+    prompt
+}
+
+/// The prompt for a message starting with `/name` when `name` is a custom
+/// command; None leaves the message as typed.
+pub fn expand(work_dir: &str, message: &str) -> Option<String> {
+    let text = message.trim_start().strip_prefix('/')?;
+    let (name, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = load_all(Path::new(work_dir))
+        .into_iter()
+        .find(|command| command.name == name)?;
+    Some(render(&command.body, arguments.trim()))
+}
+
+#[tauri::command]
+pub fn commands_list(work_dir: Option<String>) -> Result<Vec<CustomCommand>, String> {
+    let work_dir = work_dir
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::app_paths().work_dir.into());
+    Ok(load_all(&work_dir))
+}
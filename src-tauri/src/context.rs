@@ -0,0 +1,249 @@
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::paths;
+
+const DEFAULT_BUDGET_TOKENS: usize = 32_000;
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+const MAX_CANDIDATES: usize = 5_000;
+/// Rough chars-per-token ratio for code; good enough to stay under a budget
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Clone, Serialize)]
+pub struct PackedFile {
+    pub path: String,
+    pub tokens: usize,
+    pub score: f64,
+    /// Number of other candidate files that import this one
+    pub imported_by: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ContextPack {
+    /// Concatenated files, ready to attach to a prompt
+    pub payload: String,
+    pub files: Vec<PackedFile>,
+    /// Candidates left out because they did not fit the budget
+    pub skipped: Vec<String>,
+    pub total_tokens: usize,
+    pub budget_tokens: usize,
+}
+
+struct Candidate {
+    rel: String,
+    content: String,
+    tokens: usize,
+    modified: SystemTime,
+    stem: String,
+    references: HashSet<String>,
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+fn is_import_line(line: &str) -> bool {
+    ["use ", "pub use ", "mod ", "pub mod ", "import ", "from ", "#include", "require "]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+        || line.contains("require(")
+        || line.contains("import(")
+}
+
+/// Identifiers named on import-like lines (Rust, JS/TS, Python, C). Matching them
+/// against file stems is a cheap stand-in for resolving the real import graph.
+fn references(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| is_import_line(line))
+        .flat_map(|line| line.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// File under a work dir that .gitignore doesn't exclude.
+pub struct ProjectFile {
+    pub path: PathBuf,
+    /// Path relative to the work dir, with forward slashes
+    pub rel: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Walker over `start`, `root` or a folder below it, that skips gitignored
+/// files, those on `root`'s ignore list, and symlinks leading out of `root`.
+pub fn project_walker(root: &Path, start: &Path) -> WalkBuilder {
+    let mut walker = WalkBuilder::new(start);
+    // Honour .gitignore even when the folder is not a git checkout yet;
+    // hidden files are left to the ignore list, which can re-include them
+    walker.require_git(false).hidden(false);
+    walker.follow_links(paths::follow_symlinks());
+    let ignores = crate::ignores::IgnoreList::load(root);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    walker.filter_entry(move |entry| {
+        !ignores.is_ignored(entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+            && (!entry.path_is_symlink() || paths::link_allowed(&canonical_root, entry.path(), true))
+    });
+    walker
+}
+
+/// Non-empty files under `root` small enough to be prompt context, optionally
+/// limited to `globs`. Gitignored files and those on the ignore list are skipped.
+pub fn project_files(root: &Path, globs: &[String]) -> Result<Vec<ProjectFile>, String> {
+    let mut walker = project_walker(root, root);
+    if !globs.is_empty() {
+        let mut overrides = OverrideBuilder::new(root);
+        for glob in globs {
+            overrides
+                .add(glob)
+                .map_err(|e| format!("Invalid glob {}: {}", glob, e))?;
+        }
+        walker.overrides(overrides.build().map_err(|e| e.to_string())?);
+    }
+
+    let mut files = Vec::new();
+    for entry in walker.build().flatten() {
+        if files.len() >= MAX_CANDIDATES {
+            break;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() || meta.len() == 0 || meta.len() > MAX_FILE_BYTES {
+            continue;
+        }
+        let path = entry.into_path();
+        let rel = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        files.push(ProjectFile {
+            rel,
+            size: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+        });
+    }
+    Ok(files)
+}
+
+/// File content, or None for binary and non-UTF-8 files.
+pub fn read_text_file(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .filter(|content| !content.contains('\0'))
+}
+
+fn collect_candidates(root: &Path, globs: &[String]) -> Result<Vec<Candidate>, String> {
+    Ok(project_files(root, globs)?
+        .into_iter()
+        .filter_map(|file| {
+            let content = read_text_file(&file.path)?;
+            Some(Candidate {
+                tokens: estimate_tokens(&content) + estimate_tokens(&file.rel) + 8,
+                modified: file.modified,
+                stem: file_stem(&file.path),
+                references: references(&content),
+                rel: file.rel,
+                content,
+            })
+        })
+        .collect())
+}
+
+/// Score each candidate by how often it is imported, how recently it changed and
+/// how small it is, in that order of weight.
+fn rank(candidates: &[Candidate]) -> Vec<(usize, f64, usize)> {
+    let mut imported_by: HashMap<&str, usize> = HashMap::new();
+    for candidate in candidates {
+        for reference in &candidate.references {
+            if *reference != candidate.stem {
+                *imported_by.entry(reference.as_str()).or_default() += 1;
+            }
+        }
+    }
+    let max_imports = imported_by.values().copied().max().unwrap_or(0).max(1) as f64;
+    let now = SystemTime::now();
+
+    let mut ranked: Vec<(usize, f64, usize)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| {
+            let imports = imported_by.get(candidate.stem.as_str()).copied().unwrap_or(0);
+            let age_days = now
+                .duration_since(candidate.modified)
+                .map(|age| age.as_secs_f64() / 86_400.0)
+                .unwrap_or(0.0);
+            let recency = 1.0 / (1.0 + age_days);
+            let size = 1.0 / (1.0 + candidate.tokens as f64 / 2_000.0);
+            let score = 2.0 * (imports as f64 / max_imports) + recency + 0.5 * size;
+            (index, score, imports)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Pick the most relevant text files under `work_dir` (optionally limited to
+/// `globs`) and concatenate them until `budget_tokens` is used up. Files
+/// ignored by .gitignore and hidden files are never included.
+#[tauri::command]
+pub fn context_pack(
+    work_dir: String,
+    globs: Option<Vec<String>>,
+    budget_tokens: Option<usize>,
+) -> Result<ContextPack, String> {
+    let root = PathBuf::from(&work_dir);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", work_dir));
+    }
+    let budget = budget_tokens.filter(|b| *b > 0).unwrap_or(DEFAULT_BUDGET_TOKENS);
+    let candidates = collect_candidates(&root, &globs.unwrap_or_default())?;
+
+    let mut payload = String::new();
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut total = 0;
+    for (index, score, imported_by) in rank(&candidates) {
+        let candidate = &candidates[index];
+        if total + candidate.tokens > budget {
+            skipped.push(candidate.rel.clone());
+            continue;
+        }
+        total += candidate.tokens;
+        payload.push_str(&format!("=== {} ===\n```\n{}", candidate.rel, candidate.content));
+        if !candidate.content.ends_with('\n') {
+            payload.push('\n');
+        }
+        payload.push_str("```\n\n");
+        files.push(PackedFile {
+            path: candidate.rel.clone(),
+            tokens: candidate.tokens,
+            score,
+            imported_by,
+        });
+    }
+
+    Ok(ContextPack {
+        payload,
+        files,
+        skipped,
+        total_tokens: total,
+        budget_tokens: budget,
+    })
+}
@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Emitter;
+use uuid::Uuid;
+
+use crate::llm::StreamEvent;
+use crate::AppState;
+
+/// Rough chars-per-token ratio for the serialized request
+const CHARS_PER_TOKEN: usize = 4;
+/// Share of a budget at which `budget_warning` is emitted
+const WARNING_RATIO: f64 = 0.8;
+/// Days of daily totals kept in the usage file
+const KEEP_DAYS: i64 = 31;
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ModelPrice {
+    /// USD per million prompt tokens
+    pub input_per_mtok: f64,
+    /// USD per million completion tokens
+    pub output_per_mtok: f64,
+}
+
+/// Ask before sending a request whose estimated prompt cost is above
+/// `threshold_usd`. Models without a price are never held back.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CostGuardSettings {
+    pub threshold_usd: Option<f64>,
+    /// Keyed by model id
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+pub struct CostEstimate {
+    pub tokens: usize,
+    pub cost_usd: f64,
+    pub threshold_usd: f64,
+}
+
+/// Estimate of `request`'s prompt cost when it exceeds the threshold.
+pub fn over_threshold(
+    settings: &CostGuardSettings,
+    model: &str,
+    request: &serde_json::Value,
+) -> Option<CostEstimate> {
+    let threshold_usd = settings.threshold_usd.filter(|t| *t > 0.0)?;
+    let price = settings.prices.get(model)?;
+    let chars = request["messages"].to_string().len() + request["tools"].to_string().len();
+    let tokens = chars.div_ceil(CHARS_PER_TOKEN);
+    let cost_usd = tokens as f64 * price.input_per_mtok / 1_000_000.0;
+    (cost_usd > threshold_usd).then_some(CostEstimate {
+        tokens,
+        cost_usd,
+        threshold_usd,
+    })
+}
+
+/// Emit `event` with a fresh `request_id` added to `data` and wait for
+/// `cost_confirmation_respond`. Cancelling the turn counts as a refusal.
+async fn ask(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    event: &str,
+    mut data: serde_json::Value,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<bool, String> {
+    let request_id = format!("{}:cost:{}", session_id, Uuid::new_v4());
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state
+        .cost_confirmations
+        .lock()
+        .map_err(|_| "Cost confirmation store poisoned".to_string())?
+        .insert(request_id.clone(), tx);
+
+    data["session_id"] = serde_json::json!(session_id);
+    data["request_id"] = serde_json::json!(request_id);
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: event.to_string(),
+            data,
+        },
+    );
+
+    tokio::select! {
+        _ = cancel_rx => {
+            state
+                .cost_confirmations
+                .lock()
+                .map_err(|_| "Cost confirmation store poisoned".to_string())?
+                .remove(&request_id);
+            Ok(false)
+        }
+        result = rx => Ok(result.unwrap_or(false)),
+    }
+}
+
+/// Ask whether to send a request above the cost threshold.
+pub async fn confirm(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    model: &str,
+    estimate: &CostEstimate,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<bool, String> {
+    let data = serde_json::json!({
+        "model": model,
+        "estimated_tokens": estimate.tokens,
+        "estimated_cost_usd": estimate.cost_usd,
+        "threshold_usd": estimate.threshold_usd,
+    });
+    ask(
+        window,
+        state,
+        session_id,
+        "cost_confirmation",
+        data,
+        cancel_rx,
+    )
+    .await
+}
+
+/// Token and cost limits for one session and for all sessions in a day.
+/// `budget_warning` is emitted when 80% of a limit is used, and past a limit
+/// no further request is sent until the user overrides it for the turn.
+/// Costs need the model's prices from the cost confirmation settings.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BudgetSettings {
+    pub session_tokens: Option<u64>,
+    pub session_usd: Option<f64>,
+    pub daily_tokens: Option<u64>,
+    pub daily_usd: Option<f64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Usage {
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Usage {
+    fn add(&mut self, tokens: u64, cost_usd: f64) {
+        self.tokens += tokens;
+        self.cost_usd += cost_usd;
+    }
+}
+
+/// What has been spent in a session and today, across sessions.
+#[derive(Clone, Copy, Serialize, Default)]
+pub struct Spend {
+    pub session: Usage,
+    pub today: Usage,
+}
+
+#[derive(Serialize)]
+pub struct BudgetLimit {
+    /// `session` or `day`
+    pub scope: &'static str,
+    /// `tokens` or `usd`
+    pub unit: &'static str,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl BudgetSettings {
+    fn limits(&self, spend: &Spend) -> Vec<BudgetLimit> {
+        let limits = [
+            (
+                "session",
+                "tokens",
+                spend.session.tokens as f64,
+                self.session_tokens.map(|t| t as f64),
+            ),
+            ("session", "usd", spend.session.cost_usd, self.session_usd),
+            (
+                "day",
+                "tokens",
+                spend.today.tokens as f64,
+                self.daily_tokens.map(|t| t as f64),
+            ),
+            ("day", "usd", spend.today.cost_usd, self.daily_usd),
+        ];
+        limits
+            .into_iter()
+            .filter_map(|(scope, unit, used, limit)| {
+                let limit = limit.filter(|limit| *limit > 0.0)?;
+                Some(BudgetLimit {
+                    scope,
+                    unit,
+                    used,
+                    limit,
+                })
+            })
+            .collect()
+    }
+
+    /// The first limit `spend` has reached.
+    pub fn exceeded(&self, spend: &Spend) -> Option<BudgetLimit> {
+        self.limits(spend)
+            .into_iter()
+            .find(|limit| limit.used >= limit.limit)
+    }
+
+    /// Limits whose warning share was crossed going from `before` to `after`.
+    fn crossed(&self, before: &Spend, after: &Spend) -> Vec<BudgetLimit> {
+        self.limits(before)
+            .into_iter()
+            .zip(self.limits(after))
+            .filter(|(before, _)| before.used < before.limit * WARNING_RATIO)
+            .filter(|(_, after)| after.used >= after.limit * WARNING_RATIO)
+            .map(|(_, after)| after)
+            .collect()
+    }
+}
+
+/// Running totals, so budgets hold across restarts and concurrent sessions
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Ledger {
+    /// Keyed by local date, `YYYY-MM-DD`
+    days: BTreeMap<String, Usage>,
+    sessions: HashMap<String, Usage>,
+}
+
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+fn ledger_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_usage.json")
+}
+
+fn load_ledger() -> Ledger {
+    crate::read_text(&ledger_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn spend_in(ledger: &Ledger, session_id: Option<&str>) -> Spend {
+    Spend {
+        session: session_id
+            .and_then(|id| ledger.sessions.get(id))
+            .copied()
+            .unwrap_or_default(),
+        today: ledger.days.get(&today()).copied().unwrap_or_default(),
+    }
+}
+
+pub fn spent(session_id: &str) -> Spend {
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    spend_in(&load_ledger(), Some(session_id))
+}
+
+/// Model prices from the saved settings, for requests made outside a chat turn.
+pub fn prices() -> HashMap<String, ModelPrice> {
+    crate::gui_settings_load(None)
+        .ok()
+        .and_then(|payload| payload.settings.cost_guard)
+        .unwrap_or_default()
+        .prices
+}
+
+/// Add one model request to the totals and return them before and after.
+/// Providers that report no usage are estimated from the request and reply
+/// sizes. Requests that belong to no session, such as benchmarks, count
+/// towards the day only.
+pub fn record(
+    session_id: Option<&str>,
+    model: &str,
+    prices: &HashMap<String, ModelPrice>,
+    usage: Option<&serde_json::Value>,
+    request: &serde_json::Value,
+    reply_chars: usize,
+) -> (Spend, Spend) {
+    let reported = |key: &str| {
+        usage
+            .and_then(|usage| usage.get(key))
+            .and_then(|v| v.as_u64())
+    };
+    let prompt_tokens = reported("prompt_tokens").unwrap_or_else(|| {
+        let chars = request["messages"].to_string().len()
+            + request["tools"].to_string().len()
+            + request["input"].to_string().len();
+        chars.div_ceil(CHARS_PER_TOKEN) as u64
+    });
+    let completion_tokens = reported("completion_tokens")
+        .unwrap_or_else(|| reply_chars.div_ceil(CHARS_PER_TOKEN) as u64);
+    let cost_usd = prices.get(model).map_or(0.0, |price| {
+        (prompt_tokens as f64 * price.input_per_mtok
+            + completion_tokens as f64 * price.output_per_mtok)
+            / 1_000_000.0
+    });
+    let tokens = prompt_tokens + completion_tokens;
+
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut ledger = load_ledger();
+    let before = spend_in(&ledger, session_id);
+    if let Some(session_id) = session_id {
+        ledger
+            .sessions
+            .entry(session_id.to_string())
+            .or_default()
+            .add(tokens, cost_usd);
+    }
+    ledger
+        .days
+        .entry(today())
+        .or_default()
+        .add(tokens, cost_usd);
+    let cutoff = (chrono::Local::now() - chrono::Duration::days(KEEP_DAYS))
+        .format("%Y-%m-%d")
+        .to_string();
+    ledger.days.retain(|day, _| *day >= cutoff);
+    let after = spend_in(&ledger, session_id);
+    if let Ok(raw) = serde_json::to_string(&ledger) {
+        let _ = crate::write_text(&ledger_path(), &raw);
+    }
+    (before, after)
+}
+
+/// Drop a deleted session's totals; the day's totals keep what it spent.
+pub fn forget_session(session_id: &str) {
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut ledger = load_ledger();
+    if ledger.sessions.remove(session_id).is_some() {
+        if let Ok(raw) = serde_json::to_string(&ledger) {
+            let _ = crate::write_text(&ledger_path(), &raw);
+        }
+    }
+}
+
+/// Emit `budget_warning` for each limit the last request took past 80%.
+pub fn warn(
+    window: &tauri::Window,
+    session_id: &str,
+    budget: &BudgetSettings,
+    before: &Spend,
+    after: &Spend,
+) {
+    for limit in budget.crossed(before, after) {
+        let _ = window.emit(
+            "chat://event",
+            StreamEvent {
+                event: "budget_warning".to_string(),
+                data: serde_json::json!({
+                    "session_id": session_id,
+                    "scope": limit.scope,
+                    "unit": limit.unit,
+                    "used": limit.used,
+                    "limit": limit.limit,
+                }),
+            },
+        );
+    }
+}
+
+/// Ask whether to go past a reached budget for the rest of the turn.
+pub async fn confirm_over_budget(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    limit: &BudgetLimit,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<bool, String> {
+    let data = serde_json::json!({
+        "scope": limit.scope,
+        "unit": limit.unit,
+        "used": limit.used,
+        "limit": limit.limit,
+    });
+    ask(
+        window,
+        state,
+        session_id,
+        "budget_exceeded",
+        data,
+        cancel_rx,
+    )
+    .await
+}
+
+/// Tokens and cost spent in `session_id` and today, for the settings panel.
+#[tauri::command]
+pub fn cost_usage(session_id: Option<String>) -> Spend {
+    spent(session_id.as_deref().unwrap_or(""))
+}
+
+#[tauri::command]
+pub fn cost_confirmation_respond(
+    state: tauri::State<'_, AppState>,
+    request_id: String,
+    approved: bool,
+) -> Result<(), String> {
+    let sender = state
+        .cost_confirmations
+        .lock()
+        .map_err(|_| "Cost confirmation store poisoned".to_string())?
+        .remove(&request_id)
+        .ok_or_else(|| "Cost confirmation not found".to_string())?;
+    let _ = sender.send(approved);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::http::{AuthScheme, Credentials};
+
+/// Name that always means the chat login from the top of `gui_auth.json`
+pub const DEFAULT_CREDENTIAL: &str = "default";
+
+/// Extra credential kept in the auth store under a name, so services such as
+/// search or embeddings can use a different account than chat. Config entries
+/// refer to it with `credential = "<name>"`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedCredential {
+    /// "oauth" (the Kimi login) or "api_key"
+    pub mode: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub api_base: Option<String>,
+    #[serde(default)]
+    pub auth_scheme: Option<AuthScheme>,
+}
+
+/// What the settings page shows; the key never leaves the backend.
+#[derive(Clone, Serialize)]
+pub struct CredentialInfo {
+    pub name: String,
+    pub mode: String,
+    pub api_base: Option<String>,
+    pub has_key: bool,
+}
+
+fn auth_config_for(name: &str) -> Result<crate::AuthConfig, String> {
+    let auth_config = crate::load_auth_config();
+    if name.is_empty() || name == DEFAULT_CREDENTIAL {
+        return Ok(auth_config);
+    }
+    let credential = auth_config
+        .credentials
+        .get(name)
+        .ok_or_else(|| format!("Unknown credential: {}", name))?;
+    Ok(crate::AuthConfig {
+        mode: credential.mode.clone(),
+        api_key: credential.api_key.clone(),
+        api_base: credential.api_base.clone(),
+        auth_scheme: credential.auth_scheme.clone(),
+        endpoint: None,
+        ..crate::AuthConfig::default()
+    })
+}
+
+/// Token, base and scheme of the credential called `name`.
+pub async fn resolve(name: &str) -> Result<Credentials, String> {
+    crate::llm::resolve_credentials(&auth_config_for(name)?).await
+}
+
+/// Whether `name` signs requests with the Kimi OAuth login.
+pub fn is_oauth(name: &str) -> bool {
+    auth_config_for(name).is_ok_and(|config| config.mode != "api_key")
+}
+
+#[tauri::command]
+pub fn auth_credentials_list() -> Vec<CredentialInfo> {
+    let mut list: Vec<CredentialInfo> = crate::load_auth_config()
+        .credentials
+        .into_iter()
+        .map(|(name, credential)| CredentialInfo {
+            name,
+            mode: credential.mode,
+            api_base: credential.api_base,
+            has_key: credential.api_key.is_some_and(|key| !key.is_empty()),
+        })
+        .collect();
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    list
+}
+
+/// Add or replace a named credential. An api_key credential saved without a
+/// key keeps the one it had.
+#[tauri::command]
+pub fn auth_credential_set(name: String, mut credential: NamedCredential) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name == DEFAULT_CREDENTIAL {
+        return Err(format!("Credential name cannot be empty or \"{}\"", DEFAULT_CREDENTIAL));
+    }
+    if !matches!(credential.mode.as_str(), "oauth" | "api_key") {
+        return Err(format!("Unknown credential mode: {}", credential.mode));
+    }
+    let mut auth_config = crate::load_auth_config();
+    credential.api_key = credential.api_key.filter(|key| !key.is_empty()).or_else(|| {
+        auth_config
+            .credentials
+            .get(&name)
+            .and_then(|existing| existing.api_key.clone())
+    });
+    credential.api_base = credential.api_base.filter(|base| !base.is_empty());
+    auth_config.credentials.insert(name, credential);
+    crate::save_auth_config(&auth_config)
+}
+
+#[tauri::command]
+pub fn auth_credential_delete(name: String) -> Result<(), String> {
+    let mut auth_config = crate::load_auth_config();
+    if auth_config.credentials.remove(&name).is_none() {
+        return Err("Credential not found".to_string());
+    }
+    crate::save_auth_config(&auth_config)
+}
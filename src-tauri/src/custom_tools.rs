@@ -0,0 +1,186 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::hooks;
+use crate::tools::{self, ToolOutput};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+/// Function names providers accept
+const MAX_NAME_CHARS: usize = 64;
+/// Stderr quoted in the summary of a failed call
+const MAX_STDERR_CHARS: usize = 2000;
+
+/// A tool declared in the config's `custom_tools` table and run as a shell
+/// command, e.g.
+///
+/// ```toml
+/// [custom_tools.ticket]
+/// description = "Look up an issue in the tracker"
+/// command = "./scripts/ticket.sh {id}"
+/// parameters = { type = "object", properties = { id = { type = "string" } }, required = ["id"] }
+/// ```
+///
+/// The arguments arrive as JSON on stdin; `{name}` in the command is replaced
+/// with the shell-quoted argument of that name. Stdout is the tool result.
+#[derive(Clone, Deserialize)]
+pub struct CustomTool {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub command: String,
+    /// JSON schema of the arguments; no arguments when unset
+    #[serde(default = "empty_schema")]
+    pub parameters: serde_json::Value,
+    pub timeout_secs: Option<u64>,
+}
+
+fn empty_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_CHARS
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+/// Custom tools in the config. Entries that are malformed, badly named or
+/// clash with a built-in tool are left out.
+pub fn load(config_path: Option<&str>) -> Vec<CustomTool> {
+    let Ok(config) = tools::load_config_value(config_path) else {
+        return Vec::new();
+    };
+    let builtin = tools::builtin_tool_names();
+    config["custom_tools"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| valid_name(name) && !builtin.contains(name))
+        .filter_map(|(name, value)| {
+            let mut tool = serde_json::from_value::<CustomTool>(value.clone()).ok()?;
+            tool.name = name.clone();
+            (!tool.command.trim().is_empty()).then_some(tool)
+        })
+        .collect()
+}
+
+pub fn find(config_path: Option<&str>, name: &str) -> Option<CustomTool> {
+    load(config_path).into_iter().find(|tool| tool.name == name)
+}
+
+/// Function definitions for the model, in the shape of the built-in ones.
+pub fn definitions(config_path: Option<&str>) -> Vec<serde_json::Value> {
+    load(config_path)
+        .into_iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                }
+            })
+        })
+        .collect()
+}
+
+fn quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// The command with each `{name}` placeholder replaced by that argument, in
+/// one pass over the template so a value is never expanded again. Strings are
+/// inserted as they are and other values as JSON, both quoted.
+pub fn render(template: &str, args: &serde_json::Value) -> String {
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        command.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let value = after
+            .find('}')
+            .and_then(|close| Some((close, args.get(&after[..close])?)));
+        match value {
+            Some((close, value)) => {
+                let text = match value {
+                    serde_json::Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                command.push_str(&quote(&text));
+                rest = &after[close + 1..];
+            }
+            None => {
+                command.push('{');
+                rest = after;
+            }
+        }
+    }
+    command.push_str(rest);
+    command
+}
+
+pub async fn run(
+    tool: &CustomTool,
+    work_dir: &str,
+    args: &serde_json::Value,
+    env: &HashMap<String, String>,
+    dry_run: bool,
+) -> ToolOutput {
+    let command = render(&tool.command, args);
+    if dry_run {
+        return ToolOutput {
+            ok: true,
+            summary: format!("Dry run: {} was not run.", tool.name),
+            output: command,
+        };
+    }
+    let timeout = Duration::from_secs(tool.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let output = match hooks::run_command_with_env(
+        &command,
+        work_dir,
+        env,
+        &args.to_string(),
+        timeout,
+    )
+    .await
+    {
+        Ok(output) => output,
+        Err(err) => {
+            return ToolOutput {
+                ok: false,
+                summary: err,
+                output: String::new(),
+            }
+        }
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (text, truncated) = tools::truncate_output(stdout.trim_end());
+    let suffix = if truncated { " (output truncated)" } else { "" };
+    if output.status.success() {
+        return ToolOutput {
+            ok: true,
+            summary: format!("{} succeeded{}", tool.name, suffix),
+            output: text,
+        };
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = crate::truncate_with_ellipsis(stderr.trim(), MAX_STDERR_CHARS);
+    ToolOutput {
+        ok: false,
+        summary: if stderr.is_empty() {
+            format!("{} exited with {}", tool.name, output.status)
+        } else {
+            format!("{} exited with {}: {}", tool.name, output.status, stderr)
+        },
+        output: text,
+    }
+}
@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::claims;
+use crate::hooks;
+use crate::tools::ToolOutput;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+/// Errors attached to a write tool's output; the problems panel shows all
+const MAX_ATTACHED: usize = 20;
+
+/// A command whose output lists problems as `path:line:col: message` or
+/// `path(line,col): message`, e.g. `cargo check --message-format=short`,
+/// `tsc --noEmit --pretty false` or `ruff check --output-format=concise`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checker {
+    #[serde(default)]
+    pub name: String,
+    pub command: String,
+    pub timeout_secs: Option<u64>,
+}
+
+/// Checkers for the problems panel, kept in the user's settings rather than
+/// the project so a cloned repository can't name commands to run. Without
+/// any, checkers are detected from the project's manifests.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DiagnosticsSettings {
+    /// Run the checkers after every successful write tool; off by default
+    pub after_write: bool,
+    pub checkers: Vec<Checker>,
+}
+
+static SETTINGS: RwLock<Option<DiagnosticsSettings>> = RwLock::new(None);
+
+pub fn set_settings(settings: DiagnosticsSettings) {
+    *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = Some(settings);
+}
+
+fn settings() -> DiagnosticsSettings {
+    SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+fn checker(name: &str, command: &str) -> Checker {
+    Checker {
+        name: name.to_string(),
+        command: command.to_string(),
+        timeout_secs: None,
+    }
+}
+
+fn detect(root: &Path) -> Vec<Checker> {
+    let mut checkers = Vec::new();
+    if root.join("Cargo.toml").is_file() {
+        checkers.push(checker("cargo", "cargo check --message-format=short"));
+    }
+    if root.join("tsconfig.json").is_file() {
+        checkers.push(checker(
+            "tsc",
+            "npx --no-install tsc --noEmit --pretty false",
+        ));
+    }
+    let ruff_configured = ["ruff.toml", ".ruff.toml"]
+        .iter()
+        .any(|file| root.join(file).is_file())
+        || std::fs::read_to_string(root.join("pyproject.toml"))
+            .is_ok_and(|pyproject| pyproject.contains("[tool.ruff"));
+    if ruff_configured {
+        checkers.push(checker("ruff", "ruff check --output-format=concise"));
+    }
+    checkers
+}
+
+fn checkers(work_dir: &str) -> Vec<Checker> {
+    let configured: Vec<Checker> = settings()
+        .checkers
+        .into_iter()
+        .filter(|checker| !checker.command.trim().is_empty())
+        .enumerate()
+        .map(|(i, checker)| Checker {
+            name: if checker.name.trim().is_empty() {
+                format!("checker {}", i + 1)
+            } else {
+                checker.name
+            },
+            ..checker
+        })
+        .collect();
+    if configured.is_empty() {
+        detect(Path::new(work_dir))
+    } else {
+        configured
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Diagnostic {
+    pub checker: String,
+    /// As the checker printed it, relative to the work dir when inside it
+    pub path: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    /// `error` or `warning`
+    pub severity: &'static str,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CheckerRun {
+    pub name: String,
+    pub command: String,
+    pub ok: bool,
+    /// Why the checker couldn't run, e.g. a timeout or missing program
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+    pub checkers: Vec<CheckerRun>,
+}
+
+impl Report {
+    fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|d| d.severity == "error")
+    }
+}
+
+fn digits(text: &str) -> Option<(u32, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+/// `path(line,col): message`, as tsc prints without `--pretty`.
+fn parse_parenthesized(line: &str) -> Option<(&str, u32, Option<u32>, &str)> {
+    let (head, message) = line.split_once("): ")?;
+    let open = head.rfind('(')?;
+    let (row, rest) = digits(&head[open + 1..])?;
+    let column = rest.strip_prefix(',').and_then(digits).map(|(col, _)| col);
+    Some((&head[..open], row, column, message))
+}
+
+/// `path:line:col: message` or `path:line: message`. The first colon that is
+/// followed by a line number ends the path, so Windows drive letters survive.
+fn parse_colon(line: &str) -> Option<(&str, u32, Option<u32>, &str)> {
+    line.match_indices(':').find_map(|(at, _)| {
+        let (row, rest) = digits(&line[at + 1..])?;
+        let rest = rest.strip_prefix(':')?;
+        let (column, message) = match digits(rest) {
+            Some((col, tail)) => (Some(col), tail.strip_prefix(':')?),
+            None => (None, rest),
+        };
+        (at > 0).then_some((&line[..at], row, column, message))
+    })
+}
+
+fn parse_line(checker: &str, work_dir: &Path, line: &str) -> Option<Diagnostic> {
+    let line = line.trim_end();
+    let (path, row, column, message) = parse_parenthesized(line).or_else(|| parse_colon(line))?;
+    let path = path.trim();
+    let message = message.trim();
+    if path.is_empty() || path.contains(' ') || message.is_empty() {
+        return None;
+    }
+    let lower = message.to_lowercase();
+    if lower.starts_with("note") || lower.starts_with("help") {
+        return None;
+    }
+    let path = Path::new(path)
+        .strip_prefix(work_dir)
+        .map(|relative| relative.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string());
+    Some(Diagnostic {
+        checker: checker.to_string(),
+        path,
+        line: row,
+        column,
+        severity: if lower.starts_with("warning") {
+            "warning"
+        } else {
+            "error"
+        },
+        message: message.to_string(),
+    })
+}
+
+async fn run_checker(
+    checker: &Checker,
+    work_dir: &str,
+    env: &HashMap<String, String>,
+    report: &mut Report,
+) {
+    let timeout = Duration::from_secs(checker.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let result = hooks::run_command_with_env(&checker.command, work_dir, env, "", timeout).await;
+    let mut run = CheckerRun {
+        name: checker.name.clone(),
+        command: checker.command.clone(),
+        ok: false,
+        error: None,
+    };
+    match result {
+        Ok(output) => {
+            run.ok = output.status.success();
+            // cargo writes to stderr, tsc and ruff to stdout
+            let text = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let root = Path::new(work_dir);
+            let before = report.diagnostics.len();
+            report.diagnostics.extend(
+                text.lines()
+                    .filter_map(|line| parse_line(&checker.name, root, line)),
+            );
+            // Exiting non-zero with nothing we could parse is worth surfacing
+            if !run.ok && report.diagnostics.len() == before {
+                let tail: Vec<&str> = text.trim().lines().rev().take(5).collect();
+                run.error = Some(format!(
+                    "exited with {}: {}",
+                    output.status,
+                    tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+                ));
+            }
+        }
+        Err(err) => run.error = Some(err),
+    }
+    report.checkers.push(run);
+}
+
+async fn run_all(checkers: &[Checker], work_dir: &str, env: &HashMap<String, String>) -> Report {
+    let mut report = Report::default();
+    for checker in checkers {
+        run_checker(checker, work_dir, env, &mut report).await;
+    }
+    report
+}
+
+/// Checker results after a write tool ran, or None unless the user turned on
+/// checking after writes.
+pub async fn after_write(work_dir: &str, env: &HashMap<String, String>) -> Option<Report> {
+    if !settings().after_write {
+        return None;
+    }
+    let checkers = checkers(work_dir);
+    if checkers.is_empty() {
+        return None;
+    }
+    Some(run_all(&checkers, work_dir, env).await)
+}
+
+/// Add the report's errors to a write tool's output, those in `edited` files
+/// first, so the model sees what its change broke.
+pub fn attach(output: &mut ToolOutput, report: &Report, work_dir: &str, edited: &[PathBuf]) {
+    let root = Path::new(work_dir);
+    let in_edited = |d: &Diagnostic| edited.contains(&claims::normalize(&root.join(&d.path)));
+    let mut errors: Vec<&Diagnostic> = report.errors().collect();
+    if errors.is_empty() {
+        output.summary.push_str(" No errors reported by checkers.");
+        return;
+    }
+    errors.sort_by_key(|d| !in_edited(d));
+    let total = errors.len();
+    output.summary.push_str(&format!(
+        " Checkers reported {} error(s), {} in the edited file(s).",
+        total,
+        errors.iter().filter(|d| in_edited(d)).count()
+    ));
+    output
+        .output
+        .push_str("\n\nDiagnostics after this change:\n");
+    for d in errors.into_iter().take(MAX_ATTACHED) {
+        let column = d.column.map(|col| format!(":{}", col)).unwrap_or_default();
+        output.output.push_str(&format!(
+            "{}:{}{}: [{}] {}\n",
+            d.path, d.line, column, d.checker, d.message
+        ));
+    }
+    if total > MAX_ATTACHED {
+        output
+            .output
+            .push_str(&format!("... {} more\n", total - MAX_ATTACHED));
+    }
+}
+
+/// Run the project's checkers for the problems panel.
+#[tauri::command]
+pub async fn diagnostics_run(
+    work_dir: String,
+    env: Option<HashMap<String, String>>,
+) -> Result<Report, String> {
+    let checkers = checkers(&work_dir);
+    if checkers.is_empty() {
+        return Err(
+            "No checkers found. Add them in settings, or add a Cargo.toml, tsconfig.json or ruff config."
+                .to_string(),
+        );
+    }
+    Ok(run_all(&checkers, &work_dir, &env.unwrap_or_default()).await)
+}
@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::context;
+use crate::tools::ToolOutput;
+
+const DOC_GLOBS: &[&str] = &["*.md", "*.mdx", "*.markdown", "*.txt", "*.rst", "*.adoc", "*.html", "*.htm"];
+const MAX_CHUNK_CHARS: usize = 1_500;
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+// BM25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Documentation folder or page registered for a work dir.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DocsSource {
+    pub id: String,
+    pub work_dir: String,
+    /// "dir" | "url"
+    pub kind: String,
+    pub location: String,
+    #[serde(default)]
+    pub chunks: usize,
+    #[serde(default)]
+    pub indexed_at: Option<i64>,
+    /// Why the last indexing failed
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DocsStore {
+    #[serde(default)]
+    sources: Vec<DocsSource>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct DocChunk {
+    /// File path relative to the docs folder, or the page URL
+    title: String,
+    heading: Option<String>,
+    text: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DocsHit {
+    pub source: String,
+    pub title: String,
+    pub heading: Option<String>,
+    pub score: f64,
+    pub text: String,
+}
+
+fn store_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_docs.json")
+}
+
+fn chunks_path(id: &str) -> PathBuf {
+    crate::kimi_share_dir().join("gui_docs").join(format!("{}.json", id))
+}
+
+fn load_store() -> Result<DocsStore, String> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(DocsStore::default());
+    }
+    let raw = crate::read_text(&path)?;
+    serde_json::from_str(&raw).map_err(|error| format!("Invalid docs file: {error}"))
+}
+
+fn save_store(store: &DocsStore) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(store).map_err(|error| error.to_string())?;
+    crate::write_text(&store_path(), &raw)
+}
+
+fn load_chunks(id: &str) -> Vec<DocChunk> {
+    crate::read_text(&chunks_path(id))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn sources_for(store: &DocsStore, work_dir: &str) -> Vec<DocsSource> {
+    store
+        .sources
+        .iter()
+        .filter(|source| crate::same_path(&source.work_dir, work_dir))
+        .cloned()
+        .collect()
+}
+
+pub fn has_sources(work_dir: &str) -> bool {
+    load_store()
+        .map(|store| !sources_for(&store, work_dir).is_empty())
+        .unwrap_or(false)
+}
+
+/// Crude HTML to text: drops scripts, styles and tags and decodes common entities.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len() / 2);
+    let lower = html.to_ascii_lowercase();
+    let mut i = 0;
+    while i < html.len() {
+        if lower[i..].starts_with("<script") || lower[i..].starts_with("<style") {
+            let close = if lower[i..].starts_with("<script") { "</script>" } else { "</style>" };
+            i = lower[i..].find(close).map(|end| i + end + close.len()).unwrap_or(html.len());
+            continue;
+        }
+        if html[i..].starts_with('<') {
+            let end = html[i..].find('>').map(|end| i + end + 1).unwrap_or(html.len());
+            let tag = &lower[i..end];
+            // Keep block structure so chunking by paragraph still works
+            if ["<p", "<br", "<div", "<li", "<h1", "<h2", "<h3", "<h4", "<tr", "<pre"]
+                .iter()
+                .any(|block| tag.starts_with(block))
+            {
+                text.push('\n');
+            }
+            if let Some(level) = ["<h1", "<h2", "<h3", "<h4"].iter().position(|h| tag.starts_with(h)) {
+                text.push_str(&"#".repeat(level + 1));
+                text.push(' ');
+            }
+            i = end;
+            continue;
+        }
+        let ch = html[i..].chars().next().unwrap_or(' ');
+        text.push(ch);
+        i += ch.len_utf8();
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Split a document at markdown headings, then at blank lines when a section is
+/// longer than a chunk.
+fn chunk_document(title: &str, content: &str) -> Vec<DocChunk> {
+    let mut chunks = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut current = String::new();
+    let flush = |heading: &Option<String>, current: &mut String, chunks: &mut Vec<DocChunk>| {
+        let text = current.trim();
+        if !text.is_empty() {
+            chunks.push(DocChunk {
+                title: title.to_string(),
+                heading: heading.clone(),
+                text: text.to_string(),
+            });
+        }
+        current.clear();
+    };
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') {
+            flush(&heading, &mut current, &mut chunks);
+            heading = Some(trimmed.trim_start_matches('#').trim().to_string());
+            continue;
+        }
+        if trimmed.is_empty() && current.chars().count() >= MAX_CHUNK_CHARS {
+            flush(&heading, &mut current, &mut chunks);
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    flush(&heading, &mut current, &mut chunks);
+    for chunk in &mut chunks {
+        chunk.text = crate::truncate_with_ellipsis(&chunk.text, MAX_CHUNK_CHARS * 2);
+    }
+    chunks
+}
+
+fn index_dir(location: &str) -> Result<Vec<DocChunk>, String> {
+    let root = Path::new(location);
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", location));
+    }
+    let globs: Vec<String> = DOC_GLOBS.iter().map(|glob| glob.to_string()).collect();
+    let mut chunks = Vec::new();
+    for file in context::project_files(root, &globs)? {
+        let Some(content) = context::read_text_file(&file.path) else {
+            continue;
+        };
+        let is_html = file.rel.ends_with(".html") || file.rel.ends_with(".htm");
+        let content = if is_html { html_to_text(&content) } else { content };
+        chunks.extend(chunk_document(&file.rel, &content));
+    }
+    Ok(chunks)
+}
+
+async fn index_url(url: &str) -> Result<Vec<DocChunk>, String> {
+    crate::network::ensure_online(url)?;
+    let response = crate::http::plain_client()?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Fetching {} failed with status {}", url, response.status()));
+    }
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("html"));
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    let content = if is_html { html_to_text(&body) } else { body };
+    Ok(chunk_document(url, &content))
+}
+
+/// Re-chunk a source and record the outcome on it.
+async fn index_source(source: &mut DocsSource) {
+    let result = if source.kind == "url" {
+        index_url(&source.location).await
+    } else {
+        index_dir(&source.location)
+    };
+    let result = result.and_then(|chunks| {
+        let raw = serde_json::to_string(&chunks).map_err(|e| e.to_string())?;
+        crate::write_text(&chunks_path(&source.id), &raw)?;
+        Ok(chunks.len())
+    });
+    match result {
+        Ok(count) => {
+            source.chunks = count;
+            source.indexed_at = Some(chrono::Utc::now().timestamp());
+            source.error = None;
+        }
+        Err(error) => source.error = Some(error),
+    }
+}
+
+fn update_source(source: &DocsSource) -> Result<(), String> {
+    let mut store = load_store()?;
+    if let Some(existing) = store.sources.iter_mut().find(|s| s.id == source.id) {
+        *existing = source.clone();
+        save_store(&store)?;
+    }
+    Ok(())
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Rank chunks from every source of `work_dir` against `query` with BM25.
+fn search(work_dir: &str, query: &str, limit: usize) -> Result<Vec<DocsHit>, String> {
+    let sources = sources_for(&load_store()?, work_dir);
+    let chunks: Vec<(String, DocChunk)> = sources
+        .iter()
+        .flat_map(|source| {
+            load_chunks(&source.id)
+                .into_iter()
+                .map(|chunk| (source.location.clone(), chunk))
+        })
+        .collect();
+    if chunks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let docs: Vec<Vec<String>> = chunks
+        .iter()
+        .map(|(_, chunk)| {
+            let heading = chunk.heading.as_deref().unwrap_or_default();
+            tokenize(&format!("{} {} {}", chunk.title, heading, chunk.text))
+        })
+        .collect();
+    let avg_len = docs.iter().map(Vec::len).sum::<usize>() as f64 / docs.len() as f64;
+    let doc_freq: HashMap<&str, usize> = terms
+        .iter()
+        .map(|term| (term.as_str(), docs.iter().filter(|doc| doc.contains(term)).count()))
+        .collect();
+    let n = docs.len() as f64;
+
+    let mut hits: Vec<DocsHit> = chunks
+        .into_iter()
+        .zip(&docs)
+        .filter_map(|((source, chunk), doc)| {
+            let len = doc.len() as f64;
+            let score: f64 = terms
+                .iter()
+                .map(|term| {
+                    let tf = doc.iter().filter(|word| *word == term).count() as f64;
+                    let df = doc_freq.get(term.as_str()).copied().unwrap_or(0) as f64;
+                    let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg_len.max(1.0)))
+                })
+                .sum();
+            (score > 0.0).then_some(DocsHit {
+                source,
+                title: chunk.title,
+                heading: chunk.heading,
+                score,
+                text: chunk.text,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit.clamp(1, MAX_LIMIT));
+    Ok(hits)
+}
+
+/// `DocsSearch` tool call.
+pub fn search_tool(work_dir: &str, query: &str, limit: usize) -> ToolOutput {
+    match search(work_dir, query, limit) {
+        Ok(hits) if hits.is_empty() => ToolOutput {
+            ok: true,
+            summary: "No matching documentation.".to_string(),
+            output: String::new(),
+        },
+        Ok(hits) => {
+            let text = hits
+                .iter()
+                .map(|hit| match &hit.heading {
+                    Some(heading) => format!("[{} > {}]\n{}", hit.title, heading, hit.text),
+                    None => format!("[{}]\n{}", hit.title, hit.text),
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let (output, truncated) = crate::tools::truncate_output(&text);
+            ToolOutput {
+                ok: true,
+                summary: crate::tools::append_truncation(
+                    format!("Found {} documentation sections.", hits.len()),
+                    truncated,
+                ),
+                output,
+            }
+        }
+        Err(err) => ToolOutput {
+            ok: false,
+            summary: err,
+            output: String::new(),
+        },
+    }
+}
+
+#[tauri::command]
+pub fn docs_sources_list(work_dir: String) -> Result<Vec<DocsSource>, String> {
+    Ok(sources_for(&load_store()?, &work_dir))
+}
+
+/// Register a docs folder or URL for `work_dir` and index it right away. A
+/// source that fails to index is still kept, with the error, so it can be retried.
+#[tauri::command]
+pub async fn docs_source_add(work_dir: String, location: String) -> Result<DocsSource, String> {
+    let location = location.trim().to_string();
+    if location.is_empty() {
+        return Err("Docs location cannot be empty".to_string());
+    }
+    let kind = if location.starts_with("http://") || location.starts_with("https://") {
+        "url"
+    } else {
+        "dir"
+    };
+    let location = if kind == "dir" && Path::new(&location).is_relative() {
+        Path::new(&work_dir).join(&location).to_string_lossy().to_string()
+    } else {
+        location
+    };
+
+    let mut source = DocsSource {
+        id: Uuid::new_v4().to_string(),
+        work_dir,
+        kind: kind.to_string(),
+        location,
+        chunks: 0,
+        indexed_at: None,
+        error: None,
+    };
+    let mut store = load_store()?;
+    store.sources.push(source.clone());
+    save_store(&store)?;
+
+    index_source(&mut source).await;
+    update_source(&source)?;
+    Ok(source)
+}
+
+#[tauri::command]
+pub async fn docs_source_reindex(id: String) -> Result<DocsSource, String> {
+    let mut source = load_store()?
+        .sources
+        .into_iter()
+        .find(|source| source.id == id)
+        .ok_or_else(|| "Docs source not found".to_string())?;
+    index_source(&mut source).await;
+    update_source(&source)?;
+    Ok(source)
+}
+
+#[tauri::command]
+pub fn docs_source_remove(id: String) -> Result<(), String> {
+    let mut store = load_store()?;
+    let before = store.sources.len();
+    store.sources.retain(|source| source.id != id);
+    if store.sources.len() == before {
+        return Err("Docs source not found".to_string());
+    }
+    save_store(&store)?;
+    let _ = std::fs::remove_file(chunks_path(&id));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn docs_search(work_dir: String, query: String, limit: Option<usize>) -> Result<Vec<DocsHit>, String> {
+    search(&work_dir, &query, limit.unwrap_or(DEFAULT_LIMIT))
+}
@@ -0,0 +1,304 @@
+use futures::StreamExt;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::io::AsyncWriteExt;
+
+use crate::paths::{self, Access};
+use crate::tools::{DownloadFileArgs, OutputSink, ToolOutput};
+
+const DEFAULT_MAX_MB: u64 = 100;
+/// Progress is reported at most this often when the size is unknown
+const PROGRESS_STEP_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Limits on what the DownloadFile tool fetches. Every download still needs
+/// the user's approval.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DownloadPolicy {
+    /// Largest file in megabytes; 100 when unset
+    #[serde(default)]
+    pub max_mb: Option<u64>,
+    /// Hosts downloads may come from, e.g. `github.com`; subdomains match.
+    /// Any host when empty
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Content types accepted, e.g. `application/zip` or `text/*`. Anything
+    /// but HTML when empty, since an HTML page is usually a login or error
+    /// page rather than the file
+    #[serde(default)]
+    pub allowed_content_types: Vec<String>,
+}
+
+static POLICY: RwLock<Option<DownloadPolicy>> = RwLock::new(None);
+
+pub fn set_policy(policy: DownloadPolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+}
+
+fn policy() -> DownloadPolicy {
+    POLICY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+impl DownloadPolicy {
+    fn max_bytes(&self) -> u64 {
+        self.max_mb.unwrap_or(DEFAULT_MAX_MB).max(1) * 1024 * 1024
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let mut domains = self
+            .allowed_domains
+            .iter()
+            .map(|d| d.trim().trim_start_matches("*.").to_lowercase())
+            .filter(|d| !d.is_empty())
+            .peekable();
+        domains.peek().is_none()
+            || domains.any(|domain| host == domain || host.ends_with(&format!(".{}", domain)))
+    }
+
+    fn check_content_type(&self, content_type: &str) -> Result<(), String> {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+        let patterns: Vec<String> = self
+            .allowed_content_types
+            .iter()
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty())
+            .collect();
+        let allowed = if patterns.is_empty() {
+            essence != "text/html" && essence != "application/xhtml+xml"
+        } else {
+            patterns
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => essence.starts_with(prefix),
+                    None => essence == *pattern,
+                })
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!(
+                "Refused to save {} content; the download settings don't allow it.",
+                if essence.is_empty() {
+                    "untyped"
+                } else {
+                    &essence
+                }
+            ))
+        }
+    }
+}
+
+fn failure(summary: String) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary,
+        output: String::new(),
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Sibling of `dest` the body is streamed into, renamed over `dest` once
+/// complete so a failed download never leaves a partial file behind.
+fn partial_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{}.download", name))
+}
+
+fn check_url(policy: &DownloadPolicy, url: &reqwest::Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Only http and https URLs can be downloaded, not {}",
+            url
+        ));
+    }
+    let host = url.host_str().unwrap_or("");
+    if !policy.allows_host(host) {
+        return Err(format!(
+            "Downloads from {} aren't allowed; add the domain in settings to permit it.",
+            host
+        ));
+    }
+    Ok(())
+}
+
+/// Stream the body into `partial`, returning its size and SHA-256.
+async fn save(
+    response: reqwest::Response,
+    partial: &Path,
+    max_bytes: u64,
+    on_progress: OutputSink<'_>,
+) -> Result<(u64, String), String> {
+    let total = response.content_length();
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut received: u64 = 0;
+    let mut reported: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Download interrupted: {}", e))?;
+        received += chunk.len() as u64;
+        if received > max_bytes {
+            return Err(format!(
+                "Download stopped at the {} limit.",
+                megabytes(max_bytes)
+            ));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        let step = total.map_or(PROGRESS_STEP_BYTES, |total| (total / 10).max(1));
+        if received - reported >= step {
+            reported = received;
+            on_progress(&match total {
+                Some(total) => format!(
+                    "Downloaded {} of {} ({}%)\n",
+                    megabytes(received),
+                    megabytes(total),
+                    received * 100 / total.max(1)
+                ),
+                None => format!("Downloaded {}\n", megabytes(received)),
+            });
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((received, hash))
+}
+
+/// The DownloadFile tool: fetch `args.url` into a file inside the work dir,
+/// within the download policy's size, domain and content type limits.
+pub async fn download_tool(
+    work_dir: &str,
+    args: &DownloadFileArgs,
+    dry_run: bool,
+    on_progress: OutputSink<'_>,
+) -> ToolOutput {
+    let policy = policy();
+    let url = match reqwest::Url::parse(args.url.trim()) {
+        Ok(url) => url,
+        Err(err) => return failure(format!("Invalid URL {}: {}", args.url, err)),
+    };
+    if let Err(err) = check_url(&policy, &url) {
+        return failure(err);
+    }
+    let dest = match paths::resolve_inside(work_dir, &args.path, Access::Write) {
+        Ok(dest) => dest,
+        Err(err) => return failure(err),
+    };
+    if dest.is_dir() {
+        return failure(format!("{} is a directory", args.path));
+    }
+    if dest.exists() && !args.overwrite.unwrap_or(false) {
+        return failure(format!(
+            "{} already exists; set overwrite to replace it.",
+            args.path
+        ));
+    }
+    if dry_run {
+        return ToolOutput {
+            ok: true,
+            summary: format!("Dry run: {} was not downloaded.", url),
+            output: format!("Would save {} to {}", url, args.path),
+        };
+    }
+    if let Err(err) = crate::network::ensure_online(url.as_str()) {
+        return failure(err);
+    }
+
+    let client = match crate::http::plain_client() {
+        Ok(client) => client,
+        Err(err) => return failure(err),
+    };
+    let response = match client.get(url.clone()).send().await {
+        Ok(response) => response,
+        Err(err) => return failure(format!("Failed to download: {}", err)),
+    };
+    // Redirects may have led somewhere the policy doesn't allow
+    if let Err(err) = check_url(&policy, response.url()) {
+        return failure(err);
+    }
+    if !response.status().is_success() {
+        return failure(format!("Download failed with status {}", response.status()));
+    }
+    let max_bytes = policy.max_bytes();
+    if let Some(length) = response.content_length().filter(|&len| len > max_bytes) {
+        return failure(format!(
+            "The file is {}, over the {} download limit.",
+            megabytes(length),
+            megabytes(max_bytes)
+        ));
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if let Err(err) = policy.check_content_type(&content_type) {
+        return failure(err);
+    }
+
+    if let Some(parent) = dest.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            return failure(format!("Failed to create {}: {}", parent.display(), err));
+        }
+    }
+    let partial = partial_path(&dest);
+    let saved = match save(response, &partial, max_bytes, on_progress).await {
+        Ok(saved) => tokio::fs::rename(&partial, &dest)
+            .await
+            .map(|_| saved)
+            .map_err(|e| format!("Failed to move the download to {}: {}", args.path, e)),
+        Err(err) => Err(err),
+    };
+    let (size, sha256) = match saved {
+        Ok(saved) => saved,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return failure(err);
+        }
+    };
+    ToolOutput {
+        ok: true,
+        summary: format!("Downloaded {} to {}.", megabytes(size), args.path),
+        output: format!(
+            "URL: {}\nPath: {}\nSize: {} bytes\nContent-Type: {}\nSHA-256: {}",
+            url,
+            args.path,
+            size,
+            if content_type.is_empty() {
+                "unknown"
+            } else {
+                &content_type
+            },
+            sha256
+        ),
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 with BOM",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+        }
+    }
+}
+
+/// How a file is stored on disk, so an edited version can be written back
+/// the same way.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Format {
+    pub encoding: Encoding,
+    /// Every line ends in CRLF; the decoded text uses `\n` and writing puts
+    /// the CRs back. Files mixing both are left as they are.
+    pub crlf: bool,
+}
+
+pub struct Decoded {
+    pub text: String,
+    pub format: Format,
+    /// Bytes that weren't valid in the encoding were replaced with U+FFFD, so
+    /// writing the text back would change them
+    pub lossy: bool,
+}
+
+fn uses_crlf(text: &str) -> bool {
+    let crlf = text.matches("\r\n").count();
+    crlf > 0 && crlf == text.matches('\n').count()
+}
+
+fn utf8(bytes: &[u8]) -> (String, bool) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), false),
+        Err(_) => (String::from_utf8_lossy(bytes).into_owned(), true),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> (String, bool) {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    let lossy = bytes.len() % 2 != 0 || String::from_utf16(&units).is_err();
+    (String::from_utf16_lossy(&units), lossy)
+}
+
+/// Text of a file's bytes, with the BOM stripped and CRLF line endings
+/// turned into `\n`. Invalid bytes are replaced rather than failing.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (encoding, (text, lossy)) = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        (Encoding::Utf8Bom, utf8(rest))
+    } else if let Some(rest) = bytes.strip_prefix(UTF16_LE_BOM) {
+        (Encoding::Utf16Le, decode_utf16(rest, u16::from_le_bytes))
+    } else if let Some(rest) = bytes.strip_prefix(UTF16_BE_BOM) {
+        (Encoding::Utf16Be, decode_utf16(rest, u16::from_be_bytes))
+    } else {
+        (Encoding::Utf8, utf8(bytes))
+    };
+    let crlf = uses_crlf(&text);
+    Decoded {
+        text: if crlf {
+            text.replace("\r\n", "\n")
+        } else {
+            text
+        },
+        format: Format { encoding, crlf },
+        lossy,
+    }
+}
+
+pub fn read(path: &Path) -> std::io::Result<Decoded> {
+    fs::read(path).map(|bytes| decode(&bytes))
+}
+
+/// Bytes of `text` stored in `format`. `\n` becomes CRLF for CRLF files;
+/// text that already has CRLF endings keeps them.
+pub fn encode(text: &str, format: Format) -> Vec<u8> {
+    let text = if format.crlf {
+        text.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        text.to_string()
+    };
+    match format.encoding {
+        Encoding::Utf8 => text.into_bytes(),
+        Encoding::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+        Encoding::Utf16Le => UTF16_LE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        Encoding::Utf16Be => UTF16_BE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+    }
+}
+
+/// Refusal for edits to a file whose text couldn't be decoded exactly.
+pub fn lossy_error(path: &str) -> String {
+    format!(
+        "{} is not valid UTF-8 or UTF-16 text; editing it would corrupt the undecodable bytes. Use Shell tools to change it, or WriteFile to replace it entirely.",
+        path
+    )
+}
@@ -0,0 +1,96 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// Known provider endpoints a profile can pin, selected by `AuthConfig.endpoint`.
+struct Endpoint {
+    id: &'static str,
+    label: &'static str,
+    base_url: &'static str,
+    /// Whether OAuth login works against this endpoint; all accept API keys
+    oauth: bool,
+}
+
+const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        id: "kimi_code",
+        label: "Kimi Code (api.kimi.com)",
+        base_url: "https://api.kimi.com/coding/v1",
+        oauth: true,
+    },
+    Endpoint {
+        id: "moonshot_cn",
+        label: "Moonshot China (api.moonshot.cn)",
+        base_url: "https://api.moonshot.cn/v1",
+        oauth: false,
+    },
+    Endpoint {
+        id: "moonshot_global",
+        label: "Moonshot International (api.moonshot.ai)",
+        base_url: "https://api.moonshot.ai/v1",
+        oauth: false,
+    },
+];
+
+#[derive(Clone, Serialize)]
+pub struct EndpointProbe {
+    pub id: String,
+    pub label: String,
+    pub base_url: String,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+pub fn endpoint_base(id: &str) -> Option<&'static str> {
+    ENDPOINTS
+        .iter()
+        .find(|endpoint| endpoint.id == id)
+        .map(|endpoint| endpoint.base_url)
+}
+
+fn endpoints_for(mode: Option<&str>) -> impl Iterator<Item = &'static Endpoint> {
+    let oauth_only = mode == Some("oauth");
+    ENDPOINTS.iter().filter(move |endpoint| !oauth_only || endpoint.oauth)
+}
+
+async fn probe(client: &reqwest::Client, endpoint: &Endpoint) -> EndpointProbe {
+    let mut result = EndpointProbe {
+        id: endpoint.id.to_string(),
+        label: endpoint.label.to_string(),
+        base_url: endpoint.base_url.to_string(),
+        reachable: false,
+        latency_ms: None,
+        error: None,
+    };
+    if let Err(err) = crate::network::ensure_online(endpoint.base_url) {
+        result.error = Some(err);
+        return result;
+    }
+
+    let started = Instant::now();
+    // Any HTTP response, including 401 without credentials, means the endpoint is reachable
+    match client
+        .get(format!("{}/models", endpoint.base_url))
+        .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .send()
+        .await
+    {
+        Ok(_) => {
+            result.reachable = true;
+            result.latency_ms = Some(started.elapsed().as_millis() as u64);
+        }
+        Err(err) => result.error = Some(format!("Unreachable: {}", err)),
+    }
+    result
+}
+
+/// Probe every endpoint usable with `mode` ("oauth" | "api_key") in parallel,
+/// so the login screen can offer the ones that actually work from here.
+#[tauri::command]
+pub async fn endpoints_probe(mode: Option<String>) -> Result<Vec<EndpointProbe>, String> {
+    let client = crate::http::plain_client()?;
+    let probes = endpoints_for(mode.as_deref()).map(|endpoint| probe(&client, endpoint));
+    Ok(futures::future::join_all(probes).await)
+}
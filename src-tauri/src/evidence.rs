@@ -0,0 +1,142 @@
+use serde::Serialize;
+
+use crate::parts::MessagePart;
+use crate::AppState;
+
+const MAX_CLAIM_CHARS: usize = 240;
+const MAX_EVIDENCE_OUTPUT_CHARS: usize = 4000;
+/// File names shorter than this are too ambiguous to match on their own
+const MIN_FILE_NAME_CHARS: usize = 4;
+
+/// A tool call made during the turn, as `(tool_call_id, name, arguments)`.
+pub type TurnCall = (String, String, serde_json::Value);
+
+#[derive(Clone, Serialize)]
+pub struct EvidenceItem {
+    pub tool_call_id: String,
+    pub tool: String,
+    pub target: String,
+    pub claim: String,
+    /// The recorded tool result, when it is still in the transcript
+    pub ok: Option<bool>,
+    pub summary: Option<String>,
+    pub output: Option<String>,
+}
+
+fn call_target(name: &str, args: &serde_json::Value) -> Option<String> {
+    let key = match name {
+        "ReadFile" | "WriteFile" | "StrReplaceFile" => "path",
+        "Shell" => "command",
+        "FetchURL" => "url",
+        "SearchWeb" => "query",
+        _ => return None,
+    };
+    args.get(key)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(str::to_string)
+}
+
+/// Strings whose appearance in the reply counts as citing the call.
+fn mentions(name: &str, target: &str) -> Vec<String> {
+    let mut mentions = vec![target.to_string()];
+    if matches!(name, "ReadFile" | "WriteFile" | "StrReplaceFile") {
+        if let Some(file_name) = std::path::Path::new(target).file_name().and_then(|n| n.to_str()) {
+            if file_name != target && file_name.chars().count() >= MIN_FILE_NAME_CHARS {
+                mentions.push(file_name.to_string());
+            }
+        }
+    }
+    mentions
+}
+
+fn sentences(content: &str) -> Vec<&str> {
+    content
+        .split_inclusive(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Link each sentence of `content` that mentions a path, command or URL used
+/// earlier in the turn to that tool call.
+pub fn link_claims(content: &str, calls: &[TurnCall]) -> Vec<MessagePart> {
+    let sentences = sentences(content);
+    let mut parts = Vec::new();
+    for (tool_call_id, name, args) in calls {
+        let Some(target) = call_target(name, args) else {
+            continue;
+        };
+        let mentions = mentions(name, &target);
+        for sentence in &sentences {
+            if mentions.iter().any(|mention| sentence.contains(mention.as_str())) {
+                parts.push(MessagePart::Evidence {
+                    tool_call_id: tool_call_id.clone(),
+                    tool: name.clone(),
+                    target: target.clone(),
+                    claim: crate::truncate_with_ellipsis(sentence, MAX_CLAIM_CHARS),
+                });
+            }
+        }
+    }
+    parts
+}
+
+/// Evidence linked to an assistant message, joined with the tool results it
+/// points at so the UI can show what the agent actually saw.
+#[tauri::command]
+pub fn message_evidence(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    message_index: usize,
+    work_dir: Option<String>,
+) -> Result<Vec<EvidenceItem>, String> {
+    let messages = {
+        let mut manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        match manager.find_session(&session_id) {
+            Some(session) => session.messages,
+            None => {
+                let wd = work_dir.ok_or_else(|| "Session not found".to_string())?;
+                manager.load_messages(&wd, &session_id)?
+            }
+        }
+    };
+    let message = messages
+        .get(message_index)
+        .ok_or_else(|| "Message index out of range".to_string())?;
+
+    let items = message
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            MessagePart::Evidence {
+                tool_call_id,
+                tool,
+                target,
+                claim,
+            } => {
+                let result = messages
+                    .iter()
+                    .find(|m| m.role == "tool" && m.tool_call_id.as_deref() == Some(tool_call_id.as_str()))
+                    .and_then(|m| serde_json::from_str::<serde_json::Value>(&m.content).ok());
+                Some(EvidenceItem {
+                    tool_call_id: tool_call_id.clone(),
+                    tool: tool.clone(),
+                    target: target.clone(),
+                    claim: claim.clone(),
+                    ok: result.as_ref().and_then(|r| r["ok"].as_bool()),
+                    summary: result.as_ref().and_then(|r| r["summary"].as_str()).map(str::to_string),
+                    output: result.as_ref().and_then(|r| r["output"].as_str()).map(|output| {
+                        crate::truncate_with_ellipsis(output, MAX_EVIDENCE_OUTPUT_CHARS)
+                    }),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    Ok(items)
+}
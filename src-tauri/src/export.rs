@@ -0,0 +1,183 @@
+use tauri_plugin_dialog::DialogExt;
+
+use crate::llm;
+use crate::AppState;
+
+/// Tool output kept per result in Markdown; the JSON export keeps everything
+const MAX_TOOL_OUTPUT_CHARS: usize = 4_000;
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Fence that no line of `text` can close early.
+fn fence(text: &str) -> String {
+    let longest = text
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|c| *c == '`').count())
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn fenced(language: &str, text: &str) -> String {
+    let fence = fence(text);
+    format!("{}{}\n{}\n{}\n", fence, language, text.trim_end(), fence)
+}
+
+fn tool_names(messages: &[crate::Message]) -> std::collections::HashMap<&str, &str> {
+    messages
+        .iter()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .map(|call| (call.id.as_str(), call.name.as_str()))
+        .collect()
+}
+
+pub fn render_markdown(title: &str, work_dir: &str, messages: &[crate::Message]) -> String {
+    let names = tool_names(messages);
+    let mut markdown = format!("# {}\n\n", title);
+    markdown.push_str(&format!(
+        "_Work dir: `{}` · {} messages · exported {}_\n\n",
+        work_dir,
+        messages.len(),
+        format_time(chrono::Utc::now().timestamp())
+    ));
+
+    for message in messages {
+        let time = format_time(message.timestamp);
+        match message.role.as_str() {
+            "user" => {
+                markdown.push_str(&format!("## User · {}\n\n{}\n\n", time, message.content.trim()));
+            }
+            "assistant" => {
+                markdown.push_str(&format!("## Assistant · {}\n\n", time));
+                if !message.content.trim().is_empty() {
+                    markdown.push_str(&format!("{}\n\n", message.content.trim()));
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    let args = serde_json::from_str::<serde_json::Value>(&call.arguments)
+                        .ok()
+                        .and_then(|value| serde_json::to_string_pretty(&value).ok())
+                        .unwrap_or_else(|| call.arguments.clone());
+                    markdown.push_str(&format!("**Tool call:** `{}`\n\n", call.name));
+                    markdown.push_str(&fenced("json", &args));
+                    markdown.push('\n');
+                }
+            }
+            "tool" => {
+                let name = message
+                    .tool_call_id
+                    .as_deref()
+                    .and_then(|id| names.get(id).copied())
+                    .unwrap_or("tool");
+                // Results are stored as `{ok, summary, output}`; older ones are plain text
+                let (status, summary, output) =
+                    match serde_json::from_str::<serde_json::Value>(&message.content) {
+                        Ok(result) if result.is_object() => (
+                            if result["ok"].as_bool().unwrap_or(false) { "ok" } else { "failed" },
+                            result["summary"].as_str().unwrap_or_default().to_string(),
+                            result["output"].as_str().unwrap_or_default().to_string(),
+                        ),
+                        _ => ("ok", String::new(), message.content.clone()),
+                    };
+                markdown.push_str(&format!("**Result of `{}`** ({})", name, status));
+                if !summary.is_empty() {
+                    markdown.push_str(&format!(": {}", summary.trim()));
+                }
+                markdown.push_str("\n\n");
+                if !output.trim().is_empty() {
+                    markdown.push_str(&fenced(
+                        "",
+                        &crate::truncate_with_ellipsis(&output, MAX_TOOL_OUTPUT_CHARS),
+                    ));
+                    markdown.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+    markdown
+}
+
+/// Title, work dir and messages of a GUI session, or of the CLI session
+/// `session_id` under `work_dir`.
+pub fn load_transcript(
+    state: &AppState,
+    session_id: &str,
+    work_dir: Option<String>,
+) -> Result<(String, String, Vec<crate::Message>), String> {
+    let mut manager = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+    match manager.find_session(session_id) {
+        Some(session) => Ok((session.title, session.work_dir, session.messages)),
+        None => {
+            let wd = work_dir.ok_or_else(|| "Session not found".to_string())?;
+            let messages = manager.load_messages(&wd, session_id)?;
+            Ok((session_id.to_string(), wd, messages))
+        }
+    }
+}
+
+pub fn file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .take(60)
+        .collect();
+    match name.trim_matches('-') {
+        "" => "session".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Export a session as a readable Markdown transcript (`format: "markdown"`) or
+/// an OpenAI-style message array (`format: "json"`) to a path picked in a save
+/// dialog. Returns the written path, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn session_export(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    format: String,
+    work_dir: Option<String>,
+) -> Result<Option<String>, String> {
+    let (title, session_work_dir, messages) = load_transcript(&state, &session_id, work_dir)?;
+    if messages.is_empty() {
+        return Err("Session has no messages to export".to_string());
+    }
+
+    let (contents, extension) = match format.as_str() {
+        "markdown" | "md" => (
+            render_markdown(&title, &session_work_dir, &messages),
+            "md",
+        ),
+        "json" => (
+            serde_json::to_string_pretty(&llm::history_messages(&messages))
+                .map_err(|e| e.to_string())?,
+            "json",
+        ),
+        other => return Err(format!("Unknown export format: {}", other)),
+    };
+
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_file_name(format!("{}.{}", file_name(&title), extension))
+        .add_filter(extension, &[extension])
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+    crate::write_text(&path, &contents)?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
@@ -0,0 +1,346 @@
+use ignore::WalkBuilder;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::Emitter;
+
+/// Repos with fewer entries are walked directly, which is fast enough
+const MIN_INDEXED_ENTRIES: usize = 20_000;
+/// An index older than this is rebuilt in the background while it keeps serving
+const REBUILD_AFTER: Duration = Duration::from_secs(10 * 60);
+const PROGRESS_EVERY: usize = 5_000;
+const MAGIC: &[u8; 4] = b"KFI1";
+
+/// Trigram index of a work dir's paths, stored as
+/// `magic, path count, trigram count, path offsets, trigram table, postings, paths`.
+/// Only the offsets and the trigram table are kept in memory; postings and
+/// paths are read from disk per lookup.
+struct FileIndex {
+    /// Kept open so a rebuild replacing the file doesn't shift these offsets
+    file: Mutex<File>,
+    built_at: SystemTime,
+    /// Start of each path in the path blob, plus its end
+    offsets: Vec<u32>,
+    /// (trigram, first posting, posting count), sorted by trigram
+    trigrams: Vec<(u32, u32, u32)>,
+    postings_start: u64,
+    paths_start: u64,
+}
+
+enum Entry {
+    /// Too few entries to be worth indexing, as of the given time
+    Small(SystemTime),
+    Ready(Arc<FileIndex>),
+}
+
+/// Indexes per canonical work dir, shared with the build threads.
+#[derive(Default)]
+pub struct IndexRegistry {
+    entries: Arc<Mutex<HashMap<PathBuf, Entry>>>,
+    /// Work dirs with a build running
+    building: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+fn index_file(root: &Path) -> PathBuf {
+    let hash: String = Sha256::digest(root.to_string_lossy().as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    crate::kimi_share_dir()
+        .join("gui_file_index")
+        .join(format!("{}.idx", hash))
+}
+
+fn trigrams(text: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    text.windows(3)
+        .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+}
+
+fn read_u32s(file: &mut File, count: usize) -> Result<Vec<u32>, String> {
+    let mut bytes = vec![0u8; count * 4];
+    file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn write_u32s(out: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Paths relative to `root`, sorted, the way `list_files` reports them.
+/// Directories are included so they can be mentioned too.
+fn collect_paths(window: &tauri::Window, root: &Path) -> Vec<String> {
+    let mut walker = WalkBuilder::new(root);
+    walker
+        .require_git(false)
+        .hidden(false)
+        .follow_links(crate::paths::follow_symlinks());
+    let ignores = crate::ignores::IgnoreList::load(root);
+    let canonical_root = root.to_path_buf();
+    walker.filter_entry(move |entry| {
+        !ignores.is_ignored(entry.path(), entry.file_type().is_some_and(|t| t.is_dir()))
+            && (!entry.path_is_symlink()
+                || crate::paths::link_allowed(&canonical_root, entry.path(), true))
+    });
+    let mut paths = Vec::new();
+    for entry in walker.build().flatten() {
+        let Ok(rel) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        paths.push(rel.to_string_lossy().to_string());
+        if paths.len() % PROGRESS_EVERY == 0 {
+            emit_progress(window, root, paths.len(), false, false);
+        }
+    }
+    paths.sort();
+    paths
+}
+
+fn write_index(file: &Path, paths: &[String]) -> Result<(), String> {
+    let mut postings: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut offsets = Vec::with_capacity(paths.len() + 1);
+    let mut blob = Vec::new();
+    for (id, path) in paths.iter().enumerate() {
+        offsets.push(blob.len() as u32);
+        blob.extend_from_slice(path.as_bytes());
+        let lower = path.to_lowercase();
+        let mut seen: Vec<u32> = trigrams(lower.as_bytes()).collect();
+        seen.sort_unstable();
+        seen.dedup();
+        for trigram in seen {
+            postings.entry(trigram).or_default().push(id as u32);
+        }
+    }
+    offsets.push(blob.len() as u32);
+
+    let mut keys: Vec<u32> = postings.keys().copied().collect();
+    keys.sort_unstable();
+    let mut table = Vec::with_capacity(keys.len() * 3);
+    let mut flat = Vec::new();
+    for key in &keys {
+        let ids = &postings[key];
+        table.extend_from_slice(&[*key, flat.len() as u32, ids.len() as u32]);
+        flat.extend_from_slice(ids);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    write_u32s(&mut out, &[paths.len() as u32, keys.len() as u32]);
+    write_u32s(&mut out, &offsets);
+    write_u32s(&mut out, &table);
+    write_u32s(&mut out, &flat);
+    out.extend_from_slice(&blob);
+
+    crate::ensure_parent(file)?;
+    // Write next to the old index and swap, so lookups never see a partial file
+    let partial = file.with_extension("idx.partial");
+    File::create(&partial)
+        .and_then(|mut f| f.write_all(&out))
+        .map_err(|e| format!("Failed to write file index: {}", e))?;
+    std::fs::rename(&partial, file).map_err(|e| format!("Failed to write file index: {}", e))
+}
+
+impl FileIndex {
+    fn open(file: &Path) -> Result<Self, String> {
+        let mut f = File::open(file).map_err(|e| e.to_string())?;
+        let built_at = f
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| e.to_string())?;
+        let mut magic = [0u8; 4];
+        f.read_exact(&mut magic).map_err(|e| e.to_string())?;
+        if &magic != MAGIC {
+            return Err("Unknown file index format".to_string());
+        }
+        let counts = read_u32s(&mut f, 2)?;
+        let (path_count, trigram_count) = (counts[0] as usize, counts[1] as usize);
+        let offsets = read_u32s(&mut f, path_count + 1)?;
+        let trigrams = read_u32s(&mut f, trigram_count * 3)?
+            .chunks_exact(3)
+            .map(|t| (t[0], t[1], t[2]))
+            .collect::<Vec<_>>();
+        let postings_start = f.stream_position().map_err(|e| e.to_string())?;
+        let posting_count: u64 = trigrams.iter().map(|t| t.2 as u64).sum();
+        Ok(Self {
+            file: Mutex::new(f),
+            built_at,
+            offsets,
+            trigrams,
+            postings_start,
+            paths_start: postings_start + posting_count * 4,
+        })
+    }
+
+    fn postings(&self, f: &mut File, trigram: u32) -> Result<Vec<u32>, String> {
+        let Ok(index) = self.trigrams.binary_search_by_key(&trigram, |t| t.0) else {
+            return Ok(Vec::new());
+        };
+        let (_, start, count) = self.trigrams[index];
+        f.seek(SeekFrom::Start(self.postings_start + start as u64 * 4))
+            .map_err(|e| e.to_string())?;
+        read_u32s(f, count as usize)
+    }
+
+    fn path(&self, f: &mut File, id: u32) -> Result<String, String> {
+        let (start, end) = (self.offsets[id as usize], self.offsets[id as usize + 1]);
+        f.seek(SeekFrom::Start(self.paths_start + start as u64))
+            .map_err(|e| e.to_string())?;
+        let mut bytes = vec![0u8; (end - start) as usize];
+        f.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// Up to `limit` paths containing `query`, case-insensitively, in path order.
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<String>, String> {
+        let mut f = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let f = &mut *f;
+        let query = query.to_lowercase();
+        let candidates: Vec<u32> = if query.len() < 3 {
+            (0..self.offsets.len() as u32 - 1).collect()
+        } else {
+            // Intersect the postings, rarest trigram first
+            let mut keys: Vec<u32> = trigrams(query.as_bytes()).collect();
+            keys.sort_unstable();
+            keys.dedup();
+            let mut lists = keys
+                .into_iter()
+                .map(|key| self.postings(f, key))
+                .collect::<Result<Vec<_>, _>>()?;
+            lists.sort_by_key(|list| list.len());
+            let mut ids = lists.first().cloned().unwrap_or_default();
+            for list in &lists[1..] {
+                ids.retain(|id| list.binary_search(id).is_ok());
+            }
+            ids
+        };
+        let mut matches = Vec::new();
+        for id in candidates {
+            if matches.len() >= limit {
+                break;
+            }
+            let path = self.path(f, id)?;
+            if path.to_lowercase().contains(&query) {
+                matches.push(path);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// `ready` is set on the final event when the repo was large enough to index.
+fn emit_progress(window: &tauri::Window, root: &Path, indexed: usize, done: bool, ready: bool) {
+    let _ = window.emit(
+        "file_index://progress",
+        serde_json::json!({
+            "work_dir": root.to_string_lossy(),
+            "indexed": indexed,
+            "done": done,
+            "ready": ready,
+        }),
+    );
+}
+
+impl IndexRegistry {
+    /// Build the index for `root` on a background thread, unless one is
+    /// already being built.
+    fn build(&self, window: &tauri::Window, root: &Path) {
+        // Any old index keeps serving while the new one is built
+        if !self
+            .building
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(root.to_path_buf())
+        {
+            return;
+        }
+        let entries = self.entries.clone();
+        let building = self.building.clone();
+        let window = window.clone();
+        let root = root.to_path_buf();
+        std::thread::spawn(move || {
+            let paths = collect_paths(&window, &root);
+            let file = index_file(&root);
+            let entry = if paths.len() < MIN_INDEXED_ENTRIES {
+                let _ = std::fs::remove_file(&file);
+                Entry::Small(SystemTime::now())
+            } else {
+                match write_index(&file, &paths).and_then(|_| FileIndex::open(&file)) {
+                    Ok(index) => Entry::Ready(Arc::new(index)),
+                    Err(_) => Entry::Small(SystemTime::now()),
+                }
+            };
+            let ready = matches!(entry, Entry::Ready(_));
+            entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(root.clone(), entry);
+            building.lock().unwrap_or_else(|e| e.into_inner()).remove(&root);
+            emit_progress(&window, &root, paths.len(), true, ready);
+        });
+    }
+
+    /// Paths under `root` matching `query` from the index, or None when the
+    /// repo is small or not indexed yet, in which case the caller walks it.
+    /// Starts a build when there is no index or it has gone stale.
+    pub fn search(
+        &self,
+        window: &tauri::Window,
+        root: &Path,
+        query: &str,
+        limit: usize,
+    ) -> Option<Vec<String>> {
+        let stale = |at: SystemTime| at.elapsed().is_ok_and(|age| age > REBUILD_AFTER);
+        let index = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if !entries.contains_key(root) {
+                // An index from an earlier run is used until it is rebuilt
+                if let Ok(index) = FileIndex::open(&index_file(root)) {
+                    entries.insert(root.to_path_buf(), Entry::Ready(Arc::new(index)));
+                }
+            }
+            match entries.get(root) {
+                Some(Entry::Small(at)) if !stale(*at) => return None,
+                Some(Entry::Ready(index)) => Some(index.clone()),
+                _ => None,
+            }
+        };
+        match index {
+            Some(index) => {
+                if stale(index.built_at) {
+                    self.build(window, root);
+                }
+                index.search(query, limit).ok()
+            }
+            None => {
+                self.build(window, root);
+                None
+            }
+        }
+    }
+}
+
+/// Rebuild the index of `work_dir` now, e.g. after a large checkout.
+#[tauri::command]
+pub fn file_index_build(
+    window: tauri::Window,
+    state: tauri::State<'_, crate::AppState>,
+    work_dir: String,
+) -> Result<(), String> {
+    let root = Path::new(&work_dir)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve work dir: {}", e))?;
+    state.file_index.build(&window, &root);
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::paths::{self, Access};
+use crate::tools::{CopyFileArgs, CreateDirectoryArgs, DeleteFileArgs, MoveFileArgs, ToolOutput};
+
+fn failure(summary: String) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary,
+        output: String::new(),
+    }
+}
+
+fn success(summary: String) -> ToolOutput {
+    ToolOutput {
+        ok: true,
+        summary,
+        output: String::new(),
+    }
+}
+
+fn resolve(work_dir: &str, path: &str) -> Result<PathBuf, String> {
+    if path.trim().is_empty() {
+        return Err("Missing path".to_string());
+    }
+    let resolved = paths::resolve_inside(work_dir, path, Access::Write)?;
+    let root = Path::new(work_dir);
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    if resolved == root {
+        return Err("Refusing to change the working directory itself".to_string());
+    }
+    Ok(resolved)
+}
+
+/// Resolve a path without following it if it is a link: the parent is
+/// confined to the work dir and the name joined as is, so a link is acted on
+/// itself rather than on what it points to.
+fn resolve_entry(work_dir: &str, path: &str) -> Result<PathBuf, String> {
+    if path.trim().is_empty() {
+        return Err("Missing path".to_string());
+    }
+    let path = Path::new(path);
+    let Some(name) = path.file_name() else {
+        return Err("Refusing to change the working directory or a parent of it".to_string());
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let entry = paths::resolve_inside(work_dir, &parent.to_string_lossy(), Access::Write)?.join(name);
+    paths::check_protected(&entry)?;
+    Ok(entry)
+}
+
+/// Resolve a path whose parent directories may not exist yet, one component
+/// at a time so each is confined to the work dir. Missing parents are created
+/// when `create_parents` is set.
+fn resolve_new(work_dir: &str, path: &str, create_parents: bool) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    let relative = path.strip_prefix(work_dir).unwrap_or(path);
+    let components: Vec<Component> = relative
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let mut prefix = PathBuf::new();
+    let mut resolved = None;
+    for (i, component) in components.iter().enumerate() {
+        prefix.push(component);
+        let current = resolve(work_dir, &prefix.to_string_lossy())?;
+        let is_last = i + 1 == components.len();
+        if !is_last && !current.exists() {
+            if !create_parents {
+                // Nothing below a missing directory can be a link elsewhere
+                let mut rest = current;
+                for component in &components[i + 1..] {
+                    match component {
+                        Component::Normal(name) => rest.push(name),
+                        _ => return Err("Invalid path".to_string()),
+                    }
+                }
+                return Ok(rest);
+            }
+            fs::create_dir(&current)
+                .map_err(|e| format!("Failed to create {}: {}", current.display(), e))?;
+        }
+        resolved = Some(current);
+    }
+    resolved.ok_or_else(|| "Missing path".to_string())
+}
+
+/// Source and destination of a move or copy. Only files are moved and copied,
+/// so every change can be rolled back from its checkpoint.
+fn endpoints(
+    work_dir: &str,
+    source: &str,
+    destination: &str,
+    overwrite: Option<bool>,
+) -> Result<(PathBuf, PathBuf), String> {
+    let from = resolve(work_dir, source)?;
+    let to = resolve_new(work_dir, destination, false)?;
+    if !from.exists() {
+        return Err(format!("{} does not exist", source));
+    }
+    if !from.is_file() {
+        return Err(format!(
+            "{} is a directory; move or copy the files in it one by one, or use Shell.",
+            source
+        ));
+    }
+    if from == to {
+        return Err("Source and destination are the same file".to_string());
+    }
+    if to.is_dir() {
+        return Err(format!(
+            "{} is a directory; give the full destination file path.",
+            destination
+        ));
+    }
+    if to.exists() && !overwrite.unwrap_or(false) {
+        return Err(format!(
+            "{} already exists; set overwrite to replace it.",
+            destination
+        ));
+    }
+    Ok((from, to))
+}
+
+/// The MoveFile tool: move or rename a file inside the work dir.
+pub fn move_tool(work_dir: &str, args: &MoveFileArgs, dry_run: bool) -> ToolOutput {
+    let (from, to) = match endpoints(work_dir, &args.source, &args.destination, args.overwrite) {
+        Ok(endpoints) => endpoints,
+        Err(err) => return failure(err),
+    };
+    if dry_run {
+        return success(format!(
+            "Dry run: {} was not moved to {}.",
+            args.source, args.destination
+        ));
+    }
+    if let Err(err) = resolve_new(work_dir, &args.destination, true) {
+        return failure(err);
+    }
+    // rename fails across file systems; copy and remove instead
+    let moved = fs::rename(&from, &to).or_else(|_| {
+        fs::copy(&from, &to)?;
+        fs::remove_file(&from)
+    });
+    match moved {
+        Ok(()) => success(format!("Moved {} to {}.", args.source, args.destination)),
+        Err(err) => failure(format!("Failed to move {}: {}", args.source, err)),
+    }
+}
+
+/// The CopyFile tool: copy a file inside the work dir.
+pub fn copy_tool(work_dir: &str, args: &CopyFileArgs, dry_run: bool) -> ToolOutput {
+    let (from, to) = match endpoints(work_dir, &args.source, &args.destination, args.overwrite) {
+        Ok(endpoints) => endpoints,
+        Err(err) => return failure(err),
+    };
+    if dry_run {
+        return success(format!(
+            "Dry run: {} was not copied to {}.",
+            args.source, args.destination
+        ));
+    }
+    if let Err(err) = resolve_new(work_dir, &args.destination, true) {
+        return failure(err);
+    }
+    match fs::copy(&from, &to) {
+        Ok(bytes) => success(format!(
+            "Copied {} to {} ({} bytes).",
+            args.source, args.destination, bytes
+        )),
+        Err(err) => failure(format!("Failed to copy {}: {}", args.source, err)),
+    }
+}
+
+/// The DeleteFile tool. Moves the file to the system trash unless `trash` is
+/// false. Only files, links and empty directories can go, since a checkpoint
+/// holds single files and a directory's contents could not be undone.
+pub fn delete_tool(work_dir: &str, args: &DeleteFileArgs, dry_run: bool) -> ToolOutput {
+    let path = match resolve_entry(work_dir, &args.path) {
+        Ok(path) => path,
+        Err(err) => return failure(err),
+    };
+    let Ok(meta) = fs::symlink_metadata(&path) else {
+        return failure(format!("{} does not exist", args.path));
+    };
+    let trash = args.trash.unwrap_or(true);
+    let is_dir = meta.is_dir();
+    if is_dir && fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_some()) {
+        return failure(format!(
+            "{} is a directory with files in it; delete the files one by one so each can be undone, or use Shell.",
+            args.path
+        ));
+    }
+    if dry_run {
+        return success(format!("Dry run: {} was not deleted.", args.path));
+    }
+    let deleted = if trash {
+        trash::delete(&path).map_err(|e| e.to_string())
+    } else if is_dir {
+        fs::remove_dir(&path).map_err(|e| e.to_string())
+    } else {
+        fs::remove_file(&path).map_err(|e| e.to_string())
+    };
+    match deleted {
+        Ok(()) if trash => success(format!("Moved {} to the trash.", args.path)),
+        Ok(()) => success(format!("Deleted {}.", args.path)),
+        Err(err) => failure(format!("Failed to delete {}: {}", args.path, err)),
+    }
+}
+
+/// The CreateDirectory tool: create a directory and any missing parents.
+pub fn create_directory_tool(
+    work_dir: &str,
+    args: &CreateDirectoryArgs,
+    dry_run: bool,
+) -> ToolOutput {
+    let path = match resolve_new(work_dir, &args.path, false) {
+        Ok(path) => path,
+        Err(err) => return failure(err),
+    };
+    if path.is_dir() {
+        return success(format!("{} already exists.", args.path));
+    }
+    if path.exists() {
+        return failure(format!("{} exists and is not a directory", args.path));
+    }
+    if dry_run {
+        return success(format!("Dry run: {} was not created.", args.path));
+    }
+    let created = resolve_new(work_dir, &args.path, true).and_then(|path| {
+        fs::create_dir(&path).map_err(|e| format!("Failed to create {}: {}", args.path, e))
+    });
+    match created {
+        Ok(()) => success(format!("Created {}.", args.path)),
+        Err(err) => failure(err),
+    }
+}
@@ -0,0 +1,171 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const BACKUP_DIR: &str = ".kimi/backups";
+/// Previous versions kept per file; older ones are pruned
+const MAX_BACKUPS: usize = 5;
+/// Suffix appended to a backup's file name, e.g. `main.rs.20261015-142233123`
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+fn content_hash(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(
+        Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+/// Content of each file as a session last read or wrote it, so an edit to a
+/// file that changed on disk since then is refused instead of clobbering it.
+#[derive(Default)]
+pub struct ReadTracker {
+    seen: Mutex<HashMap<(String, PathBuf), String>>,
+}
+
+impl ReadTracker {
+    /// Remember the file as it is on disk now.
+    pub fn record(&self, session_id: &str, path: &Path) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (session_id.to_string(), path.to_path_buf());
+        match content_hash(path) {
+            Some(hash) => seen.insert(key, hash),
+            None => seen.remove(&key),
+        };
+    }
+
+    /// Err when the session has seen the file and it has changed or gone
+    /// since. Files the session never looked at pass.
+    pub fn check(&self, session_id: &str, path: &Path) -> Result<(), String> {
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(expected) = seen.get(&(session_id.to_string(), path.to_path_buf())) else {
+            return Ok(());
+        };
+        if content_hash(path).as_ref() == Some(expected) {
+            return Ok(());
+        }
+        Err(format!(
+            "{} changed on disk since this session last read it. Read it again before editing so other changes aren't overwritten.",
+            path.display()
+        ))
+    }
+
+    pub fn forget_session(&self, session_id: &str) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|(owner, _), _| owner != session_id);
+    }
+}
+
+/// Where the backup of `path` goes, relative to the backups folder. Files
+/// outside the work dir are kept under `outside/` by name.
+fn backup_relative(work_dir: &str, path: &Path) -> PathBuf {
+    let root = Path::new(work_dir);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    path.strip_prefix(&canonical_root)
+        .or_else(|_| path.strip_prefix(root))
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| Path::new("outside").join(path.file_name().unwrap_or_default()))
+}
+
+fn is_stamp(suffix: &str) -> bool {
+    let (date, time) = suffix.split_once('-').unwrap_or(("", ""));
+    date.len() == 8
+        && time.len() == 9
+        && date.chars().chain(time.chars()).all(|c| c.is_ascii_digit())
+}
+
+/// Drop all but the newest `MAX_BACKUPS` versions of `name` in `dir`.
+fn prune(dir: &Path, name: &str) {
+    let prefix = format!("{}.", name);
+    let mut versions: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|file| file.strip_prefix(&prefix).is_some_and(is_stamp))
+        .collect();
+    // Stamps sort chronologically
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    for old in versions.into_iter().skip(MAX_BACKUPS) {
+        let _ = fs::remove_file(dir.join(old));
+    }
+}
+
+/// Copy the current content of `path` into the work dir's `.kimi/backups`,
+/// keeping its folder layout, and return the copy. Nothing to do when the
+/// file doesn't exist yet.
+pub fn backup(work_dir: &str, path: &Path) -> Result<Option<PathBuf>, String> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let backups = Path::new(work_dir).join(BACKUP_DIR);
+    let relative = backup_relative(work_dir, path);
+    let name = relative
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+    let dest = backups.join(&relative).with_file_name(format!(
+        "{}.{}",
+        name,
+        chrono::Local::now().format(STAMP_FORMAT)
+    ));
+    crate::ensure_parent(&dest)?;
+    // Backups are local history, not project files
+    let gitignore = backups.join(".gitignore");
+    if !gitignore.exists() {
+        let _ = fs::write(&gitignore, "*\n");
+    }
+    fs::copy(path, &dest).map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+    if let Some(dir) = dest.parent() {
+        prune(dir, &name);
+    }
+    Ok(Some(dest))
+}
+
+/// The file a write to `path` lands in: the target of a symlink, so the link
+/// itself survives the rename.
+fn write_target(path: &Path) -> PathBuf {
+    if path.is_symlink() {
+        if let Ok(target) = path.canonicalize() {
+            return target;
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Write `content` to a temp file next to `path`, with the permissions of the
+/// file it will replace. Returns the temp file and the file to rename it over.
+pub fn stage(path: &Path, content: &[u8]) -> Result<(PathBuf, PathBuf), String> {
+    let target = write_target(path);
+    let tmp = target.with_file_name(format!(
+        ".{}.kimi-tmp",
+        target
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+    ));
+    if let Err(err) = fs::write(&tmp, content) {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Failed to write {}: {}", path.display(), err));
+    }
+    if let Ok(meta) = fs::metadata(&target) {
+        let _ = fs::set_permissions(&tmp, meta.permissions());
+    }
+    Ok((tmp, target))
+}
+
+/// Replace `path` with `content` atomically: back up the current version,
+/// write a temp file, then rename it into place. A failed write leaves the
+/// original untouched.
+pub fn replace(work_dir: &str, path: &Path, content: &[u8]) -> Result<(), String> {
+    backup(work_dir, path)?;
+    let (tmp, target) = stage(path, content)?;
+    fs::rename(&tmp, &target).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
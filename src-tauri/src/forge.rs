@@ -0,0 +1,715 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+use crate::git;
+use crate::tools::{CreatePullRequestArgs, ReadIssueArgs, ReadPullRequestArgs, ToolOutput};
+
+const GITHUB_API: &str = "https://api.github.com";
+const GITLAB_URL: &str = "https://gitlab.com";
+/// The GitHub API turns away requests without an agent
+const USER_AGENT: &str = "KimiCode-GUI";
+/// One page of comments; longer threads are cut off with a note
+const MAX_COMMENTS: usize = 100;
+const MAX_LINKED_ISSUES: usize = 10;
+/// Commits searched for `#123` references when finding linked issues
+const LINKED_COMMITS: &str = "-50";
+
+/// Tokens for the issue and pull request tools, by the name of a credential
+/// in the auth store so they stay out of the settings file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ForgeSettings {
+    /// Credential holding the GitHub token; GITHUB_TOKEN or GH_TOKEN from the
+    /// environment when unset. Public repositories can be read without one
+    #[serde(default)]
+    pub github_credential: Option<String>,
+    /// Credential holding the GitLab token; GITLAB_TOKEN from the environment
+    /// when unset
+    #[serde(default)]
+    pub gitlab_credential: Option<String>,
+    /// Plaintext tokens saved by older versions, moved into credentials on load
+    #[serde(default, skip_serializing)]
+    github_token: Option<String>,
+    #[serde(default, skip_serializing)]
+    gitlab_token: Option<String>,
+    /// Self-hosted GitLab, e.g. `https://gitlab.example.com`; gitlab.com
+    /// remotes are recognized without it
+    #[serde(default)]
+    pub gitlab_url: Option<String>,
+}
+
+static SETTINGS: RwLock<Option<ForgeSettings>> = RwLock::new(None);
+
+pub fn set_settings(settings: ForgeSettings) {
+    *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = Some(settings);
+}
+
+fn settings() -> ForgeSettings {
+    SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Move plaintext tokens from older settings into named credentials. Returns
+/// whether `settings` changed and should be saved again.
+pub fn migrate_tokens(settings: &mut ForgeSettings) -> bool {
+    let mut changed = false;
+    for (legacy, credential, base) in [
+        (&mut settings.github_token, &mut settings.github_credential, "github"),
+        (&mut settings.gitlab_token, &mut settings.gitlab_credential, "gitlab"),
+    ] {
+        let Some(token) = legacy.take().filter(|token| !token.trim().is_empty()) else {
+            continue;
+        };
+        changed = true;
+        if credential.is_some() {
+            continue;
+        }
+        let existing = crate::load_auth_config().credentials;
+        let Some(name) = (1..)
+            .map(|n| if n == 1 { base.to_string() } else { format!("{}-{}", base, n) })
+            .find(|name| !existing.contains_key(name))
+        else {
+            continue;
+        };
+        let stored = crate::credentials::auth_credential_set(
+            name.clone(),
+            crate::credentials::NamedCredential {
+                mode: "api_key".to_string(),
+                api_key: Some(token.trim().to_string()),
+                api_base: None,
+                auth_scheme: None,
+            },
+        );
+        if stored.is_ok() {
+            *credential = Some(name);
+        }
+    }
+    changed
+}
+
+/// The token of the named credential, or the first of `env` that is set.
+async fn token(credential: Option<&str>, env: &[&str]) -> Result<Option<String>, String> {
+    let configured = match credential.map(str::trim).filter(|name| !name.is_empty()) {
+        Some(name) => Some(crate::credentials::resolve(name).await?.token),
+        None => None,
+    };
+    Ok(configured
+        .or_else(|| env.iter().find_map(|name| std::env::var(name).ok()))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    GitHub,
+    GitLab,
+}
+
+/// The project the work dir's `origin` remote points at.
+struct Repo {
+    kind: Kind,
+    /// `owner/name` on GitHub, the project path with its groups on GitLab
+    path: String,
+    api: String,
+    token: Option<String>,
+}
+
+impl Repo {
+    fn label(&self) -> &'static str {
+        match self.kind {
+            Kind::GitHub => "GitHub",
+            Kind::GitLab => "GitLab",
+        }
+    }
+
+    /// Pull requests are merge requests on GitLab, numbered `!12`.
+    fn pr_noun(&self) -> &'static str {
+        match self.kind {
+            Kind::GitHub => "pull request",
+            Kind::GitLab => "merge request",
+        }
+    }
+
+    fn pr_ref(&self, number: u64) -> String {
+        match self.kind {
+            Kind::GitHub => format!("#{}", number),
+            Kind::GitLab => format!("!{}", number),
+        }
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        match self.kind {
+            Kind::GitHub => format!("{}/repos/{}{}", self.api, self.path, endpoint),
+            Kind::GitLab => format!(
+                "{}/projects/{}{}",
+                self.api,
+                self.path.replace('/', "%2F"),
+                endpoint
+            ),
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        endpoint: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, String> {
+        let url = self.url(endpoint);
+        crate::network::ensure_online(&url)?;
+        let mut request = crate::http::plain_client()?.request(method, &url);
+        request = match self.kind {
+            Kind::GitHub => request
+                .header("Accept", "application/vnd.github+json")
+                .header("X-GitHub-Api-Version", "2022-11-28")
+                .header("User-Agent", USER_AGENT),
+            Kind::GitLab => request,
+        };
+        if let Some(token) = &self.token {
+            request = match self.kind {
+                Kind::GitHub => request.bearer_auth(token),
+                Kind::GitLab => request.header("PRIVATE-TOKEN", token),
+            };
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("{} request failed: {}", self.label(), e))?;
+        let status = response.status();
+        let data: serde_json::Value = response.json().await.unwrap_or_default();
+        if status.is_success() {
+            return Ok(data);
+        }
+        let message = data["message"]
+            .as_str()
+            .or_else(|| data["error"].as_str())
+            .map(|message| format!(": {}", message))
+            .unwrap_or_default();
+        let hint = if self.token.is_none() && matches!(status.as_u16(), 401 | 403 | 404) {
+            format!(
+                " Private projects need a {} token; pick a credential holding one in settings.",
+                self.label()
+            )
+        } else {
+            String::new()
+        };
+        Err(format!(
+            "{} returned {}{}.{}",
+            self.label(),
+            status,
+            message,
+            hint
+        ))
+    }
+
+    async fn get(&self, endpoint: &str) -> Result<serde_json::Value, String> {
+        self.request(Method::GET, endpoint, None).await
+    }
+}
+
+/// Find the forge from the `origin` remote. GitHub and gitlab.com remotes
+/// are recognized by host, self-hosted GitLab by the URL in settings.
+async fn detect(work_dir: &str) -> Result<Repo, String> {
+    let remote = git::run_git(work_dir, &["config", "--get", "remote.origin.url"])
+        .await
+        .map_err(|_| "The working directory has no origin remote.".to_string())?;
+    let remote = git::normalize_remote(&remote);
+    let (host, path) = remote
+        .split_once('/')
+        .ok_or_else(|| format!("Can't tell the project from the remote {}", remote))?;
+    let settings = settings();
+    let gitlab_url = settings
+        .gitlab_url
+        .as_deref()
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty());
+    let self_hosted = gitlab_url
+        .and_then(|url| reqwest::Url::parse(url).ok())
+        .and_then(|url| url.host_str().map(str::to_lowercase));
+    if host == "github.com" {
+        Ok(Repo {
+            kind: Kind::GitHub,
+            path: path.to_string(),
+            api: GITHUB_API.to_string(),
+            token: token(settings.github_credential.as_deref(), &["GITHUB_TOKEN", "GH_TOKEN"]).await?,
+        })
+    } else if host == "gitlab.com" || self_hosted.as_deref() == Some(host) {
+        let base = match self_hosted.as_deref() {
+            Some(hosted) if hosted == host => gitlab_url.unwrap_or(GITLAB_URL),
+            _ => GITLAB_URL,
+        };
+        Ok(Repo {
+            kind: Kind::GitLab,
+            path: path.to_string(),
+            api: format!("{}/api/v4", base),
+            token: token(settings.gitlab_credential.as_deref(), &["GITLAB_TOKEN"]).await?,
+        })
+    } else {
+        Err(format!(
+            "{} is not GitHub or gitlab.com; set the GitLab URL in settings for a self-hosted instance.",
+            host
+        ))
+    }
+}
+
+fn text(value: &serde_json::Value, pointer: &str) -> String {
+    value
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+#[derive(Clone, Serialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub author: String,
+    pub url: String,
+    pub labels: Vec<String>,
+    pub body: String,
+}
+
+fn parse_issue(kind: Kind, value: &serde_json::Value) -> Issue {
+    let (number, author, url, body) = match kind {
+        Kind::GitHub => ("number", "/user/login", "/html_url", "/body"),
+        Kind::GitLab => ("iid", "/author/username", "/web_url", "/description"),
+    };
+    Issue {
+        number: value[number].as_u64().unwrap_or(0),
+        title: text(value, "/title"),
+        state: text(value, "/state"),
+        author: text(value, author),
+        url: text(value, url),
+        labels: value["labels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            // GitHub labels are objects, GitLab's plain names
+            .filter_map(|label| label["name"].as_str().or_else(|| label.as_str()))
+            .map(str::to_string)
+            .collect(),
+        body: text(value, body),
+    }
+}
+
+struct Comment {
+    author: String,
+    created_at: String,
+    /// `path:line` of a review comment on the diff
+    location: Option<String>,
+    body: String,
+}
+
+fn parse_comments(kind: Kind, value: &serde_json::Value) -> Vec<Comment> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        // GitLab notes include system events such as label changes
+        .filter(|note| !note["system"].as_bool().unwrap_or(false))
+        .map(|comment| {
+            let (author, path, line) = match kind {
+                Kind::GitHub => ("/user/login", "/path", "/line"),
+                Kind::GitLab => (
+                    "/author/username",
+                    "/position/new_path",
+                    "/position/new_line",
+                ),
+            };
+            let path = text(comment, path);
+            let line = comment.pointer(line).and_then(|v| v.as_u64());
+            Comment {
+                author: text(comment, author),
+                created_at: text(comment, "/created_at"),
+                location: (!path.is_empty()).then(|| match line {
+                    Some(line) => format!("{}:{}", path, line),
+                    None => path,
+                }),
+                body: text(comment, "/body"),
+            }
+        })
+        .collect()
+}
+
+fn format_thread(header: &str, issue: &Issue, extra: &[String], comments: &[Comment]) -> String {
+    let mut out = format!("{} {}\n", header, issue.title);
+    out.push_str(&format!(
+        "State: {} · Author: {}",
+        issue.state, issue.author
+    ));
+    if !issue.labels.is_empty() {
+        out.push_str(&format!(" · Labels: {}", issue.labels.join(", ")));
+    }
+    out.push_str(&format!("\nURL: {}\n", issue.url));
+    for line in extra {
+        out.push_str(line);
+        out.push('\n');
+    }
+    let body = issue.body.trim();
+    out.push_str(&format!(
+        "\n{}\n",
+        if body.is_empty() {
+            "(no description)"
+        } else {
+            body
+        }
+    ));
+    if comments.is_empty() {
+        out.push_str("\nNo comments.\n");
+        return out;
+    }
+    out.push_str(&format!("\nComments ({}):\n", comments.len()));
+    for comment in comments.iter().take(MAX_COMMENTS) {
+        let location = comment
+            .location
+            .as_ref()
+            .map(|location| format!(" on {}", location))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "\n--- {}, {}{}\n{}\n",
+            comment.author,
+            comment.created_at,
+            location,
+            comment.body.trim()
+        ));
+    }
+    if comments.len() > MAX_COMMENTS {
+        out.push_str(&format!(
+            "\n... {} more comments\n",
+            comments.len() - MAX_COMMENTS
+        ));
+    }
+    out
+}
+
+fn finish(result: Result<ToolOutput, String>) -> ToolOutput {
+    result.unwrap_or_else(|summary| ToolOutput {
+        ok: false,
+        summary,
+        output: String::new(),
+    })
+}
+
+async fn read_issue(work_dir: &str, number: u64) -> Result<ToolOutput, String> {
+    let repo = detect(work_dir).await?;
+    let (issue, notes) = match repo.kind {
+        Kind::GitHub => (
+            repo.get(&format!("/issues/{}", number)).await?,
+            repo.get(&format!("/issues/{}/comments?per_page=100", number))
+                .await?,
+        ),
+        Kind::GitLab => (
+            repo.get(&format!("/issues/{}", number)).await?,
+            repo.get(&format!("/issues/{}/notes?sort=asc&per_page=100", number))
+                .await?,
+        ),
+    };
+    let issue = parse_issue(repo.kind, &issue);
+    let comments = parse_comments(repo.kind, &notes);
+    Ok(ToolOutput {
+        ok: true,
+        summary: format!(
+            "Read {} issue #{} with {} comment(s).",
+            repo.label(),
+            number,
+            comments.len()
+        ),
+        output: format_thread(&format!("#{}", number), &issue, &[], &comments),
+    })
+}
+
+/// The ReadIssue tool: an issue's description and comments.
+pub async fn read_issue_tool(work_dir: &str, args: &ReadIssueArgs) -> ToolOutput {
+    finish(read_issue(work_dir, args.number).await)
+}
+
+async fn read_pull_request(work_dir: &str, number: u64) -> Result<ToolOutput, String> {
+    let repo = detect(work_dir).await?;
+    let (pr, mut comments, branches) = match repo.kind {
+        Kind::GitHub => {
+            let pr = repo.get(&format!("/pulls/{}", number)).await?;
+            let mut comments = parse_comments(
+                repo.kind,
+                &repo
+                    .get(&format!("/issues/{}/comments?per_page=100", number))
+                    .await?,
+            );
+            comments.extend(parse_comments(
+                repo.kind,
+                &repo
+                    .get(&format!("/pulls/{}/comments?per_page=100", number))
+                    .await?,
+            ));
+            let branches = (text(&pr, "/head/ref"), text(&pr, "/base/ref"));
+            (pr, comments, branches)
+        }
+        Kind::GitLab => {
+            let pr = repo.get(&format!("/merge_requests/{}", number)).await?;
+            let comments = parse_comments(
+                repo.kind,
+                &repo
+                    .get(&format!(
+                        "/merge_requests/{}/notes?sort=asc&per_page=100",
+                        number
+                    ))
+                    .await?,
+            );
+            let branches = (text(&pr, "/source_branch"), text(&pr, "/target_branch"));
+            (pr, comments, branches)
+        }
+    };
+    // Conversation and review comments in the order they were written
+    comments.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    let mut issue = parse_issue(repo.kind, &pr);
+    if pr["merged_at"].as_str().is_some() && repo.kind == Kind::GitHub {
+        issue.state = "merged".to_string();
+    }
+    if pr["draft"].as_bool().unwrap_or(false) {
+        issue.state = format!("{} (draft)", issue.state);
+    }
+    let extra = [format!("Branches: {} → {}", branches.0, branches.1)];
+    Ok(ToolOutput {
+        ok: true,
+        summary: format!(
+            "Read {} {} with {} comment(s).",
+            repo.pr_noun(),
+            repo.pr_ref(number),
+            comments.len()
+        ),
+        output: format_thread(&repo.pr_ref(number), &issue, &extra, &comments),
+    })
+}
+
+/// The ReadPullRequest tool: a pull or merge request's description, branches
+/// and comments, including review comments on the diff.
+pub async fn read_pull_request_tool(work_dir: &str, args: &ReadPullRequestArgs) -> ToolOutput {
+    finish(read_pull_request(work_dir, args.number).await)
+}
+
+async fn current_branch(work_dir: &str) -> Result<String, String> {
+    let branch = git::run_git(work_dir, &["rev-parse", "--abbrev-ref", "HEAD"]).await?;
+    let branch = branch.trim();
+    if branch.is_empty() || branch == "HEAD" {
+        return Err("HEAD is detached; check out a branch first.".to_string());
+    }
+    Ok(branch.to_string())
+}
+
+async fn create_pull_request(
+    work_dir: &str,
+    args: &CreatePullRequestArgs,
+    dry_run: bool,
+) -> Result<ToolOutput, String> {
+    let repo = detect(work_dir).await?;
+    if repo.token.is_none() {
+        return Err(format!(
+            "Opening a {} needs a {} token; pick a credential holding one in settings.",
+            repo.pr_noun(),
+            repo.label()
+        ));
+    }
+    let branch = current_branch(work_dir).await?;
+    // The forge only knows what was pushed, under the upstream's name
+    let upstream = git::run_git(work_dir, &["rev-parse", "--abbrev-ref", "@{upstream}"])
+        .await
+        .map_err(|_| {
+            format!(
+                "Branch {} has not been pushed; push it first, e.g. `git push -u origin {}`.",
+                branch, branch
+            )
+        })?;
+    let head = upstream
+        .trim()
+        .split_once('/')
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| branch.clone());
+    let unpushed = git::run_git(work_dir, &["rev-list", "--count", "@{upstream}..HEAD"])
+        .await
+        .ok()
+        .and_then(|count| count.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if unpushed > 0 {
+        return Err(format!(
+            "Branch {} has {} commit(s) that are not pushed; push them first.",
+            branch, unpushed
+        ));
+    }
+    let base = match args
+        .base
+        .as_deref()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+    {
+        Some(base) => base.to_string(),
+        None => text(&repo.get("").await?, "/default_branch"),
+    };
+    if base.is_empty() {
+        return Err("Could not find the default branch; give base explicitly.".to_string());
+    }
+    if base == head {
+        return Err(format!(
+            "{} is the base branch; create the {} from another branch.",
+            base,
+            repo.pr_noun()
+        ));
+    }
+    let draft = args.draft.unwrap_or(false);
+    let body = args.body.clone().unwrap_or_default();
+    if dry_run {
+        return Ok(ToolOutput {
+            ok: true,
+            summary: format!("Dry run: no {} was opened.", repo.pr_noun()),
+            output: format!(
+                "Would open a {}{} from {} into {}: {}",
+                if draft { "draft " } else { "" },
+                repo.pr_noun(),
+                head,
+                base,
+                args.title
+            ),
+        });
+    }
+    let created = match repo.kind {
+        Kind::GitHub => {
+            repo.request(
+                Method::POST,
+                "/pulls",
+                Some(serde_json::json!({
+                    "title": args.title,
+                    "body": body,
+                    "head": head,
+                    "base": base,
+                    "draft": draft,
+                })),
+            )
+            .await?
+        }
+        Kind::GitLab => {
+            let title = if draft {
+                format!("Draft: {}", args.title)
+            } else {
+                args.title.clone()
+            };
+            repo.request(
+                Method::POST,
+                "/merge_requests",
+                Some(serde_json::json!({
+                    "title": title,
+                    "description": body,
+                    "source_branch": head,
+                    "target_branch": base,
+                })),
+            )
+            .await?
+        }
+    };
+    let issue = parse_issue(repo.kind, &created);
+    Ok(ToolOutput {
+        ok: true,
+        summary: format!(
+            "Opened {} {}: {}",
+            repo.pr_noun(),
+            repo.pr_ref(issue.number),
+            issue.url
+        ),
+        output: format!(
+            "{} {} from {} into {}\nURL: {}",
+            repo.pr_ref(issue.number),
+            issue.title,
+            head,
+            base,
+            issue.url
+        ),
+    })
+}
+
+/// The CreatePullRequest tool: open a pull request (a merge request on
+/// GitLab) from the current branch, which must already be pushed.
+pub async fn create_pull_request_tool(
+    work_dir: &str,
+    args: &CreatePullRequestArgs,
+    dry_run: bool,
+) -> ToolOutput {
+    finish(create_pull_request(work_dir, args, dry_run).await)
+}
+
+/// Issue numbers mentioned as `#123` in `text`.
+fn issue_refs(text: &str) -> Vec<u64> {
+    let mut refs = Vec::new();
+    for (at, _) in text.match_indices('#') {
+        let before = text[..at].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '&') {
+            continue;
+        }
+        let digits: String = text[at + 1..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if let Ok(number) = digits.parse() {
+            refs.push(number);
+        }
+    }
+    refs
+}
+
+/// Issue numbers in a branch name such as `fix/123-crash` or `issue-45`.
+fn branch_refs(branch: &str) -> Vec<u64> {
+    branch
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty() && part.len() <= 7)
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct LinkedIssues {
+    /// `GitHub` or `GitLab`
+    pub forge: &'static str,
+    pub project: String,
+    pub branch: Option<String>,
+    pub issues: Vec<Issue>,
+}
+
+/// Issues the current branch refers to, by number in its name or as `#123`
+/// in the commits it adds over the remote's default branch.
+#[tauri::command]
+pub async fn forge_linked_issues(work_dir: String) -> Result<LinkedIssues, String> {
+    let repo = detect(&work_dir).await?;
+    let branch = current_branch(&work_dir).await.ok();
+    let mut numbers = branch.as_deref().map(branch_refs).unwrap_or_default();
+    if let Ok(log) = git::run_git(
+        &work_dir,
+        &["log", LINKED_COMMITS, "--format=%B", "origin/HEAD..HEAD"],
+    )
+    .await
+    {
+        numbers.extend(issue_refs(&log));
+    }
+    let mut seen = std::collections::HashSet::new();
+    numbers.retain(|number| *number > 0 && seen.insert(*number));
+    let mut issues = Vec::new();
+    for number in numbers.into_iter().take(MAX_LINKED_ISSUES) {
+        // A number that isn't an issue, or a pull request on GitHub, is skipped
+        let Ok(issue) = repo.get(&format!("/issues/{}", number)).await else {
+            continue;
+        };
+        if issue.get("pull_request").is_some() {
+            continue;
+        }
+        issues.push(parse_issue(repo.kind, &issue));
+    }
+    Ok(LinkedIssues {
+        forge: repo.label(),
+        project: repo.path,
+        branch,
+        issues,
+    })
+}
@@ -0,0 +1,420 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+/// Identity of the repository a work dir belongs to, shared by all of its worktrees.
+#[derive(Clone, Debug)]
+pub struct RepoIdentity {
+    /// Normalized origin URL, or the common git dir when there is no origin.
+    pub key: String,
+    pub name: String,
+    pub branch: Option<String>,
+}
+
+/// Locate the work tree root, its git dir, and the common git dir shared by worktrees.
+fn find_git_dirs(start: &Path) -> Option<(PathBuf, PathBuf, PathBuf)> {
+    let mut current = start.canonicalize().ok()?;
+    loop {
+        let dot_git = current.join(".git");
+        if dot_git.is_dir() {
+            return Some((current, dot_git.clone(), dot_git));
+        }
+        if dot_git.is_file() {
+            // Linked worktree: `.git` is a file containing `gitdir: <path>`
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let gitdir = content.trim().strip_prefix("gitdir:")?.trim();
+            let git_dir = current.join(gitdir);
+            let common_dir = fs::read_to_string(git_dir.join("commondir"))
+                .ok()
+                .map(|dir| git_dir.join(dir.trim()))
+                .unwrap_or_else(|| git_dir.clone());
+            let common_dir = common_dir.canonicalize().unwrap_or(common_dir);
+            return Some((current, git_dir, common_dir));
+        }
+        if !current.pop() {
+            return None;
+        }
+    }
+}
+
+fn read_origin_url(common_dir: &Path) -> Option<String> {
+    let config = fs::read_to_string(common_dir.join("config")).ok()?;
+    let mut in_origin = false;
+    for line in config.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_origin = trimmed == "[remote \"origin\"]";
+            continue;
+        }
+        if in_origin {
+            if let Some((key, value)) = trimmed.split_once('=') {
+                if key.trim() == "url" {
+                    return Some(value.trim().to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+fn read_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => Some(
+            reference
+                .trim()
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference.trim())
+                .to_string(),
+        ),
+        // Detached HEAD: show the short commit
+        None => Some(head.chars().take(8).collect()),
+    }
+}
+
+/// `git@github.com:org/repo.git` and `https://github.com/org/repo` map to the same key.
+pub fn normalize_remote(url: &str) -> String {
+    let mut url = url.trim().to_lowercase();
+    for prefix in ["https://", "http://", "ssh://", "git://"] {
+        if let Some(rest) = url.strip_prefix(prefix) {
+            url = rest.to_string();
+        }
+    }
+    if let Some((user, rest)) = url.split_once('@') {
+        if !user.contains('/') {
+            url = rest.to_string();
+        }
+    }
+    if !url.contains("://") {
+        if let Some((host, path)) = url.split_once(':') {
+            if !path.starts_with('/') && !host.contains('/') {
+                url = format!("{host}/{path}");
+            }
+        }
+    }
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .to_string()
+}
+
+pub fn repo_identity(path: &Path) -> Option<RepoIdentity> {
+    let (_root, git_dir, common_dir) = find_git_dirs(path)?;
+    let origin = read_origin_url(&common_dir);
+    let key = origin
+        .as_deref()
+        .map(normalize_remote)
+        .unwrap_or_else(|| common_dir.to_string_lossy().to_string());
+    let name = origin
+        .as_deref()
+        .map(normalize_remote)
+        .and_then(|remote| remote.rsplit('/').next().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            common_dir
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "repository".to_string());
+
+    Some(RepoIdentity {
+        key,
+        name,
+        branch: read_branch(&git_dir),
+    })
+}
+
+#[derive(Clone, Serialize)]
+pub struct GitFileStatus {
+    pub path: String,
+    /// Source path of a rename or copy
+    pub orig_path: Option<String>,
+    /// Porcelain status letters, e.g. "M", "A", "?" for untracked; " " when unchanged
+    pub index: String,
+    pub worktree: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GitStatus {
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub files: Vec<GitFileStatus>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GitCommit {
+    pub hash: String,
+    pub short_hash: String,
+    pub author: String,
+    pub email: String,
+    pub timestamp: i64,
+    pub subject: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GitBranchInfo {
+    /// None on a detached HEAD
+    pub current: Option<String>,
+    pub head: String,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub branches: Vec<String>,
+}
+
+/// How much a repository changed lately.
+#[derive(Clone, Serialize)]
+pub struct GitActivity {
+    pub branch: Option<String>,
+    pub days: u32,
+    /// Commits in the last `days` days
+    pub commits: usize,
+    /// Authors of those commits, busiest first
+    pub authors: Vec<(String, usize)>,
+    pub latest: Vec<GitCommit>,
+    /// Files those commits touched, most often changed first
+    pub hot_files: Vec<(String, usize)>,
+}
+
+/// Run `git` in `work_dir` and return stdout, or stderr as the error.
+pub async fn run_git(work_dir: &str, args: &[&str]) -> Result<String, String> {
+    let output = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(work_dir)
+        .args(args)
+        // Never block on a credential or editor prompt
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_EDITOR", "true")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(if stderr.is_empty() {
+            format!("git {} failed", args.first().unwrap_or(&""))
+        } else {
+            stderr
+        })
+    }
+}
+
+/// Parse the `## branch...upstream [ahead 1, behind 2]` header of `git status -b`.
+fn parse_branch_header(header: &str) -> (Option<String>, Option<String>, usize, usize) {
+    let header = header.trim_start_matches("## ");
+    let (refs, counts) = match header.split_once(" [") {
+        Some((refs, counts)) => (refs, counts.trim_end_matches(']')),
+        None => (header, ""),
+    };
+    let (branch, upstream) = match refs.split_once("...") {
+        Some((branch, upstream)) => (branch.to_string(), Some(upstream.to_string())),
+        None => (refs.to_string(), None),
+    };
+    let branch = branch
+        .strip_prefix("No commits yet on ")
+        .map(str::to_string)
+        .unwrap_or(branch);
+    let branch = (!branch.starts_with("HEAD (no branch)")).then_some(branch);
+    let count = |key: &str| {
+        counts
+            .split(", ")
+            .find_map(|part| part.strip_prefix(key))
+            .and_then(|n| n.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    (branch, upstream, count("ahead "), count("behind "))
+}
+
+fn parse_log(raw: &str) -> Vec<GitCommit> {
+    raw.split('\x1e')
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.trim_start_matches('\n').split('\x1f').collect();
+            let [hash, short_hash, author, email, timestamp, subject] = fields[..] else {
+                return None;
+            };
+            Some(GitCommit {
+                hash: hash.to_string(),
+                short_hash: short_hash.to_string(),
+                author: author.to_string(),
+                email: email.to_string(),
+                timestamp: timestamp.parse().unwrap_or(0),
+                subject: subject.to_string(),
+            })
+        })
+        .collect()
+}
+
+const LOG_FORMAT: &str = "--format=%H%x1f%h%x1f%an%x1f%ae%x1f%at%x1f%s%x1e";
+
+/// Up to `limit` entries with the highest counts.
+fn busiest(counts: HashMap<String, usize>, limit: usize) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+/// Commits, authors and most changed files of the last `days` days; None
+/// outside a repository or before its first commit.
+pub async fn activity(work_dir: &str, days: u32) -> Option<GitActivity> {
+    let since = format!("--since={}.days", days);
+    let commits = parse_log(
+        &run_git(work_dir, &["log", &since, "-n1000", LOG_FORMAT])
+            .await
+            .ok()?,
+    );
+    let mut authors = HashMap::new();
+    for commit in &commits {
+        *authors.entry(commit.author.clone()).or_insert(0) += 1;
+    }
+    let mut files = HashMap::new();
+    let changed = run_git(work_dir, &["log", &since, "-n1000", "--name-only", "--format="])
+        .await
+        .unwrap_or_default();
+    for path in changed.lines().filter(|line| !line.is_empty()) {
+        *files.entry(path.to_string()).or_insert(0) += 1;
+    }
+    Some(GitActivity {
+        branch: repo_identity(Path::new(work_dir)).and_then(|repo| repo.branch),
+        days,
+        commits: commits.len(),
+        authors: busiest(authors, 5),
+        latest: commits.into_iter().take(5).collect(),
+        hot_files: busiest(files, 10),
+    })
+}
+
+#[tauri::command]
+pub async fn git_status(work_dir: String) -> Result<GitStatus, String> {
+    let raw = run_git(&work_dir, &["status", "--porcelain=v1", "-b", "-z", "--untracked-files=all"]).await?;
+    let mut entries = raw.split('\0').filter(|entry| !entry.is_empty());
+    let mut status = GitStatus {
+        branch: None,
+        upstream: None,
+        ahead: 0,
+        behind: 0,
+        files: Vec::new(),
+    };
+    while let Some(entry) = entries.next() {
+        if entry.starts_with("## ") {
+            (status.branch, status.upstream, status.ahead, status.behind) = parse_branch_header(entry);
+            continue;
+        }
+        if entry.len() < 4 {
+            continue;
+        }
+        let (index, worktree) = (&entry[0..1], &entry[1..2]);
+        // With -z the source of a rename or copy follows as its own entry
+        let orig_path = if matches!(index, "R" | "C") {
+            entries.next().map(str::to_string)
+        } else {
+            None
+        };
+        status.files.push(GitFileStatus {
+            path: entry[3..].to_string(),
+            orig_path,
+            index: index.to_string(),
+            worktree: worktree.to_string(),
+        });
+    }
+    Ok(status)
+}
+
+/// Unified diff of the working tree against the index, or of the index against
+/// HEAD when `staged` is set. Limited to `path` when given.
+#[tauri::command]
+pub async fn git_diff(work_dir: String, staged: Option<bool>, path: Option<String>) -> Result<String, String> {
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+    if staged.unwrap_or(false) {
+        args.push("--cached");
+    }
+    if let Some(path) = path.as_deref().filter(|p| !p.is_empty()) {
+        args.push("--");
+        args.push(path);
+    }
+    run_git(&work_dir, &args).await
+}
+
+#[tauri::command]
+pub async fn git_log(work_dir: String, limit: Option<usize>) -> Result<Vec<GitCommit>, String> {
+    let count = format!("-n{}", limit.unwrap_or(20).clamp(1, 500));
+    match run_git(&work_dir, &["log", &count, LOG_FORMAT]).await {
+        Ok(raw) => Ok(parse_log(&raw)),
+        // A repository without commits has no log rather than an error
+        Err(err) if err.contains("does not have any commits") => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Commit `paths` (staged first), everything when `all` is set, or whatever is
+/// already staged otherwise. Returns the new commit.
+#[tauri::command]
+pub async fn git_commit(
+    work_dir: String,
+    message: String,
+    paths: Option<Vec<String>>,
+    all: Option<bool>,
+) -> Result<GitCommit, String> {
+    if message.trim().is_empty() {
+        return Err("Commit message cannot be empty".to_string());
+    }
+    let paths = paths.unwrap_or_default();
+    if all.unwrap_or(false) {
+        run_git(&work_dir, &["add", "-A"]).await?;
+    } else if !paths.is_empty() {
+        let mut args = vec!["add", "--"];
+        args.extend(paths.iter().map(String::as_str));
+        run_git(&work_dir, &args).await?;
+    }
+    run_git(&work_dir, &["commit", "-m", message.trim()]).await?;
+    parse_log(&run_git(&work_dir, &["log", "-n1", LOG_FORMAT]).await?)
+        .pop()
+        .ok_or_else(|| "Committed, but the new commit could not be read".to_string())
+}
+
+#[tauri::command]
+pub async fn git_branch_info(work_dir: String) -> Result<GitBranchInfo, String> {
+    let head = run_git(&work_dir, &["rev-parse", "--abbrev-ref", "HEAD"])
+        .await
+        .map(|head| head.trim().to_string())
+        .unwrap_or_else(|_| "HEAD".to_string());
+    let branches = run_git(&work_dir, &["branch", "--format=%(refname:short)"])
+        .await?
+        .lines()
+        .map(str::to_string)
+        .filter(|branch| !branch.is_empty())
+        .collect();
+    let upstream = run_git(&work_dir, &["rev-parse", "--abbrev-ref", "@{upstream}"])
+        .await
+        .ok()
+        .map(|upstream| upstream.trim().to_string());
+    let (ahead, behind) = match upstream {
+        Some(_) => run_git(&work_dir, &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
+            .await
+            .ok()
+            .and_then(|counts| {
+                let mut counts = counts.split_whitespace().map(|n| n.parse().unwrap_or(0));
+                Some((counts.next()?, counts.next()?))
+            })
+            .unwrap_or((0, 0)),
+        None => (0, 0),
+    };
+    Ok(GitBranchInfo {
+        current: (head != "HEAD").then(|| head.clone()),
+        head: run_git(&work_dir, &["rev-parse", "--short", "HEAD"])
+            .await
+            .map(|hash| hash.trim().to_string())
+            .unwrap_or_default(),
+        upstream,
+        ahead,
+        behind,
+        branches,
+    })
+}
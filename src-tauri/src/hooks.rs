@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::tools::ToolOutput;
+
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 30;
+
+/// Shell command run on an agent event, with the event as JSON on stdin.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Hook {
+    pub command: String,
+    /// Tools the hook runs for; every tool when empty. Ignored by `turn_end`
+    pub tools: Vec<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+impl Hook {
+    fn runs_for(&self, tool: &str) -> bool {
+        !self.command.trim().is_empty()
+            && (self.tools.is_empty() || self.tools.iter().any(|name| name == tool))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS))
+    }
+}
+
+/// User commands run around tool calls and at the end of each turn. A
+/// `pre_tool_use` hook that exits non-zero, fails to start or times out
+/// blocks the call, and its stderr is passed on to the model.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HooksSettings {
+    pub pre_tool_use: Vec<Hook>,
+    pub post_tool_use: Vec<Hook>,
+    pub turn_end: Vec<Hook>,
+}
+
+/// Run `command` through the shell in `work_dir` with `input` on stdin.
+pub async fn run_command(
+    command: &str,
+    work_dir: &str,
+    input: &str,
+    timeout: Duration,
+) -> Result<Output, String> {
+    run_command_with_env(command, work_dir, &HashMap::new(), input, timeout).await
+}
+
+/// `run_command` with extra environment variables.
+pub async fn run_command_with_env(
+    command: &str,
+    work_dir: &str,
+    env: &HashMap<String, String>,
+    input: &str,
+    timeout: Duration,
+) -> Result<Output, String> {
+    let (shell, shell_args) = crate::tools::shell_command(command);
+    let mut cmd = tokio::process::Command::new(shell);
+    crate::child_env::scrub(&mut cmd);
+    let mut child = cmd
+        .args(shell_args)
+        .current_dir(work_dir)
+        .envs(env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| format!("Failed to start `{}`: {}", command, error))?;
+    // Write while reading the output, all within the timeout: a hook that
+    // doesn't read its input, or fills its stdout first, would otherwise
+    // block the write forever
+    let stdin = child.stdin.take();
+    let write = async move {
+        if let Some(mut stdin) = stdin {
+            // A hook that ignores its input closes stdin early; that's fine
+            let _ = stdin.write_all(input.as_bytes()).await;
+        }
+    };
+    let run = async { tokio::join!(write, child.wait_with_output()).1 };
+    match tokio::time::timeout(timeout, run).await {
+        Ok(result) => result.map_err(|error| format!("`{}` failed: {}", command, error)),
+        Err(_) => Err(format!("`{}` timed out after {}s", command, timeout.as_secs())),
+    }
+}
+
+/// Err with the reason when a hook blocks the call.
+pub async fn pre_tool_use(
+    hooks: &HooksSettings,
+    session_id: &str,
+    work_dir: &str,
+    name: &str,
+    args: &serde_json::Value,
+) -> Result<(), String> {
+    let input = serde_json::json!({
+        "event": "pre_tool_use",
+        "session_id": session_id,
+        "work_dir": work_dir,
+        "tool": name,
+        "args": args,
+    })
+    .to_string();
+    for hook in hooks.pre_tool_use.iter().filter(|hook| hook.runs_for(name)) {
+        let output = run_command(&hook.command, work_dir, &input, hook.timeout()).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(if stderr.is_empty() {
+                format!("`{}` exited with {}", hook.command, output.status)
+            } else {
+                stderr
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Failures are ignored: the call has already happened.
+pub async fn post_tool_use(
+    hooks: &HooksSettings,
+    session_id: &str,
+    work_dir: &str,
+    name: &str,
+    args: &serde_json::Value,
+    result: &ToolOutput,
+) {
+    let input = serde_json::json!({
+        "event": "post_tool_use",
+        "session_id": session_id,
+        "work_dir": work_dir,
+        "tool": name,
+        "args": args,
+        "ok": result.ok,
+        "summary": result.summary,
+        "output": result.output,
+    })
+    .to_string();
+    for hook in hooks.post_tool_use.iter().filter(|hook| hook.runs_for(name)) {
+        let _ = run_command(&hook.command, work_dir, &input, hook.timeout()).await;
+    }
+}
+
+/// Runs however the turn ended; `error` is None when it completed.
+pub async fn turn_end(
+    hooks: &HooksSettings,
+    session_id: &str,
+    work_dir: &str,
+    error: Option<&str>,
+) {
+    let input = serde_json::json!({
+        "event": "turn_end",
+        "session_id": session_id,
+        "work_dir": work_dir,
+        "ok": error.is_none(),
+        "error": error,
+    })
+    .to_string();
+    for hook in hooks.turn_end.iter().filter(|hook| !hook.command.trim().is_empty()) {
+        let _ = run_command(&hook.command, work_dir, &input, hook.timeout()).await;
+    }
+}
@@ -0,0 +1,303 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error as _;
+use std::sync::RwLock;
+
+use crate::oauth::common_headers;
+
+/// How requests to a provider are authenticated. Set as `auth_scheme` on the
+/// login and named credentials in `gui_auth.json`, and on each entry of the
+/// config's `providers` table; the API key is the secret for every scheme.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <key>`
+    #[default]
+    Bearer,
+    /// A custom header whose value template contains `{key}`, e.g. `X-Api-Key: {key}`.
+    Header { name: String, template: String },
+    /// HMAC-SHA256 over `METHOD\npath?query\ntimestamp\nhex(sha256(body))`, sent as
+    /// `<header>: HMAC-SHA256 Credential=<key_id>, Signature=<hex>` plus `X-Timestamp`.
+    Hmac {
+        key_id: String,
+        #[serde(default)]
+        header: Option<String>,
+    },
+    /// mTLS with PEM client certificate and PKCS#8 key; the bearer token is still
+    /// sent unless `send_bearer` is false.
+    ClientCert {
+        cert_path: String,
+        key_path: String,
+        #[serde(default = "default_true")]
+        send_bearer: bool,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Trust configuration applied to every outbound client (LLM, OAuth, tools).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TlsSettings {
+    /// PEM or DER files with extra CA certificates, e.g. a TLS-intercepting proxy's root
+    #[serde(default)]
+    pub ca_certs: Vec<String>,
+    /// Trust the OS certificate store in addition to `ca_certs`
+    #[serde(default = "default_true")]
+    pub system_store: bool,
+}
+
+impl Default for TlsSettings {
+    fn default() -> Self {
+        Self {
+            ca_certs: Vec::new(),
+            system_store: true,
+        }
+    }
+}
+
+static TLS: RwLock<Option<TlsSettings>> = RwLock::new(None);
+
+pub fn set_tls(settings: TlsSettings) {
+    *TLS.write().unwrap_or_else(|e| e.into_inner()) = Some(settings);
+}
+
+fn tls_settings() -> TlsSettings {
+    TLS.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+fn load_certificates(path: &str) -> Result<Vec<reqwest::Certificate>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read CA certificate {}: {}", path, e))?;
+    if bytes.starts_with(b"-----BEGIN") {
+        reqwest::Certificate::from_pem_bundle(&bytes)
+    } else {
+        reqwest::Certificate::from_der(&bytes).map(|cert| vec![cert])
+    }
+    .map_err(|e| format!("Invalid CA certificate {}: {}", path, e))
+}
+
+/// Client builder with the configured trust roots and compression.
+pub fn builder() -> Result<reqwest::ClientBuilder, String> {
+    let tls = tls_settings();
+    let compress = crate::network::is_low_bandwidth();
+    let mut builder = reqwest::Client::builder()
+        .tls_built_in_root_certs(tls.system_store)
+        .gzip(compress)
+        .brotli(compress);
+    for path in tls.ca_certs.iter().filter(|path| !path.trim().is_empty()) {
+        for cert in load_certificates(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder)
+}
+
+/// Client for requests that don't carry provider credentials.
+pub fn plain_client() -> Result<reqwest::Client, String> {
+    builder()?
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Token, base URL and auth scheme for the active provider.
+#[derive(Clone)]
+pub struct Credentials {
+    pub token: String,
+    pub base: String,
+    pub scheme: AuthScheme,
+    /// Extra headers the provider expects on every request
+    pub headers: Vec<(String, String)>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// HTTP client for the provider, carrying the client identity for mTLS.
+pub fn client(credentials: &Credentials) -> Result<reqwest::Client, String> {
+    let mut builder = builder()?;
+    if let AuthScheme::ClientCert {
+        cert_path, key_path, ..
+    } = &credentials.scheme
+    {
+        let cert = std::fs::read(cert_path)
+            .map_err(|e| format!("Failed to read client certificate {}: {}", cert_path, e))?;
+        let key = std::fs::read(key_path)
+            .map_err(|e| format!("Failed to read client key {}: {}", key_path, e))?;
+        let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key)
+            .map_err(|e| format!("Invalid client certificate: {}", e))?;
+        builder = builder.identity(identity);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Build an authenticated request with the common headers. `body` is sent as JSON
+/// and is what HMAC signatures cover.
+pub fn request(
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    method: reqwest::Method,
+    url: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<reqwest::RequestBuilder, String> {
+    let body = body
+        .map(serde_json::to_vec)
+        .transpose()
+        .map_err(|e| format!("Failed to serialize request: {}", e))?;
+
+    let mut req = client.request(method.clone(), url);
+    for (key, value) in common_headers().into_iter() {
+        req = req.header(key, value);
+    }
+    for (key, value) in &credentials.headers {
+        req = req.header(key.as_str(), value.as_str());
+    }
+
+    let token = &credentials.token;
+    req = match &credentials.scheme {
+        // Local servers such as Ollama run without a key
+        AuthScheme::Bearer if token.is_empty() => req,
+        AuthScheme::Bearer => req.header("Authorization", format!("Bearer {}", token)),
+        AuthScheme::Header { name, template } => {
+            req.header(name.as_str(), template.replace("{key}", token))
+        }
+        AuthScheme::Hmac { key_id, header } => {
+            let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            let body_hash = to_hex(&Sha256::digest(body.as_deref().unwrap_or_default()));
+            let canonical = format!("{}\n{}\n{}\n{}", method.as_str(), path, timestamp, body_hash);
+
+            let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes())
+                .map_err(|e| format!("Invalid HMAC secret: {}", e))?;
+            mac.update(canonical.as_bytes());
+            let signature = to_hex(&mac.finalize().into_bytes());
+
+            req.header("X-Timestamp", timestamp).header(
+                header.as_deref().unwrap_or("Authorization"),
+                format!("HMAC-SHA256 Credential={}, Signature={}", key_id, signature),
+            )
+        }
+        AuthScheme::ClientCert { send_bearer, .. } => {
+            if *send_bearer && !token.is_empty() {
+                req.header("Authorization", format!("Bearer {}", token))
+            } else {
+                req
+            }
+        }
+    };
+
+    if let Some(body) = body {
+        req = req.header("Content-Type", "application/json").body(body);
+    }
+    Ok(req)
+}
+
+#[derive(Clone, Serialize)]
+pub struct TlsDiagnosis {
+    pub url: String,
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// Plain-language cause of a TLS failure, when recognisable
+    pub reason: Option<String>,
+    pub ca_certs: Vec<String>,
+    pub system_store: bool,
+}
+
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn tls_failure_reason(message: &str) -> Option<String> {
+    let lower = message.to_lowercase();
+    let reason = if lower.contains("self signed") || lower.contains("self-signed") {
+        "The server presented a self-signed certificate. Add it (or your proxy's root CA) to the trusted CA certificates."
+    } else if lower.contains("unable to get local issuer")
+        || lower.contains("unknown issuer")
+        || lower.contains("untrusted")
+        || lower.contains("not trusted")
+    {
+        "The certificate chain is not trusted. A TLS-intercepting proxy is likely; add its root CA to the trusted CA certificates."
+    } else if lower.contains("expired") {
+        "The server certificate has expired or the system clock is wrong."
+    } else if lower.contains("hostname") || lower.contains("not valid for") || lower.contains("doesn't match") {
+        "The certificate does not match the host name."
+    } else if lower.contains("certificate") || lower.contains("handshake") || lower.contains("ssl") || lower.contains("tls") {
+        "The TLS handshake failed."
+    } else {
+        return None;
+    };
+    Some(reason.to_string())
+}
+
+/// Connect to `url` (default: the active provider) with the configured trust
+/// roots and explain any TLS failure.
+#[tauri::command]
+pub async fn tls_diagnose(url: Option<String>) -> Result<TlsDiagnosis, String> {
+    let url = url
+        .filter(|u| !u.trim().is_empty())
+        .unwrap_or_else(|| crate::llm::provider_base(&crate::load_auth_config()));
+    let tls = tls_settings();
+    let mut diagnosis = TlsDiagnosis {
+        url: url.clone(),
+        ok: false,
+        status: None,
+        error: None,
+        reason: None,
+        ca_certs: tls.ca_certs.clone(),
+        system_store: tls.system_store,
+    };
+
+    let client = match plain_client() {
+        Ok(client) => client,
+        Err(err) => {
+            diagnosis.reason = Some("A configured CA certificate could not be loaded.".to_string());
+            diagnosis.error = Some(err);
+            return Ok(diagnosis);
+        }
+    };
+
+    match client
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+    {
+        Ok(response) => {
+            diagnosis.ok = true;
+            diagnosis.status = Some(response.status().as_u16());
+        }
+        Err(err) => {
+            let message = error_chain(&err);
+            diagnosis.reason = tls_failure_reason(&message).or_else(|| {
+                if err.is_timeout() {
+                    Some("The connection timed out.".to_string())
+                } else if err.is_connect() {
+                    Some("Could not connect to the host.".to_string())
+                } else {
+                    None
+                }
+            });
+            diagnosis.error = Some(message);
+        }
+    }
+    Ok(diagnosis)
+}
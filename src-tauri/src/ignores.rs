@@ -0,0 +1,85 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Used when the settings don't set `ignore_patterns`
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    ".*",
+    "node_modules/",
+    "target/",
+    "dist/",
+    "build/",
+    "venv/",
+    "__pycache__/",
+];
+
+/// Per-project additions, in gitignore syntax, read after the global list
+pub const PROJECT_FILE: &str = ".kimi/ignore";
+
+/// Names left out of file listings, the file index and the directory summary
+/// in the system prompt. Patterns use gitignore syntax, so a project can
+/// re-include something the global list hides with `!pattern`.
+#[derive(Clone)]
+pub struct IgnoreList {
+    root: std::path::PathBuf,
+    matcher: Gitignore,
+}
+
+impl IgnoreList {
+    pub fn load(work_dir: &Path) -> Self {
+        let global = crate::gui_settings_load(None)
+            .ok()
+            .and_then(|payload| payload.settings.ignore_patterns);
+        let project = crate::read_text(&work_dir.join(PROJECT_FILE)).unwrap_or_default();
+        Self::from_patterns(work_dir, global.as_deref(), &project)
+    }
+
+    fn from_patterns(work_dir: &Path, global: Option<&[String]>, project: &str) -> Self {
+        let mut builder = GitignoreBuilder::new(work_dir);
+        let defaults: Vec<String> = DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect();
+        for pattern in global.unwrap_or(&defaults).iter().map(String::as_str).chain(project.lines()) {
+            // A bad pattern shouldn't hide the rest of the list
+            let _ = builder.add_line(None, pattern);
+        }
+        Self {
+            root: work_dir.to_path_buf(),
+            matcher: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+        }
+    }
+
+    /// `path` (absolute, or relative to the work dir) or one of its parent
+    /// folders is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        if rel.as_os_str().is_empty() || rel.is_absolute() {
+            return false;
+        }
+        self.matcher
+            .matched_path_or_any_parents(rel, is_dir)
+            .is_ignore()
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct IgnorePatterns {
+    pub defaults: Vec<String>,
+    /// Patterns from the project's `.kimi/ignore`, if it has one
+    pub project: Vec<String>,
+    pub project_file: String,
+}
+
+#[tauri::command]
+pub fn ignore_patterns(work_dir: String) -> Result<IgnorePatterns, String> {
+    let project_file = Path::new(&work_dir).join(PROJECT_FILE);
+    let project = crate::read_text(&project_file)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    Ok(IgnorePatterns {
+        defaults: DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        project,
+        project_file: project_file.to_string_lossy().to_string(),
+    })
+}
@@ -1,11 +1,49 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
+use tracing::Instrument;
 use uuid::Uuid;
 
-use crate::oauth::{common_headers, ensure_fresh_token};
+use crate::approvals;
+use crate::attachments;
+use crate::audit;
+use crate::checkpoints;
+use crate::claims;
+use crate::code_search;
+use crate::context;
+use crate::cost;
+use crate::custom_tools;
+use crate::diagnostics;
+use crate::docs;
+use crate::downloads;
+use crate::evidence;
+use crate::file_ops;
+use crate::forge;
+use crate::hooks;
+use crate::http::{self, AuthScheme, Credentials};
+use crate::oauth::{self, ensure_fresh_token};
+use crate::parts::MessagePart;
+use crate::patch;
+use crate::paths;
+use crate::policy;
+use crate::processes;
+use crate::project_commands;
+use crate::providers;
+use crate::reauth;
+use crate::resume;
+use crate::scratch;
+use crate::semantic;
+use crate::session::ToolCall;
+use crate::skills;
+use crate::stats;
+use crate::symbols;
+use crate::text_tools;
 use crate::tools;
+use crate::watchdog;
 use crate::AppState;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
 
 #[derive(Clone, Serialize)]
 pub struct StreamEvent {
@@ -13,9 +51,40 @@ pub struct StreamEvent {
     pub data: serde_json::Value,
 }
 
-const MAX_TOOL_STEPS: usize = 20;
+const RETRY_DELAY_SECS: u64 = 1;
+/// First backoff of a failed model request, doubled on each further attempt
+const BACKOFF_BASE_SECS: u64 = 2;
+const DEFAULT_MAX_RETRY_DELAY_SECS: u64 = 120;
 
-fn api_base_url() -> String {
+/// `loop_control.max_steps_per_turn` and `max_retries_per_step` from the config
+/// file, falling back to the defaults written by `default_config_data`.
+fn loop_limits(config_path: Option<&str>) -> (usize, u32) {
+    let config = tools::load_config_value(config_path).unwrap_or_default();
+    let defaults = crate::default_config_data();
+    let limit = |key: &str| {
+        config["loop_control"][key]
+            .as_u64()
+            .or_else(|| defaults["loop_control"][key].as_u64())
+            .unwrap_or(0)
+    };
+    (limit("max_steps_per_turn").max(1) as usize, limit("max_retries_per_step") as u32)
+}
+
+fn emit_cancelled(window: &tauri::Window, session_id: &str) {
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: "cancelled".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+            }),
+        },
+    );
+}
+
+/// OAuth base URL when the profile doesn't pin an endpoint. The env vars are a
+/// developer fallback; users pick the endpoint at login.
+pub fn api_base_url() -> String {
     std::env::var("KIMI_CODE_BASE_URL")
         .or_else(|_| std::env::var("KIMI_BASE_URL"))
         .unwrap_or_else(|_| "https://api.kimi.com/coding/v1".to_string())
@@ -24,17 +93,17 @@ fn api_base_url() -> String {
 /// Generate a detailed directory listing like `ls -la`
 fn list_directory(work_dir: &str) -> String {
     let work_path = Path::new(work_dir);
+    let ignores = crate::ignores::IgnoreList::load(work_path);
     let mut entries: Vec<(String, bool, u64)> = Vec::new();
     
     if let Ok(dir_entries) = std::fs::read_dir(work_path) {
         for entry in dir_entries.flatten() {
             let name = entry.file_name().to_string_lossy().to_string();
-            // Skip hidden files and common build directories
-            if name.starts_with('.') || name == "target" || name == "node_modules" || name == "dist" || name == "build" {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if ignores.is_ignored(&entry.path(), is_dir) {
                 continue;
             }
             
-            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
             let size = if let Ok(metadata) = entry.metadata() {
                 metadata.len()
             } else {
@@ -95,24 +164,164 @@ fn load_agents_md(work_dir: &str) -> Option<String> {
     None
 }
 
-fn generate_system_prompt(work_dir: &str) -> String {
+/// Most of the system prompt a directory listing may take
+const LISTING_BUDGET_TOKENS: usize = 1_500;
+/// Most of the system prompt AGENTS.md may take
+const AGENTS_MD_BUDGET_TOKENS: usize = 4_000;
+
+/// One part of the system prompt and what trimming it cost.
+#[derive(Clone, Serialize)]
+pub struct PromptComponent {
+    pub name: String,
+    pub tokens: usize,
+    /// Size before trimming; equal to `tokens` when nothing was cut
+    pub original_tokens: usize,
+    pub trimmed: bool,
+}
+
+impl PromptComponent {
+    fn new(name: &str, original: &str, kept: &str) -> Self {
+        let tokens = context::estimate_tokens(kept);
+        let original_tokens = context::estimate_tokens(original);
+        Self {
+            name: name.to_string(),
+            tokens,
+            original_tokens,
+            trimmed: tokens < original_tokens,
+        }
+    }
+}
+
+pub struct SystemPrompt {
+    pub text: String,
+    pub components: Vec<PromptComponent>,
+}
+
+/// First lines of a listing that fit `budget`, with a count of what was left out.
+fn trim_listing(listing: &str, budget: usize) -> String {
+    if context::estimate_tokens(listing) <= budget {
+        return listing.to_string();
+    }
+    let lines: Vec<&str> = listing.lines().collect();
+    let mut kept = String::new();
+    let mut count = 0;
+    for line in &lines {
+        if context::estimate_tokens(&kept) + context::estimate_tokens(line) + 1 > budget {
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+        count += 1;
+    }
+    kept.push_str(&format!(
+        "... {} more entries not shown; list the folder with Shell to see them\n",
+        lines.len() - count
+    ));
+    kept
+}
+
+/// Sections of a Markdown file in order while they fit `budget`; sections
+/// past that keep only their heading, so the model still sees what the file
+/// covers and can read the rest with ReadFile.
+fn trim_markdown(name: &str, text: &str, budget: usize) -> String {
+    if context::estimate_tokens(text) <= budget {
+        return text.to_string();
+    }
+    let mut sections: Vec<String> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with('#') || sections.is_empty() {
+            sections.push(String::new());
+        }
+        if let Some(section) = sections.last_mut() {
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+
+    let mut kept = String::new();
+    let mut omitted = Vec::new();
+    for section in &sections {
+        if omitted.is_empty()
+            && context::estimate_tokens(&kept) + context::estimate_tokens(section) <= budget
+        {
+            kept.push_str(section);
+        } else if let Some(heading) = section.lines().next().filter(|line| line.starts_with('#')) {
+            omitted.push(heading.to_string());
+        }
+    }
+    if kept.is_empty() {
+        // A single huge section; fall back to its beginning
+        kept = text.chars().take(budget * 4).collect();
+        kept.push('\n');
+    }
+    kept.push_str(&format!(
+        "\n[{} is trimmed to fit the context; read the file for the full text.",
+        name
+    ));
+    if !omitted.is_empty() {
+        kept.push_str(" Sections not included:\n");
+        kept.push_str(&omitted.join("\n"));
+    }
+    kept.push_str("]\n");
+    kept
+}
+
+pub fn generate_system_prompt(work_dir: &str) -> String {
+    system_prompt(work_dir).text
+}
+
+/// The system prompt with each project-dependent part trimmed to its budget.
+pub fn system_prompt(work_dir: &str) -> SystemPrompt {
     let mut prompt = String::new();
+    let mut components = Vec::new();
+
+    // Low-bandwidth mode leaves discovery to the tools instead of sending it every turn
+    if crate::network::is_low_bandwidth() {
+        prompt.push_str(&format!("Current working directory: {}\n", work_dir));
+        return SystemPrompt {
+            text: prompt,
+            components,
+        };
+    }
     
     // Add directory listing
     let ls_output = list_directory(work_dir);
+    let listing = trim_listing(&ls_output, LISTING_BUDGET_TOKENS);
+    components.push(PromptComponent::new("Directory listing", &ls_output, &listing));
     prompt.push_str(&format!(
         "Current working directory: {}\n\nDirectory listing:\n{}\n",
-        work_dir, ls_output
+        work_dir, listing
     ));
+
+    // Filled in by `stream_chat` before it builds the prompt
+    if let Some(stats) = stats::cached(work_dir).filter(|_| stats::in_prompt()) {
+        let summary = stats::prompt_summary(&stats);
+        components.push(PromptComponent::new("Workspace overview", &summary, &summary));
+        prompt.push_str("\nWorkspace overview:\n");
+        prompt.push_str(&summary);
+    }
+
+    let declared = project_commands::load(work_dir).unwrap_or_default();
+    if !declared.is_empty() {
+        let summary = project_commands::prompt_summary(&declared);
+        components.push(PromptComponent::new("Project commands", &summary, &summary));
+        prompt.push_str("\nProject commands (run tests and builds with the RunTests and Build tools, which use these):\n");
+        prompt.push_str(&summary);
+    }
     
     // Add AGENTS.md if exists
     if let Some(agents_md) = load_agents_md(work_dir) {
+        let kept = trim_markdown("AGENTS.md", &agents_md, AGENTS_MD_BUDGET_TOKENS);
+        components.push(PromptComponent::new("AGENTS.md", &agents_md, &kept));
         prompt.push_str("\nAGENTS.md:\n");
-        prompt.push_str(&agents_md);
+        prompt.push_str(&kept);
         prompt.push('\n');
     }
     
-    prompt
+    SystemPrompt {
+        text: prompt,
+        components,
+    }
 }
 
 fn parse_user_input(input: &str) -> String {
@@ -121,348 +330,1287 @@ fn parse_user_input(input: &str) -> String {
     input.to_string()
 }
 
-pub async fn stream_chat(
-    window: tauri::Window,
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    user_message: String,
-    model: String,
-    work_dir: String,
-    config_path: Option<String>,
-    auto_approve: bool,
-    auth_config: crate::AuthConfig,
-    mut cancel_rx: tokio::sync::oneshot::Receiver<()>,
-) -> Result<(), String> {
-    // Get auth token (OAuth or API Key)
-    let (access_token, api_base) = if auth_config.mode == "api_key" {
-        // API Key mode
-        let api_key = auth_config.api_key.ok_or_else(|| {
-            let _ = window.emit("chat://event", StreamEvent {
-                event: "error".to_string(),
-                data: serde_json::json!({
-                    "session_id": session_id,
-                    "message": "API key not configured. Please login first.",
-                }),
-            });
-            "API key not configured"
-        })?;
-        let base = auth_config.api_base
+/// API base for the configured auth mode, without touching credentials. A custom
+/// `api_base` wins over the profile's `endpoint`.
+pub fn provider_base(auth_config: &crate::AuthConfig) -> String {
+    let endpoint = auth_config
+        .endpoint
+        .as_deref()
+        .and_then(crate::endpoints::endpoint_base)
+        .map(str::to_string);
+    if auth_config.mode == "api_key" {
+        auth_config
+            .api_base
+            .clone()
             .filter(|b| !b.is_empty())
-            .unwrap_or_else(|| "https://api.moonshot.cn/v1".to_string());
-        (api_key, base)
+            .or(endpoint)
+            .unwrap_or_else(|| "https://api.moonshot.cn/v1".to_string())
+    } else {
+        endpoint.unwrap_or_else(api_base_url)
+    }
+}
+
+/// Resolve the token, API base and auth scheme for the configured auth mode.
+pub async fn resolve_credentials(auth_config: &crate::AuthConfig) -> Result<Credentials, String> {
+    let base = provider_base(auth_config);
+    if auth_config.mode == "api_key" {
+        let scheme = auth_config.auth_scheme.clone().unwrap_or_default();
+        let api_key = auth_config.api_key.clone().filter(|k| !k.is_empty());
+        let token = match (api_key, &scheme) {
+            (Some(key), _) => key,
+            // mTLS gateways may authenticate by certificate alone
+            (None, AuthScheme::ClientCert { .. }) => String::new(),
+            (None, _) => return Err("API key not configured. Please login first.".to_string()),
+        };
+        Ok(Credentials {
+            token,
+            base,
+            scheme,
+            headers: Vec::new(),
+        })
     } else {
-        // OAuth mode
         match ensure_fresh_token().await {
-            Some(token) => (token, api_base_url()),
-            None => {
-                let _ = window.emit("chat://event", StreamEvent {
-                    event: "error".to_string(),
-                    data: serde_json::json!({
-                        "session_id": session_id,
-                        "message": "Not logged in. Please login first.",
-                    }),
-                });
-                return Err("Not logged in".to_string());
+            Some(token) => Ok(Credentials {
+                token,
+                base,
+                scheme: AuthScheme::Bearer,
+                headers: Vec::new(),
+            }),
+            None => Err("Not logged in. Please login first.".to_string()),
+        }
+    }
+}
+
+/// POST a request body to `/chat/completions` and return the parsed JSON
+/// response. The usage is added to the cost ledger under `session_id`.
+pub async fn post_chat_completion(
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    request: &serde_json::Value,
+    session_id: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    crate::network::ensure_online(&credentials.base)?;
+    let url = format!("{}/chat/completions", credentials.base);
+    let req = http::request(client, credentials, reqwest::Method::POST, &url, Some(request))?;
+
+    let response = req
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("API error {}: {}", status, text));
+    }
+
+    let data: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    let reply_chars = data["choices"][0]["message"].to_string().len();
+    cost::record(
+        session_id,
+        request["model"].as_str().unwrap_or_default(),
+        &cost::prices(),
+        data.get("usage"),
+        request,
+        reply_chars,
+    );
+    Ok(data)
+}
+
+/// Assistant message assembled from a streamed response.
+#[derive(Default)]
+struct StreamedMessage {
+    content: String,
+    reasoning: String,
+    tool_calls: Vec<serde_json::Value>,
+    usage: Option<serde_json::Value>,
+}
+
+/// Merge a `tool_calls` delta into the calls accumulated so far. Providers send
+/// the id and name once and the arguments as string fragments, keyed by `index`.
+fn accumulate_tool_calls(calls: &mut Vec<serde_json::Value>, deltas: &[serde_json::Value]) {
+    for delta in deltas {
+        let index = delta
+            .get("index")
+            .and_then(|v| v.as_u64())
+            .map(|i| i as usize)
+            .unwrap_or(calls.len());
+        while calls.len() <= index {
+            calls.push(serde_json::json!({
+                "id": "",
+                "type": "function",
+                "function": { "name": "", "arguments": "" },
+            }));
+        }
+        let call = &mut calls[index];
+        if let Some(id) = delta.get("id").and_then(|v| v.as_str()) {
+            if !id.is_empty() {
+                call["id"] = serde_json::json!(id);
             }
         }
-    };
-    
-    let client = reqwest::Client::new();
+        if let Some(function) = delta.get("function") {
+            for key in ["name", "arguments"] {
+                if let Some(fragment) = function.get(key).and_then(|v| v.as_str()) {
+                    let current = call["function"][key].as_str().unwrap_or("").to_string();
+                    call["function"][key] = serde_json::json!(current + fragment);
+                }
+            }
+        }
+    }
+}
 
-    // Build system prompt with directory context
-    let system_prompt = generate_system_prompt(&work_dir);
-    let tools_def = tools::tool_definitions();
-    let mut messages = vec![
-        serde_json::json!({
-            "role": "system",
-            "content": system_prompt,
-        }),
-        serde_json::json!({
-            "role": "user",
-            "content": parse_user_input(&user_message),
-        }),
-    ];
+/// Seconds the server asks to wait, from `retry-after-ms` or `Retry-After`
+/// as either seconds or an HTTP date.
+fn retry_after_header(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<u64>().ok()) {
+        return Some(ms.div_ceil(1000));
+    }
+    let value = header("retry-after")?;
+    value.parse::<u64>().ok().or_else(|| {
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        Some((date.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64)
+    })
+}
 
-    for _ in 0..MAX_TOOL_STEPS {
-        if cancel_rx.try_recv().is_ok() {
-            let _ = window.emit(
-                "chat://event",
-                StreamEvent {
-                    event: "cancelled".to_string(),
-                    data: serde_json::json!({
-                        "session_id": session_id,
-                    }),
-                },
-            );
-            return Ok(());
+/// A failed model request. HTTP errors carry the status and `Retry-After`,
+/// so the retry logic doesn't have to read them back out of `message`.
+struct RequestError {
+    message: String,
+    status: Option<u16>,
+    /// Seconds the server asked to wait before trying again
+    retry_after: Option<u64>,
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            status: None,
+            retry_after: None,
         }
+    }
+}
 
-        let request = serde_json::json!({
-            "model": model,
-            "messages": messages.clone(),
-            "stream": false,
-            "temperature": serde_json::Value::Null,
-            "tools": tools_def.clone(),
-            "tool_choice": "auto",
+/// Seconds to wait before retrying a failed model request: the server's
+/// `Retry-After` when it sent one, otherwise exponential backoff with jitter
+/// so concurrent sessions don't retry in lockstep. None for errors a retry
+/// won't fix, such as a rejected request, or a wait longer than `max_delay_secs`.
+fn retry_delay(error: &RequestError, attempt: u32, max_delay_secs: u64) -> Option<u64> {
+    if error.message == crate::network::OFFLINE_ERROR {
+        return None;
+    }
+    if error
+        .status
+        .is_some_and(|status| status < 500 && !matches!(status, 408 | 409 | 425 | 429))
+    {
+        return None;
+    }
+    if let Some(secs) = error.retry_after {
+        return (secs <= max_delay_secs).then_some(secs.max(1));
+    }
+    let ceiling = (BACKOFF_BASE_SECS << attempt.saturating_sub(1).min(16))
+        .min(max_delay_secs)
+        .max(1);
+    let jitter = (uuid::Uuid::new_v4().as_u128() % (ceiling / 2 + 1) as u128) as u64;
+    Some(ceiling - ceiling / 2 + jitter)
+}
+
+/// POST a streaming request to `/chat/completions`, emitting `chunk` and
+/// `thinking` events as deltas arrive, and return the assembled message.
+/// Waits for the response and for each event go through `watchdog::watch`,
+/// so a model that goes quiet keeps the UI informed.
+async fn stream_chat_completion(
+    window: &tauri::Window,
+    session_id: &str,
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    request: &serde_json::Value,
+    soft_timeout_secs: u64,
+) -> Result<StreamedMessage, RequestError> {
+    crate::network::ensure_online(&credentials.base)?;
+    let url = format!("{}/chat/completions", credentials.base);
+    let req = http::request(client, credentials, reqwest::Method::POST, &url, Some(request))?
+        .header("Accept", "text/event-stream");
+
+    let response = watchdog::watch(window, session_id, soft_timeout_secs, req.send())
+        .await?
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let retry_after = retry_after_header(response.headers());
+        let text = response.text().await.unwrap_or_default();
+        return Err(RequestError {
+            message: format!("API error {}: {}", status, text),
+            status: Some(status.as_u16()),
+            retry_after,
         });
+    }
+
+    let mut message = StreamedMessage::default();
+    let mut events = response.bytes_stream().eventsource();
+    while let Some(event) = watchdog::watch(window, session_id, soft_timeout_secs, events.next()).await? {
+        let event = event.map_err(|e| format!("Stream error: {}", e))?;
+        let data = event.data.trim();
+        if data.is_empty() {
+            continue;
+        }
+        if data == "[DONE]" {
+            break;
+        }
+        let chunk: serde_json::Value = match serde_json::from_str(data) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+        if let Some(error) = chunk.get("error") {
+            let detail = error
+                .get("message")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string());
+            return Err(format!("API error: {}", detail).into());
+        }
 
-        let mut req = client.post(format!("{}/chat/completions", api_base));
-        for (key, value) in common_headers().into_iter() {
-            req = req.header(key, value);
+        let choice = chunk.get("choices").and_then(|v| v.get(0));
+        // Usage arrives on the final chunk, at the top level or on the choice
+        if let Some(usage) = chunk
+            .get("usage")
+            .or_else(|| choice.and_then(|c| c.get("usage")))
+            .filter(|u| !u.is_null())
+        {
+            message.usage = Some(usage.clone());
         }
-        req = req.header("Authorization", format!("Bearer {}", access_token));
+        let delta = match choice.and_then(|c| c.get("delta")) {
+            Some(delta) => delta,
+            None => continue,
+        };
 
-        let response = tokio::select! {
-            _ = &mut cancel_rx => {
+        if let Some(reasoning) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
+            if !reasoning.is_empty() {
+                message.reasoning.push_str(reasoning);
                 let _ = window.emit(
                     "chat://event",
                     StreamEvent {
-                        event: "cancelled".to_string(),
+                        event: "thinking".to_string(),
                         data: serde_json::json!({
                             "session_id": session_id,
+                            "content": reasoning,
                         }),
                     },
                 );
-                return Ok(());
             }
-            resp = req.json(&request).send() => resp,
         }
-        .map_err(|e| format!("Request failed: {}", e))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("API error {}: {}", status, text));
+        if let Some(content) = delta.get("content").and_then(|v| v.as_str()) {
+            if !content.is_empty() {
+                message.content.push_str(content);
+                let state = window.state::<AppState>();
+                state.mirrors.chunk(session_id, content);
+                state.shares.chunk(session_id, content);
+                let _ = window.emit(
+                    "chat://event",
+                    StreamEvent {
+                        event: "chunk".to_string(),
+                        data: serde_json::json!({
+                            "session_id": session_id,
+                            "content": content,
+                        }),
+                    },
+                );
+            }
+        }
+        if let Some(deltas) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+            accumulate_tool_calls(&mut message.tool_calls, deltas);
         }
+    }
 
-        let data: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+    Ok(message)
+}
 
-        let message = data
-            .get("choices")
-            .and_then(|v| v.get(0))
-            .and_then(|v| v.get("message"))
-            .cloned()
-            .ok_or_else(|| "No message in response".to_string())?;
+/// Body of a streaming `/chat/completions` request. With `text_tools` the
+/// tools are described in the prompt instead of sent as `tools`.
+fn chat_request(
+    api_model: &str,
+    messages: &[serde_json::Value],
+    tools_def: &[serde_json::Value],
+    text_tools: bool,
+) -> serde_json::Value {
+    let mut request = serde_json::json!({
+        "model": api_model,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "temperature": serde_json::Value::Null,
+    });
+    if text_tools {
+        request["messages"] = serde_json::json!(text_tools::convert(messages, tools_def));
+    } else {
+        request["messages"] = serde_json::json!(messages);
+        request["tools"] = serde_json::json!(tools_def);
+        request["tool_choice"] = serde_json::json!("auto");
+    }
+    request
+}
 
-        let reasoning = message
-            .get("reasoning_content")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        if !reasoning.is_empty() {
-            let _ = window.emit(
-                "chat://event",
-                StreamEvent {
-                    event: "thinking".to_string(),
-                    data: serde_json::json!({
-                        "session_id": session_id,
-                        "content": reasoning,
-                    }),
-                },
-            );
-        }
+/// Warn that the turn runs with tools described in the prompt, which is less
+/// reliable than native tool calling.
+fn emit_text_tools_fallback(window: &tauri::Window, session_id: &str, reason: &str) {
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: "degraded_mode".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "mode": "text_tools",
+                "message": format!("Tool calls fall back to a text protocol. {}", reason),
+            }),
+        },
+    );
+}
 
-        let tool_calls = message.get("tool_calls").and_then(|v| v.as_array()).cloned();
-        let content = message
-            .get("content")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+/// Assistant message for the session store, with reasoning and tool refs as parts.
+fn assistant_record(content: &str, reasoning: &str, tool_calls: &[serde_json::Value]) -> crate::Message {
+    let mut message = crate::Message::new("assistant", content.to_string());
+    if !reasoning.is_empty() {
+        message.parts.insert(
+            0,
+            MessagePart::Reasoning {
+                text: reasoning.to_string(),
+            },
+        );
+    }
+    if !tool_calls.is_empty() {
+        let calls: Vec<ToolCall> = tool_calls
+            .iter()
+            .map(|call| ToolCall {
+                id: call["id"].as_str().unwrap_or("").to_string(),
+                name: call["function"]["name"].as_str().unwrap_or("").to_string(),
+                arguments: call["function"]["arguments"].as_str().unwrap_or("").to_string(),
+            })
+            .collect();
+        message.parts.extend(calls.iter().map(|call| MessagePart::ToolRef {
+            id: call.id.clone(),
+            name: call.name.clone(),
+        }));
+        message.tool_calls = Some(calls);
+    }
+    message
+}
+
+/// Earlier session messages in API form. Tool calls without a recorded result
+/// (e.g. a cancelled turn) and orphaned results are dropped, since the API
+/// rejects unpaired tool messages.
+pub fn history_messages(history: &[crate::Message]) -> Vec<serde_json::Value> {
+    let answered: HashSet<&str> = history
+        .iter()
+        .filter(|m| m.role == "tool")
+        .filter_map(|m| m.tool_call_id.as_deref())
+        .collect();
+    let mut called: HashSet<&str> = HashSet::new();
+    let mut messages = Vec::new();
 
-        if let Some(tool_calls) = tool_calls {
-            if !tool_calls.is_empty() {
-                let mut assistant_message = serde_json::json!({
+    for message in history {
+        match message.role.as_str() {
+            "user" => messages.push(serde_json::json!({
+                "role": "user",
+                "content": attachments::user_content(&parse_user_input(&message.content), &message.parts),
+            })),
+            "assistant" => {
+                let calls: Vec<serde_json::Value> = message
+                    .tool_calls
+                    .iter()
+                    .flatten()
+                    .filter(|call| answered.contains(call.id.as_str()))
+                    .map(|call| {
+                        called.insert(call.id.as_str());
+                        serde_json::json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": { "name": call.name, "arguments": call.arguments },
+                        })
+                    })
+                    .collect();
+                if calls.is_empty() && message.content.is_empty() {
+                    continue;
+                }
+                let mut entry = serde_json::json!({
                     "role": "assistant",
-                    "content": content,
-                    "tool_calls": tool_calls,
+                    "content": message.content,
                 });
-                if let Some(reasoning_value) = message.get("reasoning_content") {
-                    assistant_message["reasoning_content"] = reasoning_value.clone();
+                if !calls.is_empty() {
+                    entry["tool_calls"] = serde_json::json!(calls);
+                    let reasoning: String = message
+                        .parts
+                        .iter()
+                        .filter_map(|part| match part {
+                            MessagePart::Reasoning { text } => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect();
+                    if !reasoning.is_empty() {
+                        entry["reasoning_content"] = serde_json::json!(reasoning);
+                    }
+                }
+                messages.push(entry);
+            }
+            "tool" => {
+                if let Some(id) = message.tool_call_id.as_deref().filter(|id| called.contains(id)) {
+                    messages.push(serde_json::json!({
+                        "role": "tool",
+                        "tool_call_id": id,
+                        "content": message.content,
+                    }));
                 }
-                messages.push(assistant_message);
+            }
+            _ => {}
+        }
+    }
+    messages
+}
+
+/// Most of the request earlier session messages may take
+const HISTORY_BUDGET_TOKENS: usize = 64_000;
+/// Most of the history a single tool result may take
+const HISTORY_TOOL_RESULT_TOKENS: usize = 8_000;
 
-                let calls = messages
-                    .last()
-                    .and_then(|v| v.get("tool_calls"))
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_default();
+/// Fit API-form history into HISTORY_BUDGET_TOKENS, estimating tokens from
+/// characters as the cost ledger does. Long tool results are shortened first,
+/// then the oldest turns dropped; cuts fall on user messages so no tool result
+/// loses its call. The latest turn is kept even when it alone is over.
+fn trim_history(mut messages: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    let max_chars = HISTORY_TOOL_RESULT_TOKENS * 4;
+    for message in messages.iter_mut().filter(|m| m["role"] == "tool") {
+        let content = message["content"].as_str().unwrap_or_default();
+        if context::estimate_tokens(content) > HISTORY_TOOL_RESULT_TOKENS {
+            let kept: String = content.chars().take(max_chars).collect();
+            message["content"] =
+                serde_json::json!(format!("{kept}\n[... rest of the output left out of the history]"));
+        }
+    }
+
+    let tokens: Vec<usize> = messages
+        .iter()
+        .map(|m| context::estimate_tokens(&m.to_string()))
+        .collect();
+    let mut total: usize = tokens.iter().sum();
+    let turn_starts: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m["role"] == "user")
+        .map(|(index, _)| index)
+        .collect();
+    let mut start = 0;
+    for &next in turn_starts.iter().skip_while(|&&index| index == 0) {
+        if total <= HISTORY_BUDGET_TOKENS {
+            break;
+        }
+        total -= tokens[start..next].iter().sum::<usize>();
+        start = next;
+    }
+    messages.split_off(start)
+}
 
-                for tool_call in calls {
-                    if cancel_rx.try_recv().is_ok() {
+fn persist_message(state: &AppState, session_id: &str, message: crate::Message) {
+    let _span = tracing::info_span!("persist_message", role = %message.role).entered();
+    state.mirrors.record(session_id, &message);
+    state.shares.record(session_id, &message);
+    if let Ok(mut manager) = state.session_manager.lock() {
+        let _ = manager.append_message(session_id, message);
+    }
+}
+
+/// Tools retried by default: their failures are usually network hiccups
+const DEFAULT_RETRY_TOOLS: &[&str] = &["FetchURL", "SearchWeb"];
+
+/// Re-run failed tool calls before the model sees the failure, so a transient
+/// error doesn't cost a step.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ToolRetrySettings {
+    pub max_retries: u32,
+    /// Tools that are retried; FetchURL and SearchWeb when empty. Tools that
+    /// change anything are never retried, listed or not.
+    pub tools: Vec<String>,
+}
+
+impl ToolRetrySettings {
+    /// Only read-only tools are retried; re-running anything else would act
+    /// again without the approval covering a second run.
+    fn retries_for(&self, name: &str) -> u32 {
+        if !CONCURRENT_TOOLS.contains(&name) {
+            return 0;
+        }
+        let listed = if self.tools.is_empty() {
+            DEFAULT_RETRY_TOOLS.contains(&name)
+        } else {
+            self.tools.iter().any(|tool| tool == name)
+        };
+        if listed {
+            self.max_retries
+        } else {
+            0
+        }
+    }
+}
+
+/// Retries of model requests that failed with a rate limit, a server error,
+/// a timeout or a dropped connection. Other errors end the turn at once.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ApiRetrySettings {
+    /// Retries per request; `loop_control.max_retries_per_step` from the
+    /// config when unset
+    pub max_retries: Option<u32>,
+    /// Longest wait between attempts; a longer `Retry-After` ends the turn.
+    /// 120 when unset
+    pub max_delay_secs: Option<u64>,
+}
+
+/// Per-turn settings resolved from `GuiSettings` by `chat_stream`.
+pub struct ChatOptions {
+    pub model: String,
+    pub work_dir: String,
+    pub config_path: Option<String>,
+    /// Decides Shell commands and, for the yolo preset, skips approval of
+    /// other tools; None asks about every risky call
+    pub command_policy: Option<policy::CommandPolicy>,
+    /// Folder-scoped allow and ask rules for file edits, checked before the policy
+    pub directory_rules: Vec<policy::DirectoryRule>,
+    /// Per-tool overrides of `needs_approval`, keyed by tool name.
+    pub tool_approvals: HashMap<String, bool>,
+    /// Mutating tools return the diff they would apply instead of touching disk
+    pub dry_run: bool,
+    /// Earlier messages of the session, sent ahead of the new user message
+    pub history: Vec<crate::Message>,
+    /// Extra environment variables for Shell commands
+    pub env: HashMap<String, String>,
+    /// Interpreter for Shell calls that don't name one
+    pub shell_interpreter: Option<tools::Interpreter>,
+    /// Tools left out of the definitions sent to the model
+    pub disabled_tools: Vec<String>,
+    /// Hold requests estimated above a cost threshold until the user confirms
+    pub cost_guard: cost::CostGuardSettings,
+    /// Session and daily limits; past one, requests wait for an override
+    pub budget: cost::BudgetSettings,
+    pub tool_retry: ToolRetrySettings,
+    pub api_retry: ApiRetrySettings,
+    /// Consulted before prompting the user about a tool call
+    pub approval_hook: approvals::ApprovalHook,
+    pub hooks: hooks::HooksSettings,
+    /// Image parts of the new user message, already stored by `attachments::prepare`
+    pub attachments: Vec<MessagePart>,
+    /// Seconds without a response before asking whether to keep waiting; 0 never asks
+    pub soft_timeout_secs: u64,
+    /// Skill the user picked for this message
+    pub skill: Option<skills::SkillDetail>,
+    /// Set when resuming an interrupted turn: the step it had reached. The
+    /// history then already ends with the turn so far, and no new user
+    /// message is sent.
+    pub resume_from: Option<usize>,
+}
+
+/// Read-only tools that may run at the same time as each other
+const CONCURRENT_TOOLS: &[&str] = &[
+    "ReadFile",
+    "Grep",
+    "Glob",
+    "CodeOutline",
+    "FindSymbol",
+    "SearchWeb",
+    "FetchURL",
+    "ReadIssue",
+    "ReadPullRequest",
+    "DocsSearch",
+    "SemanticSearch",
+];
+
+/// Whether a tool only reads, so it can run with no side effects.
+pub fn is_read_only(name: &str) -> bool {
+    CONCURRENT_TOOLS.contains(&name)
+}
+
+/// Whether a call only reads and goes ahead without approval, so it can run
+/// alongside the read-only calls next to it.
+fn runs_concurrently(
+    name: &str,
+    args: &serde_json::Value,
+    work_dir: &str,
+    tool_approvals: &HashMap<String, bool>,
+    command_policy: Option<&policy::CommandPolicy>,
+) -> bool {
+    CONCURRENT_TOOLS.contains(&name)
+        && !approval_required(name, args, work_dir, tool_approvals)
+        && !matches!(
+            command_policy.map(|policy| policy.decide(name, args)),
+            Some(policy::Decision::Deny(_))
+        )
+}
+
+/// Id, name and arguments of a tool call from the model. Calls without an id
+/// get one; RunTests and Build get the command they resolve to.
+fn parse_tool_call(
+    tool_call: &serde_json::Value,
+    work_dir: &str,
+) -> (String, String, serde_json::Value) {
+    let tool_call_id = tool_call
+        .get("id")
+        .and_then(|v| v.as_str())
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let function = tool_call.get("function").cloned().unwrap_or_default();
+    let name = function
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let arguments_raw = function
+        .get("arguments")
+        .and_then(|v| v.as_str())
+        .unwrap_or("{}");
+
+    let mut args_value: serde_json::Value =
+        serde_json::from_str(arguments_raw).unwrap_or(serde_json::json!({}));
+    project_commands::annotate(work_dir, &name, &mut args_value);
+    (tool_call_id, name, args_value)
+}
+
+pub async fn stream_chat(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    user_message: String,
+    options: ChatOptions,
+    auth_config: crate::AuthConfig,
+    mut cancel_rx: tokio::sync::oneshot::Receiver<()>,
+) -> Result<(), String> {
+    let ChatOptions {
+        model,
+        work_dir,
+        config_path,
+        command_policy,
+        directory_rules,
+        tool_approvals,
+        dry_run,
+        history,
+        env,
+        shell_interpreter,
+        disabled_tools,
+        cost_guard,
+        budget,
+        tool_retry,
+        api_retry,
+        approval_hook,
+        hooks,
+        attachments,
+        soft_timeout_secs,
+        skill,
+        resume_from,
+    } = options;
+
+    // Get auth token (OAuth or API Key), or the provider the config routes the model to
+    let resolved = match providers::resolve(&auth_config, config_path.as_deref(), &model)
+        .await
+        .and_then(|(credentials, api_model)| {
+            http::client(&credentials).map(|client| (credentials, client, api_model))
+        }) {
+        Ok(resolved) => resolved,
+        Err(message) => {
+            let _ = window.emit("chat://event", StreamEvent {
+                event: "error".to_string(),
+                data: serde_json::json!({
+                    "session_id": session_id,
+                    "message": message,
+                }),
+            });
+            return Err(message);
+        }
+    };
+    let (mut credentials, client, api_model) = resolved;
+    // An OAuth login can lapse mid-turn; such turns pause for a new login
+    let uses_oauth = providers::uses_oauth(&auth_config, config_path.as_deref(), &model);
+    // Models without native tool calling get the tools as text instead
+    let mut text_tools = !providers::supports_tool_calls(config_path.as_deref(), &model);
+    if text_tools {
+        emit_text_tools_fallback(&window, &session_id, "The model is configured without tool calling.");
+    }
+    let _claims = claims::SessionClaims::new(&state.file_claims, &session_id);
+
+    // Build system prompt with directory context
+    if stats::in_prompt() && !crate::network::is_low_bandwidth() {
+        let _ = stats::get(&work_dir, false).await;
+    }
+    let SystemPrompt {
+        text: mut system_prompt,
+        components: mut prompt_components,
+    } = system_prompt(&work_dir);
+    if let Some(skill) = &skill {
+        let section = skills::prompt_section(skill);
+        prompt_components.insert(0, PromptComponent::new("skill", &section, &section));
+        system_prompt.insert_str(0, &section);
+    }
+    let mut env = env;
+    if let Ok(scratch) = scratch::ensure(&session_id) {
+        system_prompt.push_str(&scratch::prompt_section(&scratch));
+        env.insert(
+            "KIMI_SCRATCH_DIR".to_string(),
+            scratch.to_string_lossy().to_string(),
+        );
+    }
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: "context_breakdown".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "components": prompt_components,
+            }),
+        },
+    );
+    let semantic_search = semantic::is_configured();
+    let docs_search = docs::has_sources(&work_dir);
+    let declared_commands = project_commands::load(&work_dir).unwrap_or_default();
+    let custom_tools = custom_tools::definitions(config_path.as_deref());
+    let custom_names: Vec<&str> = custom_tools
+        .iter()
+        .filter_map(|tool| tool["function"]["name"].as_str())
+        .collect();
+    // A custom tool takes the name from an MCP tool, as execute_tool tries it first
+    let mcp_tools: Vec<serde_json::Value> = state
+        .mcp
+        .tool_definitions()
+        .into_iter()
+        .filter(|tool| !custom_names.contains(&tool["function"]["name"].as_str().unwrap_or("")))
+        .collect();
+    let all_tools: Vec<serde_json::Value> = tools::tool_definitions()
+        .into_iter()
+        .chain(custom_tools.iter().cloned())
+        .chain(mcp_tools)
+        .collect();
+    // A skill's `allowed-tools` hides everything else, and execute_tool refuses it
+    let mut disabled_tools = disabled_tools;
+    if let Some(allowed) = skill.as_ref().and_then(|skill| skill.allowed_tools.as_ref()) {
+        disabled_tools.extend(
+            all_tools
+                .iter()
+                .filter_map(|tool| tool["function"]["name"].as_str())
+                .filter(|name| !allowed.iter().any(|allowed| allowed == name))
+                .map(str::to_string),
+        );
+    }
+    let tools_def: Vec<serde_json::Value> = all_tools
+        .into_iter()
+        .filter(|tool| {
+            let name = tool["function"]["name"].as_str().unwrap_or("");
+            !disabled_tools.iter().any(|disabled| disabled == name)
+                && (semantic_search || name != "SemanticSearch")
+                && (docs_search || name != "DocsSearch")
+                && (!matches!(name, "RunTests" | "Build")
+                    || project_commands::declares(&declared_commands, name))
+        })
+        .collect();
+    let earlier_turns = history.iter().filter(|m| m.role == "user").count();
+    let turn = checkpoints::Turn::new(match resume_from {
+        Some(_) => earlier_turns,
+        None => earlier_turns + 1,
+    });
+    let tool_context = ToolContext {
+        work_dir: &work_dir,
+        config_path: config_path.as_deref(),
+        dry_run,
+        env: &env,
+        shell: shell_interpreter,
+        policy: command_policy.as_ref(),
+        disabled_tools: &disabled_tools,
+        turn: Some(&turn),
+        hooks: Some(&hooks),
+    };
+    let mut messages = vec![serde_json::json!({
+        "role": "system",
+        "content": system_prompt,
+    })];
+    messages.extend(trim_history(history_messages(&history)));
+    if resume_from.is_none() {
+        messages.push(serde_json::json!({
+            "role": "user",
+            "content": attachments::user_content(&parse_user_input(&user_message), &attachments),
+        }));
+    }
+
+    // Successful tool calls of this turn, which later replies can cite
+    let mut turn_calls: Vec<evidence::TurnCall> = Vec::new();
+    let (max_steps, max_retries) = loop_limits(config_path.as_deref());
+    let max_retries = api_retry.max_retries.unwrap_or(max_retries);
+    let max_delay_secs = api_retry
+        .max_delay_secs
+        .unwrap_or(DEFAULT_MAX_RETRY_DELAY_SECS);
+    let mut budget_overridden = false;
+    for step in resume_from.unwrap_or(0).min(max_steps - 1)..max_steps {
+        // Closed when the iteration ends; tool calls and the model request nest under it
+        let step_span = tracing::info_span!("step", step = step as u64);
+        resume::step(&session_id, step);
+        if cancel_rx.try_recv().is_ok() {
+            emit_cancelled(&window, &session_id);
+            return Ok(());
+        }
+
+        if uses_oauth {
+            match reauth::step_token(&window, &session_id, &mut cancel_rx).await {
+                Some(token) => credentials.token = token,
+                None => {
+                    emit_cancelled(&window, &session_id);
+                    return Ok(());
+                }
+            }
+        }
+
+        let mut request = chat_request(&api_model, &messages, &tools_def, text_tools);
+
+        if !budget_overridden {
+            if let Some(limit) = budget.exceeded(&cost::spent(&session_id)) {
+                if !cost::confirm_over_budget(&window, &state, &session_id, &limit, &mut cancel_rx).await? {
+                    emit_cancelled(&window, &session_id);
+                    return Ok(());
+                }
+                budget_overridden = true;
+            }
+        }
+
+        // Every request over the threshold is confirmed on its own
+        if let Some(estimate) = cost::over_threshold(&cost_guard, &model, &request) {
+            if !cost::confirm(&window, &state, &session_id, &model, &estimate, &mut cancel_rx).await? {
+                emit_cancelled(&window, &session_id);
+                return Ok(());
+            }
+        }
+
+        // A failed step is retried from scratch; `retry` tells the UI to drop the
+        // partial output it streamed
+        let mut attempt = 0;
+        let streamed = loop {
+            let result = tokio::select! {
+                _ = &mut cancel_rx => {
+                    emit_cancelled(&window, &session_id);
+                    return Ok(());
+                }
+                resp = stream_chat_completion(&window, &session_id, &client, &credentials, &request, soft_timeout_secs)
+                    .instrument(tracing::info_span!(parent: &step_span, "model_request", model = %api_model, attempt = attempt as u64)) => resp,
+            };
+            match result {
+                Ok(streamed) => break streamed,
+                Err(error) if error.message == watchdog::ABORTED => {
+                    emit_cancelled(&window, &session_id);
+                    return Ok(());
+                }
+                // The server dropped the login: wait for a new one and resend this step
+                Err(error) if uses_oauth && error.status == Some(401) => {
+                    let rejected = std::mem::take(&mut credentials.token);
+                    let renewed = reauth::wait_for_login(
+                        &window,
+                        &session_id,
+                        &error.message,
+                        &rejected,
+                        &mut cancel_rx,
+                    )
+                    .await;
+                    if !renewed {
+                        emit_cancelled(&window, &session_id);
+                        return Ok(());
+                    }
+                    match oauth::load_token() {
+                        Some(token) => credentials.token = token.access_token,
+                        None => return Err(error.message),
+                    }
+                }
+                Err(error) if !text_tools && text_tools::rejects_tools(&error.message) => {
+                    text_tools = true;
+                    emit_text_tools_fallback(&window, &session_id, &error.message);
+                    request = chat_request(&api_model, &messages, &tools_def, text_tools);
+                }
+                Err(error) => {
+                    let delay = match retry_delay(&error, attempt + 1, max_delay_secs) {
+                        Some(delay) if attempt < max_retries => delay,
+                        _ => return Err(error.message),
+                    };
+                    let message = error.message;
+                    attempt += 1;
+                    let _ = window.emit(
+                        "chat://event",
+                        StreamEvent {
+                            event: "retry".to_string(),
+                            data: serde_json::json!({
+                                "session_id": session_id,
+                                "attempt": attempt,
+                                "max_retries": max_retries,
+                                "delay_secs": delay,
+                                "message": message,
+                            }),
+                        },
+                    );
+                    // Counts down once a second so the UI can show when the next attempt goes out
+                    for remaining in (1..=delay).rev() {
                         let _ = window.emit(
                             "chat://event",
                             StreamEvent {
-                                event: "cancelled".to_string(),
+                                event: "retrying".to_string(),
                                 data: serde_json::json!({
                                     "session_id": session_id,
+                                    "attempt": attempt,
+                                    "max_retries": max_retries,
+                                    "remaining_secs": remaining,
                                 }),
                             },
                         );
-                        return Ok(());
+                        tokio::select! {
+                            _ = &mut cancel_rx => {
+                                emit_cancelled(&window, &session_id);
+                                return Ok(());
+                            }
+                            _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                        }
                     }
-                    let mut tool_call_id = tool_call
-                        .get("id")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    let function = tool_call.get("function").cloned().unwrap_or_default();
-                    let name = function
-                        .get("name")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    let arguments_raw = function
-                        .get("arguments")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("{}");
-
-                    let args_value: serde_json::Value =
-                        serde_json::from_str(arguments_raw).unwrap_or(serde_json::json!({}));
-
-                    if tool_call_id.is_empty() {
-                        tool_call_id = Uuid::new_v4().to_string();
+                }
+            }
+        };
+
+        let StreamedMessage {
+            mut content,
+            reasoning,
+            mut tool_calls,
+            usage,
+        } = streamed;
+        let reply_chars = content.len()
+            + reasoning.len()
+            + tool_calls.iter().map(|call| call.to_string().len()).sum::<usize>();
+        let (spent_before, spent_after) = cost::record(
+            Some(&session_id),
+            &model,
+            &cost_guard.prices,
+            usage.as_ref(),
+            &request,
+            reply_chars,
+        );
+        cost::warn(&window, &session_id, &budget, &spent_before, &spent_after);
+        if text_tools && tool_calls.is_empty() {
+            (content, tool_calls) = text_tools::parse_calls(&content);
+        }
+
+        if !tool_calls.is_empty() {
+            let mut assistant_message = serde_json::json!({
+                "role": "assistant",
+                "content": content,
+                "tool_calls": tool_calls,
+            });
+            if !reasoning.is_empty() {
+                assistant_message["reasoning_content"] = serde_json::json!(reasoning);
+            }
+            messages.push(assistant_message);
+            let mut record = assistant_record(&content, &reasoning, &tool_calls);
+            record.parts.extend(evidence::link_claims(&content, &turn_calls));
+            persist_message(&state, &session_id, record);
+
+            let calls = messages
+                .last()
+                .and_then(|v| v.get("tool_calls"))
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+
+            let calls: Vec<(String, String, serde_json::Value)> = calls
+                .iter()
+                .map(|tool_call| parse_tool_call(tool_call, &work_dir))
+                .collect();
+            let rules = ApprovalRules {
+                command_policy: command_policy.as_ref(),
+                directory_rules: &directory_rules,
+                tool_approvals: &tool_approvals,
+                approval_hook: &approval_hook,
+                dry_run,
+            };
+            // Decisions made ahead of a concurrent batch; None means approved
+            let mut decided: HashMap<usize, Option<String>> = HashMap::new();
+            // Outputs of read-only calls already run alongside their neighbours
+            let mut prefetched: HashMap<usize, tools::ToolOutput> = HashMap::new();
+
+            for (index, call) in calls.iter().enumerate() {
+                if cancel_rx.try_recv().is_ok() {
+                    emit_cancelled(&window, &session_id);
+                    return Ok(());
+                }
+                let (tool_call_id, name, args_value) = call.clone();
+
+                // A run of consecutive read-only calls is approved and claimed one
+                // call at a time, in order, and only then do the approved ones
+                // execute together; anything that mutates or needs approval still
+                // runs on its own, in order
+                if !decided.contains_key(&index) {
+                    let batch: Vec<usize> = (index..calls.len())
+                        .take_while(|&i| {
+                            let (_, name, args) = &calls[i];
+                            runs_concurrently(
+                                name,
+                                args,
+                                &work_dir,
+                                &tool_approvals,
+                                command_policy.as_ref(),
+                            )
+                        })
+                        .collect();
+                    if batch.len() > 1 {
+                        for &i in &batch {
+                            let (id, name, args) = &calls[i];
+                            let approval = ApprovalRequest {
+                                session_id: &session_id,
+                                tool_call_id: id,
+                                name,
+                                args,
+                                work_dir: &work_dir,
+                                config_path: config_path.as_deref(),
+                            };
+                            match approve_and_claim(&window, &state, &approval, &rules, &mut cancel_rx).await {
+                                Ok(rejection) => {
+                                    decided.insert(i, rejection);
+                                }
+                                Err(_) => {
+                                    emit_cancelled(&window, &session_id);
+                                    return Ok(());
+                                }
+                            }
+                        }
+                        let approved: Vec<usize> = batch
+                            .into_iter()
+                            .filter(|i| decided.get(i).is_some_and(Option::is_none))
+                            .collect();
+                        let runs: Vec<_> = approved
+                            .iter()
+                            .map(|&i| {
+                                let (id, name, args) = &calls[i];
+                                emit_tool_status(
+                                    &window,
+                                    &session_id,
+                                    id,
+                                    "start",
+                                    name,
+                                    &tool_label(name, args),
+                                    None,
+                                    None,
+                                );
+                                execute_tool(
+                                    &window,
+                                    &state,
+                                    &session_id,
+                                    id,
+                                    name,
+                                    args,
+                                    &tool_context,
+                                )
+                                .instrument(step_span.clone())
+                            })
+                            .collect();
+                        let outputs = tokio::select! {
+                            _ = &mut cancel_rx => {
+                                emit_cancelled(&window, &session_id);
+                                return Ok(());
+                            }
+                            outputs = futures::future::join_all(runs) => outputs,
+                        };
+                        prefetched.extend(approved.into_iter().zip(outputs));
                     }
+                }
 
-                    let approved = if needs_approval(&name) && !auto_approve {
-                        match request_approval(
-                            &window,
-                            &state,
-                            &session_id,
-                            &tool_call_id,
-                            &name,
-                            &args_value,
-                            &mut cancel_rx,
-                        )
-                        .await
-                        {
-                            Ok(value) => value,
+                let decision = match decided.remove(&index) {
+                    Some(decision) => decision,
+                    None => {
+                        let approval = ApprovalRequest {
+                            session_id: &session_id,
+                            tool_call_id: &tool_call_id,
+                            name: &name,
+                            args: &args_value,
+                            work_dir: &work_dir,
+                            config_path: config_path.as_deref(),
+                        };
+                        match approve_and_claim(&window, &state, &approval, &rules, &mut cancel_rx).await {
+                            Ok(decision) => decision,
                             Err(_) => {
-                                let _ = window.emit(
-                                    "chat://event",
-                                    StreamEvent {
-                                        event: "cancelled".to_string(),
-                                        data: serde_json::json!({
-                                            "session_id": session_id,
-                                        }),
-                                    },
-                                );
+                                emit_cancelled(&window, &session_id);
                                 return Ok(());
                             }
                         }
-                    } else {
-                        true
-                    };
+                    }
+                };
+                let mut rejection = String::new();
+                let approved = match decision {
+                    None => true,
+                    Some(reason) => {
+                        rejection = reason;
+                        false
+                    }
+                };
 
-                    let label = tool_label(&name, &args_value);
-                    let output = if approved {
+                let label = tool_label(&name, &args_value);
+                let output = if approved {
+                    let mut tool_output = match prefetched.remove(&index) {
+                        Some(output) => output,
+                        None => {
+                            emit_tool_status(
+                                &window,
+                                &session_id,
+                                &tool_call_id,
+                                "start",
+                                &name,
+                                &label,
+                                None,
+                                None,
+                            );
+                            execute_tool(
+                                &window,
+                                &state,
+                                &session_id,
+                                &tool_call_id,
+                                &name,
+                                &args_value,
+                                &tool_context,
+                            )
+                            .instrument(step_span.clone())
+                            .await
+                        }
+                    };
+                    let max_retries = if dry_run { 0 } else { tool_retry.retries_for(&name) };
+                    let mut retries = 0;
+                    let mut failures = Vec::new();
+                    while !tool_output.ok && retries < max_retries {
+                        retries += 1;
+                        failures.push(tool_output.summary.clone());
                         emit_tool_status(
                             &window,
                             &session_id,
                             &tool_call_id,
-                            "start",
+                            "retry",
                             &name,
                             &label,
-                            None,
-                            None,
+                            Some(false),
+                            Some(format!("Retrying ({}/{}): {}", retries, max_retries, tool_output.summary)),
                         );
-
-                        let tool_output = execute_tool(
+                        tokio::select! {
+                            _ = &mut cancel_rx => {
+                                emit_cancelled(&window, &session_id);
+                                return Ok(());
+                            }
+                            _ = tokio::time::sleep(std::time::Duration::from_secs(RETRY_DELAY_SECS * retries as u64)) => {}
+                        }
+                        tool_output = execute_tool(
                             &window,
                             &state,
                             &session_id,
                             &tool_call_id,
                             &name,
                             &args_value,
-                            &work_dir,
-                            config_path.as_deref(),
+                            &tool_context,
                         )
+                        .instrument(step_span.clone())
                         .await;
+                    }
+                    if retries > 0 {
+                        tool_output.summary = if tool_output.ok {
+                            format!("{} (succeeded after {} retries)", tool_output.summary, retries)
+                        } else {
+                            format!(
+                                "{} (failed {} times; earlier errors: {})",
+                                tool_output.summary,
+                                retries + 1,
+                                failures.join(" | ")
+                            )
+                        };
+                    }
 
-                        emit_tool_status(
-                            &window,
-                            &session_id,
-                            &tool_call_id,
-                            "end",
-                            &name,
-                            &label,
-                            Some(tool_output.ok),
-                            Some(tool_output.summary.clone()),
-                        );
+                    emit_tool_status(
+                        &window,
+                        &session_id,
+                        &tool_call_id,
+                        "end",
+                        &name,
+                        &label,
+                        Some(tool_output.ok),
+                        Some(tool_output.summary.clone()),
+                    );
 
-                        tool_output
-                    } else {
-                        emit_tool_status(
-                            &window,
-                            &session_id,
-                            &tool_call_id,
-                            "end",
-                            &name,
-                            &label,
-                            Some(false),
-                            Some("User rejected tool request.".to_string()),
-                        );
+                    tool_output
+                } else {
+                    emit_tool_status(
+                        &window,
+                        &session_id,
+                        &tool_call_id,
+                        "end",
+                        &name,
+                        &label,
+                        Some(false),
+                        Some(rejection.clone()),
+                    );
 
-                        tools::ToolOutput {
-                            ok: false,
-                            summary: "User rejected tool request.".to_string(),
-                            output: String::new(),
-                        }
-                    };
+                    tools::ToolOutput {
+                        ok: false,
+                        summary: rejection,
+                        output: String::new(),
+                    }
+                };
 
-                    let _ = window.emit(
-                        "chat://event",
-                        StreamEvent {
-                            event: "tool_result".to_string(),
-                            data: serde_json::json!({
-                                "session_id": session_id,
-                                "tool_call_id": tool_call_id,
-                                "name": name,
-                                "ok": output.ok,
-                                "summary": output.summary,
-                                "output": output.output,
-                            }),
-                        },
-                    );
+                let _ = window.emit(
+                    "chat://event",
+                    StreamEvent {
+                        event: "tool_result".to_string(),
+                        data: serde_json::json!({
+                            "session_id": session_id,
+                            "tool_call_id": tool_call_id,
+                            "name": name,
+                            "ok": output.ok,
+                            "summary": output.summary,
+                            "output": output.output,
+                        }),
+                    },
+                );
 
-                    let tool_content = serde_json::json!({
-                        "ok": output.ok,
-                        "summary": output.summary,
-                        "output": output.output,
-                    })
-                    .to_string();
+                let tool_content = serde_json::json!({
+                    "ok": output.ok,
+                    "summary": output.summary,
+                    "output": output.output,
+                })
+                .to_string();
 
-                    messages.push(serde_json::json!({
-                        "role": "tool",
-                        "tool_call_id": tool_call_id,
-                        "content": tool_content,
-                    }));
-                }
+                messages.push(serde_json::json!({
+                    "role": "tool",
+                    "tool_call_id": tool_call_id,
+                    "content": tool_content,
+                }));
 
-                continue;
+                let mut record = crate::Message::new("tool", tool_content);
+                record.parts = vec![MessagePart::ToolRef {
+                    id: tool_call_id.clone(),
+                    name: name.clone(),
+                }];
+                if output.ok {
+                    turn_calls.push((tool_call_id.clone(), name.clone(), args_value.clone()));
+                }
+                record.tool_call_id = Some(tool_call_id);
+                persist_message(&state, &session_id, record);
             }
+
+            continue;
         }
 
         if !content.is_empty() {
+            let mut record = assistant_record(&content, &reasoning, &[]);
+            record.parts.extend(evidence::link_claims(&content, &turn_calls));
+            persist_message(&state, &session_id, record);
+
             // Extract token usage from response if available
-            let usage = data.get("usage").cloned().unwrap_or(serde_json::json!({}));
+            let usage = usage.unwrap_or(serde_json::json!({}));
             let prompt_tokens = usage.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
             let completion_tokens = usage.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
             let total_tokens = usage.get("total_tokens").and_then(|v| v.as_u64())
                 .unwrap_or(prompt_tokens + completion_tokens);
             
-            let _ = window.emit(
-                "chat://event",
-                StreamEvent {
-                    event: "chunk".to_string(),
-                    data: serde_json::json!({
-                        "session_id": session_id,
-                        "content": content,
-                    }),
-                },
-            );
             let _ = window.emit(
                 "chat://event",
                 StreamEvent {
@@ -481,32 +1629,20 @@ pub async fn stream_chat(
         }
     }
 
-    Err("Exceeded maximum tool steps".to_string())
+    Err(format!("Exceeded maximum tool steps ({})", max_steps))
 }
 
 #[tauri::command]
-pub async fn llm_fetch_models(auth_config: crate::AuthConfig) -> Result<Vec<serde_json::Value>, String> {
-    let (access_token, api_base) = if auth_config.mode == "api_key" {
-        let api_key = auth_config.api_key.ok_or("API key not configured")?;
-        let base = auth_config.api_base
-            .filter(|b| !b.is_empty())
-            .unwrap_or_else(|| "https://api.moonshot.cn/v1".to_string());
-        (api_key, base)
-    } else {
-        let token = ensure_fresh_token()
-            .await
-            .ok_or_else(|| "Not logged in".to_string())?;
-        let base = std::env::var("KIMI_BASE_URL")
-            .unwrap_or_else(|_| "https://api.kimi.com/coding/v1".to_string());
-        (token, base)
-    };
+pub async fn llm_fetch_models(
+    auth_config: crate::AuthConfig,
+    config_file: Option<String>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let credentials = resolve_credentials(&auth_config).await?;
     
-    let client = reqwest::Client::new();
-    let mut req = client.get(format!("{}/models", api_base));
-    for (key, value) in common_headers().into_iter() {
-        req = req.header(key, value);
-    }
-    req = req.header("Authorization", format!("Bearer {}", access_token));
+    crate::network::ensure_online(&credentials.base)?;
+    let client = http::client(&credentials)?;
+    let url = format!("{}/models", credentials.base);
+    let req = http::request(&client, &credentials, reqwest::Method::GET, &url, None)?;
     let response = req.send().await.map_err(|e| format!("Request failed: {}", e))?;
     
     if !response.status().is_success() {
@@ -520,19 +1656,59 @@ pub async fn llm_fetch_models(auth_config: crate::AuthConfig) -> Result<Vec<serd
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
     
-    let models = data["data"]
+    let mut models = data["data"]
         .as_array()
         .cloned()
         .unwrap_or_default();
+    // Models the config routes to other providers
+    models.extend(providers::external_models(
+        config_file.as_deref().filter(|path| !path.is_empty()),
+    ));
     
     Ok(models)
 }
 
-fn needs_approval(tool_name: &str) -> bool {
-    matches!(tool_name, "Shell" | "WriteFile" | "StrReplaceFile")
+/// Tools no override, saved rule or policy preset approves on the user's behalf
+const ALWAYS_ASK: &[&str] = &["DownloadFile", "CreatePullRequest"];
+
+/// Built-in mutating tools and every MCP tool, since what those do is unknown.
+pub fn needs_approval(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "Shell"
+            | "DownloadFile"
+            | "CreatePullRequest"
+            | "MoveFile"
+            | "CopyFile"
+            | "DeleteFile"
+            | "CreateDirectory"
+            | "RunInBackground"
+            | "RunTests"
+            | "Build"
+            | "WriteFile"
+            | "StrReplaceFile"
+            | "ApplyPatch"
+    )
+        || !tools::builtin_tool_names().iter().any(|name| name == tool_name)
+}
+
+/// Whether a tool call must be approved, honoring user overrides before the
+/// defaults and then any "always allow" rule saved for `work_dir`.
+pub fn approval_required(
+    tool_name: &str,
+    args: &serde_json::Value,
+    work_dir: &str,
+    overrides: &HashMap<String, bool>,
+) -> bool {
+    ALWAYS_ASK.contains(&tool_name)
+        || overrides
+            .get(tool_name)
+            .copied()
+            .unwrap_or_else(|| needs_approval(tool_name))
+            && !approvals::rule_allows(work_dir, tool_name, args)
 }
 
-fn emit_tool_status(
+pub fn emit_tool_status(
     window: &tauri::Window,
     session_id: &str,
     tool_call_id: &str,
@@ -559,18 +1735,63 @@ fn emit_tool_status(
     );
 }
 
-fn tool_label(name: &str, args: &serde_json::Value) -> String {
+/// Live output of a running Shell call. The tool result still carries the
+/// full, truncated text.
+fn emit_tool_output_chunk(window: &tauri::Window, session_id: &str, tool_call_id: &str, chunk: &str) {
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: "tool_output_chunk".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "tool_call_id": tool_call_id,
+                "chunk": chunk,
+            }),
+        },
+    );
+}
+
+pub fn tool_label(name: &str, args: &serde_json::Value) -> String {
     match name {
         "ReadFile" => args
             .get("path")
             .and_then(|v| v.as_str())
             .map(|p| format!("正在读取 {}", p))
             .unwrap_or_else(|| "正在读取文件".to_string()),
+        "Grep" => args
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在搜索代码 {}", p))
+            .unwrap_or_else(|| "正在搜索代码".to_string()),
+        "Glob" => args
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在查找文件 {}", p))
+            .unwrap_or_else(|| "正在查找文件".to_string()),
+        "CodeOutline" => args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在分析结构 {}", p))
+            .unwrap_or_else(|| "正在分析代码结构".to_string()),
+        "FindSymbol" => args
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(|n| format!("正在查找符号 {}", n))
+            .unwrap_or_else(|| "正在查找符号".to_string()),
         "Shell" => args
             .get("command")
             .and_then(|v| v.as_str())
             .map(|cmd| format!("正在执行 {}", cmd))
             .unwrap_or_else(|| "正在执行命令".to_string()),
+        "RunInBackground" => args
+            .get("command")
+            .and_then(|v| v.as_str())
+            .map(|cmd| format!("正在后台启动 {}", cmd))
+            .unwrap_or_else(|| "正在后台启动命令".to_string()),
+        "RunTests" => "正在运行测试".to_string(),
+        "Build" => "正在构建项目".to_string(),
+        "ReadProcessOutput" => "正在读取后台进程输出".to_string(),
+        "KillProcess" => "正在停止后台进程".to_string(),
         "WriteFile" => args
             .get("path")
             .and_then(|v| v.as_str())
@@ -581,6 +1802,31 @@ fn tool_label(name: &str, args: &serde_json::Value) -> String {
             .and_then(|v| v.as_str())
             .map(|p| format!("正在修改 {}", p))
             .unwrap_or_else(|| "正在修改文件".to_string()),
+        "MoveFile" => args
+            .get("source")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在移动 {}", p))
+            .unwrap_or_else(|| "正在移动文件".to_string()),
+        "CopyFile" => args
+            .get("source")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在复制 {}", p))
+            .unwrap_or_else(|| "正在复制文件".to_string()),
+        "DeleteFile" => args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在删除 {}", p))
+            .unwrap_or_else(|| "正在删除文件".to_string()),
+        "CreateDirectory" => args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(|p| format!("正在创建目录 {}", p))
+            .unwrap_or_else(|| "正在创建目录".to_string()),
+        "ApplyPatch" => match tools::edit_paths(name, args).as_slice() {
+            [path] => format!("正在修改 {}", path),
+            [] => "正在应用补丁".to_string(),
+            paths => format!("正在修改 {} 个文件", paths.len()),
+        },
         "SearchWeb" => args
             .get("query")
             .and_then(|v| v.as_str())
@@ -591,29 +1837,222 @@ fn tool_label(name: &str, args: &serde_json::Value) -> String {
             .and_then(|v| v.as_str())
             .map(|u| format!("正在抓取 {}", u))
             .unwrap_or_else(|| "正在抓取网页".to_string()),
+        "DownloadFile" => args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .map(|u| format!("正在下载 {}", u))
+            .unwrap_or_else(|| "正在下载文件".to_string()),
+        "ReadIssue" => args
+            .get("number")
+            .and_then(|v| v.as_u64())
+            .map(|n| format!("正在读取 Issue #{}", n))
+            .unwrap_or_else(|| "正在读取 Issue".to_string()),
+        "ReadPullRequest" => args
+            .get("number")
+            .and_then(|v| v.as_u64())
+            .map(|n| format!("正在读取 PR #{}", n))
+            .unwrap_or_else(|| "正在读取 PR".to_string()),
+        "CreatePullRequest" => "正在创建 PR".to_string(),
+        "DocsSearch" => args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(|q| format!("正在查阅文档 {}", q))
+            .unwrap_or_else(|| "正在查阅文档".to_string()),
+        "SemanticSearch" => args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(|q| format!("正在检索代码 {}", q))
+            .unwrap_or_else(|| "正在检索代码".to_string()),
         _ => format!("正在执行 {}", name),
     }
 }
 
-async fn request_approval(
+/// A tool call waiting on the user.
+pub struct ApprovalRequest<'a> {
+    pub session_id: &'a str,
+    pub tool_call_id: &'a str,
+    pub name: &'a str,
+    pub args: &'a serde_json::Value,
+    pub work_dir: &'a str,
+    /// Where custom tools are declared, so their command can be shown
+    pub config_path: Option<&'a str>,
+}
+
+/// Unified diff a file-editing call would apply, from a dry run against the
+/// file on disk. None for other tools or when the dry run fails.
+async fn change_preview(
     window: &tauri::Window,
     state: &tauri::State<'_, AppState>,
-    session_id: &str,
-    tool_call_id: &str,
-    name: &str,
-    args: &serde_json::Value,
+    request: &ApprovalRequest<'_>,
+) -> Option<String> {
+    if !matches!(request.name, "WriteFile" | "StrReplaceFile" | "ApplyPatch") {
+        return None;
+    }
+    let env = HashMap::new();
+    let ctx = ToolContext {
+        work_dir: request.work_dir,
+        config_path: None,
+        dry_run: true,
+        env: &env,
+        shell: None,
+        policy: None,
+        disabled_tools: &[],
+        turn: None,
+        hooks: None,
+    };
+    let output = execute_tool(
+        window,
+        state,
+        request.session_id,
+        request.tool_call_id,
+        request.name,
+        request.args,
+        &ctx,
+    )
+    .await;
+    output.ok.then_some(output.output)
+}
+
+/// The user's rules for letting tool calls run without asking.
+pub struct ApprovalRules<'a> {
+    pub command_policy: Option<&'a policy::CommandPolicy>,
+    pub directory_rules: &'a [policy::DirectoryRule],
+    pub tool_approvals: &'a HashMap<String, bool>,
+    pub approval_hook: &'a approvals::ApprovalHook,
+    /// Dry-run calls can't change anything, so there's nothing to approve
+    pub dry_run: bool,
+}
+
+/// Decide whether a tool call may run, for calls from the model and reruns
+/// alike: writes outside the work dir are refused or always asked about, then
+/// directory rules and the command policy apply (neither allows `ALWAYS_ASK`
+/// tools), decisions made for the user are audited, and calls that still
+/// need approval go to the hook and then the prompt. Ok(Some(reason)) when
+/// the call is refused; Err when the turn was cancelled while asking.
+pub async fn decide_approval(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    request: &ApprovalRequest<'_>,
+    rules: &ApprovalRules<'_>,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<Option<String>, String> {
+    let ApprovalRequest {
+        session_id,
+        name,
+        args,
+        work_dir,
+        ..
+    } = *request;
+    let escapes = paths::write_escapes(session_id, work_dir, name, args);
+    let refusal = paths::refuse_outside_write(session_id, work_dir, name, args);
+    let directory_rule = policy::directory_rule(rules.directory_rules, work_dir, name, args);
+    let (decision, matched) = match (refusal, directory_rule, rules.command_policy) {
+        (Some(reason), _, _) => (policy::Decision::Deny(reason), Some("outside writes: deny".to_string())),
+        // Nothing approves a write outside the work dir on the user's behalf
+        _ if escapes => (policy::Decision::Ask, None),
+        (None, Some(rule), _) => (rule.decision(), Some(rule.describe())),
+        (None, None, Some(policy)) => (
+            policy.decide(name, args),
+            Some(format!("{} command policy", policy.preset.label())),
+        ),
+        _ => (policy::Decision::Ask, None),
+    };
+    // Rules and presets may refuse these but never allow them
+    let (decision, matched) = match decision {
+        policy::Decision::Allow if ALWAYS_ASK.contains(&name) => (policy::Decision::Ask, None),
+        decision => (decision, matched),
+    };
+    // Only decisions that stand in for the user are worth an entry
+    let audited = match decision {
+        policy::Decision::Allow => approval_required(name, args, work_dir, rules.tool_approvals),
+        policy::Decision::Deny(_) => true,
+        policy::Decision::Ask => directory_rule.is_some(),
+    };
+    if let (Some(rule), true, false) = (&matched, audited, rules.dry_run) {
+        audit::record(session_id, work_dir, name, args, &decision, rule);
+    }
+    match decision {
+        policy::Decision::Deny(reason) => Ok(Some(reason)),
+        policy::Decision::Ask
+            if !rules.dry_run
+                && (escapes
+                    || approval_required(name, args, work_dir, rules.tool_approvals)
+                        && !scratch::covers(session_id, work_dir, name, args)) =>
+        {
+            match approvals::run_hook(rules.approval_hook, session_id, work_dir, name, args).await {
+                approvals::HookDecision::Allow => Ok(None),
+                approvals::HookDecision::Deny(reason) => Ok(Some(reason)),
+                approvals::HookDecision::Ask => {
+                    let approved = request_approval(window, state, request, cancel_rx).await?;
+                    Ok((!approved).then(|| "User rejected tool request.".to_string()))
+                }
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Decide whether a call may run and, when it may, claim the files it will
+/// change. Ok(Some(reason)) when the call is refused or its files stay
+/// claimed by another session; Err when the turn was cancelled meanwhile.
+async fn approve_and_claim(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    request: &ApprovalRequest<'_>,
+    rules: &ApprovalRules<'_>,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<Option<String>, String> {
+    if let Some(reason) = decide_approval(window, state, request, rules, cancel_rx).await? {
+        return Ok(Some(reason));
+    }
+    let targets = claims::claim_targets(request.name, request.args, request.work_dir);
+    match claims::acquire(
+        window,
+        &state.file_claims,
+        request.session_id,
+        request.tool_call_id,
+        &targets,
+        cancel_rx,
+    )
+    .await
+    {
+        Ok(()) => Ok(None),
+        Err(claims::ClaimError::TimedOut(reason)) => Ok(Some(reason)),
+        Err(claims::ClaimError::Cancelled) => Err("Cancelled".to_string()),
+    }
+}
+
+pub async fn request_approval(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    request: &ApprovalRequest<'_>,
     cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
 ) -> Result<bool, String> {
+    let ApprovalRequest {
+        session_id,
+        tool_call_id,
+        name,
+        args,
+        work_dir,
+        config_path,
+    } = *request;
+    let diff = change_preview(window, state, request).await;
+    // What a custom tool will actually run, rather than only its arguments
+    let command = custom_tools::find(config_path, name)
+        .map(|tool| custom_tools::render(&tool.command, args));
+    let outside_work_dir = paths::write_escapes(session_id, work_dir, name, args);
     let request_id = format!("{}:{}", session_id, tool_call_id);
     let (tx, rx) = tokio::sync::oneshot::channel();
 
-    {
-        let mut approvals = state
-            .approvals
-            .lock()
-            .map_err(|_| "Approval store poisoned".to_string())?;
-        approvals.insert(request_id.clone(), tx);
-    }
+    state
+        .approvals
+        .lock()
+        .map_err(|_| "Approval store poisoned".to_string())?
+        .insert(request_id.clone(), tx);
+    let _pending = PendingApproval {
+        approvals: &state.approvals,
+        request_id: request_id.clone(),
+    };
 
     let _ = window.emit(
         "chat://event",
@@ -624,19 +2063,19 @@ async fn request_approval(
                 "request_id": request_id,
                 "name": name,
                 "args": args,
+                "diff": diff,
+                "command": command,
+                "outside_work_dir": outside_work_dir,
             }),
         },
     );
 
     let approved = tokio::select! {
         _ = cancel_rx => {
-            let mut approvals = state
-                .approvals
-                .lock()
-                .map_err(|_| "Approval store poisoned".to_string())?;
-            approvals.remove(&request_id);
+            emit_approval_cancelled(window, &request_id, "turn_cancelled");
             return Err("Cancelled".to_string());
         }
+        // A dropped sender means the request was cancelled, which denies it
         result = rx => {
             result.unwrap_or(false)
         }
@@ -645,109 +2084,328 @@ async fn request_approval(
     Ok(approved)
 }
 
-async fn execute_tool(
-    _window: &tauri::Window,
-    _state: &tauri::State<'_, AppState>,
-    _session_id: &str,
+/// Removes an approval from the pending map however the wait ends, so an
+/// aborted turn can't leave a sender behind.
+pub struct PendingApproval<'a> {
+    pub approvals: &'a std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    pub request_id: String,
+}
+
+impl Drop for PendingApproval<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut approvals) = self.approvals.lock() {
+            approvals.remove(&self.request_id);
+        }
+    }
+}
+
+/// Tell the UI a pending approval went away without an answer. `reason` is
+/// "user" or "turn_cancelled".
+pub fn emit_approval_cancelled(window: &tauri::Window, request_id: &str, reason: &str) {
+    let session_id = request_id.split_once(':').map_or(request_id, |(id, _)| id);
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: "tool_approval_cancelled".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "request_id": request_id,
+                "reason": reason,
+            }),
+        },
+    );
+}
+
+/// Where and how `execute_tool` runs a call.
+pub struct ToolContext<'a> {
+    pub work_dir: &'a str,
+    pub config_path: Option<&'a str>,
+    /// Mutating tools return the diff they would apply instead of touching disk
+    pub dry_run: bool,
+    /// Extra environment variables for Shell commands
+    pub env: &'a HashMap<String, String>,
+    /// Interpreter for Shell calls that don't name one; the platform's default
+    /// when None
+    pub shell: Option<tools::Interpreter>,
+    /// Deny rules are enforced here too, for calls that skip the approval step
+    pub policy: Option<&'a policy::CommandPolicy>,
+    /// Tools hidden from the model; calls to them are refused
+    pub disabled_tools: &'a [String],
+    /// Turn whose first change to a file snapshots it for `file_rollback`
+    pub turn: Option<&'a checkpoints::Turn>,
+    /// User hooks run around the call; dry runs skip them
+    pub hooks: Option<&'a hooks::HooksSettings>,
+}
+
+pub async fn execute_tool(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
     tool_call_id: &str,
     name: &str,
     args: &serde_json::Value,
-    work_dir: &str,
-    config_path: Option<&str>,
+    ctx: &ToolContext<'_>,
+) -> tools::ToolOutput {
+    let span = tracing::info_span!("tool", tool = name, dry_run = ctx.dry_run);
+    async {
+        if let Some(policy::Decision::Deny(reason)) = ctx.policy.map(|policy| policy.decide(name, args)) {
+            return tools::ToolOutput {
+                ok: false,
+                summary: reason,
+                output: String::new(),
+            };
+        }
+        if let Some(reason) = paths::refuse_outside_write(session_id, ctx.work_dir, name, args) {
+            return tools::ToolOutput {
+                ok: false,
+                summary: reason,
+                output: String::new(),
+            };
+        }
+        // Edits to files that changed on disk since this session saw them are refused
+        let targets = if ctx.dry_run {
+            Vec::new()
+        } else {
+            claims::claim_targets(name, args, ctx.work_dir)
+        };
+        for path in &targets {
+            if let Err(reason) = state.file_reads.check(session_id, path) {
+                let _ = window.emit(
+                    "chat://event",
+                    StreamEvent {
+                        event: "file_conflict".to_string(),
+                        data: serde_json::json!({
+                            "session_id": session_id,
+                            "tool_call_id": tool_call_id,
+                            "path": path.to_string_lossy(),
+                        }),
+                    },
+                );
+                return tools::ToolOutput {
+                    ok: false,
+                    summary: reason,
+                    output: String::new(),
+                };
+            }
+        }
+        let hooks = ctx.hooks.filter(|_| !ctx.dry_run);
+        if let Some(hooks) = hooks {
+            if let Err(reason) = hooks::pre_tool_use(hooks, session_id, ctx.work_dir, name, args).await {
+                return tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Blocked by a pre_tool_use hook: {}", reason),
+                    output: String::new(),
+                };
+            }
+        }
+        let mut output = run_tool(window, state, session_id, tool_call_id, name, args, ctx).await;
+        if output.ok && !ctx.dry_run && !targets.is_empty() {
+            if let Some(report) = diagnostics::after_write(ctx.work_dir, ctx.env).await {
+                diagnostics::attach(&mut output, &report, ctx.work_dir, &targets);
+                let _ = window.emit(
+                    "diagnostics://updated",
+                    serde_json::json!({ "work_dir": ctx.work_dir, "report": report }),
+                );
+            }
+        }
+        if output.ok && !ctx.dry_run {
+            if !targets.is_empty() {
+                semantic::index_in_background(window, ctx.work_dir);
+            }
+            for path in &targets {
+                state.file_reads.record(session_id, path);
+            }
+            let read_path = args.get("path").and_then(|v| v.as_str());
+            if let (Some(path), "ReadFile") = (read_path, name) {
+                let path = claims::normalize(&std::path::Path::new(ctx.work_dir).join(path));
+                state.file_reads.record(session_id, &path);
+            }
+        }
+        if let Some(hooks) = hooks {
+            hooks::post_tool_use(hooks, session_id, ctx.work_dir, name, args, &output).await;
+        }
+        output
+    }
+    .instrument(span)
+    .await
+}
+
+async fn run_tool(
+    window: &tauri::Window,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    tool_call_id: &str,
+    name: &str,
+    args: &serde_json::Value,
+    ctx: &ToolContext<'_>,
 ) -> tools::ToolOutput {
+    if ctx.disabled_tools.iter().any(|tool| tool == name) {
+        return tools::ToolOutput {
+            ok: false,
+            summary: format!("Tool {} is disabled for this workspace.", name),
+            output: String::new(),
+        };
+    }
+    let work_dir = ctx.work_dir;
+    let config_path = ctx.config_path;
+    let dry_run = ctx.dry_run;
+    if let (Some(turn), false) = (ctx.turn, dry_run) {
+        for path in claims::claim_targets(name, args, work_dir) {
+            if let Err(err) = checkpoints::snapshot(session_id, turn, &path) {
+                return tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Refusing to modify {} without a checkpoint: {}", path.display(), err),
+                    output: String::new(),
+                };
+            }
+        }
+    }
+    macro_rules! args {
+        ($ty:ty) => {
+            match tools::parse_args::<$ty>(name, args) {
+                Ok(parsed) => parsed,
+                Err(output) => return output,
+            }
+        };
+    }
     match name {
         "ReadFile" => {
-            let path = match args.get("path").and_then(|v| v.as_str()) {
-                Some(p) => p,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing path".to_string(),
-                        output: String::new(),
-                    }
-                }
-            };
-            let line_offset = args
-                .get("line_offset")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(1) as usize;
-            let n_lines = args
-                .get("n_lines")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(1000) as usize;
-            tools::read_file(work_dir, path, line_offset, n_lines)
+            let args = args!(tools::ReadFileArgs);
+            tools::read_file(
+                work_dir,
+                &args.path,
+                args.line_offset.unwrap_or(1),
+                args.n_lines.unwrap_or(1000),
+            )
+        }
+        "Grep" => {
+            let args = args!(tools::GrepArgs);
+            let dir = work_dir.to_string();
+            tokio::task::spawn_blocking(move || code_search::search_tool(&dir, &args))
+                .await
+                .unwrap_or_else(|err| tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Search failed: {}", err),
+                    output: String::new(),
+                })
+        }
+        "Glob" => {
+            let args = args!(tools::GlobArgs);
+            let dir = work_dir.to_string();
+            tokio::task::spawn_blocking(move || code_search::glob_tool(&dir, &args))
+                .await
+                .unwrap_or_else(|err| tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Search failed: {}", err),
+                    output: String::new(),
+                })
+        }
+        "CodeOutline" => {
+            let args = args!(tools::CodeOutlineArgs);
+            let dir = work_dir.to_string();
+            tokio::task::spawn_blocking(move || symbols::outline_tool(&dir, &args))
+                .await
+                .unwrap_or_else(|err| tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Outline failed: {}", err),
+                    output: String::new(),
+                })
+        }
+        "FindSymbol" => {
+            let args = args!(tools::FindSymbolArgs);
+            let dir = work_dir.to_string();
+            tokio::task::spawn_blocking(move || symbols::find_tool(&dir, &args))
+                .await
+                .unwrap_or_else(|err| tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Search failed: {}", err),
+                    output: String::new(),
+                })
         }
         "Shell" => {
-            let command = match args.get("command").and_then(|v| v.as_str()) {
-                Some(cmd) => cmd,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing command".to_string(),
-                        output: String::new(),
-                    }
-                }
+            let args = args!(tools::ShellArgs);
+            if dry_run {
+                return tools::dry_run_shell(&args.command);
+            }
+            let on_output = |chunk: &str| emit_tool_output_chunk(window, session_id, tool_call_id, chunk);
+            let call = tools::ShellCall {
+                command: &args.command,
+                timeout_secs: args.timeout.unwrap_or(60),
+                interpreter: args
+                    .interpreter
+                    .or(ctx.shell)
+                    .unwrap_or_else(tools::Interpreter::platform_default),
+                env: ctx.env,
             };
-            let timeout = args
-                .get("timeout")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(60);
-            tools::run_shell(work_dir, command, timeout).await
+            tools::run_shell_in_session(&state.shells, session_id, work_dir, &call, &on_output).await
         }
-        "WriteFile" => {
-            let path = match args.get("path").and_then(|v| v.as_str()) {
-                Some(p) => p,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing path".to_string(),
-                        output: String::new(),
-                    }
-                }
+        "RunTests" | "Build" => {
+            // The command `annotate` resolved is the one the policy and the user
+            // approved; the config may have changed since
+            let command = args.get("command").and_then(|v| v.as_str()).map(str::to_string);
+            let args = args!(tools::ProjectCommandArgs);
+            let Some(command) = command else {
+                return tools::ToolOutput {
+                    ok: false,
+                    summary: format!(
+                        "This project declares no {} command. Add one to .kimi/commands.toml or the commands section of AGENTS.md, or use Shell.",
+                        if name == "RunTests" { "test" } else { "build" }
+                    ),
+                    output: String::new(),
+                };
             };
-            let content = match args.get("content").and_then(|v| v.as_str()) {
-                Some(c) => c,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing content".to_string(),
-                        output: String::new(),
-                    }
-                }
+            if dry_run {
+                return tools::dry_run_shell(&command);
+            }
+            let on_output = |chunk: &str| emit_tool_output_chunk(window, session_id, tool_call_id, chunk);
+            let call = tools::ShellCall {
+                command: &command,
+                timeout_secs: args.timeout.unwrap_or(600),
+                interpreter: ctx.shell.unwrap_or_else(tools::Interpreter::platform_default),
+                env: ctx.env,
             };
-            let mode = args
-                .get("mode")
-                .and_then(|v| v.as_str())
-                .unwrap_or("overwrite");
-            tools::write_file(work_dir, path, content, mode)
+            tools::run_shell_in_session(&state.shells, session_id, work_dir, &call, &on_output).await
         }
-        "StrReplaceFile" => {
-            let path = match args.get("path").and_then(|v| v.as_str()) {
-                Some(p) => p,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing path".to_string(),
-                        output: String::new(),
-                    }
-                }
-            };
-
-            let mut edits = Vec::new();
-            if let Some(edit_value) = args.get("edit") {
-                if edit_value.is_array() {
-                    if let Ok(list) = serde_json::from_value::<Vec<tools::ReplaceEdit>>(
-                        edit_value.clone(),
-                    ) {
-                        edits = list;
-                    }
-                } else if let Ok(edit) =
-                    serde_json::from_value::<tools::ReplaceEdit>(edit_value.clone())
-                {
-                    edits.push(edit);
-                }
+        "RunInBackground" => {
+            let args = args!(tools::RunInBackgroundArgs);
+            if dry_run {
+                return tools::dry_run_shell(&args.command);
             }
-
+            processes::run_in_background_tool(
+                &state.processes,
+                session_id,
+                work_dir,
+                &args.command,
+                ctx.env,
+            )
+        }
+        "ListBackgroundProcesses" => {
+            let _ = args!(tools::ListBackgroundProcessesArgs);
+            processes::list_tool(&state.processes, session_id)
+        }
+        "ReadProcessOutput" => {
+            let args = args!(tools::ProcessIdArgs);
+            processes::read_output_tool(&state.processes, session_id, &args.id)
+        }
+        "KillProcess" => {
+            let args = args!(tools::ProcessIdArgs);
+            if dry_run {
+                return tools::ToolOutput {
+                    ok: true,
+                    summary: format!("Dry run: process {} was not stopped.", args.id),
+                    output: String::new(),
+                };
+            }
+            processes::kill_tool(&state.processes, session_id, &args.id)
+        }
+        "WriteFile" => {
+            let args = args!(tools::WriteFileArgs);
+            let mode = args.mode.unwrap_or_default();
+            tools::write_file(work_dir, &args.path, &args.content, mode.as_str(), dry_run)
+        }
+        "StrReplaceFile" => {
+            let args = args!(tools::StrReplaceFileArgs);
+            let edits = args.edit.into_edits();
             if edits.is_empty() {
                 return tools::ToolOutput {
                     ok: false,
@@ -756,46 +2414,88 @@ async fn execute_tool(
                 };
             }
 
-            tools::str_replace_file(work_dir, path, edits)
+            tools::str_replace_file(work_dir, &args.path, edits, dry_run)
+        }
+        "ApplyPatch" => {
+            let args = args!(tools::ApplyPatchArgs);
+            patch::apply_tool(work_dir, &args.patch, dry_run)
+        }
+        "MoveFile" => {
+            let args = args!(tools::MoveFileArgs);
+            file_ops::move_tool(work_dir, &args, dry_run)
+        }
+        "CopyFile" => {
+            let args = args!(tools::CopyFileArgs);
+            file_ops::copy_tool(work_dir, &args, dry_run)
+        }
+        "DeleteFile" => {
+            let args = args!(tools::DeleteFileArgs);
+            file_ops::delete_tool(work_dir, &args, dry_run)
+        }
+        "CreateDirectory" => {
+            let args = args!(tools::CreateDirectoryArgs);
+            file_ops::create_directory_tool(work_dir, &args, dry_run)
         }
         "SearchWeb" => {
-            let query = match args.get("query").and_then(|v| v.as_str()) {
-                Some(q) => q,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing query".to_string(),
-                        output: String::new(),
-                    }
-                }
-            };
-            let limit = args
-                .get("limit")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5) as usize;
-            let include_content = args
-                .get("include_content")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            tools::search_web(config_path, tool_call_id, query, limit, include_content).await
+            let args = args!(tools::SearchWebArgs);
+            tools::search_web(
+                config_path,
+                tool_call_id,
+                &args.query,
+                args.limit.unwrap_or(5),
+                args.include_content.unwrap_or(false),
+            )
+            .await
         }
         "FetchURL" => {
-            let url = match args.get("url").and_then(|v| v.as_str()) {
-                Some(u) => u,
-                None => {
-                    return tools::ToolOutput {
-                        ok: false,
-                        summary: "Missing URL".to_string(),
-                        output: String::new(),
-                    }
-                }
-            };
-            tools::fetch_url(config_path, tool_call_id, url).await
+            let args = args!(tools::FetchUrlArgs);
+            tools::fetch_url(config_path, tool_call_id, &args.url).await
+        }
+        "DownloadFile" => {
+            let args = args!(tools::DownloadFileArgs);
+            let on_progress = |chunk: &str| emit_tool_output_chunk(window, session_id, tool_call_id, chunk);
+            downloads::download_tool(work_dir, &args, dry_run, &on_progress).await
+        }
+        "ReadIssue" => {
+            let args = args!(tools::ReadIssueArgs);
+            forge::read_issue_tool(work_dir, &args).await
+        }
+        "ReadPullRequest" => {
+            let args = args!(tools::ReadPullRequestArgs);
+            forge::read_pull_request_tool(work_dir, &args).await
+        }
+        "CreatePullRequest" => {
+            let args = args!(tools::CreatePullRequestArgs);
+            forge::create_pull_request_tool(work_dir, &args, dry_run).await
+        }
+        "SemanticSearch" => {
+            let args = args!(tools::SemanticSearchArgs);
+            semantic::search_tool(window, work_dir, &args.query, args.limit.unwrap_or(8)).await
+        }
+        "DocsSearch" => {
+            let args = args!(tools::DocsSearchArgs);
+            docs::search_tool(work_dir, &args.query, args.limit.unwrap_or(5))
+        }
+        _ => {
+            if let Some(tool) = custom_tools::find(config_path, name) {
+                return custom_tools::run(&tool, work_dir, args, ctx.env, dry_run).await;
+            }
+            if dry_run && state.mcp.server_for_tool(name).is_some() {
+                return tools::ToolOutput {
+                    ok: true,
+                    summary: format!("Dry run: MCP tool {} was not called.", name),
+                    output: String::new(),
+                };
+            }
+            state
+                .mcp
+                .call_tool(name, args, crate::mcp::tool_call_timeout_ms(config_path))
+                .await
+                .unwrap_or_else(|| tools::ToolOutput {
+                    ok: false,
+                    summary: format!("Unknown tool: {}", name),
+                    output: String::new(),
+                })
         }
-        _ => tools::ToolOutput {
-            ok: false,
-            summary: format!("Unknown tool: {}", name),
-            output: String::new(),
-        },
     }
 }
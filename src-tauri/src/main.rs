@@ -1,9 +1,71 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod apply;
+mod archive;
+mod attachments;
+mod audit;
+mod approvals;
+mod background;
+mod benchmark;
+mod checkpoints;
+mod child_env;
+mod claims;
+mod code_search;
+mod commands;
+mod context;
+mod custom_tools;
+mod cost;
+mod credentials;
+mod diagnostics;
+mod docs;
+mod downloads;
+mod encoding;
+mod endpoints;
+mod evidence;
+mod export;
+mod file_index;
+mod file_ops;
+mod file_writes;
+mod forge;
+mod git;
+mod hooks;
+mod http;
+mod ignores;
 mod llm;
+mod mcp;
+mod mirror;
+mod network;
 mod oauth;
+mod outline;
+mod parts;
+mod patch;
+mod policy;
+mod providers;
+mod reauth;
+mod resume;
+mod paths;
+mod processes;
+mod profile;
+mod project_commands;
+mod replay;
+mod scratch;
+mod search;
+mod semantic;
 mod session;
+mod share;
+mod skills;
+mod snippets;
+mod stats;
+mod suggestions;
+mod symbols;
+mod templates;
+mod text_tools;
+mod tool_history;
 mod tools;
+mod watchdog;
+mod web_search;
+mod wire;
+mod worklog;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -11,6 +73,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+use tracing::Instrument;
 // 
 
 pub use oauth::{OAuthToken, load_token, save_token, delete_token, is_logged_in};
@@ -42,7 +106,72 @@ struct GuiSettings {
     model: Option<String>,
     thinking: Option<bool>,
     yolo: Option<bool>,
+    /// Preview mutating tools as diffs without touching disk
+    dry_run: Option<bool>,
+    offline: Option<bool>,
+    low_bandwidth: Option<bool>,
     pinned_sessions: Vec<String>,
+    /// Per-tool approval overrides, e.g. `{"FetchURL": true, "Shell": false}`.
+    tool_approvals: HashMap<String, bool>,
+    /// Extra CA certificates and OS store usage for all outbound HTTPS
+    tls: Option<http::TlsSettings>,
+    /// Extra environment variables for Shell commands
+    env: HashMap<String, String>,
+    /// Which GUI environment variables child processes inherit; unset keeps
+    /// only the defaults such as PATH and HOME
+    child_env: Option<child_env::EnvPolicy>,
+    /// Size, domain and content type limits for the DownloadFile tool
+    downloads: Option<downloads::DownloadPolicy>,
+    /// Checkers for the problems panel and whether they run after writes
+    diagnostics: Option<diagnostics::DiagnosticsSettings>,
+    /// GitHub and GitLab tokens for the issue and pull request tools
+    forge: Option<forge::ForgeSettings>,
+    /// Interpreter for Shell commands: bash, powershell or cmd; PowerShell on
+    /// Windows and the login shell elsewhere when unset
+    shell_interpreter: Option<tools::Interpreter>,
+    /// Tools hidden from the model, e.g. `["Shell"]`
+    disabled_tools: Vec<String>,
+    /// Named settings bundles applied when opening a work dir
+    workspace_templates: Vec<templates::WorkspaceTemplate>,
+    /// Embeddings endpoint for the SemanticSearch index; off when unset
+    embeddings: Option<semantic::EmbeddingsSettings>,
+    /// Closing the window hides it to the tray and keeps the agent running
+    background_mode: Option<bool>,
+    /// Confirmation before requests estimated to cost more than a threshold
+    cost_guard: Option<cost::CostGuardSettings>,
+    /// Per-session and per-day token and cost limits
+    budget: Option<cost::BudgetSettings>,
+    /// Automatic retries of failed tool calls
+    tool_retry: Option<llm::ToolRetrySettings>,
+    /// Backoff for model requests that hit rate limits or transient errors
+    api_retry: Option<llm::ApiRetrySettings>,
+    /// Gitignore-style patterns hidden from file listings; defaults when unset
+    ignore_patterns: Option<Vec<String>>,
+    /// Days an idle session scratch dir is kept; 0 keeps them forever
+    scratch_retention_days: Option<u64>,
+    /// Days file checkpoints are kept for rollback; 0 keeps them forever
+    checkpoint_retention_days: Option<u64>,
+    /// Seconds a silent model request runs before asking whether to keep waiting
+    model_soft_timeout_secs: Option<u64>,
+    /// File walkers follow symlinked folders that stay inside the work dir
+    follow_symlinks: Option<bool>,
+    /// Command that decides approval prompts before the user sees them
+    approval_hook: Option<approvals::ApprovalHook>,
+    /// Commands run around tool calls and at the end of each turn
+    hooks: Option<hooks::HooksSettings>,
+    /// Deny and allow rules for Shell commands, and the default preset
+    command_policy: Option<policy::PolicySettings>,
+    /// Add a summary of `workspace_stats` to the system prompt
+    workspace_stats_in_prompt: Option<bool>,
+    /// Writes to absolute paths outside the work dir: "ask" (the default) or "deny"
+    outside_writes: Option<paths::OutsideWrites>,
+    /// Port of the session share server; 0 or unset picks a free one
+    share_port: Option<u16>,
+    /// Suggest follow-up prompts after each turn with one extra model call
+    follow_up_suggestions: Option<bool>,
+    /// Model for outlines, worklogs and follow-up suggestions; unset uses the
+    /// chat model (or each feature's default)
+    summarizer_model: Option<String>,
 }
 
 #[derive(Clone, Serialize)]
@@ -51,28 +180,49 @@ struct GuiSettingsPayload {
     settings: GuiSettings,
 }
 
-#[derive(Clone, Serialize)]
-struct SkillInfo {
-    name: String,
-    description: Option<String>,
-    path: String,
-    root: String,
-}
-
-#[derive(Clone, Serialize)]
-struct SkillsPayload {
-    roots: Vec<String>,
-    skills: Vec<SkillInfo>,
-}
-
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Default)]
 struct SessionInfo {
     id: String,
     title: String,
     updated_at: f64,
     work_dir: String,
+    /// Repository name shared by all worktrees of the same origin
+    repo: Option<String>,
+    branch: Option<String>,
+    /// Display label used to group sessions, e.g. "kimi-cli (main)"
+    group: String,
+    /// Unknown for CLI sessions, which would require parsing the whole wire file
+    message_count: Option<usize>,
+    last_message_preview: Option<String>,
+    /// A tool approval is waiting or the session holds file claims
+    has_pending_changes: bool,
+    /// A turn (or an apply) is running on the session right now
+    turn_in_progress: bool,
+    /// User-assigned color tag, one of `SESSION_COLORS`
+    color: Option<String>,
+    emoji: Option<String>,
+    /// Place in the manual order; ordered sessions come first
+    position: Option<i64>,
+}
+
+/// Chat model when the settings don't pick one
+const DEFAULT_MODEL: &str = "kimi-k2.5";
+
+impl GuiSettings {
+    /// The chat model from the settings, or DEFAULT_MODEL.
+    fn chat_model(&self) -> String {
+        self.model
+            .clone()
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+    }
 }
 
+/// Color tags a session can be given
+const SESSION_COLORS: &[&str] = &["red", "orange", "yellow", "green", "blue", "purple", "gray"];
+/// Longest emoji label, in characters; flags and skin tones take several
+const MAX_EMOJI_CHARS: usize = 8;
+
 #[derive(Clone, Serialize)]
 struct AuthStatus {
     is_logged_in: bool,
@@ -85,6 +235,16 @@ pub struct AuthConfig {
     pub mode: String, // "oauth" | "api_key"
     pub api_key: Option<String>,
     pub api_base: Option<String>,
+    /// How api_key mode authenticates against `api_base`/`endpoint`; bearer
+    /// when unset. Dropped when the login moves to another provider.
+    #[serde(default)]
+    pub auth_scheme: Option<http::AuthScheme>,
+    /// Endpoint id picked at login, e.g. "kimi_code" or "moonshot_cn"
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Credentials that services pick by name instead of using the chat login
+    #[serde(default)]
+    pub credentials: HashMap<String, credentials::NamedCredential>,
 }
 
 impl Default for AuthConfig {
@@ -93,6 +253,9 @@ impl Default for AuthConfig {
             mode: "oauth".to_string(),
             api_key: None,
             api_base: None,
+            auth_scheme: None,
+            endpoint: None,
+            credentials: HashMap::new(),
         }
     }
 }
@@ -131,22 +294,60 @@ fn auth_set_config(config: AuthConfig) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn auth_set_api_key(api_key: String, api_base: Option<String>) -> Result<(), String> {
+fn auth_set_api_key(
+    api_key: String,
+    api_base: Option<String>,
+    endpoint: Option<String>,
+) -> Result<(), String> {
+    let previous = load_auth_config();
+    let api_base = api_base.filter(|b| !b.is_empty());
+    let endpoint = endpoint.filter(|e| !e.is_empty());
+    // Keep a hand-configured scheme when the key is re-entered for the same
+    // provider; another provider gets the default rather than the old scheme
+    let same_provider = previous.mode == "api_key"
+        && previous.api_base == api_base
+        && previous.endpoint == endpoint;
     let config = AuthConfig {
         mode: "api_key".to_string(),
         api_key: Some(api_key),
-        api_base: api_base.filter(|b| !b.is_empty()),
+        api_base,
+        auth_scheme: previous.auth_scheme.filter(|_| same_provider),
+        endpoint,
+        credentials: previous.credentials,
     };
     save_auth_config(&config)
 }
 
+/// Pin the endpoint used by OAuth login and chat before the device flow starts.
+#[tauri::command]
+fn auth_set_endpoint(endpoint: Option<String>) -> Result<(), String> {
+    let endpoint = endpoint.filter(|e| !e.is_empty());
+    if let Some(id) = endpoint.as_deref() {
+        if endpoints::endpoint_base(id).is_none() {
+            return Err(format!("Unknown endpoint: {}", id));
+        }
+    }
+    let mut config = load_auth_config();
+    if config.endpoint != endpoint {
+        config.auth_scheme = None;
+    }
+    config.endpoint = endpoint;
+    save_auth_config(&config)
+}
+
 #[tauri::command]
 fn auth_clear() -> Result<(), String> {
     // Clear OAuth token
     let _ = oauth::delete_token();
-    // Clear API key config
+    // Clear API key config, keeping the named service credentials
+    let credentials = load_auth_config().credentials;
     let path = auth_config_path();
-    if path.exists() {
+    if !credentials.is_empty() {
+        save_auth_config(&AuthConfig {
+            credentials,
+            ..AuthConfig::default()
+        })?;
+    } else if path.exists() {
         let _ = fs::remove_file(&path);
     }
     Ok(())
@@ -157,10 +358,69 @@ struct AppState {
     next_id: AtomicU64,
     session_manager: Mutex<SessionManager>,
     approvals: Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    /// Cost and budget confirmations waiting for the user, kept apart from tool approvals
+    cost_confirmations: Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+    offline_queue: Mutex<Vec<network::QueuedPrompt>>,
+    network_monitor: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    file_claims: claims::ClaimRegistry,
+    /// What each session last saw of the files it read or wrote
+    file_reads: file_writes::ReadTracker,
+    file_index: file_index::IndexRegistry,
+    mcp: mcp::McpRegistry,
+    mirrors: mirror::MirrorRegistry,
+    /// Stops the device-code login in progress
+    oauth_login: Mutex<Option<oauth::LoginTask>>,
+    processes: processes::ProcessRegistry,
+    shells: tools::ShellSessions,
+    shares: share::ShareRegistry,
 }
 
 struct SessionHandle {
-    cancel_tx: tokio::sync::oneshot::Sender<()>,
+    session_id: String,
+    /// Taken when the turn is cancelled; the entry itself stays until the
+    /// turn's StreamGuard drops, so no second turn starts while it winds down.
+    cancel_tx: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+const TURN_IN_PROGRESS: &str = "A turn is already in progress for this session";
+
+/// Register a cancellable stream for `session_id`. Pair with `unregister_stream`.
+/// A session runs one stream at a time, so two turns can't interleave their
+/// messages; a second one is refused with `TURN_IN_PROGRESS`.
+fn register_stream(
+    state: &AppState,
+    session_id: &str,
+) -> Result<(u64, tokio::sync::oneshot::Receiver<()>), String> {
+    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+    let mut sessions = state.sessions.lock()
+        .map_err(|_| "Session store poisoned".to_string())?;
+    if sessions.values().any(|handle| handle.session_id == session_id) {
+        return Err(TURN_IN_PROGRESS.to_string());
+    }
+    let stream_id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    sessions.insert(stream_id, SessionHandle {
+        session_id: session_id.to_string(),
+        cancel_tx: Some(cancel_tx),
+    });
+    Ok((stream_id, cancel_rx))
+}
+
+fn unregister_stream(state: &AppState, stream_id: u64) {
+    if let Ok(mut sessions) = state.sessions.lock() {
+        sessions.remove(&stream_id);
+    }
+}
+
+/// Unregisters a stream however the function holding it returns.
+struct StreamGuard<'a> {
+    state: &'a AppState,
+    stream_id: u64,
+}
+
+impl Drop for StreamGuard<'_> {
+    fn drop(&mut self) {
+        unregister_stream(self.state, self.stream_id);
+    }
 }
 
 impl Default for AppState {
@@ -170,6 +430,18 @@ impl Default for AppState {
             next_id: AtomicU64::new(1),
             session_manager: Mutex::new(SessionManager::new()),
             approvals: Mutex::new(HashMap::new()),
+            cost_confirmations: Mutex::new(HashMap::new()),
+            offline_queue: Mutex::new(Vec::new()),
+            network_monitor: Mutex::new(None),
+            file_claims: claims::ClaimRegistry::default(),
+            file_reads: file_writes::ReadTracker::default(),
+            file_index: file_index::IndexRegistry::default(),
+            mcp: mcp::McpRegistry::default(),
+            mirrors: mirror::MirrorRegistry::default(),
+            oauth_login: Mutex::new(None),
+            processes: processes::ProcessRegistry::default(),
+            shells: tools::ShellSessions::default(),
+            shares: share::ShareRegistry::default(),
         }
     }
 }
@@ -294,56 +566,6 @@ fn find_repo_root() -> Option<PathBuf> {
     None
 }
 
-fn skills_root_candidates(work_dir: &Path) -> Vec<PathBuf> {
-    let home = home_dir();
-    vec![
-        home.join(".config/agents/skills"),
-        home.join(".agents/skills"),
-        home.join(".kimi/skills"),
-        home.join(".claude/skills"),
-        home.join(".codex/skills"),
-        work_dir.join(".agents/skills"),
-        work_dir.join(".kimi/skills"),
-        work_dir.join(".claude/skills"),
-        work_dir.join(".codex/skills"),
-    ]
-}
-
-fn parse_skill_frontmatter(contents: &str) -> (Option<String>, Option<String>) {
-    let mut lines = contents.lines();
-    if lines.next().map(str::trim) != Some("---") {
-        return (None, None);
-    }
-
-    let mut name = None;
-    let mut description = None;
-
-    for line in lines {
-        let trimmed = line.trim();
-        if trimmed == "---" {
-            break;
-        }
-        if let Some((key, value)) = trimmed.split_once(':') {
-            let value = value.trim().trim_matches('"').trim_matches('\'');
-            match key.trim() {
-                "name" => {
-                    if !value.is_empty() {
-                        name = Some(value.to_string());
-                    }
-                }
-                "description" => {
-                    if !value.is_empty() {
-                        description = Some(value.to_string());
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-
-    (name, description)
-}
-
 fn truncate_with_ellipsis(input: &str, max_chars: usize) -> String {
     let total = input.chars().count();
     if total <= max_chars {
@@ -356,38 +578,53 @@ fn truncate_with_ellipsis(input: &str, max_chars: usize) -> String {
     format!("{prefix}...")
 }
 
-fn collect_skills(root: &Path) -> Vec<SkillInfo> {
-    let mut skills = Vec::new();
-    let entries = match fs::read_dir(root) {
-        Ok(entries) => entries,
-        Err(_) => return skills,
-    };
+/// Compare paths after resolving symlinks; macOS and Windows filesystems are
+/// case-insensitive by default.
+fn same_path(a: &str, b: &str) -> bool {
+    let resolve = |path: &str| Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path));
+    let (a, b) = (resolve(a), resolve(b));
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
 
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-        let skill_file = path.join("SKILL.md");
-        if !skill_file.is_file() {
-            continue;
-        }
-        let contents = fs::read_to_string(&skill_file).unwrap_or_default();
-        let (name, description) = parse_skill_frontmatter(&contents);
-        let fallback_name = path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("skill")
-            .to_string();
-        skills.push(SkillInfo {
-            name: name.unwrap_or(fallback_name),
-            description,
-            path: skill_file.to_string_lossy().to_string(),
-            root: root.to_string_lossy().to_string(),
-        });
+/// Sessions belong to the same workspace when they share a path or any
+/// worktree of the same repository.
+fn same_workspace(candidate: &str, work_dir: &str, target: Option<&git::RepoIdentity>) -> bool {
+    if same_path(candidate, work_dir) {
+        return true;
+    }
+    match (target, git::repo_identity(Path::new(candidate))) {
+        (Some(target), Some(identity)) => target.key == identity.key,
+        _ => false,
     }
+}
 
-    skills
+fn label_sessions(sessions: &mut [SessionInfo]) {
+    let mut identities: HashMap<String, Option<git::RepoIdentity>> = HashMap::new();
+    for session in sessions.iter_mut() {
+        let identity = identities
+            .entry(session.work_dir.clone())
+            .or_insert_with(|| git::repo_identity(Path::new(&session.work_dir)));
+        match identity {
+            Some(identity) => {
+                session.repo = Some(identity.name.clone());
+                session.branch = identity.branch.clone();
+                session.group = match &identity.branch {
+                    Some(branch) => format!("{} ({})", identity.name, branch),
+                    None => identity.name.clone(),
+                };
+            }
+            None => {
+                session.group = Path::new(&session.work_dir)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| session.work_dir.clone());
+            }
+        }
+    }
 }
 
 fn load_sessions(work_dir: &str) -> Result<Vec<SessionInfo>, String> {
@@ -402,14 +639,15 @@ fn load_sessions(work_dir: &str) -> Result<Vec<SessionInfo>, String> {
 
     let empty_vec = Vec::new();
     let work_dirs = data.get("work_dirs").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
+    let target = git::repo_identity(Path::new(work_dir));
 
+    let mut sessions = Vec::new();
     for wd in work_dirs {
         let path = wd.get("path").and_then(|v| v.as_str()).unwrap_or("");
-        if path == work_dir {
+        if !path.is_empty() && same_workspace(path, work_dir, target.as_ref()) {
             let kaos = wd.get("kaos").and_then(|v| v.as_str()).unwrap_or("local");
             let sessions_dir = get_session_dir(path, kaos)?;
             
-            let mut sessions = Vec::new();
             if let Ok(entries) = fs::read_dir(&sessions_dir) {
                 for entry in entries.flatten() {
                     let session_path = entry.path();
@@ -442,25 +680,26 @@ fn load_sessions(work_dir: &str) -> Result<Vec<SessionInfo>, String> {
                         .map(|d| d.as_secs_f64())
                         .unwrap_or(0.0);
 
-                    let title = extract_session_title(&wire_file).unwrap_or_else(|| {
+                    let (title, preview) = extract_session_summary(&wire_file);
+                    let title = title.unwrap_or_else(|| {
                         format!("Session {}", &session_id[..8.min(session_id.len())])
                     });
 
                     sessions.push(SessionInfo {
                         id: session_id,
+                        last_message_preview: preview,
                         title,
                         updated_at,
                         work_dir: path.to_string(),
+                        ..Default::default()
                     });
                 }
             }
-
-            sessions.sort_by(|a, b| b.updated_at.partial_cmp(&a.updated_at).unwrap());
-            return Ok(sessions);
         }
     }
-    
-    Ok(Vec::new())
+
+    sessions.sort_by(|a, b| b.updated_at.partial_cmp(&a.updated_at).unwrap());
+    Ok(sessions)
 }
 
 fn get_session_dir(work_dir: &str, kaos: &str) -> Result<PathBuf, String> {
@@ -480,39 +719,18 @@ fn get_session_dir(work_dir: &str, kaos: &str) -> Result<PathBuf, String> {
     Ok(session_dir)
 }
 
-fn extract_session_title(wire_file: &Path) -> Option<String> {
-    if !wire_file.exists() {
-        return None;
-    }
-
-    let content = fs::read_to_string(wire_file).ok()?;
-
-    for line in content.lines().take(50) {
-        if let Ok(record) = serde_json::from_str::<serde_json::Value>(line) {
-            // Handle nested message format: {"message": {"type": "TurnBegin", "payload": {"user_input": [...]}}}
-            let msg_type = record.get("message")
-                .and_then(|m| m.get("type"))
-                .and_then(|v| v.as_str());
-
-            if msg_type == Some("TurnBegin") {
-                // user_input is an array of objects with "type" and "text" fields
-                if let Some(user_input) = record.get("message")
-                    .and_then(|m| m.get("payload"))
-                    .and_then(|p| p.get("user_input"))
-                    .and_then(|u| u.as_array())
-                {
-                    for input_item in user_input {
-                        if let Some(text) = input_item.get("text").and_then(|t| t.as_str()) {
-                            let title = truncate_with_ellipsis(text, 50);
-                            return Some(title);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    None
+/// Title and preview of a CLI session: its first user message, and the text
+/// of its last user or assistant message.
+fn extract_session_summary(wire_file: &Path) -> (Option<String>, Option<String>) {
+    let Ok(content) = fs::read_to_string(wire_file) else {
+        return (None, None);
+    };
+    let messages = wire::transcript(&wire::parse(&content).records, 0);
+    let title = messages
+        .iter()
+        .find(|message| message.role == "user" && !message.content.trim().is_empty())
+        .map(|message| truncate_with_ellipsis(message.content.trim(), 50));
+    (title, session::message_preview(&messages))
 }
 
 #[tauri::command]
@@ -644,8 +862,20 @@ fn gui_settings_load(path: Option<String>) -> Result<GuiSettingsPayload, String>
         });
     }
     let raw = read_text(&path)?;
-    let settings: GuiSettings =
+    let mut settings: GuiSettings =
         serde_json::from_str(&raw).map_err(|error| format!("Invalid GUI settings: {error}"))?;
+    // Older versions kept forge tokens in this file
+    if settings.forge.as_mut().is_some_and(forge::migrate_tokens) {
+        let raw = serde_json::to_string_pretty(&settings).map_err(|error| error.to_string())?;
+        write_text(&path, &raw)?;
+    }
+    network::set_offline(settings.offline.unwrap_or(false));
+    network::set_low_bandwidth(settings.low_bandwidth.unwrap_or(false));
+    http::set_tls(settings.tls.clone().unwrap_or_default());
+    child_env::set_policy(settings.child_env.clone().unwrap_or_default());
+    downloads::set_policy(settings.downloads.clone().unwrap_or_default());
+    forge::set_settings(settings.forge.clone().unwrap_or_default());
+    diagnostics::set_settings(settings.diagnostics.clone().unwrap_or_default());
     Ok(GuiSettingsPayload {
         path: path.to_string_lossy().to_string(),
         settings,
@@ -657,54 +887,22 @@ fn gui_settings_save(path: Option<String>, settings: GuiSettings) -> Result<(),
     let path = path.map(PathBuf::from).unwrap_or_else(default_gui_path);
     let raw = serde_json::to_string_pretty(&settings).map_err(|error| error.to_string())?;
     write_text(&path, &raw)?;
+    network::set_offline(settings.offline.unwrap_or(false));
+    network::set_low_bandwidth(settings.low_bandwidth.unwrap_or(false));
+    http::set_tls(settings.tls.unwrap_or_default());
+    child_env::set_policy(settings.child_env.unwrap_or_default());
+    downloads::set_policy(settings.downloads.unwrap_or_default());
+    forge::set_settings(settings.forge.unwrap_or_default());
+    diagnostics::set_settings(settings.diagnostics.unwrap_or_default());
     Ok(())
 }
 
-#[tauri::command]
-fn skills_list(work_dir: Option<String>, skills_dir: Option<String>) -> Result<SkillsPayload, String> {
-    let work_dir = work_dir
-        .map(PathBuf::from)
-        .unwrap_or_else(|| find_repo_root().unwrap_or_else(|| PathBuf::from(".")));
-
-    let mut roots = Vec::new();
-    if let Some(skills_dir) = skills_dir {
-        let root = PathBuf::from(skills_dir);
-        if root.is_dir() {
-            roots.push(root);
-        }
-    } else {
-        for root in skills_root_candidates(&work_dir) {
-            if root.is_dir() {
-                roots.push(root);
-            }
-        }
-    }
-
-    let mut seen = HashMap::new();
-    let mut skills = Vec::new();
-    for root in &roots {
-        for skill in collect_skills(root) {
-            let key = skill.name.to_lowercase();
-            if !seen.contains_key(&key) {
-                seen.insert(key, true);
-                skills.push(skill);
-            }
-        }
-    }
-
-    Ok(SkillsPayload {
-        roots: roots
-            .into_iter()
-            .map(|root| root.to_string_lossy().to_string())
-            .collect(),
-        skills,
-    })
-}
-
 #[tauri::command]
 fn session_list(
     state: tauri::State<'_, AppState>,
-    work_dir: Option<String>
+    work_dir: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
 ) -> Result<Vec<SessionInfo>, String> {
     let mut sessions = Vec::new();
     
@@ -713,35 +911,48 @@ fn session_list(
         sessions = load_sessions(wd)?;
     }
     
-    // Also load GUI sessions from SessionManager
-    let mut manager = state.session_manager.lock()
+    // Also load GUI session summaries from SessionManager
+    let manager = state.session_manager.lock()
         .map_err(|_| "Session manager poisoned".to_string())?;
     
-    if let Ok(gui_sessions) = manager.load_all_sessions() {
-        for session in &gui_sessions {
-            let include = if let Some(ref wd) = work_dir {
-                // Normalize paths for comparison
-                let session_path = Path::new(&session.work_dir).canonicalize().ok().unwrap_or_else(|| Path::new(&session.work_dir).to_path_buf());
-                let work_path = Path::new(wd).canonicalize().ok().unwrap_or_else(|| Path::new(wd).to_path_buf());
-                session_path == work_path || session.work_dir == *wd
-            } else {
-                // If no work_dir filter, include all sessions
-                true
-            };
-            
-            if include {
-                sessions.push(SessionInfo {
-                    id: session.id.clone(),
-                    title: session.title.clone(),
-                    updated_at: session.updated_at as f64,
-                    work_dir: session.work_dir.clone(),
-                });
-            }
+    let target = work_dir.as_deref().and_then(|wd| git::repo_identity(Path::new(wd)));
+    for session in manager.list_summaries() {
+        let include = if let Some(ref wd) = work_dir {
+            session.work_dir == *wd || same_workspace(&session.work_dir, wd, target.as_ref())
+        } else {
+            // If no work_dir filter, include all sessions
+            true
+        };
+        
+        if include {
+            sessions.push(SessionInfo {
+                id: session.id,
+                title: session.title,
+                updated_at: session.updated_at as f64,
+                work_dir: session.work_dir,
+                message_count: Some(session.message_count),
+                last_message_preview: session.last_message_preview,
+                ..Default::default()
+            });
+        }
+    }
+    let labels = manager.labels();
+    drop(manager);
+    for session in sessions.iter_mut() {
+        if let Some(label) = labels.get(&session.id) {
+            session.color = label.color.clone();
+            session.emoji = label.emoji.clone();
+            session.position = label.position;
         }
     }
     
-    // Sort by updated_at descending
-    sessions.sort_by(|a, b| b.updated_at.partial_cmp(&a.updated_at).unwrap());
+    // Manually ordered sessions first, then by updated_at descending
+    sessions.sort_by(|a, b| match (a.position, b.position) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.updated_at.partial_cmp(&a.updated_at).unwrap(),
+    });
     
     // Remove duplicates (same id)
     let mut seen = HashMap::new();
@@ -752,8 +963,37 @@ fn session_list(
             unique.push(s);
         }
     }
+
+    let mut page: Vec<SessionInfo> = unique
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    label_sessions(&mut page);
+
+    let pending_approvals: Vec<String> = state
+        .approvals
+        .lock()
+        .map_err(|_| "Approval store poisoned".to_string())?
+        .keys()
+        .cloned()
+        .collect();
+    let claims = state.file_claims.list();
+    let running: Vec<String> = state
+        .sessions
+        .lock()
+        .map_err(|_| "Session store poisoned".to_string())?
+        .values()
+        .map(|handle| handle.session_id.clone())
+        .collect();
+    for session in page.iter_mut() {
+        let prefix = format!("{}:", session.id);
+        session.has_pending_changes = pending_approvals.iter().any(|id| id.starts_with(&prefix))
+            || claims.iter().any(|claim| claim.session_id == session.id);
+        session.turn_in_progress = running.contains(&session.id);
+    }
     
-    Ok(unique)
+    Ok(page)
 }
 
 #[tauri::command]
@@ -797,20 +1037,13 @@ fn session_messages(
         }
     }
     
-    // Try loading from disk
+    // Try loading from the session database
     {
         let mut manager = state.session_manager.lock()
             .map_err(|_| "Session manager poisoned".to_string())?;
         
-        match manager.load_all_sessions() {
-            Ok(sessions) => {
-                for session in sessions {
-                    if session.id == session_id {
-                        return Ok(session.messages);
-                    }
-                }
-            }
-            Err(_) => {}
+        if let Some(session) = manager.find_session(&session_id) {
+            return Ok(session.messages);
         }
     }
     
@@ -825,13 +1058,37 @@ fn session_messages(
                     return Ok(messages);
                 }
             }
-            Err(_) => {}
+            // A wire format this GUI can't read; say so instead of showing an empty session
+            Err(error) => return Err(error),
         }
     }
     
     Ok(Vec::new())
 }
 
+/// One page of a GUI session's messages, oldest first, read from the session
+/// database without loading the whole transcript.
+#[tauri::command]
+fn session_messages_page(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Vec<Message>, String> {
+    let manager = state.session_manager.lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+    if let Some(session) = manager.sessions.get(&session_id) {
+        return Ok(session
+            .messages
+            .iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect());
+    }
+    manager.message_page(&session_id, offset.unwrap_or(0), limit.unwrap_or(usize::MAX))
+}
+
 #[tauri::command]
 fn session_save_message(
     state: tauri::State<'_, AppState>,
@@ -844,12 +1101,7 @@ fn session_save_message(
     let mut manager = state.session_manager.lock()
         .map_err(|_| "Session manager poisoned".to_string())?;
     
-    let message = SessionMessage {
-        role: role.clone(),
-        content: content.clone(),
-        timestamp: chrono::Utc::now().timestamp(),
-        tool_calls: None,
-    };
+    let message = SessionMessage::new(&role, content);
     
     // Save to file and add to memory
     match manager.save_message(&session_id, &message) {
@@ -875,6 +1127,59 @@ fn session_delete(
         .lock()
         .map_err(|_| "Session manager poisoned".to_string())?;
     manager.delete_session(&work_dir, &session_id)?;
+    scratch::remove(&session_id);
+    resume::finish(&session_id);
+    state.file_reads.forget_session(&session_id);
+    cost::forget_session(&session_id);
+    Ok(())
+}
+
+/// Set a session's color tag and emoji; None (or empty) clears them. Other
+/// windows are told through a `sessions://changed` event.
+#[tauri::command]
+fn session_set_label(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    color: Option<String>,
+    emoji: Option<String>,
+) -> Result<(), String> {
+    let color = color.filter(|c| !c.is_empty());
+    if let Some(color) = color.as_deref().filter(|c| !SESSION_COLORS.contains(c)) {
+        return Err(format!(
+            "Unknown session color {}; use one of {}",
+            color,
+            SESSION_COLORS.join(", ")
+        ));
+    }
+    let emoji = emoji.map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+    if emoji.as_ref().is_some_and(|e| e.chars().count() > MAX_EMOJI_CHARS) {
+        return Err("Session emoji must be a single emoji".to_string());
+    }
+    state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .set_label(&session_id, color.as_deref(), emoji.as_deref())?;
+    let _ = window.emit("sessions://changed", ());
+    Ok(())
+}
+
+/// Put the sessions in `session_ids` first, in that order, and send those in
+/// `unordered` back to newest-first below them.
+#[tauri::command]
+fn session_reorder(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_ids: Vec<String>,
+    unordered: Option<Vec<String>>,
+) -> Result<(), String> {
+    state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .set_order(&session_ids, &unordered.unwrap_or_default())?;
+    let _ = window.emit("sessions://changed", ());
     Ok(())
 }
 
@@ -885,78 +1190,223 @@ async fn chat_stream(
     session_id: String,
     message: String,
     settings: Option<GuiSettings>,
+    attachments: Option<Vec<attachments::Attachment>>,
+    skill: Option<String>,
+) -> Result<(), String> {
+    run_turn(window, state, session_id, Some(message), settings, attachments, skill).await
+}
+
+/// Pick up a turn a crash or force-quit cut short, at the step it was on.
+/// Tool calls that had finished are not run again.
+#[tauri::command]
+async fn chat_resume_turn(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    settings: Option<GuiSettings>,
+) -> Result<(), String> {
+    run_turn(window, state, session_id, None, settings, None, None).await
+}
+
+/// Run a chat turn for `message`, or resume the session's interrupted turn
+/// when `message` is None.
+async fn run_turn(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    message: Option<String>,
+    settings: Option<GuiSettings>,
+    attachments: Option<Vec<attachments::Attachment>>,
+    skill: Option<String>,
 ) -> Result<(), String> {
     use crate::session::{Message as SessionMessage};
     
+    // Claimed before anything is saved, so a refused turn leaves no trace
+    let (stream_id, cancel_rx) = register_stream(&state, &session_id)?;
+    let stream = StreamGuard {
+        state: &state,
+        stream_id,
+    };
     let settings = settings.unwrap_or_default();
+    let resumed = match message {
+        Some(_) => None,
+        None => Some(
+            resume::load(&session_id)
+                .ok_or_else(|| "This session has no interrupted turn".to_string())?,
+        ),
+    };
+    let message = message.unwrap_or_default();
+    let images = attachments::prepare(&attachments.unwrap_or_default())?;
+    let skill = skill
+        .filter(|path| !path.is_empty())
+        .map(|path| skills::load(&path))
+        .transpose()?;
     
-    let model = settings.model
-        .filter(|m| !m.is_empty())
-        .unwrap_or_else(|| "kimi-k2.5".to_string());
+    let model = settings.chat_model();
     
-    let work_dir = settings.work_dir
-        .unwrap_or_else(|| app_paths().work_dir);
+    // A resumed turn stays in the folder it started in
+    let work_dir = match &resumed {
+        Some(turn) => turn.work_dir.clone(),
+        None => settings.work_dir.unwrap_or_else(|| app_paths().work_dir),
+    };
 
     let config_path = settings
         .config_file
         .filter(|path| !path.is_empty())
         .or_else(|| Some(app_paths().config));
 
-    let auto_approve = settings.yolo.unwrap_or(false);
+    let policy_settings = settings.command_policy.clone().unwrap_or_default();
+    let command_policy = policy::resolve(
+        &state,
+        &session_id,
+        &policy_settings,
+        settings.yolo.unwrap_or(false),
+    );
     
     // Load auth config
     let auth_config = load_auth_config();
+
+    // Offline mode: hold the prompt until connectivity returns instead of failing mid-turn
+    if network::ensure_online(&llm::provider_base(&auth_config)).is_err() {
+        if resumed.is_some() {
+            return Err(network::OFFLINE_ERROR.to_string());
+        }
+        if !images.is_empty() {
+            return Err(format!("{} Messages with images can't be queued.", network::OFFLINE_ERROR));
+        }
+        network::queue_prompt(&state, &session_id, &message)?;
+        let _ = window.emit("chat://event", llm::StreamEvent {
+            event: "error".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "kind": "offline",
+                "queued": true,
+                "message": format!("{} Message queued until you go back online.", network::OFFLINE_ERROR),
+            }),
+        });
+        return Ok(());
+    }
     
     let title = truncate_with_ellipsis(&message, 50);
+    // `/name args` runs a custom command; the expanded prompt is what gets saved and sent
+    let message = commands::expand(&work_dir, &message).unwrap_or(message);
     
-    // Create or get session and save user message
-    {
+    // Create or get session, take its history and save user message
+    let history = {
         let mut manager = state.session_manager.lock()
             .map_err(|_| "Session manager poisoned".to_string())?;
         
-        // Get or create session
-        let _session = manager.get_or_create_session(&session_id, &title, &work_dir);
-        
-        // Save user message
-        let user_msg = SessionMessage {
-            role: "user".to_string(),
-            content: message.clone(),
-            timestamp: chrono::Utc::now().timestamp(),
-            tool_calls: None,
+        // Sessions from earlier runs are only on disk; cache them so the user
+        // message is added to the full transcript
+        let history = match manager.find_session(&session_id) {
+            Some(session) => {
+                let history = session.messages.clone();
+                manager.sessions.entry(session_id.clone()).or_insert(session);
+                history
+            }
+            None if resumed.is_some() => return Err("Session not found".to_string()),
+            // A CLI session picked up where the terminal left off
+            None => match manager.resume_cli_session(&session_id, &work_dir)? {
+                Some(session) => session.messages,
+                None => {
+                    manager.get_or_create_session(&session_id, &title, &work_dir);
+                    Vec::new()
+                }
+            },
         };
-        let _ = manager.save_message(&session_id, &user_msg);
-        let _ = manager.add_message(&session_id, user_msg);
-    }
-    
-    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+        
+        // A resumed turn's user message and finished tool calls are already saved
+        if resumed.is_none() {
+            let mut user_msg = SessionMessage::new("user", message.clone());
+            user_msg.parts.extend(images.iter().cloned());
+            state.mirrors.record(&session_id, &user_msg);
+            state.shares.record(&session_id, &user_msg);
+            let _ = manager.save_message(&session_id, &user_msg);
+            let _ = manager.add_message(&session_id, user_msg);
+            resume::begin(&session_id, &work_dir, &model, &message);
+        }
+        history
+    };
     
-    {
-        let mut sessions = state.sessions.lock()
-            .map_err(|_| "Session store poisoned".to_string())?;
-        let stream_id = state.next_id.fetch_add(1, Ordering::Relaxed);
-        sessions.insert(stream_id, SessionHandle { cancel_tx });
+    // Start MCP servers from mcp.json so their tools are offered this turn
+    for (server, error) in state.mcp.ensure_started(&settings.mcp_config_files).await {
+        let _ = window.emit("chat://event", llm::StreamEvent {
+            event: "mcp_error".to_string(),
+            data: serde_json::json!({
+                "session_id": session_id,
+                "server": server,
+                "message": error,
+            }),
+        });
     }
     
     let window_clone = window.clone();
     let session_id_clone = session_id.clone();
     
+    let turn_hooks = settings.hooks.clone().unwrap_or_default();
+    let suggestions_model = settings
+        .follow_up_suggestions
+        .unwrap_or(false)
+        .then(|| model.clone());
+    let summarizer_model = settings.summarizer_model.clone();
+    let summarizer_config = config_path.clone();
+
     // Wrap the stream_chat to capture the response
+    let options = llm::ChatOptions {
+        model,
+        work_dir: work_dir.clone(),
+        config_path,
+        command_policy,
+        directory_rules: policy_settings.directories,
+        tool_approvals: settings.tool_approvals,
+        dry_run: settings.dry_run.unwrap_or(false),
+        history,
+        env: settings.env,
+        shell_interpreter: settings.shell_interpreter,
+        disabled_tools: settings.disabled_tools,
+        cost_guard: settings.cost_guard.unwrap_or_default(),
+        budget: settings.budget.unwrap_or_default(),
+        tool_retry: settings.tool_retry.unwrap_or_default(),
+        api_retry: settings.api_retry.unwrap_or_default(),
+        approval_hook: settings.approval_hook.unwrap_or_default(),
+        hooks: turn_hooks.clone(),
+        attachments: images,
+        soft_timeout_secs: settings
+            .model_soft_timeout_secs
+            .unwrap_or(watchdog::DEFAULT_SOFT_TIMEOUT_SECS),
+        skill,
+        resume_from: resumed.map(|turn| turn.steps),
+    };
+
     let result = llm::stream_chat(
         window_clone,
         state.clone(),
         session_id_clone,
         message,
-        model,
-        work_dir.clone(),
-        config_path,
-        auto_approve,
+        options,
         auth_config,
         cancel_rx,
-    ).await;
+    )
+    .instrument(tracing::info_span!("turn", session_id = %session_id))
+    .await;
+    drop(stream);
+    resume::finish(&session_id);
+    state.mirrors.turn_ended(&session_id);
+    state.shares.turn_ended(&session_id);
+    let error = result.as_ref().err().map(String::as_str);
+    hooks::turn_end(&turn_hooks, &session_id, &work_dir, error).await;
+    if let Some(model) = suggestions_model.filter(|_| result.is_ok()) {
+        suggestions::spawn(
+            window.clone(),
+            &state,
+            &session_id,
+            model,
+            summarizer_model,
+            summarizer_config,
+        );
+    }
     
-    // Note: We can't easily capture the content from stream_chat since it emits to window.
-    // For now, sessions will be tracked but full message persistence requires 
-    // either a callback mechanism or frontend sending back the complete response.
+    // stream_chat persists assistant replies and tool results as they complete
     
     // Update session timestamp
     {
@@ -973,38 +1423,72 @@ async fn chat_stream(
     result
 }
 
+/// Answer a pending approval. An approved request can also save `rule` so
+/// matching calls in that work dir are not asked about again.
 #[tauri::command]
 fn tool_approval_respond(
     state: tauri::State<'_, AppState>,
     request_id: String,
     approved: bool,
+    rule: Option<approvals::ApprovalRule>,
 ) -> Result<(), String> {
-    let mut approvals = state
+    let tx = state
         .approvals
         .lock()
-        .map_err(|_| "Approval store poisoned".to_string())?;
-    if let Some(tx) = approvals.remove(&request_id) {
-        let _ = tx.send(approved);
-        Ok(())
-    } else {
-        Err("Approval request not found".to_string())
+        .map_err(|_| "Approval store poisoned".to_string())?
+        .remove(&request_id)
+        .ok_or_else(|| "Approval request not found".to_string())?;
+    let _ = tx.send(approved);
+    if let Some(rule) = rule.filter(|_| approved) {
+        approvals::approval_rule_add(rule)?;
     }
+    Ok(())
 }
 
+/// Withdraw a pending approval. The agent loop carries on as if the call had
+/// been denied.
 #[tauri::command]
-fn cancel_chat(state: tauri::State<'_, AppState>) -> Result<(), String> {
+fn tool_approval_cancel(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    request_id: String,
+) -> Result<(), String> {
+    let tx = state
+        .approvals
+        .lock()
+        .map_err(|_| "Approval store poisoned".to_string())?
+        .remove(&request_id)
+        .ok_or_else(|| "Approval request not found".to_string())?;
+    let _ = tx.send(false);
+    llm::emit_approval_cancelled(&window, &request_id, "user");
+    Ok(())
+}
+
+#[tauri::command]
+/// Cancel the streams of one session, or of every session when `session_id` is omitted.
+fn cancel_chat(state: tauri::State<'_, AppState>, session_id: Option<String>) -> Result<(), String> {
     let mut sessions = state.sessions.lock()
         .map_err(|_| "Session store poisoned".to_string())?;
     
-    for (_, handle) in sessions.drain() {
-        let _ = handle.cancel_tx.send(());
+    for handle in sessions
+        .values_mut()
+        .filter(|handle| session_id.as_ref().is_none_or(|id| handle.session_id == *id))
+    {
+        if let Some(cancel_tx) = handle.cancel_tx.take() {
+            let _ = cancel_tx.send(());
+        }
     }
     
     Ok(())
 }
 
 #[tauri::command]
-fn list_files(work_dir: String, query: Option<String>) -> Result<Vec<String>, String> {
+fn list_files(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    work_dir: String,
+    query: Option<String>,
+) -> Result<Vec<String>, String> {
     let root = Path::new(&work_dir);
     if !root.exists() {
         return Ok(Vec::new());
@@ -1013,17 +1497,15 @@ fn list_files(work_dir: String, query: Option<String>) -> Result<Vec<String>, St
     let mut files = Vec::new();
     let query_lower = query.unwrap_or_default().to_lowercase();
     
-    fn is_ignored(name: &str) -> bool {
-        let ignored = [
-            ".git", ".svn", ".hg", ".DS_Store",
-            "node_modules", "target", "dist", "build",
-            ".venv", "venv", "__pycache__", ".pytest_cache",
-            ".idea", ".vscode", ".next", ".nuxt",
-        ];
-        ignored.iter().any(|&i| name == i || name.starts_with('.'))
-    }
-    
-    fn walk_dir(path: &Path, root: &Path, files: &mut Vec<String>, query: &str, limit: usize) {
+    fn walk_dir(
+        path: &Path,
+        root: &Path,
+        ignores: &ignores::IgnoreList,
+        dirs: &mut paths::DirWalk,
+        files: &mut Vec<String>,
+        query: &str,
+        limit: usize,
+    ) {
         if files.len() >= limit {
             return;
         }
@@ -1034,45 +1516,46 @@ fn list_files(work_dir: String, query: Option<String>) -> Result<Vec<String>, St
                     break;
                 }
                 
-                let name = entry.file_name().to_string_lossy().to_string();
-                if is_ignored(&name) {
+                let path = entry.path();
+                let is_dir = path.is_dir();
+                if ignores.is_ignored(&path, is_dir) {
                     continue;
                 }
                 
-                let path = entry.path();
                 let rel_path = path.strip_prefix(root).unwrap_or(&path);
                 let rel_str = rel_path.to_string_lossy().to_string();
                 
-                if query.is_empty() || rel_str.to_lowercase().contains(query) {
+                // Links to protected files or out of the work dir aren't offered
+                let reachable = || {
+                    paths::resolve_inside(&root.to_string_lossy(), &rel_str, paths::Access::Read)
+                        .is_ok()
+                };
+                if (query.is_empty() || rel_str.to_lowercase().contains(query)) && reachable() {
                     files.push(rel_str);
                 }
                 
-                if path.is_dir() {
-                    walk_dir(&path, root, files, query, limit);
+                if is_dir && dirs.enter(&path) {
+                    walk_dir(&path, root, ignores, dirs, files, query, limit);
                 }
             }
         }
     }
     
-    walk_dir(root, root, &mut files, &query_lower, 50);
+    let root = root.canonicalize().map_err(|e| format!("Failed to resolve work dir: {}", e))?;
+    // Large repos are looked up in the on-disk index instead of walked
+    if let Some(files) = state.file_index.search(&window, &root, &query_lower, 50) {
+        return Ok(files);
+    }
+    let ignores = ignores::IgnoreList::load(&root);
+    let mut dirs = paths::DirWalk::new(&root);
+    walk_dir(&root, &root, &ignores, &mut dirs, &mut files, &query_lower, 50);
     files.sort();
     Ok(files)
 }
 
 #[tauri::command]
 fn read_file(work_dir: String, file_path: String) -> Result<String, String> {
-    let root = Path::new(&work_dir);
-    let full_path = root.join(&file_path);
-    
-    // Security: ensure the path is within work_dir
-    let canonical = full_path.canonicalize()
-        .map_err(|e| format!("Failed to resolve path: {}", e))?;
-    let canonical_root = root.canonicalize()
-        .map_err(|e| format!("Failed to resolve work dir: {}", e))?;
-    
-    if !canonical.starts_with(&canonical_root) {
-        return Err("Path is outside working directory".to_string());
-    }
+    let canonical = paths::resolve_inside(&work_dir, &file_path, paths::Access::Read)?;
     
     // Limit file size to 100KB
     let metadata = std::fs::metadata(&canonical)
@@ -1097,9 +1580,38 @@ async fn pick_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
 }
 
 fn main() {
+    profile::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![background::BACKGROUND_ARG]),
+        ))
         .manage(AppState::default())
+        .setup(|app| {
+            let settings = gui_settings_load(None)
+                .map(|payload| payload.settings)
+                .unwrap_or_default();
+            let retention_days = settings
+                .scratch_retention_days
+                .unwrap_or(scratch::DEFAULT_RETENTION_DAYS);
+            let checkpoint_days = settings
+                .checkpoint_retention_days
+                .unwrap_or(checkpoints::DEFAULT_RETENTION_DAYS);
+            std::thread::spawn(move || {
+                scratch::prune(retention_days);
+                checkpoints::prune(checkpoint_days);
+            });
+            Ok(background::setup(app)?)
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if background::keep_running() {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             app_info,
             app_paths,
@@ -1109,33 +1621,142 @@ fn main() {
             mcp_load,
             mcp_save,
             mcp_save_raw,
+            mcp::mcp_server_status,
+            mcp::mcp_server_start,
+            mcp::mcp_server_stop,
+            mcp::mcp_server_test_connection,
             gui_settings_load,
             gui_settings_save,
-            skills_list,
+            skills::skills_list,
+            commands::commands_list,
+            skills::skill_load,
+            skills::skill_create,
+            skills::skill_update,
+            skills::skill_duplicate,
+            skills::skill_delete,
             session_list,
             auth_check_status,
             auth_get_config,
             auth_set_config,
             auth_set_api_key,
             auth_clear,
+            credentials::auth_credentials_list,
+            credentials::auth_credential_set,
+            credentials::auth_credential_delete,
             session_messages,
+            session_messages_page,
             session_save_message,
             session_delete,
+            session_set_label,
+            session_reorder,
+            wire::wire_compat_check,
+            search::session_search,
             chat_stream,
+            chat_resume_turn,
+            resume::interrupted_turns,
+            resume::interrupted_turn_discard,
+            policy::session_policy_get,
+            policy::session_policy_set,
+            stats::workspace_stats,
             cancel_chat,
             list_files,
+            file_index::file_index_build,
+            scratch::scratch_dir,
+            providers::providers_list,
+            ignores::ignore_patterns,
             read_file,
             pick_folder,
+            background::background_status,
+            background::autostart_set,
             tool_approval_respond,
+            tool_approval_cancel,
+            watchdog::model_wait_respond,
+            cost::cost_confirmation_respond,
+            cost::cost_usage,
+            approvals::approval_rules_list,
+            audit::approval_audit_list,
+            approvals::approval_rule_add,
+            approvals::approval_rule_delete,
+            claims::file_claims_list,
+            claims::file_claims_release,
+            processes::background_process_start,
+            processes::background_processes_list,
+            processes::background_process_output,
+            processes::background_process_kill,
+            processes::background_process_remove,
+            tools::reset_shell,
+            tools::tools_get_available,
             // OAuth commands
             oauth::oauth_check_status,
             oauth::oauth_logout,
             oauth::oauth_start_login,
+            oauth::oauth_cancel_login,
             oauth::oauth_open_browser,
             oauth::oauth_get_user,
             // LLM commands
             llm::llm_fetch_models,
+            network::network_status,
+            network::network_set_offline,
+            network::network_check,
+            network::network_monitor_start,
+            network::network_monitor_stop,
+            replay::session_replay,
+            benchmark::benchmark_models,
+            profile::profile_capture,
+            apply::apply_code_blocks,
+            context::context_pack,
+            semantic::semantic_index,
+            semantic::semantic_search,
+            docs::docs_sources_list,
+            docs::docs_source_add,
+            docs::docs_source_reindex,
+            docs::docs_source_remove,
+            docs::docs_search,
+            outline::session_outline,
+            suggestions::session_suggestions,
+            tool_history::tool_history_search,
+            tool_history::tool_history_rerun,
+            worklog::session_worklog,
+            child_env::child_env_preview,
+            export::session_export,
+            archive::session_export_archive,
+            archive::session_import_archive,
+            mirror::session_mirror_start,
+            mirror::session_mirror_stop,
+            mirror::session_mirror_list,
+            share::session_share_start,
+            share::session_share_stop,
+            share::session_share_list,
+            project_commands::project_commands,
+            diagnostics::diagnostics_run,
+            symbols::code_outline,
+            forge::forge_linked_issues,
+            git::git_status,
+            git::git_diff,
+            git::git_log,
+            git::git_commit,
+            git::git_branch_info,
+            evidence::message_evidence,
+            checkpoints::file_versions,
+            checkpoints::file_rollback,
+            snippets::snippets_list,
+            snippets::snippets_save,
+            snippets::snippets_delete,
+            snippets::snippets_import,
+            templates::workspace_template_list,
+            templates::workspace_template_save,
+            templates::workspace_template_delete,
+            templates::workspace_template_apply,
+            http::tls_diagnose,
+            endpoints::endpoints_probe,
+            auth_set_endpoint,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Also reached by the tray's Quit, which calls `app.exit`
+            if let tauri::RunEvent::Exit = event {
+                app.state::<AppState>().processes.kill_all();
+            }
+        });
 }
@@ -0,0 +1,856 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, Command};
+use tokio::sync::oneshot;
+use tauri::Emitter;
+use tokio::time::{timeout, Duration};
+
+use crate::tools::{self, ToolOutput};
+use crate::AppState;
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const HANDSHAKE_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_TOOL_CALL_TIMEOUT_MS: u64 = 60_000;
+/// Stderr beyond this is dropped from the server's log
+const MAX_LOG_BYTES: usize = 1024 * 1024;
+/// Last stderr lines quoted when a server fails to start
+const STDERR_TAIL_LINES: usize = 20;
+
+/// A stdio server entry from `mcpServers` in mcp.json.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct McpTool {
+    pub server: String,
+    pub name: String,
+    pub description: String,
+    pub input_schema: serde_json::Value,
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<serde_json::Value, String>>>>>;
+type StderrTail = Arc<Mutex<Vec<String>>>;
+
+/// JSON-RPC over the server's stdin/stdout, one message per line.
+struct Connection {
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    _child: Child,
+    pending: Pending,
+    next_id: AtomicU64,
+}
+
+impl Connection {
+    async fn send(&self, message: &serde_json::Value) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to MCP server: {}", e))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write to MCP server: {}", e))
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout_ms: u64,
+    ) -> Result<serde_json::Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .map_err(|_| "MCP connection poisoned".to_string())?
+            .insert(id, tx);
+
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if let Err(err) = self.send(&message).await {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.remove(&id);
+            }
+            return Err(err);
+        }
+
+        match timeout(Duration::from_millis(timeout_ms), rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("MCP server closed the connection".to_string()),
+            Err(_) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.remove(&id);
+                }
+                Err(format!("MCP request {} timed out after {} ms", method, timeout_ms))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str) -> Result<(), String> {
+        self.send(&serde_json::json!({ "jsonrpc": "2.0", "method": method }))
+            .await
+    }
+}
+
+/// Route responses from stdout to their waiting requests until the server exits.
+async fn read_responses(stdout: tokio::process::ChildStdout, pending: Pending) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        // Server-initiated requests and notifications carry a method; ignore them
+        if message.get("method").is_some() {
+            continue;
+        }
+        let Some(id) = message.get("id").and_then(|v| v.as_u64()) else {
+            continue;
+        };
+        let result = match message.get("error") {
+            Some(error) => Err(error
+                .get("message")
+                .and_then(|v| v.as_str())
+                .unwrap_or("MCP error")
+                .to_string()),
+            None => Ok(message.get("result").cloned().unwrap_or_default()),
+        };
+        let sender = pending.lock().ok().and_then(|mut pending| pending.remove(&id));
+        if let Some(sender) = sender {
+            let _ = sender.send(result);
+        }
+    }
+    // Dropping the senders fails every request still waiting
+    if let Ok(mut pending) = pending.lock() {
+        pending.clear();
+    }
+}
+
+/// Where a server's stderr goes, replaced each time it starts.
+fn log_path(name: &str) -> PathBuf {
+    let file: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    crate::kimi_share_dir()
+        .join("gui_mcp_logs")
+        .join(format!("{}.log", file))
+}
+
+/// Copy the server's stderr to its log file until it exits, keeping the last
+/// lines for start errors.
+async fn log_stderr(stderr: ChildStderr, path: PathBuf, tail: StderrTail) {
+    let mut file = crate::ensure_parent(&path)
+        .ok()
+        .and_then(|_| std::fs::File::create(&path).ok());
+    let mut written = 0;
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(file) = file.as_mut().filter(|_| written < MAX_LOG_BYTES) {
+            let _ = writeln!(file, "{}", line);
+            written += line.len() + 1;
+        }
+        if let Ok(mut tail) = tail.lock() {
+            tail.push(line);
+            if tail.len() > STDERR_TAIL_LINES {
+                tail.remove(0);
+            }
+        }
+    }
+}
+
+pub struct McpServer {
+    pub config: ServerConfig,
+    pub tools: Vec<McpTool>,
+    connection: Connection,
+}
+
+impl McpServer {
+    /// Spawn the server, perform the initialize handshake and list its tools.
+    /// Its stderr is logged, and quoted in the error when the handshake fails.
+    async fn start(config: ServerConfig) -> Result<Self, String> {
+        let mut cmd = Command::new(&config.command);
+        crate::child_env::scrub(&mut cmd);
+        cmd.args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = config.cwd.as_deref() {
+            cmd.current_dir(cwd);
+        }
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", config.command, e))?;
+        let stdin = child.stdin.take().ok_or_else(|| "MCP server has no stdin".to_string())?;
+        let stdout = child.stdout.take().ok_or_else(|| "MCP server has no stdout".to_string())?;
+        let log = log_path(&config.name);
+        let tail: StderrTail = Arc::default();
+        if let Some(stderr) = child.stderr.take() {
+            tauri::async_runtime::spawn(log_stderr(stderr, log.clone(), tail.clone()));
+        }
+
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        tauri::async_runtime::spawn(read_responses(stdout, pending.clone()));
+        let connection = Connection {
+            stdin: tokio::sync::Mutex::new(stdin),
+            _child: child,
+            pending,
+            next_id: AtomicU64::new(1),
+        };
+
+        match Self::handshake(&connection, &config.name).await {
+            Ok(tools) => Ok(Self {
+                config,
+                tools,
+                connection,
+            }),
+            Err(error) => {
+                let tail = tail.lock().map(|tail| tail.join("\n")).unwrap_or_default();
+                if tail.is_empty() {
+                    Err(format!("{} (log: {})", error, log.display()))
+                } else {
+                    Err(format!("{}\nServer stderr:\n{}\n(log: {})", error, tail, log.display()))
+                }
+            }
+        }
+    }
+
+    async fn handshake(connection: &Connection, server: &str) -> Result<Vec<McpTool>, String> {
+        let handshake_ms = HANDSHAKE_TIMEOUT_SECS * 1000;
+        connection
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "kimi-gui", "version": env!("CARGO_PKG_VERSION") },
+                }),
+                handshake_ms,
+            )
+            .await?;
+        connection.notify("notifications/initialized").await?;
+
+        let listed = connection
+            .request("tools/list", serde_json::json!({}), handshake_ms)
+            .await?;
+        Ok(listed
+            .get("tools")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|tool| {
+                Some(McpTool {
+                    server: server.to_string(),
+                    name: tool.get("name")?.as_str()?.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| serde_json::json!({ "type": "object", "properties": {} })),
+                })
+            })
+            .collect())
+    }
+
+    async fn call_tool(&self, name: &str, args: &serde_json::Value, timeout_ms: u64) -> ToolOutput {
+        let result = match self
+            .connection
+            .request(
+                "tools/call",
+                serde_json::json!({ "name": name, "arguments": args }),
+                timeout_ms,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => {
+                return ToolOutput {
+                    ok: false,
+                    summary: format!("MCP tool {} failed: {}", name, err),
+                    output: String::new(),
+                }
+            }
+        };
+
+        let text = result
+            .get("content")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .map(|item| match item.get("type").and_then(|v| v.as_str()) {
+                Some("text") => item.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                Some(kind) => format!("[{} content]", kind),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (output, truncated) = tools::truncate_output(&text);
+        let is_error = result.get("isError").and_then(|v| v.as_bool()).unwrap_or(false);
+        let summary = if is_error {
+            format!("MCP tool {} reported an error.", name)
+        } else {
+            format!("MCP tool {} completed.", name)
+        };
+        ToolOutput {
+            ok: !is_error,
+            summary: tools::append_truncation(summary, truncated),
+            output,
+        }
+    }
+}
+
+/// MCP servers of the running app, keyed by server name.
+#[derive(Default)]
+pub struct McpRegistry {
+    servers: Mutex<HashMap<String, Arc<McpServer>>>,
+    /// Servers that failed to start or parse, with the config that failed; they
+    /// are not retried until their config changes
+    failed: Mutex<HashMap<String, (Option<ServerConfig>, String)>>,
+    /// Servers stopped from settings; chats don't start them again
+    stopped: Mutex<HashSet<String>>,
+    /// Config files and their modification times as of the last
+    /// `ensure_started`; while they are unchanged it has nothing to do
+    checked: Mutex<Option<Vec<(PathBuf, Option<SystemTime>)>>>,
+    /// One lock per server, held while it starts
+    starting: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+fn parse_servers(data: &serde_json::Value) -> Vec<Result<ServerConfig, (String, String)>> {
+    let Some(servers) = data.get("mcpServers").and_then(|v| v.as_object()) else {
+        return Vec::new();
+    };
+    servers
+        .iter()
+        .filter(|(_, entry)| !entry.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false))
+        .map(|(name, entry)| {
+            let Some(command) = entry.get("command").and_then(|v| v.as_str()) else {
+                let reason = if entry.get("url").is_some() {
+                    "Only stdio MCP servers are supported"
+                } else {
+                    "Missing command"
+                };
+                return Err((name.clone(), reason.to_string()));
+            };
+            let strings = |key: &str| -> Vec<String> {
+                entry
+                    .get(key)
+                    .and_then(|v| v.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            };
+            let env = entry
+                .get("env")
+                .and_then(|v| v.as_object())
+                .map(|map| {
+                    map.iter()
+                        .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let cwd = entry.get("cwd").and_then(|v| v.as_str()).map(str::to_string);
+            Ok(ServerConfig {
+                name: name.clone(),
+                command: command.to_string(),
+                args: strings("args"),
+                env,
+                cwd,
+            })
+        })
+        .collect()
+}
+
+/// The given mcp.json files, or the default one when none are set.
+fn config_paths(config_files: &[String]) -> Vec<PathBuf> {
+    if config_files.iter().all(|f| f.trim().is_empty()) {
+        vec![crate::default_mcp_path()]
+    } else {
+        config_files
+            .iter()
+            .filter(|f| !f.trim().is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+fn config_stamp(config_files: &[String]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    config_paths(config_files)
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Server configs from the given mcp.json files, or the default one when none
+/// are set. Later files override servers of the same name.
+pub fn load_server_configs(config_files: &[String]) -> Vec<Result<ServerConfig, (String, String)>> {
+    let files = config_paths(config_files);
+
+    let mut by_name: HashMap<String, Result<ServerConfig, (String, String)>> = HashMap::new();
+    let mut order = Vec::new();
+    for file in files {
+        let Ok(raw) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let data: serde_json::Value = match serde_json::from_str(&raw) {
+            Ok(data) => data,
+            Err(err) => {
+                let name = file.to_string_lossy().to_string();
+                order.push(name.clone());
+                by_name.insert(name.clone(), Err((name, format!("Invalid MCP JSON: {}", err))));
+                continue;
+            }
+        };
+        for entry in parse_servers(&data) {
+            let name = match &entry {
+                Ok(config) => config.name.clone(),
+                Err((name, _)) => name.clone(),
+            };
+            if !by_name.contains_key(&name) {
+                order.push(name.clone());
+            }
+            by_name.insert(name, entry);
+        }
+    }
+    order.into_iter().filter_map(|name| by_name.remove(&name)).collect()
+}
+
+/// MCP `tool_call_timeout_ms` from the config file.
+pub fn tool_call_timeout_ms(config_path: Option<&str>) -> u64 {
+    tools::load_config_value(config_path)
+        .ok()
+        .and_then(|config| config["mcp"]["client"]["tool_call_timeout_ms"].as_u64())
+        .unwrap_or(DEFAULT_TOOL_CALL_TIMEOUT_MS)
+}
+
+impl McpRegistry {
+    fn start_lock(&self, name: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.starting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Start configured servers that aren't running yet, restarting any whose
+    /// config changed. Returns `(server, error)` for servers that newly failed.
+    /// Does nothing while the config files are unchanged since the last call.
+    pub async fn ensure_started(&self, config_files: &[String]) -> Vec<(String, String)> {
+        let stamp = config_stamp(config_files);
+        if self
+            .checked
+            .lock()
+            .is_ok_and(|checked| checked.as_ref() == Some(&stamp))
+        {
+            return Vec::new();
+        }
+        let mut to_start = Vec::new();
+        let mut errors = Vec::new();
+        {
+            let servers = match self.servers.lock() {
+                Ok(servers) => servers,
+                Err(_) => return vec![("mcp".to_string(), "MCP registry poisoned".to_string())],
+            };
+            let mut failed = match self.failed.lock() {
+                Ok(failed) => failed,
+                Err(_) => return vec![("mcp".to_string(), "MCP registry poisoned".to_string())],
+            };
+            let stopped = self.stopped.lock().map(|stopped| stopped.clone()).unwrap_or_default();
+            for entry in load_server_configs(config_files) {
+                match entry {
+                    Ok(config) if stopped.contains(&config.name) => {}
+                    Ok(config) => {
+                        let running = servers.get(&config.name).is_some_and(|s| s.config == config);
+                        let known_bad = failed
+                            .get(&config.name)
+                            .is_some_and(|(c, _)| c.as_ref() == Some(&config));
+                        if !running && !known_bad {
+                            to_start.push(config);
+                        }
+                    }
+                    Err((name, error)) => {
+                        if failed.get(&name).is_none_or(|(_, e)| *e != error) {
+                            errors.push((name.clone(), error.clone()));
+                        }
+                        failed.insert(name, (None, error));
+                    }
+                }
+            }
+        }
+
+        let started =
+            futures::future::join_all(to_start.into_iter().map(|config| self.start_once(config))).await;
+        errors.extend(started.into_iter().flatten());
+        if let Ok(mut checked) = self.checked.lock() {
+            *checked = Some(stamp);
+        }
+        errors
+    }
+
+    /// Start `config` under its server's lock, unless a concurrent caller
+    /// started it, or saw it fail, while this one waited.
+    async fn start_once(&self, config: ServerConfig) -> Option<(String, String)> {
+        let lock = self.start_lock(&config.name);
+        let _guard = lock.lock().await;
+        let poisoned = || Some((config.name.clone(), "MCP registry poisoned".to_string()));
+        {
+            let (Ok(servers), Ok(failed)) = (self.servers.lock(), self.failed.lock()) else {
+                return poisoned();
+            };
+            let running = servers.get(&config.name).is_some_and(|s| s.config == config);
+            let known_bad = failed
+                .get(&config.name)
+                .is_some_and(|(c, _)| c.as_ref() == Some(&config));
+            if running || known_bad {
+                return None;
+            }
+        }
+
+        let result = McpServer::start(config.clone()).await;
+        let (Ok(mut servers), Ok(mut failed)) = (self.servers.lock(), self.failed.lock()) else {
+            return poisoned();
+        };
+        match result {
+            Ok(server) => {
+                failed.remove(&config.name);
+                servers.insert(config.name.clone(), Arc::new(server));
+                None
+            }
+            Err(error) => {
+                servers.remove(&config.name);
+                failed.insert(config.name.clone(), (Some(config.clone()), error.clone()));
+                Some((config.name, error))
+            }
+        }
+    }
+
+    fn servers(&self) -> Vec<Arc<McpServer>> {
+        let mut servers: Vec<Arc<McpServer>> = self
+            .servers
+            .lock()
+            .map(|servers| servers.values().cloned().collect())
+            .unwrap_or_default();
+        servers.sort_by(|a, b| a.config.name.cmp(&b.config.name));
+        servers
+    }
+
+    /// Every tool of the running servers. Tools that clash with a built-in or
+    /// an earlier server's tool are left out.
+    pub fn tools(&self) -> Vec<McpTool> {
+        let mut seen: Vec<String> = tools::builtin_tool_names();
+        let mut found = Vec::new();
+        for server in self.servers() {
+            for tool in &server.tools {
+                if seen.contains(&tool.name) {
+                    continue;
+                }
+                seen.push(tool.name.clone());
+                found.push(tool.clone());
+            }
+        }
+        found
+    }
+
+    /// Function definitions for every MCP tool in `tools`.
+    pub fn tool_definitions(&self) -> Vec<serde_json::Value> {
+        self.tools()
+            .into_iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.input_schema,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Server providing `tool`, resolved the same way as `tool_definitions`.
+    pub fn server_for_tool(&self, tool: &str) -> Option<Arc<McpServer>> {
+        if tools::builtin_tool_names().iter().any(|name| name == tool) {
+            return None;
+        }
+        self.servers()
+            .into_iter()
+            .find(|server| server.tools.iter().any(|t| t.name == tool))
+    }
+
+    pub async fn call_tool(
+        &self,
+        tool: &str,
+        args: &serde_json::Value,
+        timeout_ms: u64,
+    ) -> Option<ToolOutput> {
+        let server = self.server_for_tool(tool)?;
+        Some(server.call_tool(tool, args, timeout_ms).await)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct ServerStatus {
+    pub name: String,
+    /// "running" | "stopped" | "failed"
+    pub status: String,
+    pub command: Option<String>,
+    pub tools: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ConnectionTest {
+    pub name: String,
+    pub ok: bool,
+    /// Resolved path of the server binary, when found
+    pub binary: Option<String>,
+    pub handshake: bool,
+    pub tools: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Locate `command` the way spawning it would: as a path, or on PATH.
+fn find_binary(command: &str, cwd: Option<&str>) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        let path = match cwd {
+            Some(cwd) if path.is_relative() => Path::new(cwd).join(path),
+            _ => path.to_path_buf(),
+        };
+        return path.is_file().then_some(path);
+    }
+    let extensions: &[&str] = if cfg!(windows) { &["", ".exe", ".cmd", ".bat"] } else { &[""] };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |ext| dir.join(format!("{}{}", command, ext))))
+        .find(|candidate| candidate.is_file())
+}
+
+fn server_config(name: &str, config_files: &[String]) -> Result<ServerConfig, String> {
+    load_server_configs(config_files)
+        .into_iter()
+        .find_map(|entry| match entry {
+            Ok(config) if config.name == name => Some(Ok(config)),
+            Err((server, error)) if server == name => Some(Err(error)),
+            _ => None,
+        })
+        .unwrap_or_else(|| Err(format!("MCP server {} is not configured", name)))
+}
+
+impl McpRegistry {
+    /// Start (or restart) one server regardless of earlier failures or stops.
+    pub async fn start(&self, config: ServerConfig) -> Result<ServerStatus, String> {
+        let name = config.name.clone();
+        let lock = self.start_lock(&name);
+        let _guard = lock.lock().await;
+        self.stop(&name);
+        if let Ok(mut stopped) = self.stopped.lock() {
+            stopped.remove(&name);
+        }
+        match McpServer::start(config.clone()).await {
+            Ok(server) => {
+                let status = ServerStatus {
+                    name: name.clone(),
+                    status: "running".to_string(),
+                    command: Some(config.command),
+                    tools: server.tools.iter().map(|t| t.name.clone()).collect(),
+                    error: None,
+                };
+                if let (Ok(mut servers), Ok(mut failed)) = (self.servers.lock(), self.failed.lock()) {
+                    failed.remove(&name);
+                    servers.insert(name, Arc::new(server));
+                }
+                Ok(status)
+            }
+            Err(error) => {
+                if let Ok(mut failed) = self.failed.lock() {
+                    failed.insert(name, (Some(config), error.clone()));
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Stop a server; it stays stopped until started again. Calls in flight
+    /// finish before the process is killed.
+    pub fn stop(&self, name: &str) -> bool {
+        if let Ok(mut stopped) = self.stopped.lock() {
+            stopped.insert(name.to_string());
+        }
+        self.servers
+            .lock()
+            .map(|mut servers| servers.remove(name).is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn status(&self, config_files: &[String]) -> Vec<ServerStatus> {
+        let servers = self.servers.lock().map(|s| s.clone()).unwrap_or_default();
+        let failed = self.failed.lock().map(|f| f.clone()).unwrap_or_default();
+        load_server_configs(config_files)
+            .into_iter()
+            .map(|entry| {
+                let (name, command, parse_error) = match entry {
+                    Ok(config) => (config.name, Some(config.command), None),
+                    Err((name, error)) => (name, None, Some(error)),
+                };
+                let running = servers.get(&name);
+                let error = parse_error.or_else(|| {
+                    running
+                        .is_none()
+                        .then(|| failed.get(&name).map(|(_, error)| error.clone()))
+                        .flatten()
+                });
+                ServerStatus {
+                    status: if running.is_some() {
+                        "running"
+                    } else if error.is_some() {
+                        "failed"
+                    } else {
+                        "stopped"
+                    }
+                    .to_string(),
+                    tools: running
+                        .map(|server| server.tools.iter().map(|t| t.name.clone()).collect())
+                        .unwrap_or_default(),
+                    name,
+                    command,
+                    error,
+                }
+            })
+            .collect()
+    }
+}
+
+fn emit_status(window: &tauri::Window, status: &ServerStatus) {
+    let _ = window.emit("mcp://status", status.clone());
+}
+
+fn failed_status(name: &str, error: String) -> ServerStatus {
+    ServerStatus {
+        name: name.to_string(),
+        status: "failed".to_string(),
+        command: None,
+        tools: Vec::new(),
+        error: Some(error),
+    }
+}
+
+#[tauri::command]
+pub fn mcp_server_status(
+    state: tauri::State<'_, AppState>,
+    config_files: Option<Vec<String>>,
+) -> Vec<ServerStatus> {
+    state.mcp.status(&config_files.unwrap_or_default())
+}
+
+/// Start a configured server, restarting it if it is already running.
+#[tauri::command]
+pub async fn mcp_server_start(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    name: String,
+    config_files: Option<Vec<String>>,
+) -> Result<ServerStatus, String> {
+    let config = server_config(&name, &config_files.unwrap_or_default())?;
+    match state.mcp.start(config).await {
+        Ok(status) => {
+            emit_status(&window, &status);
+            Ok(status)
+        }
+        Err(error) => {
+            emit_status(&window, &failed_status(&name, error.clone()));
+            Err(error)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn mcp_server_stop(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> bool {
+    let was_running = state.mcp.stop(&name);
+    emit_status(
+        &window,
+        &ServerStatus {
+            name,
+            status: "stopped".to_string(),
+            command: None,
+            tools: Vec::new(),
+            error: None,
+        },
+    );
+    was_running
+}
+
+/// Check a server entry without registering it: the binary resolves, the
+/// handshake succeeds and tools are listed. Pass `entry` to test unsaved edits.
+#[tauri::command]
+pub async fn mcp_server_test_connection(
+    name: String,
+    entry: Option<serde_json::Value>,
+    config_files: Option<Vec<String>>,
+) -> Result<ConnectionTest, String> {
+    let config = match entry {
+        Some(entry) => parse_servers(&serde_json::json!({ "mcpServers": { name.clone(): entry } }))
+            .pop()
+            .unwrap_or_else(|| Err((name.clone(), "Missing command".to_string())))
+            .map_err(|(_, error)| error)?,
+        None => server_config(&name, &config_files.unwrap_or_default())?,
+    };
+
+    let mut test = ConnectionTest {
+        name,
+        ok: false,
+        binary: None,
+        handshake: false,
+        tools: Vec::new(),
+        error: None,
+    };
+    match find_binary(&config.command, config.cwd.as_deref()) {
+        Some(path) => test.binary = Some(path.to_string_lossy().to_string()),
+        None => {
+            test.error = Some(format!("{} was not found on PATH", config.command));
+            return Ok(test);
+        }
+    }
+    // The test server is dropped, and killed, when this returns
+    match McpServer::start(config).await {
+        Ok(server) => {
+            test.handshake = true;
+            test.ok = true;
+            test.tools = server.tools.iter().map(|t| t.name.clone()).collect();
+        }
+        Err(error) => test.error = Some(error),
+    }
+    Ok(test)
+}
@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::export;
+use crate::AppState;
+
+/// Streaming text rewrites the mirror at most this often
+const CHUNK_WRITE_INTERVAL: Duration = Duration::from_millis(500);
+/// How often the HTML mirror reloads itself in the viewer's browser
+const HTML_REFRESH_SECS: u32 = 3;
+
+struct Mirror {
+    path: PathBuf,
+    /// "markdown" | "html"
+    format: String,
+    title: String,
+    work_dir: String,
+    messages: Vec<crate::Message>,
+    /// Reply text of the step that is still streaming
+    live: String,
+    last_write: Option<Instant>,
+    started_at: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MirrorInfo {
+    pub session_id: String,
+    pub path: String,
+    pub format: String,
+    pub started_at: i64,
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Mirror {
+    fn render(&self) -> String {
+        let mut markdown = export::render_markdown(&self.title, &self.work_dir, &self.messages);
+        if !self.live.is_empty() {
+            markdown.push_str(&format!("## Assistant · streaming…\n\n{}\n", self.live));
+        }
+        match self.format.as_str() {
+            "html" => format!(
+                "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <meta http-equiv=\"refresh\" content=\"{}\">\n<title>{}</title>\n\
+                 <style>body{{font:14px/1.5 -apple-system,system-ui,sans-serif;max-width:900px;margin:24px auto;padding:0 16px}}\
+                 pre{{white-space:pre-wrap;word-break:break-word}}</style>\n</head>\n<body>\n<pre>{}</pre>\n\
+                 <script>window.scrollTo(0, document.body.scrollHeight);</script>\n</body>\n</html>\n",
+                HTML_REFRESH_SECS,
+                escape_html(&self.title),
+                escape_html(&markdown)
+            ),
+            _ => markdown,
+        }
+    }
+
+    /// Replace the file in one step so a viewer never reads half a transcript.
+    fn write(&mut self) {
+        let tmp = self.path.with_extension("tmp");
+        if fs::write(&tmp, self.render()).is_ok() {
+            let _ = fs::rename(&tmp, &self.path);
+        }
+        self.last_write = Some(Instant::now());
+    }
+
+    fn info(&self, session_id: &str) -> MirrorInfo {
+        MirrorInfo {
+            session_id: session_id.to_string(),
+            path: self.path.to_string_lossy().to_string(),
+            format: self.format.clone(),
+            started_at: self.started_at,
+        }
+    }
+}
+
+/// Sessions whose transcript is mirrored to a file as the turn streams, so
+/// someone else can follow a long run by opening it.
+#[derive(Default)]
+pub struct MirrorRegistry {
+    mirrors: Mutex<HashMap<String, Mirror>>,
+}
+
+impl MirrorRegistry {
+    /// A message was added to the session transcript.
+    pub fn record(&self, session_id: &str, message: &crate::Message) {
+        let mut mirrors = self.mirrors.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mirror) = mirrors.get_mut(session_id) {
+            mirror.messages.push(message.clone());
+            mirror.live.clear();
+            mirror.write();
+        }
+    }
+
+    /// Reply text streamed in for the current step.
+    pub fn chunk(&self, session_id: &str, text: &str) {
+        let mut mirrors = self.mirrors.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mirror) = mirrors.get_mut(session_id) {
+            mirror.live.push_str(text);
+            if mirror
+                .last_write
+                .is_none_or(|at| at.elapsed() >= CHUNK_WRITE_INTERVAL)
+            {
+                mirror.write();
+            }
+        }
+    }
+
+    /// The turn ended without saving the streamed text (cancel or error).
+    pub fn turn_ended(&self, session_id: &str) {
+        let mut mirrors = self.mirrors.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mirror) = mirrors.get_mut(session_id).filter(|m| !m.live.is_empty()) {
+            mirror.live.clear();
+            mirror.write();
+        }
+    }
+
+    fn list(&self) -> Vec<MirrorInfo> {
+        let mirrors = self.mirrors.lock().unwrap_or_else(|e| e.into_inner());
+        mirrors.iter().map(|(id, mirror)| mirror.info(id)).collect()
+    }
+}
+
+/// Start mirroring a session's transcript to `path` (picked in a save dialog
+/// when omitted) as Markdown or as a self-refreshing HTML page. Returns None if
+/// the dialog was cancelled.
+#[tauri::command]
+pub async fn session_mirror_start(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    format: String,
+    path: Option<String>,
+    work_dir: Option<String>,
+) -> Result<Option<MirrorInfo>, String> {
+    let (format, extension) = match format.as_str() {
+        "markdown" | "md" => ("markdown", "md"),
+        "html" => ("html", "html"),
+        other => return Err(format!("Unknown mirror format: {}", other)),
+    };
+    let (title, work_dir, messages) = export::load_transcript(&state, &session_id, work_dir)?;
+
+    let path = match path.filter(|p| !p.trim().is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let Some(picked) = app
+                .dialog()
+                .file()
+                .set_file_name(format!("{}-live.{}", export::file_name(&title), extension))
+                .add_filter(extension, &[extension])
+                .blocking_save_file()
+            else {
+                return Ok(None);
+            };
+            picked.into_path().map_err(|e| e.to_string())?
+        }
+    };
+    crate::ensure_parent(&path)?;
+
+    let mut mirror = Mirror {
+        path,
+        format: format.to_string(),
+        title,
+        work_dir,
+        messages,
+        live: String::new(),
+        last_write: None,
+        started_at: chrono::Utc::now().timestamp(),
+    };
+    mirror.write();
+    if !mirror.path.exists() {
+        return Err(format!("Failed to write {}", mirror.path.display()));
+    }
+    let info = mirror.info(&session_id);
+    state
+        .mirrors
+        .mirrors
+        .lock()
+        .map_err(|_| "Mirror registry poisoned".to_string())?
+        .insert(session_id, mirror);
+    Ok(Some(info))
+}
+
+/// Stop mirroring. The file keeps the transcript as of the last event.
+#[tauri::command]
+pub fn session_mirror_stop(state: tauri::State<'_, AppState>, session_id: String) -> Result<(), String> {
+    state
+        .mirrors
+        .mirrors
+        .lock()
+        .map_err(|_| "Mirror registry poisoned".to_string())?
+        .remove(&session_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn session_mirror_list(state: tauri::State<'_, AppState>) -> Result<Vec<MirrorInfo>, String> {
+    Ok(state.mirrors.list())
+}
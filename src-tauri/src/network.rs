@@ -0,0 +1,222 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+use crate::AppState;
+
+pub const OFFLINE_ERROR: &str = "Offline mode is enabled; network access is blocked.";
+const DEFAULT_CHECK_INTERVAL_SECS: u64 = 30;
+const DEFAULT_DEGRADED_MS: u64 = 1500;
+const PING_TIMEOUT_SECS: u64 = 5;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static LOW_BANDWIDTH: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Serialize)]
+pub struct QueuedPrompt {
+    pub session_id: String,
+    pub message: String,
+    pub queued_at: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct NetworkStatus {
+    pub offline: bool,
+    pub queued: Vec<QueuedPrompt>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ConnectivityStatus {
+    /// "online" | "degraded" | "offline"
+    pub status: String,
+    pub latency_ms: Option<u64>,
+    pub base_url: String,
+    pub reason: Option<String>,
+    pub checked_at: i64,
+}
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Low-bandwidth mode trims the system prompt, caps tool output, and asks for
+/// compressed responses, for metered or remote connections.
+pub fn set_low_bandwidth(enabled: bool) {
+    LOW_BANDWIDTH.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_low_bandwidth() -> bool {
+    LOW_BANDWIDTH.load(Ordering::Relaxed)
+}
+
+/// Loopback endpoints (Ollama, LM Studio, ...) stay reachable in offline mode.
+pub fn is_local_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .map(|host| {
+            matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]" | "0.0.0.0")
+                || host.ends_with(".localhost")
+        })
+        .unwrap_or(false)
+}
+
+/// Fail with `OFFLINE_ERROR` when offline mode blocks requests to `url`.
+pub fn ensure_online(url: &str) -> Result<(), String> {
+    if is_offline() && !is_local_url(url) {
+        return Err(OFFLINE_ERROR.to_string());
+    }
+    Ok(())
+}
+
+pub fn queue_prompt(state: &AppState, session_id: &str, message: &str) -> Result<(), String> {
+    let mut queue = state
+        .offline_queue
+        .lock()
+        .map_err(|_| "Offline queue poisoned".to_string())?;
+    queue.push(QueuedPrompt {
+        session_id: session_id.to_string(),
+        message: message.to_string(),
+        queued_at: chrono::Utc::now().timestamp(),
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn network_status(state: tauri::State<'_, AppState>) -> Result<NetworkStatus, String> {
+    let queue = state
+        .offline_queue
+        .lock()
+        .map_err(|_| "Offline queue poisoned".to_string())?;
+    Ok(NetworkStatus {
+        offline: is_offline(),
+        queued: queue.clone(),
+    })
+}
+
+/// Toggle offline mode. Going back online hands the queued prompts to the
+/// frontend so it can resend them with its current settings.
+#[tauri::command]
+pub fn network_set_offline(
+    state: tauri::State<'_, AppState>,
+    offline: bool,
+) -> Result<Vec<QueuedPrompt>, String> {
+    set_offline(offline);
+    if offline {
+        return Ok(Vec::new());
+    }
+    let mut queue = state
+        .offline_queue
+        .lock()
+        .map_err(|_| "Offline queue poisoned".to_string())?;
+    Ok(queue.drain(..).collect())
+}
+
+/// Ping the active provider's base URL. Any HTTP response counts as reachable;
+/// slow responses and 5xx errors are reported as degraded.
+pub async fn check_connectivity(degraded_ms: u64) -> ConnectivityStatus {
+    let base_url = crate::llm::provider_base(&crate::load_auth_config());
+    let checked_at = chrono::Utc::now().timestamp();
+
+    if let Err(err) = ensure_online(&base_url) {
+        return ConnectivityStatus {
+            status: "offline".to_string(),
+            latency_ms: None,
+            base_url,
+            reason: Some(err),
+            checked_at,
+        };
+    }
+
+    let client = match crate::http::plain_client() {
+        Ok(client) => client,
+        Err(err) => {
+            return ConnectivityStatus {
+                status: "offline".to_string(),
+                latency_ms: None,
+                base_url,
+                reason: Some(err),
+                checked_at,
+            }
+        }
+    };
+    let started = Instant::now();
+    let result = client
+        .get(&base_url)
+        .timeout(Duration::from_secs(PING_TIMEOUT_SECS))
+        .send()
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (status, reason) = match result {
+        Ok(response) if response.status().is_server_error() => (
+            "degraded",
+            Some(format!("Provider returned {}", response.status())),
+        ),
+        Ok(_) if latency_ms > degraded_ms => (
+            "degraded",
+            Some(format!("Latency above {degraded_ms}ms")),
+        ),
+        Ok(_) => ("online", None),
+        Err(err) => ("offline", Some(format!("Provider unreachable: {err}"))),
+    };
+
+    ConnectivityStatus {
+        status: status.to_string(),
+        latency_ms: (status != "offline").then_some(latency_ms),
+        base_url,
+        reason,
+        checked_at,
+    }
+}
+
+#[tauri::command]
+pub async fn network_check(degraded_ms: Option<u64>) -> Result<ConnectivityStatus, String> {
+    Ok(check_connectivity(degraded_ms.unwrap_or(DEFAULT_DEGRADED_MS)).await)
+}
+
+/// Start (or restart) the background monitor that emits `network://status` events.
+#[tauri::command]
+pub fn network_monitor_start(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    interval_secs: Option<u64>,
+    degraded_ms: Option<u64>,
+) -> Result<(), String> {
+    let interval = Duration::from_secs(interval_secs.unwrap_or(DEFAULT_CHECK_INTERVAL_SECS).max(5));
+    let degraded_ms = degraded_ms.unwrap_or(DEFAULT_DEGRADED_MS);
+
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            let status = check_connectivity(degraded_ms).await;
+            let _ = window.emit("network://status", status);
+            tokio::time::sleep(interval).await;
+        }
+    });
+
+    let mut monitor = state
+        .network_monitor
+        .lock()
+        .map_err(|_| "Network monitor poisoned".to_string())?;
+    if let Some(previous) = monitor.replace(handle) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn network_monitor_stop(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let mut monitor = state
+        .network_monitor
+        .lock()
+        .map_err(|_| "Network monitor poisoned".to_string())?;
+    if let Some(handle) = monitor.take() {
+        handle.abort();
+    }
+    Ok(())
+}
@@ -3,13 +3,17 @@ use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use chrono::{DateTime, Utc};
 
+use crate::AppState;
+
 // OAuth Constants
 const KIMI_CODE_CLIENT_ID: &str = "17e5f671-d194-4dfb-9706-5516cb48c098";
 const DEFAULT_OAUTH_HOST: &str = "https://auth.kimi.com";
 const CREDENTIALS_FILE: &str = "kimi-code.json";
+/// Used when the device authorization response has no `expires_in`
+const DEFAULT_DEVICE_CODE_TTL_SECS: i32 = 900;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OAuthToken {
@@ -33,21 +37,23 @@ pub struct DeviceAuthorization {
 #[derive(Clone, Debug, Serialize)]
 pub struct OAuthEvent {
     pub event: String,
+    /// Login the event belongs to, so a replaced login's events can be ignored
+    pub login_id: String,
     pub data: serde_json::Value,
 }
 
+/// The device-code login in progress.
+pub struct LoginTask {
+    id: String,
+    cancel_tx: tokio::sync::oneshot::Sender<()>,
+}
+
 fn oauth_host() -> String {
     std::env::var("KIMI_CODE_OAUTH_HOST")
         .or_else(|_| std::env::var("KIMI_OAUTH_HOST"))
         .unwrap_or_else(|_| DEFAULT_OAUTH_HOST.to_string())
 }
 
-fn api_base_url() -> String {
-    std::env::var("KIMI_CODE_BASE_URL")
-        .or_else(|_| std::env::var("KIMI_BASE_URL"))
-        .unwrap_or_else(|_| "https://api.kimi.com/coding/v1".to_string())
-}
-
 fn credentials_dir() -> PathBuf {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
     let dir = home.join(".kimi").join("credentials");
@@ -164,8 +170,9 @@ pub fn is_logged_in() -> bool {
 }
 
 pub async fn request_device_authorization() -> Result<DeviceAuthorization, String> {
-    let client = reqwest::Client::new();
+    let client = crate::http::plain_client()?;
     let url = format!("{}/api/oauth/device_authorization", oauth_host());
+    crate::network::ensure_online(&url)?;
     
     let form = [("client_id", KIMI_CODE_CLIENT_ID)];
     
@@ -209,16 +216,52 @@ pub async fn request_device_authorization() -> Result<DeviceAuthorization, Strin
     })
 }
 
+/// How a device-code login ended without a token.
+pub enum PollError {
+    /// The code's lifetime ran out before the user authorized it
+    Expired,
+    Cancelled,
+    Failed(String),
+}
+
+fn emit_login_event(window: &tauri::Window, login_id: &str, event: &str, data: serde_json::Value) {
+    let _ = window.emit(
+        "oauth://event",
+        OAuthEvent {
+            event: event.to_string(),
+            login_id: login_id.to_string(),
+            data,
+        },
+    );
+}
+
+/// Poll the token endpoint until the user authorizes the device, the code
+/// expires or `cancel_rx` fires. Emits `waiting` and `slow_down` as it goes.
 pub async fn poll_for_token(
     auth: &DeviceAuthorization,
-    window: tauri::Window,
-) -> Result<OAuthToken, String> {
-    let client = reqwest::Client::new();
+    window: &tauri::Window,
+    login_id: &str,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Result<OAuthToken, PollError> {
+    let client = crate::http::plain_client().map_err(PollError::Failed)?;
     let url = format!("{}/api/oauth/token", oauth_host());
-    let interval = std::time::Duration::from_secs(auth.interval.max(1) as u64);
-    
+    crate::network::ensure_online(&url).map_err(PollError::Failed)?;
+    let mut interval = std::time::Duration::from_secs(auth.interval.max(1) as u64);
+    let lifetime = auth
+        .expires_in
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_DEVICE_CODE_TTL_SECS);
+    let deadline =
+        tokio::time::Instant::now() + std::time::Duration::from_secs(lifetime as u64);
+
     loop {
-        tokio::time::sleep(interval).await;
+        tokio::select! {
+            _ = &mut *cancel_rx => return Err(PollError::Cancelled),
+            _ = tokio::time::sleep(interval) => {}
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(PollError::Expired);
+        }
         
         let form = [
             ("client_id", KIMI_CODE_CLIENT_ID),
@@ -241,13 +284,13 @@ pub async fn poll_for_token(
             ))
             .send()
             .await
-            .map_err(|e| format!("Token request failed: {}", e))?;
+            .map_err(|e| PollError::Failed(format!("Token request failed: {}", e)))?;
         
         let status = response.status();
         let data: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse token response: {}", e))?;
+            .map_err(|e| PollError::Failed(format!("Failed to parse token response: {}", e)))?;
         
         if status.is_success() && data.get("access_token").is_some() {
             let expires_in = data["expires_in"].as_f64().unwrap_or(3600.0);
@@ -267,38 +310,54 @@ pub async fn poll_for_token(
         }
         
         let error = data["error"].as_str().unwrap_or("unknown_error");
+        let seconds_left = deadline
+            .saturating_duration_since(tokio::time::Instant::now())
+            .as_secs();
         
         match error {
             "authorization_pending" => {
-                // Still waiting, emit waiting event
-                let _ = window.emit(
-                    "oauth://event",
-                    OAuthEvent {
-                        event: "waiting".to_string(),
-                        data: serde_json::json!({
-                            "message": "Waiting for authorization...",
-                        }),
-                    },
+                emit_login_event(
+                    window,
+                    login_id,
+                    "waiting",
+                    serde_json::json!({
+                        "message": "Waiting for authorization...",
+                        "seconds_left": seconds_left,
+                    }),
                 );
-                continue;
             }
-            "expired_token" => {
-                return Err("Device code expired. Please try again.".to_string());
+            "slow_down" => {
+                // RFC 8628: back off by 5 seconds for this and later requests
+                interval += std::time::Duration::from_secs(5);
+                emit_login_event(
+                    window,
+                    login_id,
+                    "slow_down",
+                    serde_json::json!({
+                        "interval": interval.as_secs(),
+                        "seconds_left": seconds_left,
+                    }),
+                );
             }
+            "expired_token" => return Err(PollError::Expired),
             "access_denied" => {
-                return Err("Authorization was denied.".to_string());
+                return Err(PollError::Failed("Authorization was denied.".to_string()));
             }
             _ => {
                 let error_desc = data["error_description"].as_str().unwrap_or(error);
-                return Err(format!("Authorization failed: {}", error_desc));
+                return Err(PollError::Failed(format!(
+                    "Authorization failed: {}",
+                    error_desc
+                )));
             }
         }
     }
 }
 
 pub async fn refresh_token(refresh_token: &str) -> Result<OAuthToken, String> {
-    let client = reqwest::Client::new();
+    let client = crate::http::plain_client()?;
     let url = format!("{}/api/oauth/token", oauth_host());
+    crate::network::ensure_online(&url)?;
     
     let form = [
         ("client_id", KIMI_CODE_CLIENT_ID),
@@ -378,6 +437,47 @@ pub async fn ensure_fresh_token() -> Option<String> {
     }
 }
 
+/// Whether a running turn can keep using the OAuth login.
+pub enum LoginState {
+    Fresh(String),
+    /// A refresh failed but the token still works until `expires_at`
+    Expiring {
+        access_token: String,
+        expires_at: f64,
+        error: String,
+    },
+    /// Nothing usable is left; the user has to log in again. Holds the reason
+    /// and the last token, if any
+    Expired(String, Option<String>),
+}
+
+/// Like `ensure_fresh_token`, but tells a lapsing login apart from a dead one
+/// instead of handing back a token that will be rejected.
+pub async fn login_state() -> LoginState {
+    let Some(token) = load_token() else {
+        return LoginState::Expired("Not logged in.".to_string(), None);
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    if token.expires_at >= now + 300.0 {
+        return LoginState::Fresh(token.access_token);
+    }
+    match refresh_token(&token.refresh_token).await {
+        Ok(new_token) => match save_token(&new_token) {
+            Ok(()) => LoginState::Fresh(new_token.access_token),
+            Err(error) => LoginState::Expired(error, Some(token.access_token)),
+        },
+        Err(error) if token.expires_at > now => LoginState::Expiring {
+            access_token: token.access_token,
+            expires_at: token.expires_at,
+            error,
+        },
+        Err(error) => LoginState::Expired(error, Some(token.access_token)),
+    }
+}
+
 #[tauri::command]
 pub fn oauth_check_status() -> Result<serde_json::Value, String> {
     let is_logged_in = is_logged_in();
@@ -395,58 +495,78 @@ pub fn oauth_logout() -> Result<(), String> {
     Ok(())
 }
 
+/// Start a device-code login, cancelling any login still in progress. The
+/// code is returned with the login's id and also sent as a `code` event; the
+/// polling task then reports `waiting`, `slow_down`, `expired`, `cancelled`,
+/// `success` or `error`. Every event carries the `login_id` it belongs to.
 #[tauri::command]
-pub async fn oauth_start_login(window: tauri::Window) -> Result<serde_json::Value, String> {
+pub async fn oauth_start_login(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
     let auth = request_device_authorization().await?;
-    
-    // Clone values for the response
-    let user_code = auth.user_code.clone();
-    let verification_uri = auth.verification_uri.clone();
-    let verification_uri_complete = auth.verification_uri_complete.clone();
-    
-    // Spawn polling task - clone auth for the async task
-    let window_clone = window.clone();
-    let auth_for_poll = auth.clone();
-    
+    let login_id = uuid::Uuid::new_v4().to_string();
+    let code = serde_json::json!({
+        "login_id": login_id,
+        "user_code": auth.user_code,
+        "verification_uri": auth.verification_uri,
+        "verification_uri_complete": auth.verification_uri_complete,
+        "expires_in": auth.expires_in,
+        "interval": auth.interval,
+    });
+
+    let (cancel_tx, mut cancel_rx) = tokio::sync::oneshot::channel();
+    let previous = state
+        .oauth_login
+        .lock()
+        .map_err(|_| "Login state poisoned".to_string())?
+        .replace(LoginTask {
+            id: login_id.clone(),
+            cancel_tx,
+        });
+    if let Some(previous) = previous {
+        let _ = previous.cancel_tx.send(());
+    }
+    emit_login_event(&window, &login_id, "code", code.clone());
+
     tokio::spawn(async move {
-        match poll_for_token(&auth_for_poll, window_clone.clone()).await {
-            Ok(token) => {
-                if let Err(e) = save_token(&token) {
-                    let _ = window_clone.emit(
-                        "oauth://event",
-                        OAuthEvent {
-                            event: "error".to_string(),
-                            data: serde_json::json!({ "message": e }),
-                        },
-                    );
-                    return;
-                }
-                
-                let _ = window_clone.emit(
-                    "oauth://event",
-                    OAuthEvent {
-                        event: "success".to_string(),
-                        data: serde_json::json!({}),
-                    },
-                );
-            }
-            Err(e) => {
-                let _ = window_clone.emit(
-                    "oauth://event",
-                    OAuthEvent {
-                        event: "error".to_string(),
-                        data: serde_json::json!({ "message": e }),
-                    },
-                );
+        let result = poll_for_token(&auth, &window, &login_id, &mut cancel_rx).await;
+        // Clear the slot unless a newer login already took it
+        if let Ok(mut slot) = window.state::<AppState>().oauth_login.lock() {
+            if slot.as_ref().is_some_and(|task| task.id == login_id) {
+                slot.take();
             }
         }
+        let (event, data) = match result {
+            Ok(token) => match save_token(&token) {
+                Ok(()) => ("success", serde_json::json!({})),
+                Err(e) => ("error", serde_json::json!({ "message": e })),
+            },
+            Err(PollError::Expired) => (
+                "expired",
+                serde_json::json!({ "message": "Device code expired. Please try again." }),
+            ),
+            Err(PollError::Cancelled) => ("cancelled", serde_json::json!({})),
+            Err(PollError::Failed(e)) => ("error", serde_json::json!({ "message": e })),
+        };
+        emit_login_event(&window, &login_id, event, data);
     });
-    
-    Ok(serde_json::json!({
-        "user_code": user_code,
-        "verification_uri": verification_uri,
-        "verification_uri_complete": verification_uri_complete,
-    }))
+
+    Ok(code)
+}
+
+/// Stop the login started by `oauth_start_login`, if any.
+#[tauri::command]
+pub fn oauth_cancel_login(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let task = state
+        .oauth_login
+        .lock()
+        .map_err(|_| "Login state poisoned".to_string())?
+        .take();
+    if let Some(task) = task {
+        let _ = task.cancel_tx.send(());
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -686,9 +806,13 @@ fn find_five_hour_limit(rows: &[UsageRow]) -> Option<UsageRow> {
     None
 }
 
+/// Usage of the OAuth account. Always asks the Kimi Code API: the token is
+/// only valid there, and a profile pinned to another endpoint mustn't see it.
 async fn fetch_usage_payload(access_token: &str) -> Result<serde_json::Value, String> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/usages", api_base_url().trim_end_matches('/'));
+    let client = crate::http::plain_client()?;
+    let base = crate::llm::api_base_url();
+    let url = format!("{}/usages", base.trim_end_matches('/'));
+    crate::network::ensure_online(&url)?;
 
     let mut req = client.get(&url);
     for (key, value) in common_headers().into_iter() {
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::llm;
+use crate::providers;
+use crate::{AppState, GuiSettings};
+
+const TURN_EXCERPT_CHARS: usize = 400;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OutlineItem {
+    /// Index of the turn's user message in the session transcript
+    pub message_index: usize,
+    pub title: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionOutline {
+    pub session_id: String,
+    /// Message count the outline was built from; a longer transcript invalidates it
+    pub message_count: usize,
+    /// Model and config file the outline was built with; a change invalidates it
+    #[serde(default)]
+    pub cache_key: String,
+    pub items: Vec<OutlineItem>,
+    pub generated_at: i64,
+}
+
+/// Index and excerpt of every user turn, with the assistant reply that followed it.
+fn turn_excerpts(messages: &[crate::Message]) -> Vec<(usize, String)> {
+    let mut turns: Vec<(usize, String)> = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        let excerpt = crate::truncate_with_ellipsis(message.content.trim(), TURN_EXCERPT_CHARS);
+        match message.role.as_str() {
+            "user" => turns.push((index, format!("User: {}", excerpt))),
+            "assistant" if !excerpt.is_empty() => {
+                if let Some((_, text)) = turns.last_mut() {
+                    text.push_str(&format!("\nAssistant: {}", excerpt));
+                }
+            }
+            _ => {}
+        }
+    }
+    turns
+}
+
+/// Accept a JSON array of strings, or fall back to one title per numbered line.
+pub fn parse_titles(content: &str) -> Vec<String> {
+    let trimmed = content
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    if let Ok(titles) = serde_json::from_str::<Vec<String>>(trimmed) {
+        return titles;
+    }
+    trimmed
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == ')' || c == '-')
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Per-turn outline of a session, generated by one short LLM call and cached
+/// next to the session until new messages arrive.
+#[tauri::command]
+pub async fn session_outline(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    work_dir: Option<String>,
+    model: Option<String>,
+    refresh: Option<bool>,
+    settings: Option<GuiSettings>,
+) -> Result<SessionOutline, String> {
+    let (messages, cache_path) = {
+        let mut manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        let messages = match manager.find_session(&session_id) {
+            Some(session) => session.messages,
+            None => {
+                let wd = work_dir.ok_or_else(|| "Session not found".to_string())?;
+                manager.load_messages(&wd, &session_id)?
+            }
+        };
+        (messages, manager.sidecar_path(&session_id, "outline.json"))
+    };
+
+    let settings = settings.unwrap_or_default();
+    let chat_model = settings.chat_model();
+    let config_path = settings
+        .config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::app_paths().config));
+    let model = model.or(settings.summarizer_model);
+    let cache_key = format!(
+        "{}\n{}",
+        model.as_deref().unwrap_or(&chat_model),
+        config_path.as_deref().unwrap_or("")
+    );
+
+    if !refresh.unwrap_or(false) {
+        if let Some(cached) = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<SessionOutline>(&raw).ok())
+        {
+            if cached.message_count == messages.len() && cached.cache_key == cache_key {
+                return Ok(cached);
+            }
+        }
+    }
+
+    let turns = turn_excerpts(&messages);
+    if turns.is_empty() {
+        return Err("Session has no turns to outline".to_string());
+    }
+
+    let transcript = turns
+        .iter()
+        .enumerate()
+        .map(|(i, (_, text))| format!("Turn {}:\n{}", i + 1, text))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let (credentials, api_model) = providers::resolve_summarizer(
+        config_path.as_deref(),
+        model.as_deref(),
+        &chat_model,
+    )
+    .await?;
+    let request = serde_json::json!({
+        "model": api_model,
+        "messages": [
+            {
+                "role": "system",
+                "content": format!(
+                    "Summarize each turn of the conversation as a short action phrase (at most 8 words), \
+                     e.g. \"Investigated bug\", \"Patched parser\". Reply with only a JSON array of exactly {} strings.",
+                    turns.len()
+                ),
+            },
+            { "role": "user", "content": transcript },
+        ],
+        "stream": false,
+        "temperature": 0,
+    });
+
+    let client = crate::http::client(&credentials)?;
+    let data = llm::post_chat_completion(&client, &credentials, &request, Some(&session_id)).await?;
+    let content = data
+        .get("choices")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.get("message"))
+        .and_then(|v| v.get("content"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    let mut titles = parse_titles(content).into_iter();
+    let items = turns
+        .iter()
+        .map(|(message_index, text)| OutlineItem {
+            message_index: *message_index,
+            // Missing titles fall back to the start of the user message
+            title: titles.next().unwrap_or_else(|| {
+                crate::truncate_with_ellipsis(text.trim_start_matches("User: ").lines().next().unwrap_or(""), 60)
+            }),
+        })
+        .collect();
+
+    let outline = SessionOutline {
+        session_id,
+        message_count: messages.len(),
+        cache_key,
+        items,
+        generated_at: chrono::Utc::now().timestamp(),
+    };
+    if let Ok(json) = serde_json::to_string_pretty(&outline) {
+        let _ = fs::write(&cache_path, json);
+    }
+    Ok(outline)
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+/// Typed segment of a message. `Message.content` keeps the flat markdown for
+/// display; parts are what exports, search, and re-rendering should read.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessagePart {
+    Text {
+        text: String,
+    },
+    Code {
+        language: Option<String>,
+        /// Target path when the fence is annotated, e.g. ```rust src/main.rs
+        file: Option<String>,
+        code: String,
+    },
+    Image {
+        src: String,
+        alt: Option<String>,
+    },
+    ToolRef {
+        id: String,
+        name: String,
+    },
+    Reasoning {
+        text: String,
+    },
+    /// A claim in the reply backed by a tool result from the same turn
+    Evidence {
+        tool_call_id: String,
+        tool: String,
+        /// Path, command, URL or query the tool was called with
+        target: String,
+        claim: String,
+    },
+}
+
+/// Split a fence info string like `rust src/main.rs`, `rust:src/main.rs`, or
+/// `python file=app.py` into language and file.
+fn parse_fence_info(info: &str) -> (Option<String>, Option<String>) {
+    let info = info.trim();
+    if info.is_empty() {
+        return (None, None);
+    }
+
+    let mut tokens = info.split_whitespace();
+    let first = tokens.next().unwrap_or("");
+    let (language, mut file) = match first.split_once(':') {
+        Some((lang, path)) if !path.is_empty() => (lang.to_string(), Some(path.to_string())),
+        _ => (first.to_string(), None),
+    };
+
+    for token in tokens {
+        if file.is_some() {
+            break;
+        }
+        let value = token
+            .strip_prefix("file=")
+            .or_else(|| token.strip_prefix("path="))
+            .or_else(|| token.strip_prefix("title="))
+            .unwrap_or(token)
+            .trim_matches(|c| c == '"' || c == '\'');
+        if value.contains('/') || value.contains('.') {
+            file = Some(value.to_string());
+        }
+    }
+
+    let language = (!language.is_empty()).then_some(language);
+    (language, file)
+}
+
+/// Push a text segment, splitting out markdown images `![alt](src)`.
+fn push_text(parts: &mut Vec<MessagePart>, text: &str) {
+    let mut rest = text;
+    while let Some(start) = rest.find("![") {
+        let after = &rest[start + 2..];
+        let parsed = after.find("](").and_then(|alt_end| {
+            let src_start = alt_end + 2;
+            after[src_start..]
+                .find(')')
+                .map(|src_len| (&after[..alt_end], &after[src_start..src_start + src_len]))
+        });
+        let (alt, src) = match parsed {
+            Some(parsed) => parsed,
+            None => break,
+        };
+
+        push_plain(parts, &rest[..start]);
+        parts.push(MessagePart::Image {
+            src: src.trim().to_string(),
+            alt: (!alt.is_empty()).then(|| alt.to_string()),
+        });
+        rest = &after[alt.len() + 2 + src.len() + 1..];
+    }
+    push_plain(parts, rest);
+}
+
+fn push_plain(parts: &mut Vec<MessagePart>, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    if let Some(MessagePart::Text { text: existing }) = parts.last_mut() {
+        existing.push_str(text);
+        return;
+    }
+    parts.push(MessagePart::Text {
+        text: text.to_string(),
+    });
+}
+
+/// Parse markdown content into text, code, and image parts.
+pub fn parse_parts(content: &str) -> Vec<MessagePart> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut fence: Option<(String, Option<String>, Option<String>, String)> = None;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        match fence.take() {
+            Some((marker, language, file, mut code)) => {
+                if trimmed.trim_end() == marker {
+                    parts.push(MessagePart::Code {
+                        language,
+                        file,
+                        code,
+                    });
+                } else {
+                    code.push_str(line);
+                    fence = Some((marker, language, file, code));
+                }
+            }
+            None => {
+                let marker_len = trimmed.chars().take_while(|c| *c == '`').count();
+                if marker_len >= 3 {
+                    push_text(&mut parts, &text);
+                    text.clear();
+                    let (language, file) = parse_fence_info(&trimmed[marker_len..]);
+                    fence = Some(("`".repeat(marker_len), language, file, String::new()));
+                } else {
+                    text.push_str(line);
+                }
+            }
+        }
+    }
+
+    // An unterminated fence is still code (e.g. a reply cut off mid-block)
+    if let Some((_, language, file, code)) = fence {
+        parts.push(MessagePart::Code {
+            language,
+            file,
+            code,
+        });
+    }
+    push_text(&mut parts, &text);
+    parts
+}
@@ -0,0 +1,459 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::encoding;
+use crate::file_writes;
+use crate::paths::{self, Access};
+use crate::tools::{self, ToolOutput};
+
+struct Hunk {
+    /// The `@@ ... @@` line, for the report
+    header: String,
+    /// 1-based line the hunk starts at in the old file
+    old_start: usize,
+    /// Context and removed lines
+    old: Vec<String>,
+    /// Context and added lines
+    new: Vec<String>,
+    /// `\ No newline at end of file` after a line of the new side
+    new_no_newline: bool,
+    /// The same marker after a removed line only
+    old_no_newline: bool,
+}
+
+struct FilePatch {
+    /// None for a file the patch creates
+    old_path: Option<String>,
+    /// None for a file the patch deletes
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// `a/src/main.rs\t2024-01-01 ...` -> `src/main.rs`; `/dev/null` -> None.
+fn header_path(rest: &str) -> Option<String> {
+    let path = rest.split('\t').next().unwrap_or(rest).trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = path.trim_matches('"');
+    Some(
+        path.strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path)
+            .to_string(),
+    )
+}
+
+/// `@@ -12,5 +12,6 @@` -> (12, 5, 6); a missing count means 1.
+fn hunk_range(header: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = header.strip_prefix("@@")?.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let part = part?.strip_prefix(sign)?;
+        match part.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(parts.next(), '-')?;
+    let (_, new_count) = range(parts.next(), '+')?;
+    Some((old_start, old_count, new_count))
+}
+
+fn parse(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = patch.lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = line.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix("--- ") {
+            let Some(next) = lines.peek().copied().filter(|next| next.starts_with("+++ ")) else {
+                continue;
+            };
+            let new_path = header_path(&next[4..]);
+            lines.next();
+            files.push(FilePatch {
+                old_path: header_path(rest),
+                new_path,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        if !line.starts_with("@@") {
+            // `diff --git`, `index ...` and commentary between files
+            continue;
+        }
+        let file = files
+            .last_mut()
+            .ok_or_else(|| "Hunk before any `---`/`+++` file header".to_string())?;
+        let (old_start, mut old_left, mut new_left) =
+            hunk_range(line).ok_or_else(|| format!("Malformed hunk header: {}", line))?;
+        let mut hunk = Hunk {
+            header: line.to_string(),
+            old_start,
+            old: Vec::new(),
+            new: Vec::new(),
+            new_no_newline: false,
+            old_no_newline: false,
+        };
+        let mut last = ' ';
+        while old_left > 0 || new_left > 0 || lines.peek().is_some_and(|l| l.starts_with('\\')) {
+            let Some(body) = lines.peek().copied().map(|l| l.trim_end_matches('\r')) else {
+                break;
+            };
+            if body.starts_with("@@") || body.starts_with("diff --git") {
+                break;
+            }
+            lines.next();
+            // Some tools drop the space of blank context lines
+            let (kind, text) = match body.chars().next() {
+                Some(kind @ (' ' | '-' | '+' | '\\')) => (kind, &body[1..]),
+                None => (' ', ""),
+                Some(_) => return Err(format!("Unexpected line in hunk {}: {}", hunk.header, body)),
+            };
+            match kind {
+                ' ' => {
+                    hunk.old.push(text.to_string());
+                    hunk.new.push(text.to_string());
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+                '-' => {
+                    hunk.old.push(text.to_string());
+                    old_left = old_left.saturating_sub(1);
+                }
+                '+' => {
+                    hunk.new.push(text.to_string());
+                    new_left = new_left.saturating_sub(1);
+                }
+                _ if last == '-' => hunk.old_no_newline = true,
+                _ => hunk.new_no_newline = true,
+            }
+            last = kind;
+        }
+        file.hunks.push(hunk);
+    }
+    files.retain(|file| !file.hunks.is_empty());
+    if files.is_empty() {
+        return Err("No hunks found. Pass a unified diff with `---`/`+++` headers and `@@` hunks.".to_string());
+    }
+    Ok(files)
+}
+
+/// Paths a patch writes, as written in its headers.
+pub fn target_paths(patch: &str) -> Vec<String> {
+    parse(patch)
+        .map(|files| {
+            files
+                .iter()
+                .flat_map(|file| [file.old_path.clone(), file.new_path.clone()])
+                .flatten()
+                .fold(Vec::new(), |mut paths, path| {
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                    paths
+                })
+        })
+        .unwrap_or_default()
+}
+
+/// Where `old` occurs in `lines` at or after `from`, closest to `expected`.
+/// Trailing whitespace is ignored when there's no exact match.
+fn locate(lines: &[String], old: &[String], from: usize, expected: usize) -> Option<usize> {
+    if old.is_empty() {
+        return Some(expected.clamp(from, lines.len()));
+    }
+    let last = lines.len().checked_sub(old.len())?;
+    if from > last {
+        return None;
+    }
+    let closest = |matches: &dyn Fn(&String, &String) -> bool| {
+        (from..=last)
+            .filter(|&start| lines[start..start + old.len()].iter().zip(old).all(|(a, b)| matches(a, b)))
+            .min_by_key(|&start| start.abs_diff(expected))
+    };
+    closest(&|a, b| a == b).or_else(|| closest(&|a, b| a.trim_end() == b.trim_end()))
+}
+
+/// The file's new text, or the number of hunks that didn't apply; plus a
+/// report line per hunk.
+fn apply_hunks(original: &str, hunks: &[Hunk]) -> (Result<String, usize>, Vec<String>) {
+    let crlf = original.contains("\r\n");
+    let text = original.replace("\r\n", "\n");
+    let mut trailing_newline = text.is_empty() || text.ends_with('\n');
+    let mut lines: Vec<String> = match text.strip_suffix('\n').unwrap_or(&text) {
+        "" if text.is_empty() => Vec::new(),
+        body => body.split('\n').map(str::to_string).collect(),
+    };
+
+    let mut report = Vec::new();
+    let mut failed = 0usize;
+    let mut from = 0usize;
+    let mut shift = 0isize;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected = (hunk.old_start.saturating_sub(1) as isize + shift).max(0) as usize;
+        let Some(start) = locate(&lines, &hunk.old, from, expected) else {
+            failed += 1;
+            report.push(format!(
+                "  hunk {} {}: failed, context not found near line {}",
+                index + 1,
+                hunk.header,
+                expected + 1
+            ));
+            continue;
+        };
+        let offset = start as isize - expected as isize;
+        report.push(format!(
+            "  hunk {} {}: applied at line {}{}",
+            index + 1,
+            hunk.header,
+            start + 1,
+            if offset != 0 { format!(" (offset {:+})", offset) } else { String::new() }
+        ));
+        lines.splice(start..start + hunk.old.len(), hunk.new.iter().cloned());
+        from = start + hunk.new.len();
+        shift += hunk.new.len() as isize - hunk.old.len() as isize;
+        if hunk.new_no_newline {
+            trailing_newline = false;
+        } else if hunk.old_no_newline {
+            trailing_newline = true;
+        }
+    }
+    if failed > 0 {
+        return (Err(failed), report);
+    }
+    let mut updated = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        updated.push('\n');
+    }
+    if crlf {
+        updated = updated.replace('\n', "\r\n");
+    }
+    (Ok(updated), report)
+}
+
+enum Change {
+    Write { path: PathBuf, content: Vec<u8> },
+    Delete { path: PathBuf },
+}
+
+fn failure(summary: String, output: String) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary,
+        output,
+    }
+}
+
+/// Write every file or none: new contents go to temp files first and are
+/// renamed over the targets only once all of them are written and the
+/// current versions are backed up.
+/// Write every change or none. A failure partway restores the files already
+/// replaced or deleted from their backups and removes the temp files left.
+fn commit(work_dir: &str, changes: &[Change]) -> Result<(), String> {
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let discard = |staged: &[(PathBuf, PathBuf)]| {
+        for (tmp, _) in staged {
+            let _ = fs::remove_file(tmp);
+        }
+    };
+    for change in changes {
+        if let Change::Write { path, content } = change {
+            match file_writes::stage(path, content) {
+                Ok(tmp) => staged.push(tmp),
+                Err(err) => {
+                    discard(&staged);
+                    return Err(err);
+                }
+            }
+        }
+    }
+    let mut backups: HashMap<&PathBuf, Option<PathBuf>> = HashMap::new();
+    for change in changes {
+        let (Change::Write { path, .. } | Change::Delete { path }) = change;
+        match file_writes::backup(work_dir, path) {
+            Ok(copy) => {
+                backups.insert(path, copy);
+            }
+            Err(err) => {
+                discard(&staged);
+                return Err(err);
+            }
+        }
+    }
+
+    let writes = changes.iter().filter_map(|change| match change {
+        Change::Write { path, .. } => Some(path),
+        Change::Delete { .. } => None,
+    });
+    let mut applied: Vec<&PathBuf> = Vec::new();
+    let mut result = Ok(());
+    for (path, (tmp, target)) in writes.zip(&staged) {
+        if let Err(e) = fs::rename(tmp, target) {
+            result = Err(format!("Failed to replace {}: {}", target.display(), e));
+            break;
+        }
+        applied.push(path);
+    }
+    if result.is_ok() {
+        for change in changes {
+            if let Change::Delete { path } = change {
+                if let Err(e) = fs::remove_file(path) {
+                    result = Err(format!("Failed to delete {}: {}", path.display(), e));
+                    break;
+                }
+                applied.push(path);
+            }
+        }
+    }
+    if let Err(err) = result {
+        // Temp files already renamed are gone; the others are removed
+        discard(&staged);
+        let mut unrestored = Vec::new();
+        for path in applied {
+            let restored = match backups.get(path).cloned().flatten() {
+                Some(copy) => fs::copy(&copy, path).map(|_| ()),
+                None => fs::remove_file(path),
+            };
+            if restored.is_err() {
+                unrestored.push(path.display().to_string());
+            }
+        }
+        if unrestored.is_empty() {
+            return Err(format!("{}. No files were changed.", err));
+        }
+        return Err(format!(
+            "{}. These files could not be restored; their previous versions are in .kimi/backups: {}",
+            err,
+            unrestored.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The ApplyPatch tool: check every hunk of a unified diff against the files
+/// on disk and apply all of them, or none if any hunk's context doesn't match.
+pub fn apply_tool(work_dir: &str, patch: &str, dry_run: bool) -> ToolOutput {
+    let files = match parse(patch) {
+        Ok(files) => files,
+        Err(err) => return failure(err, String::new()),
+    };
+
+    let mut report = Vec::new();
+    let mut diffs = String::new();
+    let mut changes = Vec::new();
+    let mut failed_hunks = 0usize;
+    let mut total_hunks = 0usize;
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for file in &files {
+        let display = file.path();
+        total_hunks += file.hunks.len();
+        if matches!((&file.old_path, &file.new_path), (Some(old), Some(new)) if old != new) {
+            failed_hunks += file.hunks.len();
+            report.push(format!("{}: renames aren't supported; patch the file in place", display));
+            continue;
+        }
+        let access = if file.old_path.is_some() { Access::Read } else { Access::Write };
+        let path = match paths::resolve(work_dir, display, access) {
+            Ok(resolved) => resolved.path,
+            Err(err) => {
+                failed_hunks += file.hunks.len();
+                report.push(format!("{}: {}", display, err));
+                continue;
+            }
+        };
+        // A second section would patch the file as it is on disk and drop the first one's changes
+        if !seen.insert(path.clone()) {
+            failed_hunks += file.hunks.len();
+            report.push(format!("{}: the patch has more than one section for it; merge them into one", display));
+            continue;
+        }
+        let (original, format) = match (&file.old_path, encoding::read(&path)) {
+            (None, Ok(_)) => {
+                failed_hunks += file.hunks.len();
+                report.push(format!("{}: the patch creates it, but it already exists", display));
+                continue;
+            }
+            (None, Err(_)) => (String::new(), encoding::Format::default()),
+            (Some(_), Ok(decoded)) if decoded.lossy => {
+                failed_hunks += file.hunks.len();
+                report.push(encoding::lossy_error(display));
+                continue;
+            }
+            (Some(_), Ok(decoded)) => (decoded.text, decoded.format),
+            (Some(_), Err(err)) => {
+                failed_hunks += file.hunks.len();
+                report.push(format!("{}: failed to read: {}", display, err));
+                continue;
+            }
+        };
+
+        let (updated, hunk_report) = apply_hunks(&original, &file.hunks);
+        let updated = match updated {
+            Ok(updated) => updated,
+            Err(failed) => {
+                failed_hunks += failed;
+                report.push(format!("{}:", display));
+                report.extend(hunk_report);
+                continue;
+            }
+        };
+        if file.new_path.is_none() && !updated.is_empty() {
+            failed_hunks += 1;
+            report.push(format!("{}: the patch deletes it, but lines would remain", display));
+            continue;
+        }
+        report.push(format!("{}:", display));
+        report.extend(hunk_report);
+        diffs.push_str(&tools::unified_diff(display, &original, &updated));
+        changes.push(match file.new_path {
+            Some(_) => Change::Write {
+                path,
+                content: encoding::encode(&updated, format),
+            },
+            None => Change::Delete { path },
+        });
+    }
+
+    let report = report.join("\n");
+    if failed_hunks > 0 {
+        return failure(
+            format!(
+                "Patch not applied: {} of {} hunks failed. No files were changed; re-read the files and send a patch whose context matches.",
+                failed_hunks, total_hunks
+            ),
+            report,
+        );
+    }
+    if dry_run {
+        return ToolOutput {
+            ok: true,
+            summary: format!(
+                "Dry run: patch would change {} file(s). Nothing was written.",
+                changes.len()
+            ),
+            output: diffs,
+        };
+    }
+    if let Err(err) = commit(work_dir, &changes) {
+        return failure(err, report);
+    }
+    ToolOutput {
+        ok: true,
+        summary: format!(
+            "Patch applied: {} hunk(s) in {} file(s).",
+            total_hunks,
+            changes.len()
+        ),
+        output: report,
+    }
+}
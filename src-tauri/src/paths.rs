@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Locations under the home directory the file tools never read or write,
+/// wherever the path came from: key stores, and the app's files holding API
+/// keys, tokens, settings and approval rules
+const PROTECTED_HOME_PATHS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".aws",
+    ".kimi/credentials",
+    ".kimi/gui_auth.json",
+    ".kimi/gui.json",
+    ".kimi/gui_approvals.json",
+    ".kimi/gui_profiles",
+    ".kimi/config.toml",
+    ".kimi/mcp.json",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Access {
+    /// The target must exist
+    Read,
+    /// The target may be new, but its parent folder must exist
+    Write,
+}
+
+/// What happens to a WriteFile, StrReplaceFile or ApplyPatch call that
+/// targets an absolute path outside the work dir.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutsideWrites {
+    /// Always shown to the user, with a warning, even when calls would
+    /// otherwise be approved automatically
+    #[default]
+    Ask,
+    /// Refused outright
+    Deny,
+}
+
+pub struct ResolvedPath {
+    /// Canonical path, with symlinks resolved
+    pub path: PathBuf,
+    /// `path` is inside the canonical work dir
+    pub inside_root: bool,
+}
+
+fn canonical_root(work_dir: &str) -> Result<PathBuf, String> {
+    Path::new(work_dir)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve work dir: {e}"))
+}
+
+/// `path` is `root` or below it. Both should already be canonical.
+pub fn is_within(root: &Path, path: &Path) -> bool {
+    path.starts_with(root)
+}
+
+pub fn check_protected(path: &Path) -> Result<(), String> {
+    let Some(home) = dirs::home_dir().and_then(|home| home.canonicalize().ok()) else {
+        return Ok(());
+    };
+    match PROTECTED_HOME_PATHS
+        .iter()
+        .find(|protected| path.starts_with(home.join(protected)))
+    {
+        Some(protected) => Err(format!("Access to ~/{} is not allowed", protected)),
+        None => Ok(()),
+    }
+}
+
+/// Resolve a path given to a file tool against `work_dir`.
+///
+/// Symlinks are resolved before any check, so a link inside the work dir
+/// can't be used to reach a file outside it. Relative paths must end up inside
+/// the work dir; absolute paths may point elsewhere and are reported through
+/// `inside_root` so callers can refuse them. Protected paths are refused
+/// either way.
+pub fn resolve(work_dir: &str, path: &str, access: Access) -> Result<ResolvedPath, String> {
+    if path.trim().is_empty() {
+        return Err("Path cannot be empty".to_string());
+    }
+
+    let input_path = Path::new(path);
+    let root = canonical_root(work_dir)?;
+    let target = if input_path.is_absolute() {
+        input_path.to_path_buf()
+    } else {
+        Path::new(work_dir).join(input_path)
+    };
+
+    let canonical = match target.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(e) if access == Access::Read => return Err(format!("Failed to resolve path: {e}")),
+        Err(_) => {
+            let name = match target.components().next_back() {
+                Some(Component::Normal(name)) => name.to_owned(),
+                _ => return Err("Invalid path".to_string()),
+            };
+            let parent = target.parent().ok_or_else(|| "Invalid path".to_string())?;
+            parent
+                .canonicalize()
+                .map_err(|e| format!("Failed to resolve path: {e}"))?
+                .join(name)
+        }
+    };
+
+    let inside_root = is_within(&root, &canonical);
+    if !inside_root && !input_path.is_absolute() {
+        return Err("Path is outside working directory".to_string());
+    }
+    check_protected(&canonical)?;
+
+    Ok(ResolvedPath {
+        path: canonical,
+        inside_root,
+    })
+}
+
+/// Like `resolve`, but absolute paths outside the work dir are refused too.
+pub fn resolve_inside(work_dir: &str, path: &str, access: Access) -> Result<PathBuf, String> {
+    let resolved = resolve(work_dir, path, access)?;
+    if !resolved.inside_root {
+        return Err("Path is outside working directory".to_string());
+    }
+    Ok(resolved.path)
+}
+
+/// Whether file walkers descend into symlinked directories (and junctions on
+/// Windows, which Rust reports as symlinks). On by default.
+pub fn follow_symlinks() -> bool {
+    crate::gui_settings_load(None)
+        .ok()
+        .and_then(|payload| payload.settings.follow_symlinks)
+        .unwrap_or(true)
+}
+
+pub fn outside_writes() -> OutsideWrites {
+    crate::gui_settings_load(None)
+        .ok()
+        .and_then(|payload| payload.settings.outside_writes)
+        .unwrap_or_default()
+}
+
+/// A file-editing call with a target outside the work dir. The session's
+/// scratch dir doesn't count; it is always writable.
+pub fn write_escapes(session_id: &str, work_dir: &str, name: &str, args: &serde_json::Value) -> bool {
+    let scratch = crate::scratch::dir(session_id).canonicalize().ok();
+    crate::tools::edit_paths(name, args).iter().any(|path| {
+        resolve(work_dir, path, Access::Write).is_ok_and(|resolved| {
+            !resolved.inside_root
+                && !scratch
+                    .as_ref()
+                    .is_some_and(|scratch| is_within(scratch, &resolved.path))
+        })
+    })
+}
+
+/// Why a write outside the work dir is refused, when the settings refuse them.
+pub fn refuse_outside_write(
+    session_id: &str,
+    work_dir: &str,
+    name: &str,
+    args: &serde_json::Value,
+) -> Option<String> {
+    (outside_writes() == OutsideWrites::Deny && write_escapes(session_id, work_dir, name, args))
+        .then(|| "Writing outside the working directory is turned off.".to_string())
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|meta| meta.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// A symlink a walker of `root` (canonical) may follow: links are followed at
+/// all, and the target stays inside `root` on the same filesystem, so a link
+/// can't pull in a network mount or the rest of the disk.
+pub fn link_allowed(root: &Path, link: &Path, follow: bool) -> bool {
+    if !follow {
+        return false;
+    }
+    let Ok(target) = link.canonicalize() else {
+        return false;
+    };
+    is_within(root, &target) && device(&target) == device(root)
+}
+
+/// Decides which directories a recursive walk of `root` enters. Every folder
+/// is entered at most once by its canonical path, so symlink cycles end.
+pub struct DirWalk {
+    root: PathBuf,
+    follow: bool,
+    visited: HashSet<PathBuf>,
+}
+
+impl DirWalk {
+    /// `root` should already be canonical.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            follow: follow_symlinks(),
+            visited: HashSet::from([root.to_path_buf()]),
+        }
+    }
+
+    pub fn enter(&mut self, dir: &Path) -> bool {
+        let is_link = std::fs::symlink_metadata(dir).is_ok_and(|meta| meta.file_type().is_symlink());
+        if is_link && !link_allowed(&self.root, dir, self.follow) {
+            return false;
+        }
+        match dir.canonicalize() {
+            Ok(canonical) => is_within(&self.root, &canonical) && self.visited.insert(canonical),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::AppState;
+
+/// Checked against every command of a shell line and the line as a whole,
+/// whatever the preset. `*` matches anything.
+const DEFAULT_DENY: &[&str] = &[
+    "rm -rf /",
+    "rm -rf /*",
+    "rm -fr /",
+    "rm -fr /*",
+    "rm -rf ~",
+    "rm -rf ~/",
+    "rm -rf ~/*",
+    "rm -rf $HOME",
+    "rm -rf $HOME/*",
+    "rm -rf --no-preserve-root *",
+    "mkfs*",
+    "dd * of=/dev/*",
+    "* > /dev/sd*",
+    "chmod -R 777 /",
+    "chown -R * /",
+    "shutdown*",
+    "reboot*",
+    ":(){ :|:& };:",
+];
+
+/// Commands that only read, run without approval in every preset
+const READ_ONLY: &[&str] = &[
+    "ls", "cat", "head", "tail", "wc", "pwd", "echo", "grep", "rg", "find", "tree", "file",
+    "stat", "du", "which", "git status", "git diff", "git log", "git show", "git branch",
+    "git blame", "git ls-files", "git rev-parse",
+];
+
+/// Build and test commands the standard preset also trusts. Script runners
+/// such as `make` and `npm run` are left out: they run whatever the repo's
+/// Makefile or package.json says, which the model can edit, so they're asked
+/// about.
+const DEVELOPMENT: &[&str] = &[
+    "git add", "cargo build", "cargo check", "cargo test", "cargo clippy", "cargo fmt", "cargo doc",
+    "cargo tree", "cargo metadata", "npm ls", "pytest", "python -m pytest", "python3 -m pytest",
+    "go build", "go test", "go vet", "go fmt", "rustc", "rustfmt", "tsc", "deno check",
+    "deno test", "deno fmt", "deno lint", "bun test",
+];
+
+/// Options that make a built-in allowed command write, delete, run other
+/// programs or never finish. A command using one is asked about.
+const RISKY_FLAGS: &[(&str, &[&str])] = &[
+    ("find", &[
+        "-delete", "-exec", "-execdir", "-ok", "-okdir", "-fprint", "-fprint0", "-fprintf", "-fls",
+    ]),
+    ("tail", &["-f", "-F", "--follow", "--retry"]),
+    ("rg", &["--pre", "--pre-glob"]),
+    ("git branch", &[
+        "-d", "-D", "-m", "-M", "-c", "-C", "-f", "-u", "--delete", "--move", "--copy", "--force",
+        "--set-upstream-to", "--unset-upstream", "--edit-description",
+    ]),
+    ("git diff", &["--output", "--ext-diff"]),
+    ("git log", &["--output", "--ext-diff"]),
+    ("git show", &["--output", "--ext-diff"]),
+    ("git add", &["-f", "--force"]),
+];
+
+const NETWORK_PROGRAMS: &[&str] = &[
+    "curl", "wget", "ssh", "scp", "sftp", "rsync", "nc", "ncat", "telnet", "ftp",
+];
+
+/// Subcommands that reach the network even though the program usually doesn't
+const NETWORK_COMMANDS: &[&str] = &[
+    "git clone", "git fetch", "git pull", "git push", "git ls-remote", "npm install", "npm i",
+    "npm ci", "npm publish", "pnpm install", "pnpm add", "yarn add", "yarn install", "pip install",
+    "pip3 install", "cargo install", "cargo fetch", "cargo publish", "go get", "docker pull",
+    "docker push",
+];
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyPreset {
+    /// Read-only commands run; anything else is asked about; no network
+    Safe,
+    /// Read-only and common build tools run; anything else is asked about
+    Standard,
+    /// Everything runs except what the deny rules block
+    Yolo,
+}
+
+impl PolicyPreset {
+    pub fn label(self) -> &'static str {
+        match self {
+            PolicyPreset::Safe => "safe",
+            PolicyPreset::Standard => "standard",
+            PolicyPreset::Yolo => "yolo",
+        }
+    }
+}
+
+/// Command policy settings. The rules here are added to the preset's.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PolicySettings {
+    /// Preset for sessions that haven't picked one. When unset, YOLO mode
+    /// means the yolo preset and otherwise every risky call is asked about.
+    pub preset: Option<PolicyPreset>,
+    /// Commands never run, such as `git push --force*`
+    pub deny: Vec<String>,
+    /// Command prefixes that run without approval, such as `docker compose`
+    pub allow: Vec<String>,
+    /// Overrides whether the preset allows network access
+    pub network: Option<bool>,
+    /// File edits under these folders are approved or asked about whatever
+    /// the preset says; the most specific folder wins
+    pub directories: Vec<DirectoryRule>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectoryAction {
+    /// Run without asking, as in YOLO mode
+    Allow,
+    /// Go through the approval flow, even in YOLO mode
+    Ask,
+}
+
+/// YOLO scoped to a folder, e.g. allow edits under `tests/` but ask for `src/`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectoryRule {
+    /// Folder relative to the work dir
+    pub path: String,
+    pub action: DirectoryAction,
+}
+
+impl DirectoryRule {
+    pub fn decision(&self) -> Decision {
+        match self.action {
+            DirectoryAction::Allow => Decision::Allow,
+            DirectoryAction::Ask => Decision::Ask,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let action = match self.action {
+            DirectoryAction::Allow => "allow",
+            DirectoryAction::Ask => "ask",
+        };
+        format!("directory rule {} ({})", self.path.trim(), action)
+    }
+}
+
+/// The folder rule that decides a file-editing call: the most specific rule
+/// covering each target, with ask winning over allow. None when the call
+/// edits no files or some target isn't under any rule.
+pub fn directory_rule<'a>(
+    rules: &'a [DirectoryRule],
+    work_dir: &str,
+    tool_name: &str,
+    args: &serde_json::Value,
+) -> Option<&'a DirectoryRule> {
+    let targets = crate::tools::edit_paths(tool_name, args);
+    if rules.is_empty() || targets.is_empty() {
+        return None;
+    }
+    let root = Path::new(work_dir);
+    let mut decided: Option<&DirectoryRule> = None;
+    for target in targets {
+        let target = crate::claims::normalize(&root.join(target));
+        let rule = rules
+            .iter()
+            .filter(|rule| !rule.path.trim().is_empty())
+            .map(|rule| (rule, crate::claims::normalize(&root.join(rule.path.trim()))))
+            .filter(|(_, folder)| target.starts_with(folder))
+            .max_by_key(|(_, folder)| folder.components().count())
+            .map(|(rule, _)| rule)?;
+        if decided.is_none_or(|current| current.action == DirectoryAction::Allow) {
+            decided = Some(rule);
+        }
+    }
+    decided
+}
+
+/// The rules a session's tool calls are checked against.
+#[derive(Clone, Serialize)]
+pub struct CommandPolicy {
+    pub preset: PolicyPreset,
+    pub deny: Vec<String>,
+    /// Built-in prefixes, subject to RISKY_FLAGS
+    pub builtin: Vec<&'static str>,
+    /// Prefixes added in settings, trusted as written
+    pub allow: Vec<String>,
+    pub network: bool,
+}
+
+pub enum Decision {
+    Allow,
+    /// Reason passed on to the model
+    Deny(String),
+    /// Up to the usual approval flow
+    Ask,
+}
+
+impl CommandPolicy {
+    pub fn new(preset: PolicyPreset, settings: &PolicySettings) -> Self {
+        let mut builtin = READ_ONLY.to_vec();
+        if preset != PolicyPreset::Safe {
+            builtin.extend(DEVELOPMENT);
+        }
+        let allow = settings.allow.clone();
+        let mut deny: Vec<String> = DEFAULT_DENY.iter().map(|s| s.to_string()).collect();
+        deny.extend(settings.deny.iter().cloned());
+        Self {
+            preset,
+            deny: deny.into_iter().filter(|p| !p.trim().is_empty()).collect(),
+            builtin,
+            allow: allow.into_iter().filter(|p| !p.trim().is_empty()).collect(),
+            network: settings.network.unwrap_or(preset != PolicyPreset::Safe),
+        }
+    }
+
+    /// Whether the call runs, is refused, or goes through the approval flow.
+    pub fn decide(&self, tool_name: &str, args: &serde_json::Value) -> Decision {
+        match tool_name {
+            // RunTests and Build carry the command they resolve to
+            "Shell" | "RunInBackground" | "RunTests" | "Build" => {
+                let command = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
+                self.decide_command(command)
+            }
+            "FetchURL" | "SearchWeb" | "DownloadFile" | "ReadIssue" | "ReadPullRequest"
+            | "CreatePullRequest"
+                if !self.network =>
+            {
+                Decision::Deny(format!(
+                    "Network access is off in the {} command policy.",
+                    self.preset.label()
+                ))
+            }
+            _ if self.preset == PolicyPreset::Yolo => Decision::Allow,
+            _ => Decision::Ask,
+        }
+    }
+
+    fn decide_command(&self, command: &str) -> Decision {
+        let line = normalize(command);
+        let segments = split_commands(command);
+        for segment in std::iter::once(&line).chain(&segments) {
+            if let Some(pattern) = self.deny.iter().find(|p| glob_matches(&normalize(p), segment)) {
+                return Decision::Deny(format!(
+                    "Blocked by the {} command policy: `{}` matches the deny rule `{}`.",
+                    self.preset.label(),
+                    segment,
+                    pattern
+                ));
+            }
+        }
+        if !self.network {
+            if let Some(segment) = segments.iter().find(|s| uses_network(s)) {
+                return Decision::Deny(format!(
+                    "Blocked by the {} command policy: `{}` needs network access, which is off.",
+                    self.preset.label(),
+                    segment
+                ));
+            }
+        }
+        if self.preset == PolicyPreset::Yolo {
+            return Decision::Allow;
+        }
+        // Substitutions and redirects can do more than the allowed prefix says
+        let opaque = ["`", "$(", ">", "<("].iter().any(|token| command.contains(token));
+        let allowed = !segments.is_empty() && segments.iter().all(|segment| self.allows(segment));
+        if allowed && !opaque {
+            Decision::Allow
+        } else {
+            Decision::Ask
+        }
+    }
+}
+
+impl CommandPolicy {
+    fn allows(&self, segment: &str) -> bool {
+        self.allow.iter().any(|prefix| has_prefix(segment, prefix))
+            || self
+                .builtin
+                .iter()
+                .any(|prefix| has_prefix(segment, prefix) && !uses_risky_flag(segment, prefix))
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The commands of a shell line, split at `;`, `&&`, `||`, `|`, `&` and
+/// newlines outside quotes, with leading `VAR=value` assignments dropped.
+fn split_commands(command: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (Some(_), _) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (None, '\\') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            (None, ';' | '\n' | '|' | '&') => {
+                // `&&` and `||` count once; `>&` and `&>` are redirects
+                if matches!(c, '&' | '|') && chars.peek() == Some(&c) {
+                    chars.next();
+                } else if c == '&' && (current.ends_with('>') || chars.peek() == Some(&'>')) {
+                    current.push(c);
+                    continue;
+                }
+                segments.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+        .iter()
+        .map(|segment| {
+            let words: Vec<&str> = segment.split_whitespace().collect();
+            let start = words
+                .iter()
+                .position(|word| !is_assignment(word))
+                .unwrap_or(words.len());
+            words[start..].join(" ")
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// `segment` is `prefix` or starts with it followed by arguments.
+fn has_prefix(segment: &str, prefix: &str) -> bool {
+    let prefix = normalize(prefix);
+    segment == prefix
+        || segment
+            .strip_prefix(&prefix)
+            .is_some_and(|rest| rest.starts_with(' '))
+}
+
+/// Whether a command allowed by the built-in `prefix` passes one of the
+/// options listed for it in RISKY_FLAGS, alone, with `=value` or, for short
+/// options, bundled like `-fn`.
+fn uses_risky_flag(segment: &str, prefix: &str) -> bool {
+    let Some((_, flags)) = RISKY_FLAGS.iter().find(|(program, _)| *program == prefix) else {
+        return false;
+    };
+    segment.split(' ').skip(1).any(|word| {
+        let word = word.trim_matches(|c| c == '\'' || c == '"');
+        let name = word.split_once('=').map_or(word, |(name, _)| name);
+        flags.iter().any(|flag| {
+            name == *flag
+                || (flag.len() == 2
+                    && !word.starts_with("--")
+                    && word.starts_with('-')
+                    && word[1..].contains(&flag[1..]))
+        })
+    })
+}
+
+fn uses_network(segment: &str) -> bool {
+    let program = segment.split(' ').next().unwrap_or("");
+    let program = program.rsplit('/').next().unwrap_or(program);
+    NETWORK_PROGRAMS.contains(&program) || NETWORK_COMMANDS.iter().any(|c| has_prefix(segment, c))
+}
+
+/// Whole-text match where `*` stands for any run of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionPolicy {
+    preset: PolicyPreset,
+}
+
+fn session_preset(state: &AppState, session_id: &str) -> Option<PolicyPreset> {
+    let path = state
+        .session_manager
+        .lock()
+        .ok()?
+        .sidecar_path(session_id, "policy.json");
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str::<SessionPolicy>(&contents)
+        .ok()
+        .map(|policy| policy.preset)
+}
+
+/// Policy for a session's turn: the session's own preset, else the default
+/// one from settings, else the yolo preset when YOLO mode is on. None leaves
+/// every call to the approval flow.
+pub fn resolve(
+    state: &AppState,
+    session_id: &str,
+    settings: &PolicySettings,
+    yolo: bool,
+) -> Option<CommandPolicy> {
+    let preset = session_preset(state, session_id)
+        .or(settings.preset)
+        .or(yolo.then_some(PolicyPreset::Yolo))?;
+    Some(CommandPolicy::new(preset, settings))
+}
+
+/// The preset a session picked, if any.
+#[tauri::command]
+pub fn session_policy_get(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Option<PolicyPreset> {
+    session_preset(&state, &session_id)
+}
+
+/// Switch a session to `preset`, or back to the default with None.
+#[tauri::command]
+pub fn session_policy_set(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    preset: Option<PolicyPreset>,
+) -> Result<(), String> {
+    let path = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .sidecar_path(&session_id, "policy.json");
+    match preset {
+        Some(preset) => {
+            let contents =
+                serde_json::to_string(&SessionPolicy { preset }).map_err(|e| e.to_string())?;
+            crate::write_text(&path, &contents)
+        }
+        None => match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(()),
+        },
+    }
+}
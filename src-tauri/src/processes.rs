@@ -0,0 +1,429 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::tools::{self, ToolOutput};
+use crate::AppState;
+
+/// Output kept per process; older output is dropped once it grows past this
+const MAX_BUFFER_BYTES: usize = 1_000_000;
+
+#[derive(Default)]
+struct OutputBuffer {
+    text: String,
+    /// Offset of `text[0]` in everything the process printed
+    start: usize,
+    /// Where the next ReadProcessOutput call starts
+    cursor: usize,
+    running: bool,
+    exit_code: Option<i32>,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        if self.text.len() > MAX_BUFFER_BYTES {
+            let mut cut = self.text.len() - MAX_BUFFER_BYTES;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.start += cut;
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Output from `offset` on, skipping whatever was already dropped.
+    fn since(&self, offset: usize) -> &str {
+        let from = offset.clamp(self.start, self.end()) - self.start;
+        self.text.get(from..).unwrap_or_default()
+    }
+}
+
+struct Process {
+    info: ProcessInfo,
+    output: Arc<Mutex<OutputBuffer>>,
+    kill_tx: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ProcessInfo {
+    pub id: String,
+    pub command: String,
+    pub work_dir: String,
+    /// Session whose agent started it; None when started by the user
+    pub session_id: Option<String>,
+    pub pid: Option<u32>,
+    pub started_at: i64,
+    pub running: bool,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ProcessOutput {
+    pub output: String,
+    /// Pass back as `since` to get only what was printed after this read
+    pub next_offset: usize,
+    pub running: bool,
+    pub exit_code: Option<i32>,
+}
+
+/// Commands left running in the background, such as dev servers and long
+/// builds, whose output is buffered until someone reads it. Processes are
+/// killed when the app exits.
+#[derive(Default)]
+pub struct ProcessRegistry {
+    processes: Mutex<HashMap<String, Process>>,
+}
+
+async fn pump(mut reader: impl AsyncRead + Unpin, output: Arc<Mutex<OutputBuffer>>) {
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        let chunk = String::from_utf8_lossy(&buf[..n]);
+        output.lock().unwrap_or_else(|e| e.into_inner()).push(&chunk);
+    }
+}
+
+/// Stop the process and, on Unix, everything it started: dev servers are
+/// usually a shell running a package manager running the real server.
+pub fn kill_tree(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        kill_group(pid);
+    }
+    let _ = child.start_kill();
+}
+
+/// Stop process `pid` and its children without going through the async
+/// runtime, which may already be shutting down.
+fn kill_group(pid: u32) {
+    #[cfg(unix)]
+    let _ = std::process::Command::new("kill")
+        .args(["-TERM", &format!("-{pid}")])
+        .status();
+    #[cfg(windows)]
+    let _ = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status();
+}
+
+impl ProcessRegistry {
+    pub fn start(
+        &self,
+        command: &str,
+        work_dir: &str,
+        env: &HashMap<String, String>,
+        session_id: Option<&str>,
+    ) -> Result<ProcessInfo, String> {
+        if command.trim().is_empty() {
+            return Err("Command cannot be empty".to_string());
+        }
+        let (shell, args) = tools::shell_command(command);
+        let mut cmd = tokio::process::Command::new(shell);
+        crate::child_env::scrub(&mut cmd);
+        cmd.args(args)
+            .current_dir(work_dir)
+            .envs(env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // Own process group, so killing it reaches the command's children
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let mut child = cmd
+            .spawn()
+            .map_err(|error| format!("Failed to start command: {error}"))?;
+
+        let output = Arc::new(Mutex::new(OutputBuffer {
+            running: true,
+            ..OutputBuffer::default()
+        }));
+        let readers = [
+            child.stdout.take().map(|out| tokio::spawn(pump(out, output.clone()))),
+            child.stderr.take().map(|err| tokio::spawn(pump(err, output.clone()))),
+        ];
+        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let info = ProcessInfo {
+            id: Uuid::new_v4().to_string()[..8].to_string(),
+            command: command.to_string(),
+            work_dir: work_dir.to_string(),
+            session_id: session_id.map(str::to_string),
+            pid: child.id(),
+            started_at: chrono::Utc::now().timestamp(),
+            running: true,
+            exit_code: None,
+        };
+
+        let waiter_output = output.clone();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = kill_rx => {
+                    kill_tree(&mut child);
+                    child.wait().await
+                }
+            };
+            // Let the readers drain what the process printed last
+            for reader in readers.into_iter().flatten() {
+                let _ = reader.await;
+            }
+            let mut output = waiter_output.lock().unwrap_or_else(|e| e.into_inner());
+            output.running = false;
+            output.exit_code = status.ok().and_then(|status| status.code());
+        });
+
+        self.processes.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            info.id.clone(),
+            Process {
+                info: info.clone(),
+                output,
+                kill_tx: Some(kill_tx),
+            },
+        );
+        Ok(info)
+    }
+
+    /// Processes started by `session_id`, or all of them.
+    pub fn list(&self, session_id: Option<&str>) -> Vec<ProcessInfo> {
+        let processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<ProcessInfo> = processes
+            .values()
+            .filter(|p| session_id.is_none_or(|id| p.info.session_id.as_deref() == Some(id)))
+            .map(|p| {
+                let output = p.output.lock().unwrap_or_else(|e| e.into_inner());
+                ProcessInfo {
+                    running: output.running,
+                    exit_code: output.exit_code,
+                    ..p.info.clone()
+                }
+            })
+            .collect();
+        list.sort_by_key(|p| p.started_at);
+        list
+    }
+
+    /// Output from `since` on, or since the agent's previous read when None.
+    pub fn output(&self, id: &str, since: Option<usize>) -> Result<ProcessOutput, String> {
+        let processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        let process = processes
+            .get(id)
+            .ok_or_else(|| format!("No background process {id}"))?;
+        let mut output = process.output.lock().unwrap_or_else(|e| e.into_inner());
+        let text = output.since(since.unwrap_or(output.cursor)).to_string();
+        let next_offset = output.end();
+        if since.is_none() {
+            output.cursor = next_offset;
+        }
+        Ok(ProcessOutput {
+            output: text,
+            next_offset,
+            running: output.running,
+            exit_code: output.exit_code,
+        })
+    }
+
+    pub fn kill(&self, id: &str) -> Result<(), String> {
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        let process = processes
+            .get_mut(id)
+            .ok_or_else(|| format!("No background process {id}"))?;
+        match process.kill_tx.take() {
+            Some(kill_tx) => {
+                let _ = kill_tx.send(());
+                Ok(())
+            }
+            None => Err(format!("Process {id} is already being stopped")),
+        }
+    }
+
+    /// Stop every process still running. Called when the app exits, since the
+    /// tasks that would kill them on drop don't get to run.
+    pub fn kill_all(&self) {
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        for process in processes.values_mut() {
+            let running = process.output.lock().unwrap_or_else(|e| e.into_inner()).running;
+            if !running {
+                continue;
+            }
+            if let Some(pid) = process.info.pid {
+                kill_group(pid);
+            }
+            if let Some(kill_tx) = process.kill_tx.take() {
+                let _ = kill_tx.send(());
+            }
+        }
+    }
+
+    /// Forget a process that has exited, dropping its buffered output.
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        let running = processes
+            .get(id)
+            .ok_or_else(|| format!("No background process {id}"))?
+            .output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .running;
+        if running {
+            return Err(format!("Process {id} is still running"));
+        }
+        processes.remove(id);
+        Ok(())
+    }
+}
+
+fn status_line(running: bool, exit_code: Option<i32>) -> String {
+    match (running, exit_code) {
+        (true, _) => "running".to_string(),
+        (false, Some(code)) => format!("exited with code {code}"),
+        (false, None) => "killed".to_string(),
+    }
+}
+
+pub fn run_in_background_tool(
+    registry: &ProcessRegistry,
+    session_id: &str,
+    work_dir: &str,
+    command: &str,
+    env: &HashMap<String, String>,
+) -> ToolOutput {
+    match registry.start(command, work_dir, env, Some(session_id)) {
+        Ok(info) => ToolOutput {
+            ok: true,
+            summary: format!(
+                "Started background process {}. Use ReadProcessOutput to check on it.",
+                info.id
+            ),
+            output: String::new(),
+        },
+        Err(error) => ToolOutput {
+            ok: false,
+            summary: error,
+            output: String::new(),
+        },
+    }
+}
+
+pub fn list_tool(registry: &ProcessRegistry, session_id: &str) -> ToolOutput {
+    let list = registry.list(Some(session_id));
+    let output = list
+        .iter()
+        .map(|p| format!("{}  {}  $ {}", p.id, status_line(p.running, p.exit_code), p.command))
+        .collect::<Vec<_>>()
+        .join("\n");
+    ToolOutput {
+        ok: true,
+        summary: format!("{} background process(es).", list.len()),
+        output,
+    }
+}
+
+/// Whether `id` was started by `session_id`; the agent may only read and stop
+/// its own processes.
+fn owned(registry: &ProcessRegistry, session_id: &str, id: &str) -> bool {
+    registry.list(Some(session_id)).iter().any(|p| p.id == id)
+}
+
+pub fn read_output_tool(registry: &ProcessRegistry, session_id: &str, id: &str) -> ToolOutput {
+    let result = if owned(registry, session_id, id) {
+        registry.output(id, None)
+    } else {
+        Err(format!("No background process {id} in this session"))
+    };
+    match result {
+        Ok(read) => {
+            let (output, truncated) = tools::truncate_output(&read.output);
+            ToolOutput {
+                ok: true,
+                summary: tools::append_truncation(
+                    format!(
+                        "Process {} is {}; {} new bytes of output.",
+                        id,
+                        status_line(read.running, read.exit_code),
+                        read.output.len()
+                    ),
+                    truncated,
+                ),
+                output,
+            }
+        }
+        Err(error) => ToolOutput {
+            ok: false,
+            summary: error,
+            output: String::new(),
+        },
+    }
+}
+
+pub fn kill_tool(registry: &ProcessRegistry, session_id: &str, id: &str) -> ToolOutput {
+    let result = if owned(registry, session_id, id) {
+        registry.kill(id)
+    } else {
+        Err(format!("No background process {id} in this session"))
+    };
+    match result {
+        Ok(()) => ToolOutput {
+            ok: true,
+            summary: format!("Stopping process {id}."),
+            output: String::new(),
+        },
+        Err(error) => ToolOutput {
+            ok: false,
+            summary: error,
+            output: String::new(),
+        },
+    }
+}
+
+#[tauri::command]
+pub fn background_process_start(
+    state: tauri::State<'_, AppState>,
+    command: String,
+    work_dir: String,
+) -> Result<ProcessInfo, String> {
+    state
+        .processes
+        .start(&command, &work_dir, &HashMap::new(), None)
+}
+
+#[tauri::command]
+pub fn background_processes_list(
+    state: tauri::State<'_, AppState>,
+    session_id: Option<String>,
+) -> Vec<ProcessInfo> {
+    state.processes.list(session_id.as_deref())
+}
+
+/// `since` is the `next_offset` of the previous read; 0 reads everything kept.
+#[tauri::command]
+pub fn background_process_output(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    since: usize,
+) -> Result<ProcessOutput, String> {
+    state.processes.output(&id, Some(since))
+}
+
+#[tauri::command]
+pub fn background_process_kill(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    state.processes.kill(&id)
+}
+
+#[tauri::command]
+pub fn background_process_remove(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    state.processes.remove(&id)
+}
@@ -0,0 +1,179 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const MAX_CAPTURE_SECS: u64 = 600;
+/// Spans kept per capture; a busy agent loop shouldn't exhaust memory
+const MAX_EVENTS: usize = 200_000;
+
+/// A finished span in Chrome trace format (`ph: "X"` complete events), which
+/// chrome://tracing and ui.perfetto.dev both open.
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: String,
+    ph: &'static str,
+    /// Microseconds since the capture started
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    /// Root span of the event, so each turn gets a track of its own
+    tid: u64,
+    args: serde_json::Map<String, serde_json::Value>,
+}
+
+struct Capture {
+    started: Instant,
+    events: Vec<TraceEvent>,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+struct SpanTiming {
+    started: Instant,
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Default)]
+struct FieldVisitor(serde_json::Map<String, serde_json::Value>);
+
+impl tracing::field::Visit for FieldVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+/// Times every span and, while `profile_capture` runs, keeps the finished
+/// ones as trace events.
+struct ProfileLayer;
+
+impl<S> Layer<S> for ProfileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanTiming {
+            started: Instant::now(),
+            fields: visitor.0,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+            let mut visitor = FieldVisitor(std::mem::take(&mut timing.fields));
+            values.record(&mut visitor);
+            timing.fields = visitor.0;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(capture) = capture.as_mut().filter(|c| c.events.len() < MAX_EVENTS) else {
+            return;
+        };
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else {
+            return;
+        };
+        let root = span
+            .scope()
+            .last()
+            .map_or(id.into_u64(), |root| root.id().into_u64());
+        capture.events.push(TraceEvent {
+            name: span.name(),
+            cat: span.metadata().target().to_string(),
+            ph: "X",
+            ts: timing
+                .started
+                .saturating_duration_since(capture.started)
+                .as_micros() as u64,
+            dur: timing.started.elapsed().as_micros() as u64,
+            pid: std::process::id(),
+            tid: root,
+            args: timing.fields,
+        });
+    }
+}
+
+/// Install the profiling layer as the global subscriber; call once at startup.
+pub fn init() {
+    let subscriber = tracing_subscriber::registry().with(ProfileLayer);
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
+
+fn profile_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_profiles").join(format!(
+        "profile-{}.json",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Record the agent loop, tool calls and persistence for `duration_secs` and
+/// write them as a Chrome trace to `path` (or under `~/.kimi/gui_profiles`).
+/// Returns the written path.
+#[tauri::command]
+pub async fn profile_capture(duration_secs: u64, path: Option<String>) -> Result<String, String> {
+    if duration_secs == 0 || duration_secs > MAX_CAPTURE_SECS {
+        return Err(format!(
+            "Capture duration must be between 1 and {} seconds",
+            MAX_CAPTURE_SECS
+        ));
+    }
+    {
+        let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+        if capture.is_some() {
+            return Err("A profile is already being captured".to_string());
+        }
+        *capture = Some(Capture {
+            started: Instant::now(),
+            events: Vec::new(),
+        });
+    }
+    tokio::time::sleep(Duration::from_secs(duration_secs)).await;
+    let capture = CAPTURE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or_else(|| "Profile capture was lost".to_string())?;
+
+    let path = path
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(profile_path);
+    let trace = serde_json::json!({
+        "traceEvents": capture.events,
+        "displayTimeUnit": "ms",
+    });
+    let contents = serde_json::to_string(&trace).map_err(|e| e.to_string())?;
+    crate::write_text(&path, &contents)?;
+    Ok(path.to_string_lossy().to_string())
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const COMMANDS_FILE: &str = ".kimi/commands.toml";
+const AGENTS_FILES: &[&str] = &["AGENTS.md", "agents.md"];
+
+/// A command the project declares for a task such as `test` or `build`.
+#[derive(Clone, Serialize)]
+pub struct ProjectCommand {
+    /// Lowercase task name, e.g. `test`, `build`, `lint`
+    pub name: String,
+    pub command: String,
+    pub description: Option<String>,
+    /// File it was declared in, relative to the work dir
+    pub source: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CommandsFile {
+    commands: BTreeMap<String, CommandEntry>,
+}
+
+/// `test = "cargo test"`, or a table with `command` and `description`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandEntry {
+    Command(String),
+    Detailed {
+        command: String,
+        #[serde(default)]
+        description: Option<String>,
+    },
+}
+
+fn from_commands_file(work_dir: &Path) -> Result<Vec<ProjectCommand>, String> {
+    let Ok(contents) = std::fs::read_to_string(work_dir.join(COMMANDS_FILE)) else {
+        return Ok(Vec::new());
+    };
+    let file: CommandsFile =
+        toml::from_str(&contents).map_err(|e| format!("Invalid {}: {}", COMMANDS_FILE, e))?;
+    Ok(file
+        .commands
+        .into_iter()
+        .map(|(name, entry)| {
+            let (command, description) = match entry {
+                CommandEntry::Command(command) => (command, None),
+                CommandEntry::Detailed { command, description } => (command, description),
+            };
+            ProjectCommand {
+                name: name.trim().to_lowercase(),
+                command: command.trim().to_string(),
+                description,
+                source: COMMANDS_FILE.to_string(),
+            }
+        })
+        .filter(|c| !c.name.is_empty() && !c.command.is_empty())
+        .collect())
+}
+
+/// `- test: `cargo test` optional description` under a heading that mentions
+/// commands. The name may be bold; `*` bullets work too.
+fn parse_agents_line(line: &str) -> Option<(String, String, Option<String>)> {
+    let item = line.trim_start().strip_prefix("- ").or_else(|| line.trim_start().strip_prefix("* "))?;
+    let (name, rest) = item.split_once(':')?;
+    let name = name.trim().trim_matches('*').trim().to_lowercase();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ')) {
+        return None;
+    }
+    let rest = rest.trim().strip_prefix('`')?;
+    let (command, description) = rest.split_once('`')?;
+    let description = description
+        .trim()
+        .trim_start_matches(['-', '—', '–', '(', ':'])
+        .trim_end_matches(')')
+        .trim();
+    Some((
+        name.replace(' ', "-"),
+        command.trim().to_string(),
+        (!description.is_empty()).then(|| description.to_string()),
+    ))
+}
+
+fn from_agents_md(work_dir: &Path) -> Vec<ProjectCommand> {
+    let Some((file, contents)) = AGENTS_FILES
+        .iter()
+        .find_map(|file| Some((*file, std::fs::read_to_string(work_dir.join(file)).ok()?)))
+    else {
+        return Vec::new();
+    };
+    let mut commands = Vec::new();
+    // Level of the commands heading we're under, if any
+    let mut section: Option<usize> = None;
+    let mut fenced = false;
+    for line in contents.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+            continue;
+        }
+        if fenced {
+            continue;
+        }
+        let level = line.chars().take_while(|&c| c == '#').count();
+        if level > 0 && line[level..].starts_with(' ') {
+            if section.is_none_or(|current| level <= current) {
+                section = line.to_lowercase().contains("command").then_some(level);
+            }
+            continue;
+        }
+        if section.is_none() {
+            continue;
+        }
+        if let Some((name, command, description)) = parse_agents_line(line) {
+            if !command.is_empty() {
+                commands.push(ProjectCommand {
+                    name,
+                    command,
+                    description,
+                    source: file.to_string(),
+                });
+            }
+        }
+    }
+    commands
+}
+
+/// Commands declared in `.kimi/commands.toml` and in the commands section of
+/// AGENTS.md. The TOML file wins when both name the same task.
+pub fn load(work_dir: &str) -> Result<Vec<ProjectCommand>, String> {
+    let root = Path::new(work_dir);
+    let mut commands = from_commands_file(root)?;
+    for command in from_agents_md(root) {
+        if !commands.iter().any(|c| c.name == command.name) {
+            commands.push(command);
+        }
+    }
+    Ok(commands)
+}
+
+fn task_names(tool_name: &str) -> &'static [&'static str] {
+    match tool_name {
+        "RunTests" => &["test", "tests"],
+        "Build" => &["build"],
+        _ => &[],
+    }
+}
+
+/// The command a RunTests or Build call runs, with `extra` appended; None when
+/// the project doesn't declare one.
+pub fn resolve(work_dir: &str, tool_name: &str, extra: Option<&str>) -> Option<String> {
+    let names = task_names(tool_name);
+    let commands = load(work_dir).ok()?;
+    let declared = names
+        .iter()
+        .find_map(|name| commands.iter().find(|c| &c.name == name))?;
+    Some(match extra.map(str::trim).filter(|e| !e.is_empty()) {
+        Some(extra) => format!("{} {}", declared.command, extra),
+        None => declared.command.clone(),
+    })
+}
+
+/// Whether `tool_name` (RunTests or Build) has a command to run.
+pub fn declares(commands: &[ProjectCommand], tool_name: &str) -> bool {
+    task_names(tool_name)
+        .iter()
+        .any(|name| commands.iter().any(|c| &c.name == name))
+}
+
+/// Put the resolved command into a RunTests or Build call's arguments, so the
+/// command policy, approval rules and prompt all see what will actually run.
+pub fn annotate(work_dir: &str, tool_name: &str, args: &mut serde_json::Value) {
+    if task_names(tool_name).is_empty() {
+        return;
+    }
+    let extra = args.get("args").and_then(|v| v.as_str()).map(str::to_string);
+    if let Some(object) = args.as_object_mut() {
+        match resolve(work_dir, tool_name, extra.as_deref()) {
+            Some(command) => object.insert("command".to_string(), command.into()),
+            None => object.remove("command"),
+        };
+    }
+}
+
+/// A line per declared command, for the system prompt.
+pub fn prompt_summary(commands: &[ProjectCommand]) -> String {
+    let mut summary = String::new();
+    for command in commands {
+        summary.push_str(&format!("- {}: `{}`", command.name, command.command));
+        if let Some(description) = &command.description {
+            summary.push_str(&format!(" ({})", description));
+        }
+        summary.push('\n');
+    }
+    summary
+}
+
+/// Test, build and other commands the project declares, for one-click actions.
+#[tauri::command]
+pub fn project_commands(work_dir: String) -> Result<Vec<ProjectCommand>, String> {
+    load(&work_dir)
+}
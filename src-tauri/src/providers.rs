@@ -0,0 +1,305 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::http::{AuthScheme, Credentials};
+use crate::tools;
+
+/// Provider types the chat loop can talk to. Everything but `kimi` speaks the
+/// OpenAI chat completions API at `base_url`.
+struct Preset {
+    kind: &'static str,
+    /// Used when the provider doesn't set `base_url`
+    default_base: Option<&'static str>,
+    /// Local servers usually run without a key
+    needs_key: bool,
+    headers: &'static [(&'static str, &'static str)],
+}
+
+const PRESETS: &[Preset] = &[
+    Preset {
+        kind: "kimi",
+        default_base: Some("https://api.kimi.com/coding/v1"),
+        needs_key: false,
+        headers: &[],
+    },
+    Preset {
+        kind: "openai_legacy",
+        default_base: Some("https://api.openai.com/v1"),
+        needs_key: true,
+        headers: &[],
+    },
+    Preset {
+        kind: "openrouter",
+        default_base: Some("https://openrouter.ai/api/v1"),
+        needs_key: true,
+        // Attribution headers OpenRouter asks apps to send
+        headers: &[("HTTP-Referer", "https://github.com/conormackey/KimiCode-GUI"), ("X-Title", "Kimi Code GUI")],
+    },
+    Preset {
+        kind: "ollama",
+        default_base: Some("http://localhost:11434/v1"),
+        needs_key: false,
+        headers: &[],
+    },
+    Preset {
+        kind: "lmstudio",
+        default_base: Some("http://localhost:1234/v1"),
+        needs_key: false,
+        headers: &[],
+    },
+    Preset {
+        kind: "vllm",
+        default_base: Some("http://localhost:8000/v1"),
+        needs_key: false,
+        headers: &[],
+    },
+];
+
+fn preset(kind: &str) -> Option<&'static Preset> {
+    let kind = match kind {
+        "openai" | "openai_compatible" => "openai_legacy",
+        "lm_studio" => "lmstudio",
+        other => other,
+    };
+    PRESETS.iter().find(|preset| preset.kind == kind)
+}
+
+/// Entry of the config file's `providers` table.
+#[derive(Clone, Serialize)]
+pub struct Provider {
+    pub name: String,
+    /// `type` in the config
+    pub kind: String,
+    pub base_url: Option<String>,
+    #[serde(skip)]
+    pub api_key: Option<String>,
+    /// Named credential from the auth store, used instead of `api_key`
+    pub credential: Option<String>,
+    pub custom_headers: HashMap<String, String>,
+    /// How `api_key` is sent to this provider; bearer when unset
+    #[serde(skip)]
+    pub auth_scheme: AuthScheme,
+}
+
+/// Entry of the config file's `models` table.
+#[derive(Clone, Serialize)]
+pub struct ConfiguredModel {
+    /// Key in the `models` table, which is what the GUI settings store
+    pub name: String,
+    pub provider: String,
+    /// Model id sent to the provider; the key when unset
+    pub model: String,
+    pub max_context_size: Option<u64>,
+    pub capabilities: Vec<String>,
+    /// `tool_calls = false` in the config for models without native tool calling
+    pub tool_calls: bool,
+}
+
+fn string(value: &serde_json::Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn providers(config: &serde_json::Value) -> Vec<Provider> {
+    config["providers"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| Provider {
+            name: name.clone(),
+            kind: string(value, "type").unwrap_or_else(|| "openai_legacy".to_string()),
+            base_url: string(value, "base_url"),
+            api_key: string(value, "api_key"),
+            credential: string(value, "credential"),
+            custom_headers: value["custom_headers"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect(),
+            auth_scheme: serde_json::from_value(value["auth_scheme"].clone()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn models(config: &serde_json::Value) -> Vec<ConfiguredModel> {
+    config["models"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| {
+            Some(ConfiguredModel {
+                name: name.clone(),
+                provider: string(value, "provider")?,
+                model: string(value, "model").unwrap_or_else(|| name.clone()),
+                max_context_size: value["max_context_size"].as_u64(),
+                capabilities: value["capabilities"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|c| c.as_str().map(str::to_string))
+                    .collect(),
+                tool_calls: value["tool_calls"].as_bool().unwrap_or(true),
+            })
+        })
+        .collect()
+}
+
+/// Provider and model entry for `model`, if the config routes it to one.
+fn route(config_path: Option<&str>, model: &str) -> Option<(Provider, ConfiguredModel)> {
+    let config = tools::load_config_value(config_path).ok()?;
+    let entry = models(&config).into_iter().find(|m| m.name == model)?;
+    let provider = providers(&config)
+        .into_iter()
+        .find(|p| p.name == entry.provider)?;
+    Some((provider, entry))
+}
+
+/// Credentials for a configured provider, or None for a `kimi` provider
+/// without its own key, which uses the signed-in account.
+fn provider_credentials(provider: &Provider) -> Result<Option<Credentials>, String> {
+    let preset = preset(&provider.kind)
+        .ok_or_else(|| format!("Provider {} has unsupported type {}", provider.name, provider.kind))?;
+    if preset.kind == "kimi" && provider.api_key.is_none() {
+        return Ok(None);
+    }
+    let base = provider
+        .base_url
+        .clone()
+        .or_else(|| preset.default_base.map(str::to_string))
+        .ok_or_else(|| format!("Provider {} has no base_url", provider.name))?;
+    let token = match &provider.api_key {
+        Some(key) => key.clone(),
+        None if preset.needs_key => {
+            return Err(format!("Provider {} needs an api_key", provider.name))
+        }
+        None => String::new(),
+    };
+    let mut headers: Vec<(String, String)> = preset
+        .headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    headers.extend(provider.custom_headers.clone());
+    Ok(Some(Credentials {
+        token,
+        base: base.trim_end_matches('/').to_string(),
+        scheme: provider.auth_scheme.clone(),
+        headers,
+    }))
+}
+
+/// Credentials and provider-side model id for a chat request with `model`.
+/// Models the config doesn't route elsewhere use the signed-in Kimi account.
+pub async fn resolve(
+    auth_config: &crate::AuthConfig,
+    config_path: Option<&str>,
+    model: &str,
+) -> Result<(Credentials, String), String> {
+    let (credentials, api_model) = match route(config_path, model) {
+        Some((provider, entry)) => match &provider.credential {
+            Some(name) => {
+                let mut credentials = crate::credentials::resolve(name).await?;
+                if let Some(base) = &provider.base_url {
+                    credentials.base = base.trim_end_matches('/').to_string();
+                }
+                credentials.headers.extend(provider.custom_headers.clone());
+                (Some(credentials), entry.model)
+            }
+            None => (provider_credentials(&provider)?, entry.model),
+        },
+        None => (None, model.to_string()),
+    };
+    let credentials = match credentials {
+        Some(credentials) => credentials,
+        None => crate::llm::resolve_credentials(auth_config).await?,
+    };
+    Ok((credentials, api_model))
+}
+
+/// Credentials and provider-side model id for an auxiliary request such as an
+/// outline, worklog or follow-up suggestions. These use the summarizer model
+/// when one is set, so they can go to a cheaper model than the chat, and
+/// `fallback` otherwise.
+pub async fn resolve_summarizer(
+    config_path: Option<&str>,
+    summarizer_model: Option<&str>,
+    fallback: &str,
+) -> Result<(Credentials, String), String> {
+    let model = summarizer_model
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(fallback);
+    resolve(&crate::load_auth_config(), config_path, model).await
+}
+
+/// Requests for `model` are signed with the OAuth login rather than a key.
+pub fn uses_oauth(auth_config: &crate::AuthConfig, config_path: Option<&str>, model: &str) -> bool {
+    match route(config_path, model) {
+        Some((provider, _)) => match &provider.credential {
+            Some(name) => crate::credentials::is_oauth(name),
+            None => {
+                auth_config.mode != "api_key"
+                    && matches!(provider_credentials(&provider), Ok(None))
+            }
+        },
+        None => auth_config.mode != "api_key",
+    }
+}
+
+/// The config doesn't mark `model` as lacking native tool calling.
+pub fn supports_tool_calls(config_path: Option<&str>, model: &str) -> bool {
+    route(config_path, model).is_none_or(|(_, entry)| entry.tool_calls)
+}
+
+/// Models routed to providers other than the Kimi account, in the shape of a
+/// `/models` entry so the model picker can list them.
+pub fn external_models(config_path: Option<&str>) -> Vec<serde_json::Value> {
+    let Ok(config) = tools::load_config_value(config_path) else {
+        return Vec::new();
+    };
+    let providers = providers(&config);
+    models(&config)
+        .into_iter()
+        .filter(|model| {
+            providers
+                .iter()
+                .find(|p| p.name == model.provider)
+                .is_some_and(|p| preset(&p.kind).is_some_and(|preset| preset.kind != "kimi"))
+        })
+        .map(|model| {
+            serde_json::json!({
+                "id": model.name,
+                "provider": model.provider,
+                "context_length": model.max_context_size.unwrap_or(0),
+                "supports_reasoning": model.capabilities.iter().any(|c| c == "thinking"),
+                "supports_image_in": model.capabilities.iter().any(|c| c == "image_in"),
+                "supports_tool_calls": model.tool_calls,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct ProvidersPayload {
+    pub providers: Vec<Provider>,
+    pub models: Vec<ConfiguredModel>,
+    /// Values accepted in a provider's `type`
+    pub supported_types: Vec<String>,
+}
+
+/// Providers and models from the config file, without their keys.
+#[tauri::command]
+pub fn providers_list(config_file: Option<String>) -> Result<ProvidersPayload, String> {
+    let config = tools::load_config_value(config_file.as_deref().filter(|p| !p.is_empty()))
+        .unwrap_or_default();
+    Ok(ProvidersPayload {
+        providers: providers(&config),
+        models: models(&config),
+        supported_types: PRESETS.iter().map(|p| p.kind.to_string()).collect(),
+    })
+}
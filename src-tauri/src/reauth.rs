@@ -0,0 +1,82 @@
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::oauth::{self, LoginState};
+
+/// How often a paused turn checks whether the user has logged in again
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Access token for the next step of a turn on the OAuth login. A login that
+/// is about to lapse emits an `auth://expiring` warning and carries on; a dead
+/// one pauses the turn until the user logs in again. None when the turn was
+/// cancelled while paused.
+pub async fn step_token(
+    window: &tauri::Window,
+    session_id: &str,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> Option<String> {
+    loop {
+        match oauth::login_state().await {
+            LoginState::Fresh(token) => return Some(token),
+            LoginState::Expiring {
+                access_token,
+                expires_at,
+                error,
+            } => {
+                emit_expiring(window, session_id, false, &error, Some(expires_at));
+                return Some(access_token);
+            }
+            LoginState::Expired(reason, token) => {
+                let rejected = token.unwrap_or_default();
+                if !wait_for_login(window, session_id, &reason, &rejected, cancel_rx).await {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Pause a turn until a valid login other than `rejected_token` is saved,
+/// from this app or the CLI. False when the turn is cancelled first.
+pub async fn wait_for_login(
+    window: &tauri::Window,
+    session_id: &str,
+    reason: &str,
+    rejected_token: &str,
+    cancel_rx: &mut tokio::sync::oneshot::Receiver<()>,
+) -> bool {
+    emit_expiring(window, session_id, true, reason, None);
+    loop {
+        tokio::select! {
+            _ = &mut *cancel_rx => return false,
+            _ = tokio::time::sleep(LOGIN_POLL_INTERVAL) => {}
+        }
+        let renewed = oauth::is_logged_in()
+            && oauth::load_token().is_some_and(|token| token.access_token != rejected_token);
+        if renewed {
+            let _ = window.emit(
+                "auth://renewed",
+                serde_json::json!({ "session_id": session_id }),
+            );
+            return true;
+        }
+    }
+}
+
+fn emit_expiring(
+    window: &tauri::Window,
+    session_id: &str,
+    paused: bool,
+    reason: &str,
+    expires_at: Option<f64>,
+) {
+    let _ = window.emit(
+        "auth://expiring",
+        serde_json::json!({
+            "session_id": session_id,
+            "paused": paused,
+            "reason": reason,
+            "expires_at": expires_at,
+        }),
+    );
+}
@@ -0,0 +1,291 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+use tauri::Emitter;
+use uuid::Uuid;
+
+use crate::llm::{self, StreamEvent};
+use crate::providers;
+use crate::tools;
+use crate::AppState;
+
+const MAX_REPLAY_STEPS: usize = 20;
+
+#[derive(Clone, Serialize)]
+pub struct ReplayTurn {
+    pub index: usize,
+    pub user: String,
+    pub original: Option<String>,
+    pub replay: String,
+    pub tool_calls: Vec<String>,
+    /// Tools that ran in dry-run mode instead of executing: all but the read-only ones
+    pub skipped_tools: Vec<String>,
+    /// Diffs the dry-run file edits would have applied
+    pub diffs: Vec<String>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ReplayReport {
+    pub session_id: String,
+    pub model: String,
+    pub work_dir: String,
+    pub turns: Vec<ReplayTurn>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub duration_ms: u64,
+}
+
+/// Pair every user message with the assistant reply that followed it.
+fn user_turns(messages: &[crate::Message]) -> Vec<(String, Option<String>)> {
+    let mut turns: Vec<(String, Option<String>)> = Vec::new();
+    for message in messages {
+        match message.role.as_str() {
+            "user" => turns.push((message.content.clone(), None)),
+            "assistant" => {
+                if let Some(last) = turns.last_mut() {
+                    if last.1.is_none() && !message.content.is_empty() {
+                        last.1 = Some(message.content.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    turns
+}
+
+fn emit_replay(window: &tauri::Window, event: &str, data: serde_json::Value) {
+    let _ = window.emit(
+        "replay://event",
+        StreamEvent {
+            event: event.to_string(),
+            data,
+        },
+    );
+}
+
+#[tauri::command]
+pub async fn session_replay(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    model: String,
+    config_file: Option<String>,
+) -> Result<ReplayReport, String> {
+    let session = {
+        let mut manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        manager
+            .find_session(&session_id)
+            .ok_or_else(|| "Session not found".to_string())?
+    };
+
+    let turns = user_turns(&session.messages);
+    if turns.is_empty() {
+        return Err("Session has no user turns to replay".to_string());
+    }
+
+    let auth_config = crate::load_auth_config();
+    let config_path = config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::default_config_path().to_string_lossy().to_string()));
+    // Same routing as a chat turn, so provider models reach their own endpoint
+    let (credentials, api_model) =
+        providers::resolve(&auth_config, config_path.as_deref(), &model).await?;
+    let work_dir = session.work_dir.clone();
+    let client = crate::http::client(&credentials)?;
+    let tools_def = tools::tool_definitions();
+    let env = HashMap::new();
+    let started = Instant::now();
+
+    let mut messages = vec![serde_json::json!({
+        "role": "system",
+        "content": llm::generate_system_prompt(&work_dir),
+    })];
+    let mut report_turns = Vec::new();
+
+    for (index, (user, original)) in turns.into_iter().enumerate() {
+        emit_replay(
+            &window,
+            "turn_start",
+            serde_json::json!({ "session_id": session_id, "index": index }),
+        );
+
+        let turn_started = Instant::now();
+        let mut turn = ReplayTurn {
+            index,
+            user: user.clone(),
+            original,
+            replay: String::new(),
+            tool_calls: Vec::new(),
+            skipped_tools: Vec::new(),
+            diffs: Vec::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            duration_ms: 0,
+            error: None,
+        };
+        messages.push(serde_json::json!({ "role": "user", "content": user }));
+
+        let mut finished = false;
+        for _ in 0..MAX_REPLAY_STEPS {
+            let request = serde_json::json!({
+                "model": api_model,
+                "messages": messages.clone(),
+                "stream": false,
+                "tools": tools_def.clone(),
+                "tool_choice": "auto",
+            });
+            let data =
+                match llm::post_chat_completion(&client, &credentials, &request, Some(&session_id)).await {
+                    Ok(data) => data,
+                    Err(err) => {
+                        turn.error = Some(err);
+                        break;
+                    }
+                };
+
+            let usage = data.get("usage").cloned().unwrap_or_default();
+            turn.prompt_tokens += usage.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+            turn.completion_tokens += usage
+                .get("completion_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+
+            let message = match data
+                .get("choices")
+                .and_then(|v| v.get(0))
+                .and_then(|v| v.get("message"))
+                .cloned()
+            {
+                Some(message) => message,
+                None => {
+                    turn.error = Some("No message in response".to_string());
+                    break;
+                }
+            };
+            let content = message
+                .get("content")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            let tool_calls = message
+                .get("tool_calls")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+
+            if tool_calls.is_empty() {
+                messages.push(serde_json::json!({ "role": "assistant", "content": content }));
+                turn.replay = content;
+                finished = true;
+                break;
+            }
+
+            messages.push(serde_json::json!({
+                "role": "assistant",
+                "content": content,
+                "tool_calls": tool_calls,
+            }));
+
+            for tool_call in tool_calls {
+                let tool_call_id = tool_call
+                    .get("id")
+                    .and_then(|v| v.as_str())
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| Uuid::new_v4().to_string());
+                let function = tool_call.get("function").cloned().unwrap_or_default();
+                let name = function
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let args_value: serde_json::Value = function
+                    .get("arguments")
+                    .and_then(|v| v.as_str())
+                    .and_then(|raw| serde_json::from_str(raw).ok())
+                    .unwrap_or(serde_json::json!({}));
+
+                turn.tool_calls.push(name.clone());
+                // Replays never touch the workspace or the session's processes:
+                // everything but the read-only tools only reports what it would do
+                let dry_run = !llm::is_read_only(&name);
+                let tool_context = llm::ToolContext {
+                    work_dir: &work_dir,
+                    config_path: config_path.as_deref(),
+                    dry_run,
+                    env: &env,
+                    shell: None,
+                    policy: None,
+                    disabled_tools: &[],
+                    turn: None,
+                    hooks: None,
+                };
+                let output = llm::execute_tool(
+                    &window,
+                    &state,
+                    &session_id,
+                    &tool_call_id,
+                    &name,
+                    &args_value,
+                    &tool_context,
+                )
+                .await;
+                if dry_run {
+                    turn.skipped_tools.push(name.clone());
+                    if name != "Shell" && output.ok && !output.output.is_empty() {
+                        turn.diffs.push(output.output.clone());
+                    }
+                }
+
+                let tool_content = serde_json::json!({
+                    "ok": output.ok,
+                    "summary": output.summary,
+                    "output": output.output,
+                })
+                .to_string();
+                messages.push(serde_json::json!({
+                    "role": "tool",
+                    "tool_call_id": tool_call_id,
+                    "content": tool_content,
+                }));
+            }
+        }
+
+        if !finished && turn.error.is_none() {
+            turn.error = Some("Exceeded maximum tool steps".to_string());
+        }
+        turn.duration_ms = turn_started.elapsed().as_millis() as u64;
+
+        emit_replay(
+            &window,
+            "turn_end",
+            serde_json::json!({
+                "session_id": session_id,
+                "index": index,
+                "ok": turn.error.is_none(),
+            }),
+        );
+        report_turns.push(turn);
+    }
+
+    let prompt_tokens = report_turns.iter().map(|t| t.prompt_tokens).sum();
+    let completion_tokens = report_turns.iter().map(|t| t.completion_tokens).sum();
+
+    Ok(ReplayReport {
+        session_id,
+        model,
+        work_dir,
+        turns: report_turns,
+        prompt_tokens,
+        completion_tokens,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
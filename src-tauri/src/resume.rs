@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::AppState;
+
+/// Marker of a turn in progress, written when it starts and removed however
+/// it ends. One still on disk when the app starts belongs to a turn a crash
+/// or force-quit cut short. The messages and tool results the turn had
+/// produced are already in the session, so resuming it only needs to know
+/// where the loop was.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InterruptedTurn {
+    pub session_id: String,
+    pub work_dir: String,
+    pub model: String,
+    /// Start of the user message that began the turn
+    pub prompt: String,
+    pub started_at: i64,
+    /// Steps the turn had started; a resumed turn picks up at the last one
+    pub steps: usize,
+}
+
+fn store_dir() -> PathBuf {
+    crate::kimi_share_dir().join("gui_turns")
+}
+
+fn journal_path(session_id: &str) -> PathBuf {
+    store_dir().join(format!("{}.json", session_id))
+}
+
+fn save(turn: &InterruptedTurn) -> Result<(), String> {
+    let contents = serde_json::to_string(turn).map_err(|e| e.to_string())?;
+    crate::write_text(&journal_path(&turn.session_id), &contents)
+}
+
+pub fn load(session_id: &str) -> Option<InterruptedTurn> {
+    let contents = fs::read_to_string(journal_path(session_id)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn begin(session_id: &str, work_dir: &str, model: &str, prompt: &str) {
+    let _ = save(&InterruptedTurn {
+        session_id: session_id.to_string(),
+        work_dir: work_dir.to_string(),
+        model: model.to_string(),
+        prompt: crate::truncate_with_ellipsis(prompt.trim(), 80),
+        started_at: chrono::Utc::now().timestamp(),
+        steps: 0,
+    });
+}
+
+/// Record that step `step` (0-based) of the session's turn has started.
+pub fn step(session_id: &str, step: usize) {
+    if let Some(mut turn) = load(session_id) {
+        turn.steps = step;
+        let _ = save(&turn);
+    }
+}
+
+pub fn finish(session_id: &str) {
+    let _ = fs::remove_file(journal_path(session_id));
+}
+
+/// Turns left unfinished by an earlier run of the app, newest first. Turns
+/// streaming right now are not interrupted and are left out.
+#[tauri::command]
+pub fn interrupted_turns(state: tauri::State<'_, AppState>) -> Vec<InterruptedTurn> {
+    let streaming: Vec<String> = state
+        .sessions
+        .lock()
+        .map(|sessions| sessions.values().map(|handle| handle.session_id.clone()).collect())
+        .unwrap_or_default();
+    let mut turns: Vec<InterruptedTurn> = fs::read_dir(store_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let contents = fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str::<InterruptedTurn>(&contents).ok()
+        })
+        .filter(|turn| !streaming.contains(&turn.session_id))
+        .collect();
+    turns.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+    turns
+}
+
+/// Forget an interrupted turn without resuming it.
+#[tauri::command]
+pub fn interrupted_turn_discard(session_id: String) {
+    finish(&session_id);
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Scratch dirs untouched for this long are removed at startup
+pub const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+fn root() -> PathBuf {
+    crate::kimi_share_dir().join("gui_scratch")
+}
+
+/// Folder for a session's throwaway scripts and intermediate files, outside
+/// the work dir so they never end up in the repo.
+pub fn dir(session_id: &str) -> PathBuf {
+    let name: String = session_id
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    root().join(name)
+}
+
+/// Create the session's scratch dir and return its canonical path.
+pub fn ensure(session_id: &str) -> Result<PathBuf, String> {
+    let dir = dir(session_id);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create scratch dir {}: {}", dir.display(), e))?;
+    dir.canonicalize()
+        .map_err(|e| format!("Failed to resolve scratch dir: {}", e))
+}
+
+/// A file-editing call that only touches the session's scratch dir, which
+/// doesn't need approval.
+pub fn covers(session_id: &str, work_dir: &str, name: &str, args: &serde_json::Value) -> bool {
+    let paths = crate::tools::edit_paths(name, args);
+    let Ok(scratch) = dir(session_id).canonicalize() else {
+        return false;
+    };
+    !paths.is_empty()
+        && paths.iter().all(|path| {
+            crate::paths::resolve(work_dir, path, crate::paths::Access::Write)
+                .is_ok_and(|resolved| crate::paths::is_within(&scratch, &resolved.path))
+        })
+}
+
+/// System prompt paragraph telling the model where to put temporary files.
+pub fn prompt_section(scratch: &Path) -> String {
+    format!(
+        "\nScratch directory: {}\nPut temporary scripts, test inputs and other intermediate files here instead of the working directory. \
+         Writing files here doesn't need approval, and Shell commands can find it in $KIMI_SCRATCH_DIR. \
+         It is deleted after the session has been idle for a while, so don't keep anything there that the user needs.\n",
+        scratch.display()
+    )
+}
+
+pub fn remove(session_id: &str) {
+    let dir = dir(session_id);
+    if dir.exists() {
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
+
+/// Newest modification time under `path`. Symlinks are not followed, so a
+/// link cycle can't recurse forever.
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let mut latest = fs::symlink_metadata(path).and_then(|m| m.modified()).ok()?;
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        let modified = if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            last_modified(&entry.path())
+        } else {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        };
+        if let Some(modified) = modified {
+            latest = latest.max(modified);
+        }
+    }
+    Some(latest)
+}
+
+/// Delete scratch dirs nothing has been written to for `retention_days`.
+/// Zero keeps them forever.
+pub fn prune(retention_days: u64) {
+    if retention_days == 0 {
+        return;
+    }
+    let max_age = Duration::from_secs(retention_days * 24 * 60 * 60);
+    for entry in fs::read_dir(root()).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let expired = last_modified(&path)
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = fs::remove_dir_all(&path);
+        }
+    }
+}
+
+/// Path of the session's scratch dir, created on demand.
+#[tauri::command]
+pub fn scratch_dir(session_id: String) -> Result<String, String> {
+    Ok(ensure(&session_id)?.to_string_lossy().to_string())
+}
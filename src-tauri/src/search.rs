@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::AppState;
+
+const DEFAULT_LIMIT: usize = 30;
+const MATCHES_PER_SESSION: usize = 5;
+/// Characters of context kept on each side of the first hit
+const SNIPPET_CONTEXT: usize = 80;
+
+#[derive(Clone, Serialize)]
+pub struct MessageMatch {
+    /// Position of the message in the session transcript
+    pub index: usize,
+    pub role: String,
+    pub snippet: String,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SessionSearchResult {
+    pub session_id: String,
+    pub title: String,
+    pub work_dir: String,
+    /// "gui" | "cli"
+    pub source: String,
+    pub updated_at: i64,
+    /// Matching messages in the session, not only the ones listed in `matches`
+    pub match_count: usize,
+    pub matches: Vec<MessageMatch>,
+}
+
+/// Lowercased per character, so offsets line up with `text.chars()`.
+pub fn fold(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Snippet around the first term if every term occurs in `content`.
+pub fn match_snippet(content: &str, terms: &[Vec<char>]) -> Option<String> {
+    let folded = fold(content);
+    let mut first = None;
+    for term in terms {
+        let at = find(&folded, term)?;
+        first = Some(first.map_or(at, |f: usize| f.min(at)));
+    }
+    let at = first?;
+    let chars: Vec<char> = content.chars().collect();
+    let start = at.saturating_sub(SNIPPET_CONTEXT);
+    let end = (at + SNIPPET_CONTEXT * 2).min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+fn search_messages(messages: &[crate::Message], terms: &[Vec<char>]) -> (usize, Vec<MessageMatch>) {
+    let mut count = 0;
+    let mut matches = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        if message.content.is_empty() {
+            continue;
+        }
+        if let Some(snippet) = match_snippet(&message.content, terms) {
+            count += 1;
+            if matches.len() < MATCHES_PER_SESSION {
+                matches.push(MessageMatch {
+                    index,
+                    role: message.role.clone(),
+                    snippet,
+                    timestamp: message.timestamp,
+                });
+            }
+        }
+    }
+    (count, matches)
+}
+
+/// Work dirs kimi-cli has recorded sessions for.
+fn cli_work_dirs() -> Vec<String> {
+    crate::read_text(&crate::metadata_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok())
+        .and_then(|data| data.get("work_dirs").and_then(|v| v.as_array()).cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|wd| wd.get("path").and_then(|v| v.as_str()))
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Case-insensitive search of message content across GUI sessions and CLI
+/// wire.jsonl sessions, optionally limited to one work dir (and its other
+/// worktrees). Every whitespace-separated term must occur in the message.
+#[tauri::command]
+pub fn session_search(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    work_dir: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionSearchResult>, String> {
+    let terms: Vec<Vec<char>> = query.split_whitespace().map(fold).collect();
+    if terms.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let target = work_dir
+        .as_deref()
+        .and_then(|wd| crate::git::repo_identity(std::path::Path::new(wd)));
+    let in_scope = |candidate: &str| {
+        work_dir
+            .as_deref()
+            .is_none_or(|wd| crate::same_workspace(candidate, wd, target.as_ref()))
+    };
+
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    let mut manager = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+
+    for session in manager.load_all_sessions()? {
+        if !in_scope(&session.work_dir) {
+            continue;
+        }
+        seen.insert(session.id.clone());
+        let (match_count, matches) = search_messages(&session.messages, &terms);
+        if match_count > 0 {
+            results.push(SessionSearchResult {
+                session_id: session.id,
+                title: session.title,
+                work_dir: session.work_dir,
+                source: "gui".to_string(),
+                updated_at: session.updated_at,
+                match_count,
+                matches,
+            });
+        }
+    }
+
+    let roots = match &work_dir {
+        Some(wd) => vec![wd.clone()],
+        None => cli_work_dirs(),
+    };
+    for root in roots {
+        for info in crate::load_sessions(&root)? {
+            if !seen.insert(info.id.clone()) {
+                continue;
+            }
+            let Ok(messages) = manager.load_messages(&info.work_dir, &info.id) else {
+                continue;
+            };
+            let updated_at = info.updated_at as i64;
+            let (match_count, matches) = search_messages(&messages, &terms);
+            if match_count > 0 {
+                results.push(SessionSearchResult {
+                    session_id: info.id,
+                    title: info.title,
+                    work_dir: info.work_dir,
+                    source: "cli".to_string(),
+                    updated_at,
+                    match_count,
+                    matches,
+                });
+            }
+        }
+    }
+    drop(manager);
+
+    results.sort_by(|a, b| {
+        b.match_count
+            .cmp(&a.match_count)
+            .then(b.updated_at.cmp(&a.updated_at))
+    });
+    results.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(results)
+}
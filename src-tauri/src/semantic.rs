@@ -0,0 +1,477 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::Emitter;
+
+use crate::context;
+use crate::http::{self, AuthScheme, Credentials};
+use crate::tools::ToolOutput;
+
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP: usize = 8;
+const MAX_CHUNK_CHARS: usize = 2_000;
+const EMBED_BATCH: usize = 32;
+const DEFAULT_LIMIT: usize = 8;
+const MAX_LIMIT: usize = 50;
+const PROGRESS_EVERY: usize = 10;
+
+/// OpenAI-compatible embeddings endpoint. Semantic search is off until `model`
+/// and either `base_url` or a named `credential` are set; the chat provider's
+/// credentials are never sent to it. `base_url` overrides the credential's.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct EmbeddingsSettings {
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub credential: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Chunk {
+    start_line: usize,
+    end_line: usize,
+    text: String,
+    vector: Vec<f32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    modified: u64,
+    size: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SemanticIndex {
+    model: String,
+    files: HashMap<String, IndexedFile>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct IndexStats {
+    pub files: usize,
+    pub chunks: usize,
+    /// Files embedded by this refresh because they were new or changed
+    pub updated: usize,
+    pub removed: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f32,
+    pub text: String,
+}
+
+/// Refreshes of the same index must not interleave their writes
+static REFRESH: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+/// Work dirs with a background refresh running, and whether a change arrived
+/// while it ran
+static INDEXING: std::sync::Mutex<Vec<(String, bool)>> = std::sync::Mutex::new(Vec::new());
+
+const NOT_CONFIGURED: &str =
+    "Semantic search needs an embeddings model and an embeddings API base or named credential in settings";
+
+fn load_settings() -> Option<EmbeddingsSettings> {
+    let set = |value: &Option<String>| value.as_deref().is_some_and(|v| !v.is_empty());
+    crate::gui_settings_load(None)
+        .ok()?
+        .settings
+        .embeddings
+        .filter(|settings| set(&settings.model) && (set(&settings.base_url) || set(&settings.credential)))
+}
+
+pub fn is_configured() -> bool {
+    load_settings().is_some()
+}
+
+fn index_path(work_dir: &str) -> PathBuf {
+    let canonical = Path::new(work_dir)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(work_dir));
+    let hash: String = Sha256::digest(canonical.to_string_lossy().as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    crate::kimi_share_dir()
+        .join("gui_semantic")
+        .join(format!("{}.json", hash))
+}
+
+fn load_index(work_dir: &str) -> SemanticIndex {
+    crate::read_text(&index_path(work_dir))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(work_dir: &str, index: &SemanticIndex) -> Result<(), String> {
+    let raw = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    // Searches read the index while a refresh runs, so never expose a partial file
+    let path = index_path(work_dir);
+    let partial = path.with_extension("json.partial");
+    crate::write_text(&partial, &raw)?;
+    std::fs::rename(&partial, &path).map_err(|e| format!("Failed to write semantic index: {}", e))
+}
+
+async fn credentials(settings: &EmbeddingsSettings) -> Result<Credentials, String> {
+    let base = settings.base_url.as_deref().filter(|b| !b.is_empty());
+    if let Some(name) = settings.credential.as_deref().filter(|n| !n.is_empty()) {
+        let mut credentials = crate::credentials::resolve(name).await?;
+        if let Some(base) = base {
+            credentials.base = base.trim_end_matches('/').to_string();
+        }
+        return Ok(credentials);
+    }
+    let base = base.ok_or_else(|| NOT_CONFIGURED.to_string())?;
+    Ok(Credentials {
+        token: settings.api_key.clone().unwrap_or_default(),
+        base: base.trim_end_matches('/').to_string(),
+        scheme: AuthScheme::Bearer,
+        headers: Vec::new(),
+    })
+}
+
+async fn embed(
+    client: &reqwest::Client,
+    credentials: &Credentials,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    crate::network::ensure_online(&credentials.base)?;
+    let url = format!("{}/embeddings", credentials.base);
+    let body = serde_json::json!({ "model": model, "input": inputs });
+    let response = http::request(client, credentials, reqwest::Method::POST, &url, Some(&body))?
+        .send()
+        .await
+        .map_err(|e| format!("Embeddings request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Embeddings API error {}: {}", status, text));
+    }
+    let value: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse embeddings response: {}", e))?;
+    // Embeddings belong to the project, not a session; they count towards the day
+    crate::cost::record(None, model, &crate::cost::prices(), value.get("usage"), &body, 0);
+
+    let mut data = value["data"].as_array().cloned().unwrap_or_default();
+    data.sort_by_key(|item| item["index"].as_u64().unwrap_or(0));
+    let vectors: Vec<Vec<f32>> = data
+        .iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    if vectors.len() != inputs.len() {
+        return Err(format!(
+            "Embeddings API returned {} vectors for {} inputs",
+            vectors.len(),
+            inputs.len()
+        ));
+    }
+    Ok(vectors)
+}
+
+/// Overlapping line windows of `content`, as `(start_line, end_line, text)`.
+fn chunk_lines(rel: &str, content: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let body = lines[start..end].join("\n");
+        if !body.trim().is_empty() {
+            // The path helps queries like "where is the oauth flow" match the right file
+            let text = crate::truncate_with_ellipsis(&format!("{}\n{}", rel, body), MAX_CHUNK_CHARS);
+            chunks.push((start + 1, end, text));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+fn modified_secs(file: &context::ProjectFile) -> u64 {
+    file.modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Bring the index for `work_dir` up to date, embedding only files whose size
+/// or modification time changed since the last refresh. `progress` gets the
+/// files embedded so far and the number that need it.
+async fn refresh(
+    work_dir: &str,
+    settings: &EmbeddingsSettings,
+    progress: impl Fn(usize, usize),
+) -> Result<IndexStats, String> {
+    let credentials = credentials(settings).await?;
+    let client = http::client(&credentials)?;
+    let model = settings.model.clone().unwrap_or_default();
+    let _guard = REFRESH.lock().await;
+    let mut index = load_index(work_dir);
+    if index.model != model {
+        index = SemanticIndex {
+            model: model.clone(),
+            files: HashMap::new(),
+        };
+    }
+
+    let files = context::project_files(Path::new(work_dir), &[])?;
+    let before = index.files.len();
+    index
+        .files
+        .retain(|rel, _| files.iter().any(|file| &file.rel == rel));
+    let removed = before - index.files.len();
+
+    let changed: Vec<&context::ProjectFile> = files
+        .iter()
+        .filter(|file| {
+            !index
+                .files
+                .get(&file.rel)
+                .is_some_and(|indexed| indexed.modified == modified_secs(file) && indexed.size == file.size)
+        })
+        .collect();
+    let mut updated = 0;
+    for (done, file) in changed.iter().enumerate() {
+        if done % PROGRESS_EVERY == 0 {
+            progress(done, changed.len());
+        }
+        let modified = modified_secs(file);
+        let Some(content) = context::read_text_file(&file.path) else {
+            continue;
+        };
+        let pieces = chunk_lines(&file.rel, &content);
+        let mut chunks = Vec::with_capacity(pieces.len());
+        for batch in pieces.chunks(EMBED_BATCH) {
+            let texts: Vec<String> = batch.iter().map(|(_, _, text)| text.clone()).collect();
+            let vectors = embed(&client, &credentials, &model, &texts).await?;
+            chunks.extend(batch.iter().cloned().zip(vectors).map(
+                |((start_line, end_line, text), vector)| Chunk {
+                    start_line,
+                    end_line,
+                    text,
+                    vector,
+                },
+            ));
+        }
+        index.files.insert(
+            file.rel.clone(),
+            IndexedFile {
+                modified,
+                size: file.size,
+                chunks,
+            },
+        );
+        updated += 1;
+        // Keep progress if a later file fails to embed
+        if updated % 50 == 0 {
+            save_index(work_dir, &index)?;
+        }
+    }
+    if updated > 0 || removed > 0 {
+        save_index(work_dir, &index)?;
+    }
+
+    Ok(IndexStats {
+        files: index.files.len(),
+        chunks: index.files.values().map(|file| file.chunks.len()).sum(),
+        updated,
+        removed,
+    })
+}
+
+fn emit_progress(window: &tauri::Window, work_dir: &str, mut data: serde_json::Value) {
+    data["work_dir"] = serde_json::json!(work_dir);
+    let _ = window.emit("semantic://progress", data);
+}
+
+/// Refresh the index of `work_dir` on a background task, emitting
+/// `semantic://progress` as files are embedded and once with `done` set.
+/// A call while a refresh runs queues one more pass for the newer changes.
+pub fn index_in_background(window: &tauri::Window, work_dir: &str) {
+    if load_settings().is_none() {
+        return;
+    }
+    {
+        let mut running = INDEXING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = running.iter_mut().find(|(dir, _)| dir == work_dir) {
+            entry.1 = true;
+            return;
+        }
+        running.push((work_dir.to_string(), false));
+    }
+    let window = window.clone();
+    let work_dir = work_dir.to_string();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let result = match load_settings() {
+                Some(settings) => {
+                    refresh(&work_dir, &settings, |embedded, total| {
+                        emit_progress(
+                            &window,
+                            &work_dir,
+                            serde_json::json!({ "embedded": embedded, "total": total, "done": false }),
+                        )
+                    })
+                    .await
+                }
+                None => Err(NOT_CONFIGURED.to_string()),
+            };
+            let again = {
+                let mut running = INDEXING.lock().unwrap_or_else(|e| e.into_inner());
+                match running.iter_mut().find(|(dir, _)| *dir == work_dir) {
+                    Some(entry) if entry.1 && result.is_ok() => {
+                        entry.1 = false;
+                        true
+                    }
+                    _ => {
+                        running.retain(|(dir, _)| *dir != work_dir);
+                        false
+                    }
+                }
+            };
+            if again {
+                continue;
+            }
+            let data = match result {
+                Ok(stats) => serde_json::json!({ "done": true, "stats": stats }),
+                Err(error) => serde_json::json!({ "done": true, "error": error }),
+            };
+            emit_progress(&window, &work_dir, data);
+            break;
+        }
+    });
+}
+
+fn is_indexing(work_dir: &str) -> bool {
+    INDEXING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|(dir, _)| dir == work_dir)
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+/// Search the index as it stands; files changed since the last refresh are
+/// re-embedded in the background for later searches.
+async fn search(
+    window: &tauri::Window,
+    work_dir: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    let settings = load_settings().ok_or_else(|| NOT_CONFIGURED.to_string())?;
+    let credentials = credentials(&settings).await?;
+    let client = http::client(&credentials)?;
+    index_in_background(window, work_dir);
+
+    let model = settings.model.unwrap_or_default();
+    let index = load_index(work_dir);
+    if index.model != model || (index.files.is_empty() && is_indexing(work_dir)) {
+        return Err(
+            "The semantic index for this folder is being built in the background. Use Grep or FindSymbol for now."
+                .to_string(),
+        );
+    }
+    let query_vector = embed(&client, &credentials, &model, &[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    let mut hits: Vec<SearchHit> = index
+        .files
+        .iter()
+        .flat_map(|(rel, file)| {
+            file.chunks.iter().map(|chunk| SearchHit {
+                path: rel.clone(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                score: cosine(&query_vector, &chunk.vector),
+                text: chunk.text.clone(),
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit.clamp(1, MAX_LIMIT));
+    Ok(hits)
+}
+
+/// `SemanticSearch` tool call.
+pub async fn search_tool(window: &tauri::Window, work_dir: &str, query: &str, limit: usize) -> ToolOutput {
+    match search(window, work_dir, query, limit).await {
+        Ok(hits) if hits.is_empty() => ToolOutput {
+            ok: true,
+            summary: "No indexed files in the working directory.".to_string(),
+            output: String::new(),
+        },
+        Ok(hits) => {
+            let text = hits
+                .iter()
+                .map(|hit| {
+                    format!(
+                        "{}:{}-{} (score {:.3})\n{}",
+                        hit.path, hit.start_line, hit.end_line, hit.score, hit.text
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let (output, truncated) = crate::tools::truncate_output(&text);
+            ToolOutput {
+                ok: true,
+                summary: crate::tools::append_truncation(format!("Found {} matching chunks.", hits.len()), truncated),
+                output,
+            }
+        }
+        Err(err) => ToolOutput {
+            ok: false,
+            summary: err,
+            output: String::new(),
+        },
+    }
+}
+
+/// Start building or updating the semantic index for `work_dir`; progress
+/// arrives as `semantic://progress` events.
+#[tauri::command]
+pub async fn semantic_index(window: tauri::Window, work_dir: String) -> Result<(), String> {
+    let settings = load_settings().ok_or_else(|| NOT_CONFIGURED.to_string())?;
+    credentials(&settings).await?;
+    index_in_background(&window, &work_dir);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn semantic_search(
+    window: tauri::Window,
+    work_dir: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    search(&window, &work_dir, &query, limit.unwrap_or(DEFAULT_LIMIT)).await
+}
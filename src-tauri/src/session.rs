@@ -1,14 +1,37 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use rusqlite::{params, Connection, OptionalExtension};
 use std::path::PathBuf;
 
+use crate::parts::{parse_parts, MessagePart};
+use crate::wire;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
     pub timestamp: i64,
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Typed view of `content`; filled in on load for messages saved without it
+    #[serde(default)]
+    pub parts: Vec<MessagePart>,
+    /// Set on `tool` messages: the call this result answers
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: &str, content: String) -> Self {
+        Self {
+            role: role.to_string(),
+            parts: parse_parts(&content),
+            content,
+            timestamp: chrono::Utc::now().timestamp(),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -31,8 +54,10 @@ pub struct Session {
 pub struct SessionManager {
     pub sessions: HashMap<String, Session>,
     data_dir: PathBuf,
+    db: Connection,
 }
 
+/// `{id}.json` written before sessions moved into SQLite; read by the migration.
 #[derive(Clone, Serialize, Deserialize)]
 struct SessionData {
     pub id: String,
@@ -42,6 +67,90 @@ struct SessionData {
     pub updated_at: i64,
 }
 
+/// Session metadata for list views, read without loading transcripts.
+#[derive(Clone)]
+pub struct SessionSummary {
+    pub id: String,
+    pub title: String,
+    pub work_dir: String,
+    pub updated_at: i64,
+    pub message_count: usize,
+    pub last_message_preview: Option<String>,
+}
+
+/// User-assigned decoration and manual position of a session in the list.
+/// Kept apart from `sessions` so CLI sessions can be labelled too.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SessionLabel {
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    /// Place in the manual order; unordered sessions follow, newest first
+    pub position: Option<i64>,
+}
+
+const PREVIEW_CHARS: usize = 120;
+/// Bumped when the schema changes; 1 means the JSON files have been imported
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        work_dir TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        message_count INTEGER NOT NULL DEFAULT 0,
+        last_message_preview TEXT
+    );
+    CREATE INDEX IF NOT EXISTS sessions_by_work_dir ON sessions (work_dir, updated_at DESC);
+    CREATE INDEX IF NOT EXISTS sessions_by_updated ON sessions (updated_at DESC);
+    CREATE TABLE IF NOT EXISTS messages (
+        session_id TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (session_id, seq)
+    );
+    CREATE TABLE IF NOT EXISTS session_labels (
+        session_id TEXT PRIMARY KEY,
+        color TEXT,
+        emoji TEXT,
+        position INTEGER
+    );
+";
+
+pub fn message_preview(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .find(|message| message.role != "tool" && !message.content.trim().is_empty())
+        .map(|message| crate::truncate_with_ellipsis(message.content.trim(), PREVIEW_CHARS))
+}
+
+fn db_error(error: rusqlite::Error) -> String {
+    format!("Session database error: {}", error)
+}
+
+fn decode_message(data: &str) -> Option<Message> {
+    let mut message = serde_json::from_str::<Message>(data).ok()?;
+    if message.parts.is_empty() {
+        message.parts = parse_parts(&message.content);
+    }
+    Some(message)
+}
+
+fn summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<SessionSummary> {
+    Ok(SessionSummary {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        work_dir: row.get(2)?,
+        updated_at: row.get(3)?,
+        message_count: row.get::<_, i64>(4)? as usize,
+        last_message_preview: row.get(5)?,
+    })
+}
+
 #[derive(Clone, Serialize)]
 pub struct ConfigPayload {
     pub path: String,
@@ -65,30 +174,95 @@ impl SessionManager {
         
         // Ensure directory exists
         fs::create_dir_all(&data_dir).ok();
-        
-        Self {
+
+        // An unopenable database shouldn't stop the app; sessions then last
+        // until it quits
+        let db = Connection::open(data_dir.join("sessions.db"))
+            .or_else(|_| Connection::open_in_memory())
+            .expect("in-memory SQLite database");
+        let _ = db.pragma_update(None, "journal_mode", "WAL");
+        let manager = Self {
             sessions: HashMap::new(),
             data_dir,
-        }
+            db,
+        };
+        let _ = manager.migrate();
+        manager
     }
-    
-    fn session_file_path(&self, session_id: &str) -> PathBuf {
-        self.data_dir.join(format!("{}.json", session_id))
+
+    /// Create the schema and, once, import the `{id}.json` and
+    /// `{id}_messages.jsonl` files older versions wrote. The files are left in
+    /// place.
+    fn migrate(&self) -> Result<(), String> {
+        self.db.execute_batch(SCHEMA).map_err(db_error)?;
+        let version: i64 = self
+            .db
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(db_error)?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        let tx = self.db.unchecked_transaction().map_err(db_error)?;
+        for entry in fs::read_dir(&self.data_dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(data) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<SessionData>(&content).ok())
+            else {
+                continue;
+            };
+            let messages: Vec<Message> =
+                fs::read_to_string(self.data_dir.join(format!("{}_messages.jsonl", data.id)))
+                    .unwrap_or_default()
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(decode_message)
+                    .collect();
+            let session = Session {
+                id: data.id,
+                title: data.title,
+                work_dir: data.work_dir,
+                messages,
+                created_at: data.created_at,
+                updated_at: data.updated_at,
+            };
+            self.save_session(&session)?;
+            for message in &session.messages {
+                self.save_message(&session.id, message)?;
+            }
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(db_error)?;
+        tx.commit().map_err(db_error)
     }
     
     pub fn save_session(&self, session: &Session) -> Result<(), String> {
-        let path = self.session_file_path(&session.id);
-        let data = SessionData {
-            id: session.id.clone(),
-            title: session.title.clone(),
-            work_dir: session.work_dir.clone(),
-            created_at: session.created_at,
-            updated_at: session.updated_at,
-        };
-        let json = serde_json::to_string_pretty(&data)
-            .map_err(|e| format!("Failed to serialize session: {}", e))?;
-        fs::write(&path, json)
-            .map_err(|e| format!("Failed to write session file: {}", e))?;
+        let _span = tracing::info_span!("save_session").entered();
+        self.db
+            .execute(
+                "INSERT INTO sessions (id, title, work_dir, created_at, updated_at, message_count, last_message_preview)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (id) DO UPDATE SET
+                     title = excluded.title,
+                     work_dir = excluded.work_dir,
+                     updated_at = excluded.updated_at,
+                     message_count = excluded.message_count,
+                     last_message_preview = excluded.last_message_preview",
+                params![
+                    session.id,
+                    session.title,
+                    session.work_dir,
+                    session.created_at,
+                    session.updated_at,
+                    session.messages.len() as i64,
+                    message_preview(&session.messages),
+                ],
+            )
+            .map_err(db_error)?;
         Ok(())
     }
     
@@ -101,38 +275,57 @@ impl SessionManager {
         }
         Ok(())
     }
+
+    fn session_messages(&self, session_id: &str) -> Result<Vec<Message>, String> {
+        self.message_page(session_id, 0, usize::MAX)
+    }
+
+    /// `limit` messages of a session starting at `offset`, oldest first.
+    pub fn message_page(&self, session_id: &str, offset: usize, limit: usize) -> Result<Vec<Message>, String> {
+        let mut stmt = self
+            .db
+            .prepare_cached(
+                "SELECT data FROM messages WHERE session_id = ?1 ORDER BY seq LIMIT ?2 OFFSET ?3",
+            )
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map(
+                params![session_id, limit.min(i64::MAX as usize) as i64, offset as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .map_err(db_error)?;
+        Ok(rows.flatten().filter_map(|data| decode_message(&data)).collect())
+    }
+
+    fn session_from_row(&self, row: (String, String, String, i64, i64)) -> Result<Session, String> {
+        let (id, title, work_dir, created_at, updated_at) = row;
+        Ok(Session {
+            messages: self.session_messages(&id)?,
+            id,
+            title,
+            work_dir,
+            created_at,
+            updated_at,
+        })
+    }
     
     pub fn load_all_sessions(&mut self) -> Result<Vec<Session>, String> {
-        let mut sessions = Vec::new();
-
-        if let Ok(entries) = fs::read_dir(&self.data_dir) {
-            let entries: Vec<_> = entries.flatten().collect();
-            for entry in entries {
-                let path = entry.path();
-                if path.extension().and_then(|e| e.to_str()) != Some("json") {
-                    continue;
-                }
-                if let Ok(content) = fs::read_to_string(&path) {
-                    if let Ok(data) = serde_json::from_str::<SessionData>(&content) {
-                        // Load messages from separate messages file
-                        let messages_path = self.data_dir.join(format!("{}_messages.jsonl", data.id));
-                        let messages = if messages_path.exists() {
-                            Self::load_messages_from_file(&messages_path).unwrap_or_else(|_| Vec::new())
-                        } else {
-                            Vec::new()
-                        };
-                        
-                        sessions.push(Session {
-                            id: data.id,
-                            title: data.title,
-                            work_dir: data.work_dir,
-                            messages,
-                            created_at: data.created_at,
-                            updated_at: data.updated_at,
-                        });
-                    } else {
-                    }
-                }
+        let rows: Vec<(String, String, String, i64, i64)> = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT id, title, work_dir, created_at, updated_at FROM sessions")
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+                .map_err(db_error)?;
+            rows.flatten().collect()
+        };
+        let mut sessions = Vec::with_capacity(rows.len());
+        for row in rows {
+            // Sessions in memory may be ahead of the database mid-turn
+            match self.sessions.get(&row.0) {
+                Some(session) => sessions.push(session.clone()),
+                None => sessions.push(self.session_from_row(row)?),
             }
         }
 
@@ -144,50 +337,165 @@ impl SessionManager {
         Ok(sessions)
     }
     
-    fn load_messages_from_file(path: &PathBuf) -> Result<Vec<Message>, String> {
-        let mut messages = Vec::new();
-        if let Ok(content) = fs::read_to_string(path) {
-            for line in content.lines() {
-                if line.trim().is_empty() {
-                    continue;
-                }
-                if let Ok(msg) = serde_json::from_str::<Message>(line) {
-                    messages.push(msg);
-                }
+    /// Session metadata, newest first, without loading transcripts. Callers
+    /// match the work dir themselves, since equal folders can be spelled
+    /// differently (symlinks, case-insensitive file systems).
+    pub fn list_summaries(&self) -> Vec<SessionSummary> {
+        let mut summaries: Vec<SessionSummary> = self
+            .db
+            .prepare_cached(
+                "SELECT id, title, work_dir, updated_at, message_count, last_message_preview
+                 FROM sessions ORDER BY updated_at DESC",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], summary_from_row)
+                    .map(|rows| rows.flatten().collect())
+            })
+            .unwrap_or_default();
+        for summary in summaries.iter_mut() {
+            if let Some(session) = self.sessions.get(&summary.id) {
+                summary.message_count = session.messages.len();
+                summary.last_message_preview = message_preview(&session.messages);
             }
         }
-        Ok(messages)
+        summaries
+    }
+
+    /// Labels of every session that has one, by session id.
+    pub fn labels(&self) -> HashMap<String, SessionLabel> {
+        self.db
+            .prepare_cached("SELECT session_id, color, emoji, position FROM session_labels")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        SessionLabel {
+                            color: row.get(1)?,
+                            emoji: row.get(2)?,
+                            position: row.get(3)?,
+                        },
+                    ))
+                })
+                .map(|rows| rows.flatten().collect())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set or clear a session's color and emoji, keeping its position.
+    pub fn set_label(
+        &self,
+        session_id: &str,
+        color: Option<&str>,
+        emoji: Option<&str>,
+    ) -> Result<(), String> {
+        self.db
+            .execute(
+                "INSERT INTO session_labels (session_id, color, emoji) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (session_id) DO UPDATE SET color = excluded.color, emoji = excluded.emoji",
+                params![session_id, color, emoji],
+            )
+            .map_err(db_error)?;
+        self.prune_labels()
+    }
+
+    /// Number `session_ids` in that order and take `unordered` out of the
+    /// manual order. Other sessions keep their positions, so ordering one work
+    /// dir's list leaves the others alone.
+    pub fn set_order(&self, session_ids: &[String], unordered: &[String]) -> Result<(), String> {
+        let tx = self.db.unchecked_transaction().map_err(db_error)?;
+        for session_id in unordered {
+            tx.execute(
+                "UPDATE session_labels SET position = NULL WHERE session_id = ?1",
+                params![session_id],
+            )
+            .map_err(db_error)?;
+        }
+        for (position, session_id) in session_ids.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO session_labels (session_id, position) VALUES (?1, ?2)
+                 ON CONFLICT (session_id) DO UPDATE SET position = excluded.position",
+                params![session_id, position as i64],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)?;
+        self.prune_labels()
+    }
+
+    /// Drop rows with nothing left in them.
+    fn prune_labels(&self) -> Result<(), String> {
+        self.db
+            .execute(
+                "DELETE FROM session_labels WHERE color IS NULL AND emoji IS NULL AND position IS NULL",
+                [],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Look up a GUI session in memory, falling back to the database.
+    pub fn find_session(&mut self, session_id: &str) -> Option<Session> {
+        if let Some(session) = self.sessions.get(session_id) {
+            return Some(session.clone());
+        }
+        let row = self
+            .db
+            .query_row(
+                "SELECT id, title, work_dir, created_at, updated_at FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .optional()
+            .ok()??;
+        let session = self.session_from_row(row).ok()?;
+        self.sessions.insert(session.id.clone(), session.clone());
+        Some(session)
     }
     
     pub fn save_message(&self, session_id: &str, message: &Message) -> Result<(), String> {
-        let messages_path = self.data_dir.join(format!("{}_messages.jsonl", session_id));
-        let line = serde_json::to_string(message)
+        let _span = tracing::info_span!("save_message").entered();
+        let data = serde_json::to_string(message)
             .map_err(|e| format!("Failed to serialize message: {}", e))?;
-        let mut file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&messages_path)
-            .map_err(|e| format!("Failed to open messages file: {}", e))?;
-        use std::io::Write;
-        writeln!(file, "{}", line)
-            .map_err(|e| format!("Failed to write message: {}", e))?;
+        self.db
+            .execute(
+                "INSERT INTO messages (session_id, seq, role, timestamp, data)
+                 VALUES (?1, (SELECT COALESCE(MAX(seq), -1) + 1 FROM messages WHERE session_id = ?1), ?2, ?3, ?4)",
+                params![session_id, message.role, message.timestamp, data],
+            )
+            .map_err(db_error)?;
         Ok(())
     }
 
+    /// Path of a per-session sidecar file such as `{id}_outline.json`.
+    pub fn sidecar_path(&self, session_id: &str, suffix: &str) -> PathBuf {
+        self.data_dir.join(format!("{}_{}", session_id, suffix))
+    }
+
+    /// Append to the database and the in-memory session.
+    pub fn append_message(&mut self, session_id: &str, message: Message) -> Result<(), String> {
+        self.save_message(session_id, &message)?;
+        self.add_message(session_id, message)
+    }
+
     pub fn delete_session(&mut self, work_dir: &str, session_id: &str) -> Result<(), String> {
         self.sessions.remove(session_id);
 
-        let session_path = self.session_file_path(session_id);
-        if session_path.exists() {
-            fs::remove_file(&session_path)
-                .map_err(|e| format!("Failed to delete session file: {}", e))?;
+        for suffix in ["outline.json", "suggestions.json"] {
+            let path = self.sidecar_path(session_id, suffix);
+            if path.exists() {
+                let _ = fs::remove_file(&path);
+            }
         }
 
-        let messages_path = self.data_dir.join(format!("{}_messages.jsonl", session_id));
-        if messages_path.exists() {
-            fs::remove_file(&messages_path)
-                .map_err(|e| format!("Failed to delete session messages: {}", e))?;
-        }
+        self.db
+            .execute("DELETE FROM messages WHERE session_id = ?1", params![session_id])
+            .map_err(db_error)?;
+        self.db
+            .execute("DELETE FROM sessions WHERE id = ?1", params![session_id])
+            .map_err(db_error)?;
+        self.db
+            .execute("DELETE FROM session_labels WHERE session_id = ?1", params![session_id])
+            .map_err(db_error)?;
 
         let session_dir = self.get_session_dir(work_dir, session_id)?;
         if session_dir.exists() {
@@ -198,6 +506,41 @@ impl SessionManager {
         Ok(())
     }
     
+    /// Continue a kimi-cli session in the GUI: its wire.jsonl transcript becomes
+    /// the start of a GUI session with the same id. Turns the CLI adds later are
+    /// not picked up; the two transcripts fork here.
+    pub fn resume_cli_session(&mut self, session_id: &str, work_dir: &str) -> Result<Option<Session>, String> {
+        let messages = self.load_messages(work_dir, session_id)?;
+        let Some(first) = messages.iter().find(|m| m.role == "user") else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().timestamp();
+        let session = Session {
+            id: session_id.to_string(),
+            title: crate::truncate_with_ellipsis(first.content.trim(), 50),
+            work_dir: work_dir.to_string(),
+            created_at: messages.first().map(|m| m.timestamp).unwrap_or(now),
+            updated_at: now,
+            messages,
+        };
+        for message in &session.messages {
+            self.save_message(session_id, message)?;
+        }
+        self.save_session(&session)?;
+        self.sessions.insert(session_id.to_string(), session.clone());
+        Ok(Some(session))
+    }
+
+    /// Store a session brought in from elsewhere, messages and all.
+    pub fn import_session(&mut self, session: Session) -> Result<(), String> {
+        for message in &session.messages {
+            self.save_message(&session.id, message)?;
+        }
+        self.save_session(&session)?;
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
     pub fn get_or_create_session(&mut self, session_id: &str, title: &str, work_dir: &str) -> Session {
         if let Some(session) = self.sessions.get(session_id) {
             return session.clone();
@@ -219,8 +562,7 @@ impl SessionManager {
     }
     
     pub fn load_messages(&self, work_dir: &str, session_id: &str) -> Result<Vec<Message>, String> {
-        let session_dir = self.get_session_dir(work_dir, session_id)?;
-        let wire_file = session_dir.join("wire.jsonl");
+        let wire_file = self.wire_path(work_dir, session_id);
 
         if !wire_file.exists() {
             return Ok(Vec::new());
@@ -228,114 +570,33 @@ impl SessionManager {
 
         let content = fs::read_to_string(&wire_file)
             .map_err(|e| format!("Failed to read wire file: {}", e))?;
+        let modified = wire_file
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp());
 
-        let mut messages = Vec::new();
-        let mut current_content = String::new();
-        let mut current_role: Option<String> = None;
-
-        for line in content.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-
-            if let Ok(record) = serde_json::from_str::<serde_json::Value>(line) {
-                // Handle nested message format: {"message": {"type": "...", "payload": {...}}}
-                let msg_type = record.get("message")
-                    .and_then(|m| m.get("type"))
-                    .and_then(|v| v.as_str());
-
-                match msg_type {
-                    Some("TurnBegin") => {
-                        // Flush any previous assistant content
-                        if let Some(role) = &current_role {
-                            if !current_content.is_empty() {
-                                messages.push(Message {
-                                    role: role.clone(),
-                                    content: current_content.clone(),
-                                    timestamp: chrono::Utc::now().timestamp(),
-                                    tool_calls: None,
-                                });
-                            }
-                        }
-
-                        // Extract user message from payload.user_input array
-                        let user_text = record.get("message")
-                            .and_then(|m| m.get("payload"))
-                            .and_then(|p| p.get("user_input"))
-                            .and_then(|u| u.as_array())
-                            .and_then(|arr| {
-                                arr.iter()
-                                    .find_map(|item| item.get("text").and_then(|t| t.as_str()))
-                            })
-                            .unwrap_or("")
-                            .to_string();
-
-                        if !user_text.is_empty() {
-                            messages.push(Message {
-                                role: "user".to_string(),
-                                content: user_text,
-                                timestamp: chrono::Utc::now().timestamp(),
-                                tool_calls: None,
-                            });
-                        }
-
-                        // Switch to assistant for subsequent content
-                        current_role = Some("assistant".to_string());
-                        current_content = String::new();
-                    }
-                    Some("ContentPart") => {
-                        if current_role.as_deref() == Some("assistant") {
-                            // Check payload.type to see if it's "text" (not "think" or other types)
-                            let part_type = record.get("message")
-                                .and_then(|m| m.get("payload"))
-                                .and_then(|p| p.get("type"))
-                                .and_then(|t| t.as_str());
-
-                            if part_type == Some("text") {
-                                if let Some(text) = record.get("message")
-                                    .and_then(|m| m.get("payload"))
-                                    .and_then(|p| p.get("text"))
-                                    .and_then(|t| t.as_str())
-                                {
-                                    current_content.push_str(text);
-                                }
-                            }
-                        }
-                    }
-                    Some("ToolCall") => {
-                        // Handle tool calls if present
-                    }
-                    Some("StepEnd") | Some("TurnEnd") => {
-                        if let Some(role) = &current_role {
-                            if role == "assistant" && !current_content.is_empty() {
-                                messages.push(Message {
-                                    role: "assistant".to_string(),
-                                    content: current_content.clone(),
-                                    timestamp: chrono::Utc::now().timestamp(),
-                                    tool_calls: None,
-                                });
-                                current_content = String::new();
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        // Flush any remaining assistant content
-        if current_role.as_deref() == Some("assistant") && !current_content.is_empty() {
-            messages.push(Message {
-                role: "assistant".to_string(),
-                content: current_content,
-                timestamp: chrono::Utc::now().timestamp(),
-                tool_calls: None,
-            });
+        let parsed = wire::parse(&content);
+        let messages = wire::transcript(&parsed.records, modified);
+        if messages.is_empty() && (!parsed.records.is_empty() || parsed.unparsed_lines > 0) {
+            let report = wire::compat_report(session_id, &parsed, 0);
+            return Err(format!(
+                "Could not read this CLI session: {}",
+                report.warnings.join(" ")
+            ));
         }
-
         Ok(messages)
     }
-    
+
+    /// The kimi-cli wire.jsonl of a CLI session.
+    pub fn wire_path(&self, work_dir: &str, session_id: &str) -> PathBuf {
+        self.get_session_dir(work_dir, session_id)
+            .unwrap_or_default()
+            .join("wire.jsonl")
+    }
+
     fn get_session_dir(&self, work_dir: &str, session_id: &str) -> Result<PathBuf, String> {
         use md5::{Md5, Digest};
 
@@ -0,0 +1,427 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::export;
+use crate::AppState;
+
+/// Requests larger than this are refused; viewers only send short GETs
+const MAX_REQUEST_BYTES: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The viewer page. `{{TITLE}}` and `{{STATE_URL}}` are filled in per share.
+const PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="referrer" content="no-referrer">
+<title>{{TITLE}}</title>
+<style>
+body{font:14px/1.5 -apple-system,system-ui,sans-serif;max-width:900px;margin:24px auto;padding:0 16px;color:#1f2328;background:#fff}
+header{position:sticky;top:0;background:#fff;padding:8px 0;border-bottom:1px solid #d0d7de;margin-bottom:12px}
+h1{font-size:18px;margin:0}
+#status{font-size:12px;color:#656d76}
+.msg{margin:12px 0;padding:8px 12px;border-radius:6px;background:#f6f8fa}
+.msg.user{background:#ddf4ff}
+.msg.tool{background:#fff;border:1px solid #d0d7de}
+.role{font-size:12px;font-weight:600;color:#656d76;text-transform:uppercase}
+pre{white-space:pre-wrap;word-break:break-word;margin:4px 0;font:13px/1.45 ui-monospace,SFMono-Regular,Menlo,monospace}
+.call{font:12px ui-monospace,SFMono-Regular,Menlo,monospace;color:#8250df}
+.live{opacity:.75}
+</style>
+</head>
+<body>
+<header><h1 id="title">{{TITLE}}</h1><div id="status">Connecting…</div></header>
+<main id="messages"></main>
+<script>
+const stateUrl = "{{STATE_URL}}";
+let version = -1;
+function el(tag, cls, text) {
+  const node = document.createElement(tag);
+  if (cls) node.className = cls;
+  if (text !== undefined) node.textContent = text;
+  return node;
+}
+function render(data) {
+  const main = document.getElementById('messages');
+  const atBottom = window.innerHeight + window.scrollY >= document.body.scrollHeight - 40;
+  main.replaceChildren();
+  for (const msg of data.messages) {
+    const box = el('div', 'msg ' + msg.role);
+    const time = msg.timestamp ? new Date(msg.timestamp * 1000).toLocaleTimeString() : '';
+    box.appendChild(el('div', 'role', msg.role + (time ? ' · ' + time : '')));
+    if (msg.role === 'tool') {
+      const details = el('details');
+      details.appendChild(el('summary', null, (msg.content.split('\n')[0] || 'Tool result').slice(0, 120)));
+      details.appendChild(el('pre', null, msg.content));
+      box.appendChild(details);
+    } else if (msg.content) {
+      box.appendChild(el('pre', null, msg.content));
+    }
+    for (const call of msg.tool_calls) box.appendChild(el('div', 'call', '→ ' + call));
+    main.appendChild(box);
+  }
+  if (data.live) {
+    const box = el('div', 'msg assistant live');
+    box.appendChild(el('div', 'role', 'assistant · streaming…'));
+    box.appendChild(el('pre', null, data.live));
+    main.appendChild(box);
+  }
+  document.getElementById('title').textContent = data.title;
+  document.title = data.title;
+  if (atBottom) window.scrollTo(0, document.body.scrollHeight);
+}
+async function poll() {
+  try {
+    const response = await fetch(stateUrl + '?since=' + version, { cache: 'no-store' });
+    if (response.status === 404) {
+      document.getElementById('status').textContent = 'This share has ended.';
+      return;
+    }
+    const data = await response.json();
+    if (!data.unchanged) {
+      version = data.version;
+      render(data);
+    }
+    document.getElementById('status').textContent =
+      (data.running ? 'Agent is running' : 'Idle') + ' · read-only view · updated ' + new Date().toLocaleTimeString();
+  } catch {
+    document.getElementById('status').textContent = 'Connection lost, retrying…';
+  }
+  setTimeout(poll, 1500);
+}
+poll();
+</script>
+</body>
+</html>
+"#;
+
+struct Share {
+    token: String,
+    title: String,
+    messages: Vec<crate::Message>,
+    /// Reply text of the step that is still streaming
+    live: String,
+    running: bool,
+    /// Bumped on every change, so viewers only download what's new
+    version: u64,
+    started_at: i64,
+}
+
+#[derive(Serialize)]
+struct SharedMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+    timestamp: i64,
+    /// `Name(arguments)` of each call the message made
+    tool_calls: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    title: &'a str,
+    version: u64,
+    running: bool,
+    live: &'a str,
+    messages: Vec<SharedMessage<'a>>,
+}
+
+impl Share {
+    fn snapshot(&self) -> String {
+        let messages = self
+            .messages
+            .iter()
+            .map(|message| SharedMessage {
+                role: &message.role,
+                content: &message.content,
+                timestamp: message.timestamp,
+                tool_calls: message
+                    .tool_calls
+                    .iter()
+                    .flatten()
+                    .map(|call| format!("{}({})", call.name, call.arguments))
+                    .collect(),
+            })
+            .collect();
+        serde_json::to_string(&Snapshot {
+            title: &self.title,
+            version: self.version,
+            running: self.running,
+            live: &self.live,
+            messages,
+        })
+        .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct ShareInfo {
+    pub session_id: String,
+    pub url: String,
+    pub started_at: i64,
+}
+
+struct Server {
+    port: u16,
+    shutdown: tokio::sync::oneshot::Sender<()>,
+}
+
+/// Sessions served read-only over HTTP on the LAN. One server runs while any
+/// session is shared; each share has its own unguessable URL.
+#[derive(Default)]
+pub struct ShareRegistry {
+    /// Keyed by session id
+    shares: Arc<Mutex<HashMap<String, Share>>>,
+    server: Mutex<Option<Server>>,
+}
+
+impl ShareRegistry {
+    fn update(&self, session_id: &str, change: impl FnOnce(&mut Share)) {
+        let mut shares = self.shares.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(share) = shares.get_mut(session_id) {
+            change(share);
+            share.version += 1;
+        }
+    }
+
+    /// A message was added to the session transcript.
+    pub fn record(&self, session_id: &str, message: &crate::Message) {
+        self.update(session_id, |share| {
+            if message.role == "user" {
+                share.running = true;
+            }
+            share.messages.push(message.clone());
+            share.live.clear();
+        });
+    }
+
+    /// Reply text streamed in for the current step.
+    pub fn chunk(&self, session_id: &str, text: &str) {
+        self.update(session_id, |share| share.live.push_str(text));
+    }
+
+    /// The turn is over; text that streamed but wasn't saved is dropped.
+    pub fn turn_ended(&self, session_id: &str) {
+        self.update(session_id, |share| {
+            share.running = false;
+            share.live.clear();
+        });
+    }
+
+    fn list(&self, port: u16) -> Vec<ShareInfo> {
+        let shares = self.shares.lock().unwrap_or_else(|e| e.into_inner());
+        shares
+            .iter()
+            .map(|(id, share)| ShareInfo {
+                session_id: id.clone(),
+                url: share_url(port, &share.token),
+                started_at: share.started_at,
+            })
+            .collect()
+    }
+
+    fn port(&self) -> Option<u16> {
+        let server = self.server.lock().unwrap_or_else(|e| e.into_inner());
+        server.as_ref().map(|server| server.port)
+    }
+
+    /// Start the server unless it is running; returns its port.
+    async fn ensure_server(&self) -> Result<u16, String> {
+        if let Some(port) = self.port() {
+            return Ok(port);
+        }
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), configured_port());
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("Failed to start the share server on {}: {}", addr, e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let (shutdown, mut stopped) = tokio::sync::oneshot::channel();
+        let shares = self.shares.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    accepted = listener.accept() => {
+                        if let Ok((stream, _)) = accepted {
+                            let shares = shares.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let _ = tokio::time::timeout(REQUEST_TIMEOUT, serve(stream, &shares)).await;
+                            });
+                        }
+                    }
+                }
+            }
+        });
+        let mut server = self.server.lock().unwrap_or_else(|e| e.into_inner());
+        *server = Some(Server { port, shutdown });
+        Ok(port)
+    }
+
+    fn stop_server_if_idle(&self) {
+        let idle = self.shares.lock().map(|s| s.is_empty()).unwrap_or(false);
+        if idle {
+            let mut server = self.server.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(server) = server.take() {
+                let _ = server.shutdown.send(());
+            }
+        }
+    }
+}
+
+/// Port from settings; 0 (the default) picks a free one.
+fn configured_port() -> u16 {
+    crate::gui_settings_load(None)
+        .ok()
+        .and_then(|payload| payload.settings.share_port)
+        .unwrap_or(0)
+}
+
+/// Address other machines on the network reach this one at. Connecting a UDP
+/// socket sends nothing; it only picks the outgoing interface.
+fn lan_address() -> IpAddr {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("8.8.8.8:80")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+fn share_url(port: u16, token: &str) -> String {
+    format!("http://{}:{}/s/{}", lan_address(), port, token)
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\n\
+         X-Content-Type-Options: nosniff\r\nReferrer-Policy: no-referrer\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(body.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Answer one request: the viewer page at `/s/{token}` and its transcript at
+/// `/s/{token}/state`. Anything else is a 404, and nothing can be changed.
+async fn serve(mut stream: TcpStream, shares: &Mutex<HashMap<String, Share>>) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+        if request.len() > MAX_REQUEST_BYTES {
+            respond(&mut stream, "431 Request Header Fields Too Large", "text/plain", "Too large").await;
+            return;
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, target) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    if method != "GET" {
+        respond(&mut stream, "405 Method Not Allowed", "text/plain", "Read-only").await;
+        return;
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let since = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("since="))
+        .and_then(|v| v.parse::<u64>().ok());
+    let (token, wants_state) = match path.strip_prefix("/s/") {
+        Some(rest) => match rest.strip_suffix("/state") {
+            Some(token) => (token, true),
+            None => (rest.trim_end_matches('/'), false),
+        },
+        None => ("", false),
+    };
+
+    let body = {
+        let shares = shares.lock().unwrap_or_else(|e| e.into_inner());
+        shares
+            .values()
+            .find(|share| !token.is_empty() && share.token == token)
+            .map(|share| {
+                if !wants_state {
+                    PAGE.replace("{{TITLE}}", &crate::mirror::escape_html(&share.title))
+                        .replace("{{STATE_URL}}", &format!("/s/{}/state", share.token))
+                } else if since == Some(share.version) {
+                    format!(
+                        "{{\"version\":{},\"running\":{},\"unchanged\":true}}",
+                        share.version, share.running
+                    )
+                } else {
+                    share.snapshot()
+                }
+            })
+    };
+    match body {
+        Some(body) if wants_state => respond(&mut stream, "200 OK", "application/json", &body).await,
+        Some(body) => respond(&mut stream, "200 OK", "text/html; charset=utf-8", &body).await,
+        None => respond(&mut stream, "404 Not Found", "text/plain", "Not found").await,
+    }
+}
+
+/// Serve a read-only, live-updating view of a session on the local network.
+/// The URL carries a random token; anyone who has it can watch until the
+/// share is stopped. Sharing a session twice returns the existing link.
+#[tauri::command]
+pub async fn session_share_start(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    work_dir: Option<String>,
+) -> Result<ShareInfo, String> {
+    let (title, _, messages) = export::load_transcript(&state, &session_id, work_dir)?;
+    let port = state.shares.ensure_server().await?;
+    let running = state
+        .sessions
+        .lock()
+        .map(|sessions| sessions.values().any(|handle| handle.session_id == session_id))
+        .unwrap_or(false);
+    let mut shares = state.shares.shares.lock().unwrap_or_else(|e| e.into_inner());
+    let share = shares.entry(session_id.clone()).or_insert_with(|| Share {
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        title,
+        messages,
+        live: String::new(),
+        running,
+        version: 0,
+        started_at: chrono::Utc::now().timestamp(),
+    });
+    Ok(ShareInfo {
+        session_id,
+        url: share_url(port, &share.token),
+        started_at: share.started_at,
+    })
+}
+
+/// Stop sharing a session; its link stops working at once. The server shuts
+/// down with the last share.
+#[tauri::command]
+pub fn session_share_stop(state: tauri::State<'_, AppState>, session_id: String) -> Result<(), String> {
+    state
+        .shares
+        .shares
+        .lock()
+        .map_err(|_| "Share registry poisoned".to_string())?
+        .remove(&session_id);
+    state.shares.stop_server_if_idle();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn session_share_list(state: tauri::State<'_, AppState>) -> Vec<ShareInfo> {
+    match state.shares.port() {
+        Some(port) => state.shares.list(port),
+        None => Vec::new(),
+    }
+}
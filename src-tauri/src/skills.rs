@@ -0,0 +1,434 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SKILL_FILE: &str = "SKILL.md";
+
+#[derive(Clone, Serialize)]
+pub struct SkillInfo {
+    name: String,
+    description: Option<String>,
+    path: String,
+    root: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SkillsPayload {
+    roots: Vec<String>,
+    skills: Vec<SkillInfo>,
+}
+
+/// A parsed SKILL.md.
+#[derive(Clone, Serialize)]
+pub struct SkillDetail {
+    pub name: String,
+    pub description: Option<String>,
+    pub path: String,
+    /// Every frontmatter key; lists become arrays, everything else strings
+    pub frontmatter: serde_json::Map<String, serde_json::Value>,
+    /// Instructions after the frontmatter
+    pub body: String,
+    /// Tools the skill may use, from `allowed-tools`; None allows all
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+fn skills_root_candidates(work_dir: &Path) -> Vec<PathBuf> {
+    let home = crate::home_dir();
+    vec![
+        home.join(".config/agents/skills"),
+        home.join(".agents/skills"),
+        home.join(".kimi/skills"),
+        home.join(".claude/skills"),
+        home.join(".codex/skills"),
+        work_dir.join(".agents/skills"),
+        work_dir.join(".kimi/skills"),
+        work_dir.join(".claude/skills"),
+        work_dir.join(".codex/skills"),
+    ]
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches('"').trim_matches('\'')
+}
+
+/// Split a SKILL.md into its frontmatter and body. Handles the YAML subset
+/// skills use: `key: value`, inline `[a, b]` lists and `- item` lists.
+pub fn parse_frontmatter(contents: &str) -> (serde_json::Map<String, serde_json::Value>, String) {
+    let mut frontmatter = serde_json::Map::new();
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim) != Some("---") {
+        return (frontmatter, contents.to_string());
+    }
+
+    let mut list_key: Option<String> = None;
+    let mut body = Vec::new();
+    let mut in_frontmatter = true;
+    for line in lines {
+        if !in_frontmatter {
+            body.push(line);
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed == "---" {
+            in_frontmatter = false;
+            continue;
+        }
+        if let (Some(key), Some(item)) = (&list_key, trimmed.strip_prefix("- ")) {
+            if let Some(serde_json::Value::Array(items)) = frontmatter.get_mut(key) {
+                items.push(serde_json::json!(unquote(item)));
+            }
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_string();
+        let value = value.trim();
+        list_key = None;
+        if value.is_empty() {
+            // Items follow on the next lines
+            frontmatter.insert(key.clone(), serde_json::json!([]));
+            list_key = Some(key);
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items: Vec<&str> = inner.split(',').map(unquote).filter(|v| !v.is_empty()).collect();
+            frontmatter.insert(key, serde_json::json!(items));
+        } else {
+            frontmatter.insert(key, serde_json::json!(unquote(value)));
+        }
+    }
+
+    (frontmatter, body.join("\n").trim().to_string())
+}
+
+fn string_field(frontmatter: &serde_json::Map<String, serde_json::Value>, key: &str) -> Option<String> {
+    frontmatter
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// `allowed-tools` as a list or a comma/space separated string. Entries like
+/// `Shell(git:*)` count as the bare tool name.
+fn allowed_tools(frontmatter: &serde_json::Map<String, serde_json::Value>) -> Option<Vec<String>> {
+    let entries: Vec<String> = match frontmatter.get("allowed-tools")? {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        serde_json::Value::String(value) => value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(str::to_string)
+            .collect(),
+        _ => return None,
+    };
+    let tools: Vec<String> = entries
+        .iter()
+        .map(|entry| entry.split('(').next().unwrap_or("").trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    (!tools.is_empty()).then_some(tools)
+}
+
+fn fallback_name(skill_file: &Path) -> String {
+    skill_file
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or("skill")
+        .to_string()
+}
+
+pub fn load(path: &str) -> Result<SkillDetail, String> {
+    let skill_file = Path::new(path);
+    let contents = fs::read_to_string(skill_file)
+        .map_err(|e| format!("Failed to read skill {}: {}", path, e))?;
+    let (frontmatter, body) = parse_frontmatter(&contents);
+    Ok(SkillDetail {
+        name: string_field(&frontmatter, "name").unwrap_or_else(|| fallback_name(skill_file)),
+        description: string_field(&frontmatter, "description"),
+        path: path.to_string(),
+        allowed_tools: allowed_tools(&frontmatter),
+        frontmatter,
+        body,
+    })
+}
+
+/// Instructions put ahead of the rest of the system prompt. Relative paths in
+/// the skill refer to its own folder.
+pub fn prompt_section(skill: &SkillDetail) -> String {
+    let folder = Path::new(&skill.path)
+        .parent()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+    format!(
+        "The user selected the skill \"{}\". Follow its instructions for this request. \
+         Files it mentions are relative to {}.\n\n{}\n\n",
+        skill.name, folder, skill.body
+    )
+}
+
+/// Skills in the folders directly under `root`. Symlinked skill folders may
+/// point anywhere, since skills are often kept in a dotfiles repo, but are
+/// skipped when links aren't followed; `seen` holds canonical folders so a
+/// skill linked from two roots is listed once.
+fn collect_skills(root: &Path, follow: bool, seen: &mut HashSet<PathBuf>) -> Vec<SkillInfo> {
+    let mut skills = Vec::new();
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return skills,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+        if !path.is_dir() || (is_link && !follow) {
+            continue;
+        }
+        if !path.canonicalize().is_ok_and(|canonical| seen.insert(canonical)) {
+            continue;
+        }
+        let skill_file = path.join(SKILL_FILE);
+        if !skill_file.is_file() {
+            continue;
+        }
+        let contents = fs::read_to_string(&skill_file).unwrap_or_default();
+        let (frontmatter, _) = parse_frontmatter(&contents);
+        skills.push(SkillInfo {
+            name: string_field(&frontmatter, "name").unwrap_or_else(|| fallback_name(&skill_file)),
+            description: string_field(&frontmatter, "description"),
+            path: skill_file.to_string_lossy().to_string(),
+            root: root.to_string_lossy().to_string(),
+        });
+    }
+
+    skills
+}
+
+#[tauri::command]
+pub fn skills_list(work_dir: Option<String>, skills_dir: Option<String>) -> Result<SkillsPayload, String> {
+    let work_dir = work_dir
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::find_repo_root().unwrap_or_else(|| PathBuf::from(".")));
+
+    let mut roots = Vec::new();
+    if let Some(skills_dir) = skills_dir {
+        let root = PathBuf::from(skills_dir);
+        if root.is_dir() {
+            roots.push(root);
+        }
+    } else {
+        for root in skills_root_candidates(&work_dir) {
+            if root.is_dir() {
+                roots.push(root);
+            }
+        }
+    }
+
+    let follow = crate::paths::follow_symlinks();
+    let mut seen_dirs = HashSet::new();
+    let mut seen = HashMap::new();
+    let mut skills = Vec::new();
+    for root in &roots {
+        for skill in collect_skills(root, follow, &mut seen_dirs) {
+            let key = skill.name.to_lowercase();
+            if !seen.contains_key(&key) {
+                seen.insert(key, true);
+                skills.push(skill);
+            }
+        }
+    }
+
+    Ok(SkillsPayload {
+        roots: roots
+            .into_iter()
+            .map(|root| root.to_string_lossy().to_string())
+            .collect(),
+        skills,
+    })
+}
+
+/// Frontmatter and instructions of the SKILL.md at `path`.
+#[tauri::command]
+pub fn skill_load(path: String) -> Result<SkillDetail, String> {
+    load(&path)
+}
+
+/// Folder name for a new skill.
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '-' })
+        .collect();
+    slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-")
+}
+
+/// SKILL.md contents. Values are written as JSON strings, which YAML reads as
+/// quoted strings.
+fn render(name: &str, description: Option<&str>, body: &str) -> String {
+    let mut contents = format!("---\nname: {}\n", serde_json::json!(name));
+    if let Some(description) = description.filter(|d| !d.trim().is_empty()) {
+        contents.push_str(&format!("description: {}\n", serde_json::json!(description.trim())));
+    }
+    contents.push_str(&format!("---\n\n{}\n", body.trim()));
+    contents
+}
+
+/// The frontmatter block including its `---` lines, and the rest of the file.
+fn split_frontmatter(contents: &str) -> (&str, &str) {
+    if contents.lines().next().map(str::trim) != Some("---") {
+        return ("", contents);
+    }
+    let mut offset = 0;
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        if index > 0 && line.trim() == "---" {
+            return contents.split_at(offset);
+        }
+    }
+    ("", contents)
+}
+
+/// Every folder skills may live in: the configured skills dir plus the usual
+/// roots for the home dir, the configured work dir and the current repo.
+fn known_roots() -> Vec<PathBuf> {
+    let settings = crate::gui_settings_load(None)
+        .map(|payload| payload.settings)
+        .unwrap_or_default();
+    let mut roots: Vec<PathBuf> = settings.skills_dir.map(PathBuf::from).into_iter().collect();
+    let work_dirs = settings
+        .work_dir
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(crate::find_repo_root())
+        .chain(std::env::current_dir().ok());
+    for work_dir in work_dirs {
+        roots.extend(skills_root_candidates(&work_dir));
+    }
+    roots.iter().filter_map(|root| root.canonicalize().ok()).collect()
+}
+
+/// `path` must name an existing SKILL.md in a folder directly under one of
+/// the skill roots, so the commands below can't be pointed at arbitrary files
+/// or have a parent folder deleted.
+fn existing_skill_file(path: &str) -> Result<PathBuf, String> {
+    let not_skill = || format!("{} is not a SKILL.md file", path);
+    let file = PathBuf::from(path);
+    if file.file_name().and_then(|name| name.to_str()) != Some(SKILL_FILE) {
+        return Err(not_skill());
+    }
+    let file = file.canonicalize().map_err(|_| not_skill())?;
+    if !file.is_file() || file.file_name().and_then(|name| name.to_str()) != Some(SKILL_FILE) {
+        return Err(not_skill());
+    }
+    let root = file.parent().and_then(Path::parent).ok_or_else(not_skill)?;
+    if !known_roots().iter().any(|known| known == root) {
+        return Err(format!("{} is not in a skills folder", path));
+    }
+    Ok(file)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    for entry in fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?.flatten() {
+        let target = to.join(entry.file_name());
+        // Linked folders are left out; following them could recurse forever
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_symlink() && entry.path().is_dir() {
+            continue;
+        }
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Create `root/<slug of name>/SKILL.md`.
+#[tauri::command]
+pub fn skill_create(
+    root: String,
+    name: String,
+    description: Option<String>,
+    body: String,
+) -> Result<SkillDetail, String> {
+    let folder = slug(&name);
+    if folder.is_empty() {
+        return Err("Skill name cannot be empty".to_string());
+    }
+    let dir = Path::new(&root).join(&folder);
+    if dir.exists() {
+        return Err(format!("A skill named {} already exists in {}", folder, root));
+    }
+    let file = dir.join(SKILL_FILE);
+    crate::write_text(&file, &render(name.trim(), description.as_deref(), &body))?;
+    load(&file.to_string_lossy())
+}
+
+/// Replace the instructions of a skill, keeping its frontmatter.
+#[tauri::command]
+pub fn skill_update(path: String, body: String) -> Result<SkillDetail, String> {
+    let file = existing_skill_file(&path)?;
+    let contents = crate::read_text(&file)?;
+    let (frontmatter, _) = split_frontmatter(&contents);
+    let updated = if frontmatter.is_empty() {
+        format!("{}\n", body.trim())
+    } else {
+        format!("{}\n{}\n", frontmatter, body.trim())
+    };
+    crate::write_text(&file, &updated)?;
+    load(&file.to_string_lossy())
+}
+
+/// Copy a skill's folder next to it under a new name, `<name>-copy` by default.
+#[tauri::command]
+pub fn skill_duplicate(path: String, name: Option<String>) -> Result<SkillDetail, String> {
+    let file = existing_skill_file(&path)?;
+    let source = load(&file.to_string_lossy())?;
+    let dir = file.parent().ok_or_else(|| "Invalid skill path".to_string())?;
+    let root = dir.parent().ok_or_else(|| "Invalid skill path".to_string())?;
+    let base = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("{}-copy", source.name));
+    let (new_name, target) = (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}-{}", base, n) })
+        .map(|candidate| {
+            let target = root.join(slug(&candidate));
+            (candidate, target)
+        })
+        .find(|(_, target)| !target.exists())
+        .ok_or_else(|| "No free name for the copy".to_string())?;
+    copy_dir(dir, &target)?;
+
+    let copy = target.join(SKILL_FILE);
+    let contents = crate::read_text(&copy)?;
+    let (frontmatter, body) = split_frontmatter(&contents);
+    let name_line = format!("name: {}", serde_json::json!(new_name));
+    let updated = if frontmatter.is_empty() {
+        format!("---\n{}\n---\n\n{}", name_line, body)
+    } else {
+        let mut lines: Vec<String> = frontmatter.lines().map(str::to_string).collect();
+        match lines.iter().position(|line| line.trim_start().starts_with("name:")) {
+            Some(index) => lines[index] = name_line,
+            None => lines.insert(1, name_line),
+        }
+        format!("{}\n{}", lines.join("\n"), body)
+    };
+    crate::write_text(&copy, &updated)?;
+    load(&copy.to_string_lossy())
+}
+
+/// Delete a skill's folder, including any files next to its SKILL.md.
+#[tauri::command]
+pub fn skill_delete(path: String) -> Result<(), String> {
+    let file = existing_skill_file(&path)?;
+    let dir = file.parent().ok_or_else(|| "Invalid skill path".to_string())?;
+    fs::remove_dir_all(dir).map_err(|e| format!("Failed to delete {}: {}", dir.display(), e))
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Longest body a snippet may have
+const MAX_BODY_CHARS: usize = 20_000;
+/// Most snippets one import may bring in
+const MAX_IMPORT: usize = 500;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SnippetVariable {
+    pub name: String,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// Text expansion typed in the composer as `trigger` followed by Tab. The body
+/// may reference `${name}` variables; the composer fills them in locally.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    #[serde(default)]
+    pub id: String,
+    pub trigger: String,
+    pub body: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<SnippetVariable>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SnippetStore {
+    #[serde(default)]
+    snippets: Vec<Snippet>,
+}
+
+fn snippets_path() -> PathBuf {
+    crate::kimi_share_dir().join("gui_snippets.json")
+}
+
+fn load_store() -> Result<SnippetStore, String> {
+    let path = snippets_path();
+    if !path.exists() {
+        return Ok(SnippetStore::default());
+    }
+    let raw = crate::read_text(&path)?;
+    serde_json::from_str(&raw).map_err(|error| format!("Invalid snippets file: {error}"))
+}
+
+fn save_store(store: &SnippetStore) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(store).map_err(|error| error.to_string())?;
+    crate::write_text(&snippets_path(), &raw)
+}
+
+#[tauri::command]
+pub fn snippets_list() -> Result<Vec<Snippet>, String> {
+    Ok(load_store()?.snippets)
+}
+
+/// Trim the trigger and check the snippet's fields, giving it an id if it has none.
+fn validate(snippet: &mut Snippet) -> Result<(), String> {
+    snippet.trigger = snippet.trigger.trim().to_string();
+    if snippet.trigger.is_empty() || snippet.trigger.contains(char::is_whitespace) {
+        return Err("Snippet trigger must be a single word".to_string());
+    }
+    if snippet.body.chars().count() > MAX_BODY_CHARS {
+        return Err(format!(
+            "Snippet '{}' is longer than {} characters",
+            snippet.trigger, MAX_BODY_CHARS
+        ));
+    }
+    if snippet.variables.iter().any(|variable| variable.name.trim().is_empty()) {
+        return Err(format!("Snippet '{}' has a variable without a name", snippet.trigger));
+    }
+    if snippet.id.is_empty() {
+        snippet.id = Uuid::new_v4().to_string();
+    }
+    Ok(())
+}
+
+/// Insert or update a snippet. Snippets without an id get one; triggers must be unique.
+#[tauri::command]
+pub fn snippets_save(mut snippet: Snippet) -> Result<Snippet, String> {
+    validate(&mut snippet)?;
+
+    let mut store = load_store()?;
+    if store
+        .snippets
+        .iter()
+        .any(|existing| existing.trigger == snippet.trigger && existing.id != snippet.id)
+    {
+        return Err(format!("Snippet trigger '{}' is already in use", snippet.trigger));
+    }
+    match store.snippets.iter_mut().find(|existing| existing.id == snippet.id) {
+        Some(existing) => *existing = snippet.clone(),
+        None => store.snippets.push(snippet.clone()),
+    }
+    save_store(&store)?;
+    Ok(snippet)
+}
+
+#[tauri::command]
+pub fn snippets_delete(id: String) -> Result<(), String> {
+    let mut store = load_store()?;
+    let before = store.snippets.len();
+    store.snippets.retain(|snippet| snippet.id != id);
+    if store.snippets.len() == before {
+        return Err("Snippet not found".to_string());
+    }
+    save_store(&store)
+}
+
+/// Replace the whole store, e.g. when importing snippets from another machine.
+/// With `replace: false` the snippets are merged in instead, replacing those
+/// with the same id or trigger. Nothing is saved unless every snippet is valid
+/// and ids and triggers are unique within the import.
+#[tauri::command]
+pub fn snippets_import(snippets: Vec<Snippet>, replace: Option<bool>) -> Result<Vec<Snippet>, String> {
+    if snippets.len() > MAX_IMPORT {
+        return Err(format!("At most {} snippets can be imported at once", MAX_IMPORT));
+    }
+    let mut ids = HashSet::new();
+    let mut triggers = HashSet::new();
+    let mut imported = Vec::with_capacity(snippets.len());
+    for mut snippet in snippets {
+        validate(&mut snippet)?;
+        if !ids.insert(snippet.id.clone()) {
+            return Err(format!("Snippet id '{}' appears more than once", snippet.id));
+        }
+        if !triggers.insert(snippet.trigger.clone()) {
+            return Err(format!("Snippet trigger '{}' appears more than once", snippet.trigger));
+        }
+        imported.push(snippet);
+    }
+
+    let mut store = if replace.unwrap_or(true) {
+        SnippetStore::default()
+    } else {
+        load_store()?
+    };
+    store
+        .snippets
+        .retain(|existing| !ids.contains(&existing.id) && !triggers.contains(&existing.trigger));
+    store.snippets.extend(imported);
+    save_store(&store)?;
+    Ok(store.snippets)
+}
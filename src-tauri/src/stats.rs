@@ -0,0 +1,290 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::git::{self, GitActivity};
+
+/// Stats younger than this are served from the cache
+const CACHE_TTL: Duration = Duration::from_secs(300);
+/// Walks stop counting after this many files, so huge trees stay quick
+const MAX_FILES: usize = 50_000;
+/// Larger files count towards bytes but not lines
+const MAX_COUNT_BYTES: u64 = 2 * 1024 * 1024;
+const ACTIVITY_DAYS: u32 = 30;
+/// Folders this deep below the work dir are ranked by size
+const DIR_DEPTH: usize = 2;
+
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("Rust", &["rs"]),
+    ("TypeScript", &["ts", "tsx", "mts", "cts"]),
+    ("JavaScript", &["js", "jsx", "mjs", "cjs"]),
+    ("Python", &["py", "pyi"]),
+    ("Go", &["go"]),
+    ("Java", &["java"]),
+    ("Kotlin", &["kt", "kts"]),
+    ("Swift", &["swift"]),
+    ("C", &["c", "h"]),
+    ("C++", &["cc", "cpp", "cxx", "hpp", "hh", "hxx"]),
+    ("C#", &["cs"]),
+    ("Ruby", &["rb"]),
+    ("PHP", &["php"]),
+    ("Shell", &["sh", "bash", "zsh", "fish", "ps1"]),
+    ("HTML", &["html", "htm"]),
+    ("CSS", &["css", "scss", "sass", "less"]),
+    ("Vue", &["vue"]),
+    ("Svelte", &["svelte"]),
+    ("SQL", &["sql"]),
+    ("Markdown", &["md", "mdx"]),
+    ("JSON", &["json"]),
+    ("YAML", &["yml", "yaml"]),
+    ("TOML", &["toml"]),
+];
+
+#[derive(Clone, Serialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub lines: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DirectoryStats {
+    /// Relative to the work dir, with forward slashes
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Size and shape of a work dir, for the dashboard and the system prompt.
+#[derive(Clone, Serialize)]
+pub struct WorkspaceStats {
+    pub work_dir: String,
+    pub files: usize,
+    /// Lines of the files in a known language
+    pub lines: usize,
+    pub bytes: u64,
+    /// Most lines first
+    pub languages: Vec<LanguageStats>,
+    /// Biggest first
+    pub largest_dirs: Vec<DirectoryStats>,
+    /// None outside a git repository
+    pub git: Option<GitActivity>,
+    /// The walk stopped at the file limit, so the counts are lower bounds
+    pub truncated: bool,
+    pub computed_at: i64,
+}
+
+struct Cached {
+    at: Instant,
+    stats: WorkspaceStats,
+}
+
+static CACHE: Mutex<BTreeMap<String, Cached>> = Mutex::new(BTreeMap::new());
+
+fn language(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(name, _)| *name)
+}
+
+/// Lines of a text file; None for binary files.
+fn count_lines(path: &Path) -> Option<usize> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        return None;
+    }
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count();
+    Some(newlines + usize::from(bytes.last().is_some_and(|&b| b != b'\n')))
+}
+
+fn walk(work_dir: &str) -> WorkspaceStats {
+    let root = Path::new(work_dir);
+    let mut stats = WorkspaceStats {
+        work_dir: work_dir.to_string(),
+        files: 0,
+        lines: 0,
+        bytes: 0,
+        languages: Vec::new(),
+        largest_dirs: Vec::new(),
+        git: None,
+        truncated: false,
+        computed_at: chrono::Utc::now().timestamp(),
+    };
+    let mut languages: HashMap<&str, LanguageStats> = HashMap::new();
+    let mut dirs: HashMap<String, DirectoryStats> = HashMap::new();
+
+    for entry in crate::context::project_walker(root, root).build().flatten() {
+        if stats.files >= MAX_FILES {
+            stats.truncated = true;
+            break;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        stats.files += 1;
+        stats.bytes += meta.len();
+
+        if let Some(name) = language(path) {
+            let lines = if meta.len() <= MAX_COUNT_BYTES {
+                count_lines(path).unwrap_or(0)
+            } else {
+                0
+            };
+            let language = languages.entry(name).or_insert_with(|| LanguageStats {
+                language: name.to_string(),
+                files: 0,
+                lines: 0,
+            });
+            language.files += 1;
+            language.lines += lines;
+            stats.lines += lines;
+        }
+
+        let Ok(rel) = path.strip_prefix(root) else {
+            continue;
+        };
+        let parts: Vec<String> = rel
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.components())
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        for depth in 1..=parts.len().min(DIR_DEPTH) {
+            let dir = parts[..depth].join("/");
+            let entry = dirs.entry(dir.clone()).or_insert_with(|| DirectoryStats {
+                path: dir,
+                files: 0,
+                bytes: 0,
+            });
+            entry.files += 1;
+            entry.bytes += meta.len();
+        }
+    }
+
+    stats.languages = languages.into_values().collect();
+    stats
+        .languages
+        .sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| b.files.cmp(&a.files)));
+    stats.largest_dirs = dirs.into_values().collect();
+    stats
+        .largest_dirs
+        .sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    stats.largest_dirs.truncate(10);
+    stats
+}
+
+async fn compute(work_dir: &str) -> Result<WorkspaceStats, String> {
+    if !Path::new(work_dir).is_dir() {
+        return Err(format!("Not a directory: {}", work_dir));
+    }
+    let dir = work_dir.to_string();
+    let mut stats = tokio::task::spawn_blocking(move || walk(&dir))
+        .await
+        .map_err(|e| e.to_string())?;
+    stats.git = git::activity(work_dir, ACTIVITY_DAYS).await;
+    Ok(stats)
+}
+
+/// Stats of `work_dir`, from the cache unless they are stale or `refresh` is set.
+pub async fn get(work_dir: &str, refresh: bool) -> Result<WorkspaceStats, String> {
+    if !refresh {
+        if let Some(stats) = cached(work_dir) {
+            return Ok(stats);
+        }
+    }
+    let stats = compute(work_dir).await?;
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.insert(
+            work_dir.to_string(),
+            Cached {
+                at: Instant::now(),
+                stats: stats.clone(),
+            },
+        );
+    }
+    Ok(stats)
+}
+
+/// Fresh cached stats of `work_dir`, if any.
+pub fn cached(work_dir: &str) -> Option<WorkspaceStats> {
+    let cache = CACHE.lock().ok()?;
+    cache
+        .get(work_dir)
+        .filter(|cached| cached.at.elapsed() < CACHE_TTL)
+        .map(|cached| cached.stats.clone())
+}
+
+/// Whether a summary of the stats goes into the system prompt. Off by default.
+pub fn in_prompt() -> bool {
+    crate::gui_settings_load(None)
+        .ok()
+        .and_then(|payload| payload.settings.workspace_stats_in_prompt)
+        .unwrap_or(false)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+/// A few lines orienting the model: languages, big folders, recent changes.
+pub fn prompt_summary(stats: &WorkspaceStats) -> String {
+    let mut summary = format!(
+        "{}{} files, {} lines of code, {}.\n",
+        if stats.truncated { "Over " } else { "" },
+        stats.files,
+        stats.lines,
+        format_bytes(stats.bytes)
+    );
+    if !stats.languages.is_empty() {
+        let languages: Vec<String> = stats
+            .languages
+            .iter()
+            .take(6)
+            .map(|l| format!("{} ({} files, {} lines)", l.language, l.files, l.lines))
+            .collect();
+        summary.push_str(&format!("Languages: {}\n", languages.join(", ")));
+    }
+    if !stats.largest_dirs.is_empty() {
+        let dirs: Vec<String> = stats
+            .largest_dirs
+            .iter()
+            .take(5)
+            .map(|d| format!("{}/ ({} files, {})", d.path, d.files, format_bytes(d.bytes)))
+            .collect();
+        summary.push_str(&format!("Largest folders: {}\n", dirs.join(", ")));
+    }
+    if let Some(git) = &stats.git {
+        summary.push_str(&format!(
+            "Git: branch {}, {} commits in the last {} days",
+            git.branch.as_deref().unwrap_or("(detached)"),
+            git.commits,
+            git.days
+        ));
+        if !git.hot_files.is_empty() {
+            let files: Vec<&str> = git.hot_files.iter().take(5).map(|(path, _)| path.as_str()).collect();
+            summary.push_str(&format!("; most changed: {}", files.join(", ")));
+        }
+        summary.push('\n');
+    }
+    summary
+}
+
+/// Lines of code by language, file counts, the largest folders and recent git
+/// activity of `work_dir`. Cached for a few minutes; `refresh` recomputes.
+#[tauri::command]
+pub async fn workspace_stats(work_dir: String, refresh: Option<bool>) -> Result<WorkspaceStats, String> {
+    get(&work_dir, refresh.unwrap_or(false)).await
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tauri::Emitter;
+
+use crate::llm;
+use crate::providers;
+use crate::AppState;
+
+const SIDECAR: &str = "suggestions.json";
+const MAX_SUGGESTIONS: usize = 3;
+const REQUEST_EXCERPT_CHARS: usize = 600;
+const REPLY_EXCERPT_CHARS: usize = 1500;
+const MAX_SUGGESTION_CHARS: usize = 160;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionSuggestions {
+    pub session_id: String,
+    /// Message count of the transcript they follow; a longer one makes them stale
+    pub message_count: usize,
+    pub suggestions: Vec<String>,
+    pub generated_at: i64,
+}
+
+/// The last user request, the tools the turn used and the reply it ended with.
+fn last_turn(messages: &[crate::Message]) -> Option<String> {
+    let start = messages.iter().rposition(|m| m.role == "user")?;
+    let turn = &messages[start..];
+    let reply = turn
+        .iter()
+        .rev()
+        .find(|m| m.role == "assistant" && !m.content.trim().is_empty())?;
+    let mut tools: Vec<&str> = Vec::new();
+    for call in turn.iter().filter_map(|m| m.tool_calls.as_ref()).flatten() {
+        if !tools.contains(&call.name.as_str()) {
+            tools.push(&call.name);
+        }
+    }
+    let mut text = format!(
+        "User: {}\n",
+        crate::truncate_with_ellipsis(turn[0].content.trim(), REQUEST_EXCERPT_CHARS)
+    );
+    if !tools.is_empty() {
+        text.push_str(&format!("Tools used: {}\n", tools.join(", ")));
+    }
+    text.push_str(&format!(
+        "Assistant: {}",
+        crate::truncate_with_ellipsis(reply.content.trim(), REPLY_EXCERPT_CHARS)
+    ));
+    Some(text)
+}
+
+async fn generate(
+    session_id: &str,
+    config_path: Option<&str>,
+    summarizer_model: Option<&str>,
+    chat_model: &str,
+    turn: &str,
+) -> Result<Vec<String>, String> {
+    let (credentials, api_model) =
+        providers::resolve_summarizer(config_path, summarizer_model, chat_model).await?;
+    let request = serde_json::json!({
+        "model": api_model,
+        "messages": [
+            {
+                "role": "system",
+                "content": format!(
+                    "Suggest up to {} short prompts the user is likely to send next, written as the user \
+                     would type them, e.g. \"Run the tests\", \"Add a test for the empty case\". \
+                     Reply with only a JSON array of strings.",
+                    MAX_SUGGESTIONS
+                ),
+            },
+            { "role": "user", "content": turn },
+        ],
+        "stream": false,
+        "temperature": 0.3,
+        "max_tokens": 200,
+    });
+
+    let client = crate::http::client(&credentials)?;
+    let data = llm::post_chat_completion(&client, &credentials, &request, Some(session_id)).await?;
+    let content = data
+        .get("choices")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.get("message"))
+        .and_then(|v| v.get("content"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    let mut suggestions: Vec<String> = Vec::new();
+    for suggestion in crate::outline::parse_titles(content) {
+        let suggestion = suggestion.trim().trim_matches('"').trim().to_string();
+        if !suggestion.is_empty()
+            && suggestion.chars().count() <= MAX_SUGGESTION_CHARS
+            && !suggestions.contains(&suggestion)
+        {
+            suggestions.push(suggestion);
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    Ok(suggestions)
+}
+
+/// Suggest follow-up prompts for the turn that just finished, in the
+/// background, with the summarizer model or else the chat model. The result is
+/// emitted as a `suggestions` event and cached next to the session; failures
+/// are dropped, since suggestions are only a shortcut.
+pub fn spawn(
+    window: tauri::Window,
+    state: &AppState,
+    session_id: &str,
+    chat_model: String,
+    summarizer_model: Option<String>,
+    config_path: Option<String>,
+) {
+    // Each suggestion call costs a request, which low-bandwidth mode avoids
+    if crate::network::is_offline() || crate::network::is_low_bandwidth() {
+        return;
+    }
+    let Ok(mut manager) = state.session_manager.lock() else {
+        return;
+    };
+    let Some(messages) = manager.find_session(session_id).map(|s| s.messages) else {
+        return;
+    };
+    let cache_path = manager.sidecar_path(session_id, SIDECAR);
+    drop(manager);
+    let Some(turn) = last_turn(&messages) else {
+        return;
+    };
+    let session_id = session_id.to_string();
+
+    tauri::async_runtime::spawn(async move {
+        let generated = generate(
+            &session_id,
+            config_path.as_deref(),
+            summarizer_model.as_deref(),
+            &chat_model,
+            &turn,
+        )
+        .await;
+        let suggestions = match generated {
+            Ok(suggestions) if !suggestions.is_empty() => suggestions,
+            _ => return,
+        };
+        let cached = SessionSuggestions {
+            session_id: session_id.clone(),
+            message_count: messages.len(),
+            suggestions: suggestions.clone(),
+            generated_at: chrono::Utc::now().timestamp(),
+        };
+        if let Ok(json) = serde_json::to_string_pretty(&cached) {
+            let _ = fs::write(&cache_path, json);
+        }
+        let _ = window.emit(
+            "chat://event",
+            llm::StreamEvent {
+                event: "suggestions".to_string(),
+                data: serde_json::json!({
+                    "session_id": session_id,
+                    "suggestions": suggestions,
+                }),
+            },
+        );
+    });
+}
+
+/// Follow-up prompts suggested after the session's last turn, if they are
+/// still current.
+#[tauri::command]
+pub fn session_suggestions(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<String>, String> {
+    let mut manager = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+    let Some(cached) = fs::read_to_string(manager.sidecar_path(&session_id, SIDECAR))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<SessionSuggestions>(&raw).ok())
+    else {
+        return Ok(Vec::new());
+    };
+    let current = manager.find_session(&session_id).map(|s| s.messages.len());
+    Ok(if current == Some(cached.message_count) {
+        cached.suggestions
+    } else {
+        Vec::new()
+    })
+}
@@ -0,0 +1,383 @@
+use serde::Serialize;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, TreeCursor};
+
+use crate::paths::{self, Access};
+use crate::tools::{self, CodeOutlineArgs, FindSymbolArgs, ToolOutput};
+
+/// Larger files are generated or vendored more often than not
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+const MAX_SIGNATURE_CHARS: usize = 200;
+const DEFAULT_MAX_RESULTS: usize = 50;
+const MAX_RESULTS: usize = 500;
+/// Files FindSymbol parses before it stops, so huge trees stay quick
+const MAX_SCANNED_FILES: usize = 20_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl Lang {
+    fn for_path(path: &Path) -> Option<Lang> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "rs" => Lang::Rust,
+            "py" | "pyi" => Lang::Python,
+            "js" | "jsx" | "mjs" | "cjs" => Lang::JavaScript,
+            "ts" | "mts" | "cts" => Lang::TypeScript,
+            "tsx" => Lang::Tsx,
+            "go" => Lang::Go,
+            _ => return None,
+        })
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Symbol kind a node declares, given the kind of the symbol it's in.
+    fn symbol_kind(self, node: &Node, parent: Option<&str>) -> Option<&'static str> {
+        let in_type = matches!(parent, Some("class" | "impl" | "trait" | "interface"));
+        let function = if in_type { "method" } else { "function" };
+        Some(match (self, node.kind()) {
+            (Lang::Rust, "function_item" | "function_signature_item") => function,
+            (Lang::Rust, "struct_item") => "struct",
+            (Lang::Rust, "enum_item") => "enum",
+            (Lang::Rust, "trait_item") => "trait",
+            (Lang::Rust, "impl_item") => "impl",
+            (Lang::Rust, "mod_item") => "module",
+            (Lang::Rust, "const_item" | "static_item") => "constant",
+            (Lang::Rust, "type_item") => "type",
+            (Lang::Rust, "macro_definition") => "macro",
+            (Lang::Python, "function_definition") => function,
+            (Lang::Python, "class_definition") => "class",
+            (Lang::Go, "function_declaration") => "function",
+            (Lang::Go, "method_declaration") => "method",
+            (Lang::Go, "type_spec") => "type",
+            (_, "function_declaration" | "generator_function_declaration") => "function",
+            (_, "class_declaration" | "abstract_class_declaration") => "class",
+            (_, "method_definition" | "method_signature") => "method",
+            (_, "interface_declaration") => "interface",
+            (_, "type_alias_declaration") => "type",
+            (_, "enum_declaration") => "enum",
+            // `const f = () => {}` and `const f = function () {}`
+            (_, "variable_declarator")
+                if node.child_by_field_name("value").is_some_and(|value| {
+                    matches!(value.kind(), "arrow_function" | "function_expression")
+                }) =>
+            {
+                function
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A declaration in a source file.
+#[derive(Clone, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    /// `function`, `method`, `class`, `struct`, `impl`, ...
+    pub kind: &'static str,
+    /// The declaration up to its body, on one line
+    pub signature: String,
+    pub line: usize,
+    pub end_line: usize,
+    /// Nesting level; methods of a top-level class are at 1
+    pub depth: usize,
+    /// Name of the symbol this one is declared in
+    pub container: Option<String>,
+}
+
+fn text<'a>(node: &Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn signature(node: &Node, source: &str) -> String {
+    let body = node.child_by_field_name("body").or_else(|| {
+        node.child_by_field_name("value")
+            .and_then(|value| value.child_by_field_name("body"))
+    });
+    // A JS/TS declarator's signature is its whole statement, `const f = (a) =>`
+    let start = match node.parent() {
+        Some(parent) if node.kind() == "variable_declarator" => parent.start_byte(),
+        _ => node.start_byte(),
+    };
+    let signature = match body {
+        Some(body) => source
+            .get(start..body.start_byte())
+            .unwrap_or("")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        // Bodiless items such as constants and type aliases keep their first line
+        None => source
+            .get(start..node.end_byte())
+            .unwrap_or("")
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string(),
+    };
+    let signature = signature.trim_end_matches(['{', ':', ' ', '=']);
+    crate::truncate_with_ellipsis(signature, MAX_SIGNATURE_CHARS)
+}
+
+fn name(node: &Node, source: &str) -> Option<String> {
+    // `impl Trait for Type` is named after the type
+    let field = if node.kind() == "impl_item" {
+        "type"
+    } else {
+        "name"
+    };
+    let name = text(&node.child_by_field_name(field)?, source).trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// (node, depth, index of the enclosing symbol in the output)
+type Pending<'tree> = (Node<'tree>, usize, Option<usize>);
+
+fn push_children<'tree>(
+    stack: &mut Vec<Pending<'tree>>,
+    cursor: &mut TreeCursor<'tree>,
+    node: Node<'tree>,
+    depth: usize,
+    container: Option<usize>,
+) {
+    let first = stack.len();
+    stack.extend(node.named_children(cursor).map(|child| (child, depth, container)));
+    // Popped first-child-first
+    stack[first..].reverse();
+}
+
+/// Append the declarations under `root` in source order. Walks with an
+/// explicit stack: minified files can nest deeper than a thread's stack.
+fn visit(lang: Lang, root: Node, source: &str, symbols: &mut Vec<Symbol>) {
+    let mut stack = Vec::new();
+    let mut cursor = root.walk();
+    push_children(&mut stack, &mut cursor, root, 0, None);
+    while let Some((node, depth, container)) = stack.pop() {
+        let declared = lang
+            .symbol_kind(&node, container.map(|index: usize| symbols[index].kind))
+            .and_then(|kind| Some((kind, name(&node, source)?)));
+        match declared {
+            Some((kind, name)) => {
+                let container_name = container.map(|index| symbols[index].name.clone());
+                symbols.push(Symbol {
+                    name,
+                    kind,
+                    signature: signature(&node, source),
+                    line: node.start_position().row + 1,
+                    end_line: node.end_position().row + 1,
+                    depth,
+                    container: container_name,
+                });
+                push_children(&mut stack, &mut cursor, node, depth + 1, Some(symbols.len() - 1));
+            }
+            None => push_children(&mut stack, &mut cursor, node, depth, container),
+        }
+    }
+}
+
+fn outline_source(lang: Lang, source: &str) -> Result<Vec<Symbol>, String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&lang.language())
+        .map_err(|e| format!("Failed to load grammar: {}", e))?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| "Failed to parse file".to_string())?;
+    let mut symbols = Vec::new();
+    visit(lang, tree.root_node(), source, &mut symbols);
+    Ok(symbols)
+}
+
+/// Symbols declared in `path`, in file order. Err for languages without a
+/// grammar and files too large to parse.
+pub fn outline_file(path: &Path) -> Result<Vec<Symbol>, String> {
+    let lang = Lang::for_path(path).ok_or_else(|| {
+        format!(
+            "No outline for {}; supported are Rust, Python, JavaScript, TypeScript and Go files.",
+            path.display()
+        )
+    })?;
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len();
+    if size > MAX_FILE_BYTES {
+        return Err(format!("{} is too large to outline.", path.display()));
+    }
+    let decoded = crate::encoding::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    outline_source(lang, &decoded.text)
+}
+
+fn failure(summary: String) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary,
+        output: String::new(),
+    }
+}
+
+/// The CodeOutline tool: one line per declaration, indented by nesting.
+pub fn outline_tool(work_dir: &str, args: &CodeOutlineArgs) -> ToolOutput {
+    let path = match paths::resolve_inside(work_dir, &args.path, Access::Read) {
+        Ok(path) => path,
+        Err(err) => return failure(err),
+    };
+    let symbols = match outline_file(&path) {
+        Ok(symbols) => symbols,
+        Err(err) => return failure(err),
+    };
+    if symbols.is_empty() {
+        return ToolOutput {
+            ok: true,
+            summary: format!("No declarations found in {}.", args.path),
+            output: String::new(),
+        };
+    }
+    let text = symbols
+        .iter()
+        .map(|s| {
+            format!(
+                "{}{}-{} {} {}",
+                "  ".repeat(s.depth),
+                s.line,
+                s.end_line,
+                s.kind,
+                s.signature
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (output, truncated) = tools::truncate_output(&text);
+    ToolOutput {
+        ok: true,
+        summary: tools::append_truncation(
+            format!("{} declarations in {}.", symbols.len(), args.path),
+            truncated,
+        ),
+        output,
+    }
+}
+
+/// The FindSymbol tool: declarations named like `args.name` across the work
+/// dir (or a file or folder in it), exact matches first.
+pub fn find_tool(work_dir: &str, args: &FindSymbolArgs) -> ToolOutput {
+    let query = args.name.trim().to_lowercase();
+    if query.is_empty() {
+        return failure("Missing name".to_string());
+    }
+    let kind = args
+        .kind
+        .as_deref()
+        .map(|kind| kind.trim().to_lowercase())
+        .filter(|kind| !kind.is_empty());
+    let root = Path::new(work_dir);
+    let start = match args.path.as_deref().filter(|p| !p.trim().is_empty()) {
+        Some(path) => match paths::resolve_inside(work_dir, path, Access::Read) {
+            Ok(start) => start,
+            Err(err) => return failure(err),
+        },
+        None => root.to_path_buf(),
+    };
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    let mut found: Vec<(bool, String, Symbol)> = Vec::new();
+    let mut scanned = 0;
+    for entry in crate::context::project_walker(&root, &start)
+        .build()
+        .flatten()
+    {
+        if scanned >= MAX_SCANNED_FILES {
+            break;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) || Lang::for_path(entry.path()).is_none()
+        {
+            continue;
+        }
+        scanned += 1;
+        let Ok(symbols) = outline_file(entry.path()) else {
+            continue;
+        };
+        let rel = entry
+            .path()
+            .strip_prefix(&root)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .replace('\\', "/");
+        for symbol in symbols {
+            let name = symbol.name.to_lowercase();
+            if !name.contains(&query) || kind.as_deref().is_some_and(|kind| kind != symbol.kind) {
+                continue;
+            }
+            found.push((name == query, rel.clone(), symbol));
+        }
+    }
+    if found.is_empty() {
+        return ToolOutput {
+            ok: true,
+            summary: format!("No declarations named like {} found.", args.name),
+            output: String::new(),
+        };
+    }
+    // Exact names first, then by path and line
+    found.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then(a.2.line.cmp(&b.2.line))
+    });
+    let limit = args
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, MAX_RESULTS);
+    let total = found.len();
+    let text = found
+        .iter()
+        .take(limit)
+        .map(|(_, rel, s)| format!("{}:{}: {} {}", rel, s.line, s.kind, s.signature))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut summary = format!("Found {} declarations.", total);
+    if total > limit {
+        summary.push_str(&format!(
+            " Showing {}; narrow the name, kind or path to see the rest.",
+            limit
+        ));
+    }
+    if scanned >= MAX_SCANNED_FILES {
+        summary.push_str(&format!(" Stopped after {} files.", MAX_SCANNED_FILES));
+    }
+    let (output, truncated) = tools::truncate_output(&text);
+    ToolOutput {
+        ok: true,
+        summary: tools::append_truncation(summary, truncated),
+        output,
+    }
+}
+
+/// Declarations in a file, for the outline panel.
+#[tauri::command]
+pub async fn code_outline(work_dir: String, path: String) -> Result<Vec<Symbol>, String> {
+    tokio::task::spawn_blocking(move || {
+        let path = paths::resolve_inside(&work_dir, &path, Access::Read)?;
+        outline_file(&path)
+    })
+    .await
+    .map_err(|e| format!("Outline failed: {}", e))?
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::GuiSettings;
+
+/// Named bundle of settings for a kind of project, e.g. "Rust backend" or
+/// "Docs site". Stored in gui.json; unset fields leave the current value alone.
+#[derive(Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct WorkspaceTemplate {
+    pub name: String,
+    pub model: Option<String>,
+    pub thinking: Option<bool>,
+    pub yolo: Option<bool>,
+    pub skills_dir: Option<String>,
+    pub mcp_config_files: Option<Vec<String>>,
+    pub tool_approvals: Option<HashMap<String, bool>>,
+    pub disabled_tools: Option<Vec<String>>,
+    pub env: Option<HashMap<String, String>>,
+}
+
+fn apply_template(settings: &mut GuiSettings, template: WorkspaceTemplate) {
+    if let Some(model) = template.model {
+        settings.model = Some(model);
+    }
+    if let Some(thinking) = template.thinking {
+        settings.thinking = Some(thinking);
+    }
+    if let Some(yolo) = template.yolo {
+        settings.yolo = Some(yolo);
+    }
+    if let Some(skills_dir) = template.skills_dir {
+        settings.skills_dir = Some(skills_dir);
+    }
+    if let Some(files) = template.mcp_config_files {
+        settings.mcp_config_files = files;
+    }
+    if let Some(approvals) = template.tool_approvals {
+        settings.tool_approvals = approvals;
+    }
+    if let Some(disabled) = template.disabled_tools {
+        settings.disabled_tools = disabled;
+    }
+    if let Some(env) = template.env {
+        settings.env = env;
+    }
+}
+
+fn load_settings() -> Result<GuiSettings, String> {
+    Ok(crate::gui_settings_load(None)?.settings)
+}
+
+#[tauri::command]
+pub fn workspace_template_list() -> Result<Vec<WorkspaceTemplate>, String> {
+    Ok(load_settings()?.workspace_templates)
+}
+
+/// Insert or replace the template with the same name.
+#[tauri::command]
+pub fn workspace_template_save(mut template: WorkspaceTemplate) -> Result<Vec<WorkspaceTemplate>, String> {
+    template.name = template.name.trim().to_string();
+    if template.name.is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+
+    let mut settings = load_settings()?;
+    match settings
+        .workspace_templates
+        .iter_mut()
+        .find(|existing| existing.name == template.name)
+    {
+        Some(existing) => *existing = template,
+        None => settings.workspace_templates.push(template),
+    }
+    crate::gui_settings_save(None, settings.clone())?;
+    Ok(settings.workspace_templates)
+}
+
+#[tauri::command]
+pub fn workspace_template_delete(name: String) -> Result<Vec<WorkspaceTemplate>, String> {
+    let mut settings = load_settings()?;
+    let before = settings.workspace_templates.len();
+    settings.workspace_templates.retain(|template| template.name != name);
+    if settings.workspace_templates.len() == before {
+        return Err("Template not found".to_string());
+    }
+    crate::gui_settings_save(None, settings.clone())?;
+    Ok(settings.workspace_templates)
+}
+
+/// Apply a template to the saved settings and switch to `work_dir`, returning
+/// the updated settings for the UI to adopt.
+#[tauri::command]
+pub fn workspace_template_apply(name: String, work_dir: Option<String>) -> Result<GuiSettings, String> {
+    let mut settings = load_settings()?;
+    let template = settings
+        .workspace_templates
+        .iter()
+        .find(|template| template.name == name)
+        .cloned()
+        .ok_or_else(|| "Template not found".to_string())?;
+    apply_template(&mut settings, template);
+    if let Some(work_dir) = work_dir.filter(|wd| !wd.is_empty()) {
+        settings.work_dir = Some(work_dir);
+    }
+    crate::gui_settings_save(None, settings.clone())?;
+    Ok(settings)
+}
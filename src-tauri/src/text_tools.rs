@@ -0,0 +1,113 @@
+use uuid::Uuid;
+
+/// Fence language of a tool call written as text
+const FENCE: &str = "tool_call";
+
+/// Instructions for models without native tool calling: the tool list and
+/// how to call one in a fenced block.
+fn prompt_section(tools_def: &[serde_json::Value]) -> String {
+    let tools: Vec<String> = tools_def
+        .iter()
+        .map(|tool| {
+            let function = &tool["function"];
+            format!(
+                "- {}: {}\n  Parameters (JSON schema): {}",
+                function["name"].as_str().unwrap_or(""),
+                function["description"].as_str().unwrap_or(""),
+                function["parameters"]
+            )
+        })
+        .collect();
+    format!(
+        "\n\n# Tools\nYou can call these tools:\n{}\n\n\
+         To call a tool, reply with a fenced block like this and nothing after it:\n\
+         ```{}\n{{\"name\": \"ReadFile\", \"arguments\": {{\"path\": \"src/main.rs\"}}}}\n```\n\
+         Several blocks call several tools. Results come back in the next user message. \
+         Reply without a block once you have the final answer.",
+        tools.join("\n"),
+        FENCE
+    )
+}
+
+/// Rewrite the conversation for a model without tool calling: tool calls
+/// become fenced blocks in the assistant text, tool results become user
+/// messages, and the tool instructions go into the system prompt.
+pub fn convert(messages: &[serde_json::Value], tools_def: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    let mut converted = Vec::with_capacity(messages.len());
+    for message in messages {
+        match message["role"].as_str().unwrap_or("") {
+            "system" if converted.is_empty() => converted.push(serde_json::json!({
+                "role": "system",
+                "content": format!("{}{}", message["content"].as_str().unwrap_or(""), prompt_section(tools_def)),
+            })),
+            "assistant" => {
+                let mut content = message["content"].as_str().unwrap_or("").to_string();
+                for call in message["tool_calls"].as_array().into_iter().flatten() {
+                    let arguments: serde_json::Value =
+                        serde_json::from_str(call["function"]["arguments"].as_str().unwrap_or("{}"))
+                            .unwrap_or(serde_json::json!({}));
+                    let block = serde_json::json!({
+                        "name": call["function"]["name"],
+                        "arguments": arguments,
+                    });
+                    content.push_str(&format!("\n```{}\n{}\n```", FENCE, block));
+                }
+                converted.push(serde_json::json!({ "role": "assistant", "content": content.trim() }));
+            }
+            "tool" => converted.push(serde_json::json!({
+                "role": "user",
+                "content": format!("Tool result:\n{}", message["content"].as_str().unwrap_or("")),
+            })),
+            _ => converted.push(message.clone()),
+        }
+    }
+    converted
+}
+
+/// Pull fenced tool calls out of a reply. Returns the text without them and
+/// the calls in the same shape as native `tool_calls`.
+pub fn parse_calls(content: &str) -> (String, Vec<serde_json::Value>) {
+    let opening = format!("```{}", FENCE);
+    let mut text = String::new();
+    let mut calls = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find(&opening) {
+        let after = &rest[start + opening.len()..];
+        let Some(end) = after.find("```") else {
+            break;
+        };
+        let Ok(block) = serde_json::from_str::<serde_json::Value>(after[..end].trim()) else {
+            // Not a call; keep it as text
+            text.push_str(&rest[..start + opening.len() + end + 3]);
+            rest = &after[end + 3..];
+            continue;
+        };
+        text.push_str(&rest[..start]);
+        rest = &after[end + 3..];
+        let Some(name) = block["name"].as_str() else {
+            continue;
+        };
+        let arguments = if block["arguments"].is_object() {
+            block["arguments"].clone()
+        } else {
+            serde_json::json!({})
+        };
+        calls.push(serde_json::json!({
+            "id": format!("text_{}", Uuid::new_v4().simple()),
+            "type": "function",
+            "function": { "name": name, "arguments": arguments.to_string() },
+        }));
+    }
+    text.push_str(rest);
+    (text.trim().to_string(), calls)
+}
+
+/// An API error that means the model or server doesn't accept `tools`.
+pub fn rejects_tools(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.starts_with("api error 4")
+        && error.contains("tool")
+        && ["not support", "unsupported", "unknown field", "not allowed"]
+            .iter()
+            .any(|phrase| error.contains(phrase))
+}
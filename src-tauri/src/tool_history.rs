@@ -0,0 +1,256 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::search::{fold, match_snippet};
+use crate::{llm, policy, AppState, GuiSettings};
+
+const DEFAULT_LIMIT: usize = 50;
+const SUMMARY_SNIPPET_CHARS: usize = 160;
+
+/// One tool call from a session transcript, with its result.
+#[derive(Clone, Serialize)]
+pub struct ToolExecution {
+    pub session_id: String,
+    pub session_title: String,
+    pub work_dir: String,
+    /// Position of the tool result in the session transcript
+    pub message_index: usize,
+    pub tool: String,
+    pub arguments: serde_json::Value,
+    /// The command a Shell call ran, so it can be run again
+    pub command: Option<String>,
+    pub ok: bool,
+    pub summary: String,
+    pub snippet: String,
+    pub timestamp: i64,
+}
+
+#[derive(Serialize)]
+pub struct ToolRerun {
+    pub ok: bool,
+    pub summary: String,
+    pub output: String,
+}
+
+/// Name and arguments of every tool call the assistant made, by call id.
+fn calls_by_id(messages: &[crate::Message]) -> HashMap<&str, (&str, serde_json::Value)> {
+    messages
+        .iter()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .map(|call| {
+            let arguments = serde_json::from_str(&call.arguments)
+                .unwrap_or_else(|_| serde_json::Value::String(call.arguments.clone()));
+            (call.id.as_str(), (call.name.as_str(), arguments))
+        })
+        .collect()
+}
+
+fn session_executions(
+    session: &crate::Session,
+    terms: &[Vec<char>],
+    tool: Option<&str>,
+    found: &mut Vec<ToolExecution>,
+) {
+    let calls = calls_by_id(&session.messages);
+    for (index, message) in session.messages.iter().enumerate() {
+        if message.role != "tool" {
+            continue;
+        }
+        let Some((name, arguments)) = message.tool_call_id.as_deref().and_then(|id| calls.get(id))
+        else {
+            continue;
+        };
+        if tool.is_some_and(|tool| !tool.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        let result: serde_json::Value = serde_json::from_str(&message.content).unwrap_or_default();
+        let summary = result
+            .get("summary")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&message.content)
+            .to_string();
+        let output = result.get("output").and_then(|v| v.as_str()).unwrap_or("");
+        let snippet = if terms.is_empty() {
+            crate::truncate_with_ellipsis(&summary, SUMMARY_SNIPPET_CHARS)
+        } else {
+            let haystack = format!("{} {}\n{}\n{}", name, arguments, summary, output);
+            match match_snippet(&haystack, terms) {
+                Some(snippet) => snippet,
+                None => continue,
+            }
+        };
+        found.push(ToolExecution {
+            session_id: session.id.clone(),
+            session_title: session.title.clone(),
+            work_dir: session.work_dir.clone(),
+            message_index: index,
+            tool: name.to_string(),
+            command: (*name == "Shell")
+                .then(|| arguments.get("command").and_then(|v| v.as_str()))
+                .flatten()
+                .map(str::to_string),
+            arguments: arguments.clone(),
+            ok: result.get("ok").and_then(|v| v.as_bool()).unwrap_or(true),
+            summary,
+            snippet,
+            timestamp: message.timestamp,
+        });
+    }
+}
+
+/// Past tool calls whose name, arguments or result contain every term of
+/// `query`, newest first. An empty query lists the latest calls. Covers GUI
+/// sessions, optionally only those in one work dir and its worktrees.
+#[tauri::command]
+pub fn tool_history_search(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    work_dir: Option<String>,
+    tool: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ToolExecution>, String> {
+    let terms: Vec<Vec<char>> = query.split_whitespace().map(fold).collect();
+    let tool = tool.filter(|t| !t.is_empty());
+    let target = work_dir
+        .as_deref()
+        .and_then(|wd| crate::git::repo_identity(std::path::Path::new(wd)));
+
+    let sessions = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .load_all_sessions()?;
+    let mut found = Vec::new();
+    for session in &sessions {
+        let in_scope = work_dir
+            .as_deref()
+            .is_none_or(|wd| crate::same_workspace(&session.work_dir, wd, target.as_ref()));
+        if in_scope {
+            session_executions(session, &terms, tool.as_deref(), &mut found);
+        }
+    }
+    found.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    found.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(found)
+}
+
+/// Run a Shell command from the history again in `session_id`'s work dir. It
+/// goes through the same approval decision as a call from the model, and
+/// emits the usual `tool_status` events.
+#[tauri::command]
+pub async fn tool_history_rerun(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    command: String,
+    work_dir: Option<String>,
+    settings: Option<GuiSettings>,
+) -> Result<ToolRerun, String> {
+    let settings = settings.unwrap_or_default();
+    let session_work_dir = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?
+        .find_session(&session_id)
+        .map(|session| session.work_dir);
+    let work_dir = work_dir
+        .or(session_work_dir)
+        .filter(|wd| !wd.is_empty())
+        .ok_or_else(|| "Session not found".to_string())?;
+    let config_path = settings
+        .config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::app_paths().config));
+    let policy_settings = settings.command_policy.clone().unwrap_or_default();
+    let command_policy = policy::resolve(
+        &state,
+        &session_id,
+        &policy_settings,
+        settings.yolo.unwrap_or(false),
+    );
+    let approval_hook = settings.approval_hook.clone().unwrap_or_default();
+    let hooks = settings.hooks.clone().unwrap_or_default();
+    let tool_context = llm::ToolContext {
+        work_dir: &work_dir,
+        config_path: config_path.as_deref(),
+        dry_run: false,
+        env: &settings.env,
+        shell: settings.shell_interpreter,
+        policy: command_policy.as_ref(),
+        disabled_tools: &settings.disabled_tools,
+        turn: None,
+        hooks: Some(&hooks),
+    };
+    let tool_call_id = Uuid::new_v4().to_string();
+    let args = serde_json::json!({ "command": command });
+
+    let (stream_id, mut cancel_rx) = crate::register_stream(&state, &session_id)?;
+    let result = async {
+        let request = llm::ApprovalRequest {
+            session_id: &session_id,
+            tool_call_id: &tool_call_id,
+            name: "Shell",
+            args: &args,
+            work_dir: &work_dir,
+            config_path: config_path.as_deref(),
+        };
+        let rules = llm::ApprovalRules {
+            command_policy: command_policy.as_ref(),
+            directory_rules: &policy_settings.directories,
+            tool_approvals: &settings.tool_approvals,
+            approval_hook: &approval_hook,
+            dry_run: false,
+        };
+        if let Some(summary) =
+            llm::decide_approval(&window, &state, &request, &rules, &mut cancel_rx).await?
+        {
+            return Ok(ToolRerun {
+                ok: false,
+                summary,
+                output: String::new(),
+            });
+        }
+
+        let label = llm::tool_label("Shell", &args);
+        llm::emit_tool_status(
+            &window,
+            &session_id,
+            &tool_call_id,
+            "start",
+            "Shell",
+            &label,
+            None,
+            None,
+        );
+        let output = llm::execute_tool(
+            &window,
+            &state,
+            &session_id,
+            &tool_call_id,
+            "Shell",
+            &args,
+            &tool_context,
+        )
+        .await;
+        llm::emit_tool_status(
+            &window,
+            &session_id,
+            &tool_call_id,
+            "end",
+            "Shell",
+            &label,
+            Some(output.ok),
+            Some(output.summary.clone()),
+        );
+        Ok(ToolRerun {
+            ok: output.ok,
+            summary: output.summary,
+            output: output.output,
+        })
+    }
+    .await;
+    crate::unregister_stream(&state, stream_id);
+    result
+}
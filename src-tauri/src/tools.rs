@@ -1,19 +1,47 @@
 use reqwest::header::CONTENT_TYPE;
-use serde::Deserialize;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 
+use crate::encoding;
+use crate::file_writes;
 use crate::oauth::common_headers;
+use crate::paths::{self, Access};
 const MAX_LINES: usize = 1000;
 const MAX_LINE_LENGTH: usize = 2000;
 const MAX_BYTES: usize = 100_000;
 const MAX_OUTPUT_CHARS: usize = 50_000;
 const MAX_OUTPUT_LINE_LENGTH: usize = 2000;
 const TRUNCATION_MARKER: &str = "[...truncated]";
+// Caps used in low-bandwidth mode
+const LOW_BANDWIDTH_MAX_LINES: usize = 200;
+const LOW_BANDWIDTH_MAX_BYTES: usize = 20_000;
+const LOW_BANDWIDTH_MAX_OUTPUT_CHARS: usize = 8_000;
+const LOW_BANDWIDTH_MAX_OUTPUT_LINE_LENGTH: usize = 500;
+
+/// (max lines, max bytes) returned by ReadFile.
+fn read_limits() -> (usize, usize) {
+    if crate::network::is_low_bandwidth() {
+        (LOW_BANDWIDTH_MAX_LINES, LOW_BANDWIDTH_MAX_BYTES)
+    } else {
+        (MAX_LINES, MAX_BYTES)
+    }
+}
+
+/// (max chars, max line length) of command and web tool output.
+fn output_limits() -> (usize, usize) {
+    if crate::network::is_low_bandwidth() {
+        (LOW_BANDWIDTH_MAX_OUTPUT_CHARS, LOW_BANDWIDTH_MAX_OUTPUT_LINE_LENGTH)
+    } else {
+        (MAX_OUTPUT_CHARS, MAX_OUTPUT_LINE_LENGTH)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ToolOutput {
@@ -22,31 +50,22 @@ pub struct ToolOutput {
     pub output: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct SearchResponse {
-    #[serde(default)]
-    search_results: Vec<SearchResult>,
-}
-
-#[derive(Debug, Deserialize)]
-struct SearchResult {
-    #[serde(default)]
-    title: String,
-    #[serde(default)]
-    url: String,
-    #[serde(default)]
-    snippet: String,
-    #[serde(default)]
-    content: String,
-    #[serde(default)]
-    date: String,
-}
-
 #[derive(Clone, Debug)]
-struct ServiceConfig {
-    base_url: String,
+pub struct ServiceConfig {
+    pub base_url: String,
     api_key: String,
-    custom_headers: HashMap<String, String>,
+    /// Named credential from the auth store, used instead of `api_key`
+    credential: Option<String>,
+    pub custom_headers: HashMap<String, String>,
+}
+
+impl ServiceConfig {
+    pub async fn token(&self) -> Result<String, String> {
+        match &self.credential {
+            Some(name) => crate::credentials::resolve(name).await.map(|c| c.token),
+            None => Ok(self.api_key.clone()),
+        }
+    }
 }
 
 fn home_dir() -> PathBuf {
@@ -69,7 +88,7 @@ fn parse_config_content(path: &Path, raw: &str) -> Result<serde_json::Value, Str
     }
 }
 
-fn load_config_value(config_path: Option<&str>) -> Result<serde_json::Value, String> {
+pub fn load_config_value(config_path: Option<&str>) -> Result<serde_json::Value, String> {
     let path = config_path
         .map(PathBuf::from)
         .unwrap_or_else(default_config_path);
@@ -78,11 +97,19 @@ fn load_config_value(config_path: Option<&str>) -> Result<serde_json::Value, Str
     parse_config_content(&path, &raw)
 }
 
-fn parse_service_config(value: &serde_json::Value, key: &str) -> Option<ServiceConfig> {
+pub fn parse_service_config(value: &serde_json::Value, key: &str) -> Option<ServiceConfig> {
     let services = value.get("services")?;
     let service = services.get(key)?;
     let base_url = service.get("base_url")?.as_str()?.to_string();
-    let api_key = service.get("api_key")?.as_str()?.to_string();
+    let credential = service
+        .get("credential")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let api_key = service.get("api_key").and_then(|v| v.as_str()).map(str::to_string);
+    // A service needs its own key or a named credential
+    if api_key.is_none() && credential.is_none() {
+        return None;
+    }
     let custom_headers = service
         .get("custom_headers")
         .and_then(|v| v.as_object())
@@ -94,58 +121,17 @@ fn parse_service_config(value: &serde_json::Value, key: &str) -> Option<ServiceC
         .unwrap_or_default();
     Some(ServiceConfig {
         base_url,
-        api_key,
+        api_key: api_key.unwrap_or_default(),
+        credential,
         custom_headers,
     })
 }
 
-fn resolve_path(work_dir: &str, path: &str, must_exist: bool) -> Result<PathBuf, String> {
-    if path.trim().is_empty() {
-        return Err("Path cannot be empty".to_string());
-    }
-
-    let input_path = Path::new(path);
-    let base = Path::new(work_dir);
-    let target = if input_path.is_absolute() {
-        input_path.to_path_buf()
-    } else {
-        base.join(input_path)
-    };
-
-    if must_exist {
-        let canonical = target
-            .canonicalize()
-            .map_err(|e| format!("Failed to resolve path: {e}"))?;
-        if !input_path.is_absolute() {
-            let canonical_root = base
-                .canonicalize()
-                .map_err(|e| format!("Failed to resolve work dir: {e}"))?;
-            if !canonical.starts_with(&canonical_root) {
-                return Err("Path is outside working directory".to_string());
-            }
-        }
-        return Ok(canonical);
-    }
-
-    if !input_path.is_absolute() {
-        let canonical_root = base
-            .canonicalize()
-            .map_err(|e| format!("Failed to resolve work dir: {e}"))?;
-        let parent = target
-            .parent()
-            .ok_or_else(|| "Invalid path".to_string())?;
-        let canonical_parent = parent
-            .canonicalize()
-            .map_err(|e| format!("Failed to resolve path: {e}"))?;
-        if !canonical_parent.starts_with(&canonical_root) {
-            return Err("Path is outside working directory".to_string());
-        }
-    }
-
-    Ok(target)
+fn resolve_path(work_dir: &str, path: &str, access: Access) -> Result<PathBuf, String> {
+    paths::resolve(work_dir, path, access).map(|resolved| resolved.path)
 }
 
-fn truncate_line(line: &str) -> (String, bool) {
+pub fn truncate_line(line: &str) -> (String, bool) {
     let line_len = line.chars().count();
     if line_len <= MAX_LINE_LENGTH {
         return (line.to_string(), false);
@@ -162,13 +148,14 @@ fn truncate_line(line: &str) -> (String, bool) {
     (out, true)
 }
 
-fn truncate_output(text: &str) -> (String, bool) {
+pub fn truncate_output(text: &str) -> (String, bool) {
+    let (max_output_chars, max_line_length) = output_limits();
     let mut output = String::new();
     let mut total_chars = 0usize;
     let mut truncated = false;
 
     for line in text.split_inclusive('\n') {
-        if total_chars >= MAX_OUTPUT_CHARS {
+        if total_chars >= max_output_chars {
             truncated = true;
             break;
         }
@@ -182,12 +169,12 @@ fn truncate_output(text: &str) -> (String, bool) {
         };
 
         let (line_text, line_truncated) =
-            truncate_output_line(line_body, MAX_OUTPUT_LINE_LENGTH, line_break);
+            truncate_output_line(line_body, max_line_length, line_break);
         if line_truncated {
             truncated = true;
         }
 
-        let remaining = MAX_OUTPUT_CHARS.saturating_sub(total_chars);
+        let remaining = max_output_chars.saturating_sub(total_chars);
         if remaining == 0 {
             truncated = true;
             break;
@@ -228,7 +215,7 @@ fn truncate_output_line(line: &str, max_len: usize, line_break: &str) -> (String
     (out, true)
 }
 
-fn append_truncation(summary: String, truncated: bool) -> String {
+pub fn append_truncation(summary: String, truncated: bool) -> String {
     if truncated {
         if summary.is_empty() {
             "Output is truncated to fit in the message.".to_string()
@@ -242,124 +229,467 @@ fn append_truncation(summary: String, truncated: bool) -> String {
     }
 }
 
+/// Names of the tools defined by `tool_definitions`.
+pub fn builtin_tool_names() -> Vec<String> {
+    tool_definitions()
+        .iter()
+        .filter_map(|tool| tool["function"]["name"].as_str().map(str::to_string))
+        .collect()
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GrepArgs {
+    /// Regular expression to search for (Rust regex syntax).
+    pub pattern: String,
+    /// File or folder to search, relative to the working directory. Defaults to all of it.
+    pub path: Option<String>,
+    /// Only search files matching this glob, e.g. `*.rs` or `src/**/*.ts`.
+    pub glob: Option<String>,
+    /// Match regardless of case.
+    pub ignore_case: Option<bool>,
+    /// Lines to show before and after each match.
+    #[schemars(range(max = 10))]
+    pub context: Option<usize>,
+    /// Most matching lines to return.
+    #[schemars(range(min = 1))]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GlobArgs {
+    /// Glob pattern, e.g. `src/**/*.rs` or `*.toml`. Patterns without a slash match at any depth.
+    pub pattern: String,
+    /// Folder to match the pattern in, relative to the working directory. Defaults to all of it.
+    pub path: Option<String>,
+    /// Most paths to return.
+    #[schemars(range(min = 1))]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadFileArgs {
+    /// File path to read.
+    pub path: String,
+    /// Line number to start from.
+    #[schemars(range(min = 1))]
+    pub line_offset: Option<usize>,
+    /// Number of lines to read.
+    #[schemars(range(min = 1))]
+    pub n_lines: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ShellArgs {
+    /// Shell command to execute.
+    pub command: String,
+    /// Timeout in seconds.
+    #[schemars(range(min = 1))]
+    pub timeout: Option<u64>,
+    /// Interpreter to run the command with; the configured one when omitted.
+    pub interpreter: Option<Interpreter>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ProjectCommandArgs {
+    /// Extra arguments appended to the project's command, e.g. a test name filter.
+    pub args: Option<String>,
+    /// Timeout in seconds (default 600).
+    #[schemars(range(min = 1))]
+    pub timeout: Option<u64>,
+}
+
+/// Interpreter the Shell tool runs commands with.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpreter {
+    /// The login shell on Unix; Git Bash or WSL bash on Windows
+    Bash,
+    /// pwsh, or Windows PowerShell when pwsh isn't installed
+    Powershell,
+    /// cmd.exe; Windows only
+    Cmd,
+}
+
+impl Interpreter {
+    /// PowerShell on Windows, the login shell elsewhere.
+    pub fn platform_default() -> Self {
+        if cfg!(windows) {
+            Interpreter::Powershell
+        } else {
+            Interpreter::Bash
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RunInBackgroundArgs {
+    /// Shell command to start, e.g. a dev server or a long build.
+    pub command: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ListBackgroundProcessesArgs {}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ProcessIdArgs {
+    /// Process id returned by RunInBackground.
+    pub id: String,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteMode {
+    #[default]
+    Overwrite,
+    Append,
+}
+
+impl WriteMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WriteMode::Overwrite => "overwrite",
+            WriteMode::Append => "append",
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct WriteFileArgs {
+    /// File path to write, relative to the working directory. Relative paths
+    /// can't leave it; absolute paths outside it always need the user's approval.
+    pub path: String,
+    /// Content to write.
+    pub content: String,
+    /// Write mode.
+    pub mode: Option<WriteMode>,
+}
+
+/// One edit or a list of them
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum EditArg {
+    One(ReplaceEdit),
+    Many(Vec<ReplaceEdit>),
+}
+
+impl EditArg {
+    pub fn into_edits(self) -> Vec<ReplaceEdit> {
+        match self {
+            EditArg::One(edit) => vec![edit],
+            EditArg::Many(edits) => edits,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StrReplaceFileArgs {
+    /// File path to edit, relative to the working directory. Relative paths
+    /// can't leave it; absolute paths outside it always need the user's approval.
+    pub path: String,
+    pub edit: EditArg,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ApplyPatchArgs {
+    /// Unified diff with `--- a/path` and `+++ b/path` headers and `@@` hunks,
+    /// as `git diff` prints it. Paths are relative to the working directory;
+    /// `/dev/null` creates or deletes a file.
+    pub patch: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct MoveFileArgs {
+    /// File to move, relative to the working directory.
+    pub source: String,
+    /// New path of the file. Missing parent directories are created.
+    pub destination: String,
+    /// Replace the destination if it already exists.
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CopyFileArgs {
+    /// File to copy, relative to the working directory.
+    pub source: String,
+    /// Path of the copy. Missing parent directories are created.
+    pub destination: String,
+    /// Replace the destination if it already exists.
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteFileArgs {
+    /// File or directory to delete, relative to the working directory.
+    pub path: String,
+    /// Move it to the system trash so it can be recovered (default true). Only files, links and empty directories can be deleted; a link is removed itself, not what it points to.
+    pub trash: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateDirectoryArgs {
+    /// Directory to create, relative to the working directory. Missing parents are created too.
+    pub path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SearchWebArgs {
+    /// Search query.
+    pub query: String,
+    /// Number of results.
+    #[schemars(range(min = 1))]
+    pub limit: Option<usize>,
+    /// Include page content in results.
+    pub include_content: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FetchUrlArgs {
+    /// URL to fetch.
+    pub url: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DownloadFileArgs {
+    /// http or https URL to download.
+    pub url: String,
+    /// Where to save the file, relative to the working directory.
+    pub path: String,
+    /// Replace the file if it already exists.
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadIssueArgs {
+    /// Issue number, without the `#`.
+    pub number: u64,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadPullRequestArgs {
+    /// Pull request number (the merge request's `!` number on GitLab).
+    pub number: u64,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreatePullRequestArgs {
+    /// Title of the pull request.
+    pub title: String,
+    /// Description in Markdown. Mention issues it closes, e.g. "Fixes #12".
+    pub body: Option<String>,
+    /// Branch to merge into. Defaults to the repository's default branch.
+    pub base: Option<String>,
+    /// Open it as a draft.
+    pub draft: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DocsSearchArgs {
+    /// Keywords to look for.
+    pub query: String,
+    /// Number of sections to return.
+    #[schemars(range(min = 1))]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CodeOutlineArgs {
+    /// Source file to outline, relative to the working directory.
+    pub path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FindSymbolArgs {
+    /// Name or part of a name to look for, case-insensitive.
+    pub name: String,
+    /// Only declarations of this kind, e.g. `function`, `method`, `class`, `struct` or `trait`.
+    pub kind: Option<String>,
+    /// File or folder to search, relative to the working directory. Defaults to all of it.
+    pub path: Option<String>,
+    /// Most declarations to return.
+    #[schemars(range(min = 1))]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SemanticSearchArgs {
+    /// What to look for, in natural language.
+    pub query: String,
+    /// Number of chunks to return.
+    #[schemars(range(min = 1))]
+    pub limit: Option<usize>,
+}
+
+/// Parse a tool call's arguments into the struct its schema came from.
+/// Paths a file-editing call writes, as given in its arguments.
+pub fn edit_paths(name: &str, args: &serde_json::Value) -> Vec<String> {
+    match name {
+        "WriteFile" | "StrReplaceFile" | "DeleteFile" | "DownloadFile" => args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        "CopyFile" => args
+            .get("destination")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        "MoveFile" => ["source", "destination"]
+            .iter()
+            .filter_map(|key| args.get(*key).and_then(|v| v.as_str()).map(str::to_string))
+            .collect(),
+        "ApplyPatch" => args
+            .get("patch")
+            .and_then(|v| v.as_str())
+            .map(crate::patch::target_paths)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn parse_args<T: DeserializeOwned>(name: &str, args: &serde_json::Value) -> Result<T, ToolOutput> {
+    serde_json::from_value(args.clone()).map_err(|err| ToolOutput {
+        ok: false,
+        summary: format!("Invalid arguments for {}: {}", name, err),
+        output: String::new(),
+    })
+}
+
+/// Drop schemars' `"format": "uint"`-style hints, which some providers reject.
+fn strip_formats(schema: &mut serde_json::Value) {
+    match schema {
+        serde_json::Value::Object(object) => {
+            if object.get("type").and_then(|t| t.as_str()) == Some("integer") {
+                object.remove("format");
+            }
+            object.values_mut().for_each(strip_formats);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_formats),
+        _ => {}
+    }
+}
+
+/// Function definition whose parameters are the JSON Schema of `T`, inlined
+/// since not every provider resolves `$ref`.
+fn typed_tool<T: JsonSchema>(name: &str, description: &str) -> serde_json::Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.option_nullable = false;
+            settings.option_add_null_type = false;
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let mut parameters =
+        serde_json::to_value(generator.into_root_schema_for::<T>()).unwrap_or_default();
+    if let Some(object) = parameters.as_object_mut() {
+        object.remove("title");
+        object.remove("definitions");
+    }
+    strip_formats(&mut parameters);
+    serde_json::json!({
+        "type": "function",
+        "function": {
+            "name": name,
+            "description": description,
+            "parameters": parameters
+        }
+    })
+}
+
 pub fn tool_definitions() -> Vec<serde_json::Value> {
     vec![
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "ReadFile",
-                "description": "Read the contents of a text file from disk.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string", "description": "File path to read." },
-                        "line_offset": { "type": "integer", "description": "Line number to start from.", "minimum": 1 },
-                        "n_lines": { "type": "integer", "description": "Number of lines to read.", "minimum": 1 }
-                    },
-                    "required": ["path"]
-                }
-            }
-        }),
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "Shell",
-                "description": "Run a shell command in the working directory.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "command": { "type": "string", "description": "Shell command to execute." },
-                        "timeout": { "type": "integer", "description": "Timeout in seconds.", "minimum": 1 }
-                    },
-                    "required": ["command"]
-                }
-            }
-        }),
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "WriteFile",
-                "description": "Write content to a file (overwrite or append).",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string", "description": "File path to write." },
-                        "content": { "type": "string", "description": "Content to write." },
-                        "mode": { "type": "string", "enum": ["overwrite", "append"], "description": "Write mode." }
-                    },
-                    "required": ["path", "content"]
-                }
-            }
-        }),
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "StrReplaceFile",
-                "description": "Replace specific strings in a file.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string", "description": "File path to edit." },
-                        "edit": {
-                            "oneOf": [
-                                {
-                                    "type": "object",
-                                    "properties": {
-                                        "old": { "type": "string" },
-                                        "new": { "type": "string" },
-                                        "replace_all": { "type": "boolean" }
-                                    },
-                                    "required": ["old", "new"]
-                                },
-                                {
-                                    "type": "array",
-                                    "items": {
-                                        "type": "object",
-                                        "properties": {
-                                            "old": { "type": "string" },
-                                            "new": { "type": "string" },
-                                            "replace_all": { "type": "boolean" }
-                                        },
-                                        "required": ["old", "new"]
-                                    }
-                                }
-                            ]
-                        }
-                    },
-                    "required": ["path", "edit"]
-                }
-            }
-        }),
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "SearchWeb",
-                "description": "Search the web using the configured search service.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "query": { "type": "string", "description": "Search query." },
-                        "limit": { "type": "integer", "description": "Number of results.", "minimum": 1 },
-                        "include_content": { "type": "boolean", "description": "Include page content in results." }
-                    },
-                    "required": ["query"]
-                }
-            }
-        }),
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": "FetchURL",
-                "description": "Fetch the contents of a URL.",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "url": { "type": "string", "description": "URL to fetch." }
-                    },
-                    "required": ["url"]
-                }
-            }
-        }),
+        typed_tool::<ReadFileArgs>("ReadFile", "Read the contents of a text file from disk."),
+        typed_tool::<GrepArgs>(
+            "Grep",
+            "Search file contents in the working directory with a regular expression. Skips gitignored and binary files and needs no approval, so prefer it over grep in Shell. Results are `path:line:text`, with context lines as `path-line-text`.",
+        ),
+        typed_tool::<GlobArgs>(
+            "Glob",
+            "Find files in the working directory by glob pattern, most recently modified first. Skips gitignored files and needs no approval, so prefer it over find or ls in Shell.",
+        ),
+        typed_tool::<CodeOutlineArgs>(
+            "CodeOutline",
+            "List the functions, classes, types and other declarations in a Rust, Python, JavaScript, TypeScript or Go file, with signatures and line ranges. Use it to find your way around a large file, then ReadFile just the lines you need.",
+        ),
+        typed_tool::<FindSymbolArgs>(
+            "FindSymbol",
+            "Find where functions, classes, types and other declarations are defined across the working directory, by name. Results are `path:line: kind signature`, exact names first.",
+        ),
+        typed_tool::<ShellArgs>(
+            "Shell",
+            "Run a shell command. With the bash interpreter on macOS and Linux the shell persists across calls in this session, so `cd` and exported variables carry over. On Windows pick `powershell` (the default), `cmd` or `bash`, and use that interpreter's syntax.",
+        ),
+        typed_tool::<ProjectCommandArgs>(
+            "RunTests",
+            "Run the project's declared test command (from .kimi/commands.toml or AGENTS.md). Prefer it over guessing a test command in Shell.",
+        ),
+        typed_tool::<ProjectCommandArgs>(
+            "Build",
+            "Run the project's declared build command (from .kimi/commands.toml or AGENTS.md). Prefer it over guessing a build command in Shell.",
+        ),
+        typed_tool::<RunInBackgroundArgs>(
+            "RunInBackground",
+            "Start a shell command in the working directory without waiting for it, for dev servers, watchers and long builds. Returns a process id.",
+        ),
+        typed_tool::<ListBackgroundProcessesArgs>(
+            "ListBackgroundProcesses",
+            "List the background processes started in this session and whether they are still running.",
+        ),
+        typed_tool::<ProcessIdArgs>(
+            "ReadProcessOutput",
+            "Read what a background process printed since the last time its output was read.",
+        ),
+        typed_tool::<ProcessIdArgs>("KillProcess", "Stop a background process started in this session."),
+        typed_tool::<WriteFileArgs>("WriteFile", "Write content to a file (overwrite or append)."),
+        typed_tool::<StrReplaceFileArgs>(
+            "StrReplaceFile",
+            "Replace specific strings in a file. Reports the line numbers each edit changed; set `fuzzy` when whitespace may differ and `occurrence` to pick one of several matches.",
+        ),
+        typed_tool::<MoveFileArgs>(
+            "MoveFile",
+            "Move or rename a file inside the working directory. Prefer it over mv in Shell; the change can be rolled back from the file history.",
+        ),
+        typed_tool::<CopyFileArgs>("CopyFile", "Copy a file inside the working directory."),
+        typed_tool::<DeleteFileArgs>(
+            "DeleteFile",
+            "Delete a file or directory inside the working directory. It goes to the system trash by default; prefer it over rm in Shell.",
+        ),
+        typed_tool::<CreateDirectoryArgs>(
+            "CreateDirectory",
+            "Create a directory inside the working directory, with any missing parents.",
+        ),
+        typed_tool::<ApplyPatchArgs>(
+            "ApplyPatch",
+            "Apply a unified diff to one or more files. Every hunk's context is checked against the file first, and nothing is written unless all hunks apply. Prefer it over StrReplaceFile for multi-hunk edits.",
+        ),
+        typed_tool::<SearchWebArgs>("SearchWeb", "Search the web using the configured search provider, or DuckDuckGo when none is configured."),
+        typed_tool::<FetchUrlArgs>("FetchURL", "Fetch the contents of a URL."),
+        typed_tool::<DownloadFileArgs>(
+            "DownloadFile",
+            "Download a URL to a file in the working directory, for fixtures, datasets or release artifacts. Reports the size and SHA-256. Always needs the user's approval; for reading a page use FetchURL instead.",
+        ),
+        typed_tool::<ReadIssueArgs>(
+            "ReadIssue",
+            "Read an issue's description and comments from the GitHub or GitLab project of the origin remote.",
+        ),
+        typed_tool::<ReadPullRequestArgs>(
+            "ReadPullRequest",
+            "Read a pull request's (GitLab merge request's) description, branches and comments, including review comments on the diff, from the origin remote's project.",
+        ),
+        typed_tool::<CreatePullRequestArgs>(
+            "CreatePullRequest",
+            "Open a pull request (a merge request on GitLab) from the current branch. Push the branch with Shell first. Always needs the user's approval.",
+        ),
+        typed_tool::<DocsSearchArgs>(
+            "DocsSearch",
+            "Search the documentation registered for this project. Prefer it over guessing how the project's APIs work.",
+        ),
+        typed_tool::<SemanticSearchArgs>(
+            "SemanticSearch",
+            "Find code in the working directory by meaning rather than exact text, e.g. \"where are auth tokens refreshed\". Returns the best matching file chunks with line ranges.",
+        ),
     ]
 }
 
@@ -369,7 +699,7 @@ pub fn read_file(
     line_offset: usize,
     n_lines: usize,
 ) -> ToolOutput {
-    let resolved = match resolve_path(work_dir, path, true) {
+    let resolved = match resolve_path(work_dir, path, Access::Read) {
         Ok(p) => p,
         Err(err) => {
             return ToolOutput {
@@ -407,8 +737,9 @@ pub fn read_file(
         };
     }
 
-    let file = match fs::File::open(&resolved) {
-        Ok(f) => f,
+    // Undecodable bytes become U+FFFD instead of failing the read
+    let decoded = match encoding::read(&resolved) {
+        Ok(decoded) => decoded,
         Err(err) => {
             return ToolOutput {
                 ok: false,
@@ -418,19 +749,15 @@ pub fn read_file(
         }
     };
 
-    let reader = io::BufReader::new(file);
     let mut lines = Vec::new();
     let mut truncated_lines = Vec::new();
     let mut total_bytes = 0usize;
     let mut line_no = 0usize;
     let start = line_offset.max(1);
-    let max_lines = n_lines.max(1).min(MAX_LINES);
+    let (line_limit, byte_limit) = read_limits();
+    let max_lines = n_lines.max(1).min(line_limit);
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(_) => continue,
-        };
+    for line in decoded.text.lines() {
         line_no += 1;
         if line_no < start {
             continue;
@@ -444,7 +771,7 @@ pub fn read_file(
         total_bytes += truncated.len();
         lines.push((line_no, truncated));
 
-        if lines.len() >= max_lines || total_bytes >= MAX_BYTES {
+        if lines.len() >= max_lines || total_bytes >= byte_limit {
             break;
         }
     }
@@ -464,9 +791,9 @@ pub fn read_file(
         )
     };
 
-    if lines.len() >= MAX_LINES {
+    if lines.len() >= line_limit {
         summary.push_str(" Max lines reached.");
-    } else if total_bytes >= MAX_BYTES {
+    } else if total_bytes >= byte_limit {
         summary.push_str(" Max bytes reached.");
     }
 
@@ -474,6 +801,18 @@ pub fn read_file(
         summary.push_str(&format!(" Lines {:?} were truncated.", truncated_lines));
     }
 
+    if decoded.lossy {
+        summary.push_str(&format!(
+            " The file is not valid {} text; undecodable bytes are shown as U+FFFD and edits with StrReplaceFile are refused.",
+            decoded.format.encoding.label()
+        ));
+    } else if decoded.format.encoding != encoding::Encoding::Utf8 {
+        summary.push_str(&format!(
+            " Decoded from {}; edits keep the encoding.",
+            decoded.format.encoding.label()
+        ));
+    }
+
     ToolOutput {
         ok: true,
         summary,
@@ -481,8 +820,134 @@ pub fn read_file(
     }
 }
 
-pub async fn run_shell(work_dir: &str, command: &str, timeout_secs: u64) -> ToolOutput {
-    if command.trim().is_empty() {
+/// Receives command output as it is printed, for live display.
+pub type OutputSink<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+/// Pass the complete UTF-8 text at the start of `bytes` to `on_output`,
+/// leaving a character split across reads for the next call. Returns the
+/// number of bytes passed on.
+fn emit_text(bytes: &[u8], on_output: OutputSink<'_>) -> usize {
+    let ready = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        // Not UTF-8 at all; waiting won't help
+        Err(_) => bytes.len(),
+    };
+    if ready > 0 {
+        on_output(&String::from_utf8_lossy(&bytes[..ready]));
+    }
+    ready
+}
+
+async fn read_streaming(
+    reader: Option<impl tokio::io::AsyncRead + Unpin>,
+    on_output: OutputSink<'_>,
+) -> String {
+    use tokio::io::AsyncReadExt;
+    let Some(mut reader) = reader else {
+        return String::new();
+    };
+    let mut output = Vec::new();
+    let mut emitted = 0;
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        output.extend_from_slice(&buf[..n]);
+        emitted += emit_text(&output[emitted..], on_output);
+    }
+    String::from_utf8_lossy(&output).to_string()
+}
+
+/// A Shell tool call with its defaults filled in.
+pub struct ShellCall<'a> {
+    pub command: &'a str,
+    pub timeout_secs: u64,
+    pub interpreter: Interpreter,
+    pub env: &'a HashMap<String, String>,
+}
+
+/// First `names` entry found on PATH.
+fn find_program(names: &[&str]) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        names.iter().find_map(|name| {
+            let candidates = if cfg!(windows) {
+                vec![dir.join(format!("{name}.exe")), dir.join(name)]
+            } else {
+                vec![dir.join(name)]
+            };
+            candidates.into_iter().find(|candidate| candidate.is_file())
+        })
+    })
+}
+
+fn program_name(program: &Path) -> String {
+    program
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| program.to_string_lossy().to_string())
+}
+
+/// Command running `command` with `interpreter`, and the name of the program
+/// used, which the tool summary reports.
+fn interpreter_command(interpreter: Interpreter, command: &str) -> Result<(Command, String), String> {
+    // Models send \r\n from Windows-style examples; shells choke on the \r
+    let command = command.replace("\r\n", "\n");
+    match interpreter {
+        Interpreter::Bash if !cfg!(windows) => {
+            let (shell, args) = shell_command(&command);
+            let mut cmd = Command::new(&shell);
+            cmd.args(args);
+            Ok((cmd, program_name(Path::new(&shell))))
+        }
+        Interpreter::Bash => {
+            let bash = find_program(&["bash"]).ok_or_else(|| {
+                "bash was not found on PATH; install Git for Windows or use the powershell interpreter".to_string()
+            })?;
+            // Not a login shell: Git Bash's profile would cd to the home folder
+            let mut cmd = Command::new(&bash);
+            cmd.args(["-c", &command]);
+            Ok((cmd, program_name(&bash)))
+        }
+        Interpreter::Powershell => {
+            let program = find_program(&["pwsh", "powershell"]).ok_or_else(|| {
+                "PowerShell was not found on PATH; install pwsh or pick another interpreter".to_string()
+            })?;
+            // UTF-8 output, and a failing native command fails the call
+            let script = format!(
+                "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8\n{command}\nif ($LASTEXITCODE) {{ exit $LASTEXITCODE }}"
+            );
+            let mut cmd = Command::new(&program);
+            cmd.args(["-NoLogo", "-NoProfile", "-NonInteractive", "-Command", &script]);
+            Ok((cmd, program_name(&program)))
+        }
+        Interpreter::Cmd => {
+            #[cfg(windows)]
+            {
+                // cmd runs one line; /S strips the outer quotes and keeps the
+                // command's own quoting (paths with spaces) intact
+                let line = command
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" & ");
+                let mut cmd = Command::new("cmd");
+                cmd.raw_arg(format!("/D /S /C \"{line}\""));
+                Ok((cmd, "cmd".to_string()))
+            }
+            #[cfg(not(windows))]
+            {
+                Err("The cmd interpreter is only available on Windows".to_string())
+            }
+        }
+    }
+}
+
+pub async fn run_shell(work_dir: &str, call: &ShellCall<'_>, on_output: OutputSink<'_>) -> ToolOutput {
+    if call.command.trim().is_empty() {
         return ToolOutput {
             ok: false,
             summary: "Command cannot be empty".to_string(),
@@ -490,58 +955,365 @@ pub async fn run_shell(work_dir: &str, command: &str, timeout_secs: u64) -> Tool
         };
     }
 
-    let (shell, args) = shell_command(command);
-    let mut cmd = Command::new(shell);
-    cmd.args(args).current_dir(work_dir);
-
-    let result = timeout(Duration::from_secs(timeout_secs), cmd.output()).await;
-    match result {
-        Ok(Ok(output)) => {
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let mut combined = String::new();
-            if !stdout.is_empty() {
-                combined.push_str(&stdout);
+    let (mut cmd, program) = match interpreter_command(call.interpreter, call.command) {
+        Ok(command) => command,
+        Err(err) => {
+            return ToolOutput {
+                ok: false,
+                summary: err,
+                output: String::new(),
+            }
+        }
+    };
+    crate::child_env::scrub(&mut cmd);
+    cmd.current_dir(work_dir)
+        .envs(call.env)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            return ToolOutput {
+                ok: false,
+                summary: format!("Failed to execute command with {program}: {err}"),
+                output: String::new(),
             }
+        }
+    };
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let run = async {
+        let (stdout, stderr) = tokio::join!(
+            read_streaming(stdout, on_output),
+            read_streaming(stderr, on_output)
+        );
+        child.wait().await.map(|status| (stdout, stderr, status))
+    };
+
+    let timeout_secs = call.timeout_secs;
+    match timeout(Duration::from_secs(timeout_secs), run).await {
+        Ok(Ok((stdout, stderr, status))) => {
+            let mut combined = stdout;
             if !stderr.is_empty() {
                 if !combined.is_empty() && !combined.ends_with('\n') {
                     combined.push('\n');
                 }
                 combined.push_str(&stderr);
             }
-            let (combined, truncated) = truncate_output(&combined);
+            command_output(&combined, status.code(), &program)
+        }
+        Ok(Err(err)) => ToolOutput {
+            ok: false,
+            summary: format!("Failed to execute command with {program}: {err}"),
+            output: String::new(),
+        },
+        Err(_) => ToolOutput {
+            ok: false,
+            summary: format!("Command timed out after {timeout_secs} seconds ({program})."),
+            output: String::new(),
+        },
+    }
+}
+
+fn command_output(combined: &str, exit_code: Option<i32>, program: &str) -> ToolOutput {
+    let (combined, truncated) = truncate_output(&combined.replace("\r\n", "\n"));
+    if exit_code == Some(0) {
+        ToolOutput {
+            ok: true,
+            summary: append_truncation(format!("Command executed successfully ({program})."), truncated),
+            output: combined,
+        }
+    } else {
+        ToolOutput {
+            ok: false,
+            summary: append_truncation(
+                format!("Command failed with exit code {:?} ({program}).", exit_code),
+                truncated,
+            ),
+            output: combined,
+        }
+    }
+}
+
+/// Long-lived shell a chat session's Shell calls run in, so `cd`, exports and
+/// virtualenv activation carry over from one call to the next.
+struct PersistentShell {
+    child: tokio::process::Child,
+    program: String,
+    stdin: tokio::process::ChildStdin,
+    stdout: tokio::process::ChildStdout,
+    work_dir: String,
+    env: HashMap<String, String>,
+}
+
+/// Printed after each command with its exit status, so the reader knows where
+/// the command's output ends.
+fn done_marker() -> String {
+    format!("__KIMI_SHELL_DONE_{}__:", uuid::Uuid::new_v4().simple())
+}
+
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+impl PersistentShell {
+    async fn spawn(work_dir: &str, env: &HashMap<String, String>) -> Result<Self, String> {
+        let shell = std::env::var("SHELL")
+            .ok()
+            .filter(|shell| {
+                let name = Path::new(shell).file_name().and_then(|n| n.to_str()).unwrap_or("");
+                matches!(name, "bash" | "zsh" | "sh" | "dash" | "ksh")
+            })
+            .unwrap_or_else(|| "/bin/bash".to_string());
+        let program = program_name(Path::new(&shell));
+        let mut cmd = Command::new(shell);
+        crate::child_env::scrub(&mut cmd);
+        cmd.arg("-l")
+            .current_dir(work_dir)
+            .envs(env)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| format!("Failed to start shell: {err}"))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err("Failed to connect to shell".to_string());
+        };
+        let mut shell = Self {
+            child,
+            program,
+            stdin,
+            stdout,
+            work_dir: work_dir.to_string(),
+            env: env.clone(),
+        };
+        // Send stderr through stdout and swallow whatever the login profile prints
+        shell.send("exec 2>&1").await?;
+        shell.run(":", Duration::from_secs(30), &|_| {}).await?;
+        Ok(shell)
+    }
+
+    async fn send(&mut self, line: &str) -> Result<(), String> {
+        use tokio::io::AsyncWriteExt;
+        self.stdin
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(|err| format!("Shell exited: {err}"))
+    }
 
-            if output.status.success() {
-                ToolOutput {
-                    ok: true,
-                    summary: append_truncation("Command executed successfully.".to_string(), truncated),
-                    output: combined,
+    /// Output and exit code of `command`. Err when the shell died or the
+    /// command timed out; the shell can't be reused either way.
+    async fn run(
+        &mut self,
+        command: &str,
+        limit: Duration,
+        on_output: OutputSink<'_>,
+    ) -> Result<(String, Option<i32>), String> {
+        use tokio::io::AsyncReadExt;
+        let marker = done_marker();
+        // stdin is the command channel, so commands get /dev/null instead
+        self.send(&format!(
+            "eval {} < /dev/null\nprintf '%s%s\\n' '{}' \"$?\"",
+            single_quote(command),
+            marker
+        ))
+        .await?;
+
+        let marker = marker.as_bytes();
+        let mut output = Vec::new();
+        let mut emitted = 0;
+        let read = async {
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = self
+                    .stdout
+                    .read(&mut buf)
+                    .await
+                    .map_err(|err| format!("Failed to read shell output: {err}"))?;
+                if n == 0 {
+                    return Err("The shell exited; the next command starts a fresh one.".to_string());
                 }
-            } else {
-                ToolOutput {
-                    ok: false,
-                    summary: append_truncation(
-                        format!("Command failed with exit code {:?}.", output.status.code()),
-                        truncated,
-                    ),
-                    output: combined,
+                output.extend_from_slice(&buf[..n]);
+                let found = output.windows(marker.len()).position(|window| window == marker);
+                // Hold back what could be the start of a marker split across reads
+                let shown = found.unwrap_or(output.len().saturating_sub(marker.len()));
+                if shown > emitted {
+                    emitted += emit_text(&output[emitted..shown], on_output);
+                }
+                if let Some(at) = found {
+                    let rest = &output[at + marker.len()..];
+                    if let Some(end) = rest.iter().position(|&b| b == b'\n') {
+                        let exit_code = String::from_utf8_lossy(&rest[..end]).trim().parse().ok();
+                        return Ok((String::from_utf8_lossy(&output[..at]).to_string(), exit_code));
+                    }
                 }
             }
+        };
+        match timeout(limit, read).await {
+            Ok(result) => result,
+            Err(_) => Err(format!(
+                "Command timed out after {} seconds. The shell was restarted, so its directory and environment were reset.",
+                limit.as_secs()
+            )),
         }
-        Ok(Err(err)) => ToolOutput {
+    }
+}
+
+impl Drop for PersistentShell {
+    fn drop(&mut self) {
+        crate::processes::kill_tree(&mut self.child);
+    }
+}
+
+/// One persistent shell per chat session, started by its first Shell call.
+#[derive(Default)]
+pub struct ShellSessions {
+    shells: std::sync::Mutex<HashMap<String, std::sync::Arc<tokio::sync::Mutex<Option<PersistentShell>>>>>,
+}
+
+impl ShellSessions {
+    fn slot(&self, session_id: &str) -> std::sync::Arc<tokio::sync::Mutex<Option<PersistentShell>>> {
+        self.shells
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(session_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Kill the session's shell; the next Shell call starts over in the work dir.
+    pub fn reset(&self, session_id: &str) -> bool {
+        self.shells
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session_id)
+            .is_some()
+    }
+}
+
+/// Run a call in the session's persistent shell, starting one in `work_dir`
+/// if there is none yet. A shell left in another work dir or with other extra
+/// env vars is replaced first. Only Unix login shells persist; other
+/// interpreters start fresh for every call.
+pub async fn run_shell_in_session(
+    shells: &ShellSessions,
+    session_id: &str,
+    work_dir: &str,
+    call: &ShellCall<'_>,
+    on_output: OutputSink<'_>,
+) -> ToolOutput {
+    if cfg!(windows) || call.interpreter != Interpreter::Bash {
+        return run_shell(work_dir, call, on_output).await;
+    }
+    if call.command.trim().is_empty() {
+        return ToolOutput {
             ok: false,
-            summary: format!("Failed to execute command: {err}"),
+            summary: "Command cannot be empty".to_string(),
             output: String::new(),
+        };
+    }
+
+    // Calls in one session queue up behind each other
+    let slot = shells.slot(session_id);
+    let mut guard = slot.lock().await;
+    let mut shell = match guard.take() {
+        Some(shell) if shell.work_dir == work_dir && shell.env == *call.env => shell,
+        _ => match PersistentShell::spawn(work_dir, call.env).await {
+            Ok(shell) => shell,
+            Err(err) => {
+                return ToolOutput {
+                    ok: false,
+                    summary: err,
+                    output: String::new(),
+                }
+            }
         },
-        Err(_) => ToolOutput {
+    };
+    let command = call.command.replace("\r\n", "\n");
+    match shell
+        .run(&command, Duration::from_secs(call.timeout_secs), on_output)
+        .await
+    {
+        Ok((output, exit_code)) => {
+            let result = command_output(&output, exit_code, &shell.program);
+            *guard = Some(shell);
+            result
+        }
+        // The shell is dropped, and killed, with this error
+        Err(err) => ToolOutput {
             ok: false,
-            summary: format!("Command timed out after {timeout_secs} seconds."),
+            summary: err,
             output: String::new(),
         },
     }
 }
 
-fn shell_command(command: &str) -> (String, Vec<String>) {
+/// Throw away a session's shell state (directory, exported variables,
+/// activated environments).
+#[tauri::command]
+pub fn reset_shell(state: tauri::State<'_, crate::AppState>, session_id: String) -> bool {
+    state.shells.reset(&session_id)
+}
+
+/// A tool the model can be offered, as listed in the settings' tool matrix.
+#[derive(Serialize)]
+pub struct AvailableTool {
+    pub name: String,
+    pub description: String,
+    /// `builtin`, or the MCP server providing the tool
+    pub source: String,
+    pub enabled: bool,
+}
+
+/// Built-in tools, the config's custom tools and those of the running MCP
+/// servers, with whether `disabled_tools` leaves each one on. Chats hide the
+/// disabled ones from the model and refuse calls to them.
+#[tauri::command]
+pub fn tools_get_available(
+    state: tauri::State<'_, crate::AppState>,
+    disabled_tools: Option<Vec<String>>,
+    config_file: Option<String>,
+) -> Vec<AvailableTool> {
+    let disabled = disabled_tools.unwrap_or_default();
+    let enabled = |name: &str| !disabled.iter().any(|tool| tool == name);
+    let builtin = tool_definitions().into_iter().map(|tool| {
+        let function = &tool["function"];
+        let name = function["name"].as_str().unwrap_or("").to_string();
+        AvailableTool {
+            description: function["description"].as_str().unwrap_or("").to_string(),
+            source: "builtin".to_string(),
+            enabled: enabled(&name),
+            name,
+        }
+    });
+    let custom = crate::custom_tools::load(config_file.as_deref().filter(|path| !path.is_empty()));
+    let mcp = state
+        .mcp
+        .tools()
+        .into_iter()
+        .filter(|tool| !custom.iter().any(|custom| custom.name == tool.name))
+        .map(|tool| AvailableTool {
+            enabled: enabled(&tool.name),
+            name: tool.name,
+            description: tool.description,
+            source: tool.server,
+        })
+        .collect::<Vec<_>>();
+    let custom = custom.into_iter().map(|tool| AvailableTool {
+        enabled: enabled(&tool.name),
+        name: tool.name,
+        description: tool.description,
+        source: "config".to_string(),
+    });
+    builtin.chain(custom).chain(mcp).collect()
+}
+
+pub fn shell_command(command: &str) -> (String, Vec<String>) {
     #[cfg(windows)]
     {
         return ("cmd".to_string(), vec!["/C".to_string(), command.to_string()]);
@@ -554,8 +1326,36 @@ fn shell_command(command: &str) -> (String, Vec<String>) {
     }
 }
 
-pub fn write_file(work_dir: &str, path: &str, content: &str, mode: &str) -> ToolOutput {
-    let resolved = match resolve_path(work_dir, path, false) {
+/// Unified diff of a file change, as returned by mutating tools in dry-run mode.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
+fn dry_run_change(path: &str, original: &str, updated: &str, action: &str) -> ToolOutput {
+    ToolOutput {
+        ok: true,
+        summary: format!("Dry run: file would be {action}. Nothing was written."),
+        output: unified_diff(path, original, updated),
+    }
+}
+
+/// Stand-in result for a shell command in dry-run mode, which can't be
+/// simulated. Not ok, so the model doesn't take tests or builds as passing.
+pub fn dry_run_shell(command: &str) -> ToolOutput {
+    ToolOutput {
+        ok: false,
+        summary: "Dry run: command was not executed, so it has no result. Don't assume it succeeded."
+            .to_string(),
+        output: format!("$ {command}"),
+    }
+}
+
+pub fn write_file(work_dir: &str, path: &str, content: &str, mode: &str, dry_run: bool) -> ToolOutput {
+    let resolved = match resolve_path(work_dir, path, Access::Write) {
         Ok(p) => p,
         Err(err) => {
             return ToolOutput {
@@ -585,33 +1385,36 @@ pub fn write_file(work_dir: &str, path: &str, content: &str, mode: &str) -> Tool
         };
     }
 
-    match mode {
-        "append" => {
-            if let Err(err) = fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&resolved)
-                .and_then(|mut file| {
-                    use std::io::Write;
-                    file.write_all(content.as_bytes())
-                })
-            {
-                return ToolOutput {
-                    ok: false,
-                    summary: format!("Failed to append to file: {err}"),
-                    output: String::new(),
-                };
-            }
-        }
-        _ => {
-            if let Err(err) = fs::write(&resolved, content) {
-                return ToolOutput {
-                    ok: false,
-                    summary: format!("Failed to write file: {err}"),
-                    output: String::new(),
-                };
-            }
+    // An existing file keeps its encoding, BOM and CRLF line endings
+    let existing = encoding::read(&resolved).ok();
+    let format = existing.as_ref().map(|d| d.format).unwrap_or_default();
+    let original = existing.as_ref().map(|d| d.text.as_str()).unwrap_or_default();
+
+    if dry_run {
+        return if mode == "append" {
+            dry_run_change(path, original, &format!("{original}{content}"), "appended to")
+        } else {
+            let action = if resolved.exists() { "overwritten" } else { "created" };
+            dry_run_change(path, original, content, action)
+        };
+    }
+
+    let updated = match (mode, &existing) {
+        // Undecodable bytes are kept as they are by appending to them
+        ("append", Some(existing)) if existing.lossy => {
+            let mut bytes = fs::read(&resolved).unwrap_or_default();
+            bytes.extend_from_slice(content.as_bytes());
+            bytes
         }
+        ("append", _) => encoding::encode(&format!("{original}{content}"), format),
+        _ => encoding::encode(content, format),
+    };
+    if let Err(err) = file_writes::replace(work_dir, &resolved, &updated) {
+        return ToolOutput {
+            ok: false,
+            summary: err,
+            output: String::new(),
+        };
     }
 
     let action = if mode == "append" { "appended to" } else { "overwritten" };
@@ -622,20 +1425,69 @@ pub fn write_file(work_dir: &str, path: &str, content: &str, mode: &str) -> Tool
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct ReplaceEdit {
     pub old: String,
     pub new: String,
+    /// Replace every occurrence instead of only the first.
     #[serde(default)]
     pub replace_all: bool,
+    /// Replace only the Nth occurrence, counting from 1.
+    #[schemars(range(min = 1))]
+    pub occurrence: Option<usize>,
+    /// When `old` isn't found verbatim, match whole lines ignoring
+    /// differences in indentation and spacing.
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+/// A line without its whitespace, for whitespace-insensitive comparison.
+fn squash(line: &str) -> String {
+    line.split_whitespace().collect()
+}
+
+/// Byte ranges of `old` in `text`, matching whole lines with whitespace
+/// ignored. Each range runs from the start of the first line to the end of
+/// the last one, without its line break.
+fn fuzzy_matches(text: &str, old: &str) -> Vec<(usize, usize)> {
+    let old_lines: Vec<String> = old.lines().map(squash).collect();
+    let first = old_lines.iter().position(|l| !l.is_empty());
+    let last = old_lines.iter().rposition(|l| !l.is_empty());
+    let (Some(first), Some(last)) = (first, last) else {
+        return Vec::new();
+    };
+    let old_lines = &old_lines[first..=last];
+
+    // (start, end without line break, squashed text) per line
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n').trim_end_matches('\r');
+        lines.push((offset, offset + content.len(), squash(content)));
+        offset += line.len();
+    }
+
+    let mut ranges = Vec::new();
+    let mut index = 0;
+    while index + old_lines.len() <= lines.len() {
+        let window = &lines[index..index + old_lines.len()];
+        if window.iter().zip(old_lines).all(|(line, old)| &line.2 == old) {
+            ranges.push((window[0].0, window[window.len() - 1].1));
+            index += old_lines.len();
+        } else {
+            index += 1;
+        }
+    }
+    ranges
 }
 
 pub fn str_replace_file(
     work_dir: &str,
     path: &str,
     edits: Vec<ReplaceEdit>,
+    dry_run: bool,
 ) -> ToolOutput {
-    let resolved = match resolve_path(work_dir, path, true) {
+    let resolved = match resolve_path(work_dir, path, Access::Write) {
         Ok(p) => p,
         Err(err) => {
             return ToolOutput {
@@ -654,8 +1506,8 @@ pub fn str_replace_file(
         };
     }
 
-    let original = match fs::read_to_string(&resolved) {
-        Ok(c) => c,
+    let decoded = match encoding::read(&resolved) {
+        Ok(decoded) => decoded,
         Err(err) => {
             return ToolOutput {
                 ok: false,
@@ -664,33 +1516,139 @@ pub fn str_replace_file(
             }
         }
     };
+    if decoded.lossy {
+        return ToolOutput {
+            ok: false,
+            summary: encoding::lossy_error(path),
+            output: String::new(),
+        };
+    }
+    let format = decoded.format;
+    let original = decoded.text;
 
+    // Set for files that mix line endings; uniform CRLF is decoded to \n
+    let crlf = original.contains("\r\n");
     let mut updated = original.clone();
     let mut total_replacements = 0usize;
+    let mut applied_edits = 0usize;
+    // (edit number, byte range in `updated`) of every replacement so far
+    let mut changed: Vec<(usize, usize, usize)> = Vec::new();
+    let mut notes: Vec<String> = Vec::new();
+
+    for (number, edit) in edits.iter().enumerate().map(|(i, e)| (i + 1, e)) {
+        if edit.replace_all && edit.occurrence.is_some() {
+            notes.push(format!(
+                "Edit {number}: skipped, replace_all and occurrence can't be combined"
+            ));
+            continue;
+        }
+        if edit.old.is_empty() {
+            notes.push(format!("Edit {number}: skipped, old string is empty"));
+            continue;
+        }
+        // The model usually writes \n; match a CRLF file's line endings
+        let (old, mut new) = if crlf && !edit.old.contains("\r\n") {
+            (edit.old.replace('\n', "\r\n"), edit.new.replace('\n', "\r\n"))
+        } else {
+            (edit.old.clone(), edit.new.clone())
+        };
+        let mut ranges: Vec<(usize, usize)> = updated
+            .match_indices(&old)
+            .map(|(start, found)| (start, start + found.len()))
+            .collect();
+        let mut fuzzy = false;
+        if ranges.is_empty() && edit.fuzzy {
+            ranges = fuzzy_matches(&updated, &edit.old);
+            fuzzy = !ranges.is_empty();
+            // Fuzzy ranges stop before the last line break and start at a line
+            if fuzzy {
+                let line_break = if crlf { "\r\n" } else { "\n" };
+                if edit.old.ends_with('\n') {
+                    new = new.strip_suffix(line_break).unwrap_or(&new).to_string();
+                }
+                if edit.old.starts_with('\n') || edit.old.starts_with("\r\n") {
+                    new = new.strip_prefix(line_break).unwrap_or(&new).to_string();
+                }
+            }
+        }
+        let found = ranges.len();
+        let selected: Vec<(usize, usize)> = match edit.occurrence {
+            _ if edit.replace_all => ranges,
+            Some(n) => ranges.into_iter().skip(n.saturating_sub(1)).take(1).collect(),
+            None => ranges.into_iter().take(1).collect(),
+        };
+        if selected.is_empty() {
+            notes.push(if found == 0 {
+                format!("Edit {number}: skipped, old string not found")
+            } else {
+                format!(
+                    "Edit {number}: skipped, occurrence {} requested but only {found} found",
+                    edit.occurrence.unwrap_or(1)
+                )
+            });
+            continue;
+        }
 
-    for edit in &edits {
-        if edit.replace_all {
-            let count = updated.matches(&edit.old).count();
-            total_replacements += count;
-            updated = updated.replace(&edit.old, &edit.new);
-        } else if updated.contains(&edit.old) {
-            updated = updated.replacen(&edit.old, &edit.new, 1);
-            total_replacements += 1;
+        // Back to front, so earlier ranges stay valid
+        for &(start, end) in selected.iter().rev() {
+            updated.replace_range(start..end, &new);
+            let delta = new.len() as isize - (end - start) as isize;
+            for range in changed.iter_mut().filter(|r| r.1 >= end) {
+                range.1 = (range.1 as isize + delta) as usize;
+                range.2 = (range.2 as isize + delta) as usize;
+            }
+            changed.push((number, start, start + new.len()));
+        }
+        total_replacements += selected.len();
+        applied_edits += 1;
+        if fuzzy {
+            notes.push(format!("Edit {number}: matched ignoring whitespace"));
         }
     }
 
+    changed.sort_by_key(|&(number, start, _)| (number, start));
+    let line_of = |offset: usize| {
+        updated.as_bytes()[..offset]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1
+    };
+    let mut report: Vec<String> = changed
+        .iter()
+        .map(|&(number, start, end)| {
+            let first = line_of(start);
+            let last = if end > start { line_of(end - 1) } else { first };
+            if end == start {
+                format!("Edit {number}: removed text at line {first}")
+            } else if last == first {
+                format!("Edit {number}: line {first}")
+            } else {
+                format!("Edit {number}: lines {first}-{last}")
+            }
+        })
+        .collect();
+    report.extend(notes);
+
     if updated == original {
         return ToolOutput {
             ok: false,
             summary: "No replacements were made. The old string was not found.".to_string(),
-            output: String::new(),
+            output: report.join("\n"),
         };
     }
 
-    if let Err(err) = fs::write(&resolved, updated) {
+    if dry_run {
+        let mut preview = dry_run_change(path, &original, &updated, "edited");
+        preview.output = format!("{}\n\n{}", report.join("\n"), preview.output);
+        return preview;
+    }
+
+    let bytes = encoding::encode(&updated, format);
+    if let Err(err) = file_writes::replace(work_dir, &resolved, &bytes) {
         return ToolOutput {
             ok: false,
-            summary: format!("Failed to write file: {err}"),
+            summary: err,
             output: String::new(),
         };
     }
@@ -698,11 +1656,12 @@ pub fn str_replace_file(
     ToolOutput {
         ok: true,
         summary: format!(
-            "File successfully edited. Applied {} edit(s) with {} replacement(s).",
+            "File successfully edited. Applied {} of {} edit(s) with {} replacement(s).",
+            applied_edits,
             edits.len(),
             total_replacements
         ),
-        output: String::new(),
+        output: report.join("\n"),
     }
 }
 
@@ -713,81 +1672,24 @@ pub async fn search_web(
     limit: usize,
     include_content: bool,
 ) -> ToolOutput {
-    let config = match load_config_value(config_path) {
-        Ok(value) => value,
-        Err(err) => {
-            return ToolOutput {
-                ok: false,
-                summary: err,
-                output: String::new(),
-            }
-        }
-    };
-
-    let service = match parse_service_config(&config, "moonshot_search") {
-        Some(cfg) => cfg,
-        None => {
-            return ToolOutput {
-                ok: false,
-                summary: "Search service is not configured.".to_string(),
-                output: String::new(),
-            }
-        }
-    };
-
-    let client = reqwest::Client::new();
-    let mut req = client.post(&service.base_url);
-    req = req.header("Authorization", format!("Bearer {}", service.api_key));
-    req = req.header("X-Msh-Tool-Call-Id", tool_call_id);
-    for (k, v) in common_headers().into_iter() {
-        req = req.header(k, v);
-    }
-    for (k, v) in service.custom_headers.iter() {
-        req = req.header(k, v);
-    }
-
-    let response = req
-        .json(&serde_json::json!({
-            "text_query": query,
-            "limit": limit,
-            "enable_page_crawling": include_content,
-            "timeout_seconds": 30
-        }))
-        .send()
-        .await;
-
-    let response = match response {
-        Ok(resp) => resp,
-        Err(err) => {
-            return ToolOutput {
-                ok: false,
-                summary: format!("Failed to search: {err}"),
-                output: String::new(),
+    // Without a config the keyless fallback still answers
+    let config = load_config_value(config_path).unwrap_or_default();
+    let (provider, results) =
+        match crate::web_search::search(&config, tool_call_id, query, limit, include_content)
+            .await
+        {
+            Ok(found) => found,
+            Err(err) => {
+                return ToolOutput {
+                    ok: false,
+                    summary: err,
+                    output: String::new(),
+                }
             }
-        }
-    };
-
-    if !response.status().is_success() {
-        return ToolOutput {
-            ok: false,
-            summary: format!("Search request failed with status {}", response.status()),
-            output: String::new(),
         };
-    }
-
-    let data: SearchResponse = match response.json().await {
-        Ok(value) => value,
-        Err(err) => {
-            return ToolOutput {
-                ok: false,
-                summary: format!("Failed to parse search response: {err}"),
-                output: String::new(),
-            }
-        }
-    };
 
     let mut output = String::new();
-    for (i, result) in data.search_results.iter().enumerate() {
+    for (i, result) in results.iter().enumerate() {
         if i > 0 {
             output.push_str("---\n\n");
         }
@@ -806,7 +1708,7 @@ pub async fn search_web(
     let (output, truncated) = truncate_output(&output);
     ToolOutput {
         ok: true,
-        summary: append_truncation("Search completed.".to_string(), truncated),
+        summary: append_truncation(format!("Search completed via {}.", provider), truncated),
         output,
     }
 }
@@ -816,12 +1718,30 @@ pub async fn fetch_url(
     tool_call_id: &str,
     url: &str,
 ) -> ToolOutput {
+    if let Err(err) = crate::network::ensure_online(url) {
+        return ToolOutput {
+            ok: false,
+            summary: err,
+            output: String::new(),
+        };
+    }
+
     let config = load_config_value(config_path).ok();
     if let Some(config) = config {
-        if let Some(service) = parse_service_config(&config, "moonshot_fetch") {
-            let client = reqwest::Client::new();
+        // Fall through to a direct fetch if the configured client or credential
+        // can't be set up, or the service can't be reached offline; a local URL
+        // must not be sent off the machine
+        let service = parse_service_config(&config, "moonshot_fetch")
+            .filter(|service| crate::network::ensure_online(&service.base_url).is_ok());
+        let token = match &service {
+            Some(service) => service.token().await.ok(),
+            None => None,
+        };
+        if let (Some(service), Some(token), Ok(client)) =
+            (service, token, crate::http::plain_client())
+        {
             let mut req = client.post(&service.base_url);
-            req = req.header("Authorization", format!("Bearer {}", service.api_key));
+            req = req.header("Authorization", format!("Bearer {}", token));
             req = req.header("Accept", "text/markdown");
             req = req.header("X-Msh-Tool-Call-Id", tool_call_id);
             for (k, v) in common_headers().into_iter() {
@@ -849,7 +1769,16 @@ pub async fn fetch_url(
         }
     }
 
-    let client = reqwest::Client::new();
+    let client = match crate::http::plain_client() {
+        Ok(client) => client,
+        Err(err) => {
+            return ToolOutput {
+                ok: false,
+                summary: err,
+                output: String::new(),
+            }
+        }
+    };
     let response = match client
         .get(url)
         .header(
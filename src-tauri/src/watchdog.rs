@@ -0,0 +1,100 @@
+use std::future::Future;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use uuid::Uuid;
+
+use crate::llm::{PendingApproval, StreamEvent};
+use crate::AppState;
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Seconds without a response before the user is asked whether to keep waiting
+pub const DEFAULT_SOFT_TIMEOUT_SECS: u64 = 120;
+/// Error returned when the user gives up; the turn ends as cancelled, not retried
+pub const ABORTED: &str = "Stopped waiting for the model";
+
+/// Await `future`, a request that may go quiet for a long time, such as a
+/// thinking model before its first token. Every few seconds a
+/// `waiting_for_model` event reports the elapsed time, and after
+/// `soft_timeout_secs` a `model_wait_timeout` event asks the user whether to
+/// keep waiting. Zero never asks.
+pub async fn watch<F: Future>(
+    window: &tauri::Window,
+    session_id: &str,
+    soft_timeout_secs: u64,
+    future: F,
+) -> Result<F::Output, String> {
+    let state = window.state::<AppState>();
+    let started = tokio::time::Instant::now();
+    let mut keepalive = tokio::time::interval_at(started + KEEPALIVE_INTERVAL, KEEPALIVE_INTERVAL);
+    let mut next_prompt = (soft_timeout_secs > 0).then(|| Duration::from_secs(soft_timeout_secs));
+    let mut prompt: Option<(PendingApproval<'_>, tokio::sync::oneshot::Receiver<bool>)> = None;
+    tokio::pin!(future);
+
+    loop {
+        tokio::select! {
+            output = &mut future => return Ok(output),
+            _ = keepalive.tick() => {
+                let elapsed = started.elapsed();
+                emit(window, "waiting_for_model", serde_json::json!({
+                    "session_id": session_id,
+                    "elapsed_secs": elapsed.as_secs(),
+                }));
+                if prompt.is_none() && next_prompt.is_some_and(|at| elapsed >= at) {
+                    let request_id = format!("{}:wait:{}", session_id, Uuid::new_v4());
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    state
+                        .approvals
+                        .lock()
+                        .map_err(|_| "Approval store poisoned".to_string())?
+                        .insert(request_id.clone(), tx);
+                    emit(window, "model_wait_timeout", serde_json::json!({
+                        "session_id": session_id,
+                        "request_id": request_id,
+                        "elapsed_secs": elapsed.as_secs(),
+                    }));
+                    prompt = Some((PendingApproval { approvals: &state.approvals, request_id }, rx));
+                }
+            }
+            answer = async {
+                match prompt.as_mut() {
+                    Some((_, rx)) => rx.await,
+                    None => std::future::pending().await,
+                }
+            }, if prompt.is_some() => {
+                prompt = None;
+                // A dismissed prompt keeps waiting
+                if !answer.unwrap_or(true) {
+                    return Err(ABORTED.to_string());
+                }
+                next_prompt = Some(started.elapsed() + Duration::from_secs(soft_timeout_secs));
+            }
+        }
+    }
+}
+
+fn emit(window: &tauri::Window, event: &str, data: serde_json::Value) {
+    let _ = window.emit(
+        "chat://event",
+        StreamEvent {
+            event: event.to_string(),
+            data,
+        },
+    );
+}
+
+/// Answer a `model_wait_timeout` prompt.
+#[tauri::command]
+pub fn model_wait_respond(
+    state: tauri::State<'_, AppState>,
+    request_id: String,
+    keep_waiting: bool,
+) -> Result<(), String> {
+    let sender = state
+        .approvals
+        .lock()
+        .map_err(|_| "Approval store poisoned".to_string())?
+        .remove(&request_id)
+        .ok_or_else(|| "The model already responded".to_string())?;
+    let _ = sender.send(keep_waiting);
+    Ok(())
+}
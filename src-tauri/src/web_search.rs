@@ -0,0 +1,386 @@
+use serde::Deserialize;
+
+use crate::oauth::common_headers;
+use crate::tools::{parse_service_config, ServiceConfig};
+
+const TAVILY_URL: &str = "https://api.tavily.com/search";
+const BRAVE_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+const DUCKDUCKGO_URL: &str = "https://html.duckduckgo.com/html/";
+/// DuckDuckGo's HTML page turns away requests without a browser-like agent
+const DUCKDUCKGO_USER_AGENT: &str = "Mozilla/5.0 (compatible; KimiCode-GUI)";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchResult {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub snippet: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub date: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KimiResponse {
+    #[serde(default)]
+    search_results: Vec<SearchResult>,
+}
+
+/// Backend answering SearchWeb, chosen by `provider` in the config's `search`
+/// table:
+///
+/// ```toml
+/// [search]
+/// provider = "brave"  # kimi, tavily, brave, searxng or duckduckgo
+/// api_key = "..."     # or credential = "name"
+/// base_url = "..."    # the SearxNG instance
+/// ```
+///
+/// Without a `search` table the Kimi service is used when
+/// `services.moonshot_search` is configured, and DuckDuckGo otherwise, which
+/// needs no key.
+enum Provider {
+    Kimi(ServiceConfig),
+    Tavily { token: String },
+    Brave { token: String },
+    Searxng { base_url: String },
+    DuckDuckGo,
+}
+
+impl Provider {
+    fn label(&self) -> &'static str {
+        match self {
+            Provider::Kimi(_) => "Kimi search",
+            Provider::Tavily { .. } => "Tavily",
+            Provider::Brave { .. } => "Brave Search",
+            Provider::Searxng { .. } => "SearxNG",
+            Provider::DuckDuckGo => "DuckDuckGo",
+        }
+    }
+
+    fn url(&self) -> &str {
+        match self {
+            Provider::Kimi(service) => &service.base_url,
+            Provider::Tavily { .. } => TAVILY_URL,
+            Provider::Brave { .. } => BRAVE_URL,
+            Provider::Searxng { base_url } => base_url,
+            Provider::DuckDuckGo => DUCKDUCKGO_URL,
+        }
+    }
+}
+
+fn string(value: &serde_json::Value, key: &str) -> Option<String> {
+    value[key]
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+async fn provider_token(search: &serde_json::Value, provider: &str) -> Result<String, String> {
+    if let Some(name) = string(search, "credential") {
+        return crate::credentials::resolve(&name).await.map(|c| c.token);
+    }
+    string(search, "api_key").ok_or_else(|| {
+        format!(
+            "Search provider {} needs an api_key or credential",
+            provider
+        )
+    })
+}
+
+async fn provider(config: &serde_json::Value) -> Result<Provider, String> {
+    let search = &config["search"];
+    let kimi = || {
+        parse_service_config(config, "moonshot_search")
+            .ok_or_else(|| "Search service is not configured.".to_string())
+    };
+    let Some(name) = string(search, "provider") else {
+        return Ok(kimi().map(Provider::Kimi).unwrap_or(Provider::DuckDuckGo));
+    };
+    match name.to_lowercase().as_str() {
+        "kimi" | "moonshot" => kimi().map(Provider::Kimi),
+        "tavily" => Ok(Provider::Tavily {
+            token: provider_token(search, &name).await?,
+        }),
+        "brave" => Ok(Provider::Brave {
+            token: provider_token(search, &name).await?,
+        }),
+        "searxng" => string(search, "base_url")
+            .map(|base| Provider::Searxng {
+                base_url: format!("{}/search", base.trim_end_matches('/')),
+            })
+            .ok_or_else(|| "Search provider searxng needs the instance's base_url".to_string()),
+        "duckduckgo" => Ok(Provider::DuckDuckGo),
+        other => Err(format!(
+            "Unknown search provider {}; use kimi, tavily, brave, searxng or duckduckgo",
+            other
+        )),
+    }
+}
+
+async fn json(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|err| format!("Failed to search: {err}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Search request failed with status {}",
+            response.status()
+        ));
+    }
+    response
+        .json()
+        .await
+        .map_err(|err| format!("Failed to parse search response: {err}"))
+}
+
+fn text(value: &serde_json::Value, key: &str) -> String {
+    value[key].as_str().unwrap_or("").to_string()
+}
+
+async fn kimi(
+    client: &reqwest::Client,
+    service: &ServiceConfig,
+    tool_call_id: &str,
+    query: &str,
+    limit: usize,
+    include_content: bool,
+) -> Result<Vec<SearchResult>, String> {
+    let token = service.token().await?;
+    let mut req = client.post(&service.base_url);
+    req = req.header("Authorization", format!("Bearer {}", token));
+    req = req.header("X-Msh-Tool-Call-Id", tool_call_id);
+    for (k, v) in common_headers().into_iter() {
+        req = req.header(k, v);
+    }
+    for (k, v) in service.custom_headers.iter() {
+        req = req.header(k, v);
+    }
+    let data = json(req.json(&serde_json::json!({
+        "text_query": query,
+        "limit": limit,
+        "enable_page_crawling": include_content,
+        "timeout_seconds": 30
+    })))
+    .await?;
+    serde_json::from_value::<KimiResponse>(data)
+        .map(|response| response.search_results)
+        .map_err(|err| format!("Failed to parse search response: {err}"))
+}
+
+async fn tavily(
+    client: &reqwest::Client,
+    token: &str,
+    query: &str,
+    limit: usize,
+    include_content: bool,
+) -> Result<Vec<SearchResult>, String> {
+    let data = json(
+        client
+            .post(TAVILY_URL)
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "query": query,
+                "max_results": limit,
+                "include_raw_content": include_content,
+            })),
+    )
+    .await?;
+    Ok(data["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|result| SearchResult {
+            title: text(result, "title"),
+            url: text(result, "url"),
+            snippet: text(result, "content"),
+            content: text(result, "raw_content"),
+            date: text(result, "published_date"),
+        })
+        .collect())
+}
+
+async fn brave(
+    client: &reqwest::Client,
+    token: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    let data = json(
+        client
+            .get(BRAVE_URL)
+            .header("X-Subscription-Token", token)
+            .header("Accept", "application/json")
+            // Brave caps a page at 20 results
+            .query(&[
+                ("q", query.to_string()),
+                ("count", limit.min(20).to_string()),
+            ]),
+    )
+    .await?;
+    Ok(data["web"]["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|result| SearchResult {
+            title: crate::docs::html_to_text(&text(result, "title")),
+            url: text(result, "url"),
+            snippet: crate::docs::html_to_text(&text(result, "description")),
+            content: String::new(),
+            date: text(result, "page_age"),
+        })
+        .collect())
+}
+
+async fn searxng(
+    client: &reqwest::Client,
+    url: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    // The instance must have the json format enabled in its settings
+    let data = json(client.get(url).query(&[("q", query), ("format", "json")])).await?;
+    Ok(data["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .take(limit)
+        .map(|result| SearchResult {
+            title: text(result, "title"),
+            url: text(result, "url"),
+            snippet: text(result, "content"),
+            content: String::new(),
+            date: text(result, "publishedDate"),
+        })
+        .collect())
+}
+
+/// Target of a DuckDuckGo result link, which points at a redirect carrying
+/// the real URL in `uddg`. Ads point elsewhere on duckduckgo.com and are
+/// skipped.
+fn duckduckgo_target(href: &str) -> Option<String> {
+    let href = href.replace("&amp;", "&");
+    let absolute = if href.starts_with("//") {
+        format!("https:{}", href)
+    } else {
+        href
+    };
+    let url = reqwest::Url::parse(&absolute).ok()?;
+    if !url
+        .host_str()
+        .is_some_and(|host| host.ends_with("duckduckgo.com"))
+    {
+        return Some(absolute);
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "uddg")
+        .map(|(_, target)| target.to_string())
+}
+
+/// Inner HTML of the element whose opening tag contains `class_attr`,
+/// searching from `from`, with the tag's `href` and the offset after it.
+fn element<'a>(
+    html: &'a str,
+    class_attr: &str,
+    from: usize,
+) -> Option<(Option<&'a str>, &'a str, usize)> {
+    let at = from + html[from..].find(class_attr)?;
+    let tag_start = html[..at].rfind('<')?;
+    let tag_end = at + html[at..].find('>')?;
+    let tag = &html[tag_start..tag_end];
+    let href = tag
+        .find("href=\"")
+        .map(|start| &tag[start + 6..])
+        .and_then(|rest| rest.find('"').map(|end| &rest[..end]));
+    let inner_end = tag_end + html[tag_end..].find("</a>")?;
+    Some((href, &html[tag_end + 1..inner_end], inner_end))
+}
+
+fn parse_duckduckgo(html: &str, limit: usize) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    let mut pos = 0;
+    while results.len() < limit {
+        let Some((href, title, end)) = element(html, "class=\"result__a\"", pos) else {
+            break;
+        };
+        pos = end;
+        let next = html[pos..]
+            .find("class=\"result__a\"")
+            .map_or(html.len(), |offset| pos + offset);
+        let snippet = element(html, "class=\"result__snippet\"", pos)
+            .filter(|(_, _, snippet_end)| *snippet_end < next)
+            .map(|(_, snippet, _)| crate::docs::html_to_text(snippet))
+            .unwrap_or_default();
+        let Some(url) = href.and_then(duckduckgo_target) else {
+            continue;
+        };
+        results.push(SearchResult {
+            title: crate::docs::html_to_text(title).trim().to_string(),
+            url,
+            snippet: snippet.trim().to_string(),
+            ..Default::default()
+        });
+    }
+    results
+}
+
+async fn duckduckgo(
+    client: &reqwest::Client,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    let response = client
+        .post(DUCKDUCKGO_URL)
+        .header("User-Agent", DUCKDUCKGO_USER_AGENT)
+        .form(&[("q", query)])
+        .send()
+        .await
+        .map_err(|err| format!("Failed to search: {err}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Search request failed with status {}",
+            response.status()
+        ));
+    }
+    let html = response
+        .text()
+        .await
+        .map_err(|err| format!("Failed to read search response: {err}"))?;
+    Ok(parse_duckduckgo(&html, limit))
+}
+
+/// Results for `query` from the configured provider, and that provider's name.
+pub async fn search(
+    config: &serde_json::Value,
+    tool_call_id: &str,
+    query: &str,
+    limit: usize,
+    include_content: bool,
+) -> Result<(&'static str, Vec<SearchResult>), String> {
+    let provider = provider(config).await?;
+    crate::network::ensure_online(provider.url())?;
+    let client = crate::http::plain_client()?;
+    let results = match &provider {
+        Provider::Kimi(service) => {
+            kimi(
+                &client,
+                service,
+                tool_call_id,
+                query,
+                limit,
+                include_content,
+            )
+            .await
+        }
+        Provider::Tavily { token } => tavily(&client, token, query, limit, include_content).await,
+        Provider::Brave { token } => brave(&client, token, query, limit).await,
+        Provider::Searxng { base_url } => searxng(&client, base_url, query, limit).await,
+        Provider::DuckDuckGo => duckduckgo(&client, query, limit).await,
+    }?;
+    Ok((provider.label(), results))
+}
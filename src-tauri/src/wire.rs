@@ -0,0 +1,353 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use crate::parts::MessagePart;
+use crate::session::Message;
+use crate::AppState;
+
+/// Newest kimi-cli wire protocol major version the adapters were written against
+const SUPPORTED_MAJOR: u64 = 1;
+
+/// Record kinds that make up the transcript
+const RENDERED_KINDS: &[&str] = &["TurnBegin", "TurnEnd", "StepEnd", "ContentPart", "ToolCall"];
+/// Record kinds the GUI knows about and deliberately leaves out of the transcript
+const SKIPPED_KINDS: &[&str] = &[
+    "StepBegin",
+    "StepInterrupted",
+    "ToolCallPart",
+    "ToolResult",
+    "StatusUpdate",
+    "ApprovalRequest",
+    "ApprovalResponse",
+    "CompactionBegin",
+    "CompactionEnd",
+    "SubagentEvent",
+];
+
+/// One wire record in the shape the transcript builder understands,
+/// whatever envelope the file used.
+pub struct WireRecord {
+    pub kind: String,
+    pub payload: serde_json::Value,
+    /// Seconds since the epoch, when the record carries one
+    pub timestamp: Option<f64>,
+}
+
+type Adapter = fn(&serde_json::Value) -> Option<WireRecord>;
+
+/// Protocol 1.x: `{"timestamp": .., "message": {"type": .., "payload": {..}}}`
+fn adapt_v1(record: &serde_json::Value) -> Option<WireRecord> {
+    let message = record.get("message")?;
+    Some(WireRecord {
+        kind: message.get("type")?.as_str()?.to_string(),
+        payload: message.get("payload").cloned().unwrap_or_default(),
+        timestamp: record.get("timestamp").and_then(|t| t.as_f64()),
+    })
+}
+
+/// Files written before the `message` envelope: `{"type": .., "payload": {..}}`
+fn adapt_legacy(record: &serde_json::Value) -> Option<WireRecord> {
+    let kind = record.get("type")?.as_str()?;
+    if kind == "metadata" {
+        return None;
+    }
+    Some(WireRecord {
+        kind: kind.to_string(),
+        payload: record.get("payload").cloned().unwrap_or_default(),
+        timestamp: record.get("timestamp").and_then(|t| t.as_f64()),
+    })
+}
+
+const ADAPTERS: &[(&str, Adapter)] = &[("v1", adapt_v1), ("legacy", adapt_legacy)];
+
+fn major_version(version: &str) -> Option<u64> {
+    version.split('.').next()?.trim().parse().ok()
+}
+
+fn adapter_index(version: Option<&str>) -> usize {
+    match version.and_then(major_version) {
+        Some(0) => 1,
+        _ => 0,
+    }
+}
+
+/// Records of a wire.jsonl file plus what the parser could not place.
+pub struct ParsedWire {
+    /// `protocol_version` from the metadata line, if the file has one
+    pub protocol_version: Option<String>,
+    /// Adapter that read most records
+    pub adapter: &'static str,
+    pub records: Vec<WireRecord>,
+    /// Lines that were not JSON or matched no adapter
+    pub unparsed_lines: usize,
+}
+
+/// Read a wire.jsonl file. Each line goes through the adapter for the declared
+/// protocol version first and the others after, so a file that mixes shapes
+/// (or a newer CLI that reverts one) still reads.
+pub fn parse(content: &str) -> ParsedWire {
+    let mut protocol_version = None;
+    let mut records = Vec::new();
+    let mut unparsed_lines = 0;
+    let mut adapter_hits = [0usize; 2];
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+            unparsed_lines += 1;
+            continue;
+        };
+        if value.get("type").and_then(|t| t.as_str()) == Some("metadata") {
+            protocol_version = value
+                .get("protocol_version")
+                .and_then(|v| v.as_str().map(str::to_string).or_else(|| Some(v.to_string())));
+            continue;
+        }
+        let first = adapter_index(protocol_version.as_deref());
+        let order = [first, 1 - first];
+        match order
+            .iter()
+            .find_map(|&index| (ADAPTERS[index].1)(&value).map(|record| (index, record)))
+        {
+            Some((index, record)) => {
+                adapter_hits[index] += 1;
+                records.push(record);
+            }
+            None => unparsed_lines += 1,
+        }
+    }
+
+    let adapter = if adapter_hits[1] > adapter_hits[0] {
+        ADAPTERS[1].0
+    } else {
+        ADAPTERS[0].0
+    };
+    ParsedWire {
+        protocol_version,
+        adapter,
+        records,
+        unparsed_lines,
+    }
+}
+
+/// Text of a TurnBegin's `user_input`, which is a list of content parts or,
+/// in some versions, a plain string.
+pub fn user_text(payload: &serde_json::Value) -> String {
+    match payload.get("user_input") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .find_map(|item| item.get("text").and_then(|t| t.as_str()))
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Build a message from wire content, with reasoning and tool refs ahead of the text.
+fn wire_message(role: &str, content: String, mut parts: Vec<MessagePart>, timestamp: i64) -> Message {
+    let mut message = Message::new(role, content);
+    parts.append(&mut message.parts);
+    message.parts = parts;
+    message.timestamp = timestamp;
+    message
+}
+
+/// Messages for the GUI from wire records. Records without a timestamp use
+/// `fallback_timestamp`.
+pub fn transcript(records: &[WireRecord], fallback_timestamp: i64) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut current_content = String::new();
+    let mut in_turn = false;
+    let mut turn_timestamp = fallback_timestamp;
+    // Thinking and tool calls seen since the last flush, attached as parts
+    let mut pending_parts: Vec<MessagePart> = Vec::new();
+
+    for record in records {
+        let timestamp = record
+            .timestamp
+            .map(|t| t as i64)
+            .unwrap_or(fallback_timestamp);
+        let payload = &record.payload;
+        match record.kind.as_str() {
+            "TurnBegin" => {
+                // Flush any previous assistant content
+                if in_turn && (!current_content.is_empty() || !pending_parts.is_empty()) {
+                    messages.push(wire_message(
+                        "assistant",
+                        std::mem::take(&mut current_content),
+                        std::mem::take(&mut pending_parts),
+                        turn_timestamp,
+                    ));
+                }
+                pending_parts.clear();
+                current_content.clear();
+
+                let text = user_text(payload);
+                if !text.is_empty() {
+                    let mut message = Message::new("user", text);
+                    message.timestamp = timestamp;
+                    messages.push(message);
+                }
+                // Subsequent content belongs to the assistant
+                in_turn = true;
+                turn_timestamp = timestamp;
+            }
+            "ContentPart" if in_turn => match payload.get("type").and_then(|t| t.as_str()) {
+                Some("text") => {
+                    if let Some(text) = payload.get("text").and_then(|t| t.as_str()) {
+                        current_content.push_str(text);
+                    }
+                }
+                Some("think") => {
+                    if let Some(think) = payload.get("think").and_then(|t| t.as_str()) {
+                        match pending_parts.last_mut() {
+                            Some(MessagePart::Reasoning { text }) => text.push_str(think),
+                            _ => pending_parts.push(MessagePart::Reasoning {
+                                text: think.to_string(),
+                            }),
+                        }
+                    }
+                }
+                _ => {}
+            },
+            "ToolCall" => {
+                let id = payload.get("id").and_then(|v| v.as_str()).unwrap_or("");
+                let name = payload
+                    .get("function")
+                    .and_then(|f| f.get("name"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                if !name.is_empty() {
+                    pending_parts.push(MessagePart::ToolRef {
+                        id: id.to_string(),
+                        name: name.to_string(),
+                    });
+                }
+            }
+            "StepEnd" | "TurnEnd" => {
+                if in_turn && !current_content.is_empty() {
+                    messages.push(wire_message(
+                        "assistant",
+                        std::mem::take(&mut current_content),
+                        std::mem::take(&mut pending_parts),
+                        turn_timestamp,
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Flush any remaining assistant content
+    if in_turn && !current_content.is_empty() {
+        messages.push(wire_message("assistant", current_content, pending_parts, turn_timestamp));
+    }
+    messages
+}
+
+#[derive(Clone, Serialize)]
+pub struct WireCompat {
+    pub session_id: String,
+    pub protocol_version: Option<String>,
+    pub adapter: String,
+    /// The declared protocol major version is one the adapters know
+    pub supported: bool,
+    pub records: usize,
+    pub rendered_records: usize,
+    pub skipped_records: usize,
+    /// Record kinds this GUI doesn't know, with counts
+    pub unknown_kinds: BTreeMap<String, usize>,
+    pub unparsed_lines: usize,
+    pub messages: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Capability report for one parsed wire file.
+pub fn compat_report(session_id: &str, parsed: &ParsedWire, messages: usize) -> WireCompat {
+    let mut rendered = 0;
+    let mut skipped = 0;
+    let mut unknown: HashMap<&str, usize> = HashMap::new();
+    for record in &parsed.records {
+        let kind = record.kind.as_str();
+        if RENDERED_KINDS.contains(&kind) {
+            rendered += 1;
+        } else if SKIPPED_KINDS.contains(&kind) {
+            skipped += 1;
+        } else {
+            *unknown.entry(kind).or_default() += 1;
+        }
+    }
+
+    let major = parsed.protocol_version.as_deref().and_then(major_version);
+    let supported = major.is_none_or(|major| major <= SUPPORTED_MAJOR);
+    let mut warnings = Vec::new();
+    if !supported {
+        warnings.push(format!(
+            "Protocol version {} is newer than this GUI supports ({}.x); the transcript may be incomplete.",
+            parsed.protocol_version.as_deref().unwrap_or_default(),
+            SUPPORTED_MAJOR
+        ));
+    }
+    if parsed.unparsed_lines > 0 {
+        warnings.push(format!("{} lines could not be parsed.", parsed.unparsed_lines));
+    }
+    if !unknown.is_empty() {
+        let mut kinds: Vec<&str> = unknown.keys().copied().collect();
+        kinds.sort_unstable();
+        warnings.push(format!("Unknown record kinds were ignored: {}.", kinds.join(", ")));
+    }
+    if messages == 0 && !parsed.records.is_empty() {
+        warnings.push("No messages could be built from this session's records.".to_string());
+    }
+
+    WireCompat {
+        session_id: session_id.to_string(),
+        protocol_version: parsed.protocol_version.clone(),
+        adapter: parsed.adapter.to_string(),
+        supported,
+        records: parsed.records.len(),
+        rendered_records: rendered,
+        skipped_records: skipped,
+        unknown_kinds: unknown
+            .into_iter()
+            .map(|(kind, count)| (kind.to_string(), count))
+            .collect(),
+        unparsed_lines: parsed.unparsed_lines,
+        messages,
+        warnings,
+    }
+}
+
+/// Check how well the CLI sessions of `work_dir` (or just `session_id`) parse
+/// with this GUI's wire adapters.
+#[tauri::command]
+pub fn wire_compat_check(
+    state: tauri::State<'_, AppState>,
+    work_dir: String,
+    session_id: Option<String>,
+) -> Result<Vec<WireCompat>, String> {
+    let sessions: Vec<(String, String)> = match session_id {
+        Some(id) => vec![(id, work_dir.clone())],
+        None => crate::load_sessions(&work_dir)?
+            .into_iter()
+            .map(|info| (info.id, info.work_dir))
+            .collect(),
+    };
+    let manager = state
+        .session_manager
+        .lock()
+        .map_err(|_| "Session manager poisoned".to_string())?;
+
+    let mut reports = Vec::new();
+    for (id, wd) in sessions {
+        let path = manager.wire_path(&wd, &id);
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let parsed = parse(&content);
+        let messages = transcript(&parsed.records, 0).len();
+        reports.push(compat_report(&id, &parsed, messages));
+    }
+    Ok(reports)
+}
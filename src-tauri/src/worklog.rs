@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::checkpoints;
+use crate::llm;
+use crate::providers;
+use crate::{AppState, GuiSettings};
+
+const REQUEST_EXCERPT_CHARS: usize = 300;
+const REPLY_EXCERPT_CHARS: usize = 600;
+/// Diff text sent to the model across all files
+const MAX_PROMPT_DIFF_CHARS: usize = 12_000;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+    /// The session created the file
+    pub created: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionWorklog {
+    pub session_id: String,
+    /// Message count the worklog was built from; a longer transcript invalidates it
+    pub message_count: usize,
+    /// Model, config file and work dir the worklog was built with; a change invalidates it
+    #[serde(default)]
+    pub cache_key: String,
+    /// Markdown ready to paste into a PR description or standup notes
+    pub markdown: String,
+    pub files: Vec<FileChange>,
+    pub commands: Vec<String>,
+    pub generated_at: i64,
+}
+
+fn display_path(path: &str, work_dir: &str) -> String {
+    Path::new(path)
+        .strip_prefix(work_dir)
+        .map(|rel| rel.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Net change of every file the session touched: its content before the
+/// session's first edit against what is on disk now.
+fn file_changes(session_id: &str, work_dir: &str) -> (Vec<FileChange>, String) {
+    let mut changes = Vec::new();
+    let mut diffs = String::new();
+    for (path, original) in checkpoints::session_originals(session_id) {
+        let current = fs::read_to_string(&path).unwrap_or_default();
+        let before = original.clone().unwrap_or_default();
+        if before == current {
+            continue;
+        }
+        let rel = display_path(&path, work_dir);
+        let diff = similar::TextDiff::from_lines(&before, &current);
+        let (mut added, mut removed) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                similar::ChangeTag::Insert => added += 1,
+                similar::ChangeTag::Delete => removed += 1,
+                similar::ChangeTag::Equal => {}
+            }
+        }
+        if diffs.len() < MAX_PROMPT_DIFF_CHARS {
+            diffs.push_str(&crate::tools::unified_diff(&rel, &before, &current));
+        }
+        changes.push(FileChange {
+            path: rel,
+            added,
+            removed,
+            created: original.is_none(),
+        });
+    }
+    (changes, crate::truncate_with_ellipsis(&diffs, MAX_PROMPT_DIFF_CHARS))
+}
+
+/// Shell commands the session ran successfully, in order.
+fn commands_run(messages: &[crate::Message]) -> Vec<String> {
+    let ok = |id: &str| {
+        messages
+            .iter()
+            .find(|m| m.role == "tool" && m.tool_call_id.as_deref() == Some(id))
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(&m.content).ok())
+            .and_then(|result| result["ok"].as_bool())
+            .unwrap_or(false)
+    };
+    messages
+        .iter()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .filter(|call| call.name == "Shell" && ok(&call.id))
+        .filter_map(|call| {
+            serde_json::from_str::<serde_json::Value>(&call.arguments)
+                .ok()?
+                .get("command")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+fn conversation(messages: &[crate::Message]) -> String {
+    messages
+        .iter()
+        .filter_map(|m| match m.role.as_str() {
+            "user" => Some(format!(
+                "User: {}",
+                crate::truncate_with_ellipsis(m.content.trim(), REQUEST_EXCERPT_CHARS)
+            )),
+            "assistant" if !m.content.trim().is_empty() => Some(format!(
+                "Assistant: {}",
+                crate::truncate_with_ellipsis(m.content.trim(), REPLY_EXCERPT_CHARS)
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Used when the model can't be reached, so there is always something to paste.
+fn fallback_markdown(messages: &[crate::Message], files: &[FileChange], commands: &[String]) -> String {
+    let mut markdown = String::from("## Summary\n\n");
+    for m in messages.iter().filter(|m| m.role == "user") {
+        let request = m.content.trim().lines().next().unwrap_or_default();
+        markdown.push_str(&format!("- {}\n", crate::truncate_with_ellipsis(request, 120)));
+    }
+    if !files.is_empty() {
+        markdown.push_str("\n## Files changed\n\n");
+        for file in files {
+            let note = if file.created { " (new)" } else { "" };
+            markdown.push_str(&format!("- `{}`{} +{} -{}\n", file.path, note, file.added, file.removed));
+        }
+    }
+    if !commands.is_empty() {
+        markdown.push_str("\n## Commands run\n\n");
+        for command in commands {
+            markdown.push_str(&format!("- `{}`\n", command));
+        }
+    }
+    markdown
+}
+
+async fn generate_markdown(
+    session_id: &str,
+    config_path: Option<&str>,
+    model: Option<&str>,
+    chat_model: &str,
+    prompt: String,
+) -> Result<String, String> {
+    let (credentials, api_model) =
+        providers::resolve_summarizer(config_path, model, chat_model).await?;
+    let request = serde_json::json!({
+        "model": api_model,
+        "messages": [
+            {
+                "role": "system",
+                "content": "Write a worklog for this coding session in Markdown, for a pull request description \
+                            or standup notes. Use the sections \"## Summary\" (2-4 sentences on what was done and why), \
+                            \"## Changes\" (one bullet per meaningful change, naming the files) and, if tests or \
+                            builds were run, \"## Verification\". Describe only changes that appear in the diffs; \
+                            don't mention the assistant.",
+            },
+            { "role": "user", "content": prompt },
+        ],
+        "stream": false,
+        "temperature": 0,
+    });
+    let client = crate::http::client(&credentials)?;
+    let data = llm::post_chat_completion(&client, &credentials, &request, Some(session_id)).await?;
+    data.get("choices")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.get("message"))
+        .and_then(|v| v.get("content"))
+        .and_then(|v| v.as_str())
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+        .ok_or_else(|| "Empty worklog response".to_string())
+}
+
+/// Human-readable account of what a session changed and why, built from the
+/// file checkpoints, the commands it ran and the conversation. Cached next to
+/// the session until new messages arrive.
+#[tauri::command]
+pub async fn session_worklog(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    work_dir: Option<String>,
+    model: Option<String>,
+    refresh: Option<bool>,
+    settings: Option<GuiSettings>,
+) -> Result<SessionWorklog, String> {
+    let (messages, session_work_dir, cache_path) = {
+        let mut manager = state
+            .session_manager
+            .lock()
+            .map_err(|_| "Session manager poisoned".to_string())?;
+        let (messages, session_work_dir) = match manager.find_session(&session_id) {
+            Some(session) => (session.messages, session.work_dir),
+            None => {
+                let wd = work_dir.clone().ok_or_else(|| "Session not found".to_string())?;
+                (manager.load_messages(&wd, &session_id)?, wd)
+            }
+        };
+        (messages, session_work_dir, manager.sidecar_path(&session_id, "worklog.json"))
+    };
+
+    let work_dir = work_dir.unwrap_or(session_work_dir);
+    let settings = settings.unwrap_or_default();
+    let chat_model = settings.chat_model();
+    let config_path = settings
+        .config_file
+        .filter(|path| !path.is_empty())
+        .or_else(|| Some(crate::app_paths().config));
+    let model = model.or(settings.summarizer_model);
+    let cache_key = format!(
+        "{}\n{}\n{}",
+        model.as_deref().unwrap_or(&chat_model),
+        config_path.as_deref().unwrap_or(""),
+        work_dir
+    );
+
+    if !refresh.unwrap_or(false) {
+        if let Some(cached) = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<SessionWorklog>(&raw).ok())
+        {
+            if cached.message_count == messages.len() && cached.cache_key == cache_key {
+                return Ok(cached);
+            }
+        }
+    }
+    if !messages.iter().any(|m| m.role == "user") {
+        return Err("Session has no turns to summarize".to_string());
+    }
+
+    let (files, diffs) = file_changes(&session_id, &work_dir);
+    let commands = commands_run(&messages);
+
+    let mut prompt = format!("Conversation:\n{}\n", conversation(&messages));
+    if !diffs.is_empty() {
+        prompt.push_str(&format!("\nDiffs:\n{}\n", diffs));
+    }
+    if !commands.is_empty() {
+        prompt.push_str(&format!("\nCommands run:\n{}\n", commands.join("\n")));
+    }
+    let markdown = match generate_markdown(
+        &session_id,
+        config_path.as_deref(),
+        model.as_deref(),
+        &chat_model,
+        prompt,
+    )
+    .await
+    {
+        Ok(markdown) => markdown,
+        Err(_) => fallback_markdown(&messages, &files, &commands),
+    };
+
+    let worklog = SessionWorklog {
+        session_id,
+        message_count: messages.len(),
+        cache_key,
+        markdown,
+        files,
+        commands,
+        generated_at: chrono::Utc::now().timestamp(),
+    };
+    if let Ok(json) = serde_json::to_string_pretty(&worklog) {
+        let _ = fs::write(&cache_path, json);
+    }
+    Ok(worklog)
+}
@@ -12,22 +12,37 @@
       model: null,
       thinking: false,
       yolo: false,
+      dry_run: false,
+      low_bandwidth: false,
       pinned_sessions: [],
     },
+    // Images waiting to go out with the next chat message, as data URLs
+    pendingAttachments: [],
     paths: null,
     config: null,
     mcp: null,
     skills: [],
+    customCommands: [],
     sessions: [],
     currentSession: null,
     messages: [],
-    isStreaming: false,
+    // Sessions with a turn in flight; each runs independently in the backend
+    streamingSessions: new Set(),
     attachedFiles: [],
     currentStreamId: null,
     isLoggedIn: false,
+    // Device-code login whose events are shown; others are stale
+    loginId: null,
+    // Sessions whose turn is paused until the user logs in again
+    reauthSessions: new Set(),
+    // Turns cut short by a crash or force-quit, by session id
+    interruptedTurns: new Map(),
+    authExpiryWarned: false,
     models: [],
     user: null,
     isEditingSessions: false,
+    outlines: {},
+    snippets: [],
   };
   
   // Autocomplete state
@@ -47,6 +62,7 @@
     { name: 'clear', description: 'Clear the context', aliases: ['reset'] },
     { name: 'compact', description: 'Compact the context' },
     { name: 'skill', description: 'Use a skill (e.g., /skill:name)', aliases: [] },
+    { name: 'search', description: 'Search the project by meaning (e.g., /search token refresh)', aliases: [] },
   ];
 
   const $ = (id) => document.getElementById(id);
@@ -58,7 +74,9 @@
     elements = {
       btnNewSession: $('btn-new-session'),
       sessionList: $('session-list'),
+      sessionSearch: $('session-search'),
       btnEditSessions: $('btn-edit-sessions'),
+      btnImportSession: $('btn-import-session'),
       userBar: $('user-bar'),
       userStatus: $('user-status'),
       emptyState: $('empty-state'),
@@ -68,6 +86,7 @@
       btnConfigChat: $('btn-config-chat'),
       yoloSwitchMain: $('yolo-switch-main'),
       yoloSwitchChat: $('yolo-switch-chat'),
+      sessionPolicySelect: $('session-policy-select'),
       btnFolder: $('btn-folder'),
       btnModel: $('btn-model'),
       folderLabel: $('folder-label'),
@@ -81,13 +100,141 @@
       drawerBackdrop: $('drawer-backdrop'),
       btnCloseSettings: $('btn-close-settings'),
       drawerTabs: $$('.drawer-tab'),
+      toolMatrix: $('tool-matrix'),
+      btnToolsOffline: $('btn-tools-offline'),
+      btnToolsReadOnly: $('btn-tools-read-only'),
+      btnToolsAll: $('btn-tools-all'),
       settingWorkdir: $('setting-workdir'),
       settingConfig: $('setting-config'),
       settingMcp: $('setting-mcp'),
       settingSkills: $('setting-skills'),
       settingDefaultModel: $('setting-default-model'),
+      settingSummarizerModel: $('setting-summarizer-model'),
       settingThinking: $('setting-thinking'),
       settingYolo: $('setting-yolo'),
+      settingDryRun: $('setting-dry-run'),
+      settingBackgroundMode: $('setting-background-mode'),
+      settingFollowSymlinks: $('setting-follow-symlinks'),
+      settingShellInterpreter: $('setting-shell-interpreter'),
+      settingEnvAllow: $('setting-env-allow'),
+      settingEnvInheritAll: $('setting-env-inherit-all'),
+      settingDownloadMaxMb: $('setting-download-max-mb'),
+      settingDownloadDomains: $('setting-download-domains'),
+      settingDownloadTypes: $('setting-download-types'),
+      settingGithubCredential: $('setting-github-credential'),
+      settingGitlabCredential: $('setting-gitlab-credential'),
+      settingGitlabUrl: $('setting-gitlab-url'),
+      btnEnvPreview: $('btn-env-preview'),
+      envPreview: $('env-preview'),
+      settingWorkspaceStatsPrompt: $('setting-workspace-stats-prompt'),
+      settingFollowUpSuggestions: $('setting-follow-up-suggestions'),
+      settingAutostart: $('setting-autostart'),
+      settingToolRetries: $('setting-tool-retries'),
+      settingToolRetryTools: $('setting-tool-retry-tools'),
+      settingApiRetries: $('setting-api-retries'),
+      settingCheckers: $('setting-checkers'),
+      settingCheckersAfterWrite: $('setting-checkers-after-write'),
+      settingApiRetryMaxDelay: $('setting-api-retry-max-delay'),
+      settingIgnorePatterns: $('setting-ignore-patterns'),
+      settingScratchRetention: $('setting-scratch-retention'),
+      settingCheckpointRetention: $('setting-checkpoint-retention'),
+      settingModelTimeout: $('setting-model-timeout'),
+      settingCostThreshold: $('setting-cost-threshold'),
+      settingCostPrices: $('setting-cost-prices'),
+      settingBudgetSessionTokens: $('setting-budget-session-tokens'),
+      settingBudgetSessionUsd: $('setting-budget-session-usd'),
+      settingBudgetDailyTokens: $('setting-budget-daily-tokens'),
+      settingBudgetDailyUsd: $('setting-budget-daily-usd'),
+      budgetUsage: $('budget-usage'),
+      settingEmbeddingsModel: $('setting-embeddings-model'),
+      settingEmbeddingsBase: $('setting-embeddings-base'),
+      settingEmbeddingsKey: $('setting-embeddings-key'),
+      settingEmbeddingsCredential: $('setting-embeddings-credential'),
+      credentialList: $('credential-list'),
+      credentialName: $('credential-name'),
+      credentialMode: $('credential-mode'),
+      credentialBase: $('credential-base'),
+      credentialKey: $('credential-key'),
+      btnCredentialSave: $('btn-credential-save'),
+      btnSemanticIndex: $('btn-semantic-index'),
+      semanticIndexStatus: $('semantic-index-status'),
+      templateList: $('template-list'),
+      approvalRuleList: $('approval-rule-list'),
+      settingApprovalHook: $('setting-approval-hook'),
+      settingOutsideWrites: $('setting-outside-writes'),
+      settingSharePort: $('setting-share-port'),
+      settingPolicyPreset: $('setting-policy-preset'),
+      settingPolicyDeny: $('setting-policy-deny'),
+      settingPolicyAllow: $('setting-policy-allow'),
+      settingPolicyNetwork: $('setting-policy-network'),
+      settingPolicyDirectories: $('setting-policy-directories'),
+      approvalAuditList: $('approval-audit-list'),
+      settingHooks: $('setting-hooks'),
+      btnFileHistory: $('btn-file-history'),
+      btnContextPack: $('btn-context-pack'),
+      contextBreakdown: $('context-breakdown'),
+      suggestionList: $('suggestion-list'),
+      btnAttachImage: $('btn-attach-image'),
+      attachImageInput: $('attach-image-input'),
+      attachmentList: $('attachment-list'),
+      fileHistoryModal: $('file-history-modal'),
+      btnGitChanges: $('btn-git-changes'),
+      gitModal: $('git-modal'),
+      btnCloseGit: $('btn-close-git'),
+      gitBranch: $('git-branch'),
+      gitFileList: $('git-file-list'),
+      gitDiff: $('git-diff'),
+      gitCommitMessage: $('git-commit-message'),
+      btnGitRefresh: $('btn-git-refresh'),
+      btnGitCommit: $('btn-git-commit'),
+      gitLog: $('git-log'),
+      gitIssues: $('git-issues'),
+      btnWorklog: $('btn-worklog'),
+      btnProcesses: $('btn-processes'),
+      btnWorkspaceStats: $('btn-workspace-stats'),
+      workspaceStatsModal: $('workspace-stats-modal'),
+      workspaceStatsBody: $('workspace-stats-body'),
+      btnCloseWorkspaceStats: $('btn-close-workspace-stats'),
+      btnWorkspaceStatsRefresh: $('btn-workspace-stats-refresh'),
+      btnOutline: $('btn-outline'),
+      outlineModal: $('outline-modal'),
+      outlinePath: $('outline-path'),
+      outlineList: $('outline-list'),
+      btnOutlineLoad: $('btn-outline-load'),
+      btnCloseOutline: $('btn-close-outline'),
+      btnProblems: $('btn-problems'),
+      problemsModal: $('problems-modal'),
+      problemsBody: $('problems-body'),
+      btnCloseProblems: $('btn-close-problems'),
+      btnProblemsRun: $('btn-problems-run'),
+      processesModal: $('processes-modal'),
+      btnCloseProcesses: $('btn-close-processes'),
+      processCommand: $('process-command'),
+      btnProcessStart: $('btn-process-start'),
+      btnResetShell: $('btn-reset-shell'),
+      processList: $('process-list'),
+      projectCommandList: $('project-command-list'),
+      processOutput: $('process-output'),
+      btnExportSession: $('btn-export-session'),
+      btnMirrorSession: $('btn-mirror-session'),
+      btnShareSession: $('btn-share-session'),
+      worklogModal: $('worklog-modal'),
+      worklogText: $('worklog-text'),
+      btnCloseWorklog: $('btn-close-worklog'),
+      btnWorklogRefresh: $('btn-worklog-refresh'),
+      btnWorklogCopy: $('btn-worklog-copy'),
+      btnCloseFileHistory: $('btn-close-file-history'),
+      fileHistoryPath: $('file-history-path'),
+      btnFileHistoryLoad: $('btn-file-history-load'),
+      fileHistoryList: $('file-history-list'),
+      btnToolHistory: $('btn-tool-history'),
+      toolHistoryModal: $('tool-history-modal'),
+      btnCloseToolHistory: $('btn-close-tool-history'),
+      toolHistoryQuery: $('tool-history-query'),
+      toolHistoryTool: $('tool-history-tool'),
+      toolHistoryList: $('tool-history-list'),
+      btnSaveTemplate: $('btn-save-template'),
+      workspaceTemplateSelect: $('workspace-template-select'),
       settingApiKey: $('setting-api-key'),
       settingApiBase: $('setting-api-base'),
       btnSaveSettings: $('btn-save-settings'),
@@ -96,6 +243,10 @@
       mcpEditor: $('mcp-editor'),
       configEditor: $('config-editor'),
       btnSaveMcp: $('btn-save-mcp'),
+      mcpServerList: $('mcp-server-list'),
+      docsLocationInput: $('docs-location-input'),
+      btnAddDocs: $('btn-add-docs'),
+      docsSourceList: $('docs-source-list'),
       btnSaveConfig: $('btn-save-config'),
       // Login modal elements
       loginModal: $('login-modal'),
@@ -120,6 +271,9 @@
       apiKeyInput: $('api-key-input'),
       apiBaseInput: $('api-base-input'),
       btnSaveApikey: $('btn-save-apikey'),
+      loginEndpointSelect: $('login-endpoint-select'),
+      loginEndpointStatus: $('login-endpoint-status'),
+      btnProbeEndpoints: $('btn-probe-endpoints'),
       folderModal: $('folder-modal'),
       btnCloseFolder: $('btn-close-folder'),
       folderList: $('folder-list'),
@@ -132,8 +286,10 @@
       toolApprovalModal: $('tool-approval-modal'),
       btnCloseToolApproval: $('btn-close-tool-approval'),
       toolApprovalTitle: $('tool-approval-title'),
+      toolApprovalWarning: $('tool-approval-warning'),
       toolApprovalDetails: $('tool-approval-details'),
       btnToolApprove: $('btn-tool-approve'),
+      btnToolAlways: $('btn-tool-always'),
       btnToolReject: $('btn-tool-reject'),
     };
   }
@@ -159,11 +315,14 @@
       await loadConfig();
       await loadMcp();
       await loadSkills();
+      loadCustomCommands();
+      await loadSnippets();
       
       // Only load sessions and models if logged in
       if (state.isLoggedIn) {
         await loadSessions();
         await loadModels();
+        await loadInterruptedTurns();
       } else {
         // Show login prompt instead of sessions
         elements.sessionList.innerHTML = '<div style="padding: 24px 16px; text-align: center; color: var(--text-muted); font-size: 13px;">Please login to view sessions</div>';
@@ -178,6 +337,26 @@
       if (listen) {
         listen('chat://event', handleChatEvent);
         listen('oauth://event', handleOAuthEvent);
+        listen('auth://expiring', handleAuthExpiring);
+        listen('auth://renewed', handleAuthRenewed);
+        listen('network://status', handleNetworkStatus);
+        listen('mcp://status', handleMcpStatus);
+        // Labels and order changed in another window
+        listen('sessions://changed', () => loadSessions());
+        listen('file_index://progress', handleFileIndexProgress);
+        listen('semantic://progress', handleSemanticProgress);
+        // Checkers ran after a write tool
+        listen('diagnostics://updated', (event) => {
+          const { work_dir: workDir, report } = event.payload;
+          problems.reports[workDir] = report;
+          if (elements.problemsModal.classList.contains('open') && workDir === gitWorkDir()) {
+            renderProblems(report);
+          }
+        });
+        invoke('network_monitor_start', {
+          intervalSecs: state.settings.low_bandwidth ? 300 : null,
+          degradedMs: null,
+        }).catch(() => {});
       }
     } catch (err) {
       const message = err?.message || err || 'Initialization failed';
@@ -210,8 +389,128 @@
     });
   }
 
+  async function confirmCost(data) {
+    const approved = confirm(
+      `This request to ${data.model} is about ${data.estimated_tokens.toLocaleString()} tokens ` +
+      `(~$${data.estimated_cost_usd.toFixed(2)}), above your $${data.threshold_usd.toFixed(2)} threshold. Send it?`
+    );
+    try {
+      await invoke('cost_confirmation_respond', { requestId: data.request_id, approved });
+    } catch (err) {
+      showError('Failed to answer cost confirmation: ' + (err.message || err));
+    }
+  }
+
+  function budgetText(data) {
+    return data.unit === 'usd'
+      ? `$${data.used.toFixed(2)} of $${data.limit.toFixed(2)}`
+      : `${Math.round(data.used).toLocaleString()} of ${Math.round(data.limit).toLocaleString()} tokens`;
+  }
+
+  async function confirmOverBudget(data) {
+    const approved = confirm(
+      `${data.scope === 'day' ? 'Today\'s' : 'This session\'s'} budget is used up (${budgetText(data)}). ` +
+      'Continue this turn anyway?'
+    );
+    try {
+      await invoke('cost_confirmation_respond', { requestId: data.request_id, approved });
+    } catch (err) {
+      showError('Failed to answer budget confirmation: ' + (err.message || err));
+    }
+  }
+
+  async function confirmModelWait(data, title = null) {
+    const keepWaiting = confirm(
+      `${title ? `${title}: ` : ''}The model hasn't responded for ${data.elapsed_secs}s. Keep waiting?`
+    );
+    try {
+      await invoke('model_wait_respond', { requestId: data.request_id, keepWaiting });
+    } catch {
+      // The response arrived while the prompt was open
+    }
+  }
+
+  // Elapsed time on the pending reply while a slow model produces nothing
+  function showModelWait(elapsedSecs) {
+    showStepStatus(`Waiting for the model… ${elapsedSecs}s`);
+  }
+
+  // Status next to the pending reply's header, cleared when output arrives
+  function showStepStatus(text) {
+    if (!currentMessageEl && !currentThinkingEl) {
+      currentMessageEl = createMessageElement('assistant', '');
+      currentMessageEl.classList.add('streaming');
+      elements.messages.appendChild(currentMessageEl);
+      scrollToBottom();
+    }
+    const target = currentMessageEl || currentThinkingEl;
+    let status = target.querySelector('.message-wait');
+    if (!status) {
+      status = document.createElement('span');
+      status.className = 'message-wait';
+      target.querySelector('.message-header').appendChild(status);
+    }
+    status.textContent = text;
+  }
+
+  function formatTokens(tokens) {
+    return tokens >= 1000 ? `${(tokens / 1000).toFixed(1)}k` : String(tokens);
+  }
+
+  // System prompt parts sent with the last turn; trimmed ones are called out
+  function renderContextBreakdown(components) {
+    const el = elements.contextBreakdown;
+    if (!el) return;
+    if (!components.length) {
+      el.hidden = true;
+      return;
+    }
+    const total = components.reduce((sum, part) => sum + part.tokens, 0);
+    const trimmed = components.filter(part => part.trimmed);
+    el.textContent = trimmed.length
+      ? `Context ${formatTokens(total)} · trimmed ${trimmed.map(part => part.name).join(', ')}`
+      : `Context ${formatTokens(total)}`;
+    el.title = components.map(part => part.trimmed
+      ? `${part.name}: ${formatTokens(part.tokens)} of ${formatTokens(part.original_tokens)} tokens`
+      : `${part.name}: ${formatTokens(part.tokens)} tokens`).join('\n');
+    el.hidden = false;
+  }
+
+  // Follow-up prompts suggested after the last turn; clicking one sends it
+  function renderSuggestions(suggestions) {
+    const el = elements.suggestionList;
+    if (!el) return;
+    el.innerHTML = '';
+    suggestions.forEach(text => {
+      const chip = document.createElement('button');
+      chip.className = 'suggestion-chip';
+      chip.textContent = text;
+      chip.title = text;
+      chip.addEventListener('click', () => sendMessage(text, true));
+      el.appendChild(chip);
+    });
+    el.hidden = suggestions.length === 0;
+  }
+
+  async function loadSuggestions(sessionId) {
+    renderSuggestions([]);
+    try {
+      const suggestions = await invoke('session_suggestions', { sessionId });
+      if (state.currentSession?.id === sessionId) renderSuggestions(suggestions || []);
+    } catch (err) {
+      // Suggestions are optional; a missing cache just shows none
+    }
+  }
+
   function handleChatEvent(event) {
     const { event: eventType, data } = event.payload;
+
+    // Events from sessions that aren't on screen only update bookkeeping;
+    // their transcript is persisted by the backend and loads on open.
+    if (data?.session_id && data.session_id !== state.currentSession?.id) {
+      handleBackgroundChatEvent(eventType, data);
+      return;
+    }
     
     switch (eventType) {
       case 'chunk':
@@ -229,45 +528,147 @@
         if (data?.usage && state.authMode === 'api_key') {
           trackTokenUsage(data.usage);
         }
-        finishStreaming();
+        finishStreaming(data?.session_id);
         break;
       case 'cancelled':
-        finishStreaming();
+        finishStreaming(data?.session_id);
         break;
       case 'tool_status':
         handleToolStatus(data);
         break;
+      case 'mcp_error':
+        showError(`MCP server ${data?.server}: ${data?.message || 'failed to start'}`);
+        break;
+      case 'retry':
+        discardFailedStep();
+        showError(`Request failed, retrying in ${data?.delay_secs ?? 0}s (${data?.attempt}/${data?.max_retries}): ${data?.message || ''}`);
+        break;
+      case 'retrying':
+        showStepStatus(`Retrying in ${data?.remaining_secs}s (${data?.attempt}/${data?.max_retries})…`);
+        break;
+      case 'tool_output_chunk':
+        handleToolOutputChunk(data);
+        break;
       case 'tool_result':
         handleToolResult(data);
         break;
       case 'tool_approval':
         openToolApprovalModal(data);
         break;
+      case 'tool_approval_cancelled':
+        dropToolApproval(data?.request_id);
+        break;
+      case 'cost_confirmation':
+        confirmCost(data);
+        break;
+      case 'budget_warning':
+        showError(`${budgetText(data)} used; ${data.scope === 'day' ? 'today\'s' : 'this session\'s'} budget is almost spent.`);
+        break;
+      case 'budget_exceeded':
+        confirmOverBudget(data);
+        break;
+      case 'degraded_mode':
+        showError(data?.message || 'Running in a degraded mode');
+        break;
+      case 'waiting_for_model':
+        showModelWait(data?.elapsed_secs || 0);
+        break;
+      case 'model_wait_timeout':
+        confirmModelWait(data);
+        break;
+      case 'context_breakdown':
+        renderContextBreakdown(data?.components || []);
+        break;
+      case 'suggestions':
+        renderSuggestions(data?.suggestions || []);
+        break;
+      case 'file_conflict':
+        showError(`${data?.path || 'A file'} changed on disk since Kimi read it; the edit was not applied.`);
+        break;
       case 'error':
         showError(data?.message || 'An error occurred');
-        finishStreaming();
+        finishStreaming(data?.session_id);
+        break;
+    }
+  }
+
+  function handleBackgroundChatEvent(eventType, data) {
+    const title = state.sessions.find(s => s.id === data.session_id)?.title || 'another session';
+    switch (eventType) {
+      case 'done':
+        if (data?.usage && state.authMode === 'api_key') {
+          trackTokenUsage(data.usage);
+        }
+        state.streamingSessions.delete(data.session_id);
+        break;
+      case 'cancelled':
+        state.streamingSessions.delete(data.session_id);
+        break;
+      case 'tool_approval':
+        openToolApprovalModal(data);
+        break;
+      case 'tool_approval_cancelled':
+        dropToolApproval(data?.request_id);
+        break;
+      case 'cost_confirmation':
+        confirmCost(data);
+        break;
+      case 'model_wait_timeout':
+        confirmModelWait(data, title);
+        break;
+      case 'error':
+        showError(`${title}: ${data?.message || 'An error occurred'}`);
+        state.streamingSessions.delete(data.session_id);
         break;
     }
   }
 
   function handleOAuthEvent(event) {
-    const { event: eventType, data } = event.payload;
+    const { event: eventType, login_id: loginId, data } = event.payload;
+    // Events of a login that was since replaced or cancelled
+    if (loginId !== state.loginId) return;
     
     switch (eventType) {
+      case 'code':
+        if (elements.loginUserCode) elements.loginUserCode.textContent = data.user_code;
+        state.verificationUrl = data.verification_uri_complete || data.verification_uri;
+        break;
+
       case 'waiting':
         if (elements.loginStatus) {
-          elements.loginStatus.textContent = data.message || 'Waiting for authorization...';
+          const message = data.message || 'Waiting for authorization...';
+          elements.loginStatus.textContent = data.seconds_left != null
+            ? `${message} (code expires in ${Math.ceil(data.seconds_left / 60)} min)`
+            : message;
+        }
+        break;
+
+      case 'slow_down':
+        if (elements.loginStatus) {
+          elements.loginStatus.textContent = `Server asked to slow down; checking every ${data.interval}s...`;
         }
         break;
+
+      case 'expired':
+        showError(data.message || 'Device code expired. Please try again.');
+        resetLoginModal();
+        break;
+
+      case 'cancelled':
+        resetLoginModal();
+        break;
         
       case 'success':
         state.isLoggedIn = true;
         closeLoginModal();
         updateUserBar();
         loadSessions();
-        loadModels();
+        loadModels({ force: true });
         loadUserProfile();
         showSuccess('Login successful!');
+        // A turn paused for re-authentication resumes on its own; reloading
+        // would drop its streamed output
+        if (state.reauthSessions.size) break;
         // Refresh the main view to show the actual interface
         location.reload();
         break;
@@ -279,12 +680,55 @@
     }
   }
 
+  // A running turn found the OAuth login lapsing (`paused: false`) or gone
+  // (`paused: true`); paused turns wait for the user to log in again.
+  function handleAuthExpiring(event) {
+    const data = event.payload || {};
+    if (!data.paused) {
+      if (!state.authExpiryWarned) {
+        state.authExpiryWarned = true;
+        showError('Your login could not be refreshed and expires soon. Log in again to avoid interrupting the agent.');
+      }
+      return;
+    }
+    state.reauthSessions.add(data.session_id);
+    state.isLoggedIn = false;
+    showError('Login expired. The agent is paused until you log in again.');
+    openLoginModal();
+  }
+
+  function handleAuthRenewed(event) {
+    const sessionId = event.payload?.session_id;
+    state.reauthSessions.delete(sessionId);
+    state.authExpiryWarned = false;
+    state.isLoggedIn = true;
+    closeLoginModal();
+    updateUserBar();
+    showSuccess('Logged in again; resuming.');
+  }
+
+  function handleNetworkStatus(event) {
+    const status = event.payload;
+    const previous = state.networkStatus;
+    state.networkStatus = status?.status || 'online';
+    if (previous && previous !== state.networkStatus && state.networkStatus !== 'online') {
+      showError(status?.reason || `Network ${state.networkStatus}`);
+    }
+  }
+
   let currentMessageEl = null;
   let currentTextBuffer = '';
   let currentThinkingEl = null;
   let currentThinkingBuffer = '';
   const toolMessages = new Map();
+  // Live Shell output kept on screen per call; older output scrolls away
+  const MAX_LIVE_OUTPUT_CHARS = 20000;
+  // Buffer lengths when the current model step began, for discarding a failed step
+  let stepTextStart = 0;
+  let stepThinkingStart = 0;
   let pendingApprovalId = null;
+  let pendingApproval = null;
+  const approvalQueue = [];
 
   function appendStreamingText(text) {
     if (!currentMessageEl) {
@@ -294,6 +738,7 @@
       scrollToBottom();
     }
     
+    currentMessageEl.querySelector('.message-wait')?.remove();
     currentTextBuffer += text;
     
     // Capture reference to avoid race condition with finishStreaming
@@ -318,6 +763,12 @@
   }
   
   function appendThinkingText(text) {
+    // Drop the placeholder reply showing the wait, so thinking comes first
+    if (!currentTextBuffer && currentMessageEl?.querySelector('.message-wait')) {
+      currentMessageEl.remove();
+      currentMessageEl = null;
+    }
+    currentThinkingEl?.querySelector('.message-wait')?.remove();
     if (!currentThinkingEl) {
       // Create thinking container before the main message
       currentThinkingEl = document.createElement('div');
@@ -350,22 +801,33 @@
     });
   }
 
-  async function finishStreaming() {
-    // Save assistant message before clearing
-    if (state.currentSession && currentTextBuffer.trim()) {
-      try {
-        await invoke('session_save_message', {
-          sessionId: state.currentSession.id,
-          role: 'assistant',
-          content: currentTextBuffer
-        });
-      } catch (err) {
-        const message = err?.message || err || 'Failed to save assistant message';
-        showError(message);
+  // Roll the streamed text back to where the failed step started; the backend
+  // re-streams the whole step on retry.
+  function discardFailedStep() {
+    currentTextBuffer = currentTextBuffer.slice(0, Math.min(stepTextStart, currentTextBuffer.length));
+    currentThinkingBuffer = currentThinkingBuffer.slice(0, Math.min(stepThinkingStart, currentThinkingBuffer.length));
+    if (currentMessageEl) {
+      if (currentTextBuffer) {
+        currentMessageEl.querySelector('.message-body').innerHTML = marked.parse(currentTextBuffer);
+      } else {
+        currentMessageEl.remove();
+        currentMessageEl = null;
       }
     }
-    
-    state.isStreaming = false;
+    if (currentThinkingEl) {
+      if (currentThinkingBuffer) {
+        currentThinkingEl.querySelector('.message-body').textContent = currentThinkingBuffer;
+      } else {
+        currentThinkingEl.remove();
+        currentThinkingEl = null;
+      }
+    }
+  }
+
+  async function finishStreaming(sessionId = state.currentSession?.id) {
+    // The backend persists assistant replies and tool results itself
+    if (sessionId) state.streamingSessions.delete(sessionId);
+    if (sessionId && sessionId !== state.currentSession?.id) return;
     state.currentStreamId = null;
     
     if (currentMessageEl) {
@@ -380,6 +842,8 @@
     
     currentTextBuffer = '';
     currentThinkingBuffer = '';
+    stepTextStart = 0;
+    stepThinkingStart = 0;
     hideLoading();
     enableInputs(true);
   }
@@ -447,6 +911,63 @@
     return div;
   }
 
+  // ================================
+  // Image Attachments
+  // ================================
+
+  function addImageFiles(files) {
+    Array.from(files || [])
+      .filter(file => file.type.startsWith('image/'))
+      .forEach(file => {
+        const reader = new FileReader();
+        reader.onload = () => {
+          state.pendingAttachments.push({ name: file.name || 'pasted image', data: reader.result });
+          renderAttachments();
+        };
+        reader.onerror = () => showError(`Failed to read ${file.name}`);
+        reader.readAsDataURL(file);
+      });
+  }
+
+  function renderAttachments() {
+    const list = elements.attachmentList;
+    if (!list) return;
+    list.innerHTML = '';
+    state.pendingAttachments.forEach((attachment, index) => {
+      const chip = document.createElement('div');
+      chip.className = 'attachment-chip';
+      chip.title = attachment.name;
+      chip.innerHTML = `<img src="${attachment.data}" alt="${escapeHtml(attachment.name)}"><button title="Remove">×</button>`;
+      chip.querySelector('button').addEventListener('click', () => {
+        state.pendingAttachments.splice(index, 1);
+        renderAttachments();
+      });
+      list.appendChild(chip);
+    });
+    list.classList.toggle('hidden', !state.pendingAttachments.length);
+  }
+
+  // Hand the pending images to a message and clear the tray
+  function takeAttachments() {
+    const attachments = state.pendingAttachments;
+    state.pendingAttachments = [];
+    renderAttachments();
+    return attachments;
+  }
+
+  function appendMessageImages(msgEl, attachments) {
+    if (!attachments.length) return;
+    const images = document.createElement('div');
+    images.className = 'message-images';
+    attachments.forEach(attachment => {
+      const img = document.createElement('img');
+      img.src = attachment.data;
+      img.alt = attachment.name;
+      images.appendChild(img);
+    });
+    msgEl.querySelector('.message-body')?.appendChild(images);
+  }
+
   function createToolMessageElement(label) {
     const div = document.createElement('div');
     div.className = 'message tool';
@@ -468,6 +989,9 @@
   function handleToolStatus(data) {
     const toolCallId = data?.tool_call_id;
     if (!toolCallId) return;
+    // Tool calls end a model step; anything streamed so far is final
+    stepTextStart = currentTextBuffer.length;
+    stepThinkingStart = currentThinkingBuffer.length;
     let item = toolMessages.get(toolCallId);
     const label = data?.label || data?.name || 'Tool';
     if (!item) {
@@ -476,7 +1000,7 @@
       elements.messages.appendChild(item);
     }
     const body = item.querySelector('.message-body');
-    if (data?.state === 'end') {
+    if (data?.state === 'end' || data?.state === 'retry') {
       const summary = data?.summary;
       body.textContent = summary ? `${label}\n${summary}` : label;
     } else {
@@ -485,10 +1009,27 @@
     scrollToBottom();
   }
 
+  // Live terminal output of a running Shell call; the tool result replaces it
+  function handleToolOutputChunk(data) {
+    const item = toolMessages.get(data?.tool_call_id);
+    if (!item || !data?.chunk) return;
+    let live = item.querySelector('.tool-live-output');
+    if (!live) {
+      live = document.createElement('pre');
+      live.className = 'tool-live-output';
+      item.querySelector('.message-content')?.appendChild(live);
+    }
+    const text = live.textContent + data.chunk;
+    live.textContent = text.length > MAX_LIVE_OUTPUT_CHARS ? text.slice(-MAX_LIVE_OUTPUT_CHARS) : text;
+    live.scrollTop = live.scrollHeight;
+    scrollToBottom();
+  }
+
   function handleToolResult(data) {
     const toolCallId = data?.tool_call_id;
     if (!toolCallId) return;
     let item = toolMessages.get(toolCallId);
+    item?.querySelector('.tool-live-output')?.remove();
     const label = data?.name ? `Tool ${data.name}` : 'Tool';
     if (!item) {
       item = createToolMessageElement(label);
@@ -508,26 +1049,244 @@
   }
 
   function openToolApprovalModal(data) {
+    if (!data?.request_id) return;
+    // Concurrent sessions can ask at the same time; show one request at a time
+    approvalQueue.push(data);
+    if (!pendingApprovalId) showNextApproval();
+  }
+
+  function showNextApproval() {
+    const data = approvalQueue.shift();
+    pendingApproval = data || null;
     pendingApprovalId = data?.request_id || null;
-    if (!pendingApprovalId) return;
+    if (!pendingApprovalId) {
+      elements.toolApprovalModal.classList.remove('open');
+      return;
+    }
     const toolName = data?.name || 'Tool';
-    elements.toolApprovalTitle.textContent = `需要批准：${toolName}`;
-    elements.toolApprovalDetails.textContent = JSON.stringify(data?.args || {}, null, 2);
+    const session = data.session_id !== state.currentSession?.id
+      ? state.sessions.find(s => s.id === data.session_id)
+      : null;
+    elements.toolApprovalTitle.textContent = session
+      ? `需要批准：${toolName}（${session.title}）`
+      : `需要批准：${toolName}`;
+    elements.toolApprovalWarning.hidden = !data.outside_work_dir;
+    // A saved rule can't approve these, so don't offer one
+    elements.btnToolAlways.hidden = !!data.outside_work_dir;
+    if (data?.diff) {
+      renderApprovalDiff(data.diff);
+    } else if (data?.command) {
+      elements.toolApprovalDetails.textContent = `$ ${data.command}\n\n${JSON.stringify(data.args || {}, null, 2)}`;
+    } else {
+      elements.toolApprovalDetails.textContent = JSON.stringify(data?.args || {}, null, 2);
+    }
     elements.toolApprovalModal.classList.add('open');
   }
 
-  async function respondToolApproval(approved) {
+  function diffToHtml(diff) {
+    return diff.split('\n').map(line => {
+      const kind = line.startsWith('@@') ? 'hunk'
+        : line.startsWith('+') && !line.startsWith('+++') ? 'add'
+        : line.startsWith('-') && !line.startsWith('---') ? 'del'
+        : '';
+      return kind ? `<span class="diff-${kind}">${escapeHtml(line)}</span>` : escapeHtml(line);
+    }).join('\n');
+  }
+
+  // Show the proposed change instead of the raw args, which repeat the whole file
+  function renderApprovalDiff(diff) {
+    elements.toolApprovalDetails.innerHTML = diffToHtml(diff);
+  }
+
+  async function respondToolApproval(approved, rule = null) {
     if (!pendingApprovalId) return;
     try {
       await invoke('tool_approval_respond', {
         requestId: pendingApprovalId,
-        approved
+        approved,
+        rule
       });
     } catch (err) {
       showError(err.message || 'Failed to submit approval');
     }
     pendingApprovalId = null;
-    elements.toolApprovalModal.classList.remove('open');
+    pendingApproval = null;
+    showNextApproval();
+  }
+
+  // Dismissing the modal withdraws the request, which the agent treats as a denial
+  async function cancelToolApproval() {
+    if (!pendingApprovalId) return;
+    const requestId = pendingApprovalId;
+    try {
+      await invoke('tool_approval_cancel', { requestId });
+    } catch (err) {
+      showError(err.message || 'Failed to cancel approval');
+    }
+    dropToolApproval(requestId);
+  }
+
+  // Forget a request the backend no longer waits for
+  function dropToolApproval(requestId) {
+    if (!requestId) return;
+    const index = approvalQueue.findIndex(item => item.request_id === requestId);
+    if (index !== -1) approvalQueue.splice(index, 1);
+    if (pendingApprovalId === requestId) {
+      pendingApprovalId = null;
+      pendingApproval = null;
+      showNextApproval();
+    }
+  }
+
+  // Suggest a rule for the pending call: the command without its arguments, or
+  // the folder of the file being written
+  function suggestApprovalRule(data, workDir) {
+    const args = data?.args || {};
+    if (data.name === 'Shell') {
+      const words = String(args.command || '').trim().split(/\s+/);
+      const prefix = words[1] && !words[1].startsWith('-') ? words.slice(0, 2) : words.slice(0, 1);
+      return { kind: 'command_prefix', pattern: prefix.join(' ') };
+    }
+    if (data.name === 'WriteFile' || data.name === 'StrReplaceFile') {
+      let path = String(args.path || '');
+      if (workDir && path.startsWith(workDir)) {
+        path = path.slice(workDir.length).replace(/^[\/\\]+/, '');
+      }
+      const slash = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'));
+      return { kind: 'path_prefix', pattern: slash > 0 ? path.slice(0, slash + 1) : '.' };
+    }
+    return { kind: 'tool', pattern: '' };
+  }
+
+  async function alwaysAllowPendingTool() {
+    const data = pendingApproval;
+    if (!data) return;
+    const workDir = state.sessions.find(s => s.id === data.session_id)?.work_dir
+      || state.settings.work_dir || null;
+    if (!workDir) {
+      showError('No working folder to scope the rule to');
+      return;
+    }
+    const suggestion = suggestApprovalRule(data, workDir);
+    let pattern = suggestion.pattern;
+    if (suggestion.kind === 'command_prefix') {
+      pattern = prompt('Always allow commands starting with:', pattern);
+    } else if (suggestion.kind === 'path_prefix') {
+      pattern = prompt('Always allow writes under (relative to the project folder):', pattern);
+    } else if (!confirm(`Always allow ${data.name} in ${workDir}?`)) {
+      pattern = null;
+    }
+    if (pattern === null) return;
+    await respondToolApproval(true, {
+      work_dir: workDir,
+      kind: suggestion.kind,
+      tool: data.name,
+      pattern: pattern.trim(),
+    });
+    renderApprovalRules();
+  }
+
+  async function renderApprovalRules() {
+    const list = elements.approvalRuleList;
+    if (!list) return;
+    let rules = [];
+    try {
+      rules = await invoke('approval_rules_list', { workDir: null });
+    } catch (err) {
+      showError('Failed to load approval rules: ' + (err.message || err));
+    }
+    const describe = rule => rule.kind === 'tool'
+      ? `${rule.tool}: any call`
+      : `${rule.tool}: ${rule.kind === 'command_prefix' ? 'commands starting with' : 'files under'} ${rule.pattern}`;
+    list.innerHTML = rules.length
+      ? rules.map(rule => `
+        <div class="template-item">
+          <span>${escapeHtml(describe(rule))}<br><span class="setting-hint">${escapeHtml(rule.work_dir)}</span></span>
+          <button class="btn-secondary" data-approval-rule="${escapeHtml(rule.id)}">Delete</button>
+        </div>
+      `).join('')
+      : '<span class="setting-hint">No approval rules yet.</span>';
+    $$('button[data-approval-rule]', list).forEach(btn => {
+      btn.addEventListener('click', async () => {
+        try {
+          await invoke('approval_rule_delete', { id: btn.dataset.approvalRule });
+        } catch (err) {
+          showError('Failed to delete rule: ' + (err.message || err));
+        }
+        renderApprovalRules();
+      });
+    });
+  }
+
+  async function renderApprovalAudit() {
+    const list = elements.approvalAuditList;
+    if (!list) return;
+    let entries = [];
+    try {
+      entries = await invoke('approval_audit_list', { workDir: null, limit: 20 });
+    } catch (err) {
+      showError('Failed to load the approval log: ' + (err.message || err));
+    }
+    list.innerHTML = entries.length
+      ? entries.map(entry => `
+        <div class="template-item">
+          <span>${escapeHtml(entry.decision)} · ${escapeHtml(entry.tool)} ${escapeHtml(entry.targets.join(', '))}<br><span class="setting-hint">${escapeHtml(entry.rule)} · ${escapeHtml(new Date(entry.timestamp * 1000).toLocaleString())}</span></span>
+        </div>
+      `).join('')
+      : '<span class="setting-hint">No automatic decisions yet.</span>';
+  }
+
+  async function renderCredentials() {
+    const list = elements.credentialList;
+    if (!list) return;
+    let credentials = [];
+    try {
+      credentials = await invoke('auth_credentials_list');
+    } catch (err) {
+      showError('Failed to load credentials: ' + (err.message || err));
+    }
+    const describe = c => c.mode === 'oauth'
+      ? 'Kimi login'
+      : `API key${c.has_key ? '' : ' (missing)'}${c.api_base ? ' · ' + c.api_base : ''}`;
+    list.innerHTML = credentials.length
+      ? credentials.map(c => `
+        <div class="template-item">
+          <span>${escapeHtml(c.name)}<br><span class="setting-hint">${escapeHtml(describe(c))}</span></span>
+          <button class="btn-secondary" data-credential="${escapeHtml(c.name)}">Delete</button>
+        </div>
+      `).join('')
+      : '<span class="setting-hint">No named credentials yet.</span>';
+    $$('button[data-credential]', list).forEach(btn => {
+      btn.addEventListener('click', async () => {
+        try {
+          await invoke('auth_credential_delete', { name: btn.dataset.credential });
+        } catch (err) {
+          showError('Failed to delete credential: ' + (err.message || err));
+        }
+        renderCredentials();
+      });
+    });
+  }
+
+  async function saveCredential() {
+    const name = elements.credentialName.value.trim();
+    if (!name) return;
+    try {
+      await invoke('auth_credential_set', {
+        name,
+        credential: {
+          mode: elements.credentialMode.value,
+          api_key: elements.credentialKey.value.trim() || null,
+          api_base: elements.credentialBase.value.trim() || null,
+        },
+      });
+      elements.credentialName.value = '';
+      elements.credentialKey.value = '';
+      elements.credentialBase.value = '';
+      renderCredentials();
+    } catch (err) {
+      showError('Failed to save credential: ' + (err.message || err));
+    }
   }
 
   function scrollToBottom() {
@@ -610,63 +1369,351 @@
       const path = state.settings.mcp_config_files?.[0] || null;
       state.mcp = await invoke('mcp_load', { path });
       elements.mcpEditor.value = state.mcp.raw;
+      loadMcpStatus();
     } catch (err) {
       const message = err?.message || err || 'Failed to load MCP';
       showError(`Failed to load MCP: ${message}`);
     }
   }
 
-  async function loadSkills() {
+  async function loadMcpStatus() {
+    if (!elements.mcpServerList) return;
     try {
-      const payload = await invoke('skills_list', {
-        workDir: state.settings.work_dir || null,
-        skillsDir: state.settings.skills_dir || null
+      const servers = await invoke('mcp_server_status', {
+        configFiles: state.settings.mcp_config_files || [],
       });
-      state.skills = payload;
-      renderSkills();
+      renderMcpServers(servers);
     } catch (err) {
-      const message = err?.message || err || 'Failed to load skills';
-      showError(`Failed to load skills: ${message}`);
+      elements.mcpServerList.innerHTML = '';
     }
   }
 
-  async function loadSessions(allSessions = false) {
-    try {
-      const workDir = state.settings.work_dir || state.paths?.work_dir;
-      // If allSessions is true, pass null to get all sessions
-      // Otherwise filter by work_dir
-      const sessions = await invoke('session_list', { 
-        workDir: allSessions ? null : workDir 
-      });
-      state.sessions = sessions || [];
-      const validIds = new Set(state.sessions.map(s => s.id));
-      state.settings.pinned_sessions = (state.settings.pinned_sessions || []).filter(id => validIds.has(id));
-      renderSessions();
-    } catch (err) {
-      const message = err?.message || err || 'Failed to load sessions';
-      showError(`Failed to load sessions: ${message}`);
-      state.sessions = [];
-      renderSessions();
+  function renderMcpServers(servers) {
+    if (!servers.length) {
+      elements.mcpServerList.innerHTML = '<span class="setting-hint">No servers configured.</span>';
+      return;
     }
+    elements.mcpServerList.innerHTML = servers.map(server => {
+      const detail = server.status === 'running'
+        ? `${server.tools.length} tool(s)`
+        : (server.error || server.status);
+      const action = server.status === 'running' ? 'stop' : 'start';
+      return `
+        <div class="template-item" data-mcp-server="${escapeHtml(server.name)}">
+          <span>${escapeHtml(server.name)} <span class="mcp-status mcp-status-${server.status}">${server.status}</span><br><span class="setting-hint">${escapeHtml(detail)}</span></span>
+          <span>
+            <button class="btn-secondary" data-mcp-action="test">Test</button>
+            <button class="btn-secondary" data-mcp-action="${action}">${action === 'stop' ? 'Stop' : 'Start'}</button>
+          </span>
+        </div>
+      `;
+    }).join('');
+    $$('button[data-mcp-action]', elements.mcpServerList).forEach(btn => {
+      const name = btn.closest('[data-mcp-server]').dataset.mcpServer;
+      btn.addEventListener('click', () => runMcpAction(name, btn.dataset.mcpAction, btn));
+    });
   }
 
-  async function loadModels() {
-    if (!state.isLoggedIn) {
-      state.models = [];
-      renderModels();
-      return;
+  // Entry for `name` in the editor, so unsaved edits can be tested
+  function editorMcpEntry(name) {
+    try {
+      return JSON.parse(elements.mcpEditor.value)?.mcpServers?.[name] || null;
+    } catch (e) {
+      return null;
     }
-    
+  }
+
+  async function runMcpAction(name, action, btn) {
+    const configFiles = state.settings.mcp_config_files || [];
+    btn.disabled = true;
     try {
-      const config = await invoke('auth_get_config');
-      const models = await invoke('llm_fetch_models', { authConfig: config });
-      state.models = models || [];
-      renderModels();
+      if (action === 'test') {
+        const result = await invoke('mcp_server_test_connection', {
+          name,
+          entry: editorMcpEntry(name),
+          configFiles,
+        });
+        if (result.ok) {
+          showSuccess(`${name}: handshake OK, ${result.tools.length} tool(s): ${result.tools.join(', ')}`);
+        } else {
+          showError(`${name}: ${result.error}`);
+        }
+      } else if (action === 'start') {
+        await invoke('mcp_server_start', { name, configFiles });
+      } else {
+        await invoke('mcp_server_stop', { name });
+      }
     } catch (err) {
-      const message = err?.message || err || 'Failed to load models';
-      showError(`Failed to load models: ${message}`);
-      state.models = [];
-      renderModels();
+      showError(`${name}: ${err.message || err}`);
+    } finally {
+      btn.disabled = false;
+      loadMcpStatus();
+    }
+  }
+
+  function handleMcpStatus() {
+    loadMcpStatus();
+  }
+
+  // Large repos get an on-disk index for @-mentions; say when the first one is
+  // ready, not on every background rebuild
+  const indexedWorkDirs = new Set();
+  function handleFileIndexProgress(event) {
+    const { work_dir: workDir, done, ready, indexed } = event.payload || {};
+    if (!done || !ready || indexedWorkDirs.has(workDir)) return;
+    indexedWorkDirs.add(workDir);
+    showSuccess(`Indexed ${indexed.toLocaleString()} files for @-mentions`);
+  }
+
+  function docsWorkDir() {
+    return state.currentSession?.work_dir || state.settings.work_dir || state.paths?.work_dir || null;
+  }
+
+  async function loadDocsSources() {
+    const list = elements.docsSourceList;
+    const workDir = docsWorkDir();
+    if (!list || !workDir) return;
+    let sources = [];
+    try {
+      sources = await invoke('docs_sources_list', { workDir });
+    } catch (err) {
+      showError('Failed to load docs sources: ' + (err.message || err));
+    }
+    list.innerHTML = sources.length
+      ? sources.map(source => {
+          const detail = source.error
+            ? source.error
+            : `${source.chunks} section(s)` + (source.indexed_at ? `, indexed ${new Date(source.indexed_at * 1000).toLocaleString()}` : '');
+          return `
+            <div class="template-item" data-docs-source="${escapeHtml(source.id)}">
+              <span>${escapeHtml(source.location)}<br><span class="setting-hint">${escapeHtml(detail)}</span></span>
+              <span>
+                <button class="btn-secondary" data-docs-action="reindex">Reindex</button>
+                <button class="btn-secondary" data-docs-action="remove">Remove</button>
+              </span>
+            </div>
+          `;
+        }).join('')
+      : '<span class="setting-hint">No documentation registered for this folder.</span>';
+    $$('button[data-docs-action]', list).forEach(btn => {
+      const id = btn.closest('[data-docs-source]').dataset.docsSource;
+      btn.addEventListener('click', async () => {
+        btn.disabled = true;
+        try {
+          if (btn.dataset.docsAction === 'reindex') {
+            const source = await invoke('docs_source_reindex', { id });
+            if (source.error) showError(source.error);
+          } else {
+            await invoke('docs_source_remove', { id });
+          }
+        } catch (err) {
+          showError(err.message || err);
+        }
+        loadDocsSources();
+      });
+    });
+  }
+
+  async function addDocsSource() {
+    const location = elements.docsLocationInput.value.trim();
+    const workDir = docsWorkDir();
+    if (!location) return;
+    if (!workDir) {
+      showError('Select a folder first');
+      return;
+    }
+    elements.btnAddDocs.disabled = true;
+    try {
+      const source = await invoke('docs_source_add', { workDir, location });
+      if (source.error) {
+        showError(`Added, but indexing failed: ${source.error}`);
+      } else {
+        showSuccess(`Indexed ${source.chunks} section(s)`);
+      }
+      elements.docsLocationInput.value = '';
+    } catch (err) {
+      showError('Failed to add docs: ' + (err.message || err));
+    } finally {
+      elements.btnAddDocs.disabled = false;
+    }
+    loadDocsSources();
+  }
+
+  async function loadSkills() {
+    try {
+      const payload = await invoke('skills_list', {
+        workDir: state.settings.work_dir || null,
+        skillsDir: state.settings.skills_dir || null
+      });
+      state.skills = payload;
+      renderSkills();
+    } catch (err) {
+      const message = err?.message || err || 'Failed to load skills';
+      showError(`Failed to load skills: ${message}`);
+    }
+  }
+
+  // Markdown prompt templates from ~/.kimi/commands and the project's .kimi/commands
+  async function loadCustomCommands() {
+    try {
+      state.customCommands = await invoke('commands_list', {
+        workDir: state.settings.work_dir || null
+      }) || [];
+    } catch {
+      state.customCommands = [];
+    }
+  }
+
+  async function loadSnippets() {
+    try {
+      state.snippets = await invoke('snippets_list') || [];
+    } catch (err) {
+      state.snippets = [];
+    }
+  }
+
+  // ================================
+  // Workspace Templates
+  // ================================
+
+  function renderTemplateOptions() {
+    const select = elements.workspaceTemplateSelect;
+    if (!select) return;
+    const templates = state.settings.workspace_templates || [];
+    select.innerHTML = '<option value="">No template</option>' + templates
+      .map(t => `<option value="${escapeHtml(t.name)}">${escapeHtml(t.name)}</option>`)
+      .join('');
+    select.parentElement.classList.toggle('hidden', templates.length === 0);
+  }
+
+  function renderTemplateList() {
+    const list = elements.templateList;
+    if (!list) return;
+    const templates = state.settings.workspace_templates || [];
+    list.innerHTML = templates.length
+      ? templates.map(t => `
+        <div class="template-item">
+          <span>${escapeHtml(t.name)}</span>
+          <button class="btn-secondary" data-template="${escapeHtml(t.name)}">Delete</button>
+        </div>
+      `).join('')
+      : '<span class="setting-hint">No templates yet.</span>';
+    $$('button[data-template]', list).forEach(btn => {
+      btn.addEventListener('click', async () => {
+        try {
+          state.settings.workspace_templates = await invoke('workspace_template_delete', { name: btn.dataset.template });
+          renderTemplateList();
+        } catch (err) {
+          showError('Failed to delete template: ' + (err.message || err));
+        }
+      });
+    });
+  }
+
+  async function saveWorkspaceTemplate() {
+    const name = prompt('Template name (e.g. "Rust backend")');
+    if (!name || !name.trim()) return;
+    const s = state.settings;
+    try {
+      state.settings.workspace_templates = await invoke('workspace_template_save', {
+        template: {
+          name: name.trim(),
+          model: s.model || null,
+          thinking: !!s.thinking,
+          yolo: !!s.yolo,
+          skills_dir: s.skills_dir || null,
+          mcp_config_files: s.mcp_config_files || [],
+          tool_approvals: s.tool_approvals || {},
+          disabled_tools: s.disabled_tools || [],
+          env: s.env || {},
+        },
+      });
+      renderTemplateList();
+      showSuccess(`Saved template "${name.trim()}"`);
+    } catch (err) {
+      showError('Failed to save template: ' + (err.message || err));
+    }
+  }
+
+  async function selectWorkDir(path) {
+    const templateName = elements.workspaceTemplateSelect?.value;
+    if (templateName) {
+      try {
+        const settings = await invoke('workspace_template_apply', { name: templateName, workDir: path });
+        state.settings = { ...state.settings, ...settings };
+        syncYoloSwitches();
+        showSuccess(`Applied template "${templateName}"`);
+      } catch (err) {
+        showError('Failed to apply template: ' + (err.message || err));
+      }
+    }
+    state.settings.work_dir = path;
+    const parts = path.split('/');
+    elements.folderLabel.textContent = parts[parts.length - 1] || path;
+    updateUI();
+    closeModals();
+    loadSkills();
+    loadCustomCommands();
+    loadSessions();
+  }
+
+  async function loadSessions(allSessions = false) {
+    try {
+      const workDir = state.settings.work_dir || state.paths?.work_dir;
+      // If allSessions is true, pass null to get all sessions
+      // Otherwise filter by work_dir
+      const sessions = await invoke('session_list', { 
+        workDir: allSessions ? null : workDir 
+      });
+      state.sessions = sessions || [];
+      // Turns started before a reload keep running in the backend
+      state.sessions.filter(s => s.turn_in_progress).forEach(s => state.streamingSessions.add(s.id));
+      const validIds = new Set(state.sessions.map(s => s.id));
+      state.settings.pinned_sessions = (state.settings.pinned_sessions || []).filter(id => validIds.has(id));
+      renderSessions();
+    } catch (err) {
+      const message = err?.message || err || 'Failed to load sessions';
+      showError(`Failed to load sessions: ${message}`);
+      state.sessions = [];
+      renderSessions();
+    }
+  }
+
+  async function loadModels({ force = false } = {}) {
+    if (!state.isLoggedIn) {
+      state.models = [];
+      renderModels();
+      return;
+    }
+
+    // Low-bandwidth mode reuses the last fetched model list
+    if (state.settings.low_bandwidth && !force) {
+      try {
+        const cached = JSON.parse(localStorage.getItem('kimi.models') || 'null');
+        if (Array.isArray(cached) && cached.length) {
+          state.models = cached;
+          renderModels();
+          return;
+        }
+      } catch (e) {
+        // Fall through to a fresh fetch
+      }
+    }
+    
+    try {
+      const config = await invoke('auth_get_config');
+      const models = await invoke('llm_fetch_models', {
+        authConfig: config,
+        configFile: state.settings.config_file || null,
+      });
+      state.models = models || [];
+      localStorage.setItem('kimi.models', JSON.stringify(state.models));
+      renderModels();
+    } catch (err) {
+      const message = err?.message || err || 'Failed to load models';
+      showError(`Failed to load models: ${message}`);
+      state.models = [];
+      renderModels();
     }
   }
 
@@ -706,6 +1753,9 @@
     elements.settingDefaultModel.innerHTML = models.map(m => 
       `<option value="${m.id}">${m.id}</option>`
     ).join('') || '<option value="">No models available</option>';
+    elements.settingSummarizerModel.innerHTML = '<option value="">Same as chat model</option>' +
+      models.map(m => `<option value="${m.id}">${m.id}</option>`).join('');
+    elements.settingSummarizerModel.value = state.settings.summarizer_model || '';
     
     // Check if current model is still valid, otherwise reset to first available
     const currentModelValid = state.settings.model && models.find(m => m.id === state.settings.model);
@@ -753,6 +1803,57 @@
     });
   }
 
+  // SKILL.md of the first `$name` or `/skill:name` in the message that names a known skill
+  function selectedSkillPath(text) {
+    const skills = state.skills?.skills || [];
+    for (const match of text.matchAll(/(?:^|\s)(?:\$|\/skill:)([\w.-]+)/g)) {
+      const skill = skills.find(s => s.name.toLowerCase() === match[1].toLowerCase());
+      if (skill) return skill.path;
+    }
+    return null;
+  }
+
+  // Tools that reach the network, and tools that change files or run commands
+  const NETWORK_TOOLS = ['SearchWeb', 'FetchURL', 'DownloadFile', 'ReadIssue', 'ReadPullRequest', 'CreatePullRequest'];
+  const MUTATING_TOOLS = [
+    'Shell', 'RunTests', 'Build', 'RunInBackground', 'KillProcess',
+    'WriteFile', 'StrReplaceFile', 'ApplyPatch', 'DownloadFile',
+    'MoveFile', 'CopyFile', 'DeleteFile', 'CreateDirectory', 'CreatePullRequest',
+  ];
+
+  async function loadToolMatrix() {
+    try {
+      const tools = await invoke('tools_get_available', {
+        disabledTools: state.settings.disabled_tools || [],
+        configFile: state.settings.config_file || null,
+      });
+      elements.toolMatrix.innerHTML = tools.map(tool => `
+        <label class="checkbox-label tool-matrix-item">
+          <input type="checkbox" data-tool="${escapeHtml(tool.name)}" ${tool.enabled ? 'checked' : ''} />
+          <span><strong>${escapeHtml(tool.name)}</strong>${tool.source === 'builtin' ? '' : ` <em>${escapeHtml(tool.source)}</em>`}
+            <small>${escapeHtml(tool.description.split('. ')[0])}</small></span>
+        </label>
+      `).join('') || '<div class="list-item">No tools available</div>';
+    } catch (err) {
+      showError('Failed to load tools: ' + (err.message || err));
+    }
+  }
+
+  function setToolMatrix(disabled) {
+    elements.toolMatrix.querySelectorAll('input[data-tool]').forEach(input => {
+      input.checked = !disabled.includes(input.dataset.tool);
+    });
+  }
+
+  // Disabled tools from the matrix, keeping ones it doesn't list (e.g. a stopped MCP server's)
+  function toolMatrixDisabled() {
+    const inputs = [...elements.toolMatrix.querySelectorAll('input[data-tool]')];
+    if (!inputs.length) return state.settings.disabled_tools || [];
+    const listed = inputs.map(input => input.dataset.tool);
+    const unlisted = (state.settings.disabled_tools || []).filter(name => !listed.includes(name));
+    return unlisted.concat(inputs.filter(input => !input.checked).map(input => input.dataset.tool));
+  }
+
   function renderSkills() {
     const skills = state.skills?.skills || [];
     elements.skillsList.innerHTML = skills.map(s => `
@@ -763,6 +1864,29 @@
     `).join('') || '<div class="list-item">No skills found</div>';
   }
 
+  async function showSessionOutline(item) {
+    const session = state.sessions.find(s => s.id === item.dataset.id);
+    if (!session) return;
+    const cacheKey = `${session.id}:${session.message_count ?? ''}`;
+    let outline = state.outlines[cacheKey];
+    if (!outline) {
+      try {
+        outline = await invoke('session_outline', {
+          sessionId: session.id,
+          workDir: session.work_dir,
+          model: null,
+          refresh: false,
+          settings: state.settings,
+        });
+        state.outlines[cacheKey] = outline;
+      } catch (err) {
+        return;
+      }
+    }
+    const lines = outline.items.map((entry, i) => `${i + 1}. ${entry.title}`);
+    item.title = [session.group, ...lines].filter(Boolean).join('\n');
+  }
+
   function renderSessions() {
     if (state.sessions.length === 0) {
       elements.sessionList.innerHTML = '<div style="padding: 20px; text-align: center; color: var(--text-muted); font-size: 13px;">No sessions yet</div>';
@@ -781,13 +1905,19 @@
       const isActive = state.currentSession?.id === s.id;
       const isPinned = pinnedSet.has(s.id);
       const actionsVisible = state.isEditingSessions ? 'visible' : '';
+      const colorClass = s.color ? `session-color-${s.color}` : '';
+      const emoji = s.emoji ? `<span class="session-emoji">${escapeHtml(s.emoji)}</span>` : '';
       return `
-        <div class="session-row">
-          <button class="session-item ${isActive ? 'active' : ''}" data-id="${s.id}">
-            <div class="session-title">${escapeHtml(s.title)}</div>
-            <div class="session-meta">${timeStr}</div>
+        <div class="session-row ${colorClass}" data-id="${s.id}" draggable="${state.isEditingSessions}">
+          <button class="session-item ${isActive ? 'active' : ''}" data-id="${s.id}" title="${escapeHtml(s.group || '')}">
+            <div class="session-title">${emoji}${escapeHtml(s.title)}</div>
+            <div class="session-meta">${timeStr}${s.branch ? ` · ${escapeHtml(s.branch)}` : ''}${state.streamingSessions.has(s.id) ? ' · running' : ''}</div>
           </button>
           <div class="session-actions ${actionsVisible}">
+            <button class="session-action color" data-id="${s.id}" data-action="color" title="Color: ${s.color || 'none'}">
+              <span class="session-color-dot"></span>
+            </button>
+            <button class="session-action emoji" data-id="${s.id}" data-action="emoji" title="Emoji">${s.emoji ? escapeHtml(s.emoji) : '☺'}</button>
             <button class="session-action pin ${isPinned ? 'active' : ''}" data-id="${s.id}" data-action="pin" title="${isPinned ? 'Unpin' : 'Pin'}">
               <svg viewBox="0 0 24 24" width="14" height="14">
                 <path d="M12 17v5" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round"/>
@@ -810,6 +1940,11 @@
         const sessionId = item.dataset.id;
         openSession(sessionId);
       });
+      let hoverTimer = null;
+      item.addEventListener('mouseenter', () => {
+        hoverTimer = setTimeout(() => showSessionOutline(item), 600);
+      });
+      item.addEventListener('mouseleave', () => clearTimeout(hoverTimer));
     });
 
     if (state.isEditingSessions) {
@@ -823,104 +1958,1210 @@
             renderSessions();
             await persistSettings();
           }
+          if (action === 'color' || action === 'emoji') {
+            await labelSession(sessionId, action);
+          }
           if (action === 'delete') {
             await deleteSession(sessionId);
           }
         });
-      });
+      });
+      $$('.session-row', elements.sessionList).forEach(row => {
+        row.addEventListener('dragstart', (e) => {
+          e.dataTransfer.setData('text/plain', row.dataset.id);
+          e.dataTransfer.effectAllowed = 'move';
+        });
+        row.addEventListener('dragover', (e) => e.preventDefault());
+        row.addEventListener('drop', async (e) => {
+          e.preventDefault();
+          const draggedId = e.dataTransfer.getData('text/plain');
+          if (draggedId && draggedId !== row.dataset.id) {
+            await moveSession(draggedId, row.dataset.id);
+          }
+        });
+      });
+    }
+  }
+
+  const SESSION_COLORS = ['red', 'orange', 'yellow', 'green', 'blue', 'purple', 'gray'];
+
+  // The color button cycles through the tags; the emoji button asks for one
+  async function labelSession(sessionId, action) {
+    const session = state.sessions.find(s => s.id === sessionId);
+    if (!session) return;
+    let { color, emoji } = session;
+    if (action === 'color') {
+      const next = SESSION_COLORS.indexOf(color || '') + 1;
+      color = next < SESSION_COLORS.length ? SESSION_COLORS[next] : null;
+    } else {
+      const input = prompt('Emoji for this session (empty to clear)', emoji || '');
+      if (input === null) return;
+      emoji = input.trim() || null;
+    }
+    try {
+      await invoke('session_set_label', { sessionId, color: color || null, emoji: emoji || null });
+      session.color = color || null;
+      session.emoji = emoji || null;
+      renderSessions();
+    } catch (err) {
+      showError(err?.message || err || 'Failed to label session');
+    }
+  }
+
+  // Dropping on a manually ordered session puts the dragged one before it.
+  // Dropping on an unordered one adds an unordered session to the end of the
+  // manual order, or takes an ordered one back out of it.
+  async function moveSession(draggedId, targetId) {
+    const dragged = state.sessions.find(s => s.id === draggedId);
+    const target = state.sessions.find(s => s.id === targetId);
+    if (!dragged || !target) return;
+    const ordered = state.sessions
+      .filter(s => s.position != null && s.id !== draggedId)
+      .map(s => s.id);
+    const unordered = [];
+    if (target.position != null) {
+      ordered.splice(ordered.indexOf(targetId), 0, draggedId);
+    } else if (dragged.position == null) {
+      ordered.push(draggedId);
+    } else {
+      unordered.push(draggedId);
+    }
+    try {
+      await invoke('session_reorder', { sessionIds: ordered, unordered });
+      await loadSessions();
+    } catch (err) {
+      showError(err?.message || err || 'Failed to reorder sessions');
+    }
+  }
+
+  function togglePinnedSession(sessionId) {
+    const list = state.settings.pinned_sessions || [];
+    const idx = list.indexOf(sessionId);
+    if (idx >= 0) {
+      list.splice(idx, 1);
+    } else {
+      list.unshift(sessionId);
+    }
+    state.settings.pinned_sessions = list;
+  }
+
+  async function persistSettings() {
+    await invoke('gui_settings_save', {
+      path: null,
+      settings: state.settings,
+    });
+  }
+
+  async function deleteSession(sessionId) {
+    const session = state.sessions.find(s => s.id === sessionId);
+    if (!session) return;
+    try {
+      await invoke('session_delete', {
+        workDir: session.work_dir,
+        sessionId: session.id
+      });
+      state.sessions = state.sessions.filter(s => s.id !== sessionId);
+      state.settings.pinned_sessions = (state.settings.pinned_sessions || []).filter(id => id !== sessionId);
+      if (state.currentSession?.id === sessionId) {
+        closeChat();
+      } else {
+        renderSessions();
+      }
+      await persistSettings();
+    } catch (err) {
+      const message = err?.message || err || 'Failed to delete session';
+      showError(message);
+    }
+  }
+
+  function escapeHtml(text) {
+    const div = document.createElement('div');
+    div.textContent = text;
+    return div.innerHTML;
+  }
+
+  // Results replace the session list until the search box is cleared
+  async function searchSessions(query) {
+    if (!query.trim()) {
+      renderSessions();
+      return;
+    }
+    try {
+      const results = await invoke('session_search', { query, workDir: null, limit: null });
+      if (!results.length) {
+        elements.sessionList.innerHTML = '<div class="setting-hint" style="padding: 12px;">No matching messages.</div>';
+        return;
+      }
+      elements.sessionList.innerHTML = results.map(result => `
+        <div class="session-item" data-search-session="${escapeHtml(result.session_id)}">
+          <div class="session-title">${escapeHtml(result.title)}</div>
+          ${result.matches.map(match => `
+            <div class="session-search-snippet">${escapeHtml(match.role)}: ${escapeHtml(match.snippet)}</div>
+          `).join('')}
+          ${result.match_count > result.matches.length ? `<div class="session-search-snippet">+${result.match_count - result.matches.length} more</div>` : ''}
+        </div>
+      `).join('');
+      $$('[data-search-session]', elements.sessionList).forEach(item => {
+        item.addEventListener('click', () => {
+          const result = results.find(r => r.session_id === item.dataset.searchSession);
+          if (!state.sessions.some(s => s.id === result.session_id)) {
+            state.sessions.push({ id: result.session_id, title: result.title, work_dir: result.work_dir, updated_at: result.updated_at });
+          }
+          elements.sessionSearch.value = '';
+          openSession(result.session_id);
+        });
+      });
+    } catch (err) {
+      showError('Search failed: ' + (err.message || err));
+    }
+  }
+
+  function openSession(sessionId) {
+    const session = state.sessions.find(s => s.id === sessionId);
+    if (!session) return;
+    
+    state.currentSession = session;
+    if (session.work_dir) {
+      state.settings.work_dir = session.work_dir;
+      updateUI();
+    }
+    state.messages = [];
+    elements.chatTitle.textContent = session.title;
+    
+    elements.emptyState.classList.add('hidden');
+    elements.chatView.classList.remove('hidden');
+    
+    const streaming = state.streamingSessions.has(session.id);
+    enableInputs(!streaming);
+    if (streaming) {
+      showLoading('Kimi is thinking...');
+    } else {
+      hideLoading();
+    }
+    loadSessionMessages(session).then(() => renderInterruptedTurn(session.id));
+    loadSuggestions(session.id);
+    renderSessions();
+    refreshMirrorButton();
+    refreshShareButton();
+    refreshSessionPolicy();
+  }
+
+  async function refreshSessionPolicy() {
+    const session = state.currentSession;
+    if (!elements.sessionPolicySelect) return;
+    elements.sessionPolicySelect.value = '';
+    if (!session) return;
+    try {
+      elements.sessionPolicySelect.value = await invoke('session_policy_get', { sessionId: session.id }) || '';
+    } catch {
+      // Falls back to the default policy
+    }
+  }
+
+  async function setSessionPolicy() {
+    const session = state.currentSession;
+    if (!session) return;
+    const preset = elements.sessionPolicySelect.value || null;
+    try {
+      await invoke('session_policy_set', { sessionId: session.id, preset });
+      showSuccess(preset ? `Command policy for this chat: ${preset}` : 'This chat uses the default command policy');
+    } catch (err) {
+      showError('Failed to set command policy: ' + (err.message || err));
+    }
+  }
+
+  async function loadInterruptedTurns() {
+    try {
+      const turns = await invoke('interrupted_turns');
+      state.interruptedTurns = new Map(turns.map(turn => [turn.session_id, turn]));
+      if (turns.length) {
+        showError(`${turns.length} turn(s) were interrupted when Kimi last quit. Open the session to resume.`);
+      }
+    } catch {
+      state.interruptedTurns = new Map();
+    }
+  }
+
+  function renderInterruptedTurn(sessionId) {
+    const turn = state.interruptedTurns.get(sessionId);
+    if (!turn || sessionId !== state.currentSession?.id || state.streamingSessions.has(sessionId)) return;
+    elements.messages.querySelector('.interrupted-turn')?.remove();
+    const notice = document.createElement('div');
+    notice.className = 'interrupted-turn';
+    const text = document.createElement('span');
+    text.textContent = `This turn was interrupted at step ${turn.steps + 1}. Finished tool calls won't run again.`;
+    const resume = document.createElement('button');
+    resume.className = 'btn-primary';
+    resume.textContent = 'Resume interrupted turn';
+    resume.addEventListener('click', () => resumeInterruptedTurn(sessionId));
+    const discard = document.createElement('button');
+    discard.className = 'btn-secondary';
+    discard.textContent = 'Dismiss';
+    discard.addEventListener('click', async () => {
+      state.interruptedTurns.delete(sessionId);
+      notice.remove();
+      await invoke('interrupted_turn_discard', { sessionId }).catch(() => {});
+    });
+    notice.append(text, resume, discard);
+    elements.messages.appendChild(notice);
+    scrollToBottom();
+  }
+
+  async function resumeInterruptedTurn(sessionId) {
+    if (state.streamingSessions.has(sessionId)) return;
+    if (!state.isLoggedIn) {
+      showError('Please login first');
+      openLoginModal();
+      return;
+    }
+    const turn = state.interruptedTurns.get(sessionId);
+    state.interruptedTurns.delete(sessionId);
+    elements.messages.querySelector('.interrupted-turn')?.remove();
+
+    currentMessageEl = null;
+    currentTextBuffer = '';
+    state.streamingSessions.add(sessionId);
+    enableInputs(false);
+    showLoading('Kimi is thinking...');
+
+    try {
+      await invoke('chat_resume_turn', {
+        sessionId,
+        settings: {
+          ...state.settings,
+          work_dir: turn?.work_dir || state.currentSession?.work_dir || state.settings.work_dir,
+        },
+      });
+    } catch (err) {
+      showError('Failed to resume turn: ' + (err?.message || err));
+      finishStreaming(sessionId);
+    }
+  }
+
+  async function loadSessionMessages(session) {
+    elements.messages.innerHTML = '';
+    currentMessageEl = null;
+    currentTextBuffer = '';
+    currentThinkingEl = null;
+    currentThinkingBuffer = '';
+    toolMessages.clear();
+    try {
+      const messages = await invoke('session_messages', {
+        workDir: session.work_dir,
+        sessionId: session.id
+      });
+      
+      if (!messages || messages.length === 0) {
+        elements.messages.innerHTML = '<div style="text-align: center; color: var(--text-muted); padding: 40px;">No messages yet</div>';
+        return;
+      }
+      
+      messages.forEach((msg, index) => {
+        if (msg.role === 'tool') {
+          const ref = (msg.parts || []).find(p => p.type === 'tool_ref');
+          let summary = '';
+          try {
+            summary = JSON.parse(msg.content)?.summary || '';
+          } catch (e) {
+            summary = msg.content;
+          }
+          const label = ref?.name ? `Tool ${ref.name}` : 'Tool';
+          elements.messages.appendChild(createToolMessageElement(summary ? `${label}\n${summary}` : label));
+          return;
+        }
+        if (msg.role === 'assistant' && !msg.content) return;
+        const msgEl = createMessageElement(msg.role, msg.content);
+        const imageCount = msg.role === 'user'
+          ? (msg.parts || []).filter(p => p.type === 'image' && !msg.content.includes(p.src)).length
+          : 0;
+        if (imageCount) {
+          const note = document.createElement('div');
+          note.className = 'message-images-note';
+          note.textContent = `${imageCount} image${imageCount === 1 ? '' : 's'} attached`;
+          msgEl.querySelector('.message-body')?.appendChild(note);
+        }
+        const evidenceCount = (msg.parts || []).filter(p => p.type === 'evidence').length;
+        if (evidenceCount) {
+          attachEvidenceToggle(msgEl, session, index, evidenceCount);
+        }
+        elements.messages.appendChild(msgEl);
+      });
+      
+      scrollToBottom();
+    } catch (err) {
+      const errorMsg = err?.message || err || 'Unknown error';
+      elements.messages.innerHTML = `<div style="text-align: center; color: var(--error); padding: 40px;">Failed to load messages: ${errorMsg}</div>`;
+    }
+  }
+
+  // "Evidence" link under a reply, expanding to the claims and the tool results behind them
+  function attachEvidenceToggle(msgEl, session, messageIndex, count) {
+    const content = msgEl.querySelector('.message-content');
+    if (!content) return;
+    const toggle = document.createElement('button');
+    toggle.className = 'evidence-toggle';
+    toggle.textContent = `Evidence (${count})`;
+    const list = document.createElement('div');
+    list.className = 'evidence-list hidden';
+    toggle.addEventListener('click', async () => {
+      if (!list.classList.contains('hidden')) {
+        list.classList.add('hidden');
+        return;
+      }
+      try {
+        const items = await invoke('message_evidence', {
+          sessionId: session.id,
+          messageIndex,
+          workDir: session.work_dir,
+        });
+        list.innerHTML = items.map(item => `
+          <div class="evidence-item">
+            <div class="evidence-claim">${escapeHtml(item.claim)}</div>
+            <div class="evidence-source">${escapeHtml(item.tool)} · ${escapeHtml(item.target)}${item.summary ? ' — ' + escapeHtml(item.summary) : ''}</div>
+            ${item.output ? `<pre class="evidence-output">${escapeHtml(item.output)}</pre>` : ''}
+          </div>
+        `).join('');
+        list.classList.remove('hidden');
+      } catch (err) {
+        showError('Failed to load evidence: ' + (err.message || err));
+      }
+    });
+    content.appendChild(toggle);
+    content.appendChild(list);
+  }
+
+  // ================================
+  // Ignored Files
+  // ================================
+
+  // Show the built-in list as the placeholder, plus any project additions.
+  async function loadIgnoreDefaults() {
+    const workDir = state.settings.work_dir;
+    if (!workDir) return;
+    try {
+      const patterns = await invoke('ignore_patterns', { workDir });
+      let placeholder = `Defaults: ${patterns.defaults.join(' ')}`;
+      if (patterns.project.length) {
+        placeholder += `\nThis project adds: ${patterns.project.join(' ')}`;
+      }
+      elements.settingIgnorePatterns.placeholder = placeholder;
+    } catch {
+      // Keep the generic placeholder
+    }
+  }
+
+  // ================================
+  // Budgets
+  // ================================
+
+  async function loadBudgetUsage() {
+    try {
+      const spend = await invoke('cost_usage', { sessionId: state.currentSession?.id || null });
+      const usage = (u) => `${u.tokens.toLocaleString()} tokens ($${u.cost_usd.toFixed(2)})`;
+      elements.budgetUsage.textContent = state.currentSession
+        ? `Today: ${usage(spend.today)} · This session: ${usage(spend.session)}`
+        : `Today: ${usage(spend.today)}`;
+    } catch {
+      elements.budgetUsage.textContent = '';
+    }
+  }
+
+  // ================================
+  // Background Mode
+  // ================================
+
+  async function loadBackgroundStatus() {
+    try {
+      const status = await invoke('background_status');
+      elements.settingAutostart.checked = status.autostart;
+    } catch {
+      elements.settingAutostart.disabled = true;
+    }
+  }
+
+  async function setAutostart(enabled) {
+    try {
+      const status = await invoke('autostart_set', { enabled });
+      elements.settingAutostart.checked = status.autostart;
+      showSuccess(enabled ? 'Will start at login in the tray' : 'Removed from login items');
+    } catch (err) {
+      elements.settingAutostart.checked = !enabled;
+      showError('Failed to update login item: ' + (err.message || err));
+    }
+  }
+
+  // ================================
+  // Git Changes
+  // ================================
+
+  function gitWorkDir() {
+    return state.currentSession?.work_dir || state.settings.work_dir || null;
+  }
+
+  async function loadGitChanges() {
+    const workDir = gitWorkDir();
+    if (!workDir) return;
+    elements.gitDiff.style.display = 'none';
+    try {
+      const [status, branch, log] = await Promise.all([
+        invoke('git_status', { workDir }),
+        invoke('git_branch_info', { workDir }),
+        invoke('git_log', { workDir, limit: 5 }),
+      ]);
+      const tracking = branch.upstream
+        ? ` → ${branch.upstream} (↑${branch.ahead} ↓${branch.behind})`
+        : '';
+      elements.gitBranch.textContent = `${branch.current || `detached at ${branch.head}`}${tracking}`;
+      elements.gitFileList.innerHTML = status.files.length
+        ? status.files.map(file => {
+            const code = (file.index + file.worktree).trim() || '?';
+            return `
+              <div class="template-item">
+                <label class="checkbox-label">
+                  <input type="checkbox" data-git-path="${escapeHtml(file.path)}" checked />
+                  <span><code>${escapeHtml(code)}</code> ${escapeHtml(file.path)}</span>
+                </label>
+                <button class="btn-secondary" data-git-diff="${escapeHtml(file.path)}" data-git-staged="${file.worktree === ' '}">Diff</button>
+              </div>
+            `;
+          }).join('')
+        : '<span class="setting-hint">Working tree clean.</span>';
+      $$('button[data-git-diff]', elements.gitFileList).forEach(btn => {
+        btn.addEventListener('click', () => showGitDiff(btn.dataset.gitDiff, btn.dataset.gitStaged === 'true'));
+      });
+      elements.gitLog.innerHTML = log.map(commit => `
+        <div class="setting-hint"><code>${escapeHtml(commit.short_hash)}</code> ${escapeHtml(commit.subject)} · ${escapeHtml(commit.author)}</div>
+      `).join('');
+      loadLinkedIssues(workDir);
+    } catch (err) {
+      elements.gitBranch.textContent = '';
+      elements.gitFileList.innerHTML = `<span class="setting-hint">${escapeHtml(String(err.message || err))}</span>`;
+      elements.gitLog.innerHTML = '';
+    }
+  }
+
+  // Issues the branch name or its commits refer to; loaded after the rest of
+  // the panel since it goes over the network
+  async function loadLinkedIssues(workDir) {
+    elements.gitIssues.innerHTML = '';
+    let linked;
+    try {
+      linked = await invoke('forge_linked_issues', { workDir });
+    } catch (err) {
+      elements.gitIssues.innerHTML = `<span class="setting-hint">Linked issues unavailable: ${escapeHtml(String(err.message || err))}</span>`;
+      return;
+    }
+    if (workDir !== gitWorkDir()) return;
+    const header = `<div class="setting-hint">${escapeHtml(linked.forge)} · ${escapeHtml(linked.project)}</div>`;
+    elements.gitIssues.innerHTML = header + (linked.issues.length
+      ? linked.issues.map(issue => `
+          <div class="template-item">
+            <span><code>#${issue.number}</code> ${escapeHtml(issue.title)} · ${escapeHtml(issue.state)}</span>
+            <button class="btn-secondary" data-issue-ref="#${issue.number}">Mention</button>
+          </div>
+        `).join('')
+      : '<span class="setting-hint">No issues linked from this branch\'s name or commits.</span>');
+    $$('button[data-issue-ref]', elements.gitIssues).forEach(btn => {
+      btn.addEventListener('click', () => {
+        const input = elements.chatInput;
+        const reference = `issue ${btn.dataset.issueRef}`;
+        input.value = input.value ? `${input.value.trimEnd()} ${reference}` : reference;
+        elements.gitModal.classList.remove('open');
+        input.focus();
+      });
+    });
+  }
+
+  async function showGitDiff(path, staged) {
+    try {
+      const diff = await invoke('git_diff', { workDir: gitWorkDir(), staged, path });
+      elements.gitDiff.style.display = 'block';
+      if (diff.trim()) {
+        elements.gitDiff.innerHTML = diffToHtml(diff);
+      } else {
+        elements.gitDiff.textContent = 'New file (untracked). Select it and commit to add it.';
+      }
+    } catch (err) {
+      showError('Failed to load diff: ' + (err.message || err));
+    }
+  }
+
+  async function commitGitChanges() {
+    const message = elements.gitCommitMessage.value.trim();
+    const paths = Array.from($$('input[data-git-path]:checked', elements.gitFileList))
+      .map(input => input.dataset.gitPath);
+    if (!message) {
+      showError('Enter a commit message');
+      return;
+    }
+    if (!paths.length) {
+      showError('Select at least one file');
+      return;
+    }
+    elements.btnGitCommit.disabled = true;
+    try {
+      const commit = await invoke('git_commit', { workDir: gitWorkDir(), message, paths, all: false });
+      elements.gitCommitMessage.value = '';
+      showSuccess(`Committed ${commit.short_hash}`);
+    } catch (err) {
+      showError('Commit failed: ' + (err.message || err));
+    } finally {
+      elements.btnGitCommit.disabled = false;
+    }
+    loadGitChanges();
+  }
+
+  // ================================
+  // Worklog
+  // ================================
+
+  async function showWorklog(refresh = false) {
+    const session = state.currentSession;
+    if (!session) return;
+    elements.worklogModal.classList.add('open');
+    elements.worklogText.value = 'Generating worklog...';
+    try {
+      const worklog = await invoke('session_worklog', {
+        sessionId: session.id,
+        workDir: session.work_dir || null,
+        model: null,
+        refresh,
+        settings: state.settings,
+      });
+      elements.worklogText.value = worklog.markdown;
+    } catch (err) {
+      elements.worklogText.value = '';
+      showError('Failed to generate worklog: ' + (err.message || err));
+    }
+  }
+
+  // ================================
+  // Workspace Statistics
+  // ================================
+
+  function formatBytes(bytes) {
+    const units = ['B', 'KB', 'MB', 'GB'];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+      value /= 1024;
+      unit += 1;
+    }
+    return unit ? `${value.toFixed(1)} ${units[unit]}` : `${value} B`;
+  }
+
+  function statsTable(headers, rows) {
+    if (!rows.length) return '<div class="setting-hint">Nothing to show.</div>';
+    return `
+      <table class="stats-table">
+        <tr>${headers.map(h => `<th>${escapeHtml(h)}</th>`).join('')}</tr>
+        ${rows.map(row => `<tr>${row.map(cell => `<td>${escapeHtml(String(cell))}</td>`).join('')}</tr>`).join('')}
+      </table>
+    `;
+  }
+
+  async function showWorkspaceStats(refresh = false) {
+    const workDir = gitWorkDir();
+    if (!workDir) {
+      showError('Pick a folder first');
+      return;
+    }
+    elements.workspaceStatsModal.classList.add('open');
+    elements.workspaceStatsBody.innerHTML = '<div class="setting-hint">Counting files...</div>';
+    try {
+      const stats = await invoke('workspace_stats', { workDir, refresh });
+      const git = stats.git;
+      elements.workspaceStatsBody.innerHTML = `
+        <p>${stats.truncated ? 'Over ' : ''}${stats.files.toLocaleString()} files,
+          ${stats.lines.toLocaleString()} lines of code, ${formatBytes(stats.bytes)}</p>
+        <h4>Languages</h4>
+        ${statsTable(['Language', 'Files', 'Lines'],
+          stats.languages.map(l => [l.language, l.files.toLocaleString(), l.lines.toLocaleString()]))}
+        <h4>Largest Folders</h4>
+        ${statsTable(['Folder', 'Files', 'Size'],
+          stats.largest_dirs.map(d => [d.path + '/', d.files.toLocaleString(), formatBytes(d.bytes)]))}
+        <h4>Git Activity</h4>
+        ${git ? `
+          <p>${escapeHtml(git.branch || '(detached)')}: ${git.commits} commits in the last ${git.days} days</p>
+          ${statsTable(['Author', 'Commits'], git.authors)}
+          ${statsTable(['Most changed file', 'Commits'], git.hot_files)}
+          ${statsTable(['Commit', 'Subject'], git.latest.map(c => [c.short_hash, c.subject]))}
+        ` : '<div class="setting-hint">Not a git repository.</div>'}
+      `;
+    } catch (err) {
+      elements.workspaceStatsBody.innerHTML = '';
+      showError('Failed to compute workspace statistics: ' + (err.message || err));
+    }
+  }
+
+  // ================================
+  // Code Outline
+  // ================================
+
+  function showOutline() {
+    if (!gitWorkDir()) {
+      showError('Pick a folder first');
+      return;
+    }
+    elements.outlineModal.classList.add('open');
+    elements.outlinePath.focus();
+    if (elements.outlinePath.value.trim()) loadOutline();
+  }
+
+  async function loadOutline() {
+    const workDir = gitWorkDir();
+    const path = elements.outlinePath.value.trim();
+    if (!workDir || !path) return;
+    elements.outlineList.innerHTML = '<div class="setting-hint">Parsing...</div>';
+    try {
+      const symbols = await invoke('code_outline', { workDir, path });
+      elements.outlineList.innerHTML = symbols.length ? '' : '<div class="setting-hint">No declarations found.</div>';
+      for (const symbol of symbols) {
+        const row = document.createElement('div');
+        row.className = 'code-outline-item';
+        row.style.paddingLeft = `${8 + symbol.depth * 16}px`;
+        row.title = `${symbol.kind}, lines ${symbol.line}-${symbol.end_line}`;
+        row.innerHTML = `
+          <span class="code-outline-kind">${escapeHtml(symbol.kind)}</span>
+          <span class="code-outline-signature">${escapeHtml(symbol.signature)}</span>
+          <span class="setting-hint">${symbol.line}</span>`;
+        row.addEventListener('click', () => {
+          const input = elements.chatInput;
+          const reference = `\`${path}:${symbol.line}\` (${symbol.name})`;
+          input.value = input.value ? `${input.value.trimEnd()} ${reference}` : reference;
+          elements.outlineModal.classList.remove('open');
+          input.focus();
+        });
+        elements.outlineList.appendChild(row);
+      }
+    } catch (err) {
+      elements.outlineList.innerHTML = `<div class="setting-hint">${escapeHtml(err.message || String(err))}</div>`;
+    }
+  }
+
+  // ================================
+  // Problems
+  // ================================
+
+  // Latest checker report per work dir, from the panel or after write tools
+  const problems = { reports: {} };
+
+  function renderProblems(report) {
+    const failed = report.checkers.filter(c => c.error);
+    const errors = report.diagnostics.filter(d => d.severity === 'error').length;
+    elements.problemsBody.innerHTML = `
+      <p>${errors} error(s), ${report.diagnostics.length - errors} warning(s) from
+        ${report.checkers.map(c => escapeHtml(c.name)).join(', ')}</p>
+      ${failed.map(c => `<div class="setting-hint">${escapeHtml(c.name)} (${escapeHtml(c.command)}) ${escapeHtml(c.error)}</div>`).join('')}
+      ${statsTable(['Location', 'Severity', 'Message'], report.diagnostics.map(d => [
+        `${d.path}:${d.line}${d.column ? ':' + d.column : ''}`,
+        d.severity,
+        `[${d.checker}] ${d.message}`,
+      ]))}
+    `;
+  }
+
+  async function showProblems() {
+    const workDir = gitWorkDir();
+    if (!workDir) {
+      showError('Pick a folder first');
+      return;
+    }
+    elements.problemsModal.classList.add('open');
+    const report = problems.reports[workDir];
+    if (report) {
+      renderProblems(report);
+    } else {
+      await runDiagnostics();
+    }
+  }
+
+  async function runDiagnostics() {
+    const workDir = gitWorkDir();
+    if (!workDir) return;
+    elements.problemsBody.innerHTML = '<div class="setting-hint">Running checkers...</div>';
+    elements.btnProblemsRun.disabled = true;
+    try {
+      const report = await invoke('diagnostics_run', { workDir, env: state.settings.env || null });
+      problems.reports[workDir] = report;
+      renderProblems(report);
+    } catch (err) {
+      elements.problemsBody.innerHTML = `<div class="setting-hint">${escapeHtml(err.message || String(err))}</div>`;
+    } finally {
+      elements.btnProblemsRun.disabled = false;
+    }
+  }
+
+  // ================================
+  // Background Processes
+  // ================================
+
+  // Output of the selected process is appended from `offset` on each poll
+  const processView = { id: null, offset: 0, timer: null };
+
+  async function showProcesses() {
+    elements.processesModal.classList.add('open');
+    loadProjectCommands();
+    await loadProcesses();
+    processView.timer = processView.timer || setInterval(pollProcesses, 2000);
+  }
+
+  function closeProcesses() {
+    elements.processesModal.classList.remove('open');
+    clearInterval(processView.timer);
+    processView.timer = null;
+  }
+
+  async function loadProcesses() {
+    try {
+      const processes = await invoke('background_processes_list', { sessionId: null });
+      elements.processList.innerHTML = processes.length ? '' : '<div class="setting-hint">No background processes.</div>';
+      for (const proc of processes) {
+        const status = proc.running
+          ? 'running'
+          : proc.exit_code == null ? 'killed' : `exited (${proc.exit_code})`;
+        const row = document.createElement('div');
+        row.className = 'template-item';
+        row.innerHTML = `
+          <span style="cursor: pointer;">${escapeHtml(proc.command)}<br><span class="setting-hint">${escapeHtml(proc.id)} · ${escapeHtml(status)}</span></span>
+          <button class="btn-secondary">${proc.running ? 'Stop' : 'Remove'}</button>`;
+        row.querySelector('span').addEventListener('click', () => selectProcess(proc.id));
+        row.querySelector('button').addEventListener('click', async () => {
+          try {
+            await invoke(proc.running ? 'background_process_kill' : 'background_process_remove', { id: proc.id });
+            if (!proc.running && processView.id === proc.id) selectProcess(null);
+            await loadProcesses();
+          } catch (err) {
+            showError(err.message || String(err));
+          }
+        });
+        elements.processList.appendChild(row);
+      }
+    } catch (err) {
+      showError('Failed to list processes: ' + (err.message || err));
+    }
+  }
+
+  function selectProcess(id) {
+    processView.id = id;
+    processView.offset = 0;
+    elements.processOutput.textContent = '';
+    elements.processOutput.style.display = id ? 'block' : 'none';
+    if (id) pollProcesses();
+  }
+
+  async function pollProcesses() {
+    await loadProcesses();
+    if (!processView.id) return;
+    try {
+      const read = await invoke('background_process_output', {
+        id: processView.id,
+        since: processView.offset,
+      });
+      processView.offset = read.next_offset;
+      if (read.output) {
+        elements.processOutput.textContent += read.output;
+        elements.processOutput.scrollTop = elements.processOutput.scrollHeight;
+      }
+    } catch (err) {
+      selectProcess(null);
+    }
+  }
+
+  async function startProcess(command = elements.processCommand.value.trim()) {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir;
+    if (!command || !workDir) return;
+    try {
+      const proc = await invoke('background_process_start', { command, workDir });
+      if (command === elements.processCommand.value.trim()) elements.processCommand.value = '';
+      selectProcess(proc.id);
+    } catch (err) {
+      showError('Failed to start process: ' + (err.message || err));
+    }
+  }
+
+  // Test, build and other commands declared in .kimi/commands.toml or AGENTS.md
+  async function loadProjectCommands() {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir;
+    elements.projectCommandList.innerHTML = '';
+    if (!workDir) return;
+    try {
+      const commands = await invoke('project_commands', { workDir });
+      for (const command of commands) {
+        const button = document.createElement('button');
+        button.className = 'btn-secondary';
+        button.textContent = command.name;
+        button.title = `${command.command}${command.description ? ' — ' + command.description : ''} (${command.source})`;
+        button.addEventListener('click', () => startProcess(command.command));
+        elements.projectCommandList.appendChild(button);
+      }
+    } catch (err) {
+      showError('Failed to read project commands: ' + (err.message || err));
+    }
+  }
+
+  async function resetShell() {
+    const session = state.currentSession;
+    if (!session) return;
+    try {
+      const reset = await invoke('reset_shell', { sessionId: session.id });
+      showSuccess(reset ? 'Shell reset' : 'This session has no shell running');
+    } catch (err) {
+      showError('Failed to reset shell: ' + (err.message || err));
+    }
+  }
+
+  async function exportSession() {
+    const session = state.currentSession;
+    if (!session) return;
+    const format = prompt('Export format: markdown, json or kimichat (full archive)', 'markdown');
+    if (!format) return;
+    const archive = format.trim().toLowerCase() === 'kimichat';
+    try {
+      const path = archive
+        ? await invoke('session_export_archive', {
+          sessionId: session.id,
+          workDir: session.work_dir || null,
+          path: null,
+        })
+        : await invoke('session_export', {
+          sessionId: session.id,
+          format: format.trim().toLowerCase(),
+          workDir: session.work_dir || null,
+        });
+      if (path) showSuccess(`Exported to ${path}`);
+    } catch (err) {
+      showError('Export failed: ' + (err.message || err));
     }
   }
 
-  function togglePinnedSession(sessionId) {
-    const list = state.settings.pinned_sessions || [];
-    const idx = list.indexOf(sessionId);
-    if (idx >= 0) {
-      list.splice(idx, 1);
-    } else {
-      list.unshift(sessionId);
+  async function importSession() {
+    try {
+      const sessionId = await invoke('session_import_archive', { path: null, workDir: null });
+      if (!sessionId) return;
+      await loadSessions(true);
+      openSession(sessionId);
+      showSuccess('Session imported');
+    } catch (err) {
+      showError('Import failed: ' + (err.message || err));
     }
-    state.settings.pinned_sessions = list;
   }
 
-  async function persistSettings() {
-    await invoke('gui_settings_save', {
-      path: null,
-      settings: state.settings,
-    });
+  async function refreshMirrorButton() {
+    const session = state.currentSession;
+    if (!session || !elements.btnMirrorSession) return;
+    try {
+      const mirrors = await invoke('session_mirror_list');
+      const mirror = mirrors.find(m => m.session_id === session.id);
+      elements.btnMirrorSession.classList.toggle('active', Boolean(mirror));
+      elements.btnMirrorSession.title = mirror
+        ? `Mirroring to ${mirror.path} (click to stop)`
+        : 'Mirror transcript live to a file';
+    } catch {
+      // Mirroring is optional; leave the button as is
+    }
   }
 
-  async function deleteSession(sessionId) {
-    const session = state.sessions.find(s => s.id === sessionId);
+  async function toggleMirror() {
+    const session = state.currentSession;
     if (!session) return;
     try {
-      await invoke('session_delete', {
-        workDir: session.work_dir,
-        sessionId: session.id
-      });
-      state.sessions = state.sessions.filter(s => s.id !== sessionId);
-      state.settings.pinned_sessions = (state.settings.pinned_sessions || []).filter(id => id !== sessionId);
-      if (state.currentSession?.id === sessionId) {
-        closeChat();
+      if (elements.btnMirrorSession.classList.contains('active')) {
+        await invoke('session_mirror_stop', { sessionId: session.id });
+        showSuccess('Stopped live mirror');
       } else {
-        renderSessions();
+        const format = prompt('Mirror format: html (auto-refreshing page) or markdown', 'html');
+        if (!format) return;
+        const mirror = await invoke('session_mirror_start', {
+          sessionId: session.id,
+          format: format.trim().toLowerCase(),
+          path: null,
+          workDir: session.work_dir || null,
+        });
+        if (mirror) showSuccess(`Mirroring to ${mirror.path}`);
       }
-      await persistSettings();
     } catch (err) {
-      const message = err?.message || err || 'Failed to delete session';
-      showError(message);
+      showError('Live mirror failed: ' + (err.message || err));
     }
+    refreshMirrorButton();
   }
 
-  function escapeHtml(text) {
-    const div = document.createElement('div');
-    div.textContent = text;
-    return div.innerHTML;
+  async function refreshShareButton() {
+    const session = state.currentSession;
+    if (!session || !elements.btnShareSession) return;
+    try {
+      const shares = await invoke('session_share_list');
+      const share = shares.find(s => s.session_id === session.id);
+      elements.btnShareSession.classList.toggle('active', Boolean(share));
+      elements.btnShareSession.title = share
+        ? `Shared at ${share.url} (click to stop)`
+        : 'Share a read-only live view on your network';
+    } catch {
+      // Sharing is optional; leave the button as is
+    }
   }
 
-  function openSession(sessionId) {
-    const session = state.sessions.find(s => s.id === sessionId);
+  async function toggleShare() {
+    const session = state.currentSession;
     if (!session) return;
-    
-    state.currentSession = session;
-    if (session.work_dir) {
-      state.settings.work_dir = session.work_dir;
-      updateUI();
+    try {
+      if (elements.btnShareSession.classList.contains('active')) {
+        await invoke('session_share_stop', { sessionId: session.id });
+        showSuccess('Stopped sharing');
+      } else {
+        if (!confirm('Serve a read-only live view of this session on your network? Anyone with the link can watch until you stop sharing.')) return;
+        const share = await invoke('session_share_start', {
+          sessionId: session.id,
+          workDir: session.work_dir || null,
+        });
+        try {
+          await navigator.clipboard.writeText(share.url);
+          showSuccess(`Share link copied: ${share.url}`);
+        } catch {
+          showSuccess(`Sharing at ${share.url}`);
+        }
+      }
+    } catch (err) {
+      showError('Sharing failed: ' + (err.message || err));
     }
-    state.messages = [];
-    elements.chatTitle.textContent = session.title;
-    
-    elements.emptyState.classList.add('hidden');
-    elements.chatView.classList.remove('hidden');
-    
-    loadSessionMessages(session);
-    renderSessions();
+    refreshShareButton();
   }
 
-  async function loadSessionMessages(session) {
-    elements.messages.innerHTML = '';
-    currentMessageEl = null;
-    currentTextBuffer = '';
-    toolMessages.clear();
+  // ================================
+  // Semantic Search
+  // ================================
+
+  // Results are shown locally and not sent to the model
+  async function runSemanticSearch(query) {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir || null;
+    const item = createToolMessageElement(`Semantic search: ${query}`);
+    elements.messages.appendChild(item);
+    scrollToBottom();
+    const body = item.querySelector('.message-body');
     try {
-      const messages = await invoke('session_messages', {
-        workDir: session.work_dir,
-        sessionId: session.id
+      const hits = await invoke('semantic_search', { workDir, query, limit: null });
+      body.textContent = hits.length
+        ? `Semantic search: ${query}\n\n` + hits.map(hit =>
+            `${hit.path}:${hit.start_line}-${hit.end_line} (${hit.score.toFixed(3)})`
+          ).join('\n')
+        : `Semantic search: ${query}\nNo matches.`;
+    } catch (err) {
+      body.textContent = `Semantic search: ${query}\n${err.message || err}`;
+    }
+    scrollToBottom();
+  }
+
+  function childEnvPolicy() {
+    return {
+      inherit_all: elements.settingEnvInheritAll.checked,
+      allow: elements.settingEnvAllow.value.split(',').map(s => s.trim()).filter(Boolean),
+    };
+  }
+
+  // The environment a Shell command would get with the policy as edited
+  async function previewChildEnv() {
+    try {
+      const preview = await invoke('child_env_preview', {
+        settings: { ...state.settings, child_env: childEnvPolicy() },
+        mcpServer: null,
       });
-      
-      if (!messages || messages.length === 0) {
-        elements.messages.innerHTML = '<div style="text-align: center; color: var(--text-muted); padding: 40px;">No messages yet</div>';
+      const lines = preview.variables.map(v =>
+        v.source === 'inherited' ? `${v.name}=${v.value}` : `${v.name}=${v.value}  (${v.source})`
+      );
+      if (preview.withheld.length) {
+        lines.push('', `Withheld: ${preview.withheld.join(', ')}`);
+      }
+      elements.envPreview.textContent = lines.join('\n');
+      elements.envPreview.style.display = 'block';
+    } catch (err) {
+      showError('Failed to read environment: ' + (err.message || err));
+    }
+  }
+
+  // Work dirs whose indexing was started from settings
+  const semanticIndexRequested = new Set();
+
+  async function indexCurrentFolder() {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir || null;
+    if (!workDir) {
+      showError('Select a folder first');
+      return;
+    }
+    try {
+      await invoke('semantic_index', { workDir });
+      semanticIndexRequested.add(workDir);
+      elements.semanticIndexStatus.textContent = 'Indexing in the background...';
+    } catch (err) {
+      showError('Indexing failed: ' + (err.message || err));
+    }
+  }
+
+  // Background refreshes also follow edits and searches; only the ones asked
+  // for from settings report when they finish
+  function handleSemanticProgress(event) {
+    const { work_dir: workDir, done, embedded, total, stats, error } = event.payload || {};
+    if (!done) {
+      elements.semanticIndexStatus.textContent = `Embedding ${embedded} of ${total} changed file(s)...`;
+      return;
+    }
+    elements.semanticIndexStatus.textContent = error
+      ? `Indexing failed: ${error}`
+      : `Indexed ${stats.files} file(s), ${stats.chunks} chunk(s)`;
+    if (!semanticIndexRequested.delete(workDir)) return;
+    if (error) {
+      showError('Indexing failed: ' + error);
+    } else {
+      showSuccess(`Indexed ${stats.files} file(s), ${stats.chunks} chunk(s); ${stats.updated} updated`);
+    }
+  }
+
+  // ================================
+  // Context Pack
+  // ================================
+
+  async function attachContextPack() {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir || null;
+    if (!workDir) {
+      showError('Select a folder first');
+      return;
+    }
+    const input = prompt('Files to include (comma-separated globs, empty for all)', 'src/**');
+    if (input === null) return;
+    const globs = input.split(',').map(g => g.trim()).filter(Boolean);
+    try {
+      const pack = await invoke('context_pack', { workDir, globs, budgetTokens: null });
+      if (!pack.files.length) {
+        showError('No matching text files fit the budget');
         return;
       }
-      
-      messages.forEach(msg => {
-        const msgEl = createMessageElement(msg.role, msg.content);
-        elements.messages.appendChild(msgEl);
+      const current = elements.chatInput.value;
+      elements.chatInput.value = (current ? current + '\n\n' : '') + pack.payload;
+      elements.chatInput.dispatchEvent(new Event('input'));
+      elements.chatInput.focus();
+      const skipped = pack.skipped.length ? `, ${pack.skipped.length} skipped` : '';
+      showSuccess(`Attached ${pack.files.length} file(s), ~${pack.total_tokens} tokens${skipped}`);
+    } catch (err) {
+      showError('Failed to pack context: ' + err);
+    }
+  }
+
+  // ================================
+  // File History
+  // ================================
+
+  function openToolHistory() {
+    elements.toolHistoryModal.classList.add('open');
+    elements.toolHistoryQuery.focus();
+    elements.toolHistoryQuery.select();
+    searchToolHistory();
+  }
+
+  // Past tool calls across sessions; Shell commands can be run again
+  async function searchToolHistory() {
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir || null;
+    try {
+      const entries = await invoke('tool_history_search', {
+        query: elements.toolHistoryQuery.value,
+        workDir,
+        tool: elements.toolHistoryTool.value || null,
+      });
+      if (!entries.length) {
+        elements.toolHistoryList.innerHTML = '<span class="setting-hint">No matching tool calls in this workspace.</span>';
+        return;
+      }
+      elements.toolHistoryList.innerHTML = entries.map((entry, i) => {
+        const when = new Date(entry.timestamp * 1000).toLocaleString();
+        const target = entry.command || entry.arguments?.path || entry.arguments?.pattern || entry.arguments?.url || '';
+        return `
+          <div class="tool-history-item${entry.ok ? '' : ' failed'}">
+            <div class="tool-history-head">
+              <span class="tool-history-tool">${escapeHtml(entry.tool)}</span>
+              <code>${escapeHtml(target)}</code>
+            </div>
+            <div class="tool-history-snippet">${escapeHtml(entry.snippet)}</div>
+            <div class="tool-history-meta">
+              <span class="setting-hint">${escapeHtml(entry.session_title)} · ${when}</span>
+              <span>
+                <button class="btn-secondary" data-open="${i}">Open</button>
+                ${entry.command ? `<button class="btn-secondary" data-rerun="${i}">Run again</button>` : ''}
+              </span>
+            </div>
+            <pre class="tool-history-output hidden"></pre>
+          </div>
+        `;
+      }).join('');
+      $$('button[data-open]', elements.toolHistoryList).forEach(btn => {
+        btn.addEventListener('click', () => {
+          elements.toolHistoryModal.classList.remove('open');
+          openSession(entries[btn.dataset.open].session_id);
+        });
+      });
+      $$('button[data-rerun]', elements.toolHistoryList).forEach(btn => {
+        btn.addEventListener('click', () => rerunToolHistory(entries[btn.dataset.rerun], btn));
       });
-      
-      scrollToBottom();
     } catch (err) {
-      const errorMsg = err?.message || err || 'Unknown error';
-      elements.messages.innerHTML = `<div style="text-align: center; color: var(--error); padding: 40px;">Failed to load messages: ${errorMsg}</div>`;
+      showError('Failed to search tool history: ' + (err.message || err));
+    }
+  }
+
+  async function rerunToolHistory(entry, btn) {
+    const outputEl = btn.closest('.tool-history-item').querySelector('.tool-history-output');
+    btn.disabled = true;
+    outputEl.classList.remove('hidden');
+    outputEl.textContent = `$ ${entry.command}\nRunning...`;
+    try {
+      const result = await invoke('tool_history_rerun', {
+        sessionId: entry.session_id,
+        command: entry.command,
+        workDir: entry.work_dir,
+        settings: state.settings,
+      });
+      outputEl.textContent = `$ ${entry.command}\n${result.summary}${result.output ? `\n${result.output}` : ''}`;
+    } catch (err) {
+      outputEl.textContent = `$ ${entry.command}\n${err.message || err}`;
+    } finally {
+      btn.disabled = false;
+    }
+  }
+
+  async function loadFileVersions() {
+    const path = elements.fileHistoryPath.value.trim();
+    if (!path) return;
+    const workDir = state.currentSession?.work_dir || state.settings.work_dir || null;
+    try {
+      const versions = await invoke('file_versions', { path, workDir });
+      if (!versions.length) {
+        elements.fileHistoryList.innerHTML = '<span class="setting-hint">No saved versions. Versions are kept from the first time a turn edits the file.</span>';
+        return;
+      }
+      elements.fileHistoryList.innerHTML = versions.map(v => {
+        const when = new Date(v.created_at * 1000).toLocaleString();
+        const title = state.sessions.find(s => s.id === v.session_id)?.title;
+        const label = v.turn
+          ? `Before turn ${v.turn}${title ? ` of "${escapeHtml(title)}"` : ''}`
+          : 'Before a rollback';
+        const detail = v.existed ? `${v.size} bytes` : 'file did not exist';
+        return `
+          <div class="template-item">
+            <span>${label}<br><span class="setting-hint">${when} · ${detail}</span></span>
+            <button class="btn-secondary" data-turn="${escapeHtml(v.turn_id)}">Restore</button>
+          </div>
+        `;
+      }).join('');
+      $$('button[data-turn]', elements.fileHistoryList).forEach(btn => {
+        btn.addEventListener('click', async () => {
+          if (!confirm(`Restore ${path} to this version? Other files are not touched.`)) return;
+          try {
+            await invoke('file_rollback', { path, turnId: btn.dataset.turn, workDir });
+            showSuccess(`Restored ${path}`);
+            loadFileVersions();
+          } catch (err) {
+            showError('Rollback failed: ' + (err.message || err));
+          }
+        });
+      });
+    } catch (err) {
+      showError('Failed to load file history: ' + (err.message || err));
     }
   }
 
@@ -934,12 +3175,14 @@
     currentTextBuffer = '';
     toolMessages.clear();
     renderSessions();
+    refreshSessionPolicy();
   }
 
   async function sendMessage(text, fromChat = false) {
     hideAutocomplete();
     
-    if (!text.trim() || state.isStreaming) return;
+    const hasImages = fromChat && state.pendingAttachments.length > 0;
+    if ((!text.trim() && !hasImages) || state.streamingSessions.has(state.currentSession?.id)) return;
     
     if (!state.isLoggedIn) {
       showError('Please login first');
@@ -949,21 +3192,31 @@
     
     const inputEl = fromChat ? elements.chatInput : elements.promptInput;
     inputEl.value = '';
+    renderSuggestions([]);
+
+    const searchQuery = text.trim().match(/^\/search\s+([\s\S]+)/)?.[1];
+    if (searchQuery && state.currentSession) {
+      await runSemanticSearch(searchQuery.trim());
+      return;
+    }
     
+    const attachments = fromChat ? takeAttachments() : [];
     if (!state.currentSession) {
-      await startNewSession(text);
+      await startNewSession(text, attachments);
       return;
     }
     
     // Add user message
     const userMsg = createMessageElement('user', text);
+    appendMessageImages(userMsg, attachments);
     elements.messages.appendChild(userMsg);
     scrollToBottom();
     
     // Reset streaming state
     currentMessageEl = null;
     currentTextBuffer = '';
-    state.isStreaming = true;
+    const sessionId = state.currentSession.id;
+    state.streamingSessions.add(sessionId);
     
     enableInputs(false);
     showLoading('Kimi is thinking...');
@@ -971,21 +3224,23 @@
     try {
       const sessionWorkDir = state.currentSession?.work_dir || state.settings.work_dir || state.paths?.work_dir || null;
       await invoke('chat_stream', {
-        sessionId: state.currentSession.id,
+        sessionId,
         message: text,
         settings: {
           ...state.settings,
           work_dir: sessionWorkDir,
         },
+        attachments: attachments.map(({ name, data }) => ({ name, data })),
+        skill: selectedSkillPath(text),
       });
     } catch (err) {
       const errorMsg = err?.message || err || 'Failed to send message';
       showError(errorMsg);
-      finishStreaming();
+      finishStreaming(sessionId);
     }
   }
 
-  async function startNewSession(prompt) {
+  async function startNewSession(prompt, attachments = []) {
     if (!state.isLoggedIn) {
       showError('Please login first');
       openLoginModal();
@@ -1012,11 +3267,12 @@
     
     // Add user message
     const userMsg = createMessageElement('user', prompt);
+    appendMessageImages(userMsg, attachments);
     elements.messages.appendChild(userMsg);
     
     currentMessageEl = null;
     currentTextBuffer = '';
-    state.isStreaming = true;
+    state.streamingSessions.add(sessionId);
     
     enableInputs(false);
     showLoading('Kimi is thinking...');
@@ -1030,6 +3286,8 @@
           ...state.settings,
           work_dir: sessionWorkDir,
         },
+        attachments: attachments.map(({ name, data }) => ({ name, data })),
+        skill: selectedSkillPath(prompt),
       });
       
       // Add to sessions list
@@ -1042,7 +3300,7 @@
     } catch (err) {
       const errorMsg = err?.message || err || 'Failed to start session';
       showError(errorMsg);
-      finishStreaming();
+      finishStreaming(sessionId);
     }
   }
 
@@ -1170,6 +3428,72 @@
     elements.settingSkills.value = state.settings.skills_dir || '';
     elements.settingThinking.checked = state.settings.thinking || false;
     elements.settingYolo.checked = state.settings.yolo || false;
+    elements.settingDryRun.checked = state.settings.dry_run || false;
+    elements.settingBackgroundMode.checked = state.settings.background_mode || false;
+    elements.settingFollowSymlinks.checked = state.settings.follow_symlinks ?? true;
+    elements.settingShellInterpreter.value = state.settings.shell_interpreter || '';
+    elements.settingEnvAllow.value = (state.settings.child_env?.allow || []).join(', ');
+    elements.settingEnvInheritAll.checked = state.settings.child_env?.inherit_all || false;
+    elements.settingDownloadMaxMb.value = state.settings.downloads?.max_mb ?? '';
+    elements.settingDownloadDomains.value = (state.settings.downloads?.allowed_domains || []).join(', ');
+    elements.settingDownloadTypes.value = (state.settings.downloads?.allowed_content_types || []).join(', ');
+    elements.settingGithubCredential.value = state.settings.forge?.github_credential || '';
+    elements.settingGitlabCredential.value = state.settings.forge?.gitlab_credential || '';
+    elements.settingGitlabUrl.value = state.settings.forge?.gitlab_url || '';
+    elements.envPreview.style.display = 'none';
+    loadToolMatrix();
+    elements.settingWorkspaceStatsPrompt.checked = state.settings.workspace_stats_in_prompt || false;
+    elements.settingFollowUpSuggestions.checked = state.settings.follow_up_suggestions || false;
+    elements.settingSummarizerModel.value = state.settings.summarizer_model || '';
+    elements.settingIgnorePatterns.value = (state.settings.ignore_patterns || []).join('\n');
+    loadIgnoreDefaults();
+    elements.settingScratchRetention.value = state.settings.scratch_retention_days ?? '';
+    elements.settingCheckpointRetention.value = state.settings.checkpoint_retention_days ?? '';
+    elements.settingModelTimeout.value = state.settings.model_soft_timeout_secs ?? '';
+    const toolRetry = state.settings.tool_retry || {};
+    elements.settingToolRetries.value = toolRetry.max_retries || 0;
+    elements.settingToolRetryTools.value = (toolRetry.tools || []).join(', ');
+    elements.settingApiRetries.value = state.settings.api_retry?.max_retries ?? '';
+    elements.settingCheckers.value = (state.settings.diagnostics?.checkers || [])
+      .map(checker => `${checker.name}=${checker.command}`)
+      .join('\n');
+    elements.settingCheckersAfterWrite.checked = state.settings.diagnostics?.after_write || false;
+    elements.settingApiRetryMaxDelay.value = state.settings.api_retry?.max_delay_secs ?? '';
+    elements.settingApprovalHook.value = state.settings.approval_hook?.command || '';
+    elements.settingOutsideWrites.value = state.settings.outside_writes || 'ask';
+    elements.settingSharePort.value = state.settings.share_port ?? '';
+    const policy = state.settings.command_policy || {};
+    elements.settingPolicyPreset.value = policy.preset || '';
+    elements.settingPolicyDeny.value = (policy.deny || []).join('\n');
+    elements.settingPolicyAllow.value = (policy.allow || []).join('\n');
+    elements.settingPolicyNetwork.value = policy.network == null ? '' : (policy.network ? 'on' : 'off');
+    elements.settingPolicyDirectories.value = (policy.directories || [])
+      .map(rule => `${rule.path} ${rule.action}`)
+      .join('\n');
+    elements.settingHooks.value = state.settings.hooks
+      ? JSON.stringify(state.settings.hooks, null, 2)
+      : '';
+    const costGuard = state.settings.cost_guard || {};
+    elements.settingCostThreshold.value = costGuard.threshold_usd ?? '';
+    elements.settingCostPrices.value = Object.entries(costGuard.prices || {})
+      .map(([model, price]) => `${model}=${price.input_per_mtok}${price.output_per_mtok ? `,${price.output_per_mtok}` : ''}`)
+      .join('\n');
+    const budget = state.settings.budget || {};
+    elements.settingBudgetSessionTokens.value = budget.session_tokens ?? '';
+    elements.settingBudgetSessionUsd.value = budget.session_usd ?? '';
+    elements.settingBudgetDailyTokens.value = budget.daily_tokens ?? '';
+    elements.settingBudgetDailyUsd.value = budget.daily_usd ?? '';
+    loadBudgetUsage();
+    loadBackgroundStatus();
+    const embeddings = state.settings.embeddings || {};
+    elements.settingEmbeddingsModel.value = embeddings.model || '';
+    elements.settingEmbeddingsBase.value = embeddings.base_url || '';
+    elements.settingEmbeddingsKey.value = embeddings.api_key || '';
+    elements.settingEmbeddingsCredential.value = embeddings.credential || '';
+    renderCredentials();
+    renderTemplateList();
+    renderApprovalRules();
+    renderApprovalAudit();
     
     // Load auth config into settings
     if (state.authConfig) {
@@ -1197,6 +3521,18 @@
 
   function closeLoginModal() {
     elements.loginModal.classList.remove('open');
+    state.loginId = null;
+    invoke('oauth_cancel_login').catch(() => {});
+  }
+
+  async function cancelLogin() {
+    state.loginId = null;
+    try {
+      await invoke('oauth_cancel_login');
+    } catch (err) {
+      showError('Failed to cancel login: ' + (err.message || err));
+    }
+    resetLoginModal();
   }
 
   function resetLoginModal() {
@@ -1229,6 +3565,45 @@
     if (elements.loginMethods) elements.loginMethods.classList.add('hidden');
     if (elements.loginOauthFlow) elements.loginOauthFlow.classList.add('hidden');
     if (elements.loginApikeyForm) elements.loginApikeyForm.classList.remove('hidden');
+    probeEndpoints();
+  }
+
+  // Fill the endpoint picker with live reachability, preferring the saved
+  // endpoint and otherwise the fastest reachable one.
+  async function probeEndpoints() {
+    const select = elements.loginEndpointSelect;
+    if (!select) return;
+    if (elements.loginEndpointStatus) elements.loginEndpointStatus.textContent = 'Testing connectivity...';
+    try {
+      const probes = await invoke('endpoints_probe', { mode: 'api_key' });
+      const saved = state.authConfig?.endpoint || select.value;
+      select.innerHTML = '';
+      for (const probe of probes) {
+        const option = document.createElement('option');
+        option.value = probe.id;
+        option.textContent = probe.reachable
+          ? `${probe.label} · ${probe.latency_ms} ms`
+          : `${probe.label} · unreachable`;
+        select.appendChild(option);
+      }
+      const fastest = probes
+        .filter(p => p.reachable)
+        .sort((a, b) => a.latency_ms - b.latency_ms)[0];
+      if (probes.some(p => p.id === saved)) {
+        select.value = saved;
+      } else if (fastest) {
+        select.value = fastest.id;
+      }
+      if (elements.loginEndpointStatus) {
+        elements.loginEndpointStatus.textContent = fastest
+          ? `Fastest from here: ${fastest.label}`
+          : 'No endpoint is reachable. Check your network or proxy settings.';
+      }
+    } catch (err) {
+      if (elements.loginEndpointStatus) {
+        elements.loginEndpointStatus.textContent = 'Connectivity test failed: ' + (err.message || err);
+      }
+    }
   }
   
   function showLoginMethods() {
@@ -1240,14 +3615,17 @@
     if (elements.oauthProgress) elements.oauthProgress.classList.remove('hidden');
     
     try {
+      // OAuth only works against the Kimi Code endpoint; pin it on the profile
+      await invoke('auth_set_endpoint', { endpoint: 'kimi_code' });
       const result = await invoke('oauth_start_login');
+      state.loginId = result.login_id;
       
       if (elements.loginUserCode) {
         elements.loginUserCode.textContent = result.user_code;
       }
       
       // Store verification URL for opening browser
-      state.verificationUrl = result.verification_uri_complete;
+      state.verificationUrl = result.verification_uri_complete || result.verification_uri;
       
     } catch (err) {
       showError('Login failed: ' + err.message);
@@ -1258,6 +3636,7 @@
   async function saveApiKey() {
     const apiKey = elements.apiKeyInput?.value?.trim();
     const apiBase = elements.apiBaseInput?.value?.trim();
+    const endpoint = elements.loginEndpointSelect?.value;
     
     if (!apiKey) {
       showError('Please enter an API key');
@@ -1267,7 +3646,8 @@
     try {
       await invoke('auth_set_api_key', { 
         apiKey: apiKey,
-        apiBase: apiBase || null
+        apiBase: apiBase || null,
+        endpoint: endpoint || null
       });
       
       state.isLoggedIn = true;
@@ -1400,7 +3780,15 @@
             display: '/' + cmd.name,
             description: cmd.description,
             icon: '⌘'
-          }));
+          }))
+          .concat(state.customCommands
+            .filter(cmd => cmd.name.toLowerCase().includes(query))
+            .map(cmd => ({
+              value: '/' + cmd.name,
+              display: '/' + cmd.name + (cmd.argument_hint ? ' ' + cmd.argument_hint : ''),
+              description: cmd.description || `${cmd.scope} command`,
+              icon: '✎'
+            })));
         break;
         
       case 'skill':
@@ -1503,7 +3891,38 @@
     renderAutocompleteDropdown();
   }
   
+  function expandSnippet(input) {
+    const cursorPos = input.selectionStart;
+    const textBefore = input.value.substring(0, cursorPos);
+    const match = textBefore.match(/(\S+)$/);
+    if (!match) return false;
+    const snippet = state.snippets.find(s => s.trigger === match[1]);
+    if (!snippet) return false;
+
+    const builtins = {
+      date: new Date().toLocaleDateString(),
+      time: new Date().toLocaleTimeString(),
+      work_dir: state.settings.work_dir || state.paths?.work_dir || '',
+    };
+    const defaults = Object.fromEntries((snippet.variables || []).map(v => [v.name, v.default ?? '']));
+    const body = snippet.body.replace(/\$\{(\w+)\}/g, (whole, name) => {
+      if (name in defaults) return defaults[name];
+      if (name in builtins) return builtins[name];
+      return whole;
+    });
+
+    const start = cursorPos - match[1].length;
+    input.value = input.value.substring(0, start) + body + input.value.substring(cursorPos);
+    input.selectionStart = input.selectionEnd = start + body.length;
+    input.dispatchEvent(new Event('input'));
+    return true;
+  }
+
   function handleInputKeydown(e) {
+    if (!autocomplete.active && e.key === 'Tab' && !e.shiftKey && expandSnippet(e.target)) {
+      e.preventDefault();
+      return;
+    }
     if (!autocomplete.active) {
       // Check for trigger characters
       if (e.key === '/' || e.key === '$' || e.key === '@') {
@@ -1579,6 +3998,9 @@
       elements.btnEditSessions.classList.toggle('active', state.isEditingSessions);
       renderSessions();
     });
+    if (elements.btnImportSession) {
+      elements.btnImportSession.addEventListener('click', importSession);
+    }
     elements.btnCloseChat.addEventListener('click', closeChat);
     
     elements.btnSend.addEventListener('click', () => sendMessage(elements.promptInput.value));
@@ -1603,6 +4025,25 @@
       }
     });
     elements.chatInput.addEventListener('input', handleInput);
+    elements.chatInput.addEventListener('paste', e => {
+      const files = Array.from(e.clipboardData?.files || []).filter(file => file.type.startsWith('image/'));
+      if (files.length) {
+        e.preventDefault();
+        addImageFiles(files);
+      }
+    });
+
+    if (elements.btnAttachImage) {
+      elements.btnAttachImage.addEventListener('click', () => elements.attachImageInput.click());
+      elements.attachImageInput.addEventListener('change', () => {
+        const model = state.models.find(m => m.id === state.settings.model);
+        if (model && model.supports_image_in === false) {
+          showError(`${model.id} may not accept images`);
+        }
+        addImageFiles(elements.attachImageInput.files);
+        elements.attachImageInput.value = '';
+      });
+    }
     
     $$('.action-card').forEach(card => {
       card.addEventListener('click', () => {
@@ -1621,6 +4062,7 @@
     };
     bindYoloSwitch(elements.yoloSwitchMain);
     bindYoloSwitch(elements.yoloSwitchChat);
+    elements.sessionPolicySelect?.addEventListener('change', setSessionPolicy);
     
     elements.btnConfig.addEventListener('click', () => {
       elements.drawerBackdrop.classList.add('open');
@@ -1647,6 +4089,8 @@
         folders.push(home);
       }
       
+      renderTemplateOptions();
+      
       const uniqueFolders = [...new Set(folders)];
       elements.folderList.innerHTML = uniqueFolders.map(f => `
         <button class="folder-item" data-folder="${f}">
@@ -1658,15 +4102,7 @@
       `).join('');
       
       $$('.folder-item', elements.folderList).forEach(item => {
-        item.addEventListener('click', () => {
-          state.settings.work_dir = item.dataset.folder;
-          const parts = item.dataset.folder.split('/');
-          elements.folderLabel.textContent = parts[parts.length - 1];
-          updateUI();
-          closeModals();
-          loadSkills();
-          loadSessions();
-        });
+        item.addEventListener('click', () => selectWorkDir(item.dataset.folder));
       });
       
       // Add custom folder handler
@@ -1677,13 +4113,7 @@
         addBtn.addEventListener('click', () => {
           const path = customInput.value.trim();
           if (path) {
-            state.settings.work_dir = path;
-            const parts = path.split('/');
-            elements.folderLabel.textContent = parts[parts.length - 1] || path;
-            updateUI();
-            closeModals();
-            loadSkills();
-            loadSessions();
+            selectWorkDir(path);
           }
         });
         
@@ -1701,13 +4131,7 @@
           try {
             const path = await invoke('pick_folder');
           if (path) {
-            state.settings.work_dir = path;
-            const parts = path.split('/');
-            elements.folderLabel.textContent = parts[parts.length - 1] || path;
-            updateUI();
-            closeModals();
-            loadSkills();
-            loadSessions();
+            selectWorkDir(path);
           }
         } catch (err) {
             const message = err?.message || err || 'Failed to open folder picker';
@@ -1724,7 +4148,7 @@
     elements.btnCloseFolder.addEventListener('click', closeModals);
     elements.btnCloseModel.addEventListener('click', closeModals);
     elements.btnCloseLogin.addEventListener('click', closeLoginModal);
-    elements.btnCancelLogin.addEventListener('click', closeLoginModal);
+    elements.btnCancelLogin.addEventListener('click', cancelLogin);
     
     elements.folderModal.addEventListener('click', (e) => {
       if (e.target === elements.folderModal) closeModals();
@@ -1755,6 +4179,134 @@
     if (elements.btnBackFromApikey) {
       elements.btnBackFromApikey.addEventListener('click', showLoginMethods);
     }
+    if (elements.settingAutostart) {
+      elements.settingAutostart.addEventListener('change', () => setAutostart(elements.settingAutostart.checked));
+    }
+    if (elements.btnGitChanges) {
+      elements.btnGitChanges.addEventListener('click', () => {
+        elements.gitModal.classList.add('open');
+        loadGitChanges();
+      });
+      elements.btnCloseGit.addEventListener('click', () => elements.gitModal.classList.remove('open'));
+      elements.btnGitRefresh.addEventListener('click', loadGitChanges);
+      elements.btnGitCommit.addEventListener('click', commitGitChanges);
+    }
+    if (elements.sessionSearch) {
+      elements.sessionSearch.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter') searchSessions(elements.sessionSearch.value);
+        if (e.key === 'Escape') {
+          elements.sessionSearch.value = '';
+          renderSessions();
+        }
+      });
+      elements.sessionSearch.addEventListener('search', () => {
+        if (!elements.sessionSearch.value) renderSessions();
+      });
+    }
+    if (elements.btnMirrorSession) {
+      elements.btnMirrorSession.addEventListener('click', toggleMirror);
+    }
+    if (elements.btnShareSession) {
+      elements.btnShareSession.addEventListener('click', toggleShare);
+    }
+    if (elements.btnExportSession) {
+      elements.btnExportSession.addEventListener('click', exportSession);
+    }
+    if (elements.btnCredentialSave) {
+      elements.btnCredentialSave.addEventListener('click', saveCredential);
+    }
+    if (elements.btnWorkspaceStats) {
+      elements.btnWorkspaceStats.addEventListener('click', () => showWorkspaceStats());
+      elements.btnWorkspaceStatsRefresh.addEventListener('click', () => showWorkspaceStats(true));
+      elements.btnCloseWorkspaceStats.addEventListener('click', () => {
+        elements.workspaceStatsModal.classList.remove('open');
+      });
+    }
+    if (elements.btnOutline) {
+      elements.btnOutline.addEventListener('click', showOutline);
+      elements.btnOutlineLoad.addEventListener('click', loadOutline);
+      elements.outlinePath.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter') loadOutline();
+      });
+      elements.btnCloseOutline.addEventListener('click', () => {
+        elements.outlineModal.classList.remove('open');
+      });
+    }
+    if (elements.btnProblems) {
+      elements.btnProblems.addEventListener('click', showProblems);
+      elements.btnProblemsRun.addEventListener('click', runDiagnostics);
+      elements.btnCloseProblems.addEventListener('click', () => {
+        elements.problemsModal.classList.remove('open');
+      });
+    }
+    if (elements.btnProcesses) {
+      elements.btnProcesses.addEventListener('click', showProcesses);
+      elements.btnCloseProcesses.addEventListener('click', closeProcesses);
+      elements.btnProcessStart.addEventListener('click', () => startProcess());
+      elements.btnResetShell.addEventListener('click', resetShell);
+      elements.processCommand.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter') startProcess();
+      });
+    }
+    if (elements.btnWorklog) {
+      elements.btnWorklog.addEventListener('click', () => showWorklog(false));
+      elements.btnWorklogRefresh.addEventListener('click', () => showWorklog(true));
+      elements.btnCloseWorklog.addEventListener('click', () => {
+        elements.worklogModal.classList.remove('open');
+      });
+      elements.btnWorklogCopy.addEventListener('click', async () => {
+        try {
+          await navigator.clipboard.writeText(elements.worklogText.value);
+          showSuccess('Worklog copied');
+        } catch (err) {
+          showError('Failed to copy worklog');
+        }
+      });
+    }
+    if (elements.btnAddDocs) {
+      elements.btnAddDocs.addEventListener('click', addDocsSource);
+    }
+    elements.btnToolsOffline.addEventListener('click', () => setToolMatrix(NETWORK_TOOLS));
+    elements.btnToolsReadOnly.addEventListener('click', () => setToolMatrix(MUTATING_TOOLS));
+    elements.btnToolsAll.addEventListener('click', () => setToolMatrix([]));
+    if (elements.btnEnvPreview) {
+      elements.btnEnvPreview.addEventListener('click', previewChildEnv);
+    }
+    if (elements.btnSemanticIndex) {
+      elements.btnSemanticIndex.addEventListener('click', indexCurrentFolder);
+    }
+    if (elements.btnContextPack) {
+      elements.btnContextPack.addEventListener('click', attachContextPack);
+    }
+    if (elements.btnFileHistory) {
+      elements.btnFileHistory.addEventListener('click', () => {
+        elements.fileHistoryModal.classList.add('open');
+        elements.fileHistoryPath.focus();
+      });
+      elements.btnCloseFileHistory.addEventListener('click', () => {
+        elements.fileHistoryModal.classList.remove('open');
+      });
+      elements.btnFileHistoryLoad.addEventListener('click', loadFileVersions);
+      elements.fileHistoryPath.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter') loadFileVersions();
+      });
+    }
+    if (elements.btnToolHistory) {
+      elements.btnToolHistory.addEventListener('click', openToolHistory);
+      elements.btnCloseToolHistory.addEventListener('click', () => {
+        elements.toolHistoryModal.classList.remove('open');
+      });
+      elements.toolHistoryQuery.addEventListener('keydown', (e) => {
+        if (e.key === 'Enter') searchToolHistory();
+      });
+      elements.toolHistoryTool.addEventListener('change', searchToolHistory);
+    }
+    if (elements.btnSaveTemplate) {
+      elements.btnSaveTemplate.addEventListener('click', saveWorkspaceTemplate);
+    }
+    if (elements.btnProbeEndpoints) {
+      elements.btnProbeEndpoints.addEventListener('click', probeEndpoints);
+    }
     if (elements.btnSaveApikey) {
       elements.btnSaveApikey.addEventListener('click', saveApiKey);
     }
@@ -1768,16 +4320,19 @@
     if (elements.btnToolApprove) {
       elements.btnToolApprove.addEventListener('click', () => respondToolApproval(true));
     }
+    if (elements.btnToolAlways) {
+      elements.btnToolAlways.addEventListener('click', alwaysAllowPendingTool);
+    }
     if (elements.btnToolReject) {
       elements.btnToolReject.addEventListener('click', () => respondToolApproval(false));
     }
     if (elements.btnCloseToolApproval) {
-      elements.btnCloseToolApproval.addEventListener('click', () => respondToolApproval(false));
+      elements.btnCloseToolApproval.addEventListener('click', cancelToolApproval);
     }
     if (elements.toolApprovalModal) {
       elements.toolApprovalModal.addEventListener('click', (e) => {
         if (e.target === elements.toolApprovalModal) {
-          respondToolApproval(false);
+          cancelToolApproval();
         }
       });
     }
@@ -1792,6 +4347,8 @@
         elements.drawerTabs.forEach(t => t.classList.remove('active'));
         tab.classList.add('active');
         const target = tab.dataset.tab;
+        if (target === 'docs') loadDocsSources();
+        if (target === 'tools') loadToolMatrix();
         $$('.tab-content').forEach(c => {
           c.classList.toggle('active', c.dataset.tab === target);
         });
@@ -1799,13 +4356,128 @@
     });
     
     elements.btnSaveSettings.addEventListener('click', async () => {
+      let hooks = null;
+      if (elements.settingHooks.value.trim()) {
+        try {
+          hooks = JSON.parse(elements.settingHooks.value);
+        } catch (err) {
+          showError(`Invalid hooks JSON: ${err.message}`);
+          return;
+        }
+      }
+      state.settings.hooks = hooks;
       state.settings.work_dir = elements.settingWorkdir.value || null;
       state.settings.config_file = elements.settingConfig.value || null;
       state.settings.mcp_config_files = elements.settingMcp.value
         .split(',').map(s => s.trim()).filter(Boolean);
       state.settings.skills_dir = elements.settingSkills.value || null;
       state.settings.model = elements.settingDefaultModel.value || null;
+      state.settings.summarizer_model = elements.settingSummarizerModel.value || null;
+      state.settings.disabled_tools = toolMatrixDisabled();
       state.settings.yolo = elements.settingYolo.checked;
+      state.settings.dry_run = elements.settingDryRun.checked;
+      state.settings.background_mode = elements.settingBackgroundMode.checked;
+      state.settings.follow_symlinks = elements.settingFollowSymlinks.checked;
+      state.settings.shell_interpreter = elements.settingShellInterpreter.value || null;
+      state.settings.child_env = childEnvPolicy();
+      const downloadMaxMb = parseInt(elements.settingDownloadMaxMb.value, 10);
+      const splitList = (value) => value.split(',').map(s => s.trim()).filter(Boolean);
+      state.settings.downloads = {
+        max_mb: Number.isNaN(downloadMaxMb) ? null : Math.max(1, downloadMaxMb),
+        allowed_domains: splitList(elements.settingDownloadDomains.value),
+        allowed_content_types: splitList(elements.settingDownloadTypes.value),
+      };
+      state.settings.forge = {
+        github_credential: elements.settingGithubCredential.value.trim() || null,
+        gitlab_credential: elements.settingGitlabCredential.value.trim() || null,
+        gitlab_url: elements.settingGitlabUrl.value.trim() || null,
+      };
+      state.settings.workspace_stats_in_prompt = elements.settingWorkspaceStatsPrompt.checked;
+      state.settings.follow_up_suggestions = elements.settingFollowUpSuggestions.checked;
+      const ignorePatterns = elements.settingIgnorePatterns.value
+        .split('\n').map(s => s.trim()).filter(Boolean);
+      state.settings.ignore_patterns = ignorePatterns.length ? ignorePatterns : null;
+      const retention = parseInt(elements.settingScratchRetention.value, 10);
+      state.settings.scratch_retention_days = Number.isNaN(retention) ? null : Math.max(0, retention);
+      const checkpointRetention = parseInt(elements.settingCheckpointRetention.value, 10);
+      state.settings.checkpoint_retention_days = Number.isNaN(checkpointRetention) ? null : Math.max(0, checkpointRetention);
+      const modelTimeout = parseInt(elements.settingModelTimeout.value, 10);
+      state.settings.model_soft_timeout_secs = Number.isNaN(modelTimeout) ? null : Math.max(0, modelTimeout);
+      state.settings.tool_retry = {
+        max_retries: Math.max(0, parseInt(elements.settingToolRetries.value, 10) || 0),
+        tools: elements.settingToolRetryTools.value.split(',').map(s => s.trim()).filter(Boolean),
+      };
+      const checkers = elements.settingCheckers.value.split('\n').map(line => {
+        const at = line.indexOf('=');
+        return at > 0
+          ? { name: line.slice(0, at).trim(), command: line.slice(at + 1).trim() }
+          : { name: '', command: line.trim() };
+      }).filter(checker => checker.command);
+      state.settings.diagnostics = {
+        after_write: elements.settingCheckersAfterWrite.checked,
+        checkers,
+      };
+      const apiRetries = parseInt(elements.settingApiRetries.value, 10);
+      const apiRetryMaxDelay = parseInt(elements.settingApiRetryMaxDelay.value, 10);
+      state.settings.api_retry = Number.isNaN(apiRetries) && Number.isNaN(apiRetryMaxDelay) ? null : {
+        max_retries: Number.isNaN(apiRetries) ? null : Math.max(0, apiRetries),
+        max_delay_secs: Number.isNaN(apiRetryMaxDelay) ? null : Math.max(1, apiRetryMaxDelay),
+      };
+      const policyLines = (input) => input.value.split('\n').map(s => s.trim()).filter(Boolean);
+      const policyNetwork = elements.settingPolicyNetwork.value;
+      state.settings.command_policy = {
+        preset: elements.settingPolicyPreset.value || null,
+        deny: policyLines(elements.settingPolicyDeny),
+        allow: policyLines(elements.settingPolicyAllow),
+        network: policyNetwork ? policyNetwork === 'on' : null,
+        // `tests/ allow` or `src/ ask`; a bare folder means allow
+        directories: policyLines(elements.settingPolicyDirectories).map(line => {
+          const match = line.match(/^(.*?)\s+(allow|ask)$/i);
+          return match
+            ? { path: match[1].trim(), action: match[2].toLowerCase() }
+            : { path: line, action: 'allow' };
+        }),
+      };
+      state.settings.outside_writes = elements.settingOutsideWrites.value;
+      const sharePort = parseInt(elements.settingSharePort.value, 10);
+      state.settings.share_port = Number.isNaN(sharePort) ? null : Math.min(65535, Math.max(0, sharePort));
+      const approvalHook = elements.settingApprovalHook.value.trim();
+      state.settings.approval_hook = approvalHook
+        ? { ...state.settings.approval_hook, command: approvalHook }
+        : null;
+      const threshold = parseFloat(elements.settingCostThreshold.value);
+      const prices = {};
+      elements.settingCostPrices.value.split('\n').forEach(line => {
+        const [model, price] = line.split('=').map(part => part?.trim());
+        const [input, output] = (price || '').split(',').map(parseFloat);
+        if (model && !Number.isNaN(input)) {
+          prices[model] = { input_per_mtok: input, output_per_mtok: Number.isNaN(output) ? 0 : output };
+        }
+      });
+      state.settings.cost_guard = Number.isNaN(threshold) && !Object.keys(prices).length ? null : {
+        threshold_usd: Number.isNaN(threshold) ? null : threshold,
+        prices,
+      };
+      const budgetNumber = (input) => {
+        const value = parseFloat(input.value);
+        return Number.isNaN(value) || value <= 0 ? null : value;
+      };
+      const budget = {
+        session_tokens: budgetNumber(elements.settingBudgetSessionTokens),
+        session_usd: budgetNumber(elements.settingBudgetSessionUsd),
+        daily_tokens: budgetNumber(elements.settingBudgetDailyTokens),
+        daily_usd: budgetNumber(elements.settingBudgetDailyUsd),
+      };
+      if (budget.session_tokens) budget.session_tokens = Math.round(budget.session_tokens);
+      if (budget.daily_tokens) budget.daily_tokens = Math.round(budget.daily_tokens);
+      state.settings.budget = Object.values(budget).some(value => value !== null) ? budget : null;
+      const embeddingsModel = elements.settingEmbeddingsModel.value.trim();
+      state.settings.embeddings = embeddingsModel ? {
+        model: embeddingsModel,
+        base_url: elements.settingEmbeddingsBase.value.trim() || null,
+        api_key: elements.settingEmbeddingsKey.value.trim() || null,
+        credential: elements.settingEmbeddingsCredential.value.trim() || null,
+      } : null;
       
       // Save auth config if provided
       const apiKey = elements.settingApiKey.value?.trim();
@@ -1813,12 +4485,14 @@
       
       if (apiKey) {
         try {
+          const endpoint = state.authConfig?.endpoint || null;
           await invoke('auth_set_api_key', { 
             apiKey: apiKey,
-            apiBase: apiBase || null
+            apiBase: apiBase || null,
+            endpoint
           });
           // Update local state
-          state.authConfig = { mode: 'api_key', api_key: apiKey, api_base: apiBase };
+          state.authConfig = { mode: 'api_key', api_key: apiKey, api_base: apiBase, endpoint };
           state.isLoggedIn = true;
           state.authMode = 'api_key';
           state.user = { 
@@ -1831,7 +4505,7 @@
             limit_reset: ''
           };
           updateUserBar();
-          loadModels();
+          loadModels({ force: true });
         } catch (err) {
           showError('Failed to save API key: ' + err.message);
           return;
@@ -1862,6 +4536,7 @@
       
       await loadConfig();
       await loadSkills();
+      loadCustomCommands();
       await loadSessions();
       updateUI();
       elements.drawerBackdrop.classList.remove('open');
@@ -1895,12 +4570,17 @@
     });
     
     window.addEventListener('keydown', e => {
+      if ((e.metaKey || e.ctrlKey) && e.shiftKey && e.key.toLowerCase() === 'h') {
+        e.preventDefault();
+        openToolHistory();
+        return;
+      }
       if (e.key === 'Escape') {
         closeModals();
         elements.drawerBackdrop.classList.remove('open');
         closeLoginModal();
         if (elements.toolApprovalModal?.classList.contains('open')) {
-          respondToolApproval(false);
+          cancelToolApproval();
         }
         hideAutocomplete();
       }